@@ -0,0 +1,313 @@
+use regex::Regex;
+use select::document::Document;
+use select::node::Node;
+use select::predicate::Name;
+use serde::Serialize;
+use std::sync::LazyLock;
+
+/// WCAG AA minimum contrast for body text
+const MIN_CONTRAST: f64 = 4.5;
+/// WCAG AA minimum contrast for large text (`h1` and `h2` by default)
+const MIN_CONTRAST_LARGE: f64 = 3.0;
+
+static COLOR_PATTERN: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?i)#[0-9a-f]{3,8}\b|rgba?\([^)]*\)|\b[a-z]+\b").unwrap());
+
+/// The basic CSS named colors
+const NAMED_COLORS: &[(&str, [u8; 3])] = &[
+    ("black", [0, 0, 0]),
+    ("silver", [192, 192, 192]),
+    ("gray", [128, 128, 128]),
+    ("grey", [128, 128, 128]),
+    ("white", [255, 255, 255]),
+    ("maroon", [128, 0, 0]),
+    ("red", [255, 0, 0]),
+    ("purple", [128, 0, 128]),
+    ("fuchsia", [255, 0, 255]),
+    ("green", [0, 128, 0]),
+    ("lime", [0, 255, 0]),
+    ("olive", [128, 128, 0]),
+    ("yellow", [255, 255, 0]),
+    ("navy", [0, 0, 128]),
+    ("blue", [0, 0, 255]),
+    ("teal", [0, 128, 128]),
+    ("aqua", [0, 255, 255]),
+    ("orange", [255, 165, 0]),
+];
+
+/// Accessibility checks run on every archived page with `--a11y-audit`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum A11yRule {
+    /// An image without an `alt` attribute (`alt=""` marks it decorative)
+    ImageAlt,
+    /// A heading more than one level below the previous one (`h2` -> `h4`)
+    HeadingOrder,
+    /// Text and background colors set in inline styles with too little contrast
+    Contrast,
+}
+
+impl A11yRule {
+    pub fn name(self) -> &'static str {
+        match self {
+            A11yRule::ImageAlt => "image-alt",
+            A11yRule::HeadingOrder => "heading-order",
+            A11yRule::Contrast => "contrast",
+        }
+    }
+}
+
+/// One problem found on a page
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct A11yIssue {
+    pub url: String,
+    pub rule: A11yRule,
+    pub detail: String,
+}
+
+/// `#777`, `#767676`, `rgb(118, 118, 118)` or `gray` as RGB; transparent and
+/// translucent colors are `None`, as what shows through is unknown
+fn parse_color(value: &str) -> Option<[u8; 3]> {
+    let value = value.trim().to_ascii_lowercase();
+    if let Some(hex) = value.strip_prefix('#') {
+        let digits: Vec<u8> = match hex.len() {
+            3 | 4 => hex.chars().map(|c| c.to_digit(16).map(|d| d as u8 * 17)).collect::<Option<_>>()?,
+            6 | 8 => (0..hex.len())
+                .step_by(2)
+                .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+                .collect::<Option<_>>()?,
+            _ => return None,
+        };
+        if digits.len() == 4 && digits[3] < 255 {
+            return None;
+        }
+        return Some([digits[0], digits[1], digits[2]]);
+    }
+    if let Some(arguments) = value.strip_prefix("rgba(").or_else(|| value.strip_prefix("rgb(")) {
+        let arguments: Vec<&str> = arguments
+            .trim_end_matches(')')
+            .split([',', ' ', '/'])
+            .filter(|argument| !argument.is_empty())
+            .collect();
+        if arguments.len() == 4 {
+            let alpha = arguments[3];
+            let alpha = match alpha.strip_suffix('%') {
+                Some(percent) => percent.parse::<f64>().ok()? / 100.0,
+                None => alpha.parse::<f64>().ok()?,
+            };
+            if alpha < 1.0 {
+                return None;
+            }
+        } else if arguments.len() != 3 {
+            return None;
+        }
+        let mut rgb = [0u8; 3];
+        for (channel, argument) in rgb.iter_mut().zip(&arguments) {
+            let level = match argument.strip_suffix('%') {
+                Some(percent) => percent.parse::<f64>().ok()? * 2.55,
+                None => argument.parse::<f64>().ok()?,
+            };
+            *channel = level.round().clamp(0.0, 255.0) as u8;
+        }
+        return Some(rgb);
+    }
+    NAMED_COLORS.iter().find(|(name, _)| *name == value).map(|(_, rgb)| *rgb)
+}
+
+/// Relative luminance as defined by WCAG 2
+fn relative_luminance(rgb: [u8; 3]) -> f64 {
+    let linear = |channel: u8| {
+        let channel = channel as f64 / 255.0;
+        if channel <= 0.03928 {
+            channel / 12.92
+        } else {
+            ((channel + 0.055) / 1.055).powf(2.4)
+        }
+    };
+    0.2126 * linear(rgb[0]) + 0.7152 * linear(rgb[1]) + 0.0722 * linear(rgb[2])
+}
+
+/// WCAG contrast ratio of two colors, from 1 (none) to 21 (black on white)
+pub fn contrast_ratio(foreground: [u8; 3], background: [u8; 3]) -> f64 {
+    let (a, b) = (relative_luminance(foreground), relative_luminance(background));
+    (a.max(b) + 0.05) / (a.min(b) + 0.05)
+}
+
+/// Value of a property in an inline `style` attribute
+fn style_property<'a>(style: &'a str, name: &str) -> Option<&'a str> {
+    style
+        .split(';')
+        .filter_map(|declaration| declaration.split_once(':'))
+        .filter(|(property, _)| property.trim().eq_ignore_ascii_case(name))
+        .map(|(_, value)| value.trim().trim_end_matches("!important").trim())
+        .next_back()
+}
+
+/// The background color an element's inline style sets: `Some(None)` when it
+/// sets a background that isn't a plain opaque color (an image, a gradient,
+/// `transparent`), `None` when it sets none
+fn inline_background(node: &Node) -> Option<Option<[u8; 3]>> {
+    let style = node.attr("style")?;
+    if let Some(color) = style_property(style, "background-color") {
+        return Some(parse_color(color));
+    }
+    let background = style_property(style, "background")?;
+    if background.to_ascii_lowercase().contains("url(") || background.to_ascii_lowercase().contains("gradient") {
+        return Some(None);
+    }
+    let color = COLOR_PATTERN.find_iter(background).find_map(|color| parse_color(color.as_str()));
+    Some(color)
+}
+
+/// The opaque background behind an element, from its own or its nearest
+/// ancestor's inline style
+fn background_behind(node: &Node) -> Option<[u8; 3]> {
+    let mut current = Some(*node);
+    while let Some(element) = current {
+        if let Some(background) = inline_background(&element) {
+            return background;
+        }
+        current = element.parent();
+    }
+    None
+}
+
+/// `<img src="logo.png">`: an element by its tag and its most telling attribute
+fn describe(node: &Node) -> String {
+    let name = node.name().unwrap_or_default();
+    for attribute in ["src", "href", "id"] {
+        if let Some(value) = node.attr(attribute) {
+            return format!("<{} {}=\"{}\">", name, attribute, value);
+        }
+    }
+    format!("<{}>", name)
+}
+
+fn heading_level(node: &Node) -> Option<usize> {
+    match node.name()? {
+        "h1" => Some(1),
+        "h2" => Some(2),
+        "h3" => Some(3),
+        "h4" => Some(4),
+        "h5" => Some(5),
+        "h6" => Some(6),
+        _ => None,
+    }
+}
+
+/// Images, image buttons and image map areas without alt text
+fn check_image_alt(url: &str, document: &Document, issues: &mut Vec<A11yIssue>) {
+    let images = document
+        .find(Name("img"))
+        .chain(document.find(Name("input")).filter(|input| input.attr("type").is_some_and(|kind| kind.eq_ignore_ascii_case("image"))))
+        .chain(document.find(Name("area")).filter(|area| area.attr("href").is_some()));
+    for image in images {
+        if image.attr("alt").is_none() && image.attr("aria-label").is_none() && image.attr("aria-labelledby").is_none() {
+            issues.push(A11yIssue {
+                url: url.to_string(),
+                rule: A11yRule::ImageAlt,
+                detail: format!("{} has no alt text", describe(&image)),
+            });
+        }
+    }
+}
+
+/// Headings that skip a level on the way down (going back up any number of
+/// levels is fine)
+fn check_heading_order(url: &str, document: &Document, issues: &mut Vec<A11yIssue>) {
+    let mut previous: Option<usize> = None;
+    for heading in document.find(|node: &Node| heading_level(node).is_some()) {
+        let Some(level) = heading_level(&heading) else { continue };
+        if let Some(previous) = previous {
+            if level > previous + 1 {
+                let text: String = heading.text().split_whitespace().collect::<Vec<_>>().join(" ");
+                issues.push(A11yIssue {
+                    url: url.to_string(),
+                    rule: A11yRule::HeadingOrder,
+                    detail: format!("h{} \"{}\" follows h{} (skips h{})", level, text, previous, previous + 1),
+                });
+            }
+        }
+        previous = Some(level);
+    }
+}
+
+/// Elements whose inline text color is too close to the inline background
+/// behind them. Colors from stylesheets aren't resolved, so these are hints.
+fn check_contrast(url: &str, document: &Document, issues: &mut Vec<A11yIssue>) {
+    for element in document.find(|node: &Node| node.attr("style").is_some_and(|style| style_property(style, "color").is_some())) {
+        let Some(foreground) = element.attr("style").and_then(|style| style_property(style, "color")).and_then(parse_color) else { continue };
+        let Some(background) = background_behind(&element) else { continue };
+        let minimum = if matches!(element.name(), Some("h1") | Some("h2")) { MIN_CONTRAST_LARGE } else { MIN_CONTRAST };
+        let ratio = contrast_ratio(foreground, background);
+        if ratio < minimum {
+            issues.push(A11yIssue {
+                url: url.to_string(),
+                rule: A11yRule::Contrast,
+                detail: format!(
+                    "{} text rgb({}, {}, {}) on rgb({}, {}, {}) has a contrast of {:.2}:1 (at least {}:1 needed)",
+                    describe(&element),
+                    foreground[0], foreground[1], foreground[2],
+                    background[0], background[1], background[2],
+                    ratio,
+                    minimum
+                ),
+            });
+        }
+    }
+}
+
+/// Basic accessibility checks of one page: missing alt text, skipped heading
+/// levels and low-contrast inline colors
+pub fn audit_page(url: &str, html_content: &str) -> Vec<A11yIssue> {
+    let document = Document::from(html_content);
+    let mut issues = Vec::new();
+    check_image_alt(url, &document, &mut issues);
+    check_heading_order(url, &document, &mut issues);
+    check_contrast(url, &document, &mut issues);
+    issues
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_color_and_contrast() {
+        assert_eq!(parse_color("#777"), Some([119, 119, 119]));
+        assert_eq!(parse_color("#76767680"), None);
+        assert_eq!(parse_color("rgb(118, 118, 118)"), Some([118, 118, 118]));
+        assert_eq!(parse_color("rgb(100% 0% 0%)"), Some([255, 0, 0]));
+        assert_eq!(parse_color("rgba(0, 0, 0, 0.5)"), None);
+        assert_eq!(parse_color("White"), Some([255, 255, 255]));
+        assert_eq!(parse_color("transparent"), None);
+
+        assert!((contrast_ratio([0, 0, 0], [255, 255, 255]) - 21.0).abs() < 1e-9);
+        assert!(contrast_ratio([118, 118, 118], [255, 255, 255]) >= MIN_CONTRAST);
+        assert!(contrast_ratio([119, 119, 119], [255, 255, 255]) < MIN_CONTRAST);
+    }
+
+    #[test]
+    fn test_audit_page() {
+        let html = r##"<html><body style="background: #fff url(bg.png)">
+            <h1>Title</h1>
+            <h3>Skipped</h3>
+            <h2>Back up</h2>
+            <img src="logo.png"><img src="divider.png" alt=""><input type="image" src="go.png" alt="Search">
+            <div style="background-color: white"><p style="color: #999">Faint</p><p style="color: #595959">Fine</p></div>
+            <span style="color: #aaa">Unknown background</span>
+        </body></html>"##;
+
+        let issues = audit_page("https://example.com/", html);
+        let details: Vec<(A11yRule, &str)> = issues.iter().map(|issue| (issue.rule, issue.detail.as_str())).collect();
+        assert_eq!(
+            details,
+            vec![
+                (A11yRule::ImageAlt, "<img src=\"logo.png\"> has no alt text"),
+                (A11yRule::HeadingOrder, "h3 \"Skipped\" follows h1 (skips h2)"),
+                (A11yRule::Contrast, "<p> text rgb(153, 153, 153) on rgb(255, 255, 255) has a contrast of 2.85:1 (at least 4.5:1 needed)"),
+            ]
+        );
+        assert!(issues.iter().all(|issue| issue.url == "https://example.com/"));
+    }
+}
@@ -0,0 +1,157 @@
+use anyhow::{anyhow, Result};
+use regex::Regex;
+use serde::Serialize;
+use std::str::FromStr;
+use url::Url;
+
+/// How a page was found to have changed since the previous run
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChangeKind {
+    /// Visible text changed (`--text-diff`)
+    Text,
+    /// Rendering changed beyond the threshold (`--screenshot-command`)
+    Visual,
+}
+
+impl ChangeKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ChangeKind::Text => "text",
+            ChangeKind::Visual => "visual",
+        }
+    }
+}
+
+/// A page that changed since the previous run
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PageChange {
+    pub url: String,
+    pub kind: ChangeKind,
+}
+
+/// "Alert if any page under /legal/ changes": a URL path pattern where `*`
+/// matches within one path segment and `**` across segments. Patterns without
+/// wildcards match every path they prefix.
+#[derive(Debug, Clone)]
+pub struct AlertRule {
+    pub pattern: String,
+    regex: Regex,
+}
+
+impl FromStr for AlertRule {
+    type Err = anyhow::Error;
+
+    fn from_str(pattern: &str) -> Result<Self> {
+        let pattern = pattern.trim();
+        if !pattern.starts_with('/') {
+            return Err(anyhow!("Alert rule must be a URL path starting with '/': {}", pattern));
+        }
+
+        let mut regex = String::from("^");
+        let mut rest = pattern;
+        while !rest.is_empty() {
+            if let Some(after) = rest.strip_prefix("**") {
+                regex.push_str(".*");
+                rest = after;
+            } else if let Some(after) = rest.strip_prefix('*') {
+                regex.push_str("[^/]*");
+                rest = after;
+            } else {
+                let literal_end = rest.find('*').unwrap_or(rest.len());
+                regex.push_str(&regex::escape(&rest[..literal_end]));
+                rest = &rest[literal_end..];
+            }
+        }
+        if pattern.contains('*') {
+            regex.push('$');
+        }
+
+        Ok(Self {
+            pattern: pattern.to_string(),
+            regex: Regex::new(&regex)?,
+        })
+    }
+}
+
+impl AlertRule {
+    /// Whether the path of `url` matches the rule
+    pub fn matches(&self, url: &str) -> bool {
+        Url::parse(url).is_ok_and(|url| self.regex.is_match(url.path()))
+    }
+}
+
+/// A changed page that matched an alert rule
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Alert {
+    pub rule: String,
+    pub url: String,
+    pub change: ChangeKind,
+}
+
+/// Changes matching any rule, one alert per page and rule
+pub fn match_alerts(rules: &[AlertRule], changes: &[PageChange]) -> Vec<Alert> {
+    let mut alerts = Vec::new();
+    for change in changes {
+        for rule in rules.iter().filter(|rule| rule.matches(&change.url)) {
+            let alert = Alert {
+                rule: rule.pattern.clone(),
+                url: change.url.clone(),
+                change: change.kind,
+            };
+            if !alerts.contains(&alert) {
+                alerts.push(alert);
+            }
+        }
+    }
+    alerts
+}
+
+/// JSON body POSTed to `--alert-webhook`
+#[derive(Debug, Clone, Serialize)]
+pub struct AlertPayload<'a> {
+    pub site: &'a str,
+    pub alerts: &'a [Alert],
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(pattern: &str) -> AlertRule {
+        pattern.parse().unwrap()
+    }
+
+    #[test]
+    fn test_alert_rule_matching() {
+        let legal = rule("/legal/");
+        assert!(legal.matches("https://example.com/legal/"));
+        assert!(legal.matches("https://example.com/legal/privacy/?v=2"));
+        assert!(!legal.matches("https://example.com/blog/legal/"));
+
+        let pricing = rule("/*/pricing.html");
+        assert!(pricing.matches("https://example.com/en/pricing.html"));
+        assert!(!pricing.matches("https://example.com/en/us/pricing.html"));
+
+        let terms = rule("/**/terms");
+        assert!(terms.matches("https://example.com/en/us/terms"));
+        assert!(!terms.matches("https://example.com/en/us/terms/archive"));
+
+        assert!("legal/".parse::<AlertRule>().is_err());
+    }
+
+    #[test]
+    fn test_match_alerts() {
+        let rules = vec![rule("/legal/"), rule("/legal/privacy")];
+        let changes = vec![
+            PageChange { url: "https://example.com/legal/privacy".to_string(), kind: ChangeKind::Text },
+            PageChange { url: "https://example.com/legal/privacy".to_string(), kind: ChangeKind::Visual },
+            PageChange { url: "https://example.com/blog/".to_string(), kind: ChangeKind::Text },
+        ];
+
+        let alerts = match_alerts(&rules, &changes);
+        assert_eq!(alerts.len(), 4);
+        assert!(alerts.iter().all(|alert| alert.url == "https://example.com/legal/privacy"));
+        assert!(match_alerts(&rules, &changes[2..]).is_empty());
+    }
+}
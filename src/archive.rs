@@ -0,0 +1,422 @@
+use anyhow::{anyhow, bail, Result, Context};
+use flate2::write::{DeflateEncoder, GzEncoder};
+use flate2::{Compression, Crc};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::str::FromStr;
+use std::time::UNIX_EPOCH;
+
+#[cfg(feature = "encryption")]
+use crate::encryption::{self, Encryption};
+use crate::snapshot_diff;
+
+/// Index written as the first entry of every archive
+pub const INDEX_FILE_NAME: &str = "archive-index.json";
+
+/// Single-file archive formats a mirror can be exported as
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArchiveFormat {
+    Zip,
+    TarGz,
+}
+
+impl FromStr for ArchiveFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(format: &str) -> Result<Self> {
+        match format.to_ascii_lowercase().as_str() {
+            "zip" => Ok(ArchiveFormat::Zip),
+            "tar.gz" | "tgz" => Ok(ArchiveFormat::TarGz),
+            _ => Err(anyhow!("Unknown archive format: {} (expected zip or tar.gz)", format)),
+        }
+    }
+}
+
+impl ArchiveFormat {
+    /// Format implied by an archive's file name: `site.zip`, `site.tar.gz`, `site.tgz`
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let name = path.file_name()?.to_string_lossy().to_ascii_lowercase();
+        if name.ends_with(".zip") {
+            Some(ArchiveFormat::Zip)
+        } else if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+            Some(ArchiveFormat::TarGz)
+        } else {
+            None
+        }
+    }
+}
+
+/// One archived file, as listed in the archive's index
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct IndexEntry {
+    pub path: String,
+    pub size: u64,
+    pub sha256: String,
+}
+
+/// Contents of `archive-index.json`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct ArchiveIndex {
+    pub files: Vec<IndexEntry>,
+    pub total_bytes: u64,
+}
+
+/// A file to archive: path inside the archive, content and modification time
+/// (seconds since the Unix epoch)
+pub(crate) struct ArchiveFile {
+    pub(crate) path: String,
+    pub(crate) content: Vec<u8>,
+    pub(crate) mtime: u64,
+    /// Keep uncompressed in a zip even when deflating would shrink it (EPUB's
+    /// `mimetype` must be)
+    pub(crate) stored: bool,
+}
+
+/// Every mirrored file under `dir` in path order, the index first. The crawl
+/// manifest, run reports and `excluded` (the archive being written) are left out.
+fn archive_files(dir: &Path, excluded: &Path) -> Result<(Vec<ArchiveFile>, ArchiveIndex)> {
+    let mut paths = BTreeMap::new();
+    snapshot_diff::collect_files(dir, dir, &mut paths)?;
+    let excluded = fs::canonicalize(excluded).ok();
+
+    let mut files = Vec::new();
+    let mut index = ArchiveIndex::default();
+    for path in paths.into_keys() {
+        let full_path = dir.join(&path);
+        if excluded.is_some() && fs::canonicalize(&full_path).ok() == excluded {
+            continue;
+        }
+        let content = fs::read(&full_path).with_context(|| format!("Failed to read {:?}", full_path))?;
+        let mtime = fs::metadata(&full_path)?
+            .modified()
+            .ok()
+            .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+            .map_or(0, |age| age.as_secs());
+        index.total_bytes += content.len() as u64;
+        index.files.push(IndexEntry {
+            path: path.clone(),
+            size: content.len() as u64,
+            sha256: format!("{:x}", Sha256::digest(&content)),
+        });
+        files.push(ArchiveFile { path, content, mtime, stored: false });
+    }
+
+    // The index takes the newest file's time, so exporting an unchanged mirror twice
+    // gives identical archives
+    let index_file = ArchiveFile {
+        path: INDEX_FILE_NAME.to_string(),
+        content: serde_json::to_vec_pretty(&index)?,
+        mtime: files.iter().map(|file| file.mtime).max().unwrap_or(0),
+        stored: false,
+    };
+    files.insert(0, index_file);
+    Ok((files, index))
+}
+
+/// Package the mirror in `dir` as a single zip or gzipped tar file, in stable
+/// path order with an index of every file (`archive-index.json`) first
+pub fn export_archive(dir: &Path, archive_path: &Path, format: ArchiveFormat) -> Result<ArchiveIndex> {
+    if !dir.is_dir() {
+        bail!("{} is not a directory", dir.display());
+    }
+    let (files, index) = archive_files(dir, archive_path)?;
+    let file = File::create(archive_path).with_context(|| format!("Failed to create archive: {:?}", archive_path))?;
+    write_archive(BufWriter::new(file), &files, format)?.flush()?;
+    Ok(index)
+}
+
+/// Like `export_archive`, but the archive is encrypted as it is written
+/// (`site.zip.age`) so no plaintext copy touches the disk
+#[cfg(feature = "encryption")]
+pub fn export_archive_encrypted(dir: &Path, archive_path: &Path, format: ArchiveFormat, encryption: &Encryption) -> Result<ArchiveIndex> {
+    if !dir.is_dir() {
+        bail!("{} is not a directory", dir.display());
+    }
+    let encrypted_archive = encryption::encrypted_path(archive_path);
+    let (files, index) = archive_files(dir, &encrypted_archive)?;
+    let file = File::create(&encrypted_archive).with_context(|| format!("Failed to create archive: {:?}", encrypted_archive))?;
+    let writer = encryption.wrap_output(BufWriter::new(file))?;
+    write_archive(writer, &files, format)?.finish()?.flush()?;
+    Ok(index)
+}
+
+/// Write `files` in `format`, returning the writer once the archive is complete
+fn write_archive<W: Write>(mut writer: W, files: &[ArchiveFile], format: ArchiveFormat) -> Result<W> {
+    match format {
+        ArchiveFormat::Zip => write_zip(&mut writer, files)?,
+        ArchiveFormat::TarGz => {
+            let mut encoder = GzEncoder::new(&mut writer, Compression::default());
+            write_tar(&mut encoder, files)?;
+            encoder.finish()?;
+        }
+    }
+    Ok(writer)
+}
+
+/// `value` as a NUL-terminated octal field of `width` bytes
+fn octal_field(field: &mut [u8], value: u64) {
+    let digits = format!("{:0width$o}", value, width = field.len() - 1);
+    field[..digits.len()].copy_from_slice(digits.as_bytes());
+    field[digits.len()] = 0;
+}
+
+/// A ustar header block
+fn tar_header(name: &[u8], prefix: &[u8], size: u64, mtime: u64, type_flag: u8) -> [u8; 512] {
+    let mut header = [0u8; 512];
+    header[..name.len()].copy_from_slice(name);
+    octal_field(&mut header[100..108], 0o644);
+    octal_field(&mut header[108..116], 0);
+    octal_field(&mut header[116..124], 0);
+    octal_field(&mut header[124..136], size);
+    octal_field(&mut header[136..148], mtime);
+    header[156] = type_flag;
+    header[257..263].copy_from_slice(b"ustar\0");
+    header[263..265].copy_from_slice(b"00");
+    header[345..345 + prefix.len()].copy_from_slice(prefix);
+
+    // The checksum is computed with its own field read as spaces
+    header[148..156].fill(b' ');
+    let checksum: u32 = header.iter().map(|&byte| byte as u32).sum();
+    let digits = format!("{:06o}\0 ", checksum);
+    header[148..156].copy_from_slice(digits.as_bytes());
+    header
+}
+
+/// Split a path into the ustar `prefix` (155 bytes) and `name` (100 bytes)
+/// fields at a `/`, if it fits
+fn split_tar_path(path: &str) -> Option<(&str, &str)> {
+    if path.len() <= 100 {
+        return Some(("", path));
+    }
+    path.match_indices('/')
+        .map(|(index, _)| (&path[..index], &path[index + 1..]))
+        .find(|(prefix, name)| prefix.len() <= 155 && name.len() <= 100 && !name.is_empty())
+}
+
+/// Pad an entry of `size` bytes to the next 512-byte block
+fn write_tar_padding<W: Write>(writer: &mut W, size: usize) -> Result<()> {
+    let padding = (512 - size % 512) % 512;
+    writer.write_all(&vec![0u8; padding])?;
+    Ok(())
+}
+
+fn write_tar<W: Write>(writer: &mut W, files: &[ArchiveFile]) -> Result<()> {
+    for file in files {
+        let (prefix, name) = match split_tar_path(&file.path) {
+            Some(split) => split,
+            None => {
+                // Longer paths go in a PAX extended header, whose record length counts its own digits
+                let record_body = format!(" path={}\n", file.path);
+                let mut length = record_body.len() + 1;
+                while format!("{}{}", length, record_body).len() != length {
+                    length += 1;
+                }
+                let record = format!("{}{}", length, record_body);
+                writer.write_all(&tar_header(b"PaxHeader", b"", record.len() as u64, file.mtime, b'x'))?;
+                writer.write_all(record.as_bytes())?;
+                write_tar_padding(writer, record.len())?;
+                let short_name = &file.path[file.path.len().saturating_sub(100)..];
+                ("", short_name.trim_start_matches('/'))
+            }
+        };
+        writer.write_all(&tar_header(name.as_bytes(), prefix.as_bytes(), file.content.len() as u64, file.mtime, b'0'))?;
+        writer.write_all(&file.content)?;
+        write_tar_padding(writer, file.content.len())?;
+    }
+    // End of archive: two empty blocks
+    writer.write_all(&[0u8; 1024])?;
+    Ok(())
+}
+
+/// (year, month, day) of a Unix timestamp in UTC
+pub(crate) fn civil_date(timestamp: u64) -> (i64, i64, i64) {
+    let days = (timestamp / 86_400) as i64;
+    // Civil date from days since 1970-01-01 (proleptic Gregorian calendar)
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z - era * 146_097;
+    let year_of_era = (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 { month_index + 3 } else { month_index - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+/// MS-DOS (time, date) fields of a Unix timestamp in UTC; zip can't store
+/// dates before 1980
+fn dos_date_time(mtime: u64) -> (u16, u16) {
+    let (year, month, day) = civil_date(mtime);
+    let seconds = mtime % 86_400;
+    if year < 1980 {
+        return (0, (1 << 5) | 1);
+    }
+    let time = ((seconds / 3_600) << 11) | (((seconds % 3_600) / 60) << 5) | ((seconds % 60) / 2);
+    let date = (((year - 1980).min(127) as u64) << 9) | ((month as u64) << 5) | day as u64;
+    (time as u16, date as u16)
+}
+
+pub(crate) fn write_zip<W: Write>(mut writer: W, files: &[ArchiveFile]) -> Result<()> {
+    if files.len() > u16::MAX as usize {
+        bail!("{} files are too many for a zip archive; export as tar.gz instead", files.len());
+    }
+    let too_large = || anyhow!("The mirror is too large for a zip archive (over 4 GB); export as tar.gz instead");
+    let mut central_directory = Vec::new();
+    let mut offset: u64 = 0;
+    for file in files {
+        let mut crc = Crc::new();
+        crc.update(&file.content);
+        let mut encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&file.content)?;
+        let deflated = encoder.finish()?;
+        // Images, video and other compressed files are stored as they are
+        let (method, data): (u16, &[u8]) = if deflated.len() < file.content.len() && !file.stored {
+            (8, &deflated)
+        } else {
+            (0, &file.content)
+        };
+        let (time, date) = dos_date_time(file.mtime);
+        let name = file.path.as_bytes();
+        let local_header_offset = u32::try_from(offset).map_err(|_| too_large())?;
+        let compressed_size = u32::try_from(data.len()).map_err(|_| too_large())?;
+        let size = u32::try_from(file.content.len()).map_err(|_| too_large())?;
+
+        // Version 2.0, UTF-8 names (flag bit 11)
+        let mut common = Vec::new();
+        common.extend_from_slice(&20u16.to_le_bytes());
+        common.extend_from_slice(&0x0800u16.to_le_bytes());
+        common.extend_from_slice(&method.to_le_bytes());
+        common.extend_from_slice(&time.to_le_bytes());
+        common.extend_from_slice(&date.to_le_bytes());
+        common.extend_from_slice(&crc.sum().to_le_bytes());
+        common.extend_from_slice(&compressed_size.to_le_bytes());
+        common.extend_from_slice(&size.to_le_bytes());
+        common.extend_from_slice(&(name.len() as u16).to_le_bytes());
+        common.extend_from_slice(&0u16.to_le_bytes());
+
+        writer.write_all(&0x04034b50u32.to_le_bytes())?;
+        writer.write_all(&common)?;
+        writer.write_all(name)?;
+        writer.write_all(data)?;
+        offset += 30 + name.len() as u64 + data.len() as u64;
+
+        // Made by Unix (3) with 0644 permissions, so unzip restores sane modes
+        central_directory.extend_from_slice(&0x02014b50u32.to_le_bytes());
+        central_directory.extend_from_slice(&((3u16 << 8) | 20).to_le_bytes());
+        central_directory.extend_from_slice(&common);
+        // No comment, disk 0, no internal attributes
+        central_directory.extend_from_slice(&[0u8; 6]);
+        central_directory.extend_from_slice(&((0o100644u32) << 16).to_le_bytes());
+        central_directory.extend_from_slice(&local_header_offset.to_le_bytes());
+        central_directory.extend_from_slice(name);
+    }
+
+    let directory_offset = u32::try_from(offset).map_err(|_| too_large())?;
+    writer.write_all(&central_directory)?;
+    writer.write_all(&0x06054b50u32.to_le_bytes())?;
+    writer.write_all(&[0u8; 4])?;
+    writer.write_all(&(files.len() as u16).to_le_bytes())?;
+    writer.write_all(&(files.len() as u16).to_le_bytes())?;
+    writer.write_all(&(central_directory.len() as u32).to_le_bytes())?;
+    writer.write_all(&directory_offset.to_le_bytes())?;
+    writer.write_all(&0u16.to_le_bytes())?;
+    writer.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::write;
+    use flate2::read::GzDecoder;
+    use std::io::Read;
+    use tempfile::tempdir;
+
+    /// A path too long for the ustar name and prefix fields
+    fn deep_page() -> String {
+        format!("{}page.html", "deep/".repeat(60))
+    }
+
+    fn mirror(dir: &Path) {
+        write(dir, "index.html", "<h1>Home</h1>");
+        write(dir, "css/site.css", "body { color: red; }");
+        write(dir, &deep_page(), "<p>Deep</p>");
+        write(dir, ".download_store.json", "{}");
+        write(dir, "report.json", "{}");
+    }
+
+    #[test]
+    fn test_export_tar_gz() {
+        let temp_dir = tempdir().unwrap();
+        let dir = temp_dir.path().join("site");
+        mirror(&dir);
+        let archive = dir.join("site.tar.gz");
+
+        let index = export_archive(&dir, &archive, ArchiveFormat::TarGz).unwrap();
+        let paths: Vec<&str> = index.files.iter().map(|file| file.path.as_str()).collect();
+        assert_eq!(paths, vec!["css/site.css", deep_page().as_str(), "index.html"]);
+        assert_eq!(index.total_bytes, 44);
+
+        let mut tar = Vec::new();
+        GzDecoder::new(File::open(&archive).unwrap()).read_to_end(&mut tar).unwrap();
+        assert_eq!(tar.len() % 512, 0);
+        assert_eq!(&tar[..INDEX_FILE_NAME.len()], INDEX_FILE_NAME.as_bytes());
+        assert_eq!(&tar[257..263], b"ustar\0");
+        let text = String::from_utf8_lossy(&tar);
+        assert!(text.contains("path=deep/deep/"));
+        assert!(text.contains("body { color: red; }"));
+        assert!(!text.contains("download_store"));
+
+        // Unchanged mirrors export to identical archives
+        let first = fs::read(&archive).unwrap();
+        export_archive(&dir, &archive, ArchiveFormat::TarGz).unwrap();
+        assert_eq!(fs::read(&archive).unwrap(), first);
+    }
+
+    #[test]
+    fn test_export_zip() {
+        let temp_dir = tempdir().unwrap();
+        let dir = temp_dir.path().join("site");
+        mirror(&dir);
+        let archive = temp_dir.path().join("site.zip");
+
+        let index = export_archive(&dir, &archive, ArchiveFormat::Zip).unwrap();
+        assert_eq!(index.files.len(), 3);
+        let zip = fs::read(&archive).unwrap();
+        assert_eq!(&zip[..4], b"PK\x03\x04");
+        let end = &zip[zip.len() - 22..];
+        assert_eq!(&end[..4], b"PK\x05\x06");
+        assert_eq!(u16::from_le_bytes([end[10], end[11]]), 4);
+
+        assert_eq!(ArchiveFormat::from_path(Path::new("site.tgz")), Some(ArchiveFormat::TarGz));
+        assert_eq!("zip".parse::<ArchiveFormat>().unwrap(), ArchiveFormat::Zip);
+        assert_eq!(dos_date_time(1_700_000_000), ((22 << 11) | (13 << 5) | 10, (43 << 9) | (11 << 5) | 14));
+    }
+
+    #[cfg(feature = "encryption")]
+    #[test]
+    fn test_export_archive_encrypted() {
+        let temp_dir = tempdir().unwrap();
+        let dir = temp_dir.path().join("site");
+        mirror(&dir);
+        let identity = age::x25519::Identity::generate();
+        let archive = dir.join("site.tar.gz");
+
+        let index = export_archive_encrypted(&dir, &archive, ArchiveFormat::TarGz, &Encryption::Age(identity.to_public())).unwrap();
+        assert_eq!(index.files.len(), 3);
+        assert!(!archive.exists());
+        let encrypted = fs::read(dir.join("site.tar.gz.age")).unwrap();
+
+        let mut tar = Vec::new();
+        let decrypted = encryption::decrypt_reader(encrypted.as_slice(), &[identity]).unwrap();
+        GzDecoder::new(decrypted).read_to_end(&mut tar).unwrap();
+        let text = String::from_utf8_lossy(&tar);
+        assert!(text.contains("body { color: red; }"));
+        assert!(!text.contains("site.tar.gz.age"));
+    }
+}
@@ -0,0 +1,169 @@
+use anyhow::{anyhow, Result};
+use regex::Regex;
+use std::fmt;
+use std::str::FromStr;
+
+/// What a crawl did, checked by `--assert` at the end of the run
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CrawlStats {
+    /// HTML pages saved
+    pub pages: u64,
+    /// Other files saved (stylesheets, scripts, images, media)
+    pub files: u64,
+    /// Pages and resources that couldn't be fetched (connection errors, HTTP errors)
+    pub failures: u64,
+    /// Local references that don't resolve to a saved file, once validation has run
+    pub broken_links: Option<u64>,
+    /// Bytes written to the output directory
+    pub bytes: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CrawlMetric {
+    Pages,
+    Files,
+    Failures,
+    BrokenLinks,
+    Bytes,
+}
+
+impl CrawlMetric {
+    const ALL: [CrawlMetric; 5] = [CrawlMetric::Pages, CrawlMetric::Files, CrawlMetric::Failures, CrawlMetric::BrokenLinks, CrawlMetric::Bytes];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            CrawlMetric::Pages => "pages",
+            CrawlMetric::Files => "files",
+            CrawlMetric::Failures => "failures",
+            CrawlMetric::BrokenLinks => "broken_links",
+            CrawlMetric::Bytes => "bytes",
+        }
+    }
+
+    /// The metric's value, `None` when it wasn't measured
+    pub fn value(&self, stats: &CrawlStats) -> Option<u64> {
+        match self {
+            CrawlMetric::Pages => Some(stats.pages),
+            CrawlMetric::Files => Some(stats.files),
+            CrawlMetric::Failures => Some(stats.failures),
+            CrawlMetric::BrokenLinks => stats.broken_links,
+            CrawlMetric::Bytes => Some(stats.bytes),
+        }
+    }
+}
+
+impl FromStr for CrawlMetric {
+    type Err = anyhow::Error;
+
+    fn from_str(name: &str) -> Result<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|metric| metric.name() == name)
+            .ok_or_else(|| anyhow!("Unknown crawl metric: {} (expected pages, files, failures, broken_links or bytes)", name))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Comparison {
+    Eq,
+    Ne,
+    Ge,
+    Le,
+    Gt,
+    Lt,
+}
+
+impl Comparison {
+    fn symbol(&self) -> &'static str {
+        match self {
+            Comparison::Eq => "==",
+            Comparison::Ne => "!=",
+            Comparison::Ge => ">=",
+            Comparison::Le => "<=",
+            Comparison::Gt => ">",
+            Comparison::Lt => "<",
+        }
+    }
+
+    fn holds(&self, actual: u64, expected: u64) -> bool {
+        match self {
+            Comparison::Eq => actual == expected,
+            Comparison::Ne => actual != expected,
+            Comparison::Ge => actual >= expected,
+            Comparison::Le => actual <= expected,
+            Comparison::Gt => actual > expected,
+            Comparison::Lt => actual < expected,
+        }
+    }
+}
+
+/// A condition on the crawl's outcome (`--assert "failures==0"`); a violated
+/// assertion fails the run
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CrawlAssertion {
+    pub metric: CrawlMetric,
+    pub comparison: Comparison,
+    pub expected: u64,
+}
+
+impl FromStr for CrawlAssertion {
+    type Err = anyhow::Error;
+
+    fn from_str(text: &str) -> Result<Self> {
+        let assertion_regex = Regex::new(r"^\s*([a-z_]+)\s*(==|!=|>=|<=|>|<)\s*(\d+)\s*$").unwrap();
+        let caps = assertion_regex
+            .captures(text)
+            .ok_or_else(|| anyhow!("Invalid assertion: {} (expected <metric><op><number>, e.g. failures==0)", text))?;
+        let comparison = match &caps[2] {
+            "==" => Comparison::Eq,
+            "!=" => Comparison::Ne,
+            ">=" => Comparison::Ge,
+            "<=" => Comparison::Le,
+            ">" => Comparison::Gt,
+            _ => Comparison::Lt,
+        };
+        Ok(Self {
+            metric: caps[1].parse()?,
+            comparison,
+            expected: caps[3].parse()?,
+        })
+    }
+}
+
+impl fmt::Display for CrawlAssertion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}{}", self.metric.name(), self.comparison.symbol(), self.expected)
+    }
+}
+
+impl CrawlAssertion {
+    /// Whether the crawl satisfied the assertion; unmeasured metrics never do
+    pub fn holds(&self, stats: &CrawlStats) -> bool {
+        self.metric.value(stats).is_some_and(|actual| self.comparison.holds(actual, self.expected))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_assertion() {
+        let assertion: CrawlAssertion = "pages>=100".parse().unwrap();
+        assert_eq!(assertion.metric, CrawlMetric::Pages);
+        assert_eq!(assertion.comparison, Comparison::Ge);
+        assert_eq!(assertion.expected, 100);
+        assert_eq!(" broken_links == 0 ".parse::<CrawlAssertion>().unwrap().to_string(), "broken_links==0");
+        assert!("pages=>100".parse::<CrawlAssertion>().is_err());
+        assert!("errors==0".parse::<CrawlAssertion>().is_err());
+    }
+
+    #[test]
+    fn test_assertion_holds() {
+        let stats = CrawlStats { pages: 120, failures: 2, ..Default::default() };
+        assert!("pages>=100".parse::<CrawlAssertion>().unwrap().holds(&stats));
+        assert!(!"failures==0".parse::<CrawlAssertion>().unwrap().holds(&stats));
+        // Validation didn't run, so broken links weren't counted
+        assert!(!"broken_links==0".parse::<CrawlAssertion>().unwrap().holds(&stats));
+    }
+}
@@ -0,0 +1,48 @@
+use anyhow::{anyhow, Result, Context};
+use tokio::process::Command;
+
+/// Run a token refresh hook (e.g. `./get-token.sh`) through the shell and
+/// return the bearer token it prints on stdout
+pub async fn run_token_command(command: &str) -> Result<String> {
+    let output = Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .output()
+        .await
+        .with_context(|| format!("Failed to run token command: {}", command))?;
+
+    if !output.status.success() {
+        return Err(anyhow!(
+            "Token command exited with {}: {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+
+    let token = String::from_utf8(output.stdout)
+        .context("Token command printed invalid UTF-8")?
+        .trim()
+        .to_string();
+    if token.is_empty() {
+        return Err(anyhow!("Token command printed an empty token"));
+    }
+
+    Ok(token)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_run_token_command_trims_output() {
+        let token = run_token_command("echo '  abc123  '").await.unwrap();
+        assert_eq!(token, "abc123");
+    }
+
+    #[tokio::test]
+    async fn test_run_token_command_failures() {
+        assert!(run_token_command("exit 3").await.is_err());
+        assert!(run_token_command("printf ''").await.is_err());
+    }
+}
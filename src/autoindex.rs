@@ -0,0 +1,154 @@
+use regex::{Captures, Regex};
+use select::document::Document;
+use select::predicate::Name;
+
+use crate::html_parser::ResourceType;
+
+/// Heading/title prefixes written by Apache, nginx and lighttpd autoindex
+/// and Python's `http.server`
+const LISTING_TITLE_PREFIXES: &[&str] = &["index of ", "directory listing for "];
+
+const ANCHOR_HREF_PATTERN: &str = r#"(?is)(<a\b[^>]*?\shref\s*=\s*)(?:"([^"]*)"|'([^']*)')"#;
+
+/// A file or subdirectory linked from a directory listing, as written
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ListingEntry {
+    pub href: String,
+    pub is_directory: bool,
+}
+
+impl ListingEntry {
+    /// Subdirectories (and listed HTML files) are crawled as pages; other
+    /// files are fetched as assets classified by their extension
+    pub fn resource_type(&self) -> ResourceType {
+        let path = self.href.split(['?', '#']).next().unwrap_or(&self.href).to_lowercase();
+        if self.is_directory || path.ends_with(".html") || path.ends_with(".htm") {
+            ResourceType::Link
+        } else {
+            ResourceType::from_extension(&path)
+        }
+    }
+}
+
+/// Whether a page is a server-generated directory listing
+pub fn is_directory_listing(html_content: &str) -> bool {
+    let document = Document::from(html_content);
+    document
+        .find(Name("title"))
+        .chain(document.find(Name("h1")))
+        .any(|element| {
+            let text = element.text().trim().to_lowercase();
+            LISTING_TITLE_PREFIXES.iter().any(|prefix| text.starts_with(prefix))
+        })
+}
+
+/// Column sort links (`?C=N;O=D`) that re-render the same listing
+pub fn is_sort_link(href: &str) -> bool {
+    href.trim().starts_with('?')
+}
+
+/// Listed entries are plain names relative to the directory; sort links,
+/// fragments, the parent directory and absolute links are not entries
+fn is_listing_entry(href: &str) -> bool {
+    let href = href.trim();
+    let has_scheme = href.split_once(':').is_some_and(|(scheme, _)| !scheme.contains('/'));
+    !(href.is_empty()
+        || is_sort_link(href)
+        || href.starts_with('#')
+        || href.starts_with('/')
+        || href.starts_with("..")
+        || href == "./"
+        || has_scheme)
+}
+
+/// Every file and subdirectory in a listing, in page order without duplicates
+/// (fancy indexes link each entry from both its icon and its name)
+pub fn extract_listing_entries(html_content: &str) -> Vec<ListingEntry> {
+    let document = Document::from(html_content);
+    let mut entries: Vec<ListingEntry> = Vec::new();
+    for anchor in document.find(Name("a")) {
+        let Some(href) = anchor.attr("href").map(str::trim) else { continue };
+        if !is_listing_entry(href) || entries.iter().any(|entry| entry.href == href) {
+            continue;
+        }
+        let path = href.split(['?', '#']).next().unwrap_or(href);
+        entries.push(ListingEntry {
+            href: href.to_string(),
+            is_directory: path.ends_with('/'),
+        });
+    }
+    entries
+}
+
+/// Rewrite the `href` of every link on a listing page. The callback returns
+/// the replacement, or `None` to leave a link untouched.
+pub fn rewrite_listing_links<F>(html_content: &str, mut rewrite: F) -> String
+where
+    F: FnMut(&str) -> Option<String>,
+{
+    let anchor_regex = Regex::new(ANCHOR_HREF_PATTERN).unwrap();
+    anchor_regex
+        .replace_all(html_content, |caps: &Captures| {
+            let href = caps.get(2).or_else(|| caps.get(3)).map(|m| m.as_str()).unwrap_or("");
+            match rewrite(href.trim()) {
+                Some(new_href) => format!("{}\"{}\"", &caps[1], new_href),
+                None => caps[0].to_string(),
+            }
+        })
+        .into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const APACHE_LISTING: &str = r#"<!DOCTYPE HTML PUBLIC "-//W3C//DTD HTML 3.2 Final//EN">
+<html><head><title>Index of /pub/releases</title></head>
+<body><h1>Index of /pub/releases</h1>
+<table>
+<tr><th><a href="?C=N;O=D">Name</a></th><th><a href="?C=M;O=A">Last modified</a></th></tr>
+<tr><td><a href="/pub/">Parent Directory</a></td></tr>
+<tr><td><a href="v1.0/"><img src="/icons/folder.gif"></a></td><td><a href="v1.0/">v1.0/</a></td></tr>
+<tr><td><a href="tool-1.0.tar.gz">tool-1.0.tar.gz</a></td></tr>
+<tr><td><a href='notes.html'>notes.html</a></td></tr>
+<tr><td><a href="https://mirror.example.org/">Mirrors</a></td></tr>
+</table></body></html>"#;
+
+    #[test]
+    fn test_is_directory_listing() {
+        assert!(is_directory_listing(APACHE_LISTING));
+        assert!(is_directory_listing("<html><body><h1>Directory listing for /files/</h1></body></html>"));
+        assert!(!is_directory_listing("<html><head><title>Home</title></head><body><h1>Welcome</h1></body></html>"));
+    }
+
+    #[test]
+    fn test_extract_listing_entries() {
+        let entries = extract_listing_entries(APACHE_LISTING);
+        let hrefs: Vec<&str> = entries.iter().map(|entry| entry.href.as_str()).collect();
+
+        assert_eq!(hrefs, vec!["v1.0/", "tool-1.0.tar.gz", "notes.html"]);
+        assert!(entries[0].is_directory);
+        assert_eq!(entries[0].resource_type(), ResourceType::Link);
+        assert_eq!(entries[1].resource_type(), ResourceType::Other);
+        assert_eq!(entries[2].resource_type(), ResourceType::Link);
+    }
+
+    #[test]
+    fn test_rewrite_listing_links() {
+        let rewritten = rewrite_listing_links(APACHE_LISTING, |href| {
+            if is_sort_link(href) {
+                Some("index.html".to_string())
+            } else if href.ends_with('/') && !href.starts_with("http") {
+                Some(format!("{}index.html", href.trim_start_matches("/pub/")))
+            } else {
+                None
+            }
+        });
+
+        assert!(rewritten.contains(r#"<a href="index.html">Name</a>"#));
+        assert!(rewritten.contains(r#"<a href="v1.0/index.html">v1.0/</a>"#));
+        assert!(rewritten.contains(r#"<a href="tool-1.0.tar.gz">"#));
+        assert!(rewritten.contains(r#"<a href='notes.html'>"#));
+        assert!(rewritten.contains(r#"<a href="https://mirror.example.org/">"#));
+    }
+}
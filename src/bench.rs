@@ -0,0 +1,274 @@
+use anyhow::{bail, Result, Context};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::hint::black_box;
+use std::path::Path;
+use std::time::{Duration, Instant};
+use url::Url;
+
+use crate::css_parser;
+use crate::encoding;
+use crate::html_parser::HtmlParser;
+use crate::snapshot_diff;
+
+/// Stages of the page pipeline, in the order a page goes through them
+pub const STAGES: [&str; 4] = ["decode", "parse", "rewrite", "convert"];
+
+/// Time spent in one stage, over every page and iteration
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StageTiming {
+    pub stage: String,
+    pub total_ms: f64,
+    /// Per page
+    pub mean_us: f64,
+    pub median_us: f64,
+    pub p95_us: f64,
+}
+
+/// Result of `website-mirror bench`, also the format of `--output` and `--baseline`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BenchReport {
+    pub pages: usize,
+    pub iterations: usize,
+    /// Size of the fixture pages
+    pub bytes: u64,
+    pub stages: Vec<StageTiming>,
+}
+
+impl BenchReport {
+    pub fn total_ms(&self) -> f64 {
+        self.stages.iter().map(|stage| stage.total_ms).sum()
+    }
+}
+
+/// A stage's mean time per page against a baseline run
+#[derive(Debug, Clone, PartialEq)]
+pub struct StageChange {
+    pub stage: String,
+    pub baseline_us: f64,
+    pub current_us: f64,
+    /// Relative change in percent, positive when slower
+    pub change_percent: f64,
+}
+
+/// A saved page to run through the pipeline
+struct Fixture {
+    path: String,
+    content: Vec<u8>,
+}
+
+fn load_fixtures(dir: &Path) -> Result<Vec<Fixture>> {
+    let mut files = BTreeMap::new();
+    snapshot_diff::collect_files(dir, dir, &mut files)?;
+    let mut fixtures = Vec::new();
+    for path in files.keys().filter(|path| snapshot_diff::is_page(path)) {
+        let content = fs::read(dir.join(path)).with_context(|| format!("Failed to read fixture: {:?}", path))?;
+        fixtures.push(Fixture { path: path.clone(), content });
+    }
+    if fixtures.is_empty() {
+        bail!("No HTML fixtures in {}", dir.display());
+    }
+    Ok(fixtures)
+}
+
+/// Run one page through decoding, resource extraction, reference rewriting and
+/// link conversion as a crawl does, adding each stage's time to `samples`
+fn run_pipeline(fixture: &Fixture, page_url: &str, samples: &mut [Vec<Duration>; 4]) -> Result<()> {
+    let start = Instant::now();
+    let page_encoding = encoding::detect_html_encoding("text/html", &fixture.content);
+    let html_content = encoding::decode_html(&fixture.content, page_encoding);
+    samples[0].push(start.elapsed());
+
+    let start = Instant::now();
+    let lazy_attrs: Vec<String> = HtmlParser::DEFAULT_LAZY_ATTRS.iter().map(|attr| attr.to_string()).collect();
+    let parser = HtmlParser::new(page_url)?.with_lazy_attrs(&lazy_attrs);
+    let mut resources = parser.extract_resources(&html_content)?;
+    resources.extend(parser.extract_comment_resources(&html_content));
+    samples[1].push(start.elapsed());
+
+    let start = Instant::now();
+    let local_path = |reference: &str| {
+        let absolute_url = parser.resolve_url(reference).ok()?;
+        parser.url_to_local_path_string(absolute_url.as_str()).ok()
+    };
+    let mut rewritten = css_parser::rewrite_style_blocks(&html_content, |reference| local_path(&reference.url));
+    rewritten = parser.rewrite_lazy_attributes(&rewritten, false, local_path);
+    for resource in &resources {
+        rewritten = rewritten.replace(&resource.original_url, &resource.local_path);
+    }
+    samples[2].push(start.elapsed());
+
+    let start = Instant::now();
+    black_box(parser.convert_html_links(&rewritten)?);
+    samples[3].push(start.elapsed());
+    Ok(())
+}
+
+fn micros(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1_000_000.0
+}
+
+/// Sample at quantile `q` (0.0-1.0) of sorted samples
+fn percentile(sorted: &[Duration], q: f64) -> Duration {
+    sorted[((sorted.len() - 1) as f64 * q).round() as usize]
+}
+
+fn stage_timing(stage: &str, samples: &mut [Duration]) -> StageTiming {
+    samples.sort_unstable();
+    let total: Duration = samples.iter().sum();
+    StageTiming {
+        stage: stage.to_string(),
+        total_ms: total.as_secs_f64() * 1_000.0,
+        mean_us: micros(total) / samples.len() as f64,
+        median_us: micros(percentile(samples, 0.5)),
+        p95_us: micros(percentile(samples, 0.95)),
+    }
+}
+
+/// Time the page pipeline over every HTML page under `fixtures_dir`, served as
+/// if from `base_url`, `iterations` times after one warm-up pass
+pub fn run_bench(fixtures_dir: &Path, base_url: &str, iterations: usize) -> Result<BenchReport> {
+    let base_url = Url::parse(base_url).with_context(|| format!("Invalid base URL: {}", base_url))?;
+    let fixtures = load_fixtures(fixtures_dir)?;
+    let page_urls = fixtures
+        .iter()
+        .map(|fixture| Ok(base_url.join(&fixture.path)?.to_string()))
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut warm_up: [Vec<Duration>; 4] = Default::default();
+    for (fixture, page_url) in fixtures.iter().zip(&page_urls) {
+        run_pipeline(fixture, page_url, &mut warm_up).with_context(|| format!("Failed to process fixture: {:?}", fixture.path))?;
+    }
+    let mut samples: [Vec<Duration>; 4] = Default::default();
+    for _ in 0..iterations.max(1) {
+        for (fixture, page_url) in fixtures.iter().zip(&page_urls) {
+            run_pipeline(fixture, page_url, &mut samples)?;
+        }
+    }
+
+    Ok(BenchReport {
+        pages: fixtures.len(),
+        iterations: iterations.max(1),
+        bytes: fixtures.iter().map(|fixture| fixture.content.len() as u64).sum(),
+        stages: STAGES.iter().zip(samples.iter_mut()).map(|(stage, samples)| stage_timing(stage, samples)).collect(),
+    })
+}
+
+/// Mean time per page of every stage against a baseline report
+pub fn compare(report: &BenchReport, baseline: &BenchReport) -> Vec<StageChange> {
+    report
+        .stages
+        .iter()
+        .filter_map(|current| {
+            let previous = baseline.stages.iter().find(|stage| stage.stage == current.stage)?;
+            Some(StageChange {
+                stage: current.stage.clone(),
+                baseline_us: previous.mean_us,
+                current_us: current.mean_us,
+                change_percent: if previous.mean_us > 0.0 { (current.mean_us / previous.mean_us - 1.0) * 100.0 } else { 0.0 },
+            })
+        })
+        .collect()
+}
+
+/// The timing table printed by `website-mirror bench`
+pub fn format_report(report: &BenchReport) -> String {
+    let total_ms = report.total_ms();
+    let mut output = format!(
+        "{} pages ({:.1} KB) x {} iterations\n\n{:<10} {:>12} {:>12} {:>12} {:>12} {:>7}\n",
+        report.pages,
+        report.bytes as f64 / 1024.0,
+        report.iterations,
+        "stage",
+        "total ms",
+        "mean µs",
+        "median µs",
+        "p95 µs",
+        "share"
+    );
+    for stage in &report.stages {
+        let share = if total_ms > 0.0 { stage.total_ms / total_ms * 100.0 } else { 0.0 };
+        output.push_str(&format!(
+            "{:<10} {:>12.2} {:>12.1} {:>12.1} {:>12.1} {:>6.1}%\n",
+            stage.stage, stage.total_ms, stage.mean_us, stage.median_us, stage.p95_us, share
+        ));
+    }
+    let pages_per_second = if total_ms > 0.0 { (report.pages * report.iterations) as f64 / total_ms * 1_000.0 } else { 0.0 };
+    output.push_str(&format!("{:<10} {:>12.2}    ({:.0} pages/s)\n", "total", total_ms, pages_per_second));
+    output
+}
+
+pub fn save_report(report: &BenchReport, path: &Path) -> Result<()> {
+    fs::write(path, serde_json::to_string_pretty(report)?).with_context(|| format!("Failed to write benchmark report: {:?}", path))
+}
+
+pub fn load_report(path: &Path) -> Result<BenchReport> {
+    let data = fs::read_to_string(path).with_context(|| format!("Failed to read benchmark report: {:?}", path))?;
+    serde_json::from_str(&data).with_context(|| format!("Failed to parse benchmark report: {:?}", path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::write;
+    use tempfile::tempdir;
+
+    fn report(means: &[f64]) -> BenchReport {
+        BenchReport {
+            pages: 1,
+            iterations: 1,
+            bytes: 0,
+            stages: STAGES
+                .iter()
+                .zip(means)
+                .map(|(stage, mean)| StageTiming { stage: stage.to_string(), total_ms: mean / 1_000.0, mean_us: *mean, median_us: *mean, p95_us: *mean })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_run_bench() {
+        let temp_dir = tempdir().unwrap();
+        let dir = temp_dir.path();
+        write(dir, "index.html", r#"<html><head><link rel="stylesheet" href="/style.css"><style>body { background: url(bg.png) }</style></head><body><img data-src="/lazy.jpg"><a href="/about/">About</a></body></html>"#);
+        write(dir, "blog/post.html", r#"<html><body><img src="../logo.png"><script src="https://cdn.example.org/app.js"></script></body></html>"#);
+        write(dir, "style.css", "body {}");
+
+        let report = run_bench(dir, "https://example.com/", 3).unwrap();
+        assert_eq!(report.pages, 2);
+        assert_eq!(report.iterations, 3);
+        assert_eq!(report.stages.iter().map(|stage| stage.stage.as_str()).collect::<Vec<_>>(), STAGES);
+        for stage in &report.stages {
+            assert!(stage.median_us <= stage.p95_us);
+        }
+        let table = format_report(&report);
+        assert!(table.starts_with("2 pages "));
+        assert!(table.contains("\nrewrite "));
+
+        let path = dir.join("bench.json");
+        save_report(&report, &path).unwrap();
+        let loaded = load_report(&path).unwrap();
+        assert_eq!((loaded.pages, loaded.iterations, loaded.stages.len()), (2, 3, 4));
+
+        assert!(run_bench(&dir.join("missing"), "https://example.com/", 1).is_err());
+    }
+
+    #[test]
+    fn test_percentile() {
+        let samples: Vec<Duration> = (1..=20).map(Duration::from_micros).collect();
+        assert_eq!(percentile(&samples, 0.5), Duration::from_micros(11));
+        assert_eq!(percentile(&samples, 0.95), Duration::from_micros(19));
+        assert_eq!(percentile(&samples[..1], 0.95), Duration::from_micros(1));
+    }
+
+    #[test]
+    fn test_compare() {
+        let changes = compare(&report(&[10.0, 200.0, 75.0, 50.0]), &report(&[10.0, 100.0, 100.0, 0.0]));
+        assert_eq!(changes.len(), 4);
+        assert_eq!(changes[1].stage, "parse");
+        assert_eq!(changes[1].change_percent, 100.0);
+        assert_eq!(changes[2].change_percent, -25.0);
+        assert_eq!(changes[3].change_percent, 0.0);
+    }
+}
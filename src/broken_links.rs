@@ -0,0 +1,213 @@
+use anyhow::{Result, Context};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use crate::store::DownloadStore;
+
+/// Broken-link report written into the output directory after a crawl with failures
+pub const JSON_FILE_NAME: &str = "broken-links.json";
+/// The same report as CSV, for spreadsheets
+pub const CSV_FILE_NAME: &str = "broken-links.csv";
+
+/// What became of a failed URL in the retry pass after the crawl
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RetryOutcome {
+    /// Permanent failures (404, 410, 403, ...) aren't retried
+    NotRetried,
+    /// The retry succeeded and the file was saved
+    Recovered,
+    StillFailing,
+}
+
+impl RetryOutcome {
+    fn as_str(self) -> &'static str {
+        match self {
+            RetryOutcome::NotRetried => "not_retried",
+            RetryOutcome::Recovered => "recovered",
+            RetryOutcome::StillFailing => "still_failing",
+        }
+    }
+}
+
+/// A URL that failed to download
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct BrokenLink {
+    pub url: String,
+    /// HTTP status of the last failed request, when the server answered
+    pub status: Option<u16>,
+    pub error: String,
+    /// Failed requests for the URL
+    pub attempts: u32,
+    pub retry: RetryOutcome,
+    /// Pages (or stylesheets) referencing the URL
+    pub referrers: Vec<String>,
+}
+
+/// Network errors, timeouts, rate limiting and server errors may pass on a
+/// second try; other client errors won't
+pub fn is_retriable(status: Option<u16>) -> bool {
+    match status {
+        None => true,
+        Some(status) => matches!(status, 408 | 425 | 429) || status >= 500,
+    }
+}
+
+/// Every URL that failed during a crawl, collected as failures are counted
+#[derive(Debug, Clone, Default)]
+pub struct BrokenLinks {
+    links: BTreeMap<String, BrokenLink>,
+}
+
+impl BrokenLinks {
+    pub fn record(&mut self, url: &str, status: Option<u16>, error: &str) {
+        let link = self.links.entry(url.to_string()).or_insert_with(|| BrokenLink {
+            url: url.to_string(),
+            status,
+            error: String::new(),
+            attempts: 0,
+            retry: RetryOutcome::NotRetried,
+            referrers: Vec::new(),
+        });
+        link.status = status;
+        link.error = error.to_string();
+        link.attempts += 1;
+    }
+
+    pub fn attempts(&self, url: &str) -> u32 {
+        self.links.get(url).map_or(0, |link| link.attempts)
+    }
+
+    pub fn set_retry(&mut self, url: &str, retry: RetryOutcome) {
+        if let Some(link) = self.links.get_mut(url) {
+            link.retry = retry;
+        }
+    }
+
+    /// Failed URLs worth another request, see `is_retriable`
+    pub fn retriable(&self) -> Vec<String> {
+        self.links
+            .values()
+            .filter(|link| link.retry == RetryOutcome::NotRetried && is_retriable(link.status))
+            .map(|link| link.url.clone())
+            .collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.links.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.links.is_empty()
+    }
+
+    /// The failed URLs with the pages referencing each, from the crawl manifest
+    pub fn report(&self, store: &DownloadStore) -> Vec<BrokenLink> {
+        self.links
+            .values()
+            .map(|link| BrokenLink {
+                referrers: store.referrers(&link.url).into_iter().map(String::from).collect(),
+                ..link.clone()
+            })
+            .collect()
+    }
+}
+
+/// Quote a CSV field when it holds a comma, quote or line break
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// CSV of the report, one row per URL with its referrers separated by spaces
+pub fn to_csv(links: &[BrokenLink]) -> String {
+    let mut csv = String::from("url,status,error,attempts,retry,referrers\n");
+    for link in links {
+        let fields = [
+            csv_field(&link.url),
+            link.status.map(|status| status.to_string()).unwrap_or_default(),
+            csv_field(&link.error),
+            link.attempts.to_string(),
+            link.retry.as_str().to_string(),
+            csv_field(&link.referrers.join(" ")),
+        ];
+        csv.push_str(&fields.join(","));
+        csv.push('\n');
+    }
+    csv
+}
+
+/// Write `broken-links.json` and `broken-links.csv` into the output directory
+pub fn save_report(output_dir: &Path, links: &[BrokenLink]) -> Result<()> {
+    let json_path = output_dir.join(JSON_FILE_NAME);
+    fs::write(&json_path, serde_json::to_string_pretty(links)?)
+        .with_context(|| format!("Failed to write broken-link report: {:?}", json_path))?;
+    let csv_path = output_dir.join(CSV_FILE_NAME);
+    fs::write(&csv_path, to_csv(links)).with_context(|| format!("Failed to write broken-link report: {:?}", csv_path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_retriable() {
+        assert!(is_retriable(None));
+        assert!(is_retriable(Some(429)));
+        assert!(is_retriable(Some(503)));
+        assert!(!is_retriable(Some(404)));
+        assert!(!is_retriable(Some(410)));
+        assert!(!is_retriable(Some(403)));
+    }
+
+    #[test]
+    fn test_record_and_retry() {
+        let mut links = BrokenLinks::default();
+        links.record("https://example.com/missing", Some(404), "HTTP 404 Not Found");
+        links.record("https://example.com/flaky.css", Some(503), "HTTP 503 Service Unavailable");
+        links.record("https://example.com/slow.jpg", None, "operation timed out");
+        assert_eq!(links.len(), 3);
+        assert_eq!(links.retriable(), vec!["https://example.com/flaky.css", "https://example.com/slow.jpg"]);
+
+        links.set_retry("https://example.com/flaky.css", RetryOutcome::Recovered);
+        links.record("https://example.com/slow.jpg", Some(504), "HTTP 504 Gateway Timeout");
+        links.set_retry("https://example.com/slow.jpg", RetryOutcome::StillFailing);
+        assert_eq!(links.attempts("https://example.com/slow.jpg"), 2);
+        assert!(links.retriable().is_empty());
+
+        let mut store = DownloadStore::default();
+        store.add_referrer("https://example.com/missing", "https://example.com/");
+        store.add_referrer("https://example.com/missing", "https://example.com/about/");
+        let report = links.report(&store);
+        assert_eq!(report[0].retry, RetryOutcome::Recovered);
+        assert_eq!(report[1].url, "https://example.com/missing");
+        assert_eq!(report[1].referrers, vec!["https://example.com/", "https://example.com/about/"]);
+        assert_eq!(report[1].retry, RetryOutcome::NotRetried);
+        assert_eq!(report[2].status, Some(504));
+        assert_eq!(report[2].retry, RetryOutcome::StillFailing);
+    }
+
+    #[test]
+    fn test_to_csv() {
+        let links = vec![BrokenLink {
+            url: "https://example.com/a,b".to_string(),
+            status: None,
+            error: "error sending request: \"connection reset\"".to_string(),
+            attempts: 2,
+            retry: RetryOutcome::StillFailing,
+            referrers: vec!["https://example.com/".to_string(), "https://example.com/blog/".to_string()],
+        }];
+        assert_eq!(
+            to_csv(&links),
+            concat!(
+                "url,status,error,attempts,retry,referrers\n",
+                "\"https://example.com/a,b\",,\"error sending request: \"\"connection reset\"\"\",2,still_failing,https://example.com/ https://example.com/blog/\n",
+            )
+        );
+    }
+}
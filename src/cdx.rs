@@ -0,0 +1,171 @@
+use anyhow::{Result, Context};
+use serde::Serialize;
+use std::fs;
+use std::path::Path;
+use std::time::UNIX_EPOCH;
+use url::{Host, Url};
+
+use crate::archive;
+use crate::store::{self, DownloadStore};
+
+/// File name of the CDXJ index written into the output directory by `--cdx`
+pub const CDX_FILE_NAME: &str = "index.cdxj";
+
+/// JSON block of a CDXJ line, in the field names replay tools (pywb,
+/// OutbackCDX) read
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+struct CdxFields<'a> {
+    url: &'a str,
+    mime: &'a str,
+    status: &'a str,
+    digest: String,
+    length: u64,
+    /// Saved file, relative to the mirror's output directory
+    filename: &'a str,
+}
+
+/// Sort-friendly URL key (SURT): host labels reversed and comma separated,
+/// `www.` and default ports dropped, query parameters sorted, all lowercase.
+/// `https://www.Example.com/Blog/?b=2&a=1` becomes `com,example)/blog/?a=1&b=2`.
+pub fn surt(url: &str) -> String {
+    let Ok(parsed) = Url::parse(url) else {
+        return url.to_lowercase();
+    };
+    let mut key = match parsed.host() {
+        Some(Host::Domain(domain)) => {
+            let domain = domain.strip_prefix("www.").unwrap_or(domain);
+            domain.split('.').rev().collect::<Vec<_>>().join(",")
+        }
+        Some(host) => host.to_string(),
+        None => String::new(),
+    };
+    if let Some(port) = parsed.port() {
+        key.push_str(&format!(":{}", port));
+    }
+    key.push(')');
+    key.push_str(parsed.path());
+    if let Some(query) = parsed.query() {
+        let mut params: Vec<&str> = query.split('&').filter(|param| !param.is_empty()).collect();
+        params.sort_unstable();
+        key.push('?');
+        key.push_str(&params.join("&"));
+    }
+    key.to_lowercase()
+}
+
+/// 14-digit `YYYYMMDDhhmmss` capture timestamp of a Unix time, in UTC
+pub fn timestamp(seconds: u64) -> String {
+    let (year, month, day) = archive::civil_date(seconds);
+    let time = seconds % 86_400;
+    format!("{:04}{:02}{:02}{:02}{:02}{:02}", year, month, day, time / 3_600, (time % 3_600) / 60, time % 60)
+}
+
+/// Build the CDXJ index of a mirror: one line per downloaded URL, keyed by
+/// SURT and the file's modification time, pointing at the saved file. Lines
+/// are sorted, as replay tools binary-search the index.
+pub fn build_index(output_dir: &Path) -> Result<Vec<String>> {
+    let store = DownloadStore::load(output_dir)?;
+    let mut lines = Vec::new();
+    for (url, entry) in &store.entries {
+        let path = output_dir.join(&entry.local_path);
+        let Ok(content) = fs::read(&path) else {
+            continue;
+        };
+        let modified = fs::metadata(&path)?
+            .modified()
+            .ok()
+            .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+            .map(|elapsed| elapsed.as_secs())
+            .unwrap_or(0);
+        let mime = entry.content_type.as_deref()
+            .and_then(|content_type| content_type.split(';').next())
+            .map(str::trim)
+            .filter(|mime| !mime.is_empty())
+            .map(str::to_string)
+            .unwrap_or_else(|| mime_guess::from_path(&entry.local_path).first_or_octet_stream().essence_str().to_string());
+        let fields = CdxFields {
+            url,
+            mime: &mime,
+            status: "200",
+            digest: format!("sha256:{}", store::content_hash(&content)),
+            length: content.len() as u64,
+            filename: &entry.local_path,
+        };
+        lines.push(format!("{} {} {}", surt(url), timestamp(modified), serde_json::to_string(&fields)?));
+    }
+    lines.sort();
+    Ok(lines)
+}
+
+/// Write the CDXJ index of the mirror in `output_dir` to `index_path` and
+/// return the number of records
+pub fn write_index(output_dir: &Path, index_path: &Path) -> Result<usize> {
+    let lines = build_index(output_dir)?;
+    if let Some(parent) = index_path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let mut data = lines.join("\n");
+    if !lines.is_empty() {
+        data.push('\n');
+    }
+    fs::write(index_path, data)
+        .with_context(|| format!("Failed to write CDX index: {:?}", index_path))?;
+    Ok(lines.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::write;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_surt() {
+        assert_eq!(surt("https://www.Example.com/Blog/?b=2&a=1#top"), "com,example)/blog/?a=1&b=2");
+        assert_eq!(surt("http://docs.example.com:8080/"), "com,example,docs:8080)/");
+        assert_eq!(surt("https://example.com:443/a.css"), "com,example)/a.css");
+        assert_eq!(surt("http://127.0.0.1/"), "127.0.0.1)/");
+    }
+
+    #[test]
+    fn test_timestamp() {
+        assert_eq!(timestamp(0), "19700101000000");
+        assert_eq!(timestamp(1_700_000_000), "20231114221320");
+    }
+
+    #[test]
+    fn test_write_index() {
+        let temp_dir = tempdir().unwrap();
+        let dir = temp_dir.path();
+        write(dir, "index.html", "<p>Home</p>");
+        write(dir, "css/site.css", "body {}");
+        let mut store = DownloadStore::default();
+        store.record_download("https://example.com/", "index.html", Some("text/html; charset=utf-8"), 11);
+        store.record_download("https://example.com/css/site.css", "css/site.css", None, 7);
+        store.record_download("https://example.com/gone.png", "gone.png", Some("image/png"), 3);
+        store.save(dir).unwrap();
+
+        let index_path = dir.join(CDX_FILE_NAME);
+        assert_eq!(write_index(dir, &index_path).unwrap(), 2);
+        let index = fs::read_to_string(&index_path).unwrap();
+        let lines: Vec<&str> = index.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].starts_with("com,example)/ "));
+        assert!(lines[1].starts_with("com,example)/css/site.css "));
+
+        let (key, rest) = lines[0].split_once(' ').unwrap();
+        let (time, json) = rest.split_once(' ').unwrap();
+        assert_eq!(key, "com,example)/");
+        assert_eq!(time.len(), 14);
+        let fields: serde_json::Value = serde_json::from_str(json).unwrap();
+        assert_eq!(fields["url"], "https://example.com/");
+        assert_eq!(fields["mime"], "text/html");
+        assert_eq!(fields["status"], "200");
+        assert_eq!(fields["length"], 11);
+        assert_eq!(fields["filename"], "index.html");
+        assert_eq!(fields["digest"], format!("sha256:{}", store::content_hash(b"<p>Home</p>")));
+
+        let css: serde_json::Value = serde_json::from_str(lines[1].splitn(3, ' ').nth(2).unwrap()).unwrap();
+        assert_eq!(css["mime"], "text/css");
+    }
+}
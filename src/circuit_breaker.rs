@@ -0,0 +1,173 @@
+use std::collections::HashMap;
+use std::fmt;
+
+/// Broad category of a failed request, used for reporting and circuit breaking
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FailureKind {
+    Dns,
+    Tls,
+    Timeout,
+    ConnectionReset,
+    ConnectionRefused,
+    Other,
+}
+
+impl fmt::Display for FailureKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            FailureKind::Dns => "DNS",
+            FailureKind::Tls => "TLS",
+            FailureKind::Timeout => "timeout",
+            FailureKind::ConnectionReset => "connection reset",
+            FailureKind::ConnectionRefused => "connection refused",
+            FailureKind::Other => "network",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FailureKind {
+    /// Classify a request error from its timeout flag and error chain
+    pub fn classify(error: &reqwest::Error) -> FailureKind {
+        if error.is_timeout() {
+            return FailureKind::Timeout;
+        }
+
+        let mut messages = Vec::new();
+        let mut source: Option<&dyn std::error::Error> = Some(error);
+        while let Some(err) = source {
+            messages.push(err.to_string());
+            source = err.source();
+        }
+        Self::classify_message(&messages.join(": "))
+    }
+
+    /// Classify an error message (including its causes)
+    pub fn classify_message(message: &str) -> FailureKind {
+        let message = message.to_lowercase();
+        if message.contains("dns error") || message.contains("failed to lookup address") || message.contains("name or service not known") {
+            FailureKind::Dns
+        } else if message.contains("certificate") || message.contains("tls") || message.contains("ssl") || message.contains("handshake") {
+            FailureKind::Tls
+        } else if message.contains("timed out") || message.contains("timeout") {
+            FailureKind::Timeout
+        } else if message.contains("connection reset") || message.contains("broken pipe") || message.contains("connection closed") {
+            FailureKind::ConnectionReset
+        } else if message.contains("connection refused") {
+            FailureKind::ConnectionRefused
+        } else {
+            FailureKind::Other
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+struct HostState {
+    consecutive_failures: usize,
+    last_failure: Option<FailureKind>,
+    open: bool,
+}
+
+/// Per-host circuit breaker: after `threshold` consecutive connection
+/// failures a host is considered down and its remaining requests are skipped
+#[derive(Debug, Clone)]
+pub struct CircuitBreaker {
+    threshold: usize,
+    hosts: HashMap<String, HostState>,
+}
+
+impl CircuitBreaker {
+    /// A threshold of 0 disables the breaker
+    pub fn new(threshold: usize) -> Self {
+        Self {
+            threshold,
+            hosts: HashMap::new(),
+        }
+    }
+
+    /// Why requests to `host` are being skipped, if its circuit is open
+    pub fn open_reason(&self, host: &str) -> Option<String> {
+        let state = self.hosts.get(host).filter(|state| state.open)?;
+        Some(format!(
+            "circuit breaker open for {} after {} consecutive {} failures",
+            host,
+            state.consecutive_failures,
+            state.last_failure.unwrap_or(FailureKind::Other)
+        ))
+    }
+
+    pub fn record_success(&mut self, host: &str) {
+        self.hosts.remove(host);
+    }
+
+    /// Record a failure, returning `true` if this failure tripped the breaker
+    pub fn record_failure(&mut self, host: &str, kind: FailureKind) -> bool {
+        let state = self.hosts.entry(host.to_string()).or_default();
+        state.consecutive_failures += 1;
+        state.last_failure = Some(kind);
+        if self.threshold > 0 && !state.open && state.consecutive_failures >= self.threshold {
+            state.open = true;
+            return true;
+        }
+        false
+    }
+
+    /// Hosts whose circuit is currently open
+    pub fn open_hosts(&self) -> Vec<&str> {
+        let mut hosts: Vec<&str> = self
+            .hosts
+            .iter()
+            .filter(|(_, state)| state.open)
+            .map(|(host, _)| host.as_str())
+            .collect();
+        hosts.sort_unstable();
+        hosts
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classify_message() {
+        assert_eq!(FailureKind::classify_message("error sending request: dns error: failed to lookup address information"), FailureKind::Dns);
+        assert_eq!(FailureKind::classify_message("invalid peer certificate: UnknownIssuer"), FailureKind::Tls);
+        assert_eq!(FailureKind::classify_message("operation timed out"), FailureKind::Timeout);
+        assert_eq!(FailureKind::classify_message("Connection reset by peer (os error 104)"), FailureKind::ConnectionReset);
+        assert_eq!(FailureKind::classify_message("tcp connect error: Connection refused (os error 111)"), FailureKind::ConnectionRefused);
+        assert_eq!(FailureKind::classify_message("something else"), FailureKind::Other);
+    }
+
+    #[test]
+    fn test_breaker_trips_after_consecutive_failures() {
+        let mut breaker = CircuitBreaker::new(3);
+        assert!(!breaker.record_failure("cdn.example.com", FailureKind::Timeout));
+        assert!(!breaker.record_failure("cdn.example.com", FailureKind::Timeout));
+        assert!(breaker.open_reason("cdn.example.com").is_none());
+        assert!(breaker.record_failure("cdn.example.com", FailureKind::Dns));
+
+        let reason = breaker.open_reason("cdn.example.com").unwrap();
+        assert!(reason.contains("3 consecutive DNS failures"));
+        assert_eq!(breaker.open_hosts(), vec!["cdn.example.com"]);
+        assert!(breaker.open_reason("example.com").is_none());
+    }
+
+    #[test]
+    fn test_success_resets_failures() {
+        let mut breaker = CircuitBreaker::new(2);
+        breaker.record_failure("example.com", FailureKind::ConnectionReset);
+        breaker.record_success("example.com");
+        assert!(!breaker.record_failure("example.com", FailureKind::ConnectionReset));
+        assert!(breaker.open_reason("example.com").is_none());
+    }
+
+    #[test]
+    fn test_zero_threshold_disables_breaker() {
+        let mut breaker = CircuitBreaker::new(0);
+        for _ in 0..10 {
+            assert!(!breaker.record_failure("example.com", FailureKind::Timeout));
+        }
+        assert!(breaker.open_hosts().is_empty());
+    }
+}
@@ -1,4 +1,4 @@
-use clap::Parser;
+use clap::{Parser, Subcommand};
 use std::path::PathBuf;
 
 #[derive(Parser, Debug)]
@@ -13,7 +13,8 @@ pub struct MirrorCommand {
     #[arg(required = true)]
     pub url: String,
     
-    /// Output directory for the mirrored website
+    /// Output directory for the mirrored website, or s3://bucket/prefix to publish the
+    /// mirror to S3 (or an S3-compatible service) after the run
     #[arg(short, long, default_value = "./mirrored_site")]
     pub output_dir: PathBuf,
     
@@ -50,7 +51,7 @@ pub struct MirrorCommand {
             #[arg(long)]
             pub full_mirror: bool,
 
-            /// Mirror only specific resource types (comma-separated: images,css,js,html)
+            /// Mirror only specific resource types (comma-separated: images,css,js,html,video,audio,fonts)
             /// Examples: --only-resources images,css or --only-resources js
             #[arg(long, value_delimiter = ',')]
             pub only_resources: Option<Vec<String>>,
@@ -58,8 +59,729 @@ pub struct MirrorCommand {
             /// Convert JPEG/PNG images to WebP format for better compression
             #[arg(long)]
             pub convert_to_webp: bool,
+
+            /// Image formats to convert to WebP (comma-separated file extensions, default: jpg,jpeg,png)
+            /// Formats not listed are passed through unchanged. Implies --convert-to-webp
+            #[arg(long, value_delimiter = ',')]
+            pub convert_formats: Option<Vec<String>>,
+
+            /// Embed the original ICC color profile in converted WebP images
+            /// Prevents color shifts on wide-gamut (e.g. Display-P3) photos
+            #[arg(long)]
+            pub preserve_icc: bool,
+
+            /// Re-encode JPEG images as progressive JPEGs at the given quality (1-100, default 85)
+            /// Alternative to WebP conversion that keeps the original format
+            #[arg(long, value_name = "QUALITY", num_args = 0..=1, default_missing_value = "85",
+                  value_parser = clap::value_parser!(u8).range(1..=100))]
+            pub optimize_jpeg: Option<u8>,
+
+            /// Reduce PNG images to an indexed palette with at most this many colors (2-256, default 256)
+            /// Keeps PNG output for screenshots and diagrams while shrinking files
+            #[arg(long, value_name = "COLORS", num_args = 0..=1, default_missing_value = "256",
+                  value_parser = clap::value_parser!(u16).range(2..=256))]
+            pub quantize_png: Option<u16>,
+
+            /// Write an index.html gallery with thumbnails linking to every downloaded image
+            /// Only used together with --only-resources images
+            #[arg(long)]
+            pub gallery: bool,
+
+            /// After the crawl, report saved assets that no page or stylesheet links to
+            #[arg(long)]
+            pub report_dead_assets: bool,
+
+            /// Delete the assets found by --report-dead-assets (implies it)
+            #[arg(long)]
+            pub delete_dead_assets: bool,
+
+            /// After the crawl, delete files in the output directory that are no longer
+            /// reachable from the mirrored pages (left over from earlier runs)
+            #[arg(long)]
+            pub prune: bool,
+
+            /// Don't archive pages whose <meta name="robots"> contains noarchive or nosnippet
+            /// Skipped pages are listed in the crawl manifest and the end-of-crawl summary
+            #[arg(long)]
+            pub respect_noarchive: bool,
+
+            /// Command that prints a fresh bearer token, run when a request returns 401
+            /// The failed request is re-issued with the new token (e.g. --token-command "./get-token.sh")
+            #[arg(long, value_name = "COMMAND")]
+            pub token_command: Option<String>,
+
+            /// Attributes holding lazy-loaded image sources, downloaded and rewritten like src
+            /// (comma-separated, default: data-src,data-srcset,data-original,data-lazy,data-lazy-src,data-lazy-srcset)
+            #[arg(long, value_delimiter = ',')]
+            pub lazy_attrs: Option<Vec<String>>,
+
+            /// Also copy rewritten lazy-loaded sources into src/srcset so images show without JavaScript
+            #[arg(long)]
+            pub lazy_copy_src: bool,
+
+            /// URL requested periodically during the crawl so a session-cookie login doesn't expire
+            #[arg(long, value_name = "URL")]
+            pub keep_alive_url: Option<String>,
+
+            /// Seconds between keep-alive requests
+            #[arg(long, value_name = "SECONDS", default_value = "300")]
+            pub keep_alive_interval: u64,
+
+            /// Skip a host's remaining resources after this many consecutive connection
+            /// failures (DNS, TLS, timeout, reset); 0 disables the circuit breaker
+            #[arg(long, value_name = "FAILURES", default_value = "5")]
+            pub circuit_breaker_threshold: usize,
+
+            /// Print which rules would allow or block this URL with the given options, then exit
+            #[arg(long, value_name = "URL")]
+            pub explain: Option<String>,
+
+            /// On update runs, revalidate previously downloaded files of at least this many MB
+            /// with Range + If-Range, fetching only a missing tail or the file if it changed
+            #[arg(long, value_name = "MB")]
+            pub differential_min_mb: Option<u64>,
+
+            /// Rewrite <link rel="canonical"> and hreflang alternate links to local paths
+            #[arg(long, conflicts_with = "strip_canonical")]
+            pub rewrite_canonical: bool,
+
+            /// Remove <link rel="canonical"> and hreflang alternate links from saved pages
+            #[arg(long)]
+            pub strip_canonical: bool,
+
+            /// Subresource integrity attributes on saved stylesheets and scripts: keep, strip
+            /// (also removes crossorigin), or recompute the hash over the local file
+            #[arg(long, value_name = "MODE", default_value = "recompute",
+                  value_parser = ["keep", "strip", "recompute"])]
+            pub integrity: String,
+
+            /// Search HTML comments for references: ignore, conditional (IE conditional
+            /// comments) or all (also commented-out assets)
+            #[arg(long, value_name = "MODE", default_value = "ignore",
+                  value_parser = ["ignore", "conditional", "all"])]
+            pub comment_resources: String,
+
+            /// Save non-UTF-8 pages (Shift-JIS, GBK, ISO-8859-1, ...) in their original encoding
+            /// instead of transcoding them to UTF-8
+            #[arg(long)]
+            pub preserve_encoding: bool,
+
+            /// Make each saved page declare the encoding it was written in: add or correct
+            /// <meta charset> and drop conflicting http-equiv Content-Type tags (for file:// viewing)
+            #[arg(long)]
+            pub fix_charset: bool,
+
+            /// Crawl rules for a CMS: wordpress skips wp-json/oEmbed/feed endpoints, removes the
+            /// emoji scripts and adds common lazy-load plugin attributes
+            /// shopify skips cart/checkout/variant URLs and keeps only --image-variants product image sizes
+            #[arg(long, value_name = "NAME", value_parser = ["wordpress", "shopify"])]
+            pub preset: Option<String>,
+
+            /// Widths of Shopify CDN image size variants (_1024x1024, ?width=) to download
+            /// (comma-separated); srcset candidates are collapsed onto them (default with --preset shopify: 480,1024)
+            #[arg(long, value_name = "WIDTHS", value_delimiter = ',')]
+            pub image_variants: Option<Vec<u32>>,
+
+            /// Replace these providers' iframes with a static thumbnail linking to the original
+            /// (comma-separated): youtube, vimeo, twitter, maps or all
+            #[arg(long, value_name = "PROVIDERS", value_delimiter = ',')]
+            pub embed_placeholder: Vec<String>,
+
+            /// Fetch each placeholder embed's title and thumbnail from the provider's oEmbed
+            /// endpoint (or the one its page advertises) instead of showing a plain box
+            #[arg(long, requires = "embed_placeholder")]
+            pub oembed: bool,
+
+            /// Rewrite links to mirrored files and all absolute URLs on the original host to this
+            /// host (e.g. new.example.org) instead of making them relative, for domain migrations
+            #[arg(long, value_name = "HOST")]
+            pub rewrite_host: Option<String>,
+
+            /// Write links to mirrored files as root-relative paths under this prefix
+            /// (e.g. /archive/site1/), for mirrors served from a subpath of another site
+            #[arg(long, value_name = "PREFIX")]
+            pub link_base: Option<String>,
+
+            /// Remove utm_* and click-id (fbclid, gclid, ...) query parameters from URLs so
+            /// tracking variants of a page are crawled and saved once
+            #[arg(long)]
+            pub strip_tracking_params: bool,
+
+            /// Leave a meta-refresh page at the old path of every redirected page
+            /// (redirect chains are always recorded in redirects.json)
+            #[arg(long)]
+            pub redirect_stubs: bool,
+
+            /// Save CSS/JS/images from other hosts under _external/<host>/ instead of
+            /// merging them into the site's own tree, where same-named files overwrite each other
+            #[arg(long)]
+            pub external_host_dirs: bool,
+
+            /// Add a hash suffix to file and directory names with uppercase letters so URLs
+            /// that differ only in case don't overwrite each other on Windows/macOS
+            #[arg(long)]
+            pub case_insensitive_names: bool,
+
+            /// After the crawl, check that every local link in saved pages and stylesheets
+            /// points to a saved file and write validation-report.json
+            #[arg(long)]
+            pub validate: bool,
+
+            /// Like --validate, but fail the run if any local reference is missing
+            #[arg(long)]
+            pub validate_strict: bool,
+
+            /// Send a HEAD request to every link to a page that isn't mirrored and list
+            /// the dead ones in validation-report.json (runs --validate)
+            #[arg(long)]
+            pub check_external_links: bool,
+
+            /// Command rendering a saved page to PNG for a visual diff against the previous run,
+            /// with {url} (file:// URL) and {output} placeholders
+            /// (e.g. "chromium --headless --window-size=1280,2000 --screenshot={output} {url}")
+            #[arg(long, value_name = "COMMAND")]
+            pub screenshot_command: Option<String>,
+
+            /// Percentage of changed pixels above which the visual diff flags a page
+            #[arg(long, value_name = "PERCENT", default_value = "1.0")]
+            pub visual_diff_threshold: f64,
+
+            /// Diff each saved page's visible text against the previous run into the same
+            /// output directory and write the changed paragraphs to text-diff.html
+            #[arg(long)]
+            pub text_diff: bool,
+
+            /// Fail the run when a page whose URL path matches PATTERN changed since the previous
+            /// run (e.g. "/legal/", "/**/terms"); repeatable, implies --text-diff
+            #[arg(long = "alert-on", value_name = "PATTERN")]
+            pub alert_on: Vec<String>,
+
+            /// URL receiving a JSON POST of the changes matching --alert-on
+            #[arg(long, value_name = "URL")]
+            pub alert_webhook: Option<String>,
+
+            /// After the crawl, build a full-text index of the saved pages in .search_index/
+            /// for `website-mirror search` (requires the `search` feature)
+            #[arg(long)]
+            pub search_index: bool,
+
+            /// Stop starting new downloads once this much has been written to the output
+            /// directory (e.g. 500M, 2G); in-flight downloads finish and the rest are reported
+            #[arg(long, value_name = "SIZE", value_parser = parse_size)]
+            pub max_total_size: Option<u64>,
+
+            /// Skip resources larger than this (e.g. 50M), judged by Content-Length before the body
+            /// is downloaded, and list them in skipped-too-large.json
+            #[arg(long, value_name = "SIZE", value_parser = parse_size)]
+            pub max_file_size: Option<u64>,
+
+            /// Pages claiming text/html that are larger than this (e.g. 20M) are saved as-is
+            /// without being parsed, streamed to disk instead of held in memory
+            #[arg(long, value_name = "SIZE", value_parser = parse_size)]
+            pub max_html_size: Option<u64>,
+
+            /// Limit write throughput to the output directory, per second (e.g. 10M), for
+            /// mirroring onto SMB/NFS shares; files are written whole via a temporary .part file
+            #[arg(long, value_name = "SIZE", value_parser = parse_size)]
+            pub io_throttle: Option<u64>,
+
+            /// Files written at once when writes are throttled (default: 4)
+            #[arg(long, value_name = "N")]
+            pub io_max_open_files: Option<usize>,
+
+            /// Redact email addresses and phone numbers from the text and readable attributes
+            /// of saved pages, keeping the markup intact
+            #[arg(long)]
+            pub scrub_pii: bool,
+
+            /// Extra regex to redact from saved pages (repeatable, implies --scrub-pii)
+            #[arg(long, value_name = "REGEX")]
+            pub pii_pattern: Vec<String>,
+
+            /// Process at most this many HTML pages regardless of depth; links to the pages
+            /// left out point at the live site
+            #[arg(long, value_name = "N", value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..))]
+            pub max_pages: Option<usize>,
+
+            /// Before crawling newly discovered pages, list them in batches and ask which to
+            /// allow or deny
+            #[arg(long)]
+            pub review: bool,
+
+            /// Review pages through an allowlist file edited while the crawl runs
+            /// (`+ <url prefix>` allows, `- <url prefix>` denies; implies --review)
+            #[arg(long, value_name = "FILE")]
+            pub review_file: Option<PathBuf>,
+
+            /// Write rate-report.json with the request rates per host over the crawl and
+            /// whether each host's robots.txt Crawl-delay was honored
+            #[arg(long)]
+            pub rate_report: bool,
+
+            /// Also write the crawl report (report.json) as a readable report.html page
+            #[arg(long)]
+            pub html_report: bool,
+
+            /// Check every page for missing alt text, skipped heading levels and
+            /// low-contrast inline colors, listing problems in the crawl report
+            #[arg(long)]
+            pub a11y_audit: bool,
+
+            /// After the run, write a CDXJ index of every saved URL (index.cdxj) mapping
+            /// URL and capture time to the saved file, for web-archive replay tools
+            #[arg(long)]
+            pub cdx: bool,
+
+            /// After the run, write every page -> page and page -> asset reference to a
+            /// DOT, GraphML or JSON file (by extension), e.g. links.dot
+            #[arg(long, value_name = "FILE")]
+            pub link_graph: Option<PathBuf>,
+
+            /// Don't retry URLs that failed with a network error, 429 or 5xx once the
+            /// crawl finishes; broken-links.json still lists every failure
+            #[arg(long)]
+            pub no_retry_failed: bool,
+
+            /// Record every HTTP response of the crawl into DIR/fixtures.json, to be
+            /// replayed later with --replay-fixtures
+            #[arg(long, value_name = "DIR", conflicts_with = "replay_fixtures")]
+            pub record_fixtures: Option<PathBuf>,
+
+            /// Crawl against responses recorded with --record-fixtures, served by a
+            /// local stub server instead of the network
+            #[arg(long, value_name = "DIR")]
+            pub replay_fixtures: Option<PathBuf>,
+
+            /// Format of --log-file: jsonl writes one JSON event per line (page_start,
+            /// resource_downloaded, resource_failed, rewrite_done, crawl_done)
+            #[arg(long, value_parser = ["text", "jsonl"], default_value = "text")]
+            pub log_format: String,
+
+            /// File to write crawl events to, with --log-format jsonl
+            #[arg(long, value_name = "FILE")]
+            pub log_file: Option<PathBuf>,
+
+            /// After the run, package the output directory as a single archive
+            /// (zip or tar.gz, by extension), e.g. site.zip
+            #[arg(long, value_name = "FILE")]
+            pub archive_output: Option<PathBuf>,
+
+            /// After the run, also write every page as one self-contained HTML file
+            /// (stylesheets, scripts and images inlined) into this directory
+            #[arg(long, value_name = "DIR")]
+            pub single_file: Option<PathBuf>,
+
+            /// Endpoint of an S3-compatible service (MinIO, R2, ...) for an s3:// output,
+            /// e.g. http://localhost:9000 (default: AWS, or AWS_ENDPOINT_URL)
+            #[arg(long, value_name = "URL")]
+            pub s3_endpoint: Option<String>,
+
+            /// Local directory an s3:// output is mirrored into before it is published
+            /// (default: website-mirror/s3/<bucket>/<prefix> in the temp directory)
+            #[arg(long, value_name = "DIR")]
+            pub s3_staging_dir: Option<PathBuf>,
+
+            /// Write a unified diff of every saved page before and after link rewriting
+            /// to _diffs/<page>.diff, to audit what was changed
+            #[arg(long)]
+            pub rewrite_diff: bool,
+
+            /// Only crawl pages under this path prefix of the target site (repeatable);
+            /// assets those pages use are still downloaded from anywhere
+            #[arg(long, value_name = "PATH")]
+            pub include_path: Vec<String>,
+
+            /// Other hosts whose pages are crawled as part of the site (comma-separated,
+            /// e.g. docs.example.org,blog.example.org), with links between the hosts rewritten
+            #[arg(long, value_delimiter = ',')]
+            pub crawl_domains: Vec<String>,
+
+            /// Also crawl pages on subdomains of the site's registrable domain
+            /// (shop.example.com when mirroring example.com). Without it only the
+            /// exact host of the URL is crawled, which earlier versions didn't enforce
+            #[arg(long, overrides_with = "no_subdomains")]
+            pub span_subdomains: bool,
+
+            /// Only crawl pages on the exact host of the URL (the default)
+            #[arg(long, overrides_with = "span_subdomains")]
+            pub no_subdomains: bool,
+
+            /// Another name of the site's host whose links are treated as the site's own
+            /// (repeatable); www and apex hosts are always treated as one site
+            #[arg(long, value_name = "HOST")]
+            pub alias_host: Vec<String>,
+
+            /// Domains nothing is crawled or downloaded from, subdomains included
+            /// (comma-separated, e.g. doubleclick.net,googletagmanager.com)
+            #[arg(long, value_delimiter = ',')]
+            pub exclude_domains: Vec<String>,
+
+            /// Open connections to hosts pages announce with <link rel="preconnect"> or
+            /// rel="dns-prefetch" before their assets are requested
+            #[arg(long)]
+            pub preconnect: bool,
+
+            /// Also download data files, manifests and other non-media resources from
+            /// hosts announced with preconnect/dns-prefetch hints
+            #[arg(long)]
+            pub trust_hinted_hosts: bool,
+
+            /// Print the settings the run would use, after presets and --full-mirror, then exit
+            #[arg(long)]
+            pub print_config: bool,
+
+            /// JSON file of options to use, e.g. {"max-pages": 100, "exclude-domains": ["ads.example.net"]};
+            /// WEBSITE_MIRROR_* environment variables override it and command-line options override both
+            #[arg(long, value_name = "FILE")]
+            pub config: Option<PathBuf>,
+
+            /// Condition on the crawl's outcome that fails the run when violated (repeatable),
+            /// e.g. "pages>=100", "failures==0", "broken_links==0" (runs --validate)
+            #[arg(long = "assert", value_name = "CONDITION")]
+            pub assertions: Vec<String>,
+
+            /// Save a subtree of the site to its own directory (repeatable), with links
+            /// between the directories rewritten, e.g. /docs/**=docs-mirror
+            #[arg(long, value_name = "PATH=DIR")]
+            pub split: Vec<String>,
+
+            /// Language of the progress and summary output: en, es or de (default: from
+            /// LC_ALL/LANG, else English)
+            #[arg(long, value_name = "LANG", value_parser = ["en", "es", "de"])]
+            pub lang: Option<String>,
+
+            /// Log more: -v adds per-request and per-file details, -vv everything
+            #[arg(short, long, action = clap::ArgAction::Count, conflicts_with = "quiet")]
+            pub verbose: u8,
+
+            /// Only log warnings and errors, without progress bars
+            #[arg(short, long)]
+            pub quiet: bool,
+
+            /// Print no colors (also when NO_COLOR is set or stdout isn't a terminal)
+            #[arg(long)]
+            pub no_color: bool,
+
+            /// Format of the summary printed when the run finishes: json prints one object
+            /// (url, output, pages, assets, bytes, failures, duration_seconds) on stdout
+            /// and sends all logging to stderr
+            #[arg(long, value_parser = ["text", "json"], default_value = "text")]
+            pub output_format: String,
 } 
 
+/// Byte count from a size like `2G`, `500M`, `64k` or `1048576` (binary units)
+pub fn parse_size(value: &str) -> Result<u64, String> {
+    let value = value.trim();
+    let digits_end = value.find(|c: char| !c.is_ascii_digit() && c != '.').unwrap_or(value.len());
+    let (number, unit) = value.split_at(digits_end);
+    let number: f64 = number.parse().map_err(|_| format!("invalid size: {}", value))?;
+    let multiplier: u64 = match unit.trim().to_ascii_uppercase().trim_end_matches("IB").trim_end_matches('B') {
+        "" => 1,
+        "K" => 1 << 10,
+        "M" => 1 << 20,
+        "G" => 1 << 30,
+        "T" => 1 << 40,
+        _ => return Err(format!("invalid size unit in {} (use K, M, G or T)", value)),
+    };
+    Ok((number * multiplier as f64) as u64)
+}
+
+/// `website-mirror search <dir> <query>`: query the index built with --search-index
+#[derive(Parser, Debug)]
+#[command(
+    name = "website-mirror search",
+    about = "Search the pages of a mirrored website"
+)]
+pub struct SearchCommand {
+    /// Output directory of a mirror made with --search-index
+    #[arg(required = true)]
+    pub dir: PathBuf,
+
+    /// Words to search for ("exact phrase", AND/OR, title:word)
+    #[arg(required = true)]
+    pub query: String,
+
+    /// Maximum number of results
+    #[arg(short = 'n', long, default_value = "20")]
+    pub limit: usize,
+
+    /// Print no colors (also when NO_COLOR is set or stdout isn't a terminal)
+    #[arg(long)]
+    pub no_color: bool,
+}
+
+/// `website-mirror export <format> ...`: repackage a mirrored snapshot for storage
+#[derive(Parser, Debug)]
+#[command(
+    name = "website-mirror export",
+    about = "Export a mirrored website for long-term storage"
+)]
+pub struct ExportCommand {
+    #[command(subcommand)]
+    pub format: ExportFormat,
+
+    /// Encrypt the output as it is written (site.zip.age, or site.pack.age and site.idx.age),
+    /// e.g. age:age1ql3z7hjy54pw3hyww5ayyfg7zqgvc7w3j2elw8zmrj2kg5sfn9aqmcac8p; compact and archive only
+    #[cfg(feature = "encryption")]
+    #[arg(long, global = true, value_name = "SCHEME:RECIPIENT")]
+    pub encrypt: Option<String>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum ExportFormat {
+    /// Repack the snapshot into a content-addressed pack file plus index (<PACK> with .idx),
+    /// storing identical files once; restore it with `website-mirror extract`
+    Compact {
+        /// Output directory of the mirror to pack
+        dir: PathBuf,
+
+        /// Pack file to write, e.g. site.pack
+        pack: PathBuf,
+    },
+    /// Package the mirror as a single zip or tar.gz file with an index of every
+    /// file (archive-index.json), e.g. to attach to a ticket
+    Archive {
+        /// Output directory of the mirror to package
+        dir: PathBuf,
+
+        /// Archive to write, e.g. site.zip or site.tar.gz
+        archive: PathBuf,
+
+        /// Archive format: zip or tar.gz (default: from the archive's extension)
+        #[arg(long, value_name = "FORMAT", value_parser = ["zip", "tar.gz", "tgz"])]
+        format: Option<String>,
+    },
+    /// Write every saved page as a self-contained .mhtml file (the page plus its
+    /// stylesheets, scripts, images and fonts as MIME multipart), e.g. for page evidence
+    Mhtml {
+        /// Output directory of the mirror to export
+        dir: PathBuf,
+
+        /// Directory to write the .mhtml files to, one per page at the page's path
+        out_dir: PathBuf,
+    },
+    /// Write every saved page as one self-contained HTML file, with its stylesheets
+    /// inlined into <style>, scripts into <script> and images as data: URIs
+    SingleFile {
+        /// Output directory of the mirror to export
+        dir: PathBuf,
+
+        /// Directory to write the pages to, each at its path in the mirror
+        out_dir: PathBuf,
+    },
+    /// Write the mirror as an EPUB book, pages in sitemap or navigation order with
+    /// their stylesheets and images, e.g. to read mirrored docs on an e-reader
+    Epub {
+        /// Output directory of the mirror to export
+        dir: PathBuf,
+
+        /// Book to write, e.g. docs.epub
+        epub: PathBuf,
+
+        /// Title of the book (default: the title of the first page)
+        #[arg(long)]
+        title: Option<String>,
+    },
+    /// Write a CDXJ index of the mirror (SURT key, capture time and the saved file of
+    /// every URL), e.g. to load the mirror into web-archive replay tooling
+    Cdx {
+        /// Output directory of the mirror to index
+        dir: PathBuf,
+
+        /// Index to write, e.g. index.cdxj; file names in it are relative to <DIR>
+        index: PathBuf,
+    },
+    /// Convert the mirror into the content and assets of a Hugo or Jekyll site, each
+    /// page a content file with front matter (title, date, original URL)
+    Ssg {
+        /// Output directory of the mirror to convert
+        dir: PathBuf,
+
+        /// Directory to write the site to
+        out_dir: PathBuf,
+
+        /// Static site generator to write for: hugo or jekyll
+        #[arg(long, value_name = "SSG", value_parser = ["hugo", "jekyll"])]
+        format: String,
+    },
+    /// Write one mirrored page as a single HTML file to paste into an email client:
+    /// stylesheets inlined into style attributes and images embedded as data: URIs
+    Email {
+        /// Output directory of the mirror to export from
+        dir: PathBuf,
+
+        /// URL of the page to export, as it was mirrored
+        url: String,
+
+        /// HTML file to write, e.g. newsletter.html
+        output: PathBuf,
+
+        /// Link images to the live site instead of embedding them, for clients
+        /// such as Gmail that block data: images
+        #[arg(long)]
+        remote_images: bool,
+    },
+}
+
+/// `website-mirror config check <url> [options]`: validate the options of a run
+#[derive(Parser, Debug)]
+#[command(
+    name = "website-mirror config",
+    about = "Inspect the configuration of a mirroring run"
+)]
+pub struct ConfigCommand {
+    #[command(subcommand)]
+    pub action: ConfigAction,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum ConfigAction {
+    /// Report invalid and conflicting options and print the effective settings,
+    /// without crawling; exits with an error when any option is invalid
+    Check(MirrorCommand),
+}
+
+/// `website-mirror extract <pack> <dir>`: restore a snapshot from `export compact`
+#[derive(Parser, Debug)]
+#[command(
+    name = "website-mirror extract",
+    about = "Restore a mirrored website from a pack file"
+)]
+pub struct ExtractCommand {
+    /// Pack file written by `export compact` (its .idx must sit next to it)
+    pub pack: PathBuf,
+
+    /// Directory to restore the snapshot into
+    pub dir: PathBuf,
+
+    /// age identity file (from age-keygen) to decrypt a .age pack with
+    #[cfg(feature = "encryption")]
+    #[arg(short, long, value_name = "FILE")]
+    pub identity: Option<PathBuf>,
+}
+
+/// `website-mirror diff <old> <new>`: compare two snapshots of a site
+#[derive(Parser, Debug)]
+#[command(
+    name = "website-mirror diff",
+    about = "Compare two mirrors of a website: added, removed and changed pages and assets"
+)]
+pub struct DiffCommand {
+    /// Output directory of the earlier mirror
+    pub old_dir: PathBuf,
+
+    /// Output directory of the later mirror
+    pub new_dir: PathBuf,
+
+    /// Most changed text blocks shown per page (0 hides them)
+    #[arg(long, value_name = "N", default_value = "10")]
+    pub text_blocks: usize,
+
+    /// Print no colors (also when NO_COLOR is set or stdout isn't a terminal)
+    #[arg(long)]
+    pub no_color: bool,
+}
+
+/// `website-mirror clean <dir>`: find files an earlier run left behind
+#[derive(Parser, Debug)]
+#[command(
+    name = "website-mirror clean",
+    about = "List (or delete) files in a mirror that no mirrored page leads to anymore"
+)]
+pub struct CleanCommand {
+    /// Output directory of a mirror
+    pub dir: PathBuf,
+
+    /// Delete the orphaned files instead of listing them
+    #[arg(long)]
+    pub delete: bool,
+
+    /// Prefix links to the mirror root were written with, when the mirror was made
+    /// with --rewrite-host or --link-base (e.g. /archive/site1/)
+    #[arg(long, value_name = "PREFIX")]
+    pub link_root: Option<String>,
+
+    /// Output language: en, es or de (default: from LC_ALL/LANG, else English)
+    #[arg(long, value_name = "LANG", value_parser = ["en", "es", "de"])]
+    pub lang: Option<String>,
+
+    /// Print no colors (also when NO_COLOR is set or stdout isn't a terminal)
+    #[arg(long)]
+    pub no_color: bool,
+}
+
+/// `website-mirror bench <dir>`: time the page pipeline over saved fixture pages
+#[derive(Parser, Debug)]
+#[command(
+    name = "website-mirror bench",
+    about = "Time the decode, parse, rewrite and convert stages of the page pipeline over saved pages"
+)]
+pub struct BenchCommand {
+    /// Directory of saved HTML pages to use as fixtures, e.g. a mirror's output
+    pub dir: PathBuf,
+
+    /// Times every page is run through the pipeline, after one warm-up pass
+    #[arg(long, default_value_t = 10, value_parser = clap::value_parser!(u32).range(1..))]
+    pub iterations: u32,
+
+    /// URL the fixtures are treated as served from
+    #[arg(long, value_name = "URL", default_value = "https://example.com/")]
+    pub base_url: String,
+
+    /// Also write the timings as JSON, e.g. to use as a later --baseline
+    #[arg(long, value_name = "FILE")]
+    pub output: Option<PathBuf>,
+
+    /// Compare with the timings of an earlier run (written with --output)
+    #[arg(long, value_name = "FILE")]
+    pub baseline: Option<PathBuf>,
+
+    /// With --baseline, fail when a stage's mean time per page grew by more than
+    /// this many percent
+    #[arg(long, value_name = "PERCENT", default_value_t = 20.0, requires = "baseline")]
+    pub max_regression: f64,
+}
+
+/// `website-mirror import-warc <file> -o <dir>`: build a browsable mirror from a WARC capture
+#[derive(Parser, Debug)]
+#[command(
+    name = "website-mirror import-warc",
+    about = "Replay the responses of a WARC file through the mirror pipeline into a browsable local copy"
+)]
+pub struct ImportWarcCommand {
+    /// WARC file to import (.warc or .warc.gz)
+    pub warc: PathBuf,
+
+    /// Output directory for the mirrored website
+    #[arg(short, long, default_value = "./mirrored_site")]
+    pub output_dir: PathBuf,
+
+    /// Start page of the mirror; defaults to the first HTML page in the capture.
+    /// Pages on other hosts are kept only as assets, like in a live crawl
+    #[arg(long)]
+    pub url: Option<String>,
+}
+
+/// `website-mirror verify <dir>`: check a finished mirror for broken references
+#[derive(Parser, Debug)]
+#[command(
+    name = "website-mirror verify",
+    about = "Check a mirrored website for missing files, dangling links and empty files"
+)]
+pub struct VerifyCommand {
+    /// Output directory of a mirror
+    pub dir: PathBuf,
+
+    /// Prefix links to the mirror root were written with, when the mirror was made
+    /// with --rewrite-host or --link-base (e.g. /archive/site1/)
+    #[arg(long, value_name = "PREFIX")]
+    pub link_root: Option<String>,
+
+    /// Output language: en, es or de (default: from LC_ALL/LANG, else English)
+    #[arg(long, value_name = "LANG", value_parser = ["en", "es", "de"])]
+    pub lang: Option<String>,
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -81,6 +803,21 @@ mod tests {
         assert_eq!(args.convert_to_webp, false);
     }
 
+    #[test]
+    fn test_parse_s3_output() {
+        let args = MirrorCommand::try_parse_from([
+            "website-mirror",
+            "https://example.com",
+            "-o", "s3://docs-site/mirror",
+            "--s3-endpoint", "http://localhost:9000",
+            "--s3-staging-dir", "./staging"
+        ]).unwrap();
+
+        assert_eq!(args.output_dir.to_string_lossy(), "s3://docs-site/mirror");
+        assert_eq!(args.s3_endpoint.as_deref(), Some("http://localhost:9000"));
+        assert_eq!(args.s3_staging_dir, Some(PathBuf::from("./staging")));
+    }
+
     #[test]
     fn test_parse_all_args() {
         let args = MirrorCommand::try_parse_from(&[
@@ -127,6 +864,227 @@ mod tests {
         assert_eq!(args.only_resources, Some(vec!["js".to_string()]));
     }
 
+    #[test]
+    fn test_parse_convert_formats() {
+        let args = MirrorCommand::try_parse_from([
+            "website-mirror",
+            "https://example.com",
+            "-o", "./output",
+            "--convert-formats", "jpg,png,gif"
+        ]).unwrap();
+        
+        assert_eq!(args.convert_formats, Some(vec!["jpg".to_string(), "png".to_string(), "gif".to_string()]));
+    }
+
+    #[test]
+    fn test_parse_optimize_jpeg() {
+        let args = MirrorCommand::try_parse_from([
+            "website-mirror",
+            "https://example.com",
+            "--optimize-jpeg"
+        ]).unwrap();
+        assert_eq!(args.optimize_jpeg, Some(85));
+        
+        let args = MirrorCommand::try_parse_from([
+            "website-mirror",
+            "https://example.com",
+            "--optimize-jpeg", "70"
+        ]).unwrap();
+        assert_eq!(args.optimize_jpeg, Some(70));
+    }
+
+    #[test]
+    fn test_parse_gallery() {
+        let args = MirrorCommand::try_parse_from([
+            "website-mirror",
+            "https://example.com",
+            "--only-resources", "images",
+            "--gallery"
+        ]).unwrap();
+        
+        assert!(args.gallery);
+        assert_eq!(args.only_resources, Some(vec!["images".to_string()]));
+    }
+
+    #[test]
+    fn test_parse_lazy_attrs() {
+        let args = MirrorCommand::try_parse_from([
+            "website-mirror",
+            "https://example.com",
+            "--lazy-attrs", "data-src,data-original",
+            "--lazy-copy-src"
+        ]).unwrap();
+        
+        assert_eq!(args.lazy_attrs, Some(vec!["data-src".to_string(), "data-original".to_string()]));
+        assert!(args.lazy_copy_src);
+    }
+
+    #[test]
+    fn test_parse_keep_alive() {
+        let args = MirrorCommand::try_parse_from([
+            "website-mirror",
+            "https://example.com",
+            "--keep-alive-url", "https://example.com/api/ping"
+        ]).unwrap();
+        
+        assert_eq!(args.keep_alive_url.as_deref(), Some("https://example.com/api/ping"));
+        assert_eq!(args.keep_alive_interval, 300);
+    }
+
+    #[test]
+    fn test_parse_dead_asset_flags() {
+        let args = MirrorCommand::try_parse_from([
+            "website-mirror",
+            "https://example.com",
+            "--delete-dead-assets"
+        ]).unwrap();
+        
+        assert!(args.delete_dead_assets);
+        assert!(!args.report_dead_assets);
+    }
+
+    #[test]
+    fn test_parse_canonical_flags() {
+        let args = MirrorCommand::try_parse_from([
+            "website-mirror",
+            "https://example.com",
+            "--rewrite-canonical"
+        ]).unwrap();
+        assert!(args.rewrite_canonical);
+        assert!(!args.strip_canonical);
+        
+        let result = MirrorCommand::try_parse_from([
+            "website-mirror",
+            "https://example.com",
+            "--rewrite-canonical",
+            "--strip-canonical"
+        ]);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_parse_preset() {
+        let args = MirrorCommand::try_parse_from([
+            "website-mirror",
+            "https://example.com",
+            "--preset", "wordpress"
+        ]).unwrap();
+        assert_eq!(args.preset.as_deref(), Some("wordpress"));
+        
+        let result = MirrorCommand::try_parse_from([
+            "website-mirror",
+            "https://example.com",
+            "--preset", "joomla"
+        ]);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_parse_embed_placeholder() {
+        let args = MirrorCommand::try_parse_from([
+            "website-mirror",
+            "https://example.com",
+            "--embed-placeholder", "youtube,maps"
+        ]).unwrap();
+        assert_eq!(args.embed_placeholder, vec!["youtube", "maps"]);
+        assert!(!args.oembed);
+
+        let result = MirrorCommand::try_parse_from([
+            "website-mirror",
+            "https://example.com",
+            "--oembed"
+        ]);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_parse_link_graph() {
+        let args = MirrorCommand::try_parse_from([
+            "website-mirror",
+            "https://example.com",
+            "--link-graph", "links.graphml"
+        ]).unwrap();
+        assert_eq!(args.link_graph, Some(PathBuf::from("links.graphml")));
+    }
+
+    #[test]
+    fn test_parse_no_retry_failed() {
+        let args = MirrorCommand::try_parse_from([
+            "website-mirror",
+            "https://example.com",
+            "--no-retry-failed"
+        ]).unwrap();
+        assert!(args.no_retry_failed);
+    }
+
+    #[test]
+    fn test_parse_log_format() {
+        let args = MirrorCommand::try_parse_from([
+            "website-mirror",
+            "https://example.com",
+            "--log-format", "jsonl",
+            "--log-file", "events.jsonl"
+        ]).unwrap();
+        assert_eq!(args.log_format, "jsonl");
+        assert_eq!(args.log_file, Some(PathBuf::from("events.jsonl")));
+
+        let args = MirrorCommand::try_parse_from(["website-mirror", "https://example.com"]).unwrap();
+        assert_eq!(args.log_format, "text");
+
+        let result = MirrorCommand::try_parse_from(["website-mirror", "https://example.com", "--log-format", "xml"]);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_parse_verbosity() {
+        let args = MirrorCommand::try_parse_from(["website-mirror", "https://example.com", "-vv"]).unwrap();
+        assert_eq!(args.verbose, 2);
+        assert!(!args.quiet);
+
+        let args = MirrorCommand::try_parse_from(["website-mirror", "https://example.com", "--quiet"]).unwrap();
+        assert_eq!(args.verbose, 0);
+        assert!(args.quiet);
+
+        let result = MirrorCommand::try_parse_from(["website-mirror", "https://example.com", "-q", "-v"]);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_parse_no_color() {
+        let args = MirrorCommand::try_parse_from(["website-mirror", "https://example.com", "-q", "--no-color"]).unwrap();
+        assert!(args.quiet);
+        assert!(args.no_color);
+        let args = MirrorCommand::try_parse_from(["website-mirror", "https://example.com"]).unwrap();
+        assert!(!args.no_color);
+    }
+
+    #[test]
+    fn test_parse_output_format() {
+        let args = MirrorCommand::try_parse_from(["website-mirror", "https://example.com", "--output-format", "json"]).unwrap();
+        assert_eq!(args.output_format, "json");
+        let args = MirrorCommand::try_parse_from(["website-mirror", "https://example.com"]).unwrap();
+        assert_eq!(args.output_format, "text");
+        assert!(MirrorCommand::try_parse_from(["website-mirror", "https://example.com", "--output-format", "yaml"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_fixtures() {
+        let args = MirrorCommand::try_parse_from([
+            "website-mirror",
+            "https://example.com",
+            "--record-fixtures", "tests/fixtures/blog"
+        ]).unwrap();
+        assert_eq!(args.record_fixtures, Some(PathBuf::from("tests/fixtures/blog")));
+
+        let result = MirrorCommand::try_parse_from([
+            "website-mirror",
+            "https://example.com",
+            "--record-fixtures", "a",
+            "--replay-fixtures", "b"
+        ]);
+        assert!(result.is_err());
+    }
+
     #[test]
     fn test_parse_full_mirror() {
         let args = MirrorCommand::try_parse_from(&[
@@ -181,4 +1139,223 @@ mod tests {
         ]);
         assert!(result.is_err());
     }
-} 
\ No newline at end of file
+
+    #[test]
+    fn test_parse_search_command() {
+        let args = SearchCommand::try_parse_from([
+            "search",
+            "./output",
+            "privacy policy",
+            "-n", "5"
+        ]).unwrap();
+        assert_eq!(args.dir.to_string_lossy(), "./output");
+        assert_eq!(args.query, "privacy policy");
+        assert_eq!(args.limit, 5);
+    }
+
+    #[test]
+    fn test_parse_export_compact_command() {
+        let args = ExportCommand::try_parse_from([
+            "export",
+            "compact",
+            "./output",
+            "site.pack"
+        ]).unwrap();
+        #[cfg(feature = "encryption")]
+        assert_eq!(args.encrypt, None);
+        let ExportFormat::Compact { dir, pack } = args.format else {
+            panic!("expected the compact format");
+        };
+        assert_eq!(dir.to_string_lossy(), "./output");
+        assert_eq!(pack.to_string_lossy(), "site.pack");
+    }
+
+    #[test]
+    fn test_parse_export_archive_command() {
+        let args = ExportCommand::try_parse_from(["export", "archive", "./output", "site.bin", "--format", "tar.gz"]).unwrap();
+        let ExportFormat::Archive { dir, archive, format } = args.format else {
+            panic!("expected the archive format");
+        };
+        assert_eq!(dir, PathBuf::from("./output"));
+        assert_eq!(archive, PathBuf::from("site.bin"));
+        assert_eq!(format.as_deref(), Some("tar.gz"));
+    }
+
+    #[cfg(feature = "encryption")]
+    #[test]
+    fn test_parse_export_encrypt_option() {
+        let args = ExportCommand::try_parse_from(["export", "archive", "./output", "site.bin", "--encrypt", "age:age1example"]).unwrap();
+        assert_eq!(args.encrypt.as_deref(), Some("age:age1example"));
+        assert!(matches!(args.format, ExportFormat::Archive { .. }));
+    }
+
+    #[cfg(not(feature = "encryption"))]
+    #[test]
+    fn test_encrypt_option_requires_encryption_feature() {
+        assert!(ExportCommand::try_parse_from(["export", "archive", "./output", "site.bin", "--encrypt", "age:age1example"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_export_mhtml_command() {
+        let args = ExportCommand::try_parse_from(["export", "mhtml", "./output", "./evidence"]).unwrap();
+        let ExportFormat::Mhtml { dir, out_dir } = args.format else {
+            panic!("expected the mhtml format");
+        };
+        assert_eq!(dir, PathBuf::from("./output"));
+        assert_eq!(out_dir, PathBuf::from("./evidence"));
+    }
+
+    #[test]
+    fn test_parse_export_single_file_command() {
+        let args = ExportCommand::try_parse_from(["export", "single-file", "./output", "./portable"]).unwrap();
+        let ExportFormat::SingleFile { dir, out_dir } = args.format else {
+            panic!("expected the single-file format");
+        };
+        assert_eq!(dir, PathBuf::from("./output"));
+        assert_eq!(out_dir, PathBuf::from("./portable"));
+    }
+
+    #[test]
+    fn test_parse_export_epub_command() {
+        let args = ExportCommand::try_parse_from(["export", "epub", "./output", "docs.epub", "--title", "Manual"]).unwrap();
+        let ExportFormat::Epub { dir, epub, title } = args.format else {
+            panic!("expected the epub format");
+        };
+        assert_eq!(dir, PathBuf::from("./output"));
+        assert_eq!(epub, PathBuf::from("docs.epub"));
+        assert_eq!(title.as_deref(), Some("Manual"));
+    }
+
+    #[test]
+    fn test_parse_export_cdx_command() {
+        let args = ExportCommand::try_parse_from(["export", "cdx", "./output", "index.cdxj"]).unwrap();
+        let ExportFormat::Cdx { dir, index } = args.format else {
+            panic!("expected the cdx format");
+        };
+        assert_eq!(dir, PathBuf::from("./output"));
+        assert_eq!(index, PathBuf::from("index.cdxj"));
+    }
+
+    #[test]
+    fn test_parse_export_ssg_command() {
+        let args = ExportCommand::try_parse_from(["export", "ssg", "./output", "./hugo-site", "--format", "hugo"]).unwrap();
+        let ExportFormat::Ssg { dir, out_dir, format } = args.format else {
+            panic!("expected the ssg format");
+        };
+        assert_eq!(dir, PathBuf::from("./output"));
+        assert_eq!(out_dir, PathBuf::from("./hugo-site"));
+        assert_eq!(format, "hugo");
+
+        assert!(ExportCommand::try_parse_from(["export", "ssg", "./output", "./site"]).is_err());
+        assert!(ExportCommand::try_parse_from(["export", "ssg", "./output", "./site", "--format", "gatsby"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_export_email_command() {
+        let args = ExportCommand::try_parse_from([
+            "export",
+            "email",
+            "./output",
+            "https://example.com/blog/launch/",
+            "launch.html",
+            "--remote-images",
+        ])
+        .unwrap();
+        let ExportFormat::Email { dir, url, output, remote_images } = args.format else {
+            panic!("expected the email format");
+        };
+        assert_eq!(dir, PathBuf::from("./output"));
+        assert_eq!(url, "https://example.com/blog/launch/");
+        assert_eq!(output, PathBuf::from("launch.html"));
+        assert!(remote_images);
+    }
+
+    #[test]
+    fn test_parse_config_check_command() {
+        let args = ConfigCommand::try_parse_from([
+            "config",
+            "check",
+            "https://example.com",
+            "--only-resources", "images"
+        ]).unwrap();
+        let ConfigAction::Check(mirror_args) = args.action;
+        assert_eq!(mirror_args.url, "https://example.com");
+        assert_eq!(mirror_args.only_resources, Some(vec!["images".to_string()]));
+    }
+
+    #[test]
+    fn test_parse_diff_command() {
+        let args = DiffCommand::try_parse_from(["diff", "./january", "./february", "--text-blocks", "0"]).unwrap();
+        assert_eq!(args.old_dir, PathBuf::from("./january"));
+        assert_eq!(args.new_dir, PathBuf::from("./february"));
+        assert_eq!(args.text_blocks, 0);
+        assert!(!args.no_color);
+
+        let args = DiffCommand::try_parse_from(["diff", "./january", "./february", "--no-color"]).unwrap();
+        assert!(args.no_color);
+    }
+
+    #[test]
+    fn test_parse_verify_command() {
+        let args = VerifyCommand::try_parse_from(["verify", "./mirror", "--link-root", "/archive/site1/"]).unwrap();
+        assert_eq!(args.dir, PathBuf::from("./mirror"));
+        assert_eq!(args.link_root.as_deref(), Some("/archive/site1/"));
+    }
+
+    #[test]
+    fn test_parse_clean_command() {
+        let args = CleanCommand::try_parse_from(["clean", "./mirror", "--delete"]).unwrap();
+        assert_eq!(args.dir, PathBuf::from("./mirror"));
+        assert!(args.delete);
+        assert!(args.link_root.is_none());
+
+        let args = CleanCommand::try_parse_from(["clean", "./mirror", "--lang", "de"]).unwrap();
+        assert_eq!(args.lang.as_deref(), Some("de"));
+        assert!(CleanCommand::try_parse_from(["clean", "./mirror", "--lang", "xx"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_bench_command() {
+        let args = BenchCommand::try_parse_from(["bench", "./fixtures"]).unwrap();
+        assert_eq!(args.dir, PathBuf::from("./fixtures"));
+        assert_eq!(args.iterations, 10);
+        assert_eq!(args.base_url, "https://example.com/");
+        assert!(args.baseline.is_none());
+
+        let args = BenchCommand::try_parse_from([
+            "bench",
+            "./fixtures",
+            "--iterations", "3",
+            "--baseline", "bench.json",
+            "--max-regression", "5",
+        ]).unwrap();
+        assert_eq!(args.iterations, 3);
+        assert_eq!(args.baseline, Some(PathBuf::from("bench.json")));
+        assert_eq!(args.max_regression, 5.0);
+
+        assert!(BenchCommand::try_parse_from(["bench", "./fixtures", "--iterations", "0"]).is_err());
+        assert!(BenchCommand::try_parse_from(["bench", "./fixtures", "--max-regression", "5"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_import_warc_command() {
+        let args = ImportWarcCommand::try_parse_from(["import-warc", "capture.warc.gz", "-o", "./site"]).unwrap();
+        assert_eq!(args.warc, PathBuf::from("capture.warc.gz"));
+        assert_eq!(args.output_dir, PathBuf::from("./site"));
+        assert!(args.url.is_none());
+    }
+
+    #[test]
+    fn test_parse_size() {
+        assert_eq!(parse_size("2G"), Ok(2 * 1024 * 1024 * 1024));
+        assert_eq!(parse_size("500M"), Ok(500 * 1024 * 1024));
+        assert_eq!(parse_size("1.5k"), Ok(1536));
+        assert_eq!(parse_size("10MB"), Ok(10 * 1024 * 1024));
+        assert_eq!(parse_size("4096"), Ok(4096));
+        assert!(parse_size("2X").is_err());
+        assert!(parse_size("G").is_err());
+
+        let args = MirrorCommand::try_parse_from(["website-mirror", "https://example.com", "--max-total-size", "2G"]).unwrap();
+        assert_eq!(args.max_total_size, Some(2 << 30));
+    }
+}
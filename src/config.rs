@@ -0,0 +1,530 @@
+use anyhow::{anyhow, bail, Result, Context};
+use clap::CommandFactory;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use url::Url;
+
+use crate::cli::MirrorCommand;
+use crate::downloader::WebsiteMirror;
+use crate::html_parser::ResourceType;
+use crate::url_normalizer;
+
+/// Environment variables starting with this set options, e.g.
+/// `WEBSITE_MIRROR_MAX_PAGES=100` for `--max-pages 100`
+pub const ENV_PREFIX: &str = "WEBSITE_MIRROR_";
+/// Environment variable naming a config file, when `--config` isn't given
+pub const CONFIG_ENV_VAR: &str = "WEBSITE_MIRROR_CONFIG";
+
+/// Names accepted by `--only-resources`
+const RESOURCE_FILTER_NAMES: &[&str] = &["images", "css", "js", "html", "video", "audio", "fonts", "other"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    /// The run would fail or do something destructive the options don't intend
+    Error,
+    /// Options that cancel each other out or have no effect
+    Warning,
+}
+
+/// A problem found in the effective options of a run
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigIssue {
+    pub severity: Severity,
+    pub message: String,
+}
+
+impl ConfigIssue {
+    fn error(message: impl Into<String>) -> Self {
+        Self {
+            severity: Severity::Error,
+            message: message.into(),
+        }
+    }
+
+    fn warning(message: impl Into<String>) -> Self {
+        Self {
+            severity: Severity::Warning,
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for ConfigIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let label = match self.severity {
+            Severity::Error => "❌ ERROR",
+            Severity::Warning => "⚠️  WARN ",
+        };
+        write!(f, "{}  {}", label, self.message)
+    }
+}
+
+/// Where the value of an option came from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConfigSource {
+    File(PathBuf),
+    Env(String),
+    CommandLine,
+}
+
+impl fmt::Display for ConfigSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigSource::File(path) => write!(f, "config file {}", path.display()),
+            ConfigSource::Env(name) => write!(f, "environment variable {}", name),
+            ConfigSource::CommandLine => write!(f, "command line"),
+        }
+    }
+}
+
+/// Command-line arguments with the options of the config file and environment
+/// merged in, and the source of every option set
+#[derive(Debug, Clone, Default)]
+pub struct MergedArgs {
+    pub args: Vec<String>,
+    /// Option name (`max-pages`) -> where its value came from
+    pub sources: BTreeMap<String, ConfigSource>,
+}
+
+impl MergedArgs {
+    /// Where `option` was set; options left at their default count as the command line
+    pub fn source(&self, option: &str) -> &ConfigSource {
+        self.sources.get(option).unwrap_or(&ConfigSource::CommandLine)
+    }
+
+    /// Options a message names (`--max-pages`) that were set outside the
+    /// command line, as `--max-pages from config file site.json`
+    pub fn describe_sources(&self, message: &str) -> Vec<String> {
+        let mut described = Vec::new();
+        for word in message.split(|c: char| !(c.is_ascii_alphanumeric() || c == '-')) {
+            let Some(option) = word.strip_prefix("--") else { continue };
+            let source = self.source(option);
+            let description = format!("--{} from {}", option, source);
+            if *source != ConfigSource::CommandLine && !described.contains(&description) {
+                described.push(description);
+            }
+        }
+        described
+    }
+
+    /// A parse error, with the config file or environment variable that set the
+    /// offending option when it wasn't the command line
+    pub fn explain_error(&self, error: &clap::Error) -> Option<String> {
+        let clap::error::ContextValue::String(arg) = error.get(clap::error::ContextKind::InvalidArg)? else {
+            return None;
+        };
+        let option = arg.split_whitespace().next()?.trim_start_matches('-');
+        let long = MirrorCommand::command()
+            .get_arguments()
+            .find(|argument| argument.get_long() == Some(option) || argument.get_short().map(String::from).as_deref() == Some(option))
+            .and_then(|argument| argument.get_long().map(str::to_string))?;
+        match self.source(&long) {
+            ConfigSource::CommandLine => None,
+            source => Some(format!("{}note: --{} was set by {}", error.render(), long, source)),
+        }
+    }
+}
+
+/// Long name of the option a command-line word sets (`--max-pages=5`, `-d`),
+/// and whether the option takes a value
+fn option_of(word: &str, command: &clap::Command) -> Option<(String, bool)> {
+    let argument = if let Some(long) = word.strip_prefix("--") {
+        let long = long.split('=').next()?;
+        command.get_arguments().find(|argument| argument.get_long() == Some(long))?
+    } else {
+        let short = word.strip_prefix('-')?.chars().next()?;
+        command.get_arguments().find(|argument| argument.get_short() == Some(short))?
+    };
+    Some((argument.get_long()?.to_string(), argument.get_action().takes_values()))
+}
+
+/// Command-line arguments for `option` set to `value` in a config file or the
+/// environment: flags are given when true, lists once per item
+fn option_args(option: &str, value: &serde_json::Value, takes_value: bool) -> Result<Vec<String>> {
+    use serde_json::Value;
+    if !takes_value {
+        return match value {
+            Value::Bool(enabled) => Ok(if *enabled { vec![format!("--{}", option)] } else { Vec::new() }),
+            _ => Err(anyhow!("--{} is a flag and takes true or false, not {}", option, value)),
+        };
+    }
+    let scalar = |value: &Value| match value {
+        Value::String(text) => Ok(text.clone()),
+        Value::Number(number) => Ok(number.to_string()),
+        Value::Bool(enabled) => Ok(enabled.to_string()),
+        _ => Err(anyhow!("--{} takes a string or number, not {}", option, value)),
+    };
+    match value {
+        Value::Array(items) => items.iter().map(|item| Ok(format!("--{}={}", option, scalar(item)?))).collect(),
+        value => Ok(vec![format!("--{}={}", option, scalar(value)?)]),
+    }
+}
+
+/// Options set in a JSON config file (`{"max-pages": 100, "exclude-domains": ["ads.example.net"]}`)
+fn file_options(path: &Path) -> Result<Vec<(String, serde_json::Value)>> {
+    let data = fs::read_to_string(path).with_context(|| format!("Failed to read config file {}", path.display()))?;
+    let value: serde_json::Value = serde_json::from_str(&data).with_context(|| format!("Failed to parse config file {}", path.display()))?;
+    let serde_json::Value::Object(options) = value else {
+        bail!("Config file {} must hold a JSON object of option names and values", path.display());
+    };
+    Ok(options.into_iter().map(|(name, value)| (name.replace('_', "-"), value)).collect())
+}
+
+/// Merge the options of a config file (`--config`, or `WEBSITE_MIRROR_CONFIG`)
+/// and `WEBSITE_MIRROR_*` environment variables into the command line
+/// `leading` + `cli_args`: the command line wins over the environment, which
+/// wins over the file. Unknown options and values of the wrong type are
+/// reported with the file or variable that set them.
+pub fn merge_args(leading: &[String], cli_args: &[String], env: impl IntoIterator<Item = (String, String)>) -> Result<MergedArgs> {
+    let command = MirrorCommand::command();
+    let env: BTreeMap<String, String> = env.into_iter().filter(|(name, _)| name.starts_with(ENV_PREFIX)).collect();
+
+    let mut config_path = env.get(CONFIG_ENV_VAR).map(PathBuf::from);
+    let mut cli_options = Vec::new();
+    let mut words = cli_args.iter();
+    while let Some(word) = words.next() {
+        if word == "--" {
+            break;
+        }
+        let Some((option, takes_value)) = option_of(word, &command) else { continue };
+        if option == "config" {
+            config_path = match word.split_once('=') {
+                Some((_, path)) => Some(PathBuf::from(path)),
+                None => words.clone().next().map(PathBuf::from),
+            };
+        }
+        // `--max-pages 5` and `-d 3` take the next word; `--max-pages=5` and `-d3` don't
+        let attached = if word.starts_with("--") { word.contains('=') } else { word.len() > 2 };
+        if takes_value && !attached {
+            words.next();
+        }
+        cli_options.push(option);
+    }
+
+    // Option name -> (arguments, source), later sources replacing earlier ones
+    let mut options: BTreeMap<String, (Vec<String>, ConfigSource)> = BTreeMap::new();
+    let mut set = |name: &str, value: &serde_json::Value, source: ConfigSource| -> Result<()> {
+        let (option, takes_value) = option_of(&format!("--{}", name), &command)
+            .filter(|(option, _)| option != "config")
+            .ok_or_else(|| anyhow!("Unknown option {} in {}", name, source))?;
+        let args = option_args(&option, value, takes_value).map_err(|e| anyhow!("{} (in {})", e, source))?;
+        options.insert(option, (args, source));
+        Ok(())
+    };
+    if let Some(path) = &config_path {
+        for (name, value) in file_options(path)? {
+            set(&name, &value, ConfigSource::File(path.clone()))?;
+        }
+    }
+    for (variable, value) in &env {
+        if variable == CONFIG_ENV_VAR {
+            continue;
+        }
+        let name = variable[ENV_PREFIX.len()..].to_ascii_lowercase().replace('_', "-");
+        let is_flag = option_of(&format!("--{}", name), &command).is_some_and(|(_, takes_value)| !takes_value);
+        let value = match value.to_ascii_lowercase().as_str() {
+            "true" | "1" | "yes" if is_flag => serde_json::Value::Bool(true),
+            "false" | "0" | "no" | "" if is_flag => serde_json::Value::Bool(false),
+            _ => serde_json::Value::String(value.clone()),
+        };
+        set(&name, &value, ConfigSource::Env(variable.clone()))?;
+    }
+
+    let mut merged = MergedArgs::default();
+    merged.args.extend(leading.iter().cloned());
+    for (option, (args, source)) in options {
+        if cli_options.contains(&option) {
+            continue;
+        }
+        merged.args.extend(args);
+        merged.sources.insert(option, source);
+    }
+    merged.args.extend(cli_args.iter().cloned());
+    for option in cli_options {
+        merged.sources.insert(option, ConfigSource::CommandLine);
+    }
+    Ok(merged)
+}
+
+/// Check the options of a run, after presets and `--full-mirror` are applied,
+/// for invalid values and combinations that conflict or do nothing
+pub fn check_config(mirror: &WebsiteMirror) -> Vec<ConfigIssue> {
+    let mut issues = Vec::new();
+
+    let site_host = match Url::parse(&mirror.base_url) {
+        Ok(url) if url.scheme() == "http" || url.scheme() == "https" => url.host_str().map(str::to_string),
+        _ => {
+            issues.push(ConfigIssue::error(format!("{} is not an http(s) URL", mirror.base_url)));
+            None
+        }
+    };
+
+    if let Some(filter) = &mirror.only_resources {
+        for name in filter {
+            if !RESOURCE_FILTER_NAMES.contains(&name.to_lowercase().as_str()) {
+                issues.push(ConfigIssue::error(format!("--only-resources: unknown resource type {} (expected one of {})", name, RESOURCE_FILTER_NAMES.join(","))));
+            }
+        }
+
+        let saves_images = mirror.should_process_resource_type(&ResourceType::Image);
+        if !saves_images {
+            if mirror.convert_to_webp {
+                issues.push(ConfigIssue::warning("--convert-to-webp has no effect: images are not in --only-resources"));
+            }
+            if !mirror.image_pipeline.is_empty() {
+                issues.push(ConfigIssue::warning("--optimize-jpeg/--quantize-png have no effect: images are not in --only-resources"));
+            }
+            if mirror.generate_gallery {
+                issues.push(ConfigIssue::warning("--gallery will be empty: images are not in --only-resources"));
+            }
+        }
+
+        // Pages are crawled for discovery but not saved
+        if !mirror.should_process_resource_type(&ResourceType::Link) {
+            if mirror.delete_dead_assets {
+                issues.push(ConfigIssue::error("--delete-dead-assets would delete every asset: html is not in --only-resources, so no saved page references them"));
+            }
+            if mirror.screenshot_command.is_some() {
+                issues.push(ConfigIssue::warning("--screenshot-command has nothing to render: html is not in --only-resources"));
+            }
+            if mirror.prune {
+                issues.push(ConfigIssue::warning("--prune is skipped: html is not in --only-resources, so there are no pages to trace files from"));
+            }
+            if mirror.text_diff || !mirror.alert_rules.is_empty() {
+                issues.push(ConfigIssue::warning("--text-diff/--alert-on have no pages to compare: html is not in --only-resources"));
+            }
+            if mirror.search_index {
+                issues.push(ConfigIssue::warning("--search-index will be empty: html is not in --only-resources"));
+            }
+            if mirror.validate || mirror.validate_strict {
+                issues.push(ConfigIssue::warning("--validate only checks stylesheets: html is not in --only-resources"));
+            }
+        }
+    }
+
+    if mirror.alert_webhook.is_some() && mirror.alert_rules.is_empty() {
+        issues.push(ConfigIssue::warning("--alert-webhook is never called without --alert-on patterns"));
+    }
+    if mirror.max_pages == Some(0) {
+        issues.push(ConfigIssue::error("--max-pages 0 would not even crawl the start page"));
+    }
+    if let (Some(max_file_size), Some(max_total_size)) = (mirror.max_file_size, mirror.max_total_size) {
+        if max_file_size > max_total_size {
+            issues.push(ConfigIssue::warning("--max-file-size is larger than --max-total-size, which always stops the crawl first"));
+        }
+    }
+    if let (Some(max_html_size), Some(max_file_size)) = (mirror.max_html_size, mirror.max_file_size) {
+        if max_html_size >= max_file_size {
+            issues.push(ConfigIssue::warning("--max-html-size has no effect unless it is smaller than --max-file-size"));
+        }
+    }
+
+    if !mirror.output_sections.is_empty() && (mirror.rewrite_host.is_some() || mirror.link_base.is_some()) {
+        issues.push(ConfigIssue::warning("--split with --rewrite-host/--link-base: root-relative links can't reach section directories outside --output-dir"));
+    }
+
+    if let Some(site_host) = site_host {
+        if mirror.is_excluded_domain(&mirror.base_url) {
+            issues.push(ConfigIssue::error(format!("--exclude-domains covers {} itself: nothing would be mirrored", site_host)));
+        }
+        for domain in &mirror.crawl_domains {
+            if domain.eq_ignore_ascii_case(&site_host) {
+                issues.push(ConfigIssue::warning(format!("--crawl-domains {} is the site's own host", domain)));
+            } else if mirror.is_excluded_domain(&format!("https://{}/", domain)) {
+                issues.push(ConfigIssue::warning(format!("--crawl-domains {} is also excluded by --exclude-domains, which wins", domain)));
+            }
+        }
+        for alias in &mirror.alias_hosts {
+            if alias.eq_ignore_ascii_case(&site_host) {
+                issues.push(ConfigIssue::warning(format!("--alias-host {} is the site's own host", alias)));
+            }
+        }
+        if mirror.span_subdomains && url_normalizer::registrable_domain(&site_host).is_none() {
+            issues.push(ConfigIssue::warning(format!("--span-subdomains has no effect: {} has no registrable domain", site_host)));
+        }
+    }
+
+    issues
+}
+
+fn flag(enabled: bool) -> String {
+    if enabled { "on".to_string() } else { "off".to_string() }
+}
+
+fn list(values: &[String]) -> String {
+    if values.is_empty() { "-".to_string() } else { values.join(",") }
+}
+
+fn optional<T: ToString>(value: Option<T>) -> String {
+    value.map_or_else(|| "-".to_string(), |value| value.to_string())
+}
+
+/// The settings a run would use, as option name -> value, after presets and
+/// `--full-mirror` are applied
+pub fn effective_config(mirror: &WebsiteMirror) -> Vec<(&'static str, String)> {
+    vec![
+        ("url", mirror.base_url.clone()),
+        ("output-dir", mirror.output_dir.display().to_string()),
+        ("publish-to", optional(mirror.publish_target.as_ref().map(|target| &target.location))),
+        ("max-depth", if mirror.max_depth == 0 { "unlimited".to_string() } else { mirror.max_depth.to_string() }),
+        ("max-concurrent", mirror.max_concurrent.to_string()),
+        ("max-pages", optional(mirror.max_pages)),
+        ("max-total-size", optional(mirror.max_total_size)),
+        ("max-file-size", optional(mirror.max_file_size)),
+        ("max-html-size", optional(mirror.max_html_size)),
+        ("io-throttle", optional(mirror.io_throttle().and_then(|throttle| throttle.bytes_per_sec))),
+        ("io-max-open-files", optional(mirror.io_throttle().map(|throttle| throttle.max_open_files))),
+        ("ignore-robots", flag(mirror.ignore_robots)),
+        ("respect-noarchive", flag(mirror.respect_noarchive)),
+        ("only-resources", mirror.only_resources.as_deref().map_or_else(|| "all".to_string(), list)),
+        ("preset", optional(mirror.preset.map(|preset| preset.name()))),
+        ("include-path", list(&mirror.include_paths)),
+        ("crawl-domains", list(&mirror.crawl_domains)),
+        ("span-subdomains", flag(mirror.span_subdomains)),
+        ("alias-host", list(&mirror.alias_hosts)),
+        ("exclude-domains", list(&mirror.exclude_domains)),
+        ("preconnect", flag(mirror.preconnect)),
+        ("trust-hinted-hosts", flag(mirror.trust_hinted_hosts)),
+        ("strip-tracking-params", flag(mirror.strip_tracking_params)),
+        ("convert-to-webp", flag(mirror.convert_to_webp)),
+        ("convert-formats", list(&mirror.convert_formats)),
+        ("optimize-jpeg", optional(mirror.image_pipeline.jpeg_quality)),
+        ("quantize-png", optional(mirror.image_pipeline.png_colors)),
+        ("image-variants", mirror.image_variant_widths.iter().map(u32::to_string).collect::<Vec<_>>().join(",")),
+        ("embed-placeholder", mirror.embed_placeholders.iter().map(|provider| provider.name()).collect::<Vec<_>>().join(",")),
+        ("oembed", flag(mirror.resolve_oembed)),
+        ("lazy-attrs", list(&mirror.lazy_attrs)),
+        ("prune", flag(mirror.prune)),
+        ("integrity", format!("{:?}", mirror.integrity_mode).to_lowercase()),
+        ("comment-resources", format!("{:?}", mirror.comment_mode).to_lowercase()),
+        ("rewrite-host", optional(mirror.rewrite_host.as_deref())),
+        ("link-base", optional(mirror.link_base.as_deref())),
+        ("rewrite-canonical", flag(mirror.rewrite_canonical)),
+        ("strip-canonical", flag(mirror.strip_canonical)),
+        ("external-host-dirs", flag(mirror.external_host_dirs)),
+        ("case-insensitive-names", flag(mirror.case_insensitive_names)),
+        ("preserve-encoding", flag(mirror.preserve_encoding)),
+        ("fix-charset", flag(mirror.fix_charset)),
+        ("scrub-pii", flag(mirror.pii_scrubber.is_some())),
+        ("review", optional(mirror.reviewer.as_ref().map(|reviewer| format!("{:?}", reviewer)))),
+        ("validate", if mirror.validate_strict { "strict".to_string() } else { flag(mirror.validate) }),
+        ("check-external-links", flag(mirror.check_external_links)),
+        ("text-diff", flag(mirror.text_diff)),
+        ("alert-on", mirror.alert_rules.iter().map(|rule| rule.pattern.clone()).collect::<Vec<_>>().join(",")),
+        ("search-index", flag(mirror.search_index)),
+        ("gallery", flag(mirror.generate_gallery)),
+        ("rate-report", flag(mirror.rate_report)),
+        ("html-report", flag(mirror.html_report)),
+        ("a11y-audit", flag(mirror.a11y_audit)),
+        ("cdx", flag(mirror.cdx_index)),
+        ("link-graph", optional(mirror.link_graph.as_ref().map(|path| path.display()))),
+        ("no-retry-failed", flag(!mirror.retry_failed)),
+        ("log-file", optional(mirror.log_file.as_ref().map(|path| path.display()))),
+        ("record-fixtures", optional(mirror.record_fixtures.as_ref().map(|path| path.display()))),
+        ("archive-output", optional(mirror.archive_output.as_ref().map(|path| path.display()))),
+        ("single-file", optional(mirror.single_file_output.as_ref().map(|path| path.display()))),
+        ("rewrite-diff", flag(mirror.rewrite_diff)),
+        ("split", mirror.output_sections.iter().map(|section| format!("{}={}", section.prefix, section.dir.display())).collect::<Vec<_>>().join(",")),
+        ("assert", mirror.assertions.iter().map(|assertion| assertion.to_string()).collect::<Vec<_>>().join(",")),
+    ]
+    .into_iter()
+    .map(|(name, value)| (name, if value.is_empty() { "-".to_string() } else { value }))
+    .collect()
+}
+
+/// `effective_config` as aligned `name  value` lines, naming the config file
+/// or environment variable of settings not given on the command line
+pub fn format_config(mirror: &WebsiteMirror, merged: &MergedArgs) -> String {
+    effective_config(mirror)
+        .into_iter()
+        .map(|(name, value)| match merged.source(name) {
+            ConfigSource::CommandLine => format!("{:<24}{}\n", name, value),
+            source => format!("{:<24}{}  ({})\n", name, value, source),
+        })
+        .collect()
+}
+
+/// An issue of `check_config`, followed by the sources of the options it
+/// names that weren't set on the command line
+pub fn format_issue(issue: &ConfigIssue, merged: &MergedArgs) -> String {
+    let sources = merged.describe_sources(&issue.message);
+    if sources.is_empty() {
+        issue.to_string()
+    } else {
+        format!("{}  ({})", issue, sources.join("; "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_check_config() {
+        let temp_dir = tempdir().unwrap();
+        let mut mirror = WebsiteMirror::new("https://example.com", temp_dir.path(), 3, 10, false, false, None, false).unwrap();
+        assert!(check_config(&mirror).is_empty());
+
+        mirror.only_resources = Some(vec!["images".to_string(), "pdfs".to_string()]);
+        mirror.delete_dead_assets = true;
+        mirror.exclude_domains = vec!["example.com".to_string()];
+        let issues = check_config(&mirror);
+        let errors: Vec<&str> = issues.iter().filter(|issue| issue.severity == Severity::Error).map(|issue| issue.message.as_str()).collect();
+        assert_eq!(errors.len(), 3);
+        assert!(errors[0].contains("pdfs"));
+        assert!(errors[1].contains("--delete-dead-assets"));
+        assert!(errors[2].contains("--exclude-domains"));
+    }
+
+    #[test]
+    fn test_merge_args_sources() {
+        let temp_dir = tempdir().unwrap();
+        let config_path = temp_dir.path().join("mirror.json");
+        fs::write(&config_path, r#"{"max-pages": 50, "max-depth": 2, "exclude-domains": ["example.com"], "ignore-robots": true}"#).unwrap();
+        let words = |words: &[&str]| words.iter().map(|word| word.to_string()).collect::<Vec<_>>();
+        let env = vec![
+            ("WEBSITE_MIRROR_MAX_DEPTH".to_string(), "4".to_string()),
+            ("WEBSITE_MIRROR_MAX_PAGES".to_string(), "100".to_string()),
+            ("HOME".to_string(), "/root".to_string()),
+        ];
+
+        let cli = words(&["https://example.com", "--config", config_path.to_str().unwrap(), "--max-pages", "5"]);
+        let merged = merge_args(&words(&["website-mirror"]), &cli, env).unwrap();
+        let args = MirrorCommand::try_parse_from(&merged.args).unwrap();
+        assert_eq!(args.max_pages, Some(5));
+        assert_eq!(args.max_depth, 4);
+        assert!(args.ignore_robots);
+        assert_eq!(*merged.source("max-pages"), ConfigSource::CommandLine);
+        assert_eq!(*merged.source("max-depth"), ConfigSource::Env("WEBSITE_MIRROR_MAX_DEPTH".to_string()));
+        assert_eq!(*merged.source("exclude-domains"), ConfigSource::File(config_path.clone()));
+
+        let issue = ConfigIssue::error("--exclude-domains covers example.com itself: nothing would be mirrored");
+        assert_eq!(merged.describe_sources(&issue.message), vec![format!("--exclude-domains from config file {}", config_path.display())]);
+
+        let error = merge_args(&words(&["website-mirror"]), &cli, vec![("WEBSITE_MIRROR_MAX_PAGE".to_string(), "1".to_string())]).unwrap_err();
+        assert_eq!(error.to_string(), "Unknown option max-page in environment variable WEBSITE_MIRROR_MAX_PAGE");
+        let error = merge_args(&words(&["website-mirror"]), &cli, vec![("WEBSITE_MIRROR_IGNORE_ROBOTS".to_string(), "sometimes".to_string())]).unwrap_err();
+        assert!(error.to_string().contains("in environment variable WEBSITE_MIRROR_IGNORE_ROBOTS"));
+
+        let merged = merge_args(&words(&["website-mirror"]), &cli, vec![("WEBSITE_MIRROR_MAX_DEPTH".to_string(), "deep".to_string())]).unwrap();
+        let error = MirrorCommand::try_parse_from(&merged.args).unwrap_err();
+        assert!(merged.explain_error(&error).unwrap().ends_with("note: --max-depth was set by environment variable WEBSITE_MIRROR_MAX_DEPTH"));
+    }
+
+    #[test]
+    fn test_effective_config() {
+        let temp_dir = tempdir().unwrap();
+        let mut mirror = WebsiteMirror::new("https://example.com", temp_dir.path(), 0, 10, false, false, None, false).unwrap();
+        mirror.include_paths = vec!["/docs".to_string(), "/blog".to_string()];
+        let config = effective_config(&mirror);
+        let value = |name: &str| config.iter().find(|(key, _)| *key == name).map(|(_, value)| value.as_str());
+        assert_eq!(value("max-depth"), Some("unlimited"));
+        assert_eq!(value("include-path"), Some("/docs,/blog"));
+        assert_eq!(value("rewrite-host"), Some("-"));
+        assert!(format_config(&mirror, &MergedArgs::default()).starts_with("url                     https://example.com\n"));
+    }
+}
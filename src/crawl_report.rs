@@ -0,0 +1,348 @@
+use anyhow::{Result, Context};
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::Path;
+use std::time::Instant;
+use url::Url;
+
+use crate::a11y::{A11yIssue, A11yRule};
+use crate::assertions::CrawlStats;
+use crate::gallery::escape_html;
+use crate::html_parser::ResourceType;
+use crate::remediation;
+use crate::url_normalizer;
+
+/// File name of the crawl report, stored at the root of the output directory
+pub const REPORT_FILE_NAME: &str = "report.json";
+/// File name of the human-readable crawl report (`--html-report`)
+pub const HTML_REPORT_FILE_NAME: &str = "report.html";
+
+/// Files saved of one type and their size
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct TypeTotals {
+    pub files: u64,
+    pub bytes: u64,
+}
+
+/// A page or resource that couldn't be fetched or saved
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CrawlFailure {
+    pub url: String,
+    /// HTTP status, when the server answered
+    pub status: Option<u16>,
+    pub error: String,
+    /// What the operator can do about it, for common causes
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hint: Option<String>,
+}
+
+/// A URL deliberately not archived
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SkippedUrl {
+    pub url: String,
+    pub reason: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hint: Option<String>,
+}
+
+/// Requests sent to one registrable domain (`cdn.example.com` counts for `example.com`)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DomainRequests {
+    pub domain: String,
+    pub requests: usize,
+}
+
+/// What a crawl saved and what failed, collected as it runs
+#[derive(Debug, Clone)]
+pub struct CrawlLog {
+    started: Instant,
+    /// Type name (as used by `--only-resources`) -> files saved
+    types: BTreeMap<String, TypeTotals>,
+    failures: Vec<CrawlFailure>,
+    accessibility: Vec<A11yIssue>,
+}
+
+impl Default for CrawlLog {
+    fn default() -> Self {
+        Self {
+            started: Instant::now(),
+            types: BTreeMap::new(),
+            failures: Vec::new(),
+            accessibility: Vec::new(),
+        }
+    }
+}
+
+/// `images`, `css`, `js`, `html`, ... by content type, else by file extension
+pub fn file_type(content_type: Option<&str>, local_path: &str) -> &'static str {
+    content_type
+        .and_then(ResourceType::from_content_type)
+        .unwrap_or_else(|| ResourceType::from_extension(local_path))
+        .filter_name()
+}
+
+impl CrawlLog {
+    pub fn record_file(&mut self, content_type: Option<&str>, local_path: &str, bytes: u64) {
+        let totals = self.types.entry(file_type(content_type, local_path).to_string()).or_default();
+        totals.files += 1;
+        totals.bytes += bytes;
+    }
+
+    pub fn record_failure(&mut self, url: &str, status: Option<u16>, error: &str) {
+        self.failures.push(CrawlFailure {
+            url: url.to_string(),
+            status,
+            error: error.to_string(),
+            hint: remediation::failure_hint(status, error).map(str::to_string),
+        });
+    }
+
+    pub fn duration_seconds(&self) -> f64 {
+        self.started.elapsed().as_secs_f64()
+    }
+
+    /// Accessibility problems found on a page (`--a11y-audit`)
+    pub fn record_a11y_issues(&mut self, issues: Vec<A11yIssue>) {
+        self.accessibility.extend(issues);
+    }
+
+    /// The report of the crawl so far. `skipped` is URL -> reason from the crawl
+    /// manifest, `requests` origin -> requests sent.
+    pub fn report<'a>(
+        &self,
+        stats: &CrawlStats,
+        skipped: &BTreeMap<String, String>,
+        requests: impl IntoIterator<Item = (&'a str, usize)>,
+    ) -> CrawlReport {
+        let mut domains: BTreeMap<String, usize> = BTreeMap::new();
+        for (origin, count) in requests {
+            let Some(host) = Url::parse(origin).ok().and_then(|url| url.host_str().map(str::to_string)) else { continue };
+            let domain = url_normalizer::registrable_domain(&host).unwrap_or(&host);
+            *domains.entry(domain.to_string()).or_default() += count;
+        }
+        let mut domains: Vec<DomainRequests> = domains
+            .into_iter()
+            .map(|(domain, requests)| DomainRequests { domain, requests })
+            .collect();
+        domains.sort_by(|a, b| b.requests.cmp(&a.requests).then_with(|| a.domain.cmp(&b.domain)));
+
+        CrawlReport {
+            duration_seconds: self.duration_seconds(),
+            pages: stats.pages,
+            files: stats.files,
+            bytes: stats.bytes,
+            types: self.types.clone(),
+            failures: self.failures.clone(),
+            skipped: skipped
+                .iter()
+                .map(|(url, reason)| SkippedUrl {
+                    url: url.clone(),
+                    reason: reason.clone(),
+                    hint: remediation::skip_hint(reason).map(str::to_string),
+                })
+                .collect(),
+            domains,
+            accessibility: self.accessibility.clone(),
+        }
+    }
+}
+
+/// Outcome of a mirror run, printed as one JSON object by `--output-format json`
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct RunSummary {
+    pub url: String,
+    /// Output directory, or the `s3://` location the mirror was published to
+    pub output: String,
+    /// HTML pages saved
+    pub pages: u64,
+    /// Other files saved
+    pub assets: u64,
+    /// Bytes written to the output directory
+    pub bytes: u64,
+    pub failures: u64,
+    pub duration_seconds: f64,
+}
+
+/// Summary of a mirror run, written to `report.json` (and `report.html`)
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct CrawlReport {
+    pub duration_seconds: f64,
+    /// HTML pages saved
+    pub pages: u64,
+    /// Other files saved
+    pub files: u64,
+    /// Bytes written to the output directory
+    pub bytes: u64,
+    /// Files and bytes saved per type, pages included
+    pub types: BTreeMap<String, TypeTotals>,
+    pub failures: Vec<CrawlFailure>,
+    pub skipped: Vec<SkippedUrl>,
+    /// Domains requests were sent to, most requested first
+    pub domains: Vec<DomainRequests>,
+    /// Accessibility problems found on the pages, with `--a11y-audit`
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub accessibility: Vec<A11yIssue>,
+}
+
+impl CrawlReport {
+    /// Distinct hints of the failed and skipped URLs with how many URLs each
+    /// applies to, most common first
+    pub fn hint_counts(&self) -> Vec<(usize, &str)> {
+        let mut counts: BTreeMap<&str, usize> = BTreeMap::new();
+        let failure_hints = self.failures.iter().filter_map(|failure| failure.hint.as_deref());
+        let skip_hints = self.skipped.iter().filter_map(|skipped| skipped.hint.as_deref());
+        for hint in failure_hints.chain(skip_hints) {
+            *counts.entry(hint).or_default() += 1;
+        }
+        let mut counts: Vec<(usize, &str)> = counts.into_iter().map(|(hint, count)| (count, hint)).collect();
+        counts.sort_by(|a, b| b.0.cmp(&a.0).then_with(|| a.1.cmp(b.1)));
+        counts
+    }
+
+    /// Accessibility problems per rule, with the number of pages they were found on
+    pub fn a11y_counts(&self) -> Vec<(A11yRule, usize, usize)> {
+        let mut counts: BTreeMap<A11yRule, (usize, BTreeSet<&str>)> = BTreeMap::new();
+        for issue in &self.accessibility {
+            let (issues, pages) = counts.entry(issue.rule).or_default();
+            *issues += 1;
+            pages.insert(&issue.url);
+        }
+        counts.into_iter().map(|(rule, (issues, pages))| (rule, issues, pages.len())).collect()
+    }
+
+    pub fn save(&self, output_dir: &Path) -> Result<()> {
+        let path = output_dir.join(REPORT_FILE_NAME);
+        let data = serde_json::to_string_pretty(self)?;
+        fs::write(&path, data)
+            .with_context(|| format!("Failed to write crawl report: {:?}", path))
+    }
+
+    pub fn save_html(&self, output_dir: &Path) -> Result<()> {
+        let path = output_dir.join(HTML_REPORT_FILE_NAME);
+        fs::write(&path, self.render_html())
+            .with_context(|| format!("Failed to write crawl report: {:?}", path))
+    }
+
+    pub fn render_html(&self) -> String {
+        let mut html = String::from("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>Crawl report</title>\n<style>\n");
+        html.push_str("body { font-family: sans-serif; margin: 2em; }\ntable { border-collapse: collapse; margin-bottom: 2em; }\n");
+        html.push_str("th, td { border: 1px solid #ccc; padding: 4px 8px; text-align: left; }\ntd.number { text-align: right; }\n");
+        html.push_str("</style>\n</head>\n<body>\n<h1>Crawl report</h1>\n");
+        html.push_str(&format!(
+            "<p>{} pages and {} other files, {} bytes, in {:.1} seconds. {} failures, {} skipped.</p>\n",
+            self.pages,
+            self.files,
+            self.bytes,
+            self.duration_seconds,
+            self.failures.len(),
+            self.skipped.len()
+        ));
+
+        html.push_str("<h2>Saved files</h2>\n<table>\n<tr><th>Type</th><th>Files</th><th>Bytes</th></tr>\n");
+        for (name, totals) in &self.types {
+            html.push_str(&format!(
+                "<tr><td>{}</td><td class=\"number\">{}</td><td class=\"number\">{}</td></tr>\n",
+                escape_html(name),
+                totals.files,
+                totals.bytes
+            ));
+        }
+        html.push_str("</table>\n");
+
+        if !self.failures.is_empty() {
+            html.push_str("<h2>Failures</h2>\n<table>\n<tr><th>URL</th><th>Status</th><th>Error</th><th>Hint</th></tr>\n");
+            for failure in &self.failures {
+                html.push_str(&format!(
+                    "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>\n",
+                    escape_html(&failure.url),
+                    failure.status.map_or_else(|| "-".to_string(), |status| status.to_string()),
+                    escape_html(&failure.error),
+                    escape_html(failure.hint.as_deref().unwrap_or(""))
+                ));
+            }
+            html.push_str("</table>\n");
+        }
+
+        if !self.skipped.is_empty() {
+            html.push_str("<h2>Skipped</h2>\n<table>\n<tr><th>URL</th><th>Reason</th><th>Hint</th></tr>\n");
+            for skipped in &self.skipped {
+                html.push_str(&format!(
+                    "<tr><td>{}</td><td>{}</td><td>{}</td></tr>\n",
+                    escape_html(&skipped.url),
+                    escape_html(&skipped.reason),
+                    escape_html(skipped.hint.as_deref().unwrap_or(""))
+                ));
+            }
+            html.push_str("</table>\n");
+        }
+
+        if !self.accessibility.is_empty() {
+            html.push_str("<h2>Accessibility</h2>\n<table>\n<tr><th>Page</th><th>Rule</th><th>Problem</th></tr>\n");
+            for issue in &self.accessibility {
+                html.push_str(&format!(
+                    "<tr><td>{}</td><td>{}</td><td>{}</td></tr>\n",
+                    escape_html(&issue.url),
+                    issue.rule.name(),
+                    escape_html(&issue.detail)
+                ));
+            }
+            html.push_str("</table>\n");
+        }
+
+        html.push_str("<h2>Domains contacted</h2>\n<table>\n<tr><th>Domain</th><th>Requests</th></tr>\n");
+        for domain in &self.domains {
+            html.push_str(&format!("<tr><td>{}</td><td class=\"number\">{}</td></tr>\n", escape_html(&domain.domain), domain.requests));
+        }
+        html.push_str("</table>\n</body>\n</html>\n");
+        html
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_crawl_report() {
+        let mut log = CrawlLog::default();
+        log.record_file(Some("text/html; charset=utf-8"), "index.html", 1000);
+        log.record_file(Some("image/png"), "img/logo.png", 300);
+        log.record_file(None, "img/photo.jpg", 200);
+        log.record_failure("https://example.com/gone", Some(404), "HTTP 404 Not Found");
+        log.record_failure("https://example.com/a", Some(429), "HTTP 429 Too Many Requests");
+        log.record_failure("https://example.com/b", Some(429), "HTTP 429 Too Many Requests");
+
+        let stats = CrawlStats { pages: 1, files: 2, failures: 1, bytes: 1500, ..Default::default() };
+        let skipped = BTreeMap::from([("https://example.com/admin/".to_string(), "robots.txt Disallow: /admin/".to_string())]);
+        let requests = [("https://example.com", 5), ("https://cdn.example.com", 2), ("https://fonts.gstatic.com:443", 1)];
+        log.record_a11y_issues(vec![
+            A11yIssue { url: "https://example.com/".to_string(), rule: A11yRule::ImageAlt, detail: "<img src=\"a.png\"> has no alt text".to_string() },
+            A11yIssue { url: "https://example.com/".to_string(), rule: A11yRule::ImageAlt, detail: "<img src=\"b.png\"> has no alt text".to_string() },
+            A11yIssue { url: "https://example.com/".to_string(), rule: A11yRule::HeadingOrder, detail: "h3 \"A\" follows h1 (skips h2)".to_string() },
+        ]);
+        let report = log.report(&stats, &skipped, requests);
+
+        assert_eq!(report.types["images"], TypeTotals { files: 2, bytes: 500 });
+        assert_eq!(report.types["html"], TypeTotals { files: 1, bytes: 1000 });
+        assert_eq!(report.failures[0].status, Some(404));
+        assert_eq!(report.skipped[0].reason, "robots.txt Disallow: /admin/");
+        assert_eq!(report.skipped[0].hint.as_deref(), remediation::skip_hint("robots.txt"));
+        let hint_counts = report.hint_counts();
+        assert_eq!(hint_counts.len(), 3);
+        assert_eq!(hint_counts[0], (2, remediation::failure_hint(Some(429), "").unwrap()));
+        assert_eq!(
+            report.domains,
+            vec![
+                DomainRequests { domain: "example.com".to_string(), requests: 7 },
+                DomainRequests { domain: "gstatic.com".to_string(), requests: 1 },
+            ]
+        );
+
+        let html = report.render_html();
+        assert!(html.contains("<td>https://example.com/gone</td><td>404</td><td>HTTP 404 Not Found</td><td>The link is broken"));
+        assert!(html.contains("<td>images</td><td class=\"number\">2</td>"));
+        assert!(html.contains("<tr><td>https://example.com/</td><td>image-alt</td><td>&lt;img src=&quot;a.png&quot;&gt; has no alt text</td></tr>"));
+        assert_eq!(report.a11y_counts(), vec![(A11yRule::ImageAlt, 2, 1), (A11yRule::HeadingOrder, 1, 1)]);
+    }
+}
@@ -0,0 +1,212 @@
+use regex::{Captures, Regex};
+
+use crate::html_parser::ResourceType;
+
+/// A resource referenced from a stylesheet via `url()` or `@import`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CssReference {
+    /// The reference exactly as written in the stylesheet
+    pub url: String,
+    pub is_import: bool,
+}
+
+impl CssReference {
+    /// Resource type used for filtering and download priority
+    pub fn resource_type(&self) -> ResourceType {
+        if self.is_import {
+            ResourceType::CSS
+        } else {
+            classify_css_url(&self.url)
+        }
+    }
+}
+
+const URL_PATTERN: &str = r#"(@import\s+)?url\(\s*(?:"([^"]*)"|'([^']*)'|([^)"'\s]*))\s*\)"#;
+const IMPORT_PATTERN: &str = r#"@import\s+(?:"([^"]*)"|'([^']*)')"#;
+const STYLE_BLOCK_PATTERN: &str = r#"(?is)(<style\b[^>]*>)(.*?)(</style\s*>)"#;
+
+/// Whether a CSS reference points at something that can be downloaded
+fn is_fetchable(url: &str) -> bool {
+    let url = url.trim();
+    !(url.is_empty()
+        || url.starts_with('#')
+        || url.starts_with("data:")
+        || url.starts_with("about:")
+        || url.starts_with("javascript:"))
+}
+
+fn captured_url<'t>(caps: &Captures<'t>, groups: &[usize]) -> Option<&'t str> {
+    groups.iter().find_map(|&i| caps.get(i)).map(|m| m.as_str())
+}
+
+/// Guess the resource type of a `url()` reference from its extension
+pub fn classify_css_url(url: &str) -> ResourceType {
+    ResourceType::from_extension(url)
+}
+
+/// Extract every downloadable `url()` and `@import` reference from a stylesheet
+pub fn extract_css_references(css_content: &str) -> Vec<CssReference> {
+    let mut references = Vec::new();
+
+    let url_regex = Regex::new(URL_PATTERN).unwrap();
+    for caps in url_regex.captures_iter(css_content) {
+        if let Some(url) = captured_url(&caps, &[2, 3, 4]) {
+            if is_fetchable(url) {
+                references.push(CssReference {
+                    url: url.trim().to_string(),
+                    is_import: caps.get(1).is_some(),
+                });
+            }
+        }
+    }
+
+    let import_regex = Regex::new(IMPORT_PATTERN).unwrap();
+    for caps in import_regex.captures_iter(css_content) {
+        if let Some(url) = captured_url(&caps, &[1, 2]) {
+            if is_fetchable(url) {
+                references.push(CssReference {
+                    url: url.trim().to_string(),
+                    is_import: true,
+                });
+            }
+        }
+    }
+
+    references
+}
+
+/// Rewrite `url()` and `@import` references in a stylesheet. The callback
+/// receives each reference and returns its replacement, or `None` to leave
+/// the reference untouched.
+pub fn rewrite_css_references<F>(css_content: &str, mut rewrite: F) -> String
+where
+    F: FnMut(&CssReference) -> Option<String>,
+{
+    let url_regex = Regex::new(URL_PATTERN).unwrap();
+    let rewritten = url_regex.replace_all(css_content, |caps: &Captures| {
+        let original = caps[0].to_string();
+        let Some(url) = captured_url(caps, &[2, 3, 4]) else {
+            return original;
+        };
+        if !is_fetchable(url) {
+            return original;
+        }
+
+        let reference = CssReference {
+            url: url.trim().to_string(),
+            is_import: caps.get(1).is_some(),
+        };
+        match rewrite(&reference) {
+            Some(new_url) => {
+                let prefix = caps.get(1).map(|m| m.as_str()).unwrap_or("");
+                format!("{}url(\"{}\")", prefix, new_url)
+            }
+            None => original,
+        }
+    });
+
+    let import_regex = Regex::new(IMPORT_PATTERN).unwrap();
+    import_regex
+        .replace_all(&rewritten, |caps: &Captures| {
+            let original = caps[0].to_string();
+            let Some(url) = captured_url(caps, &[1, 2]) else {
+                return original;
+            };
+            if !is_fetchable(url) {
+                return original;
+            }
+
+            let reference = CssReference {
+                url: url.trim().to_string(),
+                is_import: true,
+            };
+            match rewrite(&reference) {
+                Some(new_url) => format!("@import \"{}\"", new_url),
+                None => original,
+            }
+        })
+        .into_owned()
+}
+
+/// Rewrite `url()` and `@import` references inside every `<style>` block of
+/// an HTML document, leaving the rest of the markup untouched
+pub fn rewrite_style_blocks<F>(html_content: &str, mut rewrite: F) -> String
+where
+    F: FnMut(&CssReference) -> Option<String>,
+{
+    let style_regex = Regex::new(STYLE_BLOCK_PATTERN).unwrap();
+    style_regex
+        .replace_all(html_content, |caps: &Captures| {
+            format!("{}{}{}", &caps[1], rewrite_css_references(&caps[2], &mut rewrite), &caps[3])
+        })
+        .into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extract_css_references() {
+        let css = r#"
+            @import "base.css";
+            @import url('theme.css');
+            @font-face { font-family: X; src: url(/fonts/x.woff2) format("woff2"), url("/fonts/x.ttf"); }
+            .logo { background: url('/img/logo.png') no-repeat; }
+            .inline { background: url(data:image/png;base64,AAAA); }
+            .filter { filter: url(#blur); }
+        "#;
+
+        let references = extract_css_references(css);
+        let urls: Vec<&str> = references.iter().map(|r| r.url.as_str()).collect();
+
+        assert_eq!(references.len(), 5);
+        assert!(urls.contains(&"base.css"));
+        assert!(urls.contains(&"theme.css"));
+        assert!(urls.contains(&"/fonts/x.woff2"));
+        assert!(urls.contains(&"/fonts/x.ttf"));
+        assert!(urls.contains(&"/img/logo.png"));
+        assert!(references.iter().filter(|r| r.is_import).count() == 2);
+    }
+
+    #[test]
+    fn test_rewrite_css_references() {
+        let css = r#"@import "base.css"; .a { background: url('/img/a.png'); } .b { background: url(/img/b.png); }"#;
+
+        let rewritten = rewrite_css_references(css, |reference| {
+            if reference.url == "/img/b.png" {
+                None
+            } else {
+                Some(format!("local/{}", reference.url.trim_start_matches('/')))
+            }
+        });
+
+        assert!(rewritten.contains(r#"@import "local/base.css""#));
+        assert!(rewritten.contains(r#"url("local/img/a.png")"#));
+        assert!(rewritten.contains("url(/img/b.png)"));
+    }
+
+    #[test]
+    fn test_rewrite_style_blocks() {
+        let html = r#"<div style="background: url(/img/inline.png)"></div>
+<STYLE type="text/css">@import 'print.css'; .hero { background: url("/img/hero.jpg"); }</STYLE>
+<p>url(/img/text.png)</p>"#;
+
+        let rewritten = rewrite_style_blocks(html, |reference| Some(format!("local{}", reference.url)));
+
+        assert!(rewritten.contains(r#"@import "localprint.css""#));
+        assert!(rewritten.contains(r#"url("local/img/hero.jpg")"#));
+        // Only <style> contents are rewritten
+        assert!(rewritten.contains(r#"style="background: url(/img/inline.png)""#));
+        assert!(rewritten.contains("<p>url(/img/text.png)</p>"));
+    }
+
+    #[test]
+    fn test_classify_css_url() {
+        assert_eq!(classify_css_url("/img/sprite.PNG?v=2"), ResourceType::Image);
+        assert_eq!(classify_css_url("print.css"), ResourceType::CSS);
+        assert_eq!(classify_css_url("/fonts/x.woff2"), ResourceType::Font);
+        assert_eq!(classify_css_url("/fonts/x.eot?#iefix"), ResourceType::Font);
+        assert_eq!(classify_css_url("/data/feed.json"), ResourceType::Other);
+    }
+}
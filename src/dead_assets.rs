@@ -0,0 +1,243 @@
+use anyhow::{Result, Context};
+use regex::Regex;
+use std::collections::{BTreeSet, HashSet, VecDeque};
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+use crate::css_parser;
+use crate::store::DownloadStore;
+
+const ATTRIBUTE_PATTERN: &str = r#"(?i)\b(?:src|href|poster|data-src)\s*=\s*(?:"([^"]*)"|'([^']*)')"#;
+const SRCSET_PATTERN: &str = r#"(?i)\b(?:srcset|data-srcset)\s*=\s*(?:"([^"]*)"|'([^']*)')"#;
+
+/// A saved asset that no live page or stylesheet links to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeadAsset {
+    pub url: String,
+    pub local_path: String,
+    pub size: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum DocumentKind {
+    Html,
+    Css,
+    Asset,
+}
+
+pub(crate) fn document_kind(local_path: &str, content_type: Option<&str>) -> DocumentKind {
+    let content_type = content_type.unwrap_or("").to_lowercase();
+    let path = local_path.to_lowercase();
+    if content_type.starts_with("text/html") || path.ends_with(".html") || path.ends_with(".htm") {
+        DocumentKind::Html
+    } else if content_type.starts_with("text/css") || path.ends_with(".css") {
+        DocumentKind::Css
+    } else {
+        DocumentKind::Asset
+    }
+}
+
+/// Normalize a path relative to the output directory, dropping `.` and
+/// resolving `..` components
+pub(crate) fn normalize_path(path: &Path) -> String {
+    let mut parts: Vec<String> = Vec::new();
+    for component in path.components() {
+        match component {
+            Component::Normal(part) => parts.push(part.to_string_lossy().to_string()),
+            Component::ParentDir => {
+                parts.pop();
+            }
+            _ => {}
+        }
+    }
+    parts.join("/")
+}
+
+/// Resolve a reference found in `document_path` to a path relative to the
+/// output directory. External, inline and fragment-only references return `None`.
+pub(crate) fn resolve_reference(document_path: &str, reference: &str) -> Option<String> {
+    let reference = reference.trim();
+    let reference = reference.split(['?', '#']).next().unwrap_or("");
+    if reference.is_empty()
+        || reference.contains("://")
+        || reference.starts_with("//")
+        || reference.starts_with("data:")
+        || reference.starts_with("mailto:")
+        || reference.starts_with("javascript:")
+    {
+        return None;
+    }
+
+    let resolved = if let Some(absolute) = reference.strip_prefix('/') {
+        PathBuf::from(absolute)
+    } else {
+        Path::new(document_path)
+            .parent()
+            .unwrap_or_else(|| Path::new(""))
+            .join(reference)
+    };
+    Some(normalize_path(&resolved))
+}
+
+fn captured<'t>(caps: &regex::Captures<'t>) -> Option<&'t str> {
+    caps.get(1).or_else(|| caps.get(2)).map(|m| m.as_str())
+}
+
+/// Every reference a saved HTML page or stylesheet makes, as written
+pub(crate) fn extract_references(content: &str, kind: DocumentKind) -> Vec<String> {
+    let mut references: Vec<String> = css_parser::extract_css_references(content)
+        .into_iter()
+        .map(|reference| reference.url)
+        .collect();
+
+    if kind == DocumentKind::Html {
+        let attribute_regex = Regex::new(ATTRIBUTE_PATTERN).unwrap();
+        references.extend(attribute_regex.captures_iter(content).filter_map(|caps| captured(&caps).map(String::from)));
+
+        let srcset_regex = Regex::new(SRCSET_PATTERN).unwrap();
+        for caps in srcset_regex.captures_iter(content) {
+            if let Some(srcset) = captured(&caps) {
+                references.extend(
+                    srcset
+                        .split(',')
+                        .filter_map(|candidate| candidate.split_whitespace().next())
+                        .map(String::from),
+                );
+            }
+        }
+    }
+
+    references
+}
+
+/// Cross-reference saved assets against the references in the rewritten
+/// output. Saved HTML pages are the roots; stylesheets only count once
+/// something live links to them, so assets pulled in by unused stylesheets
+/// are reported too.
+pub fn find_dead_assets(output_dir: &Path, store: &DownloadStore) -> Vec<DeadAsset> {
+    let mut documents = VecDeque::new();
+    let mut live: HashSet<String> = HashSet::new();
+
+    for entry in store.entries.values() {
+        let local_path = normalize_path(Path::new(&entry.local_path));
+        if document_kind(&local_path, entry.content_type.as_deref()) == DocumentKind::Html {
+            live.insert(local_path.clone());
+            documents.push_back((local_path, DocumentKind::Html));
+        }
+    }
+
+    let stylesheets: HashSet<String> = store
+        .entries
+        .values()
+        .map(|entry| normalize_path(Path::new(&entry.local_path)))
+        .filter(|path| document_kind(path, None) == DocumentKind::Css)
+        .collect();
+
+    while let Some((document_path, kind)) = documents.pop_front() {
+        let Ok(content) = fs::read_to_string(output_dir.join(&document_path)) else {
+            continue;
+        };
+        for reference in extract_references(&content, kind) {
+            let Some(target) = resolve_reference(&document_path, &reference) else {
+                continue;
+            };
+            if live.insert(target.clone()) && stylesheets.contains(&target) {
+                documents.push_back((target, DocumentKind::Css));
+            }
+        }
+    }
+
+    store
+        .entries
+        .iter()
+        .filter_map(|(url, entry)| {
+            let local_path = normalize_path(Path::new(&entry.local_path));
+            let is_asset = document_kind(&local_path, entry.content_type.as_deref()) != DocumentKind::Html;
+            let exists = !local_path.is_empty() && output_dir.join(&local_path).is_file();
+            (is_asset && exists && !live.contains(&local_path)).then(|| DeadAsset {
+                url: url.clone(),
+                local_path,
+                size: entry.size,
+            })
+        })
+        .collect()
+}
+
+/// Delete dead assets from disk and drop them from the store
+pub fn delete_dead_assets(output_dir: &Path, store: &mut DownloadStore, dead_assets: &[DeadAsset]) -> Result<()> {
+    let removed: BTreeSet<&str> = dead_assets.iter().map(|asset| asset.url.as_str()).collect();
+    for asset in dead_assets {
+        let path = output_dir.join(&asset.local_path);
+        fs::remove_file(&path)
+            .with_context(|| format!("Failed to delete dead asset: {:?}", path))?;
+    }
+    store.entries.retain(|url, _| !removed.contains(url.as_str()));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::write;
+    use tempfile::tempdir;
+
+    fn site() -> (tempfile::TempDir, DownloadStore) {
+        let temp_dir = tempdir().unwrap();
+        let dir = temp_dir.path();
+        write(dir, "index.html", r#"<link href="css/site.css" rel="stylesheet"><img srcset="img/a.png 1x, img/b.png 2x">"#);
+        write(dir, "blog/post.html", r#"<img src="../img/c.png?v=1">"#);
+        write(dir, "css/site.css", ".hero { background: url('../img/hero.jpg'); }");
+        write(dir, "css/unused.css", ".old { background: url(../img/old.jpg); }");
+        for image in ["img/a.png", "img/b.png", "img/c.png", "img/hero.jpg", "img/old.jpg", "img/orphan.gif"] {
+            write(dir, image, "x");
+        }
+
+        let mut store = DownloadStore::default();
+        for (url, path, content_type) in [
+            ("https://example.com/", "index.html", "text/html"),
+            ("https://example.com/blog/post", "blog/post.html", "text/html"),
+            ("https://example.com/css/site.css", "css/site.css", "text/css"),
+            ("https://example.com/css/unused.css", "css/unused.css", "text/css"),
+            ("https://example.com/img/a.png", "img/a.png", "image/png"),
+            ("https://example.com/img/b.png", "img/b.png", "image/png"),
+            ("https://example.com/img/c.png", "img/c.png", "image/png"),
+            ("https://example.com/img/hero.jpg", "img/hero.jpg", "image/jpeg"),
+            ("https://example.com/img/old.jpg", "img/old.jpg", "image/jpeg"),
+            ("https://example.com/img/orphan.gif", "img/orphan.gif", "image/gif"),
+        ] {
+            store.record_download(url, path, Some(content_type), 1);
+        }
+        (temp_dir, store)
+    }
+
+    #[test]
+    fn test_find_dead_assets() {
+        let (temp_dir, store) = site();
+        let dead: Vec<String> = find_dead_assets(temp_dir.path(), &store)
+            .into_iter()
+            .map(|asset| asset.local_path)
+            .collect();
+
+        assert_eq!(dead, vec!["css/unused.css", "img/old.jpg", "img/orphan.gif"]);
+    }
+
+    #[test]
+    fn test_delete_dead_assets() {
+        let (temp_dir, mut store) = site();
+        let dead = find_dead_assets(temp_dir.path(), &store);
+        delete_dead_assets(temp_dir.path(), &mut store, &dead).unwrap();
+
+        assert!(!temp_dir.path().join("img/orphan.gif").exists());
+        assert!(temp_dir.path().join("img/hero.jpg").exists());
+        assert!(!store.entries.contains_key("https://example.com/img/old.jpg"));
+        assert!(find_dead_assets(temp_dir.path(), &store).is_empty());
+    }
+
+    #[test]
+    fn test_resolve_reference() {
+        assert_eq!(resolve_reference("blog/post.html", "../img/a.png#x"), Some("img/a.png".to_string()));
+        assert_eq!(resolve_reference("blog/post.html", "/css/site.css"), Some("css/site.css".to_string()));
+        assert_eq!(resolve_reference("index.html", "https://cdn.example.com/a.js"), None);
+        assert_eq!(resolve_reference("index.html", "data:image/png;base64,AAAA"), None);
+    }
+}
@@ -0,0 +1,143 @@
+use anyhow::{Result, Context};
+use regex::Regex;
+use select::document::Document;
+use select::predicate::{Attr, Name};
+use serde_json::Value;
+
+/// Static documentation site generators whose client-side search (and
+/// version switcher) load files that no page links to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DocGenerator {
+    MkDocs,
+    Sphinx,
+    Docusaurus,
+}
+
+/// Version list written by `mike` next to the versioned MkDocs subtrees
+pub const MIKE_VERSIONS_FILE: &str = "versions.json";
+
+impl DocGenerator {
+    /// Detect the generator from `<meta name="generator">`, falling back to
+    /// the scripts each generator always emits
+    pub fn detect(html_content: &str) -> Option<DocGenerator> {
+        let document = Document::from(html_content);
+        let generator = document
+            .find(Name("meta"))
+            .filter(|meta| meta.attr("name").is_some_and(|name| name.eq_ignore_ascii_case("generator")))
+            .filter_map(|meta| meta.attr("content"))
+            .map(|content| content.to_lowercase())
+            .collect::<Vec<_>>()
+            .join(" ");
+
+        if generator.contains("mkdocs") {
+            Some(DocGenerator::MkDocs)
+        } else if generator.contains("sphinx") || document.find(Attr("id", "documentation_options")).next().is_some() {
+            Some(DocGenerator::Sphinx)
+        } else if generator.contains("docusaurus") || document.find(Attr("id", "__docusaurus")).next().is_some() {
+            Some(DocGenerator::Docusaurus)
+        } else {
+            None
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            DocGenerator::MkDocs => "MkDocs",
+            DocGenerator::Sphinx => "Sphinx",
+            DocGenerator::Docusaurus => "Docusaurus",
+        }
+    }
+
+    /// The documentation root as recorded in the page, relative to the page
+    /// (`None` if the generator doesn't record it)
+    pub fn site_root(&self, html_content: &str) -> Option<String> {
+        match self {
+            DocGenerator::MkDocs => {
+                // mkdocs themes: `var base_url = "../.."`; Material: `"base": "../.."` in #__config
+                let base_regex = Regex::new(r#"(?:\bbase_url\s*=\s*|"base"\s*:\s*)["']([^"']*)["']"#).unwrap();
+                let base = base_regex.captures(html_content)?[1].to_string();
+                Some(if base.is_empty() { ".".to_string() } else { base })
+            }
+            DocGenerator::Sphinx => {
+                let document = Document::from(html_content);
+                let options = document.find(Attr("id", "documentation_options")).next()?;
+                if let Some(url_root) = options.attr("data-url_root") {
+                    return Some(if url_root.is_empty() { ".".to_string() } else { url_root.to_string() });
+                }
+                let src = options.attr("src")?;
+                let root = src.strip_suffix("_static/documentation_options.js")?;
+                Some(if root.is_empty() { ".".to_string() } else { root.to_string() })
+            }
+            DocGenerator::Docusaurus => None,
+        }
+    }
+
+    /// Search indexes, version lists and inventories loaded at runtime,
+    /// relative to the documentation root (missing ones are simply skipped)
+    pub fn support_files(&self) -> &'static [&'static str] {
+        match self {
+            DocGenerator::MkDocs => &["search/search_index.json", "sitemap.xml", "../versions.json"],
+            DocGenerator::Sphinx => &["searchindex.js", "search.html", "objects.inv"],
+            DocGenerator::Docusaurus => &["search-index.json", "search-doc.json", "lunr-index.json", "sitemap.xml"],
+        }
+    }
+}
+
+/// Whether a URL is a `mike` version list
+pub fn is_mike_versions_file(url: &str) -> bool {
+    let path = url.split(['?', '#']).next().unwrap_or(url);
+    path.rsplit('/').next() == Some(MIKE_VERSIONS_FILE)
+}
+
+/// Version directories listed in a `mike` `versions.json`
+pub fn parse_mike_versions(versions_content: &str) -> Result<Vec<String>> {
+    let versions: Value = serde_json::from_str(versions_content).context("Failed to parse versions.json")?;
+    Ok(versions
+        .as_array()
+        .map(|entries| {
+            entries
+                .iter()
+                .filter_map(|entry| entry.get("version").and_then(Value::as_str))
+                .map(|version| version.to_string())
+                .collect()
+        })
+        .unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect_generators() {
+        let mkdocs = r#"<html><head><meta name="generator" content="mkdocs-1.5.3, mkdocs-material-9.4.6"></head></html>"#;
+        let sphinx = r#"<html><head><script id="documentation_options" data-url_root="../" src="../_static/documentation_options.js"></script></head></html>"#;
+        let docusaurus = r#"<html><head><meta name="generator" content="Docusaurus v3.1.0"></head><body><div id="__docusaurus"></div></body></html>"#;
+
+        assert_eq!(DocGenerator::detect(mkdocs), Some(DocGenerator::MkDocs));
+        assert_eq!(DocGenerator::detect(sphinx), Some(DocGenerator::Sphinx));
+        assert_eq!(DocGenerator::detect(docusaurus), Some(DocGenerator::Docusaurus));
+        assert_eq!(DocGenerator::detect("<html><head><title>Blog</title></head></html>"), None);
+    }
+
+    #[test]
+    fn test_site_root() {
+        let mkdocs = r#"<script>var base_url = "../..";</script>"#;
+        let material = r#"<script id="__config" type="application/json">{"base": "..", "features": []}</script>"#;
+        let sphinx = r#"<script id="documentation_options" src="../../_static/documentation_options.js"></script>"#;
+
+        assert_eq!(DocGenerator::MkDocs.site_root(mkdocs).as_deref(), Some("../.."));
+        assert_eq!(DocGenerator::MkDocs.site_root(material).as_deref(), Some(".."));
+        assert_eq!(DocGenerator::Sphinx.site_root(sphinx).as_deref(), Some("../../"));
+        assert_eq!(DocGenerator::Docusaurus.site_root(""), None);
+    }
+
+    #[test]
+    fn test_parse_mike_versions() {
+        let versions = r#"[{"version": "2.0", "title": "2.0", "aliases": ["latest"]}, {"version": "1.9", "title": "1.9", "aliases": []}]"#;
+
+        assert!(is_mike_versions_file("https://docs.example.com/versions.json"));
+        assert!(!is_mike_versions_file("https://docs.example.com/search/search_index.json"));
+        assert_eq!(parse_mike_versions(versions).unwrap(), vec!["2.0", "1.9"]);
+    }
+}
@@ -1,16 +1,70 @@
-use anyhow::Result;
-use reqwest::{Client, ClientBuilder, StatusCode};
-use std::collections::{HashSet, HashMap, BinaryHeap};
+use anyhow::{anyhow, Result};
+use reqwest::header::{HeaderMap, HeaderValue, IF_RANGE, RANGE};
+use reqwest::redirect::Policy;
+use reqwest::{Client, ClientBuilder, Response, StatusCode};
+use std::collections::{BTreeMap, HashSet, HashMap, BinaryHeap};
 use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicUsize, Ordering as AtomicOrdering};
 use tokio::sync::Semaphore;
+use futures::stream::{self, StreamExt};
 use std::path::{Path, PathBuf};
 use colored::*;
-use indicatif::{ProgressBar, ProgressStyle};
 use std::cmp::Ordering;
+use std::fs;
+use std::io::Write;
 use regex::Regex;
+use url::Url;
+use encoding_rs::UTF_8;
+use tracing::{debug, debug_span, error, info, warn, Instrument};
 
+use crate::a11y;
+use crate::alerts::{self, AlertPayload, AlertRule, ChangeKind, PageChange};
+use crate::archive::{self, ArchiveFormat};
+use crate::assertions::{CrawlAssertion, CrawlMetric, CrawlStats};
+use crate::auth;
+use crate::autoindex;
+use crate::broken_links::{self, BrokenLinks, RetryOutcome};
+use crate::cdx;
+use crate::circuit_breaker::{CircuitBreaker, FailureKind};
+use crate::crawl_report::{self, CrawlLog, RunSummary};
+use crate::css_parser;
+use crate::dead_assets;
+use crate::doc_generators::{self, DocGenerator};
+use crate::embeds::{self, Embed, EmbedProvider, OEmbed};
+use crate::encoding;
+use crate::event_log::{Event, EventLog};
+use crate::external_links::{self, ExternalLinks};
 use crate::file_manager::FileManager;
-use crate::html_parser::{HtmlParser, ResourceType};
+use crate::fixtures::{self, FixtureServer, FixtureSet, RecordedResponse};
+use crate::gallery;
+use crate::html_parser::{CommentMode, HtmlParser, ResourceLink, ResourceType};
+use crate::i18n::{tr, Message};
+use crate::image_transform::{self, ImagePipeline};
+use crate::image_variants;
+use crate::integrity::{self, IntegrityMode};
+use crate::io_throttle::IoThrottle;
+use crate::link_graph::{self, EdgeKind, GraphFormat, GraphReport, LinkGraph};
+use crate::manifest;
+use crate::pii::PiiScrubber;
+use crate::preset::Preset;
+use crate::progress::{self, CrawlProgress};
+use crate::prune;
+use crate::rate_report::{self, RequestLog};
+use crate::redirects::{self, RedirectMap};
+use crate::review::{self, ReviewDecision, ReviewRules, Reviewer};
+use crate::rewrite_diff;
+use crate::robots::{self, RobotsRules};
+use crate::storage::{self, S3Storage};
+use crate::store::{self, DownloadStore};
+use crate::search;
+use crate::sections::{self, OutputSection};
+use crate::single_file;
+use crate::svg_parser;
+use crate::url_normalizer;
+use crate::text_diff;
+use crate::validation;
+use crate::visual_diff;
+use crate::warc::WarcArchive;
 use webp::Encoder;
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -68,6 +122,27 @@ impl PartialOrd for DownloadTask {
     }
 }
 
+/// Parsed pages that may wait for the asset stage before the crawl stage
+/// stops to let it catch up
+const PIPELINE_DEPTH: usize = 4;
+
+/// A page fetched and parsed by the crawl stage, handed to the asset stage to
+/// download its resources, rewrite its references and save it
+struct PendingPage {
+    url: String,
+    content_type: String,
+    page_encoding: &'static encoding_rs::Encoding,
+    html_parser: HtmlParser,
+    /// Output path the page is saved under
+    local_path: String,
+    /// Page HTML with anchor and listing links already rewritten
+    html_content: String,
+    /// The page as downloaded, kept for `rewrite_diff`
+    original_html: Option<String>,
+    critical_resources: Vec<ResourceLink>,
+    normal_resources: Vec<ResourceLink>,
+}
+
 #[derive(Clone, Debug)]
 pub struct WebsiteMirror {
     pub base_url: String,
@@ -78,6 +153,167 @@ pub struct WebsiteMirror {
     pub download_external: bool,
     pub only_resources: Option<Vec<String>>,
     pub convert_to_webp: bool,
+    /// Input image formats (file extensions) converted when `convert_to_webp` is set
+    pub convert_formats: Vec<String>,
+    /// Embed the source ICC profile into converted WebP images
+    pub preserve_icc: bool,
+    /// Format-preserving image optimizations (e.g. `--optimize-jpeg`)
+    pub image_pipeline: ImagePipeline,
+    /// Write a thumbnail gallery `index.html` for image-only crawls
+    pub generate_gallery: bool,
+    /// Report saved assets that nothing in the output links to
+    pub report_dead_assets: bool,
+    /// Delete the reported dead assets (implies `report_dead_assets`)
+    pub delete_dead_assets: bool,
+    /// Delete files no mirrored page leads to anymore, left over from earlier runs
+    pub prune: bool,
+    /// Skip saving pages whose `<meta name="robots">` contains `noarchive` or `nosnippet`
+    pub respect_noarchive: bool,
+    /// Shell command printing a fresh bearer token, run when a request gets a 401
+    pub token_command: Option<String>,
+    /// Attributes treated as lazy-loaded image sources (e.g. `data-src`)
+    pub lazy_attrs: Vec<String>,
+    /// Copy rewritten lazy-loaded sources into `src`/`srcset`
+    pub lazy_copy_src: bool,
+    /// URL requested periodically during the crawl to keep a login session alive
+    pub keep_alive_url: Option<String>,
+    /// Seconds between keep-alive requests
+    pub keep_alive_interval: u64,
+    /// Consecutive connection failures after which a host is skipped (0 disables)
+    pub circuit_breaker_threshold: usize,
+    /// Revalidate previously downloaded files at least this large (bytes) with
+    /// `Range` + `If-Range` instead of re-downloading them
+    pub differential_min_size: Option<u64>,
+    /// Point canonical and hreflang alternate links at the local copies
+    pub rewrite_canonical: bool,
+    /// Remove canonical and hreflang alternate links from saved pages
+    pub strip_canonical: bool,
+    /// How `integrity` attributes on rewritten stylesheets and scripts are handled
+    pub integrity_mode: IntegrityMode,
+    /// HTML comments searched for references (IE conditional comments, commented-out assets)
+    pub comment_mode: CommentMode,
+    /// Save non-UTF-8 pages in their original encoding instead of transcoding to UTF-8
+    pub preserve_encoding: bool,
+    /// Correct `<meta charset>` and remove conflicting `http-equiv` Content-Type
+    /// tags so saved pages declare the encoding they were written in
+    pub fix_charset: bool,
+    /// CMS-specific crawl rules (`--preset`)
+    pub preset: Option<Preset>,
+    /// Widths of Shopify-style product image variants to keep (empty keeps every size)
+    pub image_variant_widths: Vec<u32>,
+    /// Providers (YouTube, Maps, ...) whose iframes are replaced by a static
+    /// thumbnail and a link to the original
+    pub embed_placeholders: Vec<EmbedProvider>,
+    /// Fetch the oEmbed preview (title, thumbnail) of every placeholder embed
+    pub resolve_oembed: bool,
+    /// Write links to mirrored files as absolute URLs on this host (e.g.
+    /// `https://new.example.org`) instead of relative paths
+    pub rewrite_host: Option<String>,
+    /// Write links to mirrored files as root-relative paths under this prefix
+    /// (e.g. `/archive/site1/`) instead of relative paths
+    pub link_base: Option<String>,
+    /// Drop `utm_*` and click-id query parameters when deduplicating and fetching URLs
+    pub strip_tracking_params: bool,
+    /// Leave a meta-refresh page at the old path of every redirected page
+    pub redirect_stubs: bool,
+    /// Save files from other hosts under `_external/<host>/` instead of merging them into the site's tree
+    pub external_host_dirs: bool,
+    /// Give names with uppercase letters a hash suffix so URLs differing only in
+    /// case don't collide on case-insensitive filesystems (Windows, macOS)
+    pub case_insensitive_names: bool,
+    /// After the crawl, check that every local reference in saved pages and stylesheets resolves
+    pub validate: bool,
+    /// Fail the run when validation finds missing references (implies `validate`)
+    pub validate_strict: bool,
+    /// Send a HEAD request to every link to a page that isn't mirrored and list
+    /// the dead ones in the validation report (implies `validate`)
+    pub check_external_links: bool,
+    /// Command rendering a saved page to a PNG (`{url}`, `{output}` placeholders);
+    /// enables the visual diff against the previous run's screenshots
+    pub screenshot_command: Option<String>,
+    /// Percentage of changed pixels above which a page is flagged by the visual diff
+    pub visual_diff_threshold: f64,
+    /// Diff each page's visible text against the previous run and write `text-diff.html`
+    pub text_diff: bool,
+    /// URL path patterns (`/legal/`, `/**/terms`) whose changes since the previous
+    /// run fail the run; implies `text_diff`
+    pub alert_rules: Vec<AlertRule>,
+    /// URL receiving a JSON POST of the matched changes
+    pub alert_webhook: Option<String>,
+    /// Build a full-text index of the saved pages for `website-mirror search`
+    pub search_index: bool,
+    /// Bytes the crawl may write before it stops starting new downloads
+    pub max_total_size: Option<u64>,
+    /// Resources larger than this many bytes are skipped and listed in `skipped-too-large.json`
+    pub max_file_size: Option<u64>,
+    /// Responses claiming `text/html` larger than this many bytes are saved unparsed, as binary downloads
+    pub max_html_size: Option<u64>,
+    /// Serve every request from this WARC capture instead of the network (`import-warc`)
+    pub replay: Option<Arc<WarcArchive>>,
+    /// Stub server answering every request from recorded fixtures (`--replay-fixtures`)
+    pub fixture_server: Option<Arc<FixtureServer>>,
+    /// Record every response of the crawl into this fixtures directory (`--record-fixtures`)
+    pub record_fixtures: Option<PathBuf>,
+    /// Write structured events of the crawl to this JSON Lines file (`--log-format jsonl`)
+    pub log_file: Option<PathBuf>,
+    /// Redact email addresses, phone numbers and custom patterns from saved pages
+    pub pii_scrubber: Option<PiiScrubber>,
+    /// Stop after this many HTML pages, linking the rest to the live site
+    pub max_pages: Option<usize>,
+    /// Put newly discovered pages in front of a curator before they are crawled
+    pub reviewer: Option<Reviewer>,
+    /// Write `rate-report.json` with the request rates per host and whether
+    /// robots.txt `Crawl-delay` was honored
+    pub rate_report: bool,
+    /// Write `report.html` next to `report.json`, the crawl report written after every run
+    pub html_report: bool,
+    /// Check every archived page for missing alt text, skipped heading levels and
+    /// low-contrast inline colors, listing the problems in the crawl report
+    pub a11y_audit: bool,
+    /// Write a CDXJ index of every saved URL (`index.cdxj`) after the run, for
+    /// web-archive replay tools
+    pub cdx_index: bool,
+    /// Write every page -> page and page -> asset reference seen during the crawl
+    /// to this .dot, .graphml or .json file after the run
+    pub link_graph: Option<PathBuf>,
+    /// Retry URLs that failed with a network error, 429 or 5xx once after the crawl
+    pub retry_failed: bool,
+    /// Package the output directory into this zip or tar.gz file after the run
+    pub archive_output: Option<PathBuf>,
+    /// Also write every saved page with its assets inlined, one HTML file per page,
+    /// into this directory after the run
+    pub single_file_output: Option<PathBuf>,
+    /// Bucket the output directory is published to once the run succeeds
+    /// (`-o s3://bucket/prefix`); the output directory is then its local staging copy
+    pub publish_target: Option<S3Storage>,
+    /// Write a unified diff of every saved page before and after rewriting to `_diffs/`
+    pub rewrite_diff: bool,
+    /// Path prefixes (`/blog`) pages must be under to be crawled; empty crawls the
+    /// whole site. Assets are fetched wherever they live.
+    pub include_paths: Vec<String>,
+    /// Other hosts (`docs.example.org`) whose pages are crawled as part of the
+    /// site, saved under `_external/<host>/` with links between the hosts rewritten
+    pub crawl_domains: Vec<String>,
+    /// Crawl pages on subdomains of the site's registrable domain (`shop.example.com`
+    /// when mirroring `example.com`), saved under `_external/<host>/`
+    pub span_subdomains: bool,
+    /// Other names of the site's host (`old.example.org`) whose URLs are treated
+    /// as the site's own, besides its www/apex counterpart
+    pub alias_hosts: Vec<String>,
+    /// Domains (`doubleclick.net`), with their subdomains, that nothing is crawled
+    /// or downloaded from; references to them are left as they are
+    pub exclude_domains: Vec<String>,
+    /// Open connections to the hosts pages announce with `<link rel="preconnect">`
+    /// or `dns-prefetch` before their assets are requested
+    pub preconnect: bool,
+    /// Download data files, manifests and other non-media resources from hosts
+    /// announced with those hints, as well as from the site itself
+    pub trust_hinted_hosts: bool,
+    /// Conditions on the crawl's outcome (`failures==0`) that fail the run when violated
+    pub assertions: Vec<CrawlAssertion>,
+    /// Subtrees of the site (`/docs`) saved to their own directories instead of
+    /// `output_dir`, with links between the directories kept working
+    pub output_sections: Vec<OutputSection>,
     client: Client,
     file_manager: FileManager,
     html_parser: HtmlParser,
@@ -85,46 +321,304 @@ pub struct WebsiteMirror {
     download_queue: Arc<Mutex<BinaryHeap<DownloadTask>>>,
     semaphore: Arc<Semaphore>,
     download_cache: Arc<Mutex<HashMap<String, String>>>, // URL -> local path mapping
+    store: Arc<Mutex<DownloadStore>>,
+    bearer_token: Arc<Mutex<Option<String>>>,
+    circuit_breaker: Arc<Mutex<CircuitBreaker>>,
+    /// Documentation roots whose search indexes have already been queued
+    doc_roots: Arc<Mutex<HashSet<String>>>,
+    /// Redirects followed during the crawl, saved as `redirects.json`
+    redirects: Arc<Mutex<RedirectMap>>,
+    /// Hops of each redirected request, keyed by the requested URL, as seen by the client's redirect policy
+    redirect_chains: Arc<Mutex<HashMap<String, Vec<String>>>>,
+    /// Bytes written to the output directory during this crawl, checked against `max_total_size`
+    bytes_written: Arc<Mutex<u64>>,
+    /// Downloads skipped because they didn't fit in `max_total_size`
+    budget_skipped: Arc<Mutex<usize>>,
+    /// URLs skipped for exceeding `max_file_size`, with their size when the server announced it
+    too_large: Arc<Mutex<BTreeMap<String, Option<u64>>>>,
+    /// Send times of the crawl's requests per origin, for the rate report
+    request_log: Arc<Mutex<RequestLog>>,
+    /// robots.txt rules per origin, fetched when the origin's first page is crawled
+    robots: Arc<Mutex<HashMap<String, RobotsRules>>>,
+    /// oEmbed previews by embed link, `None` when none could be fetched this run
+    oembed_previews: Arc<Mutex<HashMap<String, Option<OEmbed>>>>,
+    /// Pages, files and failures of this crawl, checked against `assertions`
+    crawl_stats: Arc<Mutex<CrawlStats>>,
+    /// Saved files per type and failures, for `report.json`
+    crawl_log: Arc<Mutex<CrawlLog>>,
+    /// Links to pages that aren't mirrored, for `check_external_links`
+    external_links: Arc<Mutex<ExternalLinks>>,
+    /// References between pages and assets, for `link_graph`
+    link_graph_edges: Arc<Mutex<LinkGraph>>,
+    /// Failed URLs with their status and retry outcome, for `broken-links.json`
+    broken_links: Arc<Mutex<BrokenLinks>>,
+    /// Responses captured for `record_fixtures`
+    recorded_fixtures: Arc<Mutex<FixtureSet>>,
+    /// Opened from `log_file` when the crawl starts
+    event_log: Option<Arc<EventLog>>,
+    /// Progress bars of the crawl, drawn while `mirror_website` runs
+    progress: Arc<CrawlProgress>,
+    /// Origins announced by preconnect/dns-prefetch hints so far
+    hinted_origins: Arc<Mutex<HashSet<String>>>,
 }
 
 impl WebsiteMirror {
-    /// Get the local path for a resource, converting image extensions to WebP if needed
-    fn get_local_path_for_resource(&self, html_parser: &HtmlParser, original_url: &str) -> Result<String> {
-        let local_path = html_parser.url_to_local_path_string(original_url)?;
-        
-        // Convert image extensions to WebP for JPEG/PNG files if the flag is enabled
-        if self.convert_to_webp && (original_url.ends_with(".jpg") || original_url.ends_with(".jpeg") || original_url.ends_with(".png")) {
-            let webp_path = local_path.replace(".jpg", ".webp")
-                                    .replace(".jpeg", ".webp")
-                                    .replace(".png", ".webp");
-            Ok(webp_path)
+    /// Image formats converted to WebP when no `--convert-formats` list is given
+    pub const DEFAULT_CONVERT_FORMATS: &'static [&'static str] = &["jpg", "jpeg", "png"];
+    /// Seconds between keep-alive requests when `--keep-alive-interval` isn't given
+    pub const DEFAULT_KEEP_ALIVE_INTERVAL: u64 = 300;
+    /// Consecutive connection failures before a host's circuit breaker trips
+    pub const DEFAULT_CIRCUIT_BREAKER_THRESHOLD: usize = 5;
+    /// Directory holding other hosts' files when `external_host_dirs` is set
+    pub const EXTERNAL_DIR: &'static str = "_external";
+    /// Report of the resources skipped by `max_file_size` (URL -> announced size)
+    pub const TOO_LARGE_REPORT_FILE_NAME: &'static str = "skipped-too-large.json";
+
+    pub fn default_convert_formats() -> Vec<String> {
+        Self::DEFAULT_CONVERT_FORMATS.iter().map(|f| f.to_string()).collect()
+    }
+
+    /// Formats to convert for this run (empty when WebP conversion is disabled)
+    fn active_convert_formats(&self) -> &[String] {
+        if self.convert_to_webp {
+            &self.convert_formats
+        } else {
+            &[]
+        }
+    }
+
+    /// Extension of `url` as written, if it is an image this run converts to WebP
+    fn webp_source_extension<'a>(&self, url: &'a str) -> Option<&'a str> {
+        Self::convertible_extension(url, self.active_convert_formats())
+    }
+
+    /// Extension of `url` as written, if it matches one of `formats` (case-insensitive)
+    pub fn convertible_extension<'a>(url: &'a str, formats: &[String]) -> Option<&'a str> {
+        let (_, extension) = url.rsplit_once('.')?;
+        if extension.contains('/') {
+            return None;
+        }
+        if formats.iter().any(|f| f.eq_ignore_ascii_case(extension)) {
+            Some(extension)
         } else {
-            Ok(local_path)
+            None
+        }
+    }
+
+    /// Replace the last `.{extension}` in `path` with `.webp`
+    fn with_webp_extension(path: &str, extension: &str) -> String {
+        let needle = format!(".{}", extension);
+        match path.rfind(&needle) {
+            Some(pos) => format!("{}.webp{}", &path[..pos], &path[pos + needle.len()..]),
+            None => path.to_string(),
         }
     }
 
     /// Static version for use in functions without self access
     pub fn get_local_path_for_resource_static(html_parser: &HtmlParser, original_url: &str, convert_to_webp: bool, current_html_path: &str) -> Result<String> {
+        let formats = if convert_to_webp { Self::default_convert_formats() } else { Vec::new() };
+        Self::get_local_path_for_resource_with_formats(html_parser, original_url, &formats, current_html_path)
+    }
+
+    /// Local path for a resource relative to the current HTML file, converting
+    /// image extensions listed in `convert_formats` to WebP
+    pub fn get_local_path_for_resource_with_formats(html_parser: &HtmlParser, original_url: &str, convert_formats: &[String], current_html_path: &str) -> Result<String> {
         let local_path = html_parser.url_to_local_path_string(original_url)?;
-        
-        // Convert image extensions to WebP for JPEG/PNG files if the flag is enabled
-        let final_local_path = if convert_to_webp && (original_url.ends_with(".jpg") || original_url.ends_with(".jpeg") || original_url.ends_with(".png") ||
-                                                    original_url.ends_with(".JPG") || original_url.ends_with(".JPEG") || original_url.ends_with(".PNG")) {
-            local_path.replace(".jpg", ".webp")
-                     .replace(".jpeg", ".webp")
-                     .replace(".png", ".webp")
-                     .replace(".JPG", ".webp")
-                     .replace(".JPEG", ".webp")
-                     .replace(".PNG", ".webp")
-        } else {
-            local_path
+        Ok(Self::relative_link(local_path, original_url, convert_formats, current_html_path))
+    }
+    
+    /// Path from the current HTML file to a resource saved at `local_path`
+    fn relative_link(local_path: String, original_url: &str, convert_formats: &[String], current_html_path: &str) -> String {
+        // Convert image extensions to WebP for the configured formats
+        let final_local_path = match Self::convertible_extension(original_url, convert_formats) {
+            Some(extension) => Self::with_webp_extension(&local_path, extension),
+            None => local_path,
         };
         
         // Calculate relative path from current HTML file to the resource
-        let relative_path = Self::calculate_relative_path(current_html_path, &final_local_path);
-        Ok(relative_path)
+        Self::calculate_relative_path(current_html_path, &final_local_path)
+    }
+    
+    /// Where a URL is saved, relative to the output directory. With
+    /// `external_host_dirs`, files from other hosts go under `_external/<host>/`
+    /// so same-named paths on different CDNs don't overwrite each other; files
+    /// from the extra crawled hosts (`crawl_domains`, subdomains) always do.
+    fn local_path(&self, html_parser: &HtmlParser, url: &str) -> Result<String> {
+        let url = &self.unalias_host(url);
+        let resolved = html_parser.resolve_url(url)?;
+        if let Some(section) = self.output_section(&resolved) {
+            let mut within_section = resolved.clone();
+            within_section.set_path(section.path_within(resolved.path()).unwrap_or_default());
+            let mut local_path = html_parser.url_to_local_path_string(within_section.as_str())?;
+            if self.case_insensitive_names {
+                local_path = FileManager::case_insensitive_path(&local_path);
+            }
+            return Ok(format!("{}/{}", section.relative_dir(&self.output_dir), local_path));
+        }
+        let mut local_path = html_parser.url_to_local_path_string(url)?;
+        if self.external_host_dirs || self.is_extra_host(&resolved) {
+            let site = Url::parse(&self.base_url)?;
+            let same_origin = resolved.host_str() == site.host_str() && resolved.port_or_known_default() == site.port_or_known_default();
+            if let Some(host) = resolved.host_str().filter(|_| !same_origin) {
+                let host_dir = match resolved.port() {
+                    Some(port) => format!("{}_{}", host, port),
+                    None => host.to_string(),
+                };
+                local_path = format!("{}/{}/{}", Self::EXTERNAL_DIR, host_dir, local_path);
+            }
+        }
+        if self.case_insensitive_names {
+            local_path = FileManager::case_insensitive_path(&local_path);
+        }
+        Ok(local_path)
     }
 
+    /// Link to a mirrored file as written into a saved document: relative to the
+    /// document, or under the `rewrite_host`/`link_base` root when one is set
+    fn output_link(&self, html_parser: &HtmlParser, original_url: &str, document_local_path: &str) -> Result<String> {
+        if self.is_excluded_domain(original_url) {
+            return Err(anyhow!("{} is on an excluded domain", original_url));
+        }
+        let original_url = &self.resolve_redirect(&self.normalize_url(original_url));
+        match self.link_root() {
+            Some(link_root) => {
+                // Relative to a file at the output root, i.e. the path from the root
+                let root_path = Self::relative_link(self.local_path(html_parser, original_url)?, original_url, self.active_convert_formats(), "index.html");
+                let root_path = root_path.strip_suffix("index.html").unwrap_or(&root_path);
+                Ok(format!("{}{}", link_root, root_path))
+            }
+            None => {
+                let local_path = self.local_path(html_parser, original_url)?;
+                // Paths relative to the output directory can't express links into or
+                // out of a section directory outside it
+                if local_path.starts_with("../") || document_local_path.starts_with("../") {
+                    let target = Self::relative_link(local_path, original_url, self.active_convert_formats(), "index.html");
+                    return Ok(self.cross_section_path(document_local_path, &target));
+                }
+                Ok(Self::relative_link(local_path, original_url, self.active_convert_formats(), document_local_path))
+            }
+        }
+    }
+    
+    /// The `output_sections` entry a URL of the site is saved under, if any
+    fn output_section(&self, url: &Url) -> Option<&OutputSection> {
+        if self.output_sections.is_empty() {
+            return None;
+        }
+        let site = Url::parse(&self.base_url).ok()?;
+        if url.host_str() != site.host_str() || url.port_or_known_default() != site.port_or_known_default() {
+            return None;
+        }
+        sections::find_section(&self.output_sections, url.path())
+    }
+    
+    /// Path from a saved document to a saved file, both given relative to the
+    /// output directory, when either lives in a section directory outside it
+    fn cross_section_path(&self, document_local_path: &str, target: &str) -> String {
+        let root = std::path::absolute(&self.output_dir).unwrap_or_else(|_| self.output_dir.clone());
+        let document = sections::normalize_path(&root.join(document_local_path));
+        let target_path = sections::normalize_path(&root.join(target));
+        document
+            .parent()
+            .and_then(|dir| pathdiff::diff_paths(&target_path, dir))
+            .map(|path| path.to_string_lossy().replace('\\', "/"))
+            .unwrap_or_else(|| target.to_string())
+    }
+    
+    /// URL as fetched and recorded in the crawl manifest
+    fn normalize_url(&self, url: &str) -> String {
+        self.unalias_host(&url_normalizer::normalize_url(url, self.strip_tracking_params))
+    }
+    
+    /// Key for the visited set and download cache, shared by every spelling of a URL
+    fn url_key(&self, url: &str) -> String {
+        url_normalizer::dedup_key(&self.unalias_host(url), self.strip_tracking_params)
+    }
+    
+    /// `url` on the site's own host when it uses an alias of it (www/apex, `alias_hosts`)
+    fn unalias_host(&self, url: &str) -> String {
+        match Url::parse(&self.base_url).ok().as_ref().and_then(Url::host_str) {
+            Some(site_host) => url_normalizer::unalias_host(url, site_host, &self.alias_hosts),
+            None => url.to_string(),
+        }
+    }
+    
+    /// Final URL of a URL known to redirect, otherwise the URL itself
+    fn resolve_redirect(&self, url: &str) -> String {
+        self.redirects.lock().unwrap().resolve(url).unwrap_or(url).to_string()
+    }
+    
+    /// Record the redirect chain a request for `url` followed, if it ended on a
+    /// different page or file than requested (not just an added trailing slash)
+    fn take_redirect(&self, url: &str, final_url: &str) -> bool {
+        let hops = self.redirect_chains.lock().unwrap().remove(url);
+        if self.url_key(final_url) == self.url_key(url) {
+            return false;
+        }
+        let chain = hops
+            .map(|hops| hops.iter().map(|hop| self.normalize_url(hop)).collect::<Vec<_>>())
+            .filter(|hops| hops.last().map(String::as_str) == Some(final_url))
+            .unwrap_or_else(|| vec![final_url.to_string()]);
+        info!("↪️  Redirected: {} -> {} ({} hops)", url, final_url, chain.len());
+        self.redirects.lock().unwrap().record(url, chain);
+        true
+    }
+    
+    /// `new.example.org` -> `https://new.example.org`, keeping an explicit scheme
+    fn host_base(host: &str) -> String {
+        let host = host.trim().trim_end_matches('/');
+        if host.contains("://") { host.to_string() } else { format!("https://{}", host) }
+    }
+    
+    /// Where absolute links to the output root point (always ending in `/`):
+    /// `https://new.example.org/`, `/archive/site1/`, or both combined.
+    /// `None` keeps links relative.
+    fn link_root(&self) -> Option<String> {
+        let prefix = self.link_base.as_deref().map(|prefix| {
+            let prefix = prefix.trim().trim_matches('/');
+            if prefix.is_empty() { "/".to_string() } else { format!("/{}/", prefix) }
+        });
+        match (&self.rewrite_host, prefix) {
+            (Some(host), prefix) => Some(format!("{}{}", Self::host_base(host), prefix.as_deref().unwrap_or("/"))),
+            (None, prefix) => prefix,
+        }
+    }
+    
+    /// Saved file a link written by [`Self::output_link`] points at, if it's local
+    fn linked_local_file(&self, link: &str, document_local_path: &str) -> Option<PathBuf> {
+        if let Some(link_root) = self.link_root() {
+            if let Some(root_path) = link.strip_prefix(&link_root) {
+                let root_path = root_path.split(['?', '#']).next().unwrap_or(root_path);
+                let root_path = if root_path.is_empty() || root_path.ends_with('/') { format!("{}index.html", root_path) } else { root_path.to_string() };
+                return Some(self.output_dir.join(root_path));
+            }
+        }
+        if link.contains("://") || link.starts_with("//") || link.starts_with('/') {
+            return None;
+        }
+        let document_dir = Path::new(document_local_path).parent().unwrap_or(Path::new(""));
+        Some(self.output_dir.join(document_dir).join(link))
+    }
+    
+    /// Point every remaining absolute URL on the mirrored origin (page links,
+    /// `og:url`, ...) at the `rewrite_host`/`link_base` root
+    fn rewrite_origin_urls(&self, content: &str) -> String {
+        let (Some(link_root), Ok(base_url)) = (self.link_root(), Url::parse(&self.base_url)) else {
+            return content.to_string();
+        };
+        let Some(origin_host) = base_url.host_str() else {
+            return content.to_string();
+        };
+        let hosts = [origin_host.to_string(), url_normalizer::www_alias(origin_host)]
+            .into_iter()
+            .chain(self.alias_hosts.iter().cloned())
+            .map(|host| regex::escape(&host))
+            .collect::<Vec<_>>()
+            .join("|");
+        let origin_regex = Regex::new(&format!(r"(?i)(?:https?:)?//(?:{})(?::\d+)?(?:/|\b)", hosts)).unwrap();
+        origin_regex.replace_all(content, link_root.as_str()).into_owned()
+    }
+    
     /// Calculate relative path from source file to target file
     fn calculate_relative_path(from_path: &str, to_path: &str) -> String {
         use std::path::Path;
@@ -144,7 +638,15 @@ impl WebsiteMirror {
 
     /// Perform comprehensive WebP extension replacement for any remaining image references
     pub fn perform_comprehensive_webp_replacement(html_content: &str) -> String {
+        Self::perform_comprehensive_webp_replacement_for_formats(html_content, &Self::default_convert_formats())
+    }
+
+    /// Comprehensive WebP extension replacement restricted to the given input formats
+    pub fn perform_comprehensive_webp_replacement_for_formats(html_content: &str, formats: &[String]) -> String {
         let mut updated_content = html_content.to_string();
+        if formats.is_empty() {
+            return updated_content;
+        }
         
         // First, do simple string replacements for all image extensions
         // This catches most cases including those in JavaScript, CSS, and HTML
@@ -154,22 +656,27 @@ impl WebsiteMirror {
         updated_content = updated_content.replace(".webp", "___WEBP_MARKER___");
         
         // Pass 2: Convert remaining image extensions to .webp
-        let simple_replacements = vec![
-            (".jpg", ".webp"),
-            (".jpeg", ".webp"),
-            (".png", ".webp"),
-            (".JPG", ".webp"),
-            (".JPEG", ".webp"),
-            (".PNG", ".webp"),
-        ];
+        let mut extensions = Vec::new();
+        for format in formats {
+            let lower = format.to_lowercase();
+            let upper = format.to_uppercase();
+            if !extensions.contains(&lower) {
+                extensions.push(lower);
+            }
+            if !extensions.contains(&upper) {
+                extensions.push(upper);
+            }
+        }
         
-        for (old_ext, new_ext) in simple_replacements {
-            let before_count = updated_content.matches(old_ext).count();
-            updated_content = updated_content.replace(old_ext, new_ext);
+        for extension in &extensions {
+            let old_ext = format!(".{}", extension);
+            let new_ext = ".webp";
+            let before_count = updated_content.matches(&old_ext).count();
+            updated_content = updated_content.replace(&old_ext, new_ext);
             let after_count = updated_content.matches(new_ext).count();
             
             if before_count > 0 {
-                println!("🔍 Simple WebP replacement: {} -> {} ({} replacements)", 
+                debug!("🔍 Simple WebP replacement: {} -> {} ({} replacements)", 
                          old_ext, new_ext, after_count);
             }
         }
@@ -179,23 +686,24 @@ impl WebsiteMirror {
         
         // Then use regex patterns for more specific cases that might have been missed
         // These patterns will now work correctly since we've already handled the double-conversion issue
+        let alternation = extensions.iter().map(|e| regex::escape(e)).collect::<Vec<_>>().join("|");
         let patterns = vec![
             // URLs in quotes that might have been missed
-            (r#"url\(["']?([^"']*\.(?:jpg|jpeg|png|JPG|JPEG|PNG))["']?\)"#, r#"url($1.webp)"#),
+            (format!(r#"url\(["']?([^"']*\.(?:{}))["']?\)"#, alternation), r#"url($1.webp)"#),
             // Src attributes that might have been missed
-            (r#"src=["']([^"']*\.(?:jpg|jpeg|png|JPG|JPEG|PNG))["']"#, r#"src="$1.webp""#),
+            (format!(r#"src=["']([^"']*\.(?:{}))["']"#, alternation), r#"src="$1.webp""#),
             // Background image URLs that might have been missed
-            (r#"background-image:\s*url\(["']?([^"']*\.(?:jpg|jpeg|png|JPG|JPEG|PNG))["']?\)"#, r#"background-image: url($1.webp)"#),
+            (format!(r#"background-image:\s*url\(["']?([^"']*\.(?:{}))["']?\)"#, alternation), r#"background-image: url($1.webp)"#),
         ];
         
         for (pattern, replacement) in patterns {
-            let regex = regex::Regex::new(pattern).unwrap();
+            let regex = regex::Regex::new(&pattern).unwrap();
             let before_count = regex.find_iter(&updated_content).count();
             updated_content = regex.replace_all(&updated_content, replacement).to_string();
             let after_count = regex.find_iter(&updated_content).count();
             
             if before_count > 0 {
-                println!("🔍 Regex WebP replacement: {} -> {} ({} replacements)", 
+                debug!("🔍 Regex WebP replacement: {} -> {} ({} replacements)", 
                          pattern, replacement, after_count);
             }
         }
@@ -203,18 +711,15 @@ impl WebsiteMirror {
         updated_content
     }
 
-    /// Convert JPEG/PNG images to WebP format with good quality lossy compression
-    fn convert_to_webp(&self, image_data: &[u8], original_url: &str) -> Result<Vec<u8>> {
-        Self::convert_to_webp_static(image_data, original_url)
-    }
-
-    /// Static version for use in functions without self access
-    fn convert_to_webp_static(image_data: &[u8], original_url: &str) -> Result<Vec<u8>> {
+    /// Convert images to WebP with good quality lossy compression, optionally
+    /// carrying the source ICC color profile over so Display-P3 and other
+    /// wide-gamut photos don't shift colors
+    fn convert_to_webp_with_icc(image_data: &[u8], original_url: &str, preserve_icc: bool) -> Result<Vec<u8>> {
         // Decode the image
         let img = match image::load_from_memory(image_data) {
             Ok(img) => img,
             Err(e) => {
-                eprintln!("⚠️  Failed to decode image {}: {}", original_url, e);
+                warn!("⚠️  Failed to decode image {}: {}", original_url, e);
                 return Ok(image_data.to_vec()); // Return original data if conversion fails
             }
         };
@@ -226,28 +731,103 @@ impl WebsiteMirror {
         let encoder = Encoder::from_rgb(&rgb_img, rgb_img.width(), rgb_img.height());
         
         // Encode with quality 80 (good balance between size and quality)
-        let webp_data = encoder.encode(80.0);
+        let webp_data = encoder.encode(80.0).to_vec();
+        
+        let webp_data = match preserve_icc.then(|| image_transform::extract_icc_profile(image_data)).flatten() {
+            Some(icc_profile) => {
+                info!("🎨 Preserving {} byte ICC profile for {}", icc_profile.len(), original_url);
+                image_transform::embed_icc_profile(&webp_data, &icc_profile, rgb_img.width(), rgb_img.height())
+            }
+            None => webp_data,
+        };
         
         let original_size = image_data.len();
         let webp_size = webp_data.len();
         let compression_ratio = (original_size as f64 / webp_size as f64 * 100.0) as u32;
         
-        println!("🔄 Converted {} to WebP: {} -> {} bytes ({}% of original size)", 
+        info!("🔄 Converted {} to WebP: {} -> {} bytes ({}% of original size)", 
                  original_url, original_size, webp_size, compression_ratio);
         
-        Ok(webp_data.to_vec())
+        Ok(webp_data)
     }
 
-    /// Check if a resource type should be processed based on the only_resources filter
+    /// Enable a preset, adding its lazy-load attributes to the configured ones
+    pub fn apply_preset(&mut self, preset: Preset) {
+        for attr in preset.lazy_attrs() {
+            if !self.lazy_attrs.iter().any(|existing| existing == attr) {
+                self.lazy_attrs.push(attr.to_string());
+            }
+        }
+        if self.image_variant_widths.is_empty() {
+            self.image_variant_widths = preset.image_variant_widths().to_vec();
+        }
+        self.preset = Some(preset);
+    }
+    
+    /// Why the crawl manifest says `url` was skipped, if it was
+    pub fn store_skip_reason(&self, url: &str) -> Option<String> {
+        self.store.lock().unwrap().skipped.get(url).cloned()
+    }
+    
+    /// Whether `url` is on a host other than the base URL's that is crawled as
+    /// part of the site: one of the `crawl_domains`, or a subdomain sharing the
+    /// base's registrable domain when `span_subdomains` is set
+    fn is_extra_host(&self, url: &Url) -> bool {
+        let (Some(host), Ok(base)) = (url.host_str(), Url::parse(&self.base_url)) else {
+            return false;
+        };
+        if Some(host) == base.host_str() {
+            return false;
+        }
+        if self.crawl_domains.iter().any(|domain| domain.eq_ignore_ascii_case(host)) {
+            return true;
+        }
+        self.span_subdomains
+            && base.host_str().and_then(url_normalizer::registrable_domain).is_some_and(|site| url_normalizer::registrable_domain(host) == Some(site))
+    }
+    
+    /// Whether `url` is part of the mirrored site: under the base URL on the
+    /// same host or an alias of it, or on one of the extra hosts (`crawl_domains`,
+    /// subdomains)
+    pub fn is_target_site(&self, url: &str) -> bool {
+        if self.is_excluded_domain(url) {
+            return false;
+        }
+        let (Ok(parsed), Ok(base)) = (Url::parse(&self.unalias_host(url)), Url::parse(&self.base_url)) else {
+            return false;
+        };
+        let same_host = parsed.host_str() == base.host_str() && parsed.port_or_known_default() == base.port_or_known_default();
+        (same_host && parsed.as_str().starts_with(base.as_str())) || self.is_extra_host(&parsed)
+    }
+    
+    /// Whether `url` is on one of `exclude_domains` or a subdomain of one
+    pub fn is_excluded_domain(&self, url: &str) -> bool {
+        let Some(host) = Url::parse(url).ok().and_then(|parsed| parsed.host_str().map(str::to_string)) else {
+            return false;
+        };
+        self.exclude_domains.iter().any(|domain| {
+            let domain = domain.trim().trim_matches('.').to_ascii_lowercase();
+            !domain.is_empty() && (host == domain || host.ends_with(&format!(".{}", domain)))
+        })
+    }
+    
+    /// Whether a page of the target site is crawled: it must be under one of
+    /// `include_paths` (matched by whole path segments) when any are given
+    pub fn in_include_paths(&self, url: &str) -> bool {
+        if self.include_paths.is_empty() {
+            return true;
+        }
+        let Ok(parsed) = Url::parse(url) else { return false };
+        let path = parsed.path();
+        self.include_paths.iter().any(|prefix| {
+            let prefix = prefix.trim_matches('/');
+            prefix.is_empty() || path.trim_start_matches('/').strip_prefix(prefix).is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+        })
+    }
+    
     pub fn should_process_resource_type(&self, resource_type: &ResourceType) -> bool {
         if let Some(ref only_resources) = self.only_resources {
-            let type_str = match resource_type {
-                ResourceType::Image => "images",
-                ResourceType::CSS => "css",
-                ResourceType::JavaScript => "js",
-                ResourceType::Link => "html",
-                ResourceType::Other => "other",
-            };
+            let type_str = resource_type.filter_name();
             only_resources.iter().any(|r| r.to_lowercase() == type_str)
         } else {
             // If no filter specified, process all resource types
@@ -265,9 +845,11 @@ impl WebsiteMirror {
         only_resources: Option<Vec<String>>,
         convert_to_webp: bool,
     ) -> Result<Self> {
-        let client = Self::build_http_client()?;
+        let redirect_chains = Arc::new(Mutex::new(HashMap::new()));
+        let client = Self::build_http_client(redirect_chains.clone())?;
         let file_manager = FileManager::new(output_dir)?;
         let html_parser = HtmlParser::new(base_url)?;
+        let store = DownloadStore::load(output_dir)?;
         
         Ok(Self {
             base_url: base_url.to_string(),
@@ -278,6 +860,75 @@ impl WebsiteMirror {
             download_external,
             only_resources,
             convert_to_webp,
+            convert_formats: Self::default_convert_formats(),
+            preserve_icc: false,
+            image_pipeline: ImagePipeline::default(),
+            generate_gallery: false,
+            report_dead_assets: false,
+            delete_dead_assets: false,
+            prune: false,
+            respect_noarchive: false,
+            token_command: None,
+            lazy_attrs: HtmlParser::DEFAULT_LAZY_ATTRS.iter().map(|attr| attr.to_string()).collect(),
+            lazy_copy_src: false,
+            keep_alive_url: None,
+            keep_alive_interval: Self::DEFAULT_KEEP_ALIVE_INTERVAL,
+            circuit_breaker_threshold: Self::DEFAULT_CIRCUIT_BREAKER_THRESHOLD,
+            differential_min_size: None,
+            rewrite_canonical: false,
+            strip_canonical: false,
+            integrity_mode: IntegrityMode::Recompute,
+            comment_mode: CommentMode::Ignore,
+            preserve_encoding: false,
+            fix_charset: false,
+            preset: None,
+            image_variant_widths: Vec::new(),
+            embed_placeholders: Vec::new(),
+            resolve_oembed: false,
+            rewrite_host: None,
+            link_base: None,
+            strip_tracking_params: false,
+            redirect_stubs: false,
+            external_host_dirs: false,
+            case_insensitive_names: false,
+            validate: false,
+            validate_strict: false,
+            check_external_links: false,
+            screenshot_command: None,
+            visual_diff_threshold: visual_diff::DEFAULT_THRESHOLD,
+            text_diff: false,
+            alert_rules: Vec::new(),
+            alert_webhook: None,
+            search_index: false,
+            max_total_size: None,
+            max_file_size: None,
+            max_html_size: None,
+            replay: None,
+            fixture_server: None,
+            record_fixtures: None,
+            log_file: None,
+            pii_scrubber: None,
+            max_pages: None,
+            reviewer: None,
+            rate_report: false,
+            html_report: false,
+            a11y_audit: false,
+            cdx_index: false,
+            link_graph: None,
+            retry_failed: true,
+            archive_output: None,
+            single_file_output: None,
+            publish_target: None,
+            rewrite_diff: false,
+            include_paths: Vec::new(),
+            crawl_domains: Vec::new(),
+            span_subdomains: false,
+            alias_hosts: Vec::new(),
+            exclude_domains: Vec::new(),
+            preconnect: false,
+            trust_hinted_hosts: false,
+            assertions: Vec::new(),
+            output_sections: Vec::new(),
             client,
             file_manager,
             html_parser,
@@ -285,14 +936,69 @@ impl WebsiteMirror {
             download_queue: Arc::new(Mutex::new(BinaryHeap::new())),
             semaphore: Arc::new(Semaphore::new(max_concurrent)),
             download_cache: Arc::new(Mutex::new(HashMap::new())),
+            store: Arc::new(Mutex::new(store)),
+            bearer_token: Arc::new(Mutex::new(None)),
+            circuit_breaker: Arc::new(Mutex::new(CircuitBreaker::new(Self::DEFAULT_CIRCUIT_BREAKER_THRESHOLD))),
+            doc_roots: Arc::new(Mutex::new(HashSet::new())),
+            redirects: Arc::new(Mutex::new(RedirectMap::default())),
+            redirect_chains,
+            bytes_written: Arc::new(Mutex::new(0)),
+            budget_skipped: Arc::new(Mutex::new(0)),
+            too_large: Arc::new(Mutex::new(BTreeMap::new())),
+            request_log: Arc::new(Mutex::new(RequestLog::default())),
+            robots: Arc::new(Mutex::new(HashMap::new())),
+            oembed_previews: Arc::new(Mutex::new(HashMap::new())),
+            crawl_stats: Arc::new(Mutex::new(CrawlStats::default())),
+            crawl_log: Arc::new(Mutex::new(CrawlLog::default())),
+            external_links: Arc::new(Mutex::new(ExternalLinks::default())),
+            link_graph_edges: Arc::new(Mutex::new(LinkGraph::default())),
+            broken_links: Arc::new(Mutex::new(BrokenLinks::default())),
+            recorded_fixtures: Arc::new(Mutex::new(FixtureSet::default())),
+            event_log: None,
+            progress: Arc::new(CrawlProgress::hidden()),
+            hinted_origins: Arc::new(Mutex::new(HashSet::new())),
         })
     }
     
-    fn build_http_client() -> Result<Client> {
+    /// Pace and bound every write to the output directory (`--io-throttle`)
+    pub fn with_io_throttle(mut self, throttle: IoThrottle) -> Self {
+        self.file_manager = self.file_manager.with_throttle(Arc::new(throttle));
+        self
+    }
+    
+    pub fn io_throttle(&self) -> Option<&IoThrottle> {
+        self.file_manager.throttle()
+    }
+    
+    /// Overwrite a file in the output directory, paced by `--io-throttle`
+    fn write_output(&self, path: &Path, content: &[u8]) -> Result<()> {
+        match self.file_manager.throttle() {
+            Some(throttle) => throttle.write_file(path, content),
+            None => Ok(fs::write(path, content)?),
+        }
+    }
+    
+    fn build_http_client(redirect_chains: Arc<Mutex<HashMap<String, Vec<String>>>>) -> Result<Client> {
+        // Follow redirects like the default policy, remembering every hop so the
+        // chain can be written to redirects.json
+        let redirect_policy = Policy::custom(move |attempt| {
+            if attempt.previous().len() > redirects::MAX_REDIRECTS {
+                return attempt.error("too many redirects");
+            }
+            if let Some((requested, hops)) = attempt.previous().split_first() {
+                let mut hops: Vec<String> = hops.iter().map(|hop| hop.to_string()).collect();
+                hops.push(attempt.url().to_string());
+                redirect_chains.lock().unwrap().insert(requested.to_string(), hops);
+            }
+            attempt.follow()
+        });
+        
         // Build a simple HTTP client with default SSL handling
         let client = ClientBuilder::new()
             .use_rustls_tls()
             .user_agent("WebsiteMirror/1.0")
+            .cookie_store(true)
+            .redirect(redirect_policy)
             .timeout(std::time::Duration::from_secs(480))
             .build()?;
         
@@ -300,14 +1006,22 @@ impl WebsiteMirror {
     }
     
     pub async fn mirror_website(&mut self) -> Result<()> {
-        println!("🚀 Starting website mirroring for: {}", self.base_url.blue());
-        println!("📁 Output directory: {:?}", self.output_dir);
-        println!("🔗 Max depth: {}", self.max_depth);
-        println!("⚡ Max concurrent downloads: {}", self.max_concurrent);
+        info!("{}", tr(Message::Starting, &[&self.base_url.blue()]));
+        info!("{}", tr(Message::OutputDirectory, &[&format!("{:?}", self.output_dir)]));
+        info!("{}", tr(Message::MaxDepth, &[&self.max_depth]));
+        info!("{}", tr(Message::MaxConcurrent, &[&self.max_concurrent]));
+        if let Some(log_file) = &self.log_file {
+            self.event_log = Some(Arc::new(EventLog::create(log_file)?));
+            info!("📋 Writing crawl events to {}", log_file.display());
+        }
         
         // Add the base URL to the download queue with high priority (HTML page)
-        // Only add HTML pages if we're not filtering to specific resource types
-        if self.only_resources.is_none() || self.should_process_resource_type(&ResourceType::Link) {
+        // Pages are always crawled so their resources can be discovered; when the
+        // resource filter excludes HTML they are parsed but not saved
+        if !self.should_process_resource_type(&ResourceType::Link) {
+            info!("🔍 Resource filter active: crawling HTML pages for discovery only");
+        }
+        {
             let mut queue = self.download_queue.lock().unwrap();
             queue.push(DownloadTask {
                 url: self.base_url.clone(),
@@ -315,116 +1029,442 @@ impl WebsiteMirror {
                 priority: DownloadPriority::High,
                 resource_type: None,
             });
-        } else {
-            println!("🔍 Resource filter active: skipping HTML page crawling");
+            // Replayed captures import every captured page, including ones nothing links to
+            if let Some(archive) = &self.replay {
+                for page in archive.pages().filter(|page| self.is_target_site(page)) {
+                    queue.push(DownloadTask {
+                        url: page.to_string(),
+                        depth: 1,
+                        priority: DownloadPriority::Normal,
+                        resource_type: None,
+                    });
+                }
+            }
         }
         
-        let progress_bar = ProgressBar::new_spinner();
-        progress_bar.set_style(
-            ProgressStyle::default_spinner()
-                .template("{spinner} {msg}")
-                .unwrap()
-        );
+        if self.resolve_oembed {
+            let previews = embeds::load_previews(&self.output_dir)?;
+            self.oembed_previews.lock().unwrap().extend(previews.into_iter().map(|(link, preview)| (link, Some(preview))));
+        }
         
-        // Process the download queue
-        loop {
-            let download_task = {
-                let mut queue = self.download_queue.lock().unwrap();
-                queue.pop()
-            };
-            
-            if let Some(task) = download_task {
-                let url = task.url.clone();
-                let depth = task.depth;
-                let priority = task.priority.clone();
-                let resource_type = task.resource_type.clone();
-                // Check depth limit (0 means unlimited)
-                if self.max_depth > 0 && depth > self.max_depth {
-                    continue;
+        *self.circuit_breaker.lock().unwrap() = CircuitBreaker::new(self.circuit_breaker_threshold);
+        let keep_alive = self.spawn_keep_alive();
+        
+        self.progress = Arc::new(CrawlProgress::new(progress::enabled()));
+        
+        // Process the download queue in two stages: the crawl stage fetches and
+        // parses pages while the asset stage downloads the resources of pages
+        // already parsed and saves them
+        let this = &*self;
+        let (page_sender, mut page_receiver) = tokio::sync::mpsc::channel::<PendingPage>(PIPELINE_DEPTH);
+        let pages_in_flight = AtomicUsize::new(0);
+        let mut pages_started = 0;
+        let mut page_budget_skipped = HashSet::new();
+        let mut review_decisions = HashMap::new();
+        let asset_stage = async {
+            while let Some(page) = page_receiver.recv().await {
+                let url = page.url.clone();
+                if let Err(e) = this.finish_page(page).instrument(debug_span!("assets", url = %url)).await {
+                    error!("❌ Error downloading {}: {}", url, e);
+                    this.count_failure(&url, None, &e.to_string());
                 }
+                info!("🏁 Download completed for: {}", url);
+                this.progress.finish_one();
+                pages_in_flight.fetch_sub(1, AtomicOrdering::SeqCst);
+            }
+        };
+        let crawl_stage = async {
+            loop {
+                let download_task = {
+                    let mut queue = this.download_queue.lock().unwrap();
+                    queue.pop().map(|task| (task, queue.len()))
+                };
+            
+                if let Some((task, queued)) = download_task {
+                    // Every URL taken from the queue is counted done once saved, failed or skipped
+                    this.progress.start(&task.url, queued);
+                    let url = task.url.clone();
+                    let depth = task.depth;
+                    let priority = task.priority.clone();
+                    let resource_type = task.resource_type.clone();
+                    // Check depth limit (0 means unlimited)
+                    if this.max_depth > 0 && depth > this.max_depth {
+                        this.progress.finish_one();
+                        continue;
+                    }
+                
+                    let is_page = matches!(resource_type, None | Some(ResourceType::Link));
+                    // robots.txt keeps pages out; the assets of allowed pages are still fetched
+                    let key = this.url_key(&this.normalize_url(&url));
+                    if is_page && !this.visited_urls.lock().unwrap().contains(&key) {
+                        if let Some(rule) = this.robots_txt_block(&url).await {
+                            this.store.lock().unwrap().record_skip(&url, &format!("{} {}", robots::ROBOTS_TXT_REASON, rule));
+                            this.progress.finish_one();
+                            continue;
+                        }
+                    }
                 
-                let client = self.client.clone();
-                let file_manager = self.file_manager.clone();
-                let html_parser = self.html_parser.clone();
-                let visited_urls = self.visited_urls.clone();
-                let download_queue = self.download_queue.clone();
-                let download_cache = self.download_cache.clone();
+                    if let Some(reviewer) = this.reviewer.as_ref().filter(|_| is_page && depth > 0) {
+                        let key = this.url_key(&this.normalize_url(&url));
+                        if !this.visited_urls.lock().unwrap().contains(&key) {
+                            match review_decisions.get(&key) {
+                                Some(ReviewDecision::Allow) => {}
+                                Some(ReviewDecision::Deny) => {
+                                    this.progress.finish_one();
+                                    continue;
+                                }
+                                None => {
+                                    // Allowed pages of the batch go back into the queue
+                                    this.review_pages(reviewer, task, &mut review_decisions).await?;
+                                    this.progress.finish_one();
+                                    continue;
+                                }
+                            }
+                        }
+                    }
                 
-                progress_bar.set_message(format!("Downloading: {}", url));
+                    if let Some(max_pages) = this.max_pages.filter(|_| is_page) {
+                        let key = this.url_key(&this.normalize_url(&url));
+                        if !this.visited_urls.lock().unwrap().contains(&key) {
+                            if pages_started >= max_pages {
+                                if page_budget_skipped.is_empty() {
+                                    info!("📄 Page budget of {} reached, linking remaining pages to the live site", max_pages);
+                                }
+                                this.store.lock().unwrap().record_skip(&url, "over --max-pages budget");
+                                page_budget_skipped.insert(key);
+                                this.progress.finish_one();
+                                continue;
+                            }
+                            pages_started += 1;
+                        }
+                    }
                 
-                let base_url = self.base_url.clone();
-                let download_external = self.download_external;
+                    if this.remaining_budget() == Some(0) {
+                        this.skip_over_budget(&url);
+                        this.progress.finish_one();
+                        continue;
+                    }
                 
-                // Process the download directly instead of spawning a task
-                println!("🚀 Processing download for: {}", url);
-                                        if let Err(e) = Self::download_and_process_url(
-                            &client,
-                            &file_manager,
-                            &html_parser,
-                            &url,
-                            depth,
-                            &visited_urls,
-                            &download_queue,
-                            &download_cache,
-                            download_external,
-                            &base_url,
-                            priority,
-                            resource_type,
-                            &self.only_resources,
-                            self.convert_to_webp,
-                        ).await {
-                    eprintln!("❌ Error downloading {}: {}", url, e);
-                }
-                println!("🏁 Download completed for: {}", url);
-            } else {
-                // Check if all downloads are complete
-                let queue_size = self.download_queue.lock().unwrap().len();
+                    // Fetch and parse the page here; its assets are handed to the asset stage
+                    info!("🚀 Processing download for: {}", url);
+                    match this.download_and_process_url(
+                        &url,
+                        depth,
+                        priority,
+                        resource_type,
+                    ).instrument(debug_span!("page", url = %url, depth)).await {
+                        Ok(Some(page)) => {
+                            pages_in_flight.fetch_add(1, AtomicOrdering::SeqCst);
+                            if page_sender.send(page).await.is_err() {
+                                pages_in_flight.fetch_sub(1, AtomicOrdering::SeqCst);
+                                this.progress.finish_one();
+                            }
+                        }
+                        Ok(None) => {
+                            info!("🏁 Download completed for: {}", url);
+                            this.progress.finish_one();
+                        }
+                        Err(e) => {
+                            error!("❌ Error downloading {}: {}", url, e);
+                            this.count_failure(&url, None, &e.to_string());
+                            this.progress.finish_one();
+                        }
+                    }
+                } else {
+                    // Check if all downloads are complete
+                    let queue_size = this.download_queue.lock().unwrap().len();
                 
-                if queue_size == 0 {
-                    // Wait a bit for any ongoing downloads to complete
-                    tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
+                    // Pages still in the asset stage may not have queued anything yet
+                    if queue_size == 0 && pages_in_flight.load(AtomicOrdering::SeqCst) == 0 {
+                        // Wait a bit for any ongoing downloads to complete
+                        tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
                     
-                    let final_queue_size = self.download_queue.lock().unwrap().len();
-                    if final_queue_size == 0 {
-                        break;
+                        let final_queue_size = this.download_queue.lock().unwrap().len();
+                        if final_queue_size == 0 && pages_in_flight.load(AtomicOrdering::SeqCst) == 0 {
+                            break;
+                        }
                     }
-                }
                 
-                tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
+                    tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
+                }
             }
+            // Closing the channel lets the asset stage finish once it has drained
+            drop(page_sender);
+            Ok::<(), anyhow::Error>(())
+        };
+        let (crawl_result, ()) = tokio::join!(crawl_stage, asset_stage);
+        crawl_result?;
+        
+        self.progress.finish(tr(Message::AllDownloadsCompleted, &[]));
+        
+        if let Some(keep_alive) = keep_alive {
+            keep_alive.abort();
         }
         
-        progress_bar.finish_with_message("✅ All downloads completed!");
+        if self.retry_failed {
+            self.retry_failures().await;
+        }
         
         let visited_count = self.visited_urls.lock().unwrap().len();
-        println!("📊 Total pages downloaded: {}", visited_count);
+        info!("{}", tr(Message::TotalPagesDownloaded, &[&visited_count]));
+        if let Some(budget) = self.max_total_size {
+            let written = *self.bytes_written.lock().unwrap();
+            info!("💾 Wrote {} of the {} output budget", Self::format_size(written), Self::format_size(budget));
+            let budget_skipped = *self.budget_skipped.lock().unwrap();
+            if budget_skipped > 0 {
+                warn!("⚠️  Skipped {} queued downloads over budget (recorded in {}); raise --max-total-size to fetch them", budget_skipped, store::STORE_FILE_NAME);
+            }
+        }
+        
+        {
+            let store = self.store.lock().unwrap();
+            if !store.skipped.is_empty() {
+                info!("{}", tr(Message::SkippedUrls, &[&store.skipped.len()]));
+                for (skipped_url, reason) in &store.skipped {
+                    info!("   {} ({})", skipped_url, reason);
+                }
+            }
+            
+            let exclusions = robots::summarize_exclusions(&store.skipped);
+            if !exclusions.is_empty() {
+                let total: usize = exclusions.iter().map(|exclusion| exclusion.count).sum();
+                info!("🤖 {} pages excluded by robots rules:", total);
+                for exclusion in &exclusions {
+                    info!("   {} pages: {} (e.g. {})", exclusion.count, exclusion.rule, exclusion.example);
+                }
+                if exclusions.iter().any(|exclusion| exclusion.rule.starts_with(robots::ROBOTS_TXT_REASON)) {
+                    info!("   Re-run with --ignore-robots to include robots.txt exclusions");
+                }
+                if exclusions.iter().any(|exclusion| exclusion.rule.starts_with(robots::META_ROBOTS_REASON)) {
+                    info!("   Re-run without --respect-noarchive to include meta robots exclusions");
+                }
+            }
+        }
+        
+        if !page_budget_skipped.is_empty() {
+            let pages = self.link_unmirrored_pages(&page_budget_skipped)?;
+            info!("📄 Skipped {} pages over --max-pages; links to them in {} saved pages point at the live site", page_budget_skipped.len(), pages);
+        }
+        
+        self.store.lock().unwrap().save(&self.output_dir)?;
+        self.save_redirects()?;
+        self.save_too_large_report()?;
+        self.save_broken_links()?;
+        if let Some(fixtures_dir) = &self.record_fixtures {
+            let mut recorded = self.recorded_fixtures.lock().unwrap().clone();
+            recorded.start_url = self.base_url.clone();
+            recorded.save(fixtures_dir)?;
+            info!("📼 Recorded {} responses to {}", recorded.len(), fixtures_dir.join(fixtures::FIXTURES_FILE_NAME).display());
+        }
+        if self.resolve_oembed {
+            let previews = self.oembed_previews.lock().unwrap().iter()
+                .filter_map(|(link, preview)| Some((link.clone(), preview.clone()?)))
+                .collect();
+            embeds::save_previews(&self.output_dir, &previews)?;
+        }
+        
+        if self.rate_report {
+            self.save_rate_report().await?;
+        }
+        
+        if self.report_dead_assets || self.delete_dead_assets {
+            self.check_dead_assets()?;
+        }
+        
+        if self.prune {
+            self.prune_orphaned_files()?;
+        }
+        
+        if self.generate_gallery {
+            if self.should_process_resource_type(&ResourceType::Link) {
+                // The gallery's index.html would overwrite the mirrored home page
+                warn!("⚠️  Skipping gallery: only generated for image-only crawls (--only-resources images)");
+            } else {
+                let store = self.store.lock().unwrap().clone();
+                let gallery_path = gallery::generate_gallery(&self.output_dir, &store)?;
+                info!("🖼️  Gallery written to: {}", gallery_path.display());
+            }
+        }
+        
+        // broken_links assertions are measured by the validation pass
+        if self.validate
+            || self.validate_strict
+            || self.check_external_links
+            || self.assertions.iter().any(|assertion| assertion.metric == CrawlMetric::BrokenLinks)
+        {
+            self.validate_output().await?;
+        }
+        
+        let mut changes = Vec::new();
+        if let Some(command) = &self.screenshot_command {
+            changes.extend(self.compare_screenshots(command).await?);
+        }
+        
+        if self.text_diff || !self.alert_rules.is_empty() {
+            changes.extend(self.compare_text()?);
+        }
+        
+        if self.search_index {
+            let store = self.store.lock().unwrap().clone();
+            let indexed = search::build_index(&self.output_dir, &store)?;
+            info!("🔍 Indexed {} pages for search in {}", indexed, search::INDEX_DIR);
+        }
+        
+        if !self.alert_rules.is_empty() {
+            self.check_alerts(&changes).await?;
+        }
+        
+        self.save_crawl_report()?;
+        let stats = self.current_stats();
+        self.emit(&Event::CrawlDone { pages: stats.pages, files: stats.files, failures: stats.failures, bytes: stats.bytes });
+        
+        if let Some(graph_path) = &self.link_graph {
+            let format = GraphFormat::from_path(graph_path)
+                .ok_or_else(|| anyhow!("Unknown link graph format: {}", graph_path.display()))?;
+            let store = self.store.lock().unwrap().clone();
+            let edges = self.link_graph_edges.lock().unwrap().clone();
+            let report = GraphReport::build(&edges, &store, &self.normalize_url(&self.base_url));
+            link_graph::write_graph(&report, graph_path, format)?;
+            info!(
+                "🕸️  Wrote link graph of {} pages ({} orphaned, {} not reached) and {} references to {}",
+                report.pages(),
+                report.orphans.len(),
+                report.unreached_pages(),
+                report.edges.len(),
+                graph_path.display()
+            );
+        }
         
+        if self.cdx_index {
+            let records = cdx::write_index(&self.output_dir, &self.output_dir.join(cdx::CDX_FILE_NAME))?;
+            info!("🗃️  Indexed {} captures in {}", records, cdx::CDX_FILE_NAME);
+        }
+        
+        if let Some(archive_output) = &self.archive_output {
+            let format = ArchiveFormat::from_path(archive_output)
+                .ok_or_else(|| anyhow!("Unknown archive format: {}", archive_output.display()))?;
+            let index = archive::export_archive(&self.output_dir, archive_output, format)?;
+            info!("📦 Archived {} files into {}", index.files.len(), archive_output.display());
+        }
+        
+        if let Some(single_file_output) = &self.single_file_output {
+            let stats = single_file::export_single_files(&self.output_dir, single_file_output)?;
+            info!("🗂️  Wrote {} single-file pages into {}", stats.pages, single_file_output.display());
+        }
+        
+        if !self.assertions.is_empty() {
+            self.check_assertions()?;
+        }
+        
+        if let Some(target) = &self.publish_target {
+            let stats = storage::publish(&self.output_dir, target, self.max_concurrent).await?;
+            info!(
+                "☁️  Published {} changed files ({:.1} MB) to {}, {} unchanged",
+                stats.uploaded,
+                stats.uploaded_bytes as f64 / 1_048_576.0,
+                target.location,
+                stats.unchanged
+            );
+        }
+        
+        Ok(())
+    }
+    
+    /// Pages, files and failures so far, with the bytes written
+    fn current_stats(&self) -> CrawlStats {
+        let mut stats = self.crawl_stats.lock().unwrap().clone();
+        stats.bytes = *self.bytes_written.lock().unwrap();
+        stats
+    }
+    
+    /// Outcome of the run, for `--output-format json`
+    pub fn summary(&self) -> RunSummary {
+        let stats = self.current_stats();
+        RunSummary {
+            url: self.base_url.clone(),
+            output: match &self.publish_target {
+                Some(target) => target.location.to_string(),
+                None => self.output_dir.display().to_string(),
+            },
+            pages: stats.pages,
+            assets: stats.files,
+            bytes: stats.bytes,
+            failures: stats.failures,
+            duration_seconds: self.crawl_log.lock().unwrap().duration_seconds(),
+        }
+    }
+    
+    /// Write `report.json` (and `report.html` with `html_report`): what was saved
+    /// per type, failures, skipped URLs and the domains contacted
+    fn save_crawl_report(&self) -> Result<()> {
+        let stats = self.current_stats();
+        let skipped = self.store.lock().unwrap().skipped.clone();
+        let report = {
+            let request_log = self.request_log.lock().unwrap();
+            self.crawl_log.lock().unwrap().report(&stats, &skipped, request_log.request_counts())
+        };
+        report.save(&self.output_dir)?;
+        if self.html_report {
+            report.save_html(&self.output_dir)?;
+            let files = format!("{}, {}", crawl_report::REPORT_FILE_NAME, crawl_report::HTML_REPORT_FILE_NAME);
+            info!("{}", tr(Message::CrawlReportWritten, &[&files]));
+        } else {
+            info!("{}", tr(Message::CrawlReportWritten, &[&crawl_report::REPORT_FILE_NAME]));
+        }
+        for (count, hint) in report.hint_counts() {
+            info!("{}", tr(Message::HintSummary, &[&count, &hint]));
+        }
+        for (rule, issues, pages) in report.a11y_counts() {
+            info!("♿ {}: {} problems on {} pages", rule.name(), issues, pages);
+        }
+        Ok(())
+    }
+    
+    /// Evaluate `assertions` against this crawl, failing the run if any is violated
+    fn check_assertions(&self) -> Result<()> {
+        let stats = self.current_stats();
+        info!("📋 Crawl: {} pages, {} files, {} failures, {} written", stats.pages, stats.files, stats.failures, Self::format_size(stats.bytes));
+        
+        let mut violated = 0;
+        for assertion in &self.assertions {
+            let actual = assertion.metric.value(&stats).map_or_else(|| "not measured".to_string(), |value| value.to_string());
+            if assertion.holds(&stats) {
+                info!("   ✅ {} ({})", assertion, actual);
+            } else {
+                info!("   ❌ {} ({})", assertion, actual);
+                violated += 1;
+            }
+        }
+        if violated > 0 {
+            return Err(anyhow!("{} of {} crawl assertions failed", violated, self.assertions.len()));
+        }
         Ok(())
     }
     
     async fn download_and_process_url(
-        client: &Client,
-        file_manager: &FileManager,
-        html_parser: &HtmlParser,
+        &self,
         url: &str,
         depth: usize,
-        visited_urls: &Arc<Mutex<HashSet<String>>>,
-        download_queue: &Arc<Mutex<BinaryHeap<DownloadTask>>>,
-        download_cache: &Arc<Mutex<HashMap<String, String>>>,
-        download_external: bool,
-        base_url: &str,
         priority: DownloadPriority,
         resource_type: Option<ResourceType>,
-        only_resources: &Option<Vec<String>>,
-        convert_to_webp: bool,
-    ) -> Result<()> {
+    ) -> Result<Option<PendingPage>> {
+        // Crawl each page once however it was linked (fragment, port, tracking parameters, trailing slash)
+        let normalized_url = self.normalize_url(url);
+        let url = normalized_url.as_str();
+        
         // Check if already visited
         {
-            let mut visited = visited_urls.lock().unwrap();
-            if visited.contains(url) {
-                return Ok(());
+            let mut visited = self.visited_urls.lock().unwrap();
+            if !visited.insert(self.url_key(url)) {
+                return Ok(None);
+            }
+        }
+        
+        // Assets queued from stylesheets are fetched through the resource pipeline
+        if let Some(ref asset_type) = resource_type {
+            if *asset_type != ResourceType::Link {
+                return self.download_resource(&self.html_parser, url).await.map(|_| None);
             }
-            visited.insert(url.to_string());
         }
         
         let priority_str = match priority {
@@ -432,24 +1472,42 @@ impl WebsiteMirror {
             DownloadPriority::High => "⚡ HIGH",
             DownloadPriority::Normal => "📥 NORMAL",
         };
-        println!("{} Downloading: {} (depth: {})", priority_str, url, depth);
+        info!("{} Downloading: {} (depth: {})", priority_str, url, depth);
+        self.emit(&Event::PageStart { url, depth });
         
         // Download the URL
-        println!("🌐 Sending request to: {}", url);
-        let response = match client.get(url).send().await {
+        debug!("🌐 Sending request to: {}", url);
+        let response = match self.fetch(url).await {
             Ok(resp) => resp,
             Err(e) => {
-                eprintln!("❌ Request failed: {}", e);
-                return Ok(());
+                error!("❌ Request failed: {}", e);
+                self.count_failure(url, None, &e.to_string());
+                return Ok(None);
             }
         };
         
-        println!("📡 Response status: {}", response.status());
+        debug!("📡 Response status: {}", response.status());
         
         if response.status() != StatusCode::OK {
-            eprintln!("⚠️  HTTP {} for {}", response.status(), url);
-            return Ok(());
+            warn!("⚠️  HTTP {} for {}", response.status(), url);
+            self.count_failure(url, Some(response.status().as_u16()), &format!("HTTP {}", response.status()));
+            return Ok(None);
+        }
+        
+        // Redirected pages are saved and deduplicated under their final URL
+        let final_url = self.normalize_url(response.url().as_str());
+        if self.take_redirect(url, &final_url) {
+            if !self.is_target_site(&final_url) {
+                debug!("⏭️  Not mirroring off-site redirect target: {}", final_url);
+                return Ok(None);
+            }
+            if !self.visited_urls.lock().unwrap().insert(self.url_key(&final_url)) {
+                debug!("⏭️  Skipping {} (redirect target already mirrored)", final_url);
+                return Ok(None);
+            }
         }
+        // Relative links resolve against the final URL (e.g. `/docs` -> `/docs/`)
+        let url = final_url.as_str();
         
         let content_type = response
             .headers()
@@ -458,334 +1516,1625 @@ impl WebsiteMirror {
             .unwrap_or("text/html")
             .to_string();
         
-        let content = match response.bytes().await {
-            Ok(bytes) => bytes,
-            Err(e) => {
-                eprintln!("❌ Failed to read response body: {}", e);
-                return Ok(());
+        let content = match self.read_page_body(url, &content_type, response).await {
+            Ok(Some(bytes)) => bytes,
+            Ok(None) => return Ok(None),
+            Err(e) => {
+                error!("❌ Failed to read response body: {}", e);
+                return Ok(None);
+            }
+        };
+        
+        // Determine content type
+        let is_html = content_type.contains("text/html") || 
+                     content.starts_with(b"<!DOCTYPE") || 
+                     content.starts_with(b"<html");
+        let is_css = content_type.contains("text/css") || url.ends_with(".css");
+        
+        debug!("🔍 Content type: {}, is_html: {}, is_css: {}", content_type, is_html, is_css);
+        debug!("🔍 Content preview: {}", String::from_utf8_lossy(&content[..content.len().min(100)]));
+        
+        if is_html {
+            // Decode with the page's declared encoding (Shift-JIS, GBK, ISO-8859-1, ...)
+            let page_encoding = encoding::detect_html_encoding(&content_type, &content);
+            if page_encoding != UTF_8 {
+                info!("🔤 Decoding {} as {}", url, page_encoding.name());
+            }
+            let html_content = encoding::decode_html(&content, page_encoding);
+            let original_html = self.rewrite_diff.then(|| html_content.clone());
+            
+            // Collapse product image size variants onto the kept widths before anything is extracted
+            let html_content = if self.image_variant_widths.is_empty() {
+                html_content
+            } else {
+                image_variants::select_image_variants(&html_content, &self.image_variant_widths)
+            };
+            
+            // Embeds that can't play offline become placeholders, whose thumbnails are
+            // then downloaded like any other image
+            let html_content = if self.embed_placeholders.is_empty() {
+                html_content
+            } else {
+                let mut previews = HashMap::new();
+                if self.resolve_oembed {
+                    for embed in embeds::find_embeds(&html_content, url, &self.embed_placeholders) {
+                        if let Some(preview) = self.oembed_preview(&embed).await {
+                            previews.insert(embed.link, preview);
+                        }
+                    }
+                }
+                let (html_content, replaced) = embeds::replace_embeds(&html_content, url, &self.embed_placeholders, &previews);
+                if replaced > 0 {
+                    info!("🎬 Replaced {} embeds with placeholders on {}", replaced, url);
+                }
+                html_content
+            };
+            
+            // Create a new HTML parser with the current page's base URL
+            let mut page_html_parser = HtmlParser::new(url)?.with_lazy_attrs(&self.lazy_attrs).with_comment_mode(self.comment_mode);
+            
+            // Relative URLs resolve against <base href> when the page has one
+            let document_base_url = page_html_parser.document_base_url(&html_content);
+            if let Some(ref base_url) = document_base_url {
+                info!("🔗 Resolving relative URLs against <base href=\"{}\">", base_url);
+                page_html_parser = page_html_parser.with_base_url(base_url.clone());
+            }
+            let mut resources = page_html_parser.extract_resources(&html_content)?;
+            
+            if self.preconnect || self.trust_hinted_hosts {
+                self.apply_connection_hints(&page_html_parser.connection_hints(&html_content));
+            }
+            
+            // References hidden in conditional comments or commented-out markup
+            for resource in page_html_parser.extract_comment_resources(&html_content) {
+                if !resources.iter().any(|existing| existing.original_url == resource.original_url) {
+                    info!("💬 Found {} in an HTML comment", resource.original_url);
+                    resources.push(resource);
+                }
+            }
+            
+            // Presets skip dynamic endpoints (REST API, oEmbed, feeds, ...)
+            if let Some(preset) = self.preset {
+                resources.retain(|resource| !preset.excludes_url(&resource.original_url));
+            }
+            
+            // Directory listings: crawl the listed subdirectories, fetch the listed files
+            // by type, and don't follow the column-sort or parent-directory links
+            let is_listing = autoindex::is_directory_listing(&html_content);
+            if is_listing {
+                info!("📂 Directory listing: {}", url);
+                resources.retain(|resource| resource.resource_type != ResourceType::Link);
+                for entry in autoindex::extract_listing_entries(&html_content) {
+                    let Ok(absolute_url) = page_html_parser.resolve_url(&entry.href) else { continue };
+                    let absolute_url = absolute_url.to_string();
+                    if resources.iter().any(|resource| resource.original_url == absolute_url) {
+                        continue;
+                    }
+                    let Ok(local_path) = self.local_path(&page_html_parser, &absolute_url) else { continue };
+                    resources.push(ResourceLink {
+                        original_url: absolute_url,
+                        local_path,
+                        resource_type: entry.resource_type(),
+                        locations: Vec::new(),
+                    });
+                }
+            }
+            
+            // Documentation sites: fetch the search index and version list their scripts load
+            if let Some(generator) = DocGenerator::detect(&html_content) {
+                self.enqueue_doc_support_files(&page_html_parser, generator, &html_content);
+            }
+            
+            // Honor noarchive/nosnippet hints: follow the page's links but don't archive it
+            if let Some(directive) = self.blocking_robots_directive(&html_content) {
+                info!("🚫 Not archiving page marked {}: {}", directive, url);
+                self.store.lock().unwrap().record_skip(url, &format!("{} {}", robots::META_ROBOTS_REASON, directive));
+                for resource in &resources {
+                    if resource.resource_type == ResourceType::Link {
+                        let followed = self.is_target_site(&resource.original_url) && self.in_include_paths(&resource.original_url);
+                        self.record_link_graph_edge(url, resource, followed);
+                    }
+                    if resource.resource_type == ResourceType::Link
+                        && self.is_target_site(&resource.original_url)
+                        && self.in_include_paths(&resource.original_url)
+                        && !self.visited_urls.lock().unwrap().contains(&self.url_key(&resource.original_url))
+                    {
+                        self.download_queue.lock().unwrap().push(DownloadTask {
+                            url: resource.original_url.clone(),
+                            depth: depth + 1,
+                            priority: DownloadPriority::High,
+                            resource_type: Some(ResourceType::Link),
+                        });
+                    }
+                }
+                return Ok(None);
+            }
+            
+            if self.a11y_audit {
+                self.crawl_log.lock().unwrap().record_a11y_issues(a11y::audit_page(url, &html_content));
+            }
+            
+            // Calculate the local path for the current HTML file (needed for relative path calculations)
+            let current_html_path = self.local_path(&page_html_parser, url)?;
+            
+            // Process resources in priority order: CSS/JS first, then HTML, then images
+            let mut critical_resources = Vec::new();
+            let mut high_resources = Vec::new();
+            let mut normal_resources = Vec::new();
+            
+            // Categorize resources by priority
+            for resource in &resources {
+                let priority = match resource.resource_type {
+                    ResourceType::CSS | ResourceType::JavaScript => DownloadPriority::Critical,
+                    ResourceType::Link => DownloadPriority::High,
+                    ResourceType::Image | ResourceType::Video | ResourceType::Audio |
+                    ResourceType::Font | ResourceType::Other => DownloadPriority::Normal,
+                };
+                
+                // Nothing is fetched from excluded domains, whatever the type
+                let should_download = !self.is_excluded_domain(&resource.original_url) && match resource.resource_type {
+                    ResourceType::Image | ResourceType::CSS | ResourceType::JavaScript |
+                    ResourceType::Video | ResourceType::Audio | ResourceType::Font => {
+                        // Always download media files (images, CSS, JS, video, audio, fonts) from any site
+                        // But respect the only_resources filter
+                        self.should_process_resource_type(&resource.resource_type)
+                    },
+                    ResourceType::Link => {
+                        // Only crawl HTML pages from the target site, within --include-path
+                        // (the only_resources filter decides whether they are saved)
+                        self.is_target_site(&resource.original_url) && self.in_include_paths(&resource.original_url)
+                    },
+                    ResourceType::Other => {
+                        // Download other resources only from target site (or hosts it preconnects to,
+                        // with --trust-hinted-hosts) and respect the only_resources filter
+                        (self.is_target_site(&resource.original_url) || self.is_trusted_hinted_host(&resource.original_url))
+                            && self.should_process_resource_type(&resource.resource_type)
+                    }
+                };
+                
+                if self.check_external_links
+                    && !should_download
+                    && resource.resource_type == ResourceType::Link
+                    && (!self.is_target_site(&resource.original_url) || self.is_excluded_domain(&resource.original_url))
+                {
+                    self.external_links.lock().unwrap().record(&resource.original_url, url);
+                }
+                
+                self.record_link_graph_edge(url, resource, should_download);
+                
+                if should_download {
+                    self.store.lock().unwrap().add_referrer(&self.normalize_url(&resource.original_url), url);
+                    match priority {
+                        DownloadPriority::Critical => critical_resources.push(resource.clone()),
+                        DownloadPriority::High => high_resources.push(resource.clone()),
+                        DownloadPriority::Normal => normal_resources.push(resource.clone()),
+                    }
+                } else if self.is_excluded_domain(&resource.original_url) {
+                    info!("🚫 Skipping {} (excluded domain)", resource.original_url);
+                } else if !self.is_target_site(&resource.original_url) {
+                    // Log when we skip external HTML pages
+                    if resource.resource_type == ResourceType::Link {
+                        debug!("⏭️  Skipping external page: {} (but will download its media)", resource.original_url);
+                    }
+                } else if !self.should_process_resource_type(&resource.resource_type) {
+                    // Log when we skip resources due to filter
+                    let resource_type_str = match resource.resource_type {
+                        ResourceType::Image => "Image",
+                        ResourceType::CSS => "CSS",
+                        ResourceType::JavaScript => "JavaScript",
+                        ResourceType::Video => "Video",
+                        ResourceType::Audio => "Audio",
+                        ResourceType::Font => "Font",
+                        ResourceType::Link => "Link",
+                        ResourceType::Other => "Other",
+                    };
+                    debug!("🔍 Skipping {} due to resource filter: {}", resource_type_str, resource.original_url);
+                }
+            }
+            
+            // With a <base href>, relative URLs in the saved page would no longer resolve
+            // to the right files: point them at their local copies and drop the base tag
+            let mut html_content_updated = html_content.to_string();
+            if document_base_url.is_some() {
+                html_content_updated = HtmlParser::rewrite_relative_urls(&html_content_updated, |relative_url| {
+                    let absolute_url = page_html_parser.resolve_url(relative_url).ok()?.to_string();
+                    self.output_link(&page_html_parser, &absolute_url, &current_html_path).ok()
+                });
+                html_content_updated = HtmlParser::strip_base_tags(&html_content_updated);
+            }
+            
+            // Rewrite url()/@import references inside <style> blocks in place
+            // (before the absolute URL replacements below, which would leave
+            // page-relative paths that no longer resolve against the page URL)
+            html_content_updated = css_parser::rewrite_style_blocks(&html_content_updated, |reference| {
+                let resource_type = reference.resource_type();
+                if !self.should_process_resource_type(&resource_type) {
+                    return None;
+                }
+                let absolute_url = page_html_parser.resolve_url(&reference.url).ok()?.to_string();
+                // Other resources (e.g. fonts) are only downloaded from the target site
+                if resource_type == ResourceType::Other && !self.is_target_site(&absolute_url) {
+                    return None;
+                }
+                self.output_link(&page_html_parser, &absolute_url, &current_html_path).ok()
+            });
+            
+            // Point lazy-loading attributes (data-src, data-srcset, ...) at the local images
+            if self.should_process_resource_type(&ResourceType::Image) {
+                html_content_updated = page_html_parser.rewrite_lazy_attributes(&html_content_updated, self.lazy_copy_src, |lazy_url| {
+                    let absolute_url = page_html_parser.resolve_url(lazy_url).ok()?.to_string();
+                    self.output_link(&page_html_parser, &absolute_url, &current_html_path).ok()
+                });
+            }
+            
+            // Canonical and hreflang alternate links keep origin URLs unless asked otherwise
+            if self.strip_canonical {
+                html_content_updated = HtmlParser::strip_canonical_links(&html_content_updated);
+            } else if self.rewrite_canonical {
+                html_content_updated = HtmlParser::rewrite_canonical_links(&html_content_updated, |href| {
+                    let absolute_url = page_html_parser.resolve_url(href).ok()?.to_string();
+                    // Only pages of the mirrored site have local copies
+                    if !self.is_target_site(&absolute_url) {
+                        return None;
+                    }
+                    self.output_link(&page_html_parser, &absolute_url, &current_html_path).ok()
+                });
+            }
+            
+            // Pages spanning several hosts link to each other by absolute URL, and so
+            // do links through an alias of the site's host: point links to pages of
+            // the mirrored hosts at their local copies
+            let spans_hosts = !self.crawl_domains.is_empty() || self.span_subdomains;
+            let site_host = Url::parse(&self.base_url).ok().and_then(|base| base.host_str().map(str::to_string)).unwrap_or_default();
+            html_content_updated = HtmlParser::rewrite_anchor_links(&html_content_updated, |href| {
+                if href.starts_with('#') {
+                    return None;
+                }
+                let mut absolute_url = page_html_parser.resolve_url(href).ok()?;
+                if !spans_hosts && !url_normalizer::is_host_alias(absolute_url.host_str()?, &site_host, &self.alias_hosts) {
+                    return None;
+                }
+                let fragment = absolute_url.fragment().map(|fragment| format!("#{}", fragment)).unwrap_or_default();
+                absolute_url.set_fragment(None);
+                let absolute_url = absolute_url.to_string();
+                if !self.is_target_site(&absolute_url) || !self.in_include_paths(&absolute_url) {
+                    return None;
+                }
+                let local_link = self.output_link(&page_html_parser, &absolute_url, &current_html_path).ok()?;
+                Some(format!("{}{}", local_link, fragment))
+            });
+            
+            // Keep the saved listing browsable: entries point at their local copies and
+            // the column-sort links at the listing itself
+            if is_listing {
+                let listing_file_name = current_html_path.rsplit('/').next().unwrap_or("index.html").to_string();
+                html_content_updated = autoindex::rewrite_listing_links(&html_content_updated, |href| {
+                    if autoindex::is_sort_link(href) {
+                        return Some(listing_file_name.clone());
+                    }
+                    let absolute_url = page_html_parser.resolve_url(href).ok()?.to_string();
+                    if !self.is_target_site(&absolute_url) {
+                        return None;
+                    }
+                    self.output_link(&page_html_parser, &absolute_url, &current_html_path).ok()
+                });
+            }
+            
+            // Add high priority resources (HTML pages) to queue
+            for resource in &high_resources {
+                if !self.visited_urls.lock().unwrap().contains(&self.url_key(&resource.original_url)) {
+                    let mut queue = self.download_queue.lock().unwrap();
+                    queue.push(DownloadTask {
+                        url: resource.original_url.clone(),
+                        depth: depth + 1,
+                        priority: DownloadPriority::High,
+                        resource_type: Some(resource.resource_type.clone()),
+                    });
+                    info!("⚡ Queued HIGH priority HTML page: {}", resource.original_url);
+                }
+            }
+            
+            // Assets are downloaded and the page saved by the asset stage, while
+            // the crawl goes on fetching and parsing the next pages
+            return Ok(Some(PendingPage {
+                url: url.to_string(),
+                content_type,
+                page_encoding,
+                html_parser: page_html_parser,
+                local_path: current_html_path,
+                html_content: html_content_updated,
+                original_html,
+                critical_resources,
+                normal_resources,
+            }));
+        } else if is_css {
+            // Enqueue url()/@import targets and rewrite them to local paths
+            let css_content = String::from_utf8_lossy(&content);
+            let local_path = self.local_path(&self.html_parser, url)?;
+            let rewritten_css = self.process_css(url, &local_path, &css_content);
+            
+            // Save the CSS file
+            debug!("💾 Saving CSS to: {}", local_path);
+            let saved_path = self.file_manager.save_file(&local_path, rewritten_css.as_bytes(), Some(&content_type))?;
+            info!("✅ Saved CSS to: {:?}", saved_path);
+            self.store.lock().unwrap().record_download(url, &local_path, Some(&content_type), rewritten_css.len() as u64);
+            self.count_saved_file(url, Some(&content_type), &local_path, rewritten_css.len() as u64);
+            self.count_bytes_written(rewritten_css.len() as u64);
+        } else {
+            // Pages that turn out to be media (e.g. an extensionless podcast download link)
+            // are classified by content type so the resource filter still applies
+            let detected_type = ResourceType::from_content_type(&content_type).unwrap_or(ResourceType::Other);
+            if !self.should_process_resource_type(&detected_type) {
+                debug!("🔍 Not saving {:?} content due to resource filter: {}", detected_type, url);
+                return Ok(None);
+            }
+            
+            // Save non-HTML content as-is
+            let local_path = self.local_path(&self.html_parser, url)?;
+            debug!("💾 Saving non-HTML to: {}", local_path);
+            let saved_path = self.file_manager.save_file(&local_path, &content, Some(&content_type))?;
+            info!("✅ Saved non-HTML to: {:?}", saved_path);
+            self.store.lock().unwrap().record_download(url, &local_path, Some(&content_type), content.len() as u64);
+            self.count_saved_file(url, Some(&content_type), &local_path, content.len() as u64);
+            self.count_bytes_written(content.len() as u64);
+        }
+        
+        info!("✅ Downloaded: {}", url);
+        Ok(None)
+    }
+    
+    /// Download one of a page's resources while holding one of the
+    /// `max_concurrent` permits, returning its URL when it was saved
+    async fn fetch_page_resource<'a>(&self, page_html_parser: &HtmlParser, resource: &'a ResourceLink, priority: &str) -> Option<&'a str> {
+        let resource_type_str = match resource.resource_type {
+            ResourceType::CSS => "CSS",
+            ResourceType::JavaScript => "JavaScript",
+            ResourceType::Image => "Image",
+            ResourceType::Video => "Video",
+            ResourceType::Audio => "Audio",
+            ResourceType::Font => "Font",
+            ResourceType::Link => "Page",
+            ResourceType::Other => "Other",
+        };
+        let _permit = self.semaphore.acquire().await.ok()?;
+        info!("📥 Processing {} {} resource: {}", priority, resource_type_str, resource.original_url);
+        
+        match self.download_resource(page_html_parser, &resource.original_url).await {
+            Ok(()) => Some(resource.original_url.as_str()),
+            Err(e) => {
+                warn!("⚠️  Failed to download {} {} resource {}: {}", priority, resource_type_str, resource.original_url, e);
+                self.count_failure(&resource.original_url, None, &e.to_string());
+                None
+            }
+        }
+    }
+    
+    /// Point a page's references to a downloaded resource at its local copy,
+    /// including the `.webp` name of converted images
+    fn link_local_resource(&self, mut html_content: String, page_html_parser: &HtmlParser, resource: &ResourceLink, current_html_path: &str) -> String {
+        let Ok(local_path) = self.output_link(page_html_parser, &resource.original_url, current_html_path) else {
+            return html_content;
+        };
+        let before_count = html_content.matches(&resource.original_url).count();
+        html_content = html_content.replace(&resource.original_url, &local_path);
+        let after_count = html_content.matches(&local_path).count();
+        info!("🔄 Updated HTML: {} -> {} ({} replacements)", resource.original_url, local_path, after_count);
+        
+        // Debug: Check if the replacement actually worked
+        if before_count > 0 && after_count == 0 {
+            warn!("⚠️  Warning: URL replacement may have failed for: {}", resource.original_url);
+        }
+        
+        // If this is a WebP conversion, also update any remaining references to the old extension
+        if let Some(extension) = self.webp_source_extension(&resource.original_url) {
+            let old_extension = format!(".{}", extension);
+            
+            // Extract just the filename part for extension replacement
+            if let Some(filename) = resource.original_url.split('/').next_back() {
+                let new_filename = filename.replace(&old_extension, ".webp");
+                let old_filename_with_path = resource.original_url.clone();
+                let new_filename_with_path = resource.original_url.replace(filename, &new_filename);
+                
+                // Replace the filename with .webp extension
+                let before_ext_count = html_content.matches(&old_filename_with_path).count();
+                html_content = html_content.replace(&old_filename_with_path, &new_filename_with_path);
+                let after_ext_count = html_content.matches(&new_filename_with_path).count();
+                
+                if before_ext_count > 0 {
+                    info!("🔄 Updated file extension: {} -> {} ({} replacements)", 
+                             old_filename_with_path, new_filename_with_path, after_ext_count);
+                }
+            }
+        }
+        html_content
+    }
+    
+    /// Asset stage of the crawl pipeline: download a parsed page's stylesheets,
+    /// scripts, images and media, point its references at the local copies and
+    /// save it
+    async fn finish_page(&self, page: PendingPage) -> Result<()> {
+        let PendingPage {
+            url,
+            content_type,
+            page_encoding,
+            html_parser: page_html_parser,
+            local_path: current_html_path,
+            html_content: mut html_content_updated,
+            original_html,
+            critical_resources,
+            normal_resources,
+        } = page;
+        let url = url.as_str();
+        
+        // Download critical resources (CSS/JS) first, then images, media and the rest,
+        // up to max_concurrent at a time, and point the page at the local copies
+        for (priority, resources) in [("CRITICAL", &critical_resources), ("NORMAL", &normal_resources)] {
+            let mut seen = HashSet::new();
+            let unique: Vec<&ResourceLink> = resources.iter().filter(|resource| seen.insert(resource.original_url.as_str())).collect();
+            let saved: HashSet<&str> = stream::iter(unique.iter().map(|resource| self.fetch_page_resource(&page_html_parser, resource, priority)))
+                .buffer_unordered(self.max_concurrent.max(1))
+                .filter_map(|saved| async move { saved })
+                .collect()
+                .await;
+            for resource in unique.into_iter().filter(|resource| saved.contains(resource.original_url.as_str())) {
+                html_content_updated = self.link_local_resource(html_content_updated, &page_html_parser, resource, &current_html_path);
+            }
+        }
+        
+        // Additional comprehensive WebP extension replacement for any remaining image references
+        if self.convert_to_webp {
+            debug!("🔍 Performing comprehensive WebP extension replacement...");
+            html_content_updated = Self::perform_comprehensive_webp_replacement_for_formats(&html_content_updated, &self.convert_formats);
+        }
+        
+        if let Some(preset) = self.preset {
+            html_content_updated = preset.clean_html(&html_content_updated);
+        }
+        
+        // Integrity hashes of the originals don't match rewritten local copies
+        html_content_updated = integrity::rewrite_integrity(&html_content_updated, self.integrity_mode, |reference| {
+            fs::read(self.linked_local_file(reference, &current_html_path)?).ok()
+        });
+        html_content_updated = self.rewrite_origin_urls(&html_content_updated);
+        if let Some(scrubber) = &self.pii_scrubber {
+            html_content_updated = scrubber.scrub_html(&html_content_updated);
+        }
+        
+        // Debug: Show a preview of the updated HTML content
+        debug!("🔍 HTML content preview (first 500 chars):");
+        let preview = html_content_updated.chars().take(500).collect::<String>();
+        debug!("{}", preview);
+        
+        // Save the updated HTML with local paths for resources
+        if self.should_process_resource_type(&ResourceType::Link) {
+            if self.fix_charset {
+                let saved_encoding = if self.preserve_encoding { page_encoding } else { UTF_8 };
+                html_content_updated = encoding::fix_meta_charset(&html_content_updated, saved_encoding);
+            }
+            if let Some(original_html) = &original_html {
+                let (diff, stats) = rewrite_diff::unified_diff(original_html, &html_content_updated, &current_html_path);
+                if !diff.is_empty() {
+                    let diff_path = rewrite_diff::save_diff(&self.output_dir, &current_html_path, &diff)?;
+                    info!("📝 Rewrote {} (-{} +{} lines), diff in {}", current_html_path, stats.removed, stats.added, diff_path.display());
+                }
+            }
+            // Pages are re-encoded in their original encoding, or transcoded to UTF-8
+            // with the meta charset updated to match
+            let html_bytes = if page_encoding == UTF_8 {
+                html_content_updated.into_bytes()
+            } else if self.preserve_encoding {
+                encoding::encode_html(&html_content_updated, page_encoding)
+            } else if self.fix_charset {
+                html_content_updated.into_bytes()
+            } else {
+                encoding::set_meta_charset(&html_content_updated, "utf-8").into_bytes()
+            };
+            // Pages that rewrite to exactly what an earlier run saved, and that nothing has
+            // touched since, are left alone so their mtimes don't churn for rsync or git
+            let unchanged = self.store.lock().unwrap().is_unchanged(url, &current_html_path, &html_bytes)
+                && fs::read(self.file_manager.file_path(&current_html_path, &html_bytes, Some(&content_type)))
+                    .is_ok_and(|saved| saved == html_bytes);
+            if unchanged {
+                debug!("⏭️  HTML unchanged since the last run, keeping: {}", current_html_path);
+            } else {
+                debug!("💾 Saving HTML to: {}", current_html_path);
+                let saved_path = self.file_manager.save_file(&current_html_path, &html_bytes, Some(&content_type))?;
+                info!("✅ Saved HTML to: {}", saved_path.display());
+                self.count_bytes_written(html_bytes.len() as u64);
+            }
+            let mut store = self.store.lock().unwrap();
+            store.record_download(url, &current_html_path, Some(&content_type), html_bytes.len() as u64);
+            store.record_content_hash(url, &html_bytes);
+            drop(store);
+            self.crawl_stats.lock().unwrap().pages += 1;
+            self.crawl_log.lock().unwrap().record_file(Some(&content_type), &current_html_path, html_bytes.len() as u64);
+            self.progress.count_file(crawl_report::file_type(Some(&content_type), &current_html_path));
+            self.emit(&Event::RewriteDone { url, local_path: &current_html_path, bytes: html_bytes.len() as u64, unchanged });
+        } else {
+            debug!("🔍 Not saving HTML due to resource filter: {}", url);
+        }
+        
+        info!("✅ Downloaded: {}", url);
+        Ok(())
+    }
+    
+    /// Read a response body, or `None` when it exceeds `max_file_size` or
+    /// what is left of `max_total_size`: an announced `Content-Length` over
+    /// the limit skips the body entirely, otherwise the transfer is aborted
+    /// once the limit is passed. Large bodies show their byte progress while
+    /// they download.
+    async fn read_body(&self, url: &str, mut response: Response) -> Result<Option<Vec<u8>>> {
+        if let Some(limit) = self.max_file_size {
+            if let Some(length) = response.content_length().filter(|length| *length > limit) {
+                self.record_too_large(url, Some(length), limit);
+                return Ok(None);
+            }
+        }
+        let budget = self.remaining_budget();
+        if response.content_length().zip(budget).is_some_and(|(length, budget)| length > budget) {
+            self.skip_over_budget(url);
+            return Ok(None);
+        }
+        
+        let file_bar = self.progress.file_bar(url, response.content_length());
+        let mut body = Vec::new();
+        while let Some(chunk) = response.chunk().await? {
+            body.extend_from_slice(&chunk);
+            if let Some(file_bar) = &file_bar {
+                file_bar.inc(chunk.len());
+            }
+            if let Some(limit) = self.max_file_size.filter(|limit| body.len() as u64 > *limit) {
+                self.record_too_large(url, None, limit);
+                return Ok(None);
+            }
+            if budget.is_some_and(|budget| body.len() as u64 > budget) {
+                self.skip_over_budget(url);
+                return Ok(None);
+            }
+        }
+        Ok(Some(body))
+    }
+    
+    /// Read a page body like `read_body`, or `None` when a `text/html` response
+    /// is larger than `max_html_size`: it is then streamed to disk unparsed as
+    /// a binary download instead of being held in memory and parsed
+    async fn read_page_body(&self, url: &str, content_type: &str, mut response: Response) -> Result<Option<Vec<u8>>> {
+        let Some(limit) = self.max_html_size.filter(|_| content_type.contains("text/html")) else {
+            return self.read_body(url, response).await;
+        };
+        if let Some(max_file_size) = self.max_file_size {
+            if let Some(length) = response.content_length().filter(|length| *length > max_file_size) {
+                self.record_too_large(url, Some(length), max_file_size);
+                return Ok(None);
+            }
+        }
+        
+        let mut head = Vec::new();
+        if response.content_length().is_none_or(|length| length <= limit) {
+            while let Some(chunk) = response.chunk().await? {
+                head.extend_from_slice(&chunk);
+                if head.len() as u64 > limit {
+                    break;
+                }
+            }
+            if head.len() as u64 <= limit {
+                return Ok(Some(head));
+            }
+        }
+        
+        info!("🐘 {} claims {} but is over the {} --max-html-size; saving it unparsed", url, content_type, Self::format_size(limit));
+        self.save_oversized_html(url, content_type, head, response).await?;
+        Ok(None)
+    }
+    
+    /// Stream the rest of an oversized HTML response to its local path after
+    /// the bytes already read, still honoring `max_file_size`
+    async fn save_oversized_html(&self, url: &str, content_type: &str, head: Vec<u8>, mut response: Response) -> Result<()> {
+        let local_path = self.local_path(&self.html_parser, url)?;
+        let mut file = self.file_manager.create_file(&local_path, &head, Some(content_type))?;
+        let mut size = head.len() as u64;
+        drop(head);
+        
+        let budget = self.remaining_budget();
+        let file_bar = self.progress.file_bar(url, response.content_length());
+        if let Some(file_bar) = &file_bar {
+            file_bar.inc(size as usize);
+        }
+        while let Some(chunk) = response.chunk().await? {
+            size += chunk.len() as u64;
+            if let Some(file_bar) = &file_bar {
+                file_bar.inc(chunk.len());
+            }
+            if let Some(max_file_size) = self.max_file_size.filter(|max_file_size| size > *max_file_size) {
+                file.discard()?;
+                self.record_too_large(url, None, max_file_size);
+                return Ok(());
+            }
+            if budget.is_some_and(|budget| size > budget) {
+                file.discard()?;
+                self.skip_over_budget(url);
+                return Ok(());
+            }
+            file.write_all(&chunk)?;
+        }
+        let path = file.finish()?;
+        
+        info!("✅ Saved {} unparsed to: {:?}", Self::format_size(size), path);
+        self.store.lock().unwrap().record_download(url, &local_path, Some(content_type), size);
+        self.count_saved_file(url, Some(content_type), &local_path, size);
+        self.count_bytes_written(size);
+        Ok(())
+    }
+    
+    fn record_too_large(&self, url: &str, size: Option<u64>, limit: u64) {
+        let size_text = size.map_or_else(|| "size unknown".to_string(), Self::format_size);
+        info!("🐘 Skipping {} ({}, over the {} --max-file-size)", url, size_text, Self::format_size(limit));
+        self.store.lock().unwrap().record_skip(url, &format!("larger than --max-file-size {}", Self::format_size(limit)));
+        self.too_large.lock().unwrap().insert(url.to_string(), size);
+    }
+    
+    /// Write `skipped-too-large.json` listing the resources `max_file_size` kept out
+    fn save_too_large_report(&self) -> Result<()> {
+        let too_large = self.too_large.lock().unwrap().clone();
+        if too_large.is_empty() {
+            return Ok(());
+        }
+        let report_path = self.output_dir.join(Self::TOO_LARGE_REPORT_FILE_NAME);
+        fs::write(&report_path, serde_json::to_string_pretty(&too_large)?)?;
+        info!("🐘 Skipped {} resources over --max-file-size, listed in {}", too_large.len(), Self::TOO_LARGE_REPORT_FILE_NAME);
+        Ok(())
+    }
+    
+    /// robots.txt rules of an origin (`https://example.com`) for our user agent,
+    /// fetched once per crawl; a missing or unreachable robots.txt allows everything
+    async fn robots_rules(&self, origin: &str) -> RobotsRules {
+        if let Some(rules) = self.robots.lock().unwrap().get(origin) {
+            return rules.clone();
+        }
+        let robots_url = format!("{}/robots.txt", origin);
+        // Recorded and replayed along with the crawl's other requests
+        let response = if self.fixture_server.is_some() || self.record_fixtures.is_some() {
+            self.fetch(&robots_url).await
+        } else {
+            self.client.get(&robots_url).send().await.map_err(Into::into)
+        };
+        let rules = match response.and_then(|response| Ok(response.error_for_status()?)) {
+            Ok(response) => RobotsRules::parse(&response.text().await.unwrap_or_default(), robots::ROBOTS_USER_AGENT),
+            Err(_) => RobotsRules::default(),
+        };
+        self.robots.lock().unwrap().insert(origin.to_string(), rules.clone());
+        rules
+    }
+    
+    /// oEmbed preview of an embed, from the provider's endpoint or the one its page
+    /// advertises, fetched once per crawl
+    async fn oembed_preview(&self, embed: &Embed) -> Option<OEmbed> {
+        if let Some(preview) = self.oembed_previews.lock().unwrap().get(&embed.link) {
+            return preview.clone();
+        }
+        let preview = self.fetch_oembed(embed).await;
+        match &preview {
+            Some(preview) => info!("🎬 oEmbed preview of {}: {}", embed.link, preview.title.as_deref().unwrap_or("(untitled)")),
+            None => warn!("⚠️  No oEmbed preview for {}", embed.link),
+        }
+        self.oembed_previews.lock().unwrap().insert(embed.link.clone(), preview.clone());
+        preview
+    }
+    
+    async fn fetch_oembed(&self, embed: &Embed) -> Option<OEmbed> {
+        let endpoint = match embed.provider.oembed_request(&embed.link) {
+            Some(endpoint) => endpoint,
+            None => {
+                let response = self.client.get(&embed.link).send().await.and_then(|response| response.error_for_status()).ok()?;
+                embeds::discover_oembed(&response.text().await.ok()?, &embed.link)?
+            }
+        };
+        let response = self.client.get(&endpoint).send().await.and_then(|response| response.error_for_status()).ok()?;
+        response.json().await.ok()
+    }
+    
+    /// The robots.txt rule disallowing `url`, unless `ignore_robots` is set
+    async fn robots_txt_block(&self, url: &str) -> Option<String> {
+        if self.ignore_robots {
+            return None;
+        }
+        let parsed = Url::parse(url).ok()?;
+        let mut path = parsed.path().to_string();
+        if let Some(query) = parsed.query() {
+            path.push('?');
+            path.push_str(query);
+        }
+        let rules = self.robots_rules(&parsed.origin().ascii_serialization()).await;
+        rules.blocking_rule(&path).map(|rule| rule.describe())
+    }
+    
+    /// Write `rate-report.json` and print the compliance summary: the peak
+    /// request rate per host and whether its robots.txt `Crawl-delay` was honored
+    async fn save_rate_report(&self) -> Result<()> {
+        let origins: Vec<String> = self.request_log.lock().unwrap().origins().map(str::to_string).collect();
+        let mut crawl_delays = BTreeMap::new();
+        for origin in origins {
+            if let Some(delay) = self.robots_rules(&origin).await.crawl_delay {
+                crawl_delays.insert(origin, delay);
+            }
+        }
+        
+        let report = self.request_log.lock().unwrap().report(&crawl_delays);
+        report.save(&self.output_dir)?;
+        info!("🚦 Request rates (details in {}):", rate_report::REPORT_FILE_NAME);
+        for host in &report.hosts {
+            let compliance = match (host.crawl_delay_seconds, host.crawl_delay_honored) {
+                (Some(delay), Some(true)) => format!("Crawl-delay {}s honored", delay),
+                (Some(delay), _) => format!("Crawl-delay {}s NOT honored (shortest gap {:.2}s)", delay, host.min_interval_seconds.unwrap_or_default()),
+                (None, _) => "no Crawl-delay".to_string(),
+            };
+            info!("   {}: {} requests, max {}/s, avg {:.2}/s, {}",
+                     host.host, host.requests, host.max_requests_per_second, host.average_requests_per_second, compliance);
+        }
+        let violations = report.violations();
+        if !violations.is_empty() {
+            warn!("⚠️  robots.txt Crawl-delay was not honored for {} hosts", violations.len());
+        }
+        Ok(())
+    }
+    
+    /// Put `first` and every other queued page without a decision in front of
+    /// the curator as one batch; allowed pages are queued again, denied ones
+    /// are recorded as skipped
+    async fn review_pages(&self, reviewer: &Reviewer, first: DownloadTask, decisions: &mut HashMap<String, ReviewDecision>) -> Result<()> {
+        let mut batch = vec![first];
+        {
+            let mut queue = self.download_queue.lock().unwrap();
+            let mut batch_keys: HashSet<String> = batch.iter().map(|task| self.url_key(&self.normalize_url(&task.url))).collect();
+            let visited = self.visited_urls.lock().unwrap();
+            let mut remaining = BinaryHeap::new();
+            for task in queue.drain() {
+                let key = self.url_key(&self.normalize_url(&task.url));
+                let is_page = matches!(task.resource_type, None | Some(ResourceType::Link));
+                if is_page && !decisions.contains_key(&key) && !visited.contains(&key) {
+                    if batch_keys.insert(key) {
+                        batch.push(task);
+                    }
+                } else {
+                    remaining.push(task);
+                }
+            }
+            *queue = remaining;
+        }
+        batch.sort_by(|a, b| a.url.cmp(&b.url));
+        
+        let batch_decisions = match reviewer {
+            Reviewer::Interactive => Self::prompt_review(&batch).await?,
+            Reviewer::File(path) => Self::wait_for_review_file(path, &batch).await?,
+        };
+        
+        let mut denied = 0;
+        for (task, decision) in batch.into_iter().zip(batch_decisions) {
+            decisions.insert(self.url_key(&self.normalize_url(&task.url)), decision);
+            match decision {
+                ReviewDecision::Allow => self.download_queue.lock().unwrap().push(task),
+                ReviewDecision::Deny => {
+                    self.store.lock().unwrap().record_skip(&task.url, "denied in review");
+                    denied += 1;
+                }
+            }
+        }
+        if denied > 0 {
+            info!("🙅 Denied {} pages in review", denied);
+        }
+        Ok(())
+    }
+    
+    async fn prompt_review(batch: &[DownloadTask]) -> Result<Vec<ReviewDecision>> {
+        let urls: Vec<String> = batch.iter().map(|task| task.url.clone()).collect();
+        tokio::task::spawn_blocking(move || review::prompt(&urls, &mut std::io::stdin().lock(), &mut std::io::stdout())).await?
+    }
+    
+    /// Re-read the review file until its rules decide every page of the batch,
+    /// listing the undecided ones in `<file>.pending` meanwhile
+    async fn wait_for_review_file(path: &Path, batch: &[DownloadTask]) -> Result<Vec<ReviewDecision>> {
+        let pending_path = Reviewer::pending_path(path);
+        let mut listed = Vec::new();
+        loop {
+            let rules = ReviewRules::load(path)?;
+            let undecided: Vec<&str> = batch.iter().map(|task| task.url.as_str()).filter(|url| rules.decide(url).is_none()).collect();
+            if undecided.is_empty() {
+                let _ = fs::remove_file(&pending_path);
+                return Ok(batch.iter().filter_map(|task| rules.decide(&task.url)).collect());
+            }
+            if undecided != listed {
+                fs::write(&pending_path, format!("{}\n", undecided.join("\n")))?;
+                info!("⏸️  Waiting for {} pages to be allowed (+ <url prefix>) or denied (- <url prefix>) in {} (listed in {})", undecided.len(), path.display(), pending_path.display());
+                listed = undecided;
+            }
+            tokio::time::sleep(tokio::time::Duration::from_secs(review::REVIEW_POLL_SECONDS)).await;
+        }
+    }
+    
+    /// Point `<a href>` links to pages left out of the mirror (`unmirrored`
+    /// holds their URL keys) at the live site, returning how many saved pages changed
+    fn link_unmirrored_pages(&self, unmirrored: &HashSet<String>) -> Result<usize> {
+        let store = self.store.lock().unwrap().clone();
+        let mut pages_changed = 0;
+        for (page_url, entry) in &store.entries {
+            let is_page = entry.local_path.ends_with(".html") || entry.content_type.as_deref().is_some_and(|c| c.starts_with("text/html"));
+            if entry.local_path.is_empty() || !is_page {
+                continue;
+            }
+            let path = self.output_dir.join(&entry.local_path);
+            let (Ok(content), Ok(page_parser)) = (fs::read(&path), HtmlParser::new(page_url)) else { continue };
+            // `--preserve-encoding` pages are saved in the encoding they were served in
+            let page_encoding = if self.preserve_encoding {
+                encoding::detect_html_encoding(entry.content_type.as_deref().unwrap_or_default(), &content)
+            } else {
+                UTF_8
+            };
+            let html_content = encoding::decode_html(&content, page_encoding);
+            let updated = HtmlParser::rewrite_anchor_links(&html_content, |href| {
+                let absolute = page_parser.resolve_url(href).ok()?.to_string();
+                unmirrored.contains(&self.url_key(&absolute)).then_some(absolute)
+            });
+            if updated != html_content {
+                self.write_output(&path, &encoding::encode_html(&updated, page_encoding))?;
+                pages_changed += 1;
+            }
+        }
+        Ok(pages_changed)
+    }
+    
+    fn count_bytes_written(&self, bytes: u64) {
+        *self.bytes_written.lock().unwrap() += bytes;
+    }
+    
+    /// Bytes left in the `max_total_size` budget, `None` without a budget
+    fn remaining_budget(&self) -> Option<u64> {
+        let written = *self.bytes_written.lock().unwrap();
+        self.max_total_size.map(|budget| budget.saturating_sub(written))
+    }
+    
+    /// Record a download left out because it doesn't fit in the output budget
+    fn skip_over_budget(&self, url: &str) {
+        let mut budget_skipped = self.budget_skipped.lock().unwrap();
+        if *budget_skipped == 0 {
+            if let Some(budget) = self.max_total_size {
+                info!("💾 Output size budget of {} reached, not starting new downloads", Self::format_size(budget));
+            }
+        }
+        *budget_skipped += 1;
+        self.store.lock().unwrap().record_skip(url, "over --max-total-size budget");
+    }
+    
+    fn count_failure(&self, url: &str, status: Option<u16>, error: &str) {
+        self.crawl_stats.lock().unwrap().failures += 1;
+        self.crawl_log.lock().unwrap().record_failure(url, status, error);
+        self.broken_links.lock().unwrap().record(url, status, error);
+        self.store.lock().unwrap().record_failure(&self.normalize_url(url));
+        self.emit(&Event::ResourceFailed { url, status, error });
+    }
+    
+    fn emit(&self, event: &Event) {
+        if let Some(event_log) = &self.event_log {
+            event_log.emit(event);
+        }
+    }
+    
+    /// Fetch every URL that failed with a network error, 429 or 5xx once more.
+    /// A retried page is saved with its assets, but links only it revealed aren't followed.
+    async fn retry_failures(&self) {
+        let urls = self.broken_links.lock().unwrap().retriable();
+        if urls.is_empty() {
+            return;
+        }
+        info!("🔁 Retrying {} failed URLs", urls.len());
+        let mut recovered = 0;
+        for url in &urls {
+            let attempts = self.broken_links.lock().unwrap().attempts(url);
+            // Pages were marked visited by the crawl; assets only when they came from a stylesheet
+            let key = self.url_key(url);
+            let result = if self.visited_urls.lock().unwrap().remove(&key) {
+                match self.download_and_process_url(url, 1, DownloadPriority::High, None).await {
+                    Ok(Some(page)) => self.finish_page(page).await,
+                    Ok(None) => Ok(()),
+                    Err(e) => Err(e),
+                }
+            } else {
+                self.download_resource(&self.html_parser, url).await
+            };
+            if let Err(e) = result {
+                error!("❌ Retry failed for {}: {}", url, e);
+                self.count_failure(url, None, &e.to_string());
+            }
+            let outcome = if self.broken_links.lock().unwrap().attempts(url) == attempts {
+                recovered += 1;
+                RetryOutcome::Recovered
+            } else {
+                RetryOutcome::StillFailing
+            };
+            self.broken_links.lock().unwrap().set_retry(url, outcome);
+        }
+        info!("🔁 Recovered {} of {} failed URLs on retry", recovered, urls.len());
+    }
+    
+    /// Write `broken-links.json` and `broken-links.csv` when any URL failed
+    fn save_broken_links(&self) -> Result<()> {
+        let report = {
+            let broken_links = self.broken_links.lock().unwrap();
+            if broken_links.is_empty() {
+                return Ok(());
+            }
+            broken_links.report(&self.store.lock().unwrap())
+        };
+        broken_links::save_report(&self.output_dir, &report)?;
+        let broken = report.iter().filter(|link| link.retry != RetryOutcome::Recovered).count();
+        info!(
+            "💔 {} broken links written to {} and {}",
+            broken,
+            broken_links::JSON_FILE_NAME,
+            broken_links::CSV_FILE_NAME
+        );
+        Ok(())
+    }
+    
+    fn count_saved_file(&self, url: &str, content_type: Option<&str>, local_path: &str, bytes: u64) {
+        self.emit(&Event::ResourceDownloaded { url, local_path, content_type, bytes });
+        self.crawl_stats.lock().unwrap().files += 1;
+        self.crawl_log.lock().unwrap().record_file(content_type, local_path, bytes);
+        self.progress.count_file(crawl_report::file_type(content_type, local_path));
+    }
+    
+    /// `1536` -> `1.5 KB`, `2147483648` -> `2.0 GB`
+    fn format_size(bytes: u64) -> String {
+        const UNITS: [&str; 5] = ["B", "KB", "MB", "GB", "TB"];
+        let mut size = bytes as f64;
+        let mut unit = 0;
+        while size >= 1024.0 && unit < UNITS.len() - 1 {
+            size /= 1024.0;
+            unit += 1;
+        }
+        if unit == 0 {
+            format!("{} B", bytes)
+        } else {
+            format!("{:.1} {}", size, UNITS[unit])
+        }
+    }
+    
+    /// Periodically request `keep_alive_url` in the background so session
+    /// cookies don't expire halfway through a long crawl
+    fn spawn_keep_alive(&self) -> Option<tokio::task::JoinHandle<()>> {
+        let url = self.keep_alive_url.clone()?;
+        let client = self.client.clone();
+        let bearer_token = self.is_target_site(&url).then(|| Arc::clone(&self.bearer_token));
+        let interval = tokio::time::Duration::from_secs(self.keep_alive_interval.max(1));
+        info!("💓 Pinging {} every {}s to keep the session alive", url, interval.as_secs());
+        
+        Some(tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            // The first tick completes immediately; the crawl itself keeps the session fresh at the start
+            ticker.tick().await;
+            loop {
+                ticker.tick().await;
+                let token = bearer_token.as_ref().and_then(|token| token.lock().unwrap().clone());
+                let request = match token {
+                    Some(token) => client.get(&url).bearer_auth(token),
+                    None => client.get(&url),
+                };
+                match request.send().await {
+                    Ok(response) => info!("💓 Keep-alive {}: HTTP {}", url, response.status()),
+                    Err(e) => warn!("⚠️  Keep-alive request to {} failed: {}", url, e),
+                }
+            }
+        }))
+    }
+    
+    /// Remember the origins a page announces with preconnect/dns-prefetch hints
+    /// and, with `preconnect`, open a pooled connection to each new one in the
+    /// background so its first asset doesn't pay for DNS, TCP and TLS setup
+    fn apply_connection_hints(&self, origins: &[String]) {
+        for origin in origins {
+            if self.is_excluded_domain(origin) || self.is_target_site(origin) {
+                continue;
+            }
+            if !self.hinted_origins.lock().unwrap().insert(origin.clone()) {
+                continue;
+            }
+            if !self.preconnect {
+                continue;
+            }
+            info!("🔌 Preconnecting to {}", origin);
+            let client = self.client.clone();
+            let origin = origin.clone();
+            tokio::spawn(async move {
+                if let Err(e) = client.head(&origin).send().await {
+                    warn!("⚠️  Preconnect to {} failed: {}", origin, e);
+                }
+            });
+        }
+    }
+    
+    /// Whether `url` is on a host a page announced with a preconnect/dns-prefetch
+    /// hint and `trust_hinted_hosts` is set
+    fn is_trusted_hinted_host(&self, url: &str) -> bool {
+        self.trust_hinted_hosts
+            && Url::parse(url).is_ok_and(|parsed| self.hinted_origins.lock().unwrap().contains(&parsed.origin().ascii_serialization()))
+    }
+
+    /// Add a reference of the page at `page_url` to the link graph (with
+    /// `link_graph`), noting why it isn't followed
+    fn record_link_graph_edge(&self, page_url: &str, resource: &ResourceLink, followed: bool) {
+        if self.link_graph.is_none() {
+            return;
+        }
+        let target = &resource.original_url;
+        let not_followed = if followed {
+            None
+        } else if self.is_excluded_domain(target) {
+            Some("excluded domain")
+        } else if !self.is_target_site(target) {
+            Some("external")
+        } else if !self.should_process_resource_type(&resource.resource_type) {
+            Some("resource filter")
+        } else {
+            Some("outside include paths")
+        };
+        let kind = if resource.resource_type == ResourceType::Link { EdgeKind::Page } else { EdgeKind::Asset };
+        self.link_graph_edges.lock().unwrap().record(&self.normalize_url(page_url), &self.normalize_url(target), kind, not_followed);
+    }
+
+    /// GET a URL with the current bearer token. On a 401 from the target site,
+    /// refresh the token with `token_command` (when configured) and re-issue
+    /// the request once.
+    async fn fetch(&self, url: &str) -> Result<Response> {
+        self.fetch_with_headers(url, HeaderMap::new()).await
+    }
+    
+    async fn fetch_with_headers(&self, url: &str, headers: HeaderMap) -> Result<Response> {
+        if let Some(archive) = &self.replay {
+            let (response, hops) = archive.replay(url)?;
+            if !hops.is_empty() {
+                self.redirect_chains.lock().unwrap().insert(url.to_string(), hops);
+            }
+            return Ok(response);
+        }
+        if let Some(server) = &self.fixture_server {
+            let (response, hops) = server.fetch(&self.client, url).await?;
+            if !hops.is_empty() {
+                self.redirect_chains.lock().unwrap().insert(url.to_string(), hops);
+            }
+            return Ok(response);
+        }
+        // Conditional and range requests only make sense against the live site
+        if self.record_fixtures.is_none() || !headers.is_empty() {
+            return self.fetch_live(url, headers).await;
+        }
+        match self.fetch_live(url, headers).await {
+            Ok(response) => {
+                let hops = self.redirect_chains.lock().unwrap().get(url).cloned().unwrap_or_default();
+                let (response, recorded) = fixtures::capture(url, hops, response).await?;
+                self.recorded_fixtures.lock().unwrap().insert(recorded);
+                Ok(response)
+            }
+            Err(e) => {
+                self.recorded_fixtures.lock().unwrap().insert(RecordedResponse::failed(url, &e.to_string()));
+                Err(e)
+            }
+        }
+    }
+    
+    async fn fetch_live(&self, url: &str, headers: HeaderMap) -> Result<Response> {
+        let response = self.send_checked(url, headers.clone()).await?;
+        if response.status() != StatusCode::UNAUTHORIZED {
+            return Ok(response);
+        }
+        
+        let Some(ref command) = self.token_command else {
+            return Ok(response);
+        };
+        if !self.is_target_site(url) {
+            return Ok(response);
+        }
+        info!("🔑 HTTP 401 for {}, refreshing token", url);
+        let token = auth::run_token_command(command).await?;
+        *self.bearer_token.lock().unwrap() = Some(token);
+        
+        self.send_checked(url, headers).await
+    }
+    
+    /// Send a GET through the per-host circuit breaker: requests to hosts that
+    /// keep failing at the connection level are skipped (and recorded in the
+    /// manifest) instead of timing out one by one
+    async fn send_checked(&self, url: &str, headers: HeaderMap) -> Result<Response> {
+        let host = Url::parse(url)
+            .ok()
+            .and_then(|parsed| parsed.host_str().map(|h| h.to_string()))
+            .unwrap_or_default();
+        
+        let open_reason = self.circuit_breaker.lock().unwrap().open_reason(&host);
+        if let Some(reason) = open_reason {
+            self.store.lock().unwrap().record_skip(url, &reason);
+            return Err(anyhow!("Skipped: {}", reason));
+        }
+        
+        if let Ok(parsed) = Url::parse(url) {
+            self.request_log.lock().unwrap().record(&parsed.origin().ascii_serialization());
+        }
+        match self.authorized_get(url).headers(headers).send().await {
+            Ok(response) => {
+                self.circuit_breaker.lock().unwrap().record_success(&host);
+                Ok(response)
+            }
+            Err(e) => {
+                let kind = FailureKind::classify(&e);
+                if self.circuit_breaker.lock().unwrap().record_failure(&host, kind) {
+                    info!("🔌 Circuit breaker tripped for {} after {} consecutive failures; skipping its remaining resources",
+                             host, self.circuit_breaker_threshold);
+                }
+                Err(anyhow!("{} error: {}", kind, e))
+            }
+        }
+    }
+    
+    /// On update runs, revalidate a large, untransformed file from a previous
+    /// run with a single `Range: bytes=<local length>-` + `If-Range` request:
+    /// 416 means it is unchanged and complete, 206 returns only the missing
+    /// tail, and 200 returns the whole file because it changed. Returns
+    /// `false` when the file isn't eligible and should be downloaded normally.
+    async fn differential_fetch(&self, url: &str) -> Result<bool> {
+        let Some(min_size) = self.differential_min_size else {
+            return Ok(false);
+        };
+        let Some(entry) = self.store.lock().unwrap().entries.get(url).cloned() else {
+            return Ok(false);
+        };
+        let content_type = entry.content_type.clone().unwrap_or_default();
+        // Rewritten or re-encoded files no longer match the server's bytes
+        if entry.size < min_size
+            || content_type.starts_with("text/")
+            || content_type.starts_with("image/")
+            || self.webp_source_extension(url).is_some()
+        {
+            return Ok(false);
+        }
+        let Some(validator) = entry.validator().and_then(|v| HeaderValue::from_str(v).ok()) else {
+            return Ok(false);
+        };
+        let path = self.output_dir.join(entry.local_path.trim_start_matches('/'));
+        let Ok(metadata) = fs::metadata(&path) else {
+            return Ok(false);
+        };
+        let local_len = metadata.len();
+        
+        let mut headers = HeaderMap::new();
+        headers.insert(RANGE, HeaderValue::from_str(&format!("bytes={}-", local_len))?);
+        headers.insert(IF_RANGE, validator);
+        let response = self.fetch_with_headers(url, headers).await?;
+        
+        match response.status() {
+            StatusCode::RANGE_NOT_SATISFIABLE | StatusCode::NOT_MODIFIED => {
+                debug!("⏭️  Unchanged since last run: {} ({} bytes)", url, local_len);
+            }
+            StatusCode::PARTIAL_CONTENT => {
+                let tail = response.bytes().await?;
+                let mut file = fs::OpenOptions::new().append(true).open(&path)?;
+                file.write_all(&tail)?;
+                info!("📎 Fetched {} missing bytes of {}", tail.len(), url);
+                self.store.lock().unwrap().record_download(url, &entry.local_path, Some(&content_type), local_len + tail.len() as u64);
+                self.count_saved_file(url, Some(&content_type), &entry.local_path, tail.len() as u64);
+                self.count_bytes_written(tail.len() as u64);
+            }
+            StatusCode::OK => {
+                let etag = Self::header_string(&response, "etag");
+                let last_modified = Self::header_string(&response, "last-modified");
+                let content_type = Self::header_string(&response, "content-type").unwrap_or(content_type);
+                let content = response.bytes().await?;
+                self.write_output(&path, &content)?;
+                self.count_bytes_written(content.len() as u64);
+                info!("🔄 {} changed since last run, downloaded {} bytes", url, content.len());
+                self.count_saved_file(url, Some(&content_type), &entry.local_path, content.len() as u64);
+                let mut store = self.store.lock().unwrap();
+                store.record_download(url, &entry.local_path, Some(&content_type), content.len() as u64);
+                store.record_validators(url, etag.as_deref(), last_modified.as_deref());
+            }
+            status => {
+                warn!("⚠️  HTTP {} revalidating {}, downloading it again", status, url);
+                return Ok(false);
+            }
+        }
+        
+        self.download_cache.lock().unwrap().insert(self.url_key(url), entry.local_path);
+        Ok(true)
+    }
+    
+    fn header_string(response: &Response, name: &str) -> Option<String> {
+        response.headers().get(name).and_then(|v| v.to_str().ok()).map(|v| v.to_string())
+    }
+    
+    /// GET request carrying the bearer token, which is only ever sent to the
+    /// target site, never to CDNs, analytics or embed hosts
+    fn authorized_get(&self, url: &str) -> reqwest::RequestBuilder {
+        let request = self.client.get(url);
+        if !self.is_target_site(url) {
+            return request;
+        }
+        match self.bearer_token.lock().unwrap().as_ref() {
+            Some(token) => request.bearer_auth(token),
+            None => request,
+        }
+    }
+    
+    /// The `noarchive`/`nosnippet` directive that excludes a page from the
+    /// mirror, when `respect_noarchive` is set
+    fn blocking_robots_directive(&self, html_content: &str) -> Option<String> {
+        if !self.respect_noarchive {
+            return None;
+        }
+        HtmlParser::meta_robots_directives(html_content)
+            .into_iter()
+            .find(|directive| directive == "noarchive" || directive == "nosnippet")
+    }
+    
+    /// Report (and optionally delete) saved assets that no saved page or
+    /// live stylesheet references
+    fn check_dead_assets(&self) -> Result<()> {
+        if !self.should_process_resource_type(&ResourceType::Link) {
+            warn!("⚠️  Skipping dead-asset check: no HTML pages were saved to cross-reference");
+            return Ok(());
+        }
+        if self.link_root().is_some() {
+            warn!("⚠️  Skipping dead-asset check: --rewrite-host/--link-base links can't be traced to local files");
+            return Ok(());
+        }
+        
+        let mut store = self.store.lock().unwrap();
+        let dead = dead_assets::find_dead_assets(&self.output_dir, &store);
+        if dead.is_empty() {
+            info!("🧹 No dead assets found");
+            return Ok(());
+        }
+        
+        let total_size: u64 = dead.iter().map(|asset| asset.size).sum();
+        info!("🧹 Found {} dead assets ({} bytes) not referenced by any page:", dead.len(), total_size);
+        for asset in &dead {
+            info!("   {} ({})", asset.local_path.yellow(), asset.url);
+        }
+        
+        if self.delete_dead_assets {
+            dead_assets::delete_dead_assets(&self.output_dir, &mut store, &dead)?;
+            store.save(&self.output_dir)?;
+            info!("🗑️  Deleted {} dead assets", dead.len());
+        }
+        
+        Ok(())
+    }
+    
+    /// Delete files left over from earlier runs that no mirrored page leads to
+    fn prune_orphaned_files(&self) -> Result<()> {
+        if !self.should_process_resource_type(&ResourceType::Link) {
+            warn!("⚠️  Skipping prune: no HTML pages were saved to trace files from");
+            return Ok(());
+        }
+        if !self.output_sections.is_empty() {
+            warn!("⚠️  Skipping prune: pages saved by --split link into the output directory from outside it");
+            return Ok(());
+        }
+        
+        let mut store = self.store.lock().unwrap();
+        let orphans = prune::find_orphaned_files(&self.output_dir, &store, self.link_root().as_deref())?;
+        if orphans.is_empty() {
+            info!("🧹 No orphaned files to prune");
+            return Ok(());
+        }
+        
+        let total_size: u64 = orphans.iter().map(|orphan| orphan.size).sum();
+        prune::delete_orphaned_files(&self.output_dir, &mut store, &orphans)?;
+        store.save(&self.output_dir)?;
+        info!("🗑️  Pruned {} orphaned files ({} bytes) left over from earlier runs", orphans.len(), total_size);
+        Ok(())
+    }
+    
+    /// Enqueue every `url()`/`@import` target of a stylesheet and rewrite the
+    /// references to paths relative to the stylesheet's local copy
+    fn process_css(&self, css_url: &str, css_local_path: &str, css_content: &str) -> String {
+        let css_url_parser = match HtmlParser::new(css_url) {
+            Ok(parser) => parser,
+            Err(_) => return css_content.to_string(),
+        };
+        
+        css_parser::rewrite_css_references(css_content, |reference| {
+            self.localize_reference(&css_url_parser, css_url, css_local_path, &reference.url, reference.resource_type())
+        })
+    }
+    
+    /// Enqueue the external images, stylesheets and scripts an SVG references
+    /// and rewrite them to paths relative to the SVG's local copy
+    fn process_svg(&self, svg_url: &str, svg_local_path: &str, svg_content: &str) -> String {
+        let svg_url_parser = match HtmlParser::new(svg_url) {
+            Ok(parser) => parser,
+            Err(_) => return svg_content.to_string(),
+        };
+        
+        svg_parser::rewrite_svg_references(svg_content, |reference| {
+            self.localize_reference(&svg_url_parser, svg_url, svg_local_path, &reference.url, reference.resource_type.clone())
+        })
+    }
+    
+    /// Validation pass over the saved output (missing references and HTML parse
+    /// errors, and dead external links with `check_external_links`), written to
+    /// `validation-report.json`
+    async fn validate_output(&self) -> Result<()> {
+        let link_root = self.link_root();
+        let mut report = validation::validate_output(&self.output_dir, link_root.as_deref())?;
+        if self.check_external_links {
+            let links = self.external_links.lock().unwrap().clone();
+            report.dead_external_links = external_links::check_links(&links, self.max_concurrent).await?;
+            info!("🌐 Checked {} external links: {} dead", links.len(), report.dead_external_links.len());
+            for link in &report.dead_external_links {
+                let pages = match link.pages.len() {
+                    1 => link.pages[0].clone(),
+                    count => format!("{} and {} more", link.pages[0], count - 1),
+                };
+                info!("   {} ({}) linked from {}", link.url.yellow(), link.error, pages);
+            }
+        }
+        report.save(&self.output_dir)?;
+        self.crawl_stats.lock().unwrap().broken_links = Some(report.missing.len() as u64);
+        info!("🔎 Validated {} references in {} saved pages and stylesheets", report.references_checked, report.documents_checked);
+        if !report.parse_errors.is_empty() {
+            let total_errors: usize = report.parse_errors.iter().map(|page| page.error_count).sum();
+            warn!("⚠️  {} pages have HTML parse errors ({} in total) that browsers will have to repair:", report.parse_errors.len(), total_errors);
+            for page in &report.parse_errors {
+                warn!("   {} ({} errors)", page.document, page.error_count);
+            }
+        }
+        if report.is_valid() {
+            info!("✅ Every local reference resolves to a saved file");
+            return Ok(());
+        }
+        
+        error!("❌ {} references point to files that weren't saved:", report.missing.len());
+        for missing in &report.missing {
+            error!("   {} -> {} ({})", missing.document, missing.reference.yellow(), missing.target);
+        }
+        if self.validate_strict {
+            return Err(anyhow!("Validation failed: {} missing local references (see {})", report.missing.len(), validation::REPORT_FILE_NAME));
+        }
+        Ok(())
+    }
+    
+    /// Screenshot every saved page and flag the ones whose rendering changed
+    /// since the previous run by more than `visual_diff_threshold`
+    async fn compare_screenshots(&self, command: &str) -> Result<Vec<PageChange>> {
+        info!("📸 Rendering saved pages for the visual diff...");
+        let store = self.store.lock().unwrap().clone();
+        let changes = visual_diff::compare_runs(&self.output_dir, &store, command).await?;
+        info!("📸 Compared {} pages with the previous run's screenshots", changes.len());
+        
+        let flagged: Vec<_> = changes.iter().filter(|change| change.changed_percent > self.visual_diff_threshold).collect();
+        if flagged.is_empty() {
+            info!("✅ No page's rendering changed by more than {}%", self.visual_diff_threshold);
+            return Ok(Vec::new());
+        }
+        info!("🖼️  {} pages changed by more than {}%:", flagged.len(), self.visual_diff_threshold);
+        for change in &flagged {
+            info!("   {} ({:.1}% of pixels, diff: {})", change.url.yellow(), change.changed_percent, change.diff_image.as_deref().unwrap_or("-"));
+        }
+        Ok(flagged.iter().map(|change| PageChange { url: change.url.clone(), kind: ChangeKind::Visual }).collect())
+    }
+    
+    /// Diff every saved page's visible text against the previous run and write
+    /// the added and removed paragraphs to `text-diff.html`
+    fn compare_text(&self) -> Result<Vec<PageChange>> {
+        let store = self.store.lock().unwrap().clone();
+        let diffs = text_diff::compare_runs(&self.output_dir, &store)?;
+        if diffs.is_empty() {
+            info!("✅ No page's text changed since the previous run");
+            return Ok(Vec::new());
+        }
+        
+        let report_path = text_diff::save_report(&self.output_dir, &diffs)?;
+        info!("📝 Text changed on {} pages since the previous run:", diffs.len());
+        for diff in &diffs {
+            info!("   {} ({} paragraphs)", diff.url.yellow(), diff.changes.len());
+        }
+        info!("📝 Text diff report written to: {}", report_path.display());
+        Ok(diffs.into_iter().map(|diff| PageChange { url: diff.url, kind: ChangeKind::Text }).collect())
+    }
+    
+    /// Report changed pages matching `alert_rules`, POST them to `alert_webhook`
+    /// and fail the run so schedulers see a non-zero exit code
+    async fn check_alerts(&self, changes: &[PageChange]) -> Result<()> {
+        let alerts = alerts::match_alerts(&self.alert_rules, changes);
+        if alerts.is_empty() {
+            info!("✅ No changes matching the alert rules");
+            return Ok(());
+        }
+        
+        info!("🚨 {} changes match alert rules:", alerts.len());
+        for alert in &alerts {
+            info!("   {} ({} change, rule {})", alert.url.red(), alert.change.as_str(), alert.rule);
+        }
+        if let Some(webhook) = &self.alert_webhook {
+            let payload = AlertPayload { site: &self.base_url, alerts: &alerts };
+            match self.client.post(webhook).json(&payload).send().await.and_then(|response| response.error_for_status()) {
+                Ok(_) => info!("📨 Sent alert to {}", webhook),
+                Err(e) => warn!("⚠️  Failed to send alert to {}: {}", webhook, e),
+            }
+        }
+        Err(anyhow!("Change alert: {} changes match alert rules", alerts.len()))
+    }
+    
+    /// Save `redirects.json` and, with `redirect_stubs`, a meta-refresh page at
+    /// the old path of every redirected page
+    fn save_redirects(&self) -> Result<()> {
+        let redirects = self.redirects.lock().unwrap().clone();
+        if redirects.is_empty() {
+            return Ok(());
+        }
+        redirects.save(&self.output_dir)?;
+        info!("↪️  Recorded {} redirects in {}", redirects.redirects.len(), redirects::REDIRECTS_FILE_NAME);
+        
+        if !self.redirect_stubs {
+            return Ok(());
+        }
+        let store = self.store.lock().unwrap().clone();
+        let mut stub_count = 0;
+        for (old_url, redirect) in &redirects.redirects {
+            let Ok(stub_path) = self.local_path(&self.html_parser, old_url) else { continue };
+            // A moved image or script can't forward the browser, and real files win over stubs
+            if !stub_path.ends_with(".html") || self.output_dir.join(&stub_path).exists() {
+                continue;
             }
-        };
+            // Targets that weren't mirrored (off-site, failed) are forwarded to the live URL
+            let target = match store.entries.get(&redirect.target) {
+                Some(entry) if !entry.local_path.is_empty() => self.output_link(&self.html_parser, &redirect.target, &stub_path)?,
+                _ => redirect.target.clone(),
+            };
+            self.file_manager.save_file(&stub_path, redirects::stub_page(&target).as_bytes(), Some("text/html"))?;
+            stub_count += 1;
+        }
+        info!("↪️  Wrote {} redirect stub pages", stub_count);
+        Ok(())
+    }
+    
+    /// Enqueue a web app manifest's icons and screenshots and rewrite their
+    /// `src` entries to paths relative to the manifest's local copy
+    fn process_manifest(&self, manifest_url: &str, manifest_local_path: &str, manifest_content: &str) -> Result<String> {
+        let manifest_url_parser = HtmlParser::new(manifest_url)?;
+        manifest::rewrite_manifest_images(manifest_content, |src| {
+            self.localize_reference(&manifest_url_parser, manifest_url, manifest_local_path, src, ResourceType::Image)
+        })
+    }
+    
+    /// Queue a reference found inside a downloaded document (stylesheet, SVG, manifest)
+    /// and return its path relative to that document's local copy
+    fn localize_reference(
+        &self,
+        document_parser: &HtmlParser,
+        document_url: &str,
+        document_local_path: &str,
+        reference_url: &str,
+        resource_type: ResourceType,
+    ) -> Option<String> {
+        if !self.should_process_resource_type(&resource_type) {
+            debug!("🔍 Skipping reference due to resource filter: {}", reference_url);
+            return None;
+        }
         
-        // Determine content type
-        let is_html = content_type.contains("text/html") || 
-                     content.starts_with(b"<!DOCTYPE") || 
-                     content.starts_with(b"<html");
-        let is_css = content_type.contains("text/css") || url.ends_with(".css");
+        let absolute_url = document_parser.resolve_url(reference_url).ok()?.to_string();
+        if self.is_excluded_domain(&absolute_url) {
+            return None;
+        }
+        self.store.lock().unwrap().add_referrer(&self.normalize_url(&absolute_url), document_url);
+        if self.link_graph.is_some() {
+            let kind = if resource_type == ResourceType::Link { EdgeKind::Page } else { EdgeKind::Asset };
+            self.link_graph_edges.lock().unwrap().record(&self.normalize_url(document_url), &self.normalize_url(&absolute_url), kind, None);
+        }
+        self.enqueue_resource(&absolute_url, resource_type);
         
-        println!("🔍 Content type: {}, is_html: {}, is_css: {}", content_type, is_html, is_css);
-        println!("🔍 Content preview: {}", String::from_utf8_lossy(&content[..content.len().min(100)]));
+        self.output_link(document_parser, &absolute_url, document_local_path).ok()
+    }
+    
+    /// Queue the files a documentation generator's client-side search and
+    /// version switcher load at runtime, once per documentation root
+    fn enqueue_doc_support_files(&self, page_parser: &HtmlParser, generator: DocGenerator, html_content: &str) {
+        let root = match generator.site_root(html_content) {
+            Some(site_root) => page_parser.resolve_url(&site_root).ok(),
+            None => Url::parse(&self.base_url).ok(),
+        };
+        let Some(mut root) = root else { return };
+        if !root.path().ends_with('/') {
+            let path = format!("{}/", root.path());
+            root.set_path(&path);
+        }
+        if !self.doc_roots.lock().unwrap().insert(root.to_string()) {
+            return;
+        }
         
-        if is_html {
-            // Parse HTML and extract resources
-            let html_content = String::from_utf8_lossy(&content);
-            
-            // Create a new HTML parser with the current page's base URL
-            let page_html_parser = HtmlParser::new(url)?;
-            let resources = page_html_parser.extract_resources(&html_content)?;
-            
-            // Calculate the local path for the current HTML file (needed for relative path calculations)
-            let current_html_path = page_html_parser.url_to_local_path_string(url)?;
-            
-            // Helper function to check if a resource type should be processed
-            let should_process_resource_type = |resource_type: &ResourceType| -> bool {
-                if let Some(ref only_resources) = only_resources {
-                    let type_str = match resource_type {
-                        ResourceType::Image => "images",
-                        ResourceType::CSS => "css",
-                        ResourceType::JavaScript => "js",
-                        ResourceType::Link => "html",
-                        ResourceType::Other => "other",
-                    };
-                    only_resources.iter().any(|r| r.to_lowercase() == type_str)
-                } else {
-                    // If no filter specified, process all resource types
-                    true
-                }
+        info!("📚 Detected {} documentation at {}", generator.name(), root);
+        for support_file in generator.support_files() {
+            let Ok(support_url) = root.join(support_file) else { continue };
+            let resource_type = match ResourceType::from_extension(support_url.path()) {
+                ResourceType::Other if support_url.path().ends_with(".html") => ResourceType::Link,
+                resource_type => resource_type,
             };
-            
-            // Process resources in priority order: CSS/JS first, then HTML, then images
-            let mut critical_resources = Vec::new();
-            let mut high_resources = Vec::new();
-            let mut normal_resources = Vec::new();
-            
-            // Categorize resources by priority
-            for resource in &resources {
-                let priority = match resource.resource_type {
-                    ResourceType::CSS | ResourceType::JavaScript => DownloadPriority::Critical,
-                    ResourceType::Link => DownloadPriority::High,
-                    ResourceType::Image | ResourceType::Other => DownloadPriority::Normal,
-                };
-                
-                let should_download = match resource.resource_type {
-                    ResourceType::Image | ResourceType::CSS | ResourceType::JavaScript => {
-                        // Always download media files (images, CSS, JS) from any site
-                        // But respect the only_resources filter
-                        should_process_resource_type(&resource.resource_type)
-                    },
-                    ResourceType::Link => {
-                        // Only download HTML pages from the target site
-                        // And respect the only_resources filter
-                        resource.original_url.contains(base_url) && should_process_resource_type(&resource.resource_type)
-                    },
-                    ResourceType::Other => {
-                        // Download other resources only from target site
-                        // And respect the only_resources filter
-                        resource.original_url.contains(base_url) && should_process_resource_type(&resource.resource_type)
-                    }
-                };
-                
-                if should_download {
-                    match priority {
-                        DownloadPriority::Critical => critical_resources.push(resource.clone()),
-                        DownloadPriority::High => high_resources.push(resource.clone()),
-                        DownloadPriority::Normal => normal_resources.push(resource.clone()),
-                    }
-                } else if !resource.original_url.contains(base_url) {
-                    // Log when we skip external HTML pages
-                    match resource.resource_type {
-                        ResourceType::Link => println!("⏭️  Skipping external page: {} (but will download its media)", resource.original_url),
-                        _ => {}
-                    }
-                } else if !should_process_resource_type(&resource.resource_type) {
-                    // Log when we skip resources due to filter
-                    let resource_type_str = match resource.resource_type {
-                        ResourceType::Image => "Image",
-                        ResourceType::CSS => "CSS",
-                        ResourceType::JavaScript => "JavaScript",
-                        ResourceType::Link => "Link",
-                        ResourceType::Other => "Other",
-                    };
-                    println!("🔍 Skipping {} due to resource filter: {}", resource_type_str, resource.original_url);
-                }
-            }
-            
-            // Download critical resources first (CSS/JS) and collect local paths for HTML rewriting
-            let mut html_content_updated = html_content.to_string();
-            for resource in &critical_resources {
-                let resource_type_str = match resource.resource_type {
-                    ResourceType::CSS => "CSS",
-                    ResourceType::JavaScript => "JavaScript",
-                    _ => "Critical",
-                };
-                println!("🔥 Processing CRITICAL {} resource: {}", resource_type_str, resource.original_url);
-                
-                if let Err(e) = Self::download_resource(
-                    client,
-                    file_manager,
-                    &page_html_parser,
-                    &resource.original_url,
-                    download_cache,
-                    convert_to_webp,
-                ).await {
-                    eprintln!("⚠️  Failed to download CRITICAL {} resource {}: {}", resource_type_str, resource.original_url, e);
-                } else {
-                    // Get the local path for this resource and update HTML content
-                    if let Ok(local_path) = Self::get_local_path_for_resource_static(&page_html_parser, &resource.original_url, convert_to_webp, &current_html_path) {
-                        let before_count = html_content_updated.matches(&resource.original_url).count();
-                        html_content_updated = html_content_updated.replace(&resource.original_url, &local_path);
-                        let after_count = html_content_updated.matches(&local_path).count();
-                        println!("🔄 Updated HTML: {} -> {} ({} replacements)", resource.original_url, local_path, after_count);
-                        
-                        // Debug: Check if the replacement actually worked
-                        if before_count > 0 && after_count == 0 {
-                            eprintln!("⚠️  Warning: URL replacement may have failed for: {}", resource.original_url);
-                        }
-                        
-                        // If this is a WebP conversion, also update any remaining references to the old extension
-                        if convert_to_webp && (resource.original_url.ends_with(".jpg") || resource.original_url.ends_with(".jpeg") || resource.original_url.ends_with(".png") ||
-                                             resource.original_url.ends_with(".JPG") || resource.original_url.ends_with(".JPEG") || resource.original_url.ends_with(".PNG")) {
-                            let old_extension = if resource.original_url.ends_with(".jpg") || resource.original_url.ends_with(".JPG") {
-                                if resource.original_url.ends_with(".jpg") { ".jpg" } else { ".JPG" }
-                            } else if resource.original_url.ends_with(".jpeg") || resource.original_url.ends_with(".JPEG") {
-                                if resource.original_url.ends_with(".jpeg") { ".jpeg" } else { ".JPEG" }
-                            } else {
-                                if resource.original_url.ends_with(".png") { ".png" } else { ".PNG" }
-                            };
-                            
-                            // Extract just the filename part for extension replacement
-                            if let Some(filename) = resource.original_url.split('/').last() {
-                                let new_filename = filename.replace(old_extension, ".webp");
-                                let old_filename_with_path = resource.original_url.clone();
-                                let new_filename_with_path = resource.original_url.replace(filename, &new_filename);
-                                
-                                // Replace the filename with .webp extension
-                                let before_ext_count = html_content_updated.matches(&old_filename_with_path).count();
-                                html_content_updated = html_content_updated.replace(&old_filename_with_path, &new_filename_with_path);
-                                let after_ext_count = html_content_updated.matches(&new_filename_with_path).count();
-                                
-                                if before_ext_count > 0 {
-                                    println!("🔄 Updated file extension: {} -> {} ({} replacements)", 
-                                             old_filename_with_path, new_filename_with_path, after_ext_count);
-                                }
-                            }
-                        }
-                    }
-                }
-            }
-            
-            // Add high priority resources (HTML pages) to queue
-            for resource in &high_resources {
-                if !visited_urls.lock().unwrap().contains(&resource.original_url) {
-                    let mut queue = download_queue.lock().unwrap();
-                    queue.push(DownloadTask {
-                        url: resource.original_url.clone(),
-                        depth: depth + 1,
-                        priority: DownloadPriority::High,
-                        resource_type: Some(resource.resource_type.clone()),
-                    });
-                    println!("⚡ Queued HIGH priority HTML page: {}", resource.original_url);
-                }
-            }
-            
-            // Download normal priority resources (images, etc.) and update HTML content
-            for resource in &normal_resources {
-                let resource_type_str = match resource.resource_type {
-                    ResourceType::Image => "Image",
-                    ResourceType::Other => "Other",
-                    _ => "Normal",
-                };
-                println!("📥 Processing NORMAL {} resource: {}", resource_type_str, resource.original_url);
-                
-                if let Err(e) = Self::download_resource(
-                    client,
-                    file_manager,
-                    &page_html_parser,
-                    &resource.original_url,
-                    download_cache,
-                    convert_to_webp,
-                ).await {
-                    eprintln!("⚠️  Failed to download NORMAL {} resource {}: {}", resource_type_str, resource.original_url, e);
-                } else {
-                    // Get the local path for this resource and update HTML content
-                    if let Ok(local_path) = Self::get_local_path_for_resource_static(&page_html_parser, &resource.original_url, convert_to_webp, &current_html_path) {
-                        let before_count = html_content_updated.matches(&resource.original_url).count();
-                        html_content_updated = html_content_updated.replace(&resource.original_url, &local_path);
-                        let after_count = html_content_updated.matches(&local_path).count();
-                        println!("🔄 Updated HTML: {} -> {} ({} replacements)", resource.original_url, local_path, after_count);
-                        
-                        // Debug: Check if the replacement actually worked
-                        if before_count > 0 && after_count == 0 {
-                            eprintln!("⚠️  Warning: URL replacement may have failed for: {}", resource.original_url);
-                        }
-                        
-                        // If this is a WebP conversion, also update any remaining references to the old extension
-                        if convert_to_webp && (resource.original_url.ends_with(".jpg") || resource.original_url.ends_with(".jpeg") || resource.original_url.ends_with(".png") ||
-                                             resource.original_url.ends_with(".JPG") || resource.original_url.ends_with(".JPEG") || resource.original_url.ends_with(".PNG")) {
-                            let old_extension = if resource.original_url.ends_with(".jpg") || resource.original_url.ends_with(".JPG") {
-                                if resource.original_url.ends_with(".jpg") { ".jpg" } else { ".JPG" }
-                            } else if resource.original_url.ends_with(".jpeg") || resource.original_url.ends_with(".JPEG") {
-                                if resource.original_url.ends_with(".jpeg") { ".jpeg" } else { ".JPEG" }
-                            } else {
-                                if resource.original_url.ends_with(".png") { ".png" } else { ".PNG" }
-                            };
-                            
-                            // Extract just the filename part for extension replacement
-                            if let Some(filename) = resource.original_url.split('/').last() {
-                                let new_filename = filename.replace(old_extension, ".webp");
-                                let old_filename_with_path = resource.original_url.clone();
-                                let new_filename_with_path = resource.original_url.replace(filename, &new_filename);
-                                
-                                // Replace the filename with .webp extension
-                                let before_ext_count = html_content_updated.matches(&old_filename_with_path).count();
-                                html_content_updated = html_content_updated.replace(&old_filename_with_path, &new_filename_with_path);
-                                let after_ext_count = html_content_updated.matches(&new_filename_with_path).count();
-                                
-                                if before_ext_count > 0 {
-                                    println!("🔄 Updated file extension: {} -> {} ({} replacements)", 
-                                             old_filename_with_path, new_filename_with_path, after_ext_count);
-                                }
-                            }
-                        }
-                    }
-                }
+            if self.should_process_resource_type(&resource_type) {
+                self.enqueue_resource(support_url.as_str(), resource_type);
             }
-            
-            // Additional comprehensive WebP extension replacement for any remaining image references
-            if convert_to_webp {
-                println!("🔍 Performing comprehensive WebP extension replacement...");
-                html_content_updated = Self::perform_comprehensive_webp_replacement(&html_content_updated);
-            }
-            
-            // Debug: Show a preview of the updated HTML content
-            println!("🔍 HTML content preview (first 500 chars):");
-            let preview = html_content_updated.chars().take(500).collect::<String>();
-            println!("{}", preview);
-            
-            // Save the updated HTML with local paths for resources
-            println!("💾 Saving HTML to: {}", current_html_path);
-            let saved_path = file_manager.save_file(&current_html_path, html_content_updated.as_bytes(), Some(&content_type))?;
-            println!("✅ Saved HTML to: {}", saved_path.display());
-            
-            // Note: Links are now processed in the priority-based resource processing above
-            // This section is no longer needed as links are queued with proper priority
-        } else if is_css {
-            // Process CSS files to extract background images
-            let css_content = String::from_utf8_lossy(&content);
-            let page_html_parser = HtmlParser::new(url)?;
-            
-            // Extract background images from CSS
-            let mut background_resources = Vec::new();
-            page_html_parser.extract_background_images_from_css(&css_content, &mut background_resources);
-            
-                        // Download background images with normal priority (after CSS/JS)
-            for resource in &background_resources {
-                // Always download background images from any site
-                // This ensures the CSS renders without 404 errors
-                println!("📥 Processing NORMAL background image: {}", resource.original_url);
-                if let Err(e) = Self::download_resource(
-                    client,
-                    file_manager,
-                    &page_html_parser,
-                    &resource.original_url,
-                    download_cache,
-                    convert_to_webp,
-                ).await {
-                    eprintln!("⚠️  Failed to download background image {}: {}", resource.original_url, e);
-                }
+        }
+    }
+    
+    /// Queue the version subtrees listed in a `mike` versions.json
+    fn enqueue_doc_versions(&self, versions_url: &str, versions_content: &str) {
+        let versions = match doc_generators::parse_mike_versions(versions_content) {
+            Ok(versions) => versions,
+            Err(e) => {
+                warn!("⚠️  Ignoring {}: {}", versions_url, e);
+                return;
             }
-            
-            // Save the CSS file
-            let local_path = page_html_parser.url_to_local_path_string(url)?;
-            println!("💾 Saving CSS to: {}", local_path);
-            let saved_path = file_manager.save_file(&local_path, &content, Some(&content_type))?;
-            println!("✅ Saved CSS to: {:?}", saved_path);
-        } else {
-            // Save non-HTML content as-is
-            let local_path = html_parser.url_to_local_path_string(url)?;
-            println!("💾 Saving non-HTML to: {}", local_path);
-            let saved_path = file_manager.save_file(&local_path, &content, Some(&content_type))?;
-            println!("✅ Saved non-HTML to: {:?}", saved_path);
+        };
+        let Ok(versions_base) = Url::parse(versions_url) else { return };
+        for version in versions {
+            let Ok(version_url) = versions_base.join(&format!("{}/", version)) else { continue };
+            info!("📚 Queued documentation version {}: {}", version, version_url);
+            self.enqueue_resource(version_url.as_str(), ResourceType::Link);
+        }
+    }
+    
+    /// Queue an asset for download unless it has already been fetched
+    fn enqueue_resource(&self, url: &str, resource_type: ResourceType) {
+        let key = self.url_key(url);
+        if self.visited_urls.lock().unwrap().contains(&key) || self.download_cache.lock().unwrap().contains_key(&key) {
+            return;
         }
         
-        println!("✅ Downloaded: {}", url);
-        Ok(())
+        let priority = match resource_type {
+            ResourceType::CSS | ResourceType::JavaScript => DownloadPriority::Critical,
+            _ => DownloadPriority::Normal,
+        };
+        info!("📥 Queued referenced {:?} resource: {}", resource_type, url);
+        self.download_queue.lock().unwrap().push(DownloadTask {
+            url: url.to_string(),
+            depth: 0,
+            priority,
+            resource_type: Some(resource_type),
+        });
     }
     
     async fn download_resource(
-        client: &Client,
-        file_manager: &FileManager,
+        &self,
         html_parser: &HtmlParser,
         url: &str,
-        download_cache: &Arc<Mutex<HashMap<String, String>>>,
-        convert_to_webp: bool,
     ) -> Result<()> {
+        let normalized_url = self.normalize_url(url);
+        let url = normalized_url.as_str();
+        
+        if self.is_excluded_domain(url) {
+            info!("🚫 Skipping {} (excluded domain)", url);
+            return Ok(());
+        }
+        
         // Check if already downloaded using cache
         {
-            let cache = download_cache.lock().unwrap();
-            if cache.contains_key(url) {
-                let cached_path = cache.get(url).unwrap();
-                println!("⏭️  Skipping {} (already downloaded to {})", url, cached_path);
+            let cache = self.download_cache.lock().unwrap();
+            if let Some(cached_path) = cache.get(&self.url_key(url)) {
+                debug!("⏭️  Skipping {} (already downloaded to {})", url, cached_path);
                 return Ok(());
             }
         }
         
+        // Large files from a previous run are revalidated instead of re-downloaded
+        match self.differential_fetch(url).await {
+            Ok(true) => return Ok(()),
+            Ok(false) => {}
+            Err(e) => warn!("⚠️  Differential fetch failed for {}: {}", url, e),
+        }
+        
         // Check if file exists on disk
-        if file_manager.file_exists(url) {
+        if self.file_manager.file_exists(url) {
             // Add to cache for future reference
-            let local_path = html_parser.url_to_local_path_string(url)?;
-            let mut cache = download_cache.lock().unwrap();
-            cache.insert(url.to_string(), local_path.clone());
-            println!("⏭️  Skipping {} (already exists on disk)", url);
+            let local_path = self.local_path(html_parser, url)?;
+            let mut cache = self.download_cache.lock().unwrap();
+            cache.insert(self.url_key(url), local_path.clone());
+            debug!("⏭️  Skipping {} (already exists on disk)", url);
             return Ok(());
         }
         
@@ -800,95 +3149,143 @@ impl WebsiteMirror {
         } else if url.ends_with(".woff") || url.ends_with(".woff2") || url.ends_with(".ttf") || 
                   url.ends_with(".eot") {
             "Font"
+        } else if url.ends_with(".mp4") || url.ends_with(".webm") || url.ends_with(".ogv") ||
+                  url.ends_with(".mov") {
+            "Video"
+        } else if ResourceType::from_extension(url) == ResourceType::Audio {
+            "Audio"
         } else {
             "Resource"
         };
         
-        println!("📥 Downloading {}: {}", resource_type, url);
+        if self.remaining_budget() == Some(0) {
+            self.skip_over_budget(url);
+            return Ok(());
+        }
+        
+        info!("📥 Downloading {}: {}", resource_type, url);
         
-        let response = match client.get(url).send().await {
+        let response = match self.fetch(url).await {
             Ok(resp) => resp,
             Err(e) => {
-                eprintln!("❌ Failed to send request for {} {}: {}", resource_type, url, e);
+                error!("❌ Failed to send request for {} {}: {}", resource_type, url, e);
+                self.count_failure(url, None, &e.to_string());
                 return Ok(());
             }
         };
         
         if response.status() != StatusCode::OK {
-            eprintln!("⚠️  HTTP {} for {} {}", response.status(), resource_type, url);
+            warn!("⚠️  HTTP {} for {} {}", response.status(), resource_type, url);
+            self.count_failure(url, Some(response.status().as_u16()), &format!("HTTP {}", response.status()));
             return Ok(());
         }
         
+        // Redirected resources are saved once, under their final URL
+        let requested_key = self.url_key(url);
+        let final_url = self.normalize_url(response.url().as_str());
+        if self.take_redirect(url, &final_url) {
+            let mut cache = self.download_cache.lock().unwrap();
+            if let Some(cached_path) = cache.get(&self.url_key(&final_url)).cloned() {
+                debug!("⏭️  Skipping {} (redirect target already downloaded to {})", url, cached_path);
+                cache.insert(requested_key, cached_path);
+                return Ok(());
+            }
+        }
+        let url = final_url.as_str();
+        
         let content_type = response
             .headers()
             .get("content-type")
             .and_then(|v| v.to_str().ok())
             .unwrap_or("application/octet-stream")
             .to_string();
+        let etag = Self::header_string(&response, "etag");
+        let last_modified = Self::header_string(&response, "last-modified");
         
-        let content = match response.bytes().await {
-            Ok(bytes) => bytes,
+        let content = match self.read_body(url, response).await {
+            Ok(Some(bytes)) => bytes,
+            Ok(None) => return Ok(()),
             Err(e) => {
-                eprintln!("❌ Failed to read {} body {}: {}", resource_type, url, e);
+                error!("❌ Failed to read {} body {}: {}", resource_type, url, e);
                 return Ok(());
             }
         };
         
         // Save the resource
-        let local_path = match html_parser.url_to_local_path_string(url) {
+        let local_path = match self.local_path(html_parser, url) {
             Ok(path) => path,
             Err(e) => {
-                eprintln!("❌ Failed to convert URL to local path {}: {}", url, e);
+                error!("❌ Failed to convert URL to local path {}: {}", url, e);
                 return Ok(());
             }
         };
         
-        // Convert images to WebP if they're JPEG or PNG and the flag is enabled
-        let (final_content, final_content_type, final_local_path) = if convert_to_webp && (url.ends_with(".jpg") || url.ends_with(".jpeg") || url.ends_with(".png") ||
-                                                                                        url.ends_with(".JPG") || url.ends_with(".JPEG") || url.ends_with(".PNG")) {
+        // Stylesheets and SVGs go through their asset pipelines before saving
+        let is_css = content_type.contains("text/css") || url.ends_with(".css");
+        let is_svg = content_type.contains("image/svg+xml") || url.split(['?', '#']).next().unwrap_or(url).ends_with(".svg");
+        let content: Vec<u8> = if is_css {
+            let css_content = String::from_utf8_lossy(&content);
+            self.process_css(url, &local_path, &css_content).into_bytes()
+        } else if is_svg {
+            let svg_content = String::from_utf8_lossy(&content);
+            self.process_svg(url, &local_path, &svg_content).into_bytes()
+        } else if doc_generators::is_mike_versions_file(url) {
+            self.enqueue_doc_versions(url, &String::from_utf8_lossy(&content));
+            content.to_vec()
+        } else if manifest::is_web_manifest(url, &content_type) {
+            let manifest_content = String::from_utf8_lossy(&content);
+            match self.process_manifest(url, &local_path, &manifest_content) {
+                Ok(rewritten) => rewritten.into_bytes(),
+                Err(e) => {
+                    warn!("⚠️  Saving web manifest {} unmodified: {}", url, e);
+                    content.to_vec()
+                }
+            }
+        } else {
+            content.to_vec()
+        };
+        
+        // Convert images to WebP if their format is in the conversion list and the flag is enabled
+        let (final_content, final_content_type, save_path) = if let Some(extension) = self.webp_source_extension(url) {
             // Convert to WebP
-            let webp_data = Self::convert_to_webp_static(&content, url)?;
+            let webp_data = Self::convert_to_webp_with_icc(&content, url, self.preserve_icc)?;
             
-            // Change file extension to .webp (handle both lowercase and uppercase)
-            let webp_path = local_path.replace(".jpg", ".webp")
-                                    .replace(".jpeg", ".webp")
-                                    .replace(".png", ".webp")
-                                    .replace(".JPG", ".webp")
-                                    .replace(".JPEG", ".webp")
-                                    .replace(".PNG", ".webp");
+            // Change file extension to .webp so the path matches what will be used in HTML rewriting
+            let webp_path = Self::with_webp_extension(&local_path, extension);
             
             (webp_data, "image/webp".to_string(), webp_path)
+        } else if let Some(optimized) = self.image_pipeline.optimize(&content, url, self.preserve_icc) {
+            // Format-preserving optimizations (e.g. progressive JPEG re-encode)
+            (optimized, content_type, local_path.clone())
         } else {
             // Keep original content and path
             (content.to_vec(), content_type, local_path.clone())
         };
         
-        // For WebP conversion, we need to save the file with the .webp extension
-        // but also ensure the path matches what will be used in HTML rewriting
-        let save_path = if convert_to_webp && (url.ends_with(".jpg") || url.ends_with(".jpeg") || url.ends_with(".png") ||
-                                              url.ends_with(".JPG") || url.ends_with(".JPEG") || url.ends_with(".PNG")) {
-            // Use the .webp path for saving
-            final_local_path.clone()
-        } else {
-            // Use the original path for saving
-            local_path.clone()
-        };
-        
-        let saved_path = match file_manager.save_file(&save_path, &final_content, Some(&final_content_type)) {
+        let saved_path = match self.file_manager.save_file(&save_path, &final_content, Some(&final_content_type)) {
             Ok(path) => path,
             Err(e) => {
-                eprintln!("❌ Failed to save {} {}: {}", resource_type, url, e);
+                error!("❌ Failed to save {} {}: {}", resource_type, url, e);
+                self.count_failure(url, None, &format!("Failed to save: {}", e));
                 return Ok(());
             }
         };
         
         // Add to download cache - use the save_path to ensure consistency
         {
-            let mut cache = download_cache.lock().unwrap();
-            cache.insert(url.to_string(), save_path.to_string());
+            let mut cache = self.download_cache.lock().unwrap();
+            cache.insert(self.url_key(url), save_path.to_string());
+            cache.insert(requested_key, save_path.to_string());
         }
+        {
+            let mut store = self.store.lock().unwrap();
+            store.record_download(url, &save_path, Some(&final_content_type), final_content.len() as u64);
+            store.record_validators(url, etag.as_deref(), last_modified.as_deref());
+        }
+        self.count_saved_file(url, Some(&final_content_type), &save_path, final_content.len() as u64);
+        self.count_bytes_written(final_content.len() as u64);
         
-        println!("✅ Downloaded {} to: {}", resource_type, saved_path.display());
+        info!("✅ Downloaded {} to: {}", resource_type, saved_path.display());
         
         Ok(())
     }
@@ -899,6 +3296,7 @@ mod tests {
     use super::*;
     use tempfile::tempdir;
     use std::sync::Arc;
+    use reqwest::header::AUTHORIZATION;
     use std::collections::HashMap;
 
     #[test]
@@ -981,6 +3379,7 @@ mod tests {
         assert!(!mirror.should_process_resource_type(&ResourceType::JavaScript));
         assert!(mirror.should_process_resource_type(&ResourceType::Image));
         assert!(!mirror.should_process_resource_type(&ResourceType::Link));
+        assert!(!mirror.should_process_resource_type(&ResourceType::Font));
     }
 
     #[test]
@@ -995,7 +3394,7 @@ mod tests {
             0x00, 0x00, 0x00, 0x00, 0x49, 0x45, 0x4E, 0x44, 0xAE, 0x42, 0x60, 0x82
         ];
         
-        let result = WebsiteMirror::convert_to_webp_static(&png_data, "test.png");
+        let result = WebsiteMirror::convert_to_webp_with_icc(&png_data, "test.png", false);
         assert!(result.is_ok());
         
         let webp_data = result.unwrap();
@@ -1006,7 +3405,7 @@ mod tests {
     #[test]
     fn test_convert_to_webp_invalid_image() {
         let invalid_data = b"not an image";
-        let result = WebsiteMirror::convert_to_webp_static(invalid_data, "test.txt");
+        let result = WebsiteMirror::convert_to_webp_with_icc(invalid_data, "test.txt", false);
         assert!(result.is_ok()); // Should return original data on failure
         
         let returned_data = result.unwrap();
@@ -1054,6 +3453,329 @@ mod tests {
         assert_eq!(result, "style.css");
     }
 
+    #[test]
+    fn test_get_local_path_for_resource_with_formats() {
+        let html_parser = HtmlParser::new("https://example.com").unwrap();
+        let formats = vec!["gif".to_string()];
+        
+        // Listed formats are converted
+        let result = WebsiteMirror::get_local_path_for_resource_with_formats(
+            &html_parser,
+            "https://example.com/anim.GIF",
+            &formats,
+            "index.html"
+        ).unwrap();
+        assert_eq!(result, "anim.webp");
+        
+        // Unlisted formats are passed through
+        let result = WebsiteMirror::get_local_path_for_resource_with_formats(
+            &html_parser,
+            "https://example.com/photo.jpg",
+            &formats,
+            "index.html"
+        ).unwrap();
+        assert_eq!(result, "photo.jpg");
+    }
+
+    #[test]
+    fn test_rewrite_host_links() {
+        let temp_dir = tempdir().unwrap();
+        let mut mirror = WebsiteMirror::new("https://old.example.com", temp_dir.path(), 3, 10, false, false, None, false).unwrap();
+        mirror.rewrite_host = Some("new.example.org".to_string());
+        let html_parser = HtmlParser::new("https://old.example.com/blog/post/").unwrap();
+        
+        let link = mirror.output_link(&html_parser, "https://cdn.example.net/img/logo.png", "blog/post/index.html").unwrap();
+        assert_eq!(link, "https://new.example.org/img/logo.png");
+        let link = mirror.output_link(&html_parser, "https://old.example.com/about", "blog/post/index.html").unwrap();
+        assert_eq!(link, "https://new.example.org/about/");
+        
+        let html = r#"<a href="https://old.example.com/contact/">x</a><meta property="og:url" content="//old.example.com:443/blog/">"#;
+        assert_eq!(
+            mirror.rewrite_origin_urls(html),
+            r#"<a href="https://new.example.org/contact/">x</a><meta property="og:url" content="https://new.example.org/blog/">"#
+        );
+        assert_eq!(
+            mirror.linked_local_file("https://new.example.org/about/", "index.html"),
+            Some(temp_dir.path().join("about/index.html"))
+        );
+    }
+
+    #[test]
+    fn test_link_base_links() {
+        let temp_dir = tempdir().unwrap();
+        let mut mirror = WebsiteMirror::new("https://example.com", temp_dir.path(), 3, 10, false, false, None, false).unwrap();
+        mirror.link_base = Some("archive/site1".to_string());
+        let html_parser = HtmlParser::new("https://example.com/blog/").unwrap();
+        
+        let link = mirror.output_link(&html_parser, "https://example.com/css/site.css", "blog/index.html").unwrap();
+        assert_eq!(link, "/archive/site1/css/site.css");
+        assert_eq!(mirror.rewrite_origin_urls(r#"<a href="https://example.com/about/">"#), r#"<a href="/archive/site1/about/">"#);
+        assert_eq!(mirror.linked_local_file("/archive/site1/css/site.css", "blog/index.html"), Some(temp_dir.path().join("css/site.css")));
+        
+        mirror.rewrite_host = Some("new.example.org".to_string());
+        let link = mirror.output_link(&html_parser, "https://example.com/css/site.css", "blog/index.html").unwrap();
+        assert_eq!(link, "https://new.example.org/archive/site1/css/site.css");
+    }
+
+    #[tokio::test]
+    async fn test_max_total_size_stops_new_downloads() {
+        let temp_dir = tempdir().unwrap();
+        let mut mirror = WebsiteMirror::new("https://example.com", temp_dir.path(), 3, 10, false, false, None, false).unwrap();
+        mirror.max_total_size = Some(0);
+        mirror.ignore_robots = true;
+        
+        mirror.mirror_website().await.unwrap();
+        assert_eq!(mirror.store_skip_reason("https://example.com"), Some("over --max-total-size budget".to_string()));
+        assert!(!temp_dir.path().join("index.html").exists());
+    }
+    
+    #[tokio::test]
+    async fn test_max_total_size_skips_page_assets() {
+        let temp_dir = tempdir().unwrap();
+        let mut mirror = WebsiteMirror::new("https://example.com", temp_dir.path(), 3, 10, false, false, None, false).unwrap();
+        mirror.max_total_size = Some(1024);
+        mirror.count_bytes_written(1024);
+        
+        let html_parser = HtmlParser::new("https://example.com/").unwrap();
+        mirror.download_resource(&html_parser, "https://example.com/media/intro.mp4").await.unwrap();
+        assert_eq!(mirror.store_skip_reason("https://example.com/media/intro.mp4"), Some("over --max-total-size budget".to_string()));
+        assert_eq!(*mirror.budget_skipped.lock().unwrap(), 1);
+    }
+    
+    #[test]
+    fn test_link_unmirrored_pages_keeps_page_encoding() {
+        let temp_dir = tempdir().unwrap();
+        let mut mirror = WebsiteMirror::new("https://example.com", temp_dir.path(), 3, 10, false, false, None, false).unwrap();
+        mirror.preserve_encoding = true;
+        let html = r#"<html><head><meta charset="Shift_JIS"></head><body><p>日本語のページ</p><a href="/archive/">過去ログ</a></body></html>"#;
+        let (saved, _, _) = encoding_rs::SHIFT_JIS.encode(html);
+        fs::write(temp_dir.path().join("index.html"), &saved).unwrap();
+        mirror.store.lock().unwrap().record_download("https://example.com/", "index.html", Some("text/html; charset=Shift_JIS"), saved.len() as u64);
+        
+        let unmirrored = HashSet::from([mirror.url_key(&mirror.normalize_url("https://example.com/archive/"))]);
+        assert_eq!(mirror.link_unmirrored_pages(&unmirrored).unwrap(), 1);
+        let expected = html.replace(r#"href="/archive/""#, r#"href="https://example.com/archive/""#);
+        assert_eq!(fs::read(temp_dir.path().join("index.html")).unwrap(), encoding_rs::SHIFT_JIS.encode(&expected).0.into_owned());
+    }
+
+    #[tokio::test]
+    async fn test_review_pages_batches_undecided_pages() {
+        let temp_dir = tempdir().unwrap();
+        let mirror = WebsiteMirror::new("https://example.com", temp_dir.path(), 3, 10, false, false, None, false).unwrap();
+        let review_file = temp_dir.path().join("review.txt");
+        fs::write(&review_file, "+ https://example.com/blog/\n- https://example.com/hr/\n").unwrap();
+        let task = |url: &str, resource_type| DownloadTask { url: url.to_string(), depth: 1, priority: DownloadPriority::High, resource_type };
+        {
+            let mut queue = mirror.download_queue.lock().unwrap();
+            queue.push(task("https://example.com/hr/", Some(ResourceType::Link)));
+            queue.push(task("https://example.com/blog", None));
+            queue.push(task("https://example.com/blog/archive/", Some(ResourceType::Link)));
+            queue.push(task("https://example.com/blog/logo.png", Some(ResourceType::Image)));
+        }
+        let archive_key = mirror.url_key(&mirror.normalize_url("https://example.com/blog/archive/"));
+        let mut decisions = HashMap::from([(archive_key, ReviewDecision::Deny)]);
+
+        mirror.review_pages(&Reviewer::File(review_file.clone()), task("https://example.com/blog/", Some(ResourceType::Link)), &mut decisions).await.unwrap();
+
+        // The batch is the new page and the undecided page queued with it, once
+        // each; decided pages and assets stay queued as they were
+        assert_eq!(decisions.len(), 3);
+        assert_eq!(decisions.get(&mirror.url_key(&mirror.normalize_url("https://example.com/hr/"))), Some(&ReviewDecision::Deny));
+        assert_eq!(decisions.get(&mirror.url_key(&mirror.normalize_url("https://example.com/blog/"))), Some(&ReviewDecision::Allow));
+        let mut queued: Vec<String> = mirror.download_queue.lock().unwrap().iter().map(|task| task.url.clone()).collect();
+        queued.sort();
+        assert_eq!(queued, ["https://example.com/blog/", "https://example.com/blog/archive/", "https://example.com/blog/logo.png"]);
+        let store = mirror.store.lock().unwrap();
+        assert_eq!(store.skipped.get("https://example.com/hr/").map(String::as_str), Some("denied in review"));
+        assert!(!store.skipped.contains_key("https://example.com/blog/archive/"));
+        assert!(!Reviewer::pending_path(&review_file).exists());
+    }
+
+    #[test]
+    fn test_bearer_token_only_sent_to_target_site() {
+        let temp_dir = tempdir().unwrap();
+        let mirror = WebsiteMirror::new("https://example.com", temp_dir.path(), 3, 10, false, false, None, false).unwrap();
+        *mirror.bearer_token.lock().unwrap() = Some("secret".to_string());
+        
+        let request = mirror.authorized_get("https://example.com/private/report.pdf").build().unwrap();
+        assert_eq!(request.headers().get(AUTHORIZATION).unwrap(), "Bearer secret");
+        let request = mirror.authorized_get("https://cdn.example.net/app.js").build().unwrap();
+        assert!(request.headers().get(AUTHORIZATION).is_none());
+        let request = mirror.authorized_get("https://www.google-analytics.com/collect").build().unwrap();
+        assert!(request.headers().get(AUTHORIZATION).is_none());
+    }
+    
+    #[test]
+    fn test_in_include_paths() {
+        let temp_dir = tempdir().unwrap();
+        let mut mirror = WebsiteMirror::new("https://example.com", temp_dir.path(), 3, 10, false, false, None, false).unwrap();
+        assert!(mirror.in_include_paths("https://example.com/about/"));
+        
+        mirror.include_paths = vec!["/blog".to_string(), "docs/v2/".to_string()];
+        assert!(mirror.in_include_paths("https://example.com/blog"));
+        assert!(mirror.in_include_paths("https://example.com/blog/2024/post.html?page=2"));
+        assert!(mirror.in_include_paths("https://example.com/docs/v2/install/"));
+        assert!(!mirror.in_include_paths("https://example.com/blogroll/"));
+        assert!(!mirror.in_include_paths("https://example.com/docs/v1/install/"));
+        assert!(!mirror.in_include_paths("https://example.com/"));
+    }
+    
+    #[test]
+    fn test_crawl_domains() {
+        let temp_dir = tempdir().unwrap();
+        let mut mirror = WebsiteMirror::new("https://example.com", temp_dir.path(), 3, 10, false, false, None, false).unwrap();
+        mirror.crawl_domains = vec!["docs.example.org".to_string()];
+        assert!(mirror.is_target_site("https://example.com/about/"));
+        assert!(mirror.is_target_site("https://Docs.Example.org/guide/"));
+        assert!(!mirror.is_target_site("https://blog.example.org/"));
+        
+        let html_parser = HtmlParser::new("https://example.com/").unwrap();
+        assert_eq!(mirror.local_path(&html_parser, "https://docs.example.org/guide/install.html").unwrap(), "_external/docs.example.org/guide/install.html");
+        assert_eq!(mirror.local_path(&html_parser, "https://cdn.example.net/app.js").unwrap(), "app.js");
+    }
+    
+    #[test]
+    fn test_exact_host_by_default() {
+        // Deliberately narrower than matching the start URL as text: subdomains
+        // need --span-subdomains, look-alike hosts and quoted URLs are never crawled
+        let temp_dir = tempdir().unwrap();
+        let mirror = WebsiteMirror::new("https://www.example.co.uk", temp_dir.path(), 3, 10, false, false, None, false).unwrap();
+        assert!(!mirror.span_subdomains);
+        assert!(mirror.is_target_site("https://www.example.co.uk/about/"));
+        assert!(!mirror.is_target_site("https://shop.example.co.uk/"));
+        assert!(!mirror.is_target_site("https://www.example.co.uk:8443/"));
+        assert!(!mirror.is_target_site("https://www.example.co.uk.evil.net/"));
+        assert!(!mirror.is_target_site("https://evil.net/?next=https://www.example.co.uk"));
+    }
+    
+    #[test]
+    fn test_span_subdomains() {
+        let temp_dir = tempdir().unwrap();
+        let mut mirror = WebsiteMirror::new("https://www.example.co.uk", temp_dir.path(), 3, 10, false, false, None, false).unwrap();
+        mirror.span_subdomains = true;
+        assert!(mirror.is_target_site("https://shop.example.co.uk/cart/"));
+        assert!(mirror.is_target_site("https://example.co.uk/"));
+        assert!(!mirror.is_target_site("https://other.co.uk/"));
+        
+        let html_parser = HtmlParser::new("https://www.example.co.uk/").unwrap();
+        assert_eq!(mirror.local_path(&html_parser, "https://shop.example.co.uk/cart/").unwrap(), "_external/shop.example.co.uk/cart/index.html");
+    }
+
+    #[test]
+    fn test_output_sections() {
+        let temp_dir = tempdir().unwrap();
+        let mut mirror = WebsiteMirror::new("https://example.com", &temp_dir.path().join("main"), 3, 10, false, false, None, false).unwrap();
+        mirror.output_sections = vec![format!("/docs/**={}", temp_dir.path().join("docs-mirror").display()).parse().unwrap()];
+        let html_parser = HtmlParser::new("https://example.com/").unwrap();
+        assert_eq!(mirror.local_path(&html_parser, "https://example.com/docs/guide/").unwrap(), "../docs-mirror/guide/index.html");
+        assert_eq!(mirror.local_path(&html_parser, "https://example.com/docs").unwrap(), "../docs-mirror/index.html");
+        assert_eq!(mirror.local_path(&html_parser, "https://example.com/docsearch/").unwrap(), "docsearch/index.html");
+        
+        assert_eq!(mirror.output_link(&html_parser, "https://example.com/docs/guide/", "index.html").unwrap(), "../docs-mirror/guide/index.html");
+        assert_eq!(mirror.output_link(&html_parser, "https://example.com/about/", "../docs-mirror/guide/index.html").unwrap(), "../../main/about/index.html");
+        assert_eq!(mirror.output_link(&html_parser, "https://example.com/docs/", "../docs-mirror/guide/index.html").unwrap(), "../index.html");
+    }
+
+    #[test]
+    fn test_exclude_domains() {
+        let temp_dir = tempdir().unwrap();
+        let mut mirror = WebsiteMirror::new("https://example.com", temp_dir.path(), 3, 10, false, false, None, false).unwrap();
+        mirror.exclude_domains = vec!["doubleclick.net".to_string(), "static.example.com".to_string()];
+        assert!(mirror.is_excluded_domain("https://ad.doubleclick.net/pixel.gif"));
+        assert!(mirror.is_excluded_domain("https://doubleclick.net/"));
+        assert!(!mirror.is_excluded_domain("https://notdoubleclick.net/"));
+        assert!(!mirror.is_excluded_domain("https://example.com/page"));
+        
+        mirror.span_subdomains = true;
+        assert!(mirror.is_target_site("https://shop.example.com/"));
+        assert!(!mirror.is_target_site("https://static.example.com/"));
+        let html_parser = HtmlParser::new("https://example.com/").unwrap();
+        assert!(mirror.output_link(&html_parser, "https://ad.doubleclick.net/pixel.gif", "index.html").is_err());
+    }
+    
+    #[test]
+    fn test_trust_hinted_hosts() {
+        let temp_dir = tempdir().unwrap();
+        let mut mirror = WebsiteMirror::new("https://example.com", temp_dir.path(), 3, 10, false, false, None, false).unwrap();
+        mirror.exclude_domains = vec!["doubleclick.net".to_string()];
+        mirror.apply_connection_hints(&["https://api.example.net".to_string(), "https://ad.doubleclick.net".to_string()]);
+        assert!(!mirror.is_trusted_hinted_host("https://api.example.net/menu.json"));
+        
+        mirror.trust_hinted_hosts = true;
+        assert!(mirror.is_trusted_hinted_host("https://api.example.net/menu.json"));
+        assert!(!mirror.is_trusted_hinted_host("http://api.example.net/menu.json"));
+        assert!(!mirror.is_trusted_hinted_host("https://ad.doubleclick.net/config.json"));
+    }
+
+    #[test]
+    fn test_host_aliases() {
+        let temp_dir = tempdir().unwrap();
+        let mut mirror = WebsiteMirror::new("https://example.com", temp_dir.path(), 3, 10, false, false, None, false).unwrap();
+        assert!(mirror.is_target_site("https://www.example.com/about/"));
+        assert!(!mirror.is_target_site("https://old.example.org/about/"));
+        assert_eq!(mirror.url_key("https://www.example.com/about/"), mirror.url_key("https://example.com/about"));
+        assert_eq!(mirror.normalize_url("https://WWW.example.com/about#team"), "https://example.com/about");
+
+        mirror.alias_hosts = vec!["old.example.org".to_string()];
+        assert!(mirror.is_target_site("https://old.example.org/about/"));
+        let html_parser = HtmlParser::new("https://example.com/").unwrap();
+        assert_eq!(
+            mirror.local_path(&html_parser, "https://old.example.org/about/").unwrap(),
+            mirror.local_path(&html_parser, "https://example.com/about/").unwrap()
+        );
+    }
+    
+    #[test]
+    fn test_format_size() {
+        assert_eq!(WebsiteMirror::format_size(512), "512 B");
+        assert_eq!(WebsiteMirror::format_size(1536), "1.5 KB");
+        assert_eq!(WebsiteMirror::format_size(2 << 30), "2.0 GB");
+    }
+    
+    #[test]
+    fn test_external_host_dirs() {
+        let temp_dir = tempdir().unwrap();
+        let mut mirror = WebsiteMirror::new("https://example.com", temp_dir.path(), 3, 10, false, false, None, false).unwrap();
+        let html_parser = HtmlParser::new("https://example.com/blog/").unwrap();
+        assert_eq!(mirror.local_path(&html_parser, "https://cdn.example.com/app.js").unwrap(), "app.js");
+        
+        mirror.external_host_dirs = true;
+        assert_eq!(mirror.local_path(&html_parser, "https://cdn.example.com/app.js").unwrap(), "_external/cdn.example.com/app.js");
+        assert_eq!(mirror.local_path(&html_parser, "http://cdn.example.com:8080/app.js").unwrap(), "_external/cdn.example.com_8080/app.js");
+        assert_eq!(mirror.local_path(&html_parser, "https://example.com:443/app.js").unwrap(), "app.js");
+        assert_eq!(mirror.local_path(&html_parser, "../img/logo.png").unwrap(), "img/logo.png");
+        assert_eq!(
+            mirror.output_link(&html_parser, "https://fonts.gstatic.com/s/roboto.woff2", "blog/index.html").unwrap(),
+            "../_external/fonts.gstatic.com/s/roboto.woff2"
+        );
+    }
+
+    #[test]
+    fn test_output_link_follows_redirects() {
+        let temp_dir = tempdir().unwrap();
+        let mirror = WebsiteMirror::new("https://example.com", temp_dir.path(), 3, 10, false, false, None, false).unwrap();
+        mirror.redirects.lock().unwrap().record(
+            "https://example.com/old-page",
+            vec!["https://example.com/old-page/".to_string(), "https://example.com/new-page/".to_string()],
+        );
+        let html_parser = HtmlParser::new("https://example.com/blog/").unwrap();
+        
+        let link = mirror.output_link(&html_parser, "https://example.com/old-page#top", "blog/index.html").unwrap();
+        assert_eq!(link, "../new-page/index.html");
+        assert!(!mirror.take_redirect("https://example.com/docs", "https://example.com/docs/"));
+        assert!(mirror.take_redirect("https://example.com/a.png", "https://cdn.example.com/a.png"));
+        assert_eq!(mirror.resolve_redirect("https://example.com/a.png"), "https://cdn.example.com/a.png");
+    }
+
+    #[test]
+    fn test_comprehensive_webp_replacement_for_formats() {
+        let html = r#"<img src="a.png"><img src="b.gif"><img src="c.webp">"#;
+        let updated = WebsiteMirror::perform_comprehensive_webp_replacement_for_formats(html, &["png".to_string()]);
+        assert!(updated.contains("a.webp"));
+        assert!(updated.contains("b.gif"));
+        assert!(updated.contains("c.webp"));
+    }
+
     #[test]
     fn test_download_task_ordering() {
         let task1 = DownloadTask {
@@ -1261,7 +3983,7 @@ mod tests {
         assert!(!html_content.contains(".jpeg"), "Should not contain .jpeg");
         assert!(!html_content.contains(".png"), "Should not contain .png");
         
-        println!("Updated HTML content:");
-        println!("{}", html_content);
+        info!("Updated HTML content:");
+        info!("{}", html_content);
     }
 } 
\ No newline at end of file
@@ -0,0 +1,708 @@
+use anyhow::{anyhow, bail, Result, Context};
+use html5ever::tendril::TendrilSink;
+use html5ever::{parse_document, ParseOpts};
+use markup5ever_rcdom::{Handle, NodeData, RcDom};
+use regex::{Captures, Regex};
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::rc::Rc;
+use url::Url;
+
+use crate::css_parser;
+use crate::dead_assets;
+use crate::gallery::escape_html;
+use crate::single_file::{self, Inliner};
+use crate::snapshot_diff;
+use crate::store::DownloadStore;
+use crate::url_normalizer;
+
+/// How deep `@import`ed stylesheets are followed
+const MAX_IMPORT_DEPTH: usize = 8;
+
+const VOID_ELEMENTS: &[&str] = &["area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta", "param", "source", "track", "wbr"];
+/// Left out of the email with everything inside them: scripts and embedded
+/// content mail clients don't run, head metadata of the live page, and the
+/// stylesheets, which are inlined
+const DROPPED_ELEMENTS: &[&str] = &["script", "noscript", "iframe", "object", "embed", "template", "base", "meta", "link", "style"];
+
+/// `@import url(...) media;` or `@import "..." media;`
+const IMPORT_PATTERN: &str = r#"(?i)@import\s+(?:url\(\s*)?["']?([^"')\s;]+)["']?\s*\)?\s*([^;]*);"#;
+
+/// Outcome of exporting a page for email
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EmailStats {
+    /// Elements given a `style` attribute from the page's stylesheets
+    pub styled_elements: usize,
+    /// Images embedded as data URIs, or linked to the live site
+    pub images: usize,
+    pub total_bytes: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Combinator {
+    Descendant,
+    Child,
+}
+
+/// `tag#id.class[attr=value]`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct Compound {
+    tag: Option<String>,
+    id: Option<String>,
+    classes: Vec<String>,
+    /// `[name]` or `[name=value]`
+    attributes: Vec<(String, Option<String>)>,
+}
+
+/// What selectors are matched against: an element's name and attributes
+struct ElementInfo {
+    name: String,
+    id: Option<String>,
+    classes: Vec<String>,
+    attributes: HashMap<String, String>,
+}
+
+impl Compound {
+    fn matches(&self, element: &ElementInfo) -> bool {
+        self.tag.as_ref().is_none_or(|tag| *tag == element.name)
+            && self.id.as_ref().is_none_or(|id| element.id.as_ref() == Some(id))
+            && self.classes.iter().all(|class| element.classes.contains(class))
+            && self.attributes.iter().all(|(name, value)| match (element.attributes.get(name), value) {
+                (Some(actual), Some(value)) => actual == value,
+                (Some(_), None) => true,
+                (None, _) => false,
+            })
+    }
+}
+
+/// A selector simple enough to be resolved here: compounds joined by
+/// descendant and child combinators
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Selector {
+    /// Compounds left to right, each with the combinator joining it to the one before
+    parts: Vec<(Combinator, Compound)>,
+    /// (ids, classes and attributes, types)
+    specificity: (usize, usize, usize),
+}
+
+impl Selector {
+    /// `None` for selectors with pseudo-classes, sibling combinators and other
+    /// syntax only a browser can resolve
+    fn parse(text: &str) -> Option<Self> {
+        let chars: Vec<char> = text.trim().chars().collect();
+        let is_ident = |c: char| c.is_alphanumeric() || c == '-' || c == '_';
+        let read_ident = |start: usize| {
+            let end = (start..chars.len()).find(|&i| !is_ident(chars[i])).unwrap_or(chars.len());
+            (chars[start..end].iter().collect::<String>(), end)
+        };
+
+        let mut parts = Vec::new();
+        let mut compound = Compound::default();
+        let mut has_compound = false;
+        let mut combinator = Combinator::Descendant;
+        let mut specificity = (0, 0, 0);
+        let mut i = 0;
+        while i < chars.len() {
+            let c = chars[i];
+            if c.is_whitespace() || c == '>' {
+                if has_compound {
+                    parts.push((combinator, std::mem::take(&mut compound)));
+                    has_compound = false;
+                    combinator = Combinator::Descendant;
+                } else if parts.is_empty() {
+                    return None;
+                }
+                if c == '>' {
+                    combinator = Combinator::Child;
+                }
+                i += 1;
+                continue;
+            }
+            match c {
+                '*' if !has_compound => i += 1,
+                '#' | '.' => {
+                    let (name, end) = read_ident(i + 1);
+                    if name.is_empty() {
+                        return None;
+                    }
+                    if c == '#' {
+                        if compound.id.is_some() {
+                            return None;
+                        }
+                        compound.id = Some(name);
+                        specificity.0 += 1;
+                    } else {
+                        compound.classes.push(name);
+                        specificity.1 += 1;
+                    }
+                    i = end;
+                }
+                '[' => {
+                    let close = (i..chars.len()).find(|&j| chars[j] == ']')?;
+                    let inside: String = chars[i + 1..close].iter().collect();
+                    let (name, value) = match inside.split_once('=') {
+                        Some((name, value)) => (name.trim(), Some(value.trim().trim_matches(['"', '\'']).to_string())),
+                        None => (inside.trim(), None),
+                    };
+                    if name.is_empty() || !name.chars().all(is_ident) {
+                        return None;
+                    }
+                    compound.attributes.push((name.to_ascii_lowercase(), value));
+                    specificity.1 += 1;
+                    i = close + 1;
+                }
+                c if is_ident(c) && !has_compound => {
+                    let (name, end) = read_ident(i);
+                    compound.tag = Some(name.to_ascii_lowercase());
+                    specificity.2 += 1;
+                    i = end;
+                }
+                _ => return None,
+            }
+            has_compound = true;
+        }
+        if !has_compound {
+            return None;
+        }
+        parts.push((combinator, compound));
+        Some(Self { parts, specificity })
+    }
+
+    fn matches(&self, element: &ElementInfo, ancestors: &[ElementInfo]) -> bool {
+        matches_parts(&self.parts, element, ancestors)
+    }
+}
+
+fn matches_parts(parts: &[(Combinator, Compound)], element: &ElementInfo, ancestors: &[ElementInfo]) -> bool {
+    let Some(((combinator, compound), rest)) = parts.split_last() else {
+        return true;
+    };
+    if !compound.matches(element) {
+        return false;
+    }
+    if rest.is_empty() {
+        return true;
+    }
+    match combinator {
+        Combinator::Child => ancestors.split_last().is_some_and(|(parent, above)| matches_parts(rest, parent, above)),
+        Combinator::Descendant => (0..ancestors.len()).rev().any(|i| matches_parts(rest, &ancestors[i], &ancestors[..i])),
+    }
+}
+
+/// Split on `separator` outside quotes, parentheses and brackets
+fn split_top_level(text: &str, separator: char) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut depth = 0usize;
+    let mut quote = None;
+    let mut start = 0;
+    for (index, c) in text.char_indices() {
+        match (quote, c) {
+            (Some(open), c) if c == open => quote = None,
+            (Some(_), _) => {}
+            (None, '"' | '\'') => quote = Some(c),
+            (None, '(' | '[') => depth += 1,
+            (None, ')' | ']') => depth = depth.saturating_sub(1),
+            (None, c) if c == separator && depth == 0 => {
+                parts.push(&text[start..index]);
+                start = index + c.len_utf8();
+            }
+            _ => {}
+        }
+    }
+    parts.push(&text[start..]);
+    parts
+}
+
+/// Index of the first of `targets` outside quotes and parentheses, and which it is
+fn find_top_level(text: &str, targets: &[char]) -> Option<(usize, char)> {
+    let mut depth = 0usize;
+    let mut quote = None;
+    for (index, c) in text.char_indices() {
+        match (quote, c) {
+            (Some(open), c) if c == open => quote = None,
+            (Some(_), _) => {}
+            (None, '"' | '\'') => quote = Some(c),
+            (None, '(') => depth += 1,
+            (None, ')') => depth = depth.saturating_sub(1),
+            (None, c) if depth == 0 && targets.contains(&c) => return Some((index, c)),
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Index of the `}` closing the block opened at `open`
+fn matching_brace(text: &str, open: usize) -> Option<usize> {
+    let mut depth = 0usize;
+    let mut quote = None;
+    for (index, c) in text[open..].char_indices() {
+        match (quote, c) {
+            (Some(open), c) if c == open => quote = None,
+            (Some(_), _) => {}
+            (None, '"' | '\'') => quote = Some(c),
+            (None, '{') => depth += 1,
+            (None, '}') => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(open + index);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Declaration {
+    property: String,
+    value: String,
+    important: bool,
+}
+
+fn parse_declarations(block: &str) -> Vec<Declaration> {
+    let important_regex = Regex::new(r"(?i)\s*!\s*important\s*$").unwrap();
+    split_top_level(block, ';')
+        .into_iter()
+        .filter_map(|declaration| {
+            let (property, value) = declaration.split_once(':')?;
+            let property = property.trim().to_ascii_lowercase();
+            let value = value.trim();
+            if property.is_empty() || value.is_empty() {
+                return None;
+            }
+            let important = important_regex.is_match(value);
+            let value = important_regex.replace(value, "").trim().to_string();
+            Some(Declaration { property, value, important })
+        })
+        .collect()
+}
+
+/// Wrap `css` for a `media` list: screen styles are used as they are, print-only
+/// styles dropped and other media kept as an `@media` block
+fn apply_media(css: String, media: Option<&str>) -> Option<String> {
+    match media.map(str::trim).filter(|media| !media.is_empty()) {
+        None => Some(css),
+        Some(media) if media.split(',').any(|query| matches!(query.trim().to_ascii_lowercase().as_str(), "all" | "screen")) => Some(css),
+        Some(media) if media.split(',').all(|query| query.trim().eq_ignore_ascii_case("print")) => None,
+        Some(media) => Some(format!("@media {} {{\n{}\n}}\n", media, css)),
+    }
+}
+
+/// The page's style rules, split into those inlined into `style` attributes
+/// and those that need a `<style>` block
+#[derive(Debug, Default)]
+struct Stylesheet {
+    /// Inlinable selectors in source order, with the index of their declarations
+    selectors: Vec<(Selector, usize)>,
+    declarations: Vec<Vec<Declaration>>,
+    /// Rules that can't be inlined (`@media`, `@font-face`, `:hover`, ...)
+    kept: String,
+}
+
+impl Stylesheet {
+    fn add(&mut self, css: &str) {
+        let mut rest = css;
+        loop {
+            rest = rest.trim_start();
+            let Some((index, found)) = find_top_level(rest, &['{', ';']) else {
+                break;
+            };
+            // Statements such as @charset or an @import that couldn't be read
+            if found == ';' {
+                rest = &rest[index + 1..];
+                continue;
+            }
+            let Some(close) = matching_brace(rest, index) else {
+                break;
+            };
+            let prelude = rest[..index].trim();
+            let block = &rest[index + 1..close];
+            if prelude.starts_with('@') {
+                self.kept.push_str(&format!("{} {{{}}}\n", prelude, block));
+            } else {
+                self.add_rule(prelude, block);
+            }
+            rest = &rest[close + 1..];
+        }
+    }
+
+    fn add_rule(&mut self, prelude: &str, block: &str) {
+        let declarations = parse_declarations(block);
+        if declarations.is_empty() {
+            return;
+        }
+        let index = self.declarations.len();
+        let mut kept = Vec::new();
+        for text in split_top_level(prelude, ',') {
+            match Selector::parse(text) {
+                Some(selector) => self.selectors.push((selector, index)),
+                None => kept.push(text.trim()),
+            }
+        }
+        if !kept.is_empty() {
+            self.kept.push_str(&format!("{} {{ {} }}\n", kept.join(", "), block.trim()));
+        }
+        self.declarations.push(declarations);
+    }
+
+    /// `style` attribute of every element the rules apply to (by node address):
+    /// matched declarations in cascade order, the element's own style winning
+    /// over all but `!important` rules
+    fn compute_styles(&self, handle: &Handle, ancestors: &mut Vec<ElementInfo>, styles: &mut HashMap<usize, String>) {
+        for child in handle.children.borrow().iter() {
+            let NodeData::Element { name, attrs, .. } = &child.data else {
+                continue;
+            };
+            let attributes: HashMap<String, String> = attrs
+                .borrow()
+                .iter()
+                .map(|attribute| (attribute.name.local.to_ascii_lowercase().to_string(), attribute.value.to_string()))
+                .collect();
+            let element = ElementInfo {
+                name: name.local.to_ascii_lowercase().to_string(),
+                id: attributes.get("id").cloned(),
+                classes: attributes.get("class").map(|class| class.split_whitespace().map(String::from).collect()).unwrap_or_default(),
+                attributes,
+            };
+            if element.name == "head" {
+                continue;
+            }
+
+            let mut matched = Vec::new();
+            for (position, (selector, index)) in self.selectors.iter().enumerate() {
+                if selector.matches(&element, ancestors) {
+                    for declaration in &self.declarations[*index] {
+                        matched.push(((declaration.important, false, selector.specificity, position), declaration));
+                    }
+                }
+            }
+            if !matched.is_empty() {
+                let inline = element.attributes.get("style").map(|style| parse_declarations(style)).unwrap_or_default();
+                matched.extend(inline.iter().map(|declaration| ((declaration.important, true, (0, 0, 0), 0), declaration)));
+                matched.sort_by_key(|(key, _)| *key);
+                let mut properties: Vec<(&str, &str)> = Vec::new();
+                for (_, declaration) in matched {
+                    properties.retain(|(property, _)| *property != declaration.property);
+                    properties.push((&declaration.property, &declaration.value));
+                }
+                let style = properties.iter().map(|(property, value)| format!("{}: {}", property, value)).collect::<Vec<_>>().join("; ");
+                styles.insert(Rc::as_ptr(child) as usize, style);
+            }
+
+            ancestors.push(element);
+            self.compute_styles(child, ancestors, styles);
+            ancestors.pop();
+        }
+    }
+}
+
+/// Turns one saved page into email-ready HTML
+struct EmailExporter<'a> {
+    page: &'a str,
+    page_url: &'a Url,
+    /// Normalized local path -> URL it was downloaded from
+    urls: &'a HashMap<String, String>,
+    inliner: &'a Inliner<'a>,
+    remote_images: bool,
+}
+
+/// The HTML being written
+#[derive(Default)]
+struct EmailOutput {
+    html: String,
+    images: usize,
+}
+
+impl EmailExporter<'_> {
+    /// A data URI of the saved file a reference in `document` points at, or its
+    /// URL on the live site with `remote_images`
+    fn asset_url(&self, document: &str, reference: &str) -> Option<String> {
+        if self.remote_images {
+            let path = dead_assets::resolve_reference(document, reference)?;
+            return self.urls.get(&path).cloned();
+        }
+        let (path, content) = self.inliner.read(document, reference)?;
+        Some(self.inliner.data_uri(&path, &content))
+    }
+
+    /// Links lead to the live site: mirrored pages to the URL they were saved from
+    fn link_href(&self, href: &str) -> String {
+        let href = href.trim();
+        if href.starts_with('#') || href.contains("://") || href.starts_with("mailto:") || href.starts_with("tel:") {
+            return href.to_string();
+        }
+        if let Some(rest) = href.strip_prefix("//") {
+            return format!("https://{}", rest);
+        }
+        let fragment = href.find('#').map_or("", |index| &href[index..]);
+        let original = dead_assets::resolve_reference(self.page, href)
+            .and_then(|path| self.urls.get(&path).or_else(|| self.urls.get(&format!("{}/index.html", path))));
+        match original {
+            Some(url) => format!("{}{}", url, fragment),
+            None => self.page_url.join(href).map(String::from).unwrap_or_else(|_| href.to_string()),
+        }
+    }
+
+    /// A stylesheet with its imports flattened in and its `url()`s embedded
+    fn load_stylesheet(&self, css_path: &str, css: &str, depth: usize) -> String {
+        let comment_regex = Regex::new(r"(?s)/\*.*?\*/").unwrap();
+        let css = comment_regex.replace_all(css, "");
+        let css = css_parser::rewrite_css_references(&css, |reference| {
+            if reference.is_import {
+                return None;
+            }
+            self.asset_url(css_path, &reference.url)
+        });
+        let import_regex = Regex::new(IMPORT_PATTERN).unwrap();
+        import_regex
+            .replace_all(&css, |caps: &Captures| {
+                if depth >= MAX_IMPORT_DEPTH {
+                    return String::new();
+                }
+                let Some((path, content)) = self.inliner.read(css_path, &caps[1]) else {
+                    return String::new();
+                };
+                let imported = self.load_stylesheet(&path, &String::from_utf8_lossy(&content), depth + 1);
+                apply_media(imported, Some(&caps[2])).unwrap_or_default()
+            })
+            .into_owned()
+    }
+
+    /// The CSS of every linked stylesheet and `<style>` block, in document order
+    fn collect_css(&self, handle: &Handle, css: &mut String) {
+        for child in handle.children.borrow().iter() {
+            if let NodeData::Element { name, attrs, .. } = &child.data {
+                let attrs = attrs.borrow();
+                let attr = |attr_name: &str| attrs.iter().find(|attr| &*attr.name.local == attr_name).map(|attr| attr.value.to_string());
+                match &*name.local {
+                    "link" => {
+                        let is_stylesheet = attr("rel").is_some_and(|rel| rel.split_whitespace().any(|rel| rel.eq_ignore_ascii_case("stylesheet")));
+                        let stylesheet = attr("href").filter(|_| is_stylesheet).and_then(|href| self.inliner.read(self.page, &href));
+                        if let Some((path, content)) = stylesheet {
+                            let loaded = self.load_stylesheet(&path, &String::from_utf8_lossy(&content), 0);
+                            css.push_str(&apply_media(loaded, attr("media").as_deref()).unwrap_or_default());
+                            css.push('\n');
+                        }
+                        continue;
+                    }
+                    "style" => {
+                        let mut text = String::new();
+                        for node in child.children.borrow().iter() {
+                            if let NodeData::Text { contents } = &node.data {
+                                text.push_str(&contents.borrow());
+                            }
+                        }
+                        let loaded = self.load_stylesheet(self.page, &text, 0);
+                        css.push_str(&apply_media(loaded, attr("media").as_deref()).unwrap_or_default());
+                        css.push('\n');
+                        continue;
+                    }
+                    _ => {}
+                }
+            }
+            self.collect_css(child, css);
+        }
+    }
+
+    fn write_node(&self, handle: &Handle, kept_css: &str, styles: &HashMap<usize, String>, output: &mut EmailOutput) {
+        match &handle.data {
+            NodeData::Document => {
+                for child in handle.children.borrow().iter() {
+                    self.write_node(child, kept_css, styles, output);
+                }
+            }
+            NodeData::Doctype { .. } => output.html.push_str("<!DOCTYPE html>\n"),
+            NodeData::Text { contents } => output.html.push_str(&escape_html(&contents.borrow())),
+            NodeData::Element { name, attrs, .. } => {
+                let tag = name.local.to_string();
+                if DROPPED_ELEMENTS.contains(&tag.as_str()) {
+                    return;
+                }
+                let style = styles.get(&(Rc::as_ptr(handle) as usize));
+                output.html.push('<');
+                output.html.push_str(&tag);
+                for attribute in attrs.borrow().iter() {
+                    let local = attribute.name.local.to_string();
+                    if local.to_ascii_lowercase().starts_with("on") || local == "srcset" || local == "sizes" || (local == "style" && style.is_some()) {
+                        continue;
+                    }
+                    let value = match (tag.as_str(), local.as_str()) {
+                        ("a" | "area", "href") => self.link_href(&attribute.value),
+                        ("img" | "input", "src") | (_, "background") => match self.asset_url(self.page, &attribute.value) {
+                            Some(url) => {
+                                output.images += 1;
+                                url
+                            }
+                            None => attribute.value.to_string(),
+                        },
+                        _ => attribute.value.to_string(),
+                    };
+                    let attribute_name = match &attribute.name.prefix {
+                        Some(prefix) => format!("{}:{}", prefix, local),
+                        None => local,
+                    };
+                    output.html.push_str(&format!(" {}=\"{}\"", attribute_name, escape_html(&value)));
+                }
+                if let Some(style) = style {
+                    output.html.push_str(&format!(" style=\"{}\"", escape_html(style)));
+                }
+                output.html.push('>');
+                if tag == "head" {
+                    output.html.push_str("<meta charset=\"utf-8\">");
+                }
+                if VOID_ELEMENTS.contains(&tag.as_str()) {
+                    return;
+                }
+                for child in handle.children.borrow().iter() {
+                    self.write_node(child, kept_css, styles, output);
+                }
+                if tag == "head" && !kept_css.trim().is_empty() {
+                    output.html.push_str(&format!("<style>\n{}</style>", kept_css.replace("</style", "<\\/style")));
+                }
+                output.html.push_str(&format!("</{}>", tag));
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Write the mirrored page of `url` as one HTML file for pasting into an email:
+/// stylesheet rules inlined into `style` attributes (those that can't be, like
+/// `@media` and `:hover`, kept in a `<style>` block), images embedded as data
+/// URIs (or linked to the live site with `remote_images`), scripts and embeds
+/// removed and links pointing at the live site
+pub fn export_email(output_dir: &Path, url: &str, email_path: &Path, remote_images: bool) -> Result<EmailStats> {
+    let store = DownloadStore::load(output_dir)?;
+    let entry = store
+        .entries
+        .get(url)
+        .or_else(|| store.entries.get(&url_normalizer::normalize_url(url, false)))
+        .filter(|entry| !entry.local_path.is_empty())
+        .ok_or_else(|| anyhow!("{} isn't in the mirror at {}", url, output_dir.display()))?;
+    let page = dead_assets::normalize_path(Path::new(&entry.local_path));
+    if !snapshot_diff::is_page(&page) {
+        bail!("{} was saved as {}, which isn't a page", url, page);
+    }
+    let page_url = Url::parse(url).with_context(|| format!("Invalid URL: {}", url))?;
+    let urls: HashMap<String, String> = store
+        .entries
+        .iter()
+        .filter(|(_, entry)| !entry.local_path.is_empty())
+        .map(|(url, entry)| (dead_assets::normalize_path(Path::new(&entry.local_path)), url.clone()))
+        .collect();
+    let content_types = single_file::served_content_types(&store);
+    let inliner = Inliner { output_dir, content_types: &content_types };
+    let exporter = EmailExporter { page: &page, page_url: &page_url, urls: &urls, inliner: &inliner, remote_images };
+
+    let html = fs::read(output_dir.join(&page)).with_context(|| format!("Failed to read page: {:?}", page))?;
+    let dom = parse_document(RcDom::default(), ParseOpts::default()).one(&*String::from_utf8_lossy(&html));
+    let mut css = String::new();
+    exporter.collect_css(&dom.document, &mut css);
+    let mut stylesheet = Stylesheet::default();
+    stylesheet.add(&css);
+    let mut styles = HashMap::new();
+    stylesheet.compute_styles(&dom.document, &mut Vec::new(), &mut styles);
+
+    let mut output = EmailOutput::default();
+    exporter.write_node(&dom.document, &stylesheet.kept, &styles, &mut output);
+    if let Some(parent) = email_path.parent() {
+        fs::create_dir_all(parent).with_context(|| format!("Failed to create directory: {:?}", parent))?;
+    }
+    fs::write(email_path, &output.html).with_context(|| format!("Failed to write email page: {:?}", email_path))?;
+    Ok(EmailStats {
+        styled_elements: styles.len(),
+        images: output.images,
+        total_bytes: output.html.len() as u64,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::write;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_parse_selector() {
+        let selector = Selector::parse("#hero > p.lead[data-kind='intro']").unwrap();
+        assert_eq!(selector.specificity, (1, 2, 1));
+        assert_eq!(selector.parts.len(), 2);
+        assert_eq!(selector.parts[1].0, Combinator::Child);
+        assert_eq!(selector.parts[1].1.attributes, vec![("data-kind".to_string(), Some("intro".to_string()))]);
+        assert!(Selector::parse("a:hover").is_none());
+        assert!(Selector::parse("h1 + p").is_none());
+        assert!(Selector::parse("> p").is_none());
+        assert!(Selector::parse("div >").is_none());
+    }
+
+    #[test]
+    fn test_parse_declarations() {
+        let declarations = parse_declarations(r#"color: red !important; background: url("data:image/png;base64,AA==") no-repeat; ;bad"#);
+        assert_eq!(declarations, vec![
+            Declaration { property: "color".to_string(), value: "red".to_string(), important: true },
+            Declaration { property: "background".to_string(), value: r#"url("data:image/png;base64,AA==") no-repeat"#.to_string(), important: false },
+        ]);
+    }
+
+    #[test]
+    fn test_export_email() {
+        let temp_dir = tempdir().unwrap();
+        let dir = temp_dir.path().join("site");
+        write(&dir, "blog/launch/index.html", concat!(
+            r#"<!DOCTYPE html><html><head><title>Launch</title><meta name="generator" content="x">"#,
+            r#"<link rel="stylesheet" href="../../css/site.css"><link rel="stylesheet" href="../../css/print.css" media="print">"#,
+            r#"<style>#hero p.lead { font-size: 20px } p { margin: 0 }</style><script src="../../js/app.js"></script></head>"#,
+            r#"<body><div id="hero"><p class="lead" style="color: #333">Big news</p><p>Details</p></div>"#,
+            r#"<a class="btn" href="../../about/index.html#team" onclick="track()">Meet us</a>"#,
+            r#"<img src="../../img/logo.png" srcset="../../img/logo.png 1x, ../../img/logo@2x.png 2x" alt="Logo">"#,
+            r#"<iframe src="https://www.youtube.com/embed/x"></iframe></body></html>"#,
+        ));
+        write(&dir, "about/index.html", "<p>About</p>");
+        write(&dir, "css/site.css", concat!(
+            "@import url(\"base.css\");\n/* buttons */\n",
+            ".btn { color: #fff; background: #0a66c2 url(../img/logo.png) no-repeat }\n",
+            "a:hover, .btn { text-decoration: none }\n",
+            "p { margin: 0 0 1em; color: #111 !important }\n",
+            "@media (max-width: 600px) { p { font-size: 14px } }\n",
+        ));
+        write(&dir, "css/base.css", "body { font-family: \"Helvetica\", sans-serif }");
+        write(&dir, "css/print.css", "body { color: black }");
+        write(&dir, "img/logo.png", "png");
+        let mut store = DownloadStore::default();
+        store.record_download("https://example.com/blog/launch/", "blog/launch/index.html", Some("text/html"), 0);
+        store.record_download("https://example.com/about/", "about/index.html", Some("text/html"), 0);
+        store.record_download("https://cdn.example.com/logo.png", "img/logo.png", Some("image/png"), 3);
+        store.save(&dir).unwrap();
+
+        let email_path = temp_dir.path().join("launch.html");
+        let stats = export_email(&dir, "https://example.com/blog/launch/", &email_path, false).unwrap();
+        let html = fs::read_to_string(&email_path).unwrap();
+        let png = "data:image/png;base64,cG5n";
+
+        assert!(html.starts_with("<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\"><title>Launch</title><style>\n"));
+        assert!(html.contains("a:hover { text-decoration: none }"));
+        assert!(html.contains("@media (max-width: 600px) { p { font-size: 14px } }"));
+        assert!(!html.contains("<script") && !html.contains("<iframe") && !html.contains("<link") && !html.contains("generator"));
+        assert!(html.contains(r#"<body style="font-family: &quot;Helvetica&quot;, sans-serif">"#));
+        // The page's own style beats the stylesheets, except their !important rules
+        assert!(html.contains(r#"<p class="lead" style="margin: 0; font-size: 20px; color: #111">Big news</p>"#));
+        assert!(html.contains(r#"<p style="margin: 0; color: #111">Details</p>"#));
+        assert!(html.contains(&format!(
+            r#"<a class="btn" href="https://example.com/about/#team" style="color: #fff; background: #0a66c2 url(&quot;{}&quot;) no-repeat; text-decoration: none">Meet us</a>"#,
+            png
+        )));
+        assert!(html.contains(&format!(r#"<img src="{}" alt="Logo">"#, png)));
+        assert!(!html.contains("color: black"));
+        assert_eq!(stats.images, 1);
+        assert_eq!(stats.styled_elements, 4);
+
+        export_email(&dir, "https://example.com/blog/launch/", &email_path, true).unwrap();
+        let html = fs::read_to_string(&email_path).unwrap();
+        assert!(html.contains(r#"<img src="https://cdn.example.com/logo.png" alt="Logo">"#));
+        assert!(html.contains("url(&quot;https://cdn.example.com/logo.png&quot;)"));
+
+        assert!(export_email(&dir, "https://example.com/missing/", &email_path, false).is_err());
+    }
+}
@@ -0,0 +1,413 @@
+use anyhow::{anyhow, Result, Context};
+use regex::{Captures, Regex};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::LazyLock;
+use url::Url;
+
+use crate::gallery::escape_html;
+use crate::html_parser::HtmlParser;
+
+/// Previews fetched with `--oembed`, kept between runs (link -> preview)
+pub const OEMBED_FILE_NAME: &str = ".oembed.json";
+
+/// Size of the placeholder when the iframe doesn't set one (YouTube's default embed size)
+const DEFAULT_WIDTH: &str = "560px";
+const DEFAULT_HEIGHT: &str = "315px";
+
+/// Third-party embed whose iframe can be replaced by a static placeholder
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EmbedProvider {
+    YouTube,
+    Vimeo,
+    Twitter,
+    Maps,
+}
+
+impl EmbedProvider {
+    pub const ALL: [EmbedProvider; 4] = [EmbedProvider::YouTube, EmbedProvider::Vimeo, EmbedProvider::Twitter, EmbedProvider::Maps];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            EmbedProvider::YouTube => "youtube",
+            EmbedProvider::Vimeo => "vimeo",
+            EmbedProvider::Twitter => "twitter",
+            EmbedProvider::Maps => "maps",
+        }
+    }
+
+    /// Text of the placeholder's click-through link
+    fn label(&self) -> &'static str {
+        match self {
+            EmbedProvider::YouTube => "Watch on YouTube",
+            EmbedProvider::Vimeo => "Watch on Vimeo",
+            EmbedProvider::Twitter => "View on Twitter",
+            EmbedProvider::Maps => "Open in Google Maps",
+        }
+    }
+
+    /// oEmbed endpoint of the provider; others are found through the discovery
+    /// link on the embedded page
+    fn oembed_endpoint(&self) -> Option<&'static str> {
+        match self {
+            EmbedProvider::YouTube => Some("https://www.youtube.com/oembed"),
+            EmbedProvider::Vimeo => Some("https://vimeo.com/api/oembed.json"),
+            EmbedProvider::Twitter => Some("https://publish.twitter.com/oembed"),
+            EmbedProvider::Maps => None,
+        }
+    }
+
+    /// oEmbed request for the preview of `link`, if the provider has a known endpoint
+    pub fn oembed_request(&self, link: &str) -> Option<String> {
+        let endpoint = self.oembed_endpoint()?;
+        Url::parse_with_params(endpoint, &[("url", link), ("format", "json")]).ok().map(String::from)
+    }
+
+    /// Provider serving an iframe `src`, if it is one of the known embeds
+    pub fn detect(src: &Url) -> Option<Self> {
+        let host = src.host_str()?.to_ascii_lowercase();
+        let host = host.strip_prefix("www.").unwrap_or(&host);
+        let path = src.path();
+        match host {
+            "youtube.com" | "youtube-nocookie.com" | "m.youtube.com" if path.starts_with("/embed/") => Some(EmbedProvider::YouTube),
+            "player.vimeo.com" if path.starts_with("/video/") => Some(EmbedProvider::Vimeo),
+            "platform.twitter.com" | "platform.x.com" if path.starts_with("/embed/") => Some(EmbedProvider::Twitter),
+            "maps.google.com" => Some(EmbedProvider::Maps),
+            _ if host.starts_with("google.") && path.starts_with("/maps") => Some(EmbedProvider::Maps),
+            _ => None,
+        }
+    }
+
+    /// Page on the provider's site the placeholder links to, and a thumbnail
+    /// image when the provider serves one at a predictable URL
+    fn target(&self, src: &Url) -> (String, Option<String>) {
+        let param = |name: &str| src.query_pairs().find(|(key, _)| key == name).map(|(_, value)| value.into_owned());
+        let last_segment = src.path_segments().and_then(|mut segments| segments.next_back()).unwrap_or("").to_string();
+        match self {
+            EmbedProvider::YouTube => match (last_segment.as_str(), param("list")) {
+                ("videoseries", Some(list)) => (format!("https://www.youtube.com/playlist?list={}", list), None),
+                (id, _) if !id.is_empty() => (
+                    format!("https://www.youtube.com/watch?v={}", id),
+                    Some(format!("https://i.ytimg.com/vi/{}/hqdefault.jpg", id)),
+                ),
+                _ => (src.to_string(), None),
+            },
+            EmbedProvider::Vimeo if last_segment.chars().all(|c| c.is_ascii_digit()) => {
+                (format!("https://vimeo.com/{}", last_segment), None)
+            }
+            EmbedProvider::Twitter => match param("id") {
+                Some(id) => (format!("https://twitter.com/i/status/{}", id), None),
+                None => (src.to_string(), None),
+            },
+            EmbedProvider::Maps => match param("q") {
+                Some(query) => {
+                    let mut link = Url::parse("https://www.google.com/maps").unwrap();
+                    link.query_pairs_mut().append_pair("q", &query);
+                    (link.to_string(), None)
+                }
+                None => (src.to_string(), None),
+            },
+            _ => (src.to_string(), None),
+        }
+    }
+}
+
+impl FromStr for EmbedProvider {
+    type Err = anyhow::Error;
+
+    fn from_str(name: &str) -> Result<Self> {
+        EmbedProvider::ALL
+            .into_iter()
+            .find(|provider| provider.name().eq_ignore_ascii_case(name.trim()))
+            .ok_or_else(|| anyhow!("Unknown embed provider: {} (expected youtube, vimeo, twitter, maps or all)", name))
+    }
+}
+
+/// Providers named on the command line, where `all` stands for every provider
+pub fn parse_providers(names: &[String]) -> Result<Vec<EmbedProvider>> {
+    let mut providers = Vec::new();
+    for name in names {
+        let named = if name.trim().eq_ignore_ascii_case("all") {
+            EmbedProvider::ALL.to_vec()
+        } else {
+            vec![name.parse()?]
+        };
+        for provider in named {
+            if !providers.contains(&provider) {
+                providers.push(provider);
+            }
+        }
+    }
+    Ok(providers)
+}
+
+/// Preview of an embed from its provider's oEmbed response; the other fields
+/// of the response are ignored
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OEmbed {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub author_name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub thumbnail_url: Option<String>,
+}
+
+static LINK_TAG: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(?is)<link\b[^>]*>").unwrap());
+
+/// JSON oEmbed endpoint a page advertises with
+/// `<link rel="alternate" type="application/json+oembed" href="...">`
+pub fn discover_oembed(html_content: &str, page_url: &str) -> Option<String> {
+    let page_url = Url::parse(page_url).ok()?;
+    let endpoint = LINK_TAG.find_iter(html_content).find_map(|tag| {
+        let tag = tag.as_str();
+        let is_oembed = HtmlParser::tag_attribute(tag, "type")
+            .is_some_and(|link_type| link_type.trim().eq_ignore_ascii_case("application/json+oembed"));
+        let href = HtmlParser::tag_attribute(tag, "href").filter(|_| is_oembed)?;
+        page_url.join(&href.trim().replace("&amp;", "&")).ok().map(String::from)
+    });
+    endpoint
+}
+
+/// Previews saved by an earlier run, empty if there are none
+pub fn load_previews(output_dir: &Path) -> Result<BTreeMap<String, OEmbed>> {
+    let path = output_dir.join(OEMBED_FILE_NAME);
+    if !path.exists() {
+        return Ok(BTreeMap::new());
+    }
+    let data = fs::read_to_string(&path).with_context(|| format!("Failed to read oEmbed previews: {:?}", path))?;
+    serde_json::from_str(&data).with_context(|| format!("Failed to parse oEmbed previews: {:?}", path))
+}
+
+pub fn save_previews(output_dir: &Path, previews: &BTreeMap<String, OEmbed>) -> Result<()> {
+    let path = output_dir.join(OEMBED_FILE_NAME);
+    fs::write(&path, serde_json::to_string_pretty(previews)?)
+        .with_context(|| format!("Failed to write oEmbed previews: {:?}", path))
+}
+
+/// CSS length of an iframe `width`/`height` attribute; anything but a plain
+/// number, pixel or percentage value falls back to `default`
+fn css_length(value: Option<&str>, default: &str) -> String {
+    let length_regex = Regex::new(r"^\d+(?:\.\d+)?(px|%)?$").unwrap();
+    match value.map(str::trim) {
+        Some(value) if length_regex.is_match(value) => {
+            if value.ends_with("px") || value.ends_with('%') {
+                value.to_string()
+            } else {
+                format!("{}px", value)
+            }
+        }
+        _ => default.to_string(),
+    }
+}
+
+/// Static stand-in for an embed: a box of the iframe's size with the thumbnail
+/// (if any) and a link to the original on the provider's site. An oEmbed
+/// `preview` supplies the thumbnail and caption when the iframe has none.
+fn placeholder(provider: EmbedProvider, tag: &str, link: &str, thumbnail: Option<String>, preview: Option<&OEmbed>) -> String {
+    let thumbnail = preview.and_then(|preview| preview.thumbnail_url.clone()).or(thumbnail);
+    let width = css_length(HtmlParser::tag_attribute(tag, "width"), DEFAULT_WIDTH);
+    let height = css_length(HtmlParser::tag_attribute(tag, "height"), DEFAULT_HEIGHT);
+    // The iframe title is already attribute text, entities included
+    let caption = HtmlParser::tag_attribute(tag, "title")
+        .map(str::trim)
+        .filter(|title| !title.is_empty())
+        .map(|title| title.replace('<', "&lt;").replace('>', "&gt;"))
+        .or_else(|| {
+            let preview = preview?;
+            let title = preview.title.as_deref().map(str::trim).filter(|title| !title.is_empty())?;
+            Some(match preview.author_name.as_deref().map(str::trim).filter(|author| !author.is_empty()) {
+                Some(author) => format!("{} ({})", escape_html(title), escape_html(author)),
+                None => escape_html(title),
+            })
+        });
+    let label = match caption {
+        Some(caption) => format!("{} · {}", caption, provider.label()),
+        None => provider.label().to_string(),
+    };
+    let image = thumbnail
+        .map(|thumbnail| format!(
+            r#"<img src="{}" alt="" style="position:absolute;top:0;left:0;width:100%;height:100%;object-fit:cover">"#,
+            escape_html(&thumbnail)
+        ))
+        .unwrap_or_default();
+    format!(
+        concat!(
+            r#"<a href="{link}" class="embed-placeholder embed-placeholder-{name}" target="_blank" rel="noopener" "#,
+            r#"style="display:inline-block;position:relative;width:{width};height:{height};max-width:100%;background:#202124;"#,
+            r#"color:#fff;font:15px/1.4 sans-serif;text-decoration:none;overflow:hidden;vertical-align:middle">"#,
+            r#"{image}<span style="position:absolute;left:0;right:0;bottom:0;padding:8px 12px;background:rgba(0,0,0,.7)">▶ {label}</span></a>"#,
+        ),
+        link = escape_html(link),
+        name = provider.name(),
+        width = width,
+        height = height,
+        image = image,
+        label = label,
+    )
+}
+
+/// An embed of one of the placeholder providers found on a page
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Embed {
+    pub provider: EmbedProvider,
+    /// Page on the provider's site the placeholder links to
+    pub link: String,
+}
+
+/// Apply `replace` to every iframe of `providers` (with its fallback content),
+/// leaving other iframes alone. Lazy iframes are recognized by their `data-src`.
+fn for_each_embed<F>(html_content: &str, page_url: &str, providers: &[EmbedProvider], mut replace: F) -> String
+where
+    F: FnMut(EmbedProvider, &str, &Url) -> Option<String>,
+{
+    let iframe_regex = Regex::new(r"(?is)(<iframe\b[^>]*>)(?:.*?</iframe\s*>)?").unwrap();
+    let page_url = Url::parse(page_url).ok();
+    iframe_regex
+        .replace_all(html_content, |caps: &Captures| {
+            let tag = &caps[1];
+            let src = HtmlParser::tag_attribute(tag, "src")
+                .filter(|src| !src.trim().is_empty() && !src.trim().eq_ignore_ascii_case("about:blank"))
+                .or_else(|| HtmlParser::tag_attribute(tag, "data-src"));
+            let src = src.map(|src| src.trim().replace("&amp;", "&")).and_then(|src| match &page_url {
+                Some(page_url) => page_url.join(&src).ok(),
+                None => Url::parse(&src).ok(),
+            });
+            src.and_then(|src| {
+                let provider = EmbedProvider::detect(&src).filter(|provider| providers.contains(provider))?;
+                replace(provider, tag, &src)
+            })
+            .unwrap_or_else(|| caps[0].to_string())
+        })
+        .into_owned()
+}
+
+/// Embeds of `providers` on a page, in page order
+pub fn find_embeds(html_content: &str, page_url: &str, providers: &[EmbedProvider]) -> Vec<Embed> {
+    let mut embeds = Vec::new();
+    for_each_embed(html_content, page_url, providers, |provider, _, src| {
+        embeds.push(Embed { provider, link: provider.target(src).0 });
+        None
+    });
+    embeds
+}
+
+/// Replace the iframes of `providers` by static placeholders linking to the
+/// original, with the oEmbed `previews` (by link) fetched for them, returning
+/// the page and how many were replaced
+pub fn replace_embeds(html_content: &str, page_url: &str, providers: &[EmbedProvider], previews: &HashMap<String, OEmbed>) -> (String, usize) {
+    let mut replaced = 0;
+    let updated = for_each_embed(html_content, page_url, providers, |provider, tag, src| {
+        replaced += 1;
+        let (link, thumbnail) = provider.target(src);
+        Some(placeholder(provider, tag, &link, thumbnail, previews.get(&link)))
+    });
+    (updated, replaced)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_providers() {
+        assert_eq!(parse_providers(&["youtube".to_string(), "Maps".to_string()]).unwrap(), vec![EmbedProvider::YouTube, EmbedProvider::Maps]);
+        assert_eq!(parse_providers(&["maps".to_string(), "all".to_string()]).unwrap().len(), 4);
+        assert!(parse_providers(&["myspace".to_string()]).is_err());
+    }
+
+    #[test]
+    fn test_detect_provider() {
+        let detect = |src: &str| EmbedProvider::detect(&Url::parse(src).unwrap());
+        assert_eq!(detect("https://www.youtube-nocookie.com/embed/dQw4w9WgXcQ"), Some(EmbedProvider::YouTube));
+        assert_eq!(detect("https://player.vimeo.com/video/76979871"), Some(EmbedProvider::Vimeo));
+        assert_eq!(detect("https://platform.twitter.com/embed/Tweet.html?id=20"), Some(EmbedProvider::Twitter));
+        assert_eq!(detect("https://www.google.com/maps/embed?pb=!1m18"), Some(EmbedProvider::Maps));
+        assert_eq!(detect("https://www.youtube.com/watch?v=dQw4w9WgXcQ"), None);
+        assert_eq!(detect("https://example.com/embed/video"), None);
+    }
+
+    #[test]
+    fn test_replace_embeds() {
+        let html = concat!(
+            r#"<iframe width="640" height="360" src="//www.youtube.com/embed/dQw4w9WgXcQ?rel=0" title="Launch &amp; demo" allowfullscreen></iframe>"#,
+            r#"<iframe data-src="https://www.google.com/maps/embed/v1/place?key=k&amp;q=Eiffel+Tower" width="100%"><p>Map</p></iframe>"#,
+            r#"<iframe src="https://player.vimeo.com/video/76979871"></iframe>"#,
+            r#"<iframe src="/widgets/calendar.html"></iframe>"#,
+        );
+        let (updated, replaced) = replace_embeds(html, "https://example.com/about/", &[EmbedProvider::YouTube, EmbedProvider::Maps], &HashMap::new());
+        assert_eq!(replaced, 2);
+
+        assert!(updated.starts_with(r#"<a href="https://www.youtube.com/watch?v=dQw4w9WgXcQ" class="embed-placeholder embed-placeholder-youtube""#));
+        assert!(updated.contains("width:640px;height:360px;"));
+        assert!(updated.contains(r#"<img src="https://i.ytimg.com/vi/dQw4w9WgXcQ/hqdefault.jpg""#));
+        assert!(updated.contains("▶ Launch &amp; demo · Watch on YouTube</span></a>"));
+
+        assert!(updated.contains(r#"<a href="https://www.google.com/maps?q=Eiffel+Tower" class="embed-placeholder embed-placeholder-maps""#));
+        assert!(updated.contains("width:100%;height:315px;"));
+        assert!(!updated.contains("<p>Map</p>"));
+
+        assert!(updated.contains(r#"<iframe src="https://player.vimeo.com/video/76979871"></iframe>"#));
+        assert!(updated.ends_with(r#"<iframe src="/widgets/calendar.html"></iframe>"#));
+    }
+
+    #[test]
+    fn test_oembed_previews() {
+        let html = r#"<iframe src="https://player.vimeo.com/video/76979871"></iframe><iframe src="https://www.youtube.com/embed/dQw4w9WgXcQ" title="Launch"></iframe>"#;
+        let providers = EmbedProvider::ALL;
+        let embeds = find_embeds(html, "https://example.com/", &providers);
+        assert_eq!(embeds, vec![
+            Embed { provider: EmbedProvider::Vimeo, link: "https://vimeo.com/76979871".to_string() },
+            Embed { provider: EmbedProvider::YouTube, link: "https://www.youtube.com/watch?v=dQw4w9WgXcQ".to_string() },
+        ]);
+        assert_eq!(
+            EmbedProvider::Vimeo.oembed_request(&embeds[0].link).as_deref(),
+            Some("https://vimeo.com/api/oembed.json?url=https%3A%2F%2Fvimeo.com%2F76979871&format=json")
+        );
+        assert_eq!(EmbedProvider::Maps.oembed_request("https://www.google.com/maps?q=Paris"), None);
+
+        let previews = HashMap::from([
+            ("https://vimeo.com/76979871".to_string(), OEmbed {
+                title: Some("The <Mountain>".to_string()),
+                author_name: Some("TSO Photography".to_string()),
+                thumbnail_url: Some("https://i.vimeocdn.com/video/452001751-640.jpg".to_string()),
+            }),
+            ("https://www.youtube.com/watch?v=dQw4w9WgXcQ".to_string(), OEmbed {
+                title: Some("Never Gonna Give You Up".to_string()),
+                author_name: None,
+                thumbnail_url: Some("https://i.ytimg.com/vi/dQw4w9WgXcQ/maxresdefault.jpg".to_string()),
+            }),
+        ]);
+        let (updated, replaced) = replace_embeds(html, "https://example.com/", &providers, &previews);
+        assert_eq!(replaced, 2);
+        assert!(updated.contains(r#"<img src="https://i.vimeocdn.com/video/452001751-640.jpg""#));
+        assert!(updated.contains("▶ The &lt;Mountain&gt; (TSO Photography) · Watch on Vimeo</span>"));
+        // The iframe's own title wins over the oEmbed one
+        assert!(updated.contains(r#"<img src="https://i.ytimg.com/vi/dQw4w9WgXcQ/maxresdefault.jpg""#));
+        assert!(updated.contains("▶ Launch · Watch on YouTube</span>"));
+    }
+
+    #[test]
+    fn test_discover_oembed() {
+        let html = concat!(
+            r#"<link rel="alternate" type="application/xml+oembed" href="/oembed?format=xml">"#,
+            r#"<link rel="alternate" type="application/json+oembed" href="/oembed?url=https%3A%2F%2Fexample.org%2Fp%2F1&amp;format=json" title="Post">"#,
+        );
+        assert_eq!(
+            discover_oembed(html, "https://example.org/p/1").as_deref(),
+            Some("https://example.org/oembed?url=https%3A%2F%2Fexample.org%2Fp%2F1&format=json")
+        );
+        assert_eq!(discover_oembed("<p>No preview</p>", "https://example.org/"), None);
+    }
+
+    #[test]
+    fn test_css_length() {
+        assert_eq!(css_length(Some("480"), DEFAULT_WIDTH), "480px");
+        assert_eq!(css_length(Some("50%"), DEFAULT_WIDTH), "50%");
+        assert_eq!(css_length(Some("1;background:red"), DEFAULT_WIDTH), DEFAULT_WIDTH);
+        assert_eq!(css_length(None, DEFAULT_HEIGHT), DEFAULT_HEIGHT);
+    }
+}
@@ -0,0 +1,170 @@
+use encoding_rs::{Encoding, UTF_8};
+use regex::Regex;
+
+/// How far into a page `<meta charset>` is looked for, as browsers do
+const META_PRESCAN_BYTES: usize = 1024;
+
+/// `charset=` parameter of a `Content-Type` header value
+fn content_type_charset(content_type: &str) -> Option<&'static Encoding> {
+    content_type
+        .split(';')
+        .skip(1)
+        .filter_map(|param| param.split_once('='))
+        .find(|(name, _)| name.trim().eq_ignore_ascii_case("charset"))
+        .and_then(|(_, value)| Encoding::for_label(value.trim().trim_matches(['"', '\'']).as_bytes()))
+}
+
+/// `<meta charset>` or `<meta http-equiv="Content-Type" content="...; charset=...">`
+/// within the first kilobyte of the page
+fn meta_charset(content: &[u8]) -> Option<&'static Encoding> {
+    let prefix = String::from_utf8_lossy(&content[..content.len().min(META_PRESCAN_BYTES)]);
+    let charset_regex = Regex::new(r#"(?i)<meta\b[^>]*?charset\s*=\s*["']?\s*([A-Za-z0-9._:-]+)"#).unwrap();
+    let label = charset_regex.captures(&prefix)?.get(1)?.as_str().to_string();
+    meta_label_encoding(&label)
+}
+
+/// Encoding a browser uses for a `<meta>` charset label
+fn meta_label_encoding(label: &str) -> Option<&'static Encoding> {
+    match Encoding::for_label(label.as_bytes()) {
+        // A page that was decoded at all can't really be UTF-16; browsers treat the label as UTF-8
+        Some(encoding) if encoding.output_encoding() == UTF_8 => Some(UTF_8),
+        encoding => encoding,
+    }
+}
+
+/// Character encoding of an HTML page: byte order mark, then the
+/// `Content-Type` charset, then `<meta charset>`, defaulting to UTF-8
+pub fn detect_html_encoding(content_type: &str, content: &[u8]) -> &'static Encoding {
+    if let Some((encoding, _)) = Encoding::for_bom(content) {
+        return encoding;
+    }
+    content_type_charset(content_type)
+        .or_else(|| meta_charset(content))
+        .unwrap_or(UTF_8)
+}
+
+/// Decode a page (malformed sequences become U+FFFD)
+pub fn decode_html(content: &[u8], encoding: &'static Encoding) -> String {
+    let (decoded, _, _) = encoding.decode(content);
+    decoded.into_owned()
+}
+
+/// Encode a page back into its original encoding; characters the encoding
+/// can't represent (e.g. in rewritten paths) become numeric character references
+pub fn encode_html(html_content: &str, encoding: &'static Encoding) -> Vec<u8> {
+    let (encoded, _, _) = encoding.output_encoding().encode(html_content);
+    encoded.into_owned()
+}
+
+/// Point the page's `<meta charset>` declarations at `charset`, adding one
+/// at the start of `<head>` if the page has none
+pub fn set_meta_charset(html_content: &str, charset: &str) -> String {
+    let charset_regex = Regex::new(r#"(?i)(<meta\b[^>]*?charset\s*=\s*["']?\s*)([A-Za-z0-9._:-]+)"#).unwrap();
+    if charset_regex.is_match(html_content) {
+        return charset_regex
+            .replace_all(html_content, |caps: &regex::Captures| format!("{}{}", &caps[1], charset))
+            .into_owned();
+    }
+
+    let meta = format!("<meta charset=\"{}\">", charset);
+    let head_regex = Regex::new(r"(?i)<head\b[^>]*>").unwrap();
+    match head_regex.find(html_content) {
+        Some(head) => format!("{}{}{}", &html_content[..head.end()], meta, &html_content[head.end()..]),
+        None => format!("{}{}", meta, html_content),
+    }
+}
+
+/// Make the page's charset declarations agree with `encoding`, the encoding
+/// of the bytes actually written, so it renders correctly opened via file://
+/// where there is no `Content-Type` header: `<meta charset>` and
+/// `<meta http-equiv="Content-Type">` tags naming another charset are removed,
+/// and `<meta charset>` is added at the start of `<head>` unless a matching
+/// declaration is already within the first kilobyte
+pub fn fix_meta_charset(html_content: &str, encoding: &'static Encoding) -> String {
+    let encoding = encoding.output_encoding();
+    let meta_regex = Regex::new(r"(?is)<meta\b[^>]*>").unwrap();
+    let charset_regex = Regex::new(r#"(?i)charset\s*=\s*["']?\s*([A-Za-z0-9._:-]+)"#).unwrap();
+    let declared = |tag: &str| charset_regex.captures(tag).map(|caps| meta_label_encoding(&caps[1]));
+
+    let fixed = meta_regex
+        .replace_all(html_content, |caps: &regex::Captures| match declared(&caps[0]) {
+            Some(declared) if declared != Some(encoding) => String::new(),
+            _ => caps[0].to_string(),
+        })
+        .into_owned();
+    let declared_in_prescan = meta_regex
+        .find_iter(&fixed)
+        .take_while(|tag| tag.end() <= META_PRESCAN_BYTES)
+        .any(|tag| declared(tag.as_str()).is_some());
+    if declared_in_prescan {
+        return fixed;
+    }
+
+    // Browsers ignore declarations past the prescan, so the late ones are replaced
+    let fixed = meta_regex
+        .replace_all(&fixed, |caps: &regex::Captures| if declared(&caps[0]).is_some() { String::new() } else { caps[0].to_string() })
+        .into_owned();
+    let meta = format!("<meta charset=\"{}\">", encoding.name());
+    // After <head>, else <html>, else the doctype: a tag before the doctype would
+    // put the page in quirks mode
+    let insert_at = [r"<head\b[^>]*>", r"<html\b[^>]*>", r"<!doctype\b[^>]*>"]
+        .iter()
+        .find_map(|pattern| Regex::new(&format!("(?i){}", pattern)).unwrap().find(&fixed).map(|tag| tag.end()))
+        .unwrap_or(0);
+    format!("{}{}{}", &fixed[..insert_at], meta, &fixed[insert_at..])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use encoding_rs::{GBK, SHIFT_JIS, WINDOWS_1252};
+
+    #[test]
+    fn test_detect_html_encoding() {
+        assert_eq!(detect_html_encoding("text/html; charset=Shift_JIS", b"<html></html>"), SHIFT_JIS);
+        assert_eq!(detect_html_encoding("text/html", br#"<html><head><meta charset="gbk"></head></html>"#), GBK);
+        assert_eq!(
+            detect_html_encoding("text/html", br#"<meta http-equiv="Content-Type" content="text/html; charset=iso-8859-1">"#),
+            WINDOWS_1252
+        );
+        assert_eq!(detect_html_encoding("text/html; charset=utf-8", br#"<meta charset="gbk">"#), UTF_8);
+        assert_eq!(detect_html_encoding("text/html", b"<html></html>"), UTF_8);
+    }
+
+    #[test]
+    fn test_decode_and_encode_roundtrip() {
+        let (shift_jis, _, _) = SHIFT_JIS.encode("<p>日本語</p>");
+        let decoded = decode_html(&shift_jis, SHIFT_JIS);
+        assert_eq!(decoded, "<p>日本語</p>");
+        assert_eq!(encode_html(&decoded, SHIFT_JIS), shift_jis.into_owned());
+    }
+
+    #[test]
+    fn test_set_meta_charset() {
+        let html = r#"<html><head><meta http-equiv="Content-Type" content="text/html; charset=Shift_JIS"></head></html>"#;
+        assert!(set_meta_charset(html, "utf-8").contains(r#"content="text/html; charset=utf-8""#));
+
+        let html = "<html><head><title>t</title></head></html>";
+        assert_eq!(set_meta_charset(html, "utf-8"), r#"<html><head><meta charset="utf-8"><title>t</title></head></html>"#);
+    }
+
+    #[test]
+    fn test_fix_meta_charset() {
+        // Transcoded to UTF-8: the conflicting http-equiv header goes, a meta charset is added
+        let html = r#"<html><head><meta http-equiv="Content-Type" content="text/html; charset=Shift_JIS"><meta name="viewport" content="width=device-width"></head></html>"#;
+        assert_eq!(
+            fix_meta_charset(html, UTF_8),
+            r#"<html><head><meta charset="UTF-8"><meta name="viewport" content="width=device-width"></head></html>"#
+        );
+
+        // A matching declaration is kept as it is
+        let html = r#"<!DOCTYPE html><html><head><meta charset="shift-jis"></head></html>"#;
+        assert_eq!(fix_meta_charset(html, SHIFT_JIS), html);
+
+        // A declaration past the first kilobyte is moved into it
+        let html = format!("<!DOCTYPE html><p>{}</p><meta charset=\"utf-8\">", "x".repeat(META_PRESCAN_BYTES));
+        let fixed = fix_meta_charset(&html, UTF_8);
+        assert!(fixed.starts_with(r#"<!DOCTYPE html><meta charset="UTF-8"><p>"#));
+        assert_eq!(fixed.matches("charset").count(), 1);
+    }
+}
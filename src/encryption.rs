@@ -0,0 +1,133 @@
+use anyhow::{anyhow, Result, Context};
+use std::fs;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// Extension appended to encrypted exports (`site.pack` -> `site.pack.age`)
+pub const AGE_EXTENSION: &str = "age";
+
+/// How an export is encrypted at rest, from `--encrypt <SCHEME>:<KEY>`
+#[derive(Clone)]
+pub enum Encryption {
+    /// age (https://age-encryption.org) to an X25519 recipient (`age1...`)
+    Age(age::x25519::Recipient),
+}
+
+impl FromStr for Encryption {
+    type Err = anyhow::Error;
+
+    fn from_str(spec: &str) -> Result<Self> {
+        match spec.split_once(':') {
+            Some(("age", recipient)) => recipient
+                .trim()
+                .parse::<age::x25519::Recipient>()
+                .map(Encryption::Age)
+                .map_err(|e| anyhow!("Invalid age recipient {}: {}", recipient, e)),
+            _ => Err(anyhow!("Unsupported encryption: {} (expected age:<recipient>)", spec)),
+        }
+    }
+}
+
+impl Encryption {
+    /// Wrap `output` so everything written to it is encrypted; call `finish()`
+    /// on the returned writer to flush the final chunk
+    pub fn wrap_output<W: Write>(&self, output: W) -> Result<age::stream::StreamWriter<W>> {
+        match self {
+            Encryption::Age(recipient) => {
+                let recipients: Vec<Box<dyn age::Recipient + Send>> = vec![Box::new(recipient.clone())];
+                let encryptor = age::Encryptor::with_recipients(recipients)
+                    .ok_or_else(|| anyhow!("No age recipient given"))?;
+                Ok(encryptor.wrap_output(output)?)
+            }
+        }
+    }
+}
+
+/// `site.pack` -> `site.pack.age`
+pub fn encrypted_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".");
+    name.push(AGE_EXTENSION);
+    PathBuf::from(name)
+}
+
+/// `site.pack.age` -> `Some(site.pack)`; `None` for unencrypted paths
+pub fn decrypted_path(path: &Path) -> Option<PathBuf> {
+    if path.extension().is_some_and(|extension| extension == AGE_EXTENSION) {
+        Some(path.with_extension(""))
+    } else {
+        None
+    }
+}
+
+/// X25519 identities (`AGE-SECRET-KEY-1...`) from an age identity file, as
+/// written by `age-keygen`; comment and blank lines are ignored
+pub fn load_identities(identity_file: &Path) -> Result<Vec<age::x25519::Identity>> {
+    let data = fs::read_to_string(identity_file)
+        .with_context(|| format!("Failed to read identity file: {:?}", identity_file))?;
+    let identities = data
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| line.parse::<age::x25519::Identity>().map_err(|e| anyhow!("Invalid age identity in {:?}: {}", identity_file, e)))
+        .collect::<Result<Vec<_>>>()?;
+    if identities.is_empty() {
+        return Err(anyhow!("No age identities in {:?}", identity_file));
+    }
+    Ok(identities)
+}
+
+/// Reader yielding the plaintext of age-encrypted `input`
+pub fn decrypt_reader<R: Read>(input: R, identities: &[age::x25519::Identity]) -> Result<age::stream::StreamReader<R>> {
+    let decryptor = match age::Decryptor::new(input)? {
+        age::Decryptor::Recipients(decryptor) => decryptor,
+        _ => return Err(anyhow!("Passphrase-encrypted files aren't supported, use an age identity")),
+    };
+    Ok(decryptor.decrypt(identities.iter().map(|identity| identity as &dyn age::Identity))?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use age::secrecy::ExposeSecret;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_parse_encryption() {
+        let identity = age::x25519::Identity::generate();
+        let spec = format!("age:{}", identity.to_public());
+        assert!(matches!(spec.parse::<Encryption>(), Ok(Encryption::Age(_))));
+        assert!("age:not-a-key".parse::<Encryption>().is_err());
+        assert!("gpg:ABCDEF".parse::<Encryption>().is_err());
+    }
+
+    #[test]
+    fn test_encrypt_and_decrypt_round_trip() {
+        let temp_dir = tempdir().unwrap();
+        let identity = age::x25519::Identity::generate();
+        let identity_file = temp_dir.path().join("key.txt");
+        fs::write(&identity_file, format!("# created: today\n{}\n", identity.to_string().expose_secret())).unwrap();
+
+        let encryption = Encryption::Age(identity.to_public());
+        let mut writer = encryption.wrap_output(Vec::new()).unwrap();
+        writer.write_all(b"pre-release pricing").unwrap();
+        let ciphertext = writer.finish().unwrap();
+        assert!(!ciphertext.windows(7).any(|window| window == b"pricing"));
+
+        let identities = load_identities(&identity_file).unwrap();
+        let mut plaintext = String::new();
+        decrypt_reader(&ciphertext[..], &identities).unwrap().read_to_string(&mut plaintext).unwrap();
+        assert_eq!(plaintext, "pre-release pricing");
+
+        let other = age::x25519::Identity::generate();
+        assert!(decrypt_reader(&ciphertext[..], &[other]).is_err());
+    }
+
+    #[test]
+    fn test_encrypted_paths() {
+        assert_eq!(encrypted_path(Path::new("out/site.pack")), PathBuf::from("out/site.pack.age"));
+        assert_eq!(decrypted_path(Path::new("out/site.pack.age")), Some(PathBuf::from("out/site.pack")));
+        assert_eq!(decrypted_path(Path::new("out/site.pack")), None);
+    }
+}
@@ -0,0 +1,547 @@
+use anyhow::{bail, Result, Context};
+use html5ever::tendril::TendrilSink;
+use html5ever::{parse_document, ParseOpts};
+use markup5ever_rcdom::{Handle, NodeData, RcDom};
+use mime_guess::MimeGuess;
+use regex::Regex;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
+use std::fs::{self, File};
+use std::io::BufWriter;
+use std::path::Path;
+use std::time::UNIX_EPOCH;
+
+use crate::archive::{self, ArchiveFile};
+use crate::dead_assets::{self, DocumentKind};
+use crate::search;
+use crate::snapshot_diff;
+use crate::store::{self, DownloadStore};
+
+/// Directory of the book's content inside the EPUB
+const CONTENT_DIR: &str = "OEBPS";
+/// Navigation document and NCX table of contents, named so they don't collide
+/// with a mirrored page
+const NAV_FILE: &str = "_toc.xhtml";
+const NCX_FILE: &str = "_toc.ncx";
+
+const XHTML_NS: &str = "http://www.w3.org/1999/xhtml";
+const SVG_NS: &str = "http://www.w3.org/2000/svg";
+const MATHML_NS: &str = "http://www.w3.org/1998/Math/MathML";
+
+const VOID_ELEMENTS: &[&str] = &["area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta", "param", "source", "track", "wbr"];
+/// Left out of chapters with everything inside them: scripts and embedded
+/// content reading systems don't run, and head metadata for the live site
+const DROPPED_ELEMENTS: &[&str] = &["script", "noscript", "iframe", "object", "embed", "template", "base", "meta"];
+
+const NAV_PATTERN: &str = r"(?is)<nav\b[^>]*>(.*?)</nav\s*>";
+const ANCHOR_HREF_PATTERN: &str = r#"(?is)<a\b[^>]*?\shref\s*=\s*(?:"([^"]*)"|'([^']*)')"#;
+const SITEMAP_LOC_PATTERN: &str = r"(?is)<loc>\s*(.*?)\s*</loc>";
+const HTML_LANG_PATTERN: &str = r#"(?is)<html\b[^>]*?\slang\s*=\s*["']?([A-Za-z0-9-]+)"#;
+
+/// Outcome of exporting a mirror as an EPUB book
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EpubStats {
+    /// Pages in the book, in reading order
+    pub chapters: usize,
+    /// Stylesheets, images and fonts embedded
+    pub resources: usize,
+    pub total_bytes: u64,
+}
+
+/// `blog/index.html` -> `blog/index.xhtml`
+fn chapter_path(page: &str) -> String {
+    Path::new(page).with_extension("xhtml").to_string_lossy().replace('\\', "/")
+}
+
+/// `path` relative to the directory of `from`, both inside the book
+fn relative_href(from: &str, path: &str) -> String {
+    let from_dir = Path::new(from).parent().unwrap_or_else(|| Path::new(""));
+    let relative = pathdiff::diff_paths(path, from_dir).map_or_else(|| path.to_string(), |relative| relative.to_string_lossy().replace('\\', "/"));
+    escape_href(&relative)
+}
+
+/// Percent-encode what isn't allowed unescaped in a URL path
+fn escape_href(path: &str) -> String {
+    let mut escaped = String::with_capacity(path.len());
+    for byte in path.bytes() {
+        if byte.is_ascii_alphanumeric() || b"-._~/!$&'()*+,;=:@%".contains(&byte) {
+            escaped.push(byte as char);
+        } else {
+            escaped.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    escaped
+}
+
+/// Escape text and attribute values for XML, dropping characters XML doesn't allow
+fn escape_xml(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\t' | '\n' | '\r' => escaped.push(c),
+            c if (c as u32) < 0x20 || c == '\u{FFFE}' || c == '\u{FFFF}' => {}
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Whether `name` can be written as an XML element or attribute name
+fn is_xml_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars.next().is_some_and(|first| first.is_alphabetic() || first == '_')
+        && chars.all(|c| c.is_alphanumeric() || matches!(c, '-' | '_' | '.'))
+}
+
+/// The page a link in `document` leads to, if it is in the book (`blog/`
+/// leads to `blog/index.html`)
+fn page_target(document: &str, href: &str, pages: &HashSet<String>) -> Option<String> {
+    let target = dead_assets::resolve_reference(document, href)?;
+    if pages.contains(&target) {
+        return Some(target);
+    }
+    let index = if target.is_empty() { "index.html".to_string() } else { format!("{}/index.html", target) };
+    pages.contains(&index).then_some(index)
+}
+
+/// `urn:uuid:` identifier derived from `seed`, so the same mirror exports as the same book
+fn book_identifier(seed: &str) -> String {
+    let hash = store::content_hash(seed.as_bytes());
+    let variant = ["8", "9", "a", "b"][usize::from_str_radix(&hash[16..17], 16).unwrap_or(0) & 3];
+    format!("urn:uuid:{}-{}-5{}-{}{}-{}", &hash[0..8], &hash[8..12], &hash[13..16], variant, &hash[17..20], &hash[20..32])
+}
+
+/// `dcterms:modified` value of a Unix timestamp: `2024-05-01T12:00:00Z`
+fn modified_timestamp(timestamp: u64) -> String {
+    let (year, month, day) = archive::civil_date(timestamp);
+    let seconds = timestamp % 86_400;
+    format!("{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z", year, month, day, seconds / 3_600, seconds % 3_600 / 60, seconds % 60)
+}
+
+/// Pages in reading order: the order of the mirrored `sitemap.xml` when there
+/// is one, else the order of the `<nav>` links from the home page on. Pages
+/// neither reaches follow in path order.
+fn reading_order(output_dir: &Path, pages: &[String], local_paths: &HashMap<String, String>) -> Vec<String> {
+    let page_set: HashSet<String> = pages.iter().cloned().collect();
+    let mut order = Vec::new();
+    let mut seen = HashSet::new();
+
+    if let Ok(sitemap) = fs::read_to_string(output_dir.join("sitemap.xml")) {
+        let loc_regex = Regex::new(SITEMAP_LOC_PATTERN).unwrap();
+        for caps in loc_regex.captures_iter(&sitemap) {
+            let url = caps[1].replace("&amp;", "&");
+            let Some(page) = local_paths.get(&url).or_else(|| local_paths.get(url.trim_end_matches('/'))) else { continue };
+            if page_set.contains(page) && seen.insert(page.clone()) {
+                order.push(page.clone());
+            }
+        }
+    }
+
+    if order.is_empty() {
+        let start = pages.iter().find(|page| page.as_str() == "index.html").or_else(|| pages.first());
+        let nav_regex = Regex::new(NAV_PATTERN).unwrap();
+        let anchor_regex = Regex::new(ANCHOR_HREF_PATTERN).unwrap();
+        let mut queue: VecDeque<String> = start.into_iter().cloned().collect();
+        seen.extend(queue.iter().cloned());
+        while let Some(page) = queue.pop_front() {
+            let html = fs::read(output_dir.join(&page)).map(|html| String::from_utf8_lossy(&html).into_owned()).unwrap_or_default();
+            for nav in nav_regex.captures_iter(&html) {
+                for caps in anchor_regex.captures_iter(&nav[1]) {
+                    let href = caps.get(1).or_else(|| caps.get(2)).map_or("", |m| m.as_str());
+                    let Some(target) = page_target(&page, href, &page_set) else { continue };
+                    if seen.insert(target.clone()) {
+                        queue.push_back(target);
+                    }
+                }
+            }
+            order.push(page);
+        }
+    }
+
+    order.extend(pages.iter().filter(|page| !seen.contains(*page)).cloned());
+    order
+}
+
+/// The stylesheets, images and fonts a chapter uses, following stylesheet
+/// imports and `url()`s
+fn collect_resources(output_dir: &Path, chapter: &str, xhtml: &str, files: &BTreeMap<String, u64>, resources: &mut BTreeSet<String>) {
+    let mut documents = VecDeque::from([(chapter.to_string(), DocumentKind::Html, xhtml.to_string())]);
+    while let Some((document, kind, content)) = documents.pop_front() {
+        for reference in dead_assets::extract_references(&content, kind) {
+            let Some(target) = dead_assets::resolve_reference(&document, &reference) else { continue };
+            let target_kind = dead_assets::document_kind(&target, None);
+            if !files.contains_key(&target) || target_kind == DocumentKind::Html || !resources.insert(target.clone()) {
+                continue;
+            }
+            if target_kind == DocumentKind::Css {
+                if let Ok(css) = fs::read(output_dir.join(&target)) {
+                    documents.push_back((target, DocumentKind::Css, String::from_utf8_lossy(&css).into_owned()));
+                }
+            }
+        }
+    }
+}
+
+/// Writes one saved page as an XHTML content document
+struct ChapterWriter<'a> {
+    page: &'a str,
+    chapter: String,
+    title: String,
+    pages: &'a HashSet<String>,
+    files: &'a BTreeMap<String, u64>,
+    xhtml: String,
+    has_title: bool,
+    /// Manifest properties the chapter needs (`svg`, `mathml`)
+    properties: BTreeSet<&'static str>,
+}
+
+impl ChapterWriter<'_> {
+    /// A reference to a file packaged in the book (or inline data)
+    fn is_packaged(&self, reference: &str) -> bool {
+        let reference = reference.trim();
+        if reference.starts_with("data:") {
+            return true;
+        }
+        dead_assets::resolve_reference(self.page, reference)
+            .is_some_and(|target| self.files.contains_key(&target) && dead_assets::document_kind(&target, None) != DocumentKind::Html)
+    }
+
+    /// A link's `href` in the book: links to other chapters point at their
+    /// `.xhtml`, links to the web are kept, links to pages left out are dropped
+    fn link_href(&self, href: &str) -> Option<String> {
+        let href = href.trim();
+        if href.starts_with('#') || href.contains("://") || href.starts_with("mailto:") || href.starts_with("tel:") {
+            return Some(href.to_string());
+        }
+        if href.starts_with("//") {
+            return Some(format!("https:{}", href));
+        }
+        let fragment = href.find('#').map_or("", |index| &href[index..]);
+        let target = page_target(self.page, href, self.pages)?;
+        Some(format!("{}{}", relative_href(&self.chapter, &chapter_path(&target)), fragment))
+    }
+
+    fn write_children(&mut self, handle: &Handle, foreign: bool) {
+        for child in handle.children.borrow().iter() {
+            self.write_node(child, foreign);
+        }
+    }
+
+    /// `foreign`: inside an `<svg>` or `<math>` element
+    fn write_node(&mut self, handle: &Handle, foreign: bool) {
+        match &handle.data {
+            NodeData::Document => self.write_children(handle, foreign),
+            NodeData::Text { contents } => {
+                let text = escape_xml(&contents.borrow());
+                self.xhtml.push_str(&text);
+            }
+            NodeData::Element { name, attrs, .. } => {
+                let tag = name.local.to_string();
+                let namespace: &str = &name.ns;
+                let is_html = namespace == XHTML_NS;
+                if is_html && DROPPED_ELEMENTS.contains(&tag.as_str()) {
+                    return;
+                }
+                let attrs = attrs.borrow();
+                let attr = |attr_name: &str| attrs.iter().find(|attr| &*attr.name.local == attr_name).map(|attr| attr.value.to_string());
+                if is_html && tag == "link" {
+                    let is_stylesheet = attr("rel").is_some_and(|rel| rel.split_whitespace().any(|rel| rel.eq_ignore_ascii_case("stylesheet")));
+                    if !is_stylesheet || !attr("href").is_some_and(|href| self.is_packaged(&href)) {
+                        return;
+                    }
+                }
+                if !is_xml_name(&tag) {
+                    self.write_children(handle, foreign);
+                    return;
+                }
+
+                self.xhtml.push('<');
+                self.xhtml.push_str(&tag);
+                if is_html && tag == "html" {
+                    self.xhtml.push_str(&format!(" xmlns=\"{}\" xmlns:epub=\"http://www.idpf.org/2007/ops\"", XHTML_NS));
+                    if let Some(lang) = attr("lang") {
+                        self.xhtml.push_str(&format!(" xml:lang=\"{}\"", escape_xml(&lang)));
+                    }
+                } else if namespace == SVG_NS && !foreign {
+                    self.xhtml.push_str(&format!(" xmlns=\"{}\" xmlns:xlink=\"http://www.w3.org/1999/xlink\"", SVG_NS));
+                    self.properties.insert("svg");
+                } else if namespace == MATHML_NS && !foreign {
+                    self.xhtml.push_str(&format!(" xmlns=\"{}\"", MATHML_NS));
+                    self.properties.insert("mathml");
+                }
+                for attribute in attrs.iter() {
+                    let local = attribute.name.local.to_string();
+                    let prefix = attribute.name.prefix.as_ref().map(|prefix| prefix.to_string());
+                    if !is_xml_name(&local) || local.to_ascii_lowercase().starts_with("on") || local == "xmlns" || prefix.as_deref() == Some("xmlns") {
+                        continue;
+                    }
+                    let value = match local.as_str() {
+                        "href" if is_html && (tag == "a" || tag == "area") => match self.link_href(&attribute.value) {
+                            Some(href) => href,
+                            None => continue,
+                        },
+                        "src" | "poster" if is_html && !self.is_packaged(&attribute.value) => continue,
+                        "srcset" if is_html => {
+                            let mut candidates = attribute.value.split(',').filter_map(|candidate| candidate.split_whitespace().next());
+                            if !candidates.all(|candidate| self.is_packaged(candidate)) {
+                                continue;
+                            }
+                            attribute.value.to_string()
+                        }
+                        _ => attribute.value.to_string(),
+                    };
+                    let attribute_name = match prefix {
+                        Some(prefix) => format!("{}:{}", prefix, local),
+                        None => local,
+                    };
+                    self.xhtml.push_str(&format!(" {}=\"{}\"", attribute_name, escape_xml(&value)));
+                }
+                drop(attrs);
+
+                if is_html && VOID_ELEMENTS.contains(&tag.as_str()) {
+                    self.xhtml.push_str("/>");
+                    return;
+                }
+                self.xhtml.push('>');
+                if is_html && tag == "title" {
+                    self.has_title = true;
+                }
+                self.write_children(handle, foreign || !is_html);
+                if is_html && tag == "head" && !self.has_title {
+                    self.xhtml.push_str(&format!("<title>{}</title>", escape_xml(&self.title)));
+                    self.has_title = true;
+                }
+                self.xhtml.push_str(&format!("</{}>", tag));
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Build the files of an EPUB 3 book of the mirror in `output_dir`: every saved
+/// page as a chapter (in sitemap or navigation order) with the stylesheets,
+/// images and fonts it uses, a navigation document and an NCX for EPUB 2 readers
+fn book_files(output_dir: &Path, title: Option<&str>) -> Result<(Vec<ArchiveFile>, EpubStats)> {
+    let mut files = BTreeMap::new();
+    snapshot_diff::collect_files(output_dir, output_dir, &mut files)?;
+    let store = DownloadStore::load(output_dir)?;
+    let mut local_paths = HashMap::new();
+    let mut content_types = HashMap::new();
+    for (url, entry) in &store.entries {
+        let local_path = dead_assets::normalize_path(Path::new(&entry.local_path));
+        if let Some(content_type) = &entry.content_type {
+            content_types.insert(local_path.clone(), content_type.clone());
+        }
+        local_paths.insert(url.clone(), local_path);
+    }
+
+    let pages: Vec<String> = files.keys().filter(|path| snapshot_diff::is_page(path)).cloned().collect();
+    if pages.is_empty() {
+        bail!("No saved pages in {}", output_dir.display());
+    }
+    let order = reading_order(output_dir, &pages, &local_paths);
+    let page_set: HashSet<String> = pages.iter().cloned().collect();
+
+    let mut chapters = Vec::new();
+    let mut resources = BTreeSet::new();
+    let mut language = None;
+    let html_lang = Regex::new(HTML_LANG_PATTERN).unwrap();
+    for page in &order {
+        let html = fs::read(output_dir.join(page)).with_context(|| format!("Failed to read page: {:?}", page))?;
+        let html = String::from_utf8_lossy(&html);
+        if language.is_none() {
+            language = html_lang.captures(&html).map(|caps| caps[1].to_string());
+        }
+        let page_title = search::page_title(&html);
+        let mut writer = ChapterWriter {
+            page,
+            chapter: chapter_path(page),
+            title: if page_title.is_empty() { page.clone() } else { page_title },
+            pages: &page_set,
+            files: &files,
+            xhtml: String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<!DOCTYPE html>\n"),
+            has_title: false,
+            properties: BTreeSet::new(),
+        };
+        let dom = parse_document(RcDom::default(), ParseOpts::default()).one(&*html);
+        writer.write_node(&dom.document, false);
+        collect_resources(output_dir, &writer.chapter, &writer.xhtml, &files, &mut resources);
+        chapters.push(writer);
+    }
+
+    let title = title.map(str::to_string).unwrap_or_else(|| chapters[0].title.clone());
+    let language = language.unwrap_or_else(|| "en".to_string());
+    let identifier = book_identifier(&format!("{}\n{}", title, order.join("\n")));
+    let modified = order
+        .iter()
+        .chain(resources.iter())
+        .filter_map(|path| fs::metadata(output_dir.join(path)).ok()?.modified().ok())
+        .filter_map(|modified| modified.duration_since(UNIX_EPOCH).ok())
+        .map(|age| age.as_secs())
+        .max()
+        .unwrap_or(0);
+    let content_type = |path: &str| {
+        let content_type = content_types
+            .get(path)
+            .cloned()
+            .unwrap_or_else(|| MimeGuess::from_path(path).first_or_octet_stream().to_string());
+        content_type.split(';').next().unwrap_or_default().trim().to_string()
+    };
+
+    let mut manifest = format!(
+        "    <item id=\"toc\" href=\"{}\" media-type=\"application/xhtml+xml\" properties=\"nav\"/>\n    <item id=\"ncx\" href=\"{}\" media-type=\"application/x-dtbncx+xml\"/>\n",
+        NAV_FILE, NCX_FILE
+    );
+    let mut spine = String::new();
+    let mut toc = String::new();
+    let mut nav_points = String::new();
+    for (index, chapter) in chapters.iter().enumerate() {
+        let properties = if chapter.properties.is_empty() {
+            String::new()
+        } else {
+            format!(" properties=\"{}\"", chapter.properties.iter().copied().collect::<Vec<_>>().join(" "))
+        };
+        let href = escape_href(&chapter.chapter);
+        manifest.push_str(&format!("    <item id=\"chapter-{}\" href=\"{}\" media-type=\"application/xhtml+xml\"{}/>\n", index + 1, href, properties));
+        spine.push_str(&format!("    <itemref idref=\"chapter-{}\"/>\n", index + 1));
+        toc.push_str(&format!("      <li><a href=\"{}\">{}</a></li>\n", href, escape_xml(&chapter.title)));
+        nav_points.push_str(&format!(
+            "    <navPoint id=\"nav-{0}\" playOrder=\"{0}\"><navLabel><text>{1}</text></navLabel><content src=\"{2}\"/></navPoint>\n",
+            index + 1,
+            escape_xml(&chapter.title),
+            href
+        ));
+    }
+    for (index, resource) in resources.iter().enumerate() {
+        manifest.push_str(&format!("    <item id=\"resource-{}\" href=\"{}\" media-type=\"{}\"/>\n", index + 1, escape_href(resource), content_type(resource)));
+    }
+
+    let package = format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<package xmlns=\"http://www.idpf.org/2007/opf\" version=\"3.0\" unique-identifier=\"book-id\" xml:lang=\"{lang}\">\n  <metadata xmlns:dc=\"http://purl.org/dc/elements/1.1/\">\n    <dc:identifier id=\"book-id\">{id}</dc:identifier>\n    <dc:title>{title}</dc:title>\n    <dc:language>{lang}</dc:language>\n    <meta property=\"dcterms:modified\">{modified}</meta>\n  </metadata>\n  <manifest>\n{manifest}  </manifest>\n  <spine toc=\"ncx\">\n{spine}  </spine>\n</package>\n",
+        lang = escape_xml(&language),
+        id = identifier,
+        title = escape_xml(&title),
+        modified = modified_timestamp(modified),
+        manifest = manifest,
+        spine = spine
+    );
+    let nav = format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<!DOCTYPE html>\n<html xmlns=\"{ns}\" xmlns:epub=\"http://www.idpf.org/2007/ops\" lang=\"{lang}\" xml:lang=\"{lang}\">\n<head><title>{title}</title></head>\n<body>\n  <nav epub:type=\"toc\" id=\"toc\">\n    <h1>{title}</h1>\n    <ol>\n{toc}    </ol>\n  </nav>\n</body>\n</html>\n",
+        ns = XHTML_NS,
+        lang = escape_xml(&language),
+        title = escape_xml(&title),
+        toc = toc
+    );
+    let ncx = format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<ncx xmlns=\"http://www.daisy.org/z3986/2005/ncx/\" version=\"2005-1\">\n  <head><meta name=\"dtb:uid\" content=\"{id}\"/></head>\n  <docTitle><text>{title}</text></docTitle>\n  <navMap>\n{nav_points}  </navMap>\n</ncx>\n",
+        id = identifier,
+        title = escape_xml(&title),
+        nav_points = nav_points
+    );
+    let container = format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<container version=\"1.0\" xmlns=\"urn:oasis:names:tc:opendocument:xmlns:container\">\n  <rootfiles>\n    <rootfile full-path=\"{}/content.opf\" media-type=\"application/oebps-package+xml\"/>\n  </rootfiles>\n</container>\n",
+        CONTENT_DIR
+    );
+
+    // The mimetype must come first and uncompressed, so readers can identify the file
+    let file = |path: String, content: Vec<u8>| ArchiveFile { path, content, mtime: modified, stored: false };
+    let mut book = vec![
+        ArchiveFile { path: "mimetype".to_string(), content: b"application/epub+zip".to_vec(), mtime: modified, stored: true },
+        file("META-INF/container.xml".to_string(), container.into_bytes()),
+        file(format!("{}/content.opf", CONTENT_DIR), package.into_bytes()),
+        file(format!("{}/{}", CONTENT_DIR, NAV_FILE), nav.into_bytes()),
+        file(format!("{}/{}", CONTENT_DIR, NCX_FILE), ncx.into_bytes()),
+    ];
+    let stats = EpubStats { chapters: chapters.len(), resources: resources.len(), total_bytes: 0 };
+    for chapter in chapters {
+        book.push(file(format!("{}/{}", CONTENT_DIR, chapter.chapter), chapter.xhtml.into_bytes()));
+    }
+    for resource in &resources {
+        let content = fs::read(output_dir.join(resource)).with_context(|| format!("Failed to read resource: {:?}", resource))?;
+        book.push(file(format!("{}/{}", CONTENT_DIR, resource), content));
+    }
+    Ok((book, stats))
+}
+
+/// Write the mirror in `output_dir` as an EPUB 3 book at `epub_path`, e.g. to
+/// read mirrored documentation on an e-reader. `title` defaults to the title
+/// of the first page.
+pub fn export_epub(output_dir: &Path, epub_path: &Path, title: Option<&str>) -> Result<EpubStats> {
+    let (book, mut stats) = book_files(output_dir, title)?;
+    let file = File::create(epub_path).with_context(|| format!("Failed to create EPUB file: {:?}", epub_path))?;
+    archive::write_zip(BufWriter::new(file), &book)?;
+    stats.total_bytes = fs::metadata(epub_path)?.len();
+    Ok(stats)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::write;
+    use tempfile::tempdir;
+
+    fn book_file<'a>(book: &'a [ArchiveFile], path: &str) -> &'a str {
+        let file = book.iter().find(|file| file.path == path).unwrap();
+        std::str::from_utf8(&file.content).unwrap()
+    }
+
+    #[test]
+    fn test_epub_book() {
+        let temp_dir = tempdir().unwrap();
+        let dir = temp_dir.path().join("site");
+        write(&dir, "index.html", concat!(
+            r#"<html lang="de"><head><title>Docs</title><meta charset="utf-8"><link rel="stylesheet" href="css/docs.css"><script src="js/app.js"></script></head>"#,
+            r#"<body><nav><a href="guide/">Guide</a><a href="api.html">API</a></nav><p onclick="x()">Intro<br>&amp; more</p></body></html>"#,
+        ));
+        write(&dir, "api.html", r#"<title>API</title><img src="img/a.png"><img src="img/missing.png" alt="gone"><a href="index.html#top">Home</a><a href="secret.pdf">PDF</a>"#);
+        write(&dir, "guide/index.html", r#"<title>Guide</title><svg viewBox="0 0 1 1"><path d="M0 0"/></svg><a href="https://example.org/">Elsewhere</a>"#);
+        write(&dir, "zzz.html", "<p>Orphan</p>");
+        write(&dir, "css/docs.css", "@font-face { src: url(../fonts/a.woff2); }");
+        write(&dir, "fonts/a.woff2", "font");
+        write(&dir, "img/a.png", "png");
+        write(&dir, "js/app.js", "x()");
+        write(&dir, "secret.pdf", "pdf");
+
+        let (book, stats) = book_files(&dir, None).unwrap();
+        assert_eq!(stats, EpubStats { chapters: 4, resources: 3, total_bytes: 0 });
+        assert_eq!(book[0].path, "mimetype");
+        assert!(book[0].stored);
+
+        let package = book_file(&book, "OEBPS/content.opf");
+        assert!(package.contains("<dc:title>Docs</dc:title>"));
+        assert!(package.contains("<dc:language>de</dc:language>"));
+        assert!(package.contains("<item id=\"chapter-2\" href=\"guide/index.xhtml\" media-type=\"application/xhtml+xml\" properties=\"svg\"/>"));
+        assert!(package.contains("href=\"fonts/a.woff2\""));
+        assert!(!package.contains("app.js") && !package.contains("secret.pdf"));
+        let toc = book_file(&book, "OEBPS/_toc.xhtml");
+        let positions: Vec<usize> = ["index.xhtml", "guide/index.xhtml", "api.xhtml", "zzz.xhtml"]
+            .iter().map(|href| toc.find(&format!("href=\"{}\"", href)).unwrap()).collect();
+        assert!(positions.windows(2).all(|pair| pair[0] < pair[1]));
+
+        let index = book_file(&book, "OEBPS/index.xhtml");
+        assert!(index.starts_with("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<!DOCTYPE html>\n<html xmlns=\"http://www.w3.org/1999/xhtml\""));
+        assert!(index.contains(r#"<link rel="stylesheet" href="css/docs.css"/>"#));
+        assert!(!index.contains("<script") && !index.contains("<meta") && !index.contains("onclick"));
+        assert!(index.contains(r#"<a href="guide/index.xhtml">Guide</a><a href="api.xhtml">API</a>"#));
+        assert!(index.contains("<p>Intro<br/>&amp; more</p>"));
+
+        let api = book_file(&book, "OEBPS/api.xhtml");
+        assert!(api.contains(r#"<img src="img/a.png"/><img alt="gone"/><a href="index.xhtml#top">Home</a><a>PDF</a>"#));
+        let guide = book_file(&book, "OEBPS/guide/index.xhtml");
+        assert!(guide.contains(r#"<svg xmlns="http://www.w3.org/2000/svg" xmlns:xlink="http://www.w3.org/1999/xlink" viewBox="0 0 1 1"><path d="M0 0"></path></svg>"#));
+        assert!(guide.contains(r#"<a href="https://example.org/">Elsewhere</a>"#));
+        assert!(book_file(&book, "OEBPS/zzz.xhtml").contains("<title>zzz.html</title>"));
+
+        let epub = temp_dir.path().join("docs.epub");
+        let stats = export_epub(&dir, &epub, Some("Manual")).unwrap();
+        let content = fs::read(&epub).unwrap();
+        assert_eq!(stats.total_bytes, content.len() as u64);
+        assert_eq!(&content[..4], b"PK\x03\x04");
+        assert_eq!(u16::from_le_bytes([content[8], content[9]]), 0);
+        assert_eq!(&content[30..38], b"mimetype");
+        assert_eq!(&content[38..58], b"application/epub+zip");
+    }
+}
@@ -0,0 +1,154 @@
+use anyhow::{Result, Context};
+use serde::Serialize;
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tracing::warn;
+
+use crate::archive;
+
+/// A step of the crawl, written as one line of `--log-file` with `--log-format jsonl`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event<'a> {
+    /// A page is about to be fetched
+    PageStart { url: &'a str, depth: usize },
+    /// A file other than a page was saved
+    ResourceDownloaded {
+        url: &'a str,
+        local_path: &'a str,
+        content_type: Option<&'a str>,
+        bytes: u64,
+    },
+    /// A page or resource couldn't be fetched or saved
+    ResourceFailed {
+        url: &'a str,
+        status: Option<u16>,
+        error: &'a str,
+    },
+    /// A page's references were rewritten to local paths and the page saved;
+    /// `unchanged` when it matched what an earlier run saved and wasn't rewritten
+    RewriteDone {
+        url: &'a str,
+        local_path: &'a str,
+        bytes: u64,
+        unchanged: bool,
+    },
+    /// The crawl finished
+    CrawlDone {
+        pages: u64,
+        files: u64,
+        failures: u64,
+        bytes: u64,
+    },
+}
+
+#[derive(Serialize)]
+struct EventLine<'a> {
+    ts: String,
+    elapsed_ms: u64,
+    #[serde(flatten)]
+    event: &'a Event<'a>,
+}
+
+/// `YYYY-MM-DDThh:mm:ss.mmmZ` of a time since the Unix epoch
+fn timestamp(since_epoch: Duration) -> String {
+    let seconds = since_epoch.as_secs();
+    let (year, month, day) = archive::civil_date(seconds);
+    let time = seconds % 86_400;
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+        year,
+        month,
+        day,
+        time / 3_600,
+        (time % 3_600) / 60,
+        time % 60,
+        since_epoch.subsec_millis()
+    )
+}
+
+/// Structured crawl events, one JSON object per line, for CI pipelines and dashboards
+#[derive(Debug)]
+pub struct EventLog {
+    path: PathBuf,
+    file: Mutex<File>,
+    started: Instant,
+}
+
+impl EventLog {
+    /// Start a new log at `path`, replacing any earlier one
+    pub fn create(path: &Path) -> Result<Self> {
+        if let Some(parent) = path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+            fs::create_dir_all(parent).with_context(|| format!("Failed to create log directory: {:?}", parent))?;
+        }
+        let file = File::create(path).with_context(|| format!("Failed to create event log: {:?}", path))?;
+        Ok(Self {
+            path: path.to_path_buf(),
+            file: Mutex::new(file),
+            started: Instant::now(),
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Append an event. Each line is written in one call so readers tailing the
+    /// file never see half an event; a failed write is reported but doesn't stop the crawl.
+    pub fn emit(&self, event: &Event) {
+        let since_epoch = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+        let line = EventLine {
+            ts: timestamp(since_epoch),
+            elapsed_ms: self.started.elapsed().as_millis() as u64,
+            event,
+        };
+        let Ok(mut json) = serde_json::to_string(&line) else { return };
+        json.push('\n');
+        if let Err(e) = self.file.lock().unwrap().write_all(json.as_bytes()) {
+            warn!("⚠️  Failed to write event log {}: {}", self.path.display(), e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_timestamp() {
+        assert_eq!(timestamp(Duration::from_millis(0)), "1970-01-01T00:00:00.000Z");
+        assert_eq!(timestamp(Duration::from_millis(1_792_152_245_042)), "2026-10-16T12:04:05.042Z");
+    }
+
+    #[test]
+    fn test_emit_writes_json_lines() {
+        let temp_dir = tempdir().unwrap();
+        let path = temp_dir.path().join("logs/events.jsonl");
+        let log = EventLog::create(&path).unwrap();
+        log.emit(&Event::PageStart { url: "https://example.com/", depth: 0 });
+        log.emit(&Event::ResourceDownloaded {
+            url: "https://example.com/logo.png",
+            local_path: "logo.png",
+            content_type: Some("image/png"),
+            bytes: 1234,
+        });
+        log.emit(&Event::ResourceFailed { url: "https://example.com/gone", status: Some(404), error: "HTTP 404 Not Found" });
+
+        let content = fs::read_to_string(&path).unwrap();
+        let events: Vec<serde_json::Value> = content.lines().map(|line| serde_json::from_str(line).unwrap()).collect();
+        assert_eq!(events.len(), 3);
+        assert_eq!(events[0]["event"], "page_start");
+        assert_eq!(events[0]["depth"], 0);
+        assert!(events[0]["ts"].as_str().unwrap().ends_with('Z'));
+        assert!(events[0]["elapsed_ms"].is_u64());
+        assert_eq!(events[1]["event"], "resource_downloaded");
+        assert_eq!(events[1]["bytes"], 1234);
+        assert_eq!(events[1]["content_type"], "image/png");
+        assert_eq!(events[2]["event"], "resource_failed");
+        assert_eq!(events[2]["status"], 404);
+    }
+}
@@ -0,0 +1,212 @@
+use std::fmt;
+use url::Url;
+
+use crate::downloader::WebsiteMirror;
+use crate::html_parser::ResourceType;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RuleOutcome {
+    Allow,
+    Block,
+    /// The rule can only be decided during the crawl
+    Info,
+}
+
+/// How one mirroring rule treats a URL
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RuleDecision {
+    pub rule: &'static str,
+    pub outcome: RuleOutcome,
+    pub detail: String,
+}
+
+impl RuleDecision {
+    fn new(rule: &'static str, outcome: RuleOutcome, detail: impl Into<String>) -> Self {
+        Self {
+            rule,
+            outcome,
+            detail: detail.into(),
+        }
+    }
+}
+
+impl fmt::Display for RuleDecision {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let label = match self.outcome {
+            RuleOutcome::Allow => "✅ ALLOW",
+            RuleOutcome::Block => "❌ BLOCK",
+            RuleOutcome::Info => "ℹ️  INFO ",
+        };
+        write!(f, "{}  {:<16} {}", label, self.rule, self.detail)
+    }
+}
+
+/// Resource type the crawler would assign to a URL found in a link
+fn guess_resource_type(url: &Url) -> ResourceType {
+    match ResourceType::from_extension(url.path()) {
+        ResourceType::Other => {
+            let file_name = url.path().rsplit('/').next().unwrap_or("");
+            if !file_name.contains('.') || file_name.ends_with(".html") || file_name.ends_with(".htm") {
+                ResourceType::Link
+            } else {
+                ResourceType::Other
+            }
+        }
+        resource_type => resource_type,
+    }
+}
+
+/// Evaluate a URL against every active mirroring rule
+pub fn explain_url(mirror: &WebsiteMirror, url: &str) -> Vec<RuleDecision> {
+    let mut decisions = Vec::new();
+
+    let parsed = match Url::parse(url) {
+        Ok(parsed) if parsed.scheme() == "http" || parsed.scheme() == "https" => parsed,
+        Ok(parsed) => {
+            decisions.push(RuleDecision::new("scheme", RuleOutcome::Block, format!("{}: URLs are not fetched", parsed.scheme())));
+            return decisions;
+        }
+        Err(e) => {
+            decisions.push(RuleDecision::new("scheme", RuleOutcome::Block, format!("not a valid absolute URL ({})", e)));
+            return decisions;
+        }
+    };
+    decisions.push(RuleDecision::new("scheme", RuleOutcome::Allow, format!("{} URL", parsed.scheme())));
+
+    let resource_type = guess_resource_type(&parsed);
+    decisions.push(RuleDecision::new("resource-type", RuleOutcome::Info, format!("treated as {:?} (from the URL; links and content type can override)", resource_type)));
+
+    let same_site = url.contains(&mirror.base_url);
+    match resource_type {
+        ResourceType::Link | ResourceType::Other if !same_site => {
+            decisions.push(RuleDecision::new("domain", RuleOutcome::Block, format!("only pages and other files under {} are fetched", mirror.base_url)));
+        }
+        ResourceType::Link | ResourceType::Other => {
+            decisions.push(RuleDecision::new("domain", RuleOutcome::Allow, format!("under {}", mirror.base_url)));
+        }
+        _ => {
+            decisions.push(RuleDecision::new("domain", RuleOutcome::Allow, "page assets are fetched from any host"));
+        }
+    }
+
+    let type_name = resource_type.filter_name();
+    match &mirror.only_resources {
+        None => decisions.push(RuleDecision::new("only-resources", RuleOutcome::Allow, "no resource filter")),
+        Some(_) if mirror.should_process_resource_type(&resource_type) => {
+            decisions.push(RuleDecision::new("only-resources", RuleOutcome::Allow, format!("{} is in --only-resources", type_name)));
+        }
+        Some(_) if resource_type == ResourceType::Link => {
+            decisions.push(RuleDecision::new("only-resources", RuleOutcome::Block, "html is not in --only-resources: the page is crawled for discovery but not saved"));
+        }
+        Some(filter) => {
+            decisions.push(RuleDecision::new("only-resources", RuleOutcome::Block, format!("{} is not in --only-resources {}", type_name, filter.join(","))));
+        }
+    }
+
+    if let Some(preset) = mirror.preset {
+        if preset.excludes_url(url) {
+            decisions.push(RuleDecision::new("preset", RuleOutcome::Block, format!("{} preset skips this dynamic endpoint", preset.name())));
+        } else {
+            decisions.push(RuleDecision::new("preset", RuleOutcome::Allow, format!("not excluded by the {} preset", preset.name())));
+        }
+    }
+
+    if resource_type == ResourceType::Link {
+        let depth = if mirror.max_depth == 0 { "unlimited".to_string() } else { mirror.max_depth.to_string() };
+        decisions.push(RuleDecision::new("depth", RuleOutcome::Info, format!("crawled only if linked within {} hops of the start URL", depth)));
+
+        if mirror.respect_noarchive {
+            decisions.push(RuleDecision::new("noarchive", RuleOutcome::Info, "skipped if the page's <meta name=\"robots\"> contains noarchive or nosnippet"));
+        }
+    }
+
+    decisions.push(RuleDecision::new("robots.txt", RuleOutcome::Info, "robots.txt is not consulted by the crawler"));
+
+    if let Some(reason) = mirror.store_skip_reason(url) {
+        decisions.push(RuleDecision::new("previous-run", RuleOutcome::Info, format!("skipped by the last run: {}", reason)));
+    }
+
+    decisions
+}
+
+/// Human-readable explanation ending with the overall verdict
+pub fn format_explanation(url: &str, decisions: &[RuleDecision]) -> String {
+    let mut output = format!("🔎 Explaining {}\n", url);
+    for decision in decisions {
+        output.push_str(&format!("  {}\n", decision));
+    }
+
+    let blocking: Vec<&str> = decisions
+        .iter()
+        .filter(|decision| decision.outcome == RuleOutcome::Block)
+        .map(|decision| decision.rule)
+        .collect();
+    if blocking.is_empty() {
+        output.push_str("Verdict: ALLOWED\n");
+    } else {
+        output.push_str(&format!("Verdict: BLOCKED by {}\n", blocking.join(", ")));
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn mirror(only_resources: Option<Vec<String>>) -> (tempfile::TempDir, WebsiteMirror) {
+        let temp_dir = tempdir().unwrap();
+        let mirror = WebsiteMirror::new("https://example.com", temp_dir.path(), 3, 10, false, false, only_resources, false).unwrap();
+        (temp_dir, mirror)
+    }
+
+    fn outcome(decisions: &[RuleDecision], rule: &str) -> Option<RuleOutcome> {
+        decisions.iter().find(|d| d.rule == rule).map(|d| d.outcome)
+    }
+
+    #[test]
+    fn test_explain_external_page_is_blocked_by_domain() {
+        let (_temp_dir, mirror) = mirror(None);
+        let decisions = explain_url(&mirror, "https://other.com/about");
+
+        assert_eq!(outcome(&decisions, "domain"), Some(RuleOutcome::Block));
+        assert!(format_explanation("https://other.com/about", &decisions).contains("Verdict: BLOCKED by domain"));
+    }
+
+    #[test]
+    fn test_explain_external_image_is_allowed() {
+        let (_temp_dir, mirror) = mirror(None);
+        let decisions = explain_url(&mirror, "https://cdn.other.com/logo.png");
+
+        assert_eq!(outcome(&decisions, "domain"), Some(RuleOutcome::Allow));
+        assert!(format_explanation("https://cdn.other.com/logo.png", &decisions).contains("Verdict: ALLOWED"));
+    }
+
+    #[test]
+    fn test_explain_resource_filter() {
+        let (_temp_dir, mirror) = mirror(Some(vec!["css".to_string()]));
+        let decisions = explain_url(&mirror, "https://example.com/img/logo.png");
+
+        assert_eq!(outcome(&decisions, "only-resources"), Some(RuleOutcome::Block));
+    }
+
+    #[test]
+    fn test_explain_wordpress_preset() {
+        let (_temp_dir, mut mirror) = mirror(None);
+        mirror.apply_preset(crate::preset::Preset::WordPress);
+
+        let decisions = explain_url(&mirror, "https://example.com/wp-json/wp/v2/posts");
+        assert_eq!(outcome(&decisions, "preset"), Some(RuleOutcome::Block));
+        let decisions = explain_url(&mirror, "https://example.com/hello-world/");
+        assert_eq!(outcome(&decisions, "preset"), Some(RuleOutcome::Allow));
+    }
+
+    #[test]
+    fn test_explain_invalid_url() {
+        let (_temp_dir, mirror) = mirror(None);
+        let decisions = explain_url(&mirror, "mailto:someone@example.com");
+
+        assert_eq!(decisions.len(), 1);
+        assert_eq!(outcome(&decisions, "scheme"), Some(RuleOutcome::Block));
+    }
+}
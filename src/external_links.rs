@@ -0,0 +1,139 @@
+use anyhow::Result;
+use futures::stream::{self, StreamExt};
+use reqwest::redirect::Policy;
+use reqwest::{Client, ClientBuilder, StatusCode};
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
+use std::time::Duration;
+use url::Url;
+
+use crate::parallel;
+
+/// How long each external link gets to answer
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+/// An outbound link that didn't answer or answered with an error
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DeadExternalLink {
+    pub url: String,
+    /// HTTP status, when the server answered
+    pub status: Option<u16>,
+    pub error: String,
+    /// Pages linking to it
+    pub pages: Vec<String>,
+}
+
+/// Links to pages that aren't mirrored (other sites, excluded domains), with
+/// the pages linking to each, collected during a crawl
+#[derive(Debug, Clone, Default)]
+pub struct ExternalLinks {
+    links: BTreeMap<String, BTreeSet<String>>,
+}
+
+impl ExternalLinks {
+    /// Remember a link from `page`; only http(s) links are checked, once per
+    /// URL whatever the fragment
+    pub fn record(&mut self, url: &str, page: &str) {
+        let Ok(mut parsed) = Url::parse(url) else { return };
+        if parsed.scheme() != "http" && parsed.scheme() != "https" {
+            return;
+        }
+        parsed.set_fragment(None);
+        self.links.entry(parsed.to_string()).or_default().insert(page.to_string());
+    }
+
+    pub fn len(&self) -> usize {
+        self.links.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.links.is_empty()
+    }
+}
+
+/// Statuses servers answer HEAD requests with when they don't support them
+/// (or reject them outright); such links are retried with a GET
+fn head_unsupported(status: StatusCode) -> bool {
+    matches!(status, StatusCode::FORBIDDEN | StatusCode::METHOD_NOT_ALLOWED | StatusCode::NOT_IMPLEMENTED)
+}
+
+fn is_dead(status: StatusCode) -> bool {
+    status.is_client_error() || status.is_server_error()
+}
+
+/// Status and error of a dead link, `None` if it is alive. Only headers are
+/// read: a GET fallback is dropped before its body is downloaded.
+async fn check_link(client: &Client, url: &str) -> Option<(Option<u16>, String)> {
+    let mut status = match client.head(url).send().await {
+        Ok(response) => response.status(),
+        Err(e) => return Some((None, e.to_string())),
+    };
+    if head_unsupported(status) {
+        status = match client.get(url).send().await {
+            Ok(response) => response.status(),
+            Err(e) => return Some((None, e.to_string())),
+        };
+    }
+    is_dead(status).then(|| (Some(status.as_u16()), format!("HTTP {}", status)))
+}
+
+/// Send a HEAD request to every external link, `concurrency` at a time, and
+/// return the dead ones in URL order
+pub async fn check_links(links: &ExternalLinks, concurrency: usize) -> Result<Vec<DeadExternalLink>> {
+    let client = ClientBuilder::new()
+        .use_rustls_tls()
+        .user_agent("WebsiteMirror/1.0")
+        .redirect(Policy::limited(10))
+        .timeout(TIMEOUT)
+        .build()?;
+    let progress_bar = parallel::progress_bar(links.len(), "Checking external links");
+    let results: Vec<_> = stream::iter(&links.links)
+        .map(|(url, pages)| {
+            let client = &client;
+            let progress_bar = &progress_bar;
+            async move {
+                let dead = check_link(client, url).await;
+                progress_bar.inc(1);
+                dead.map(|(status, error)| DeadExternalLink {
+                    url: url.clone(),
+                    status,
+                    error,
+                    pages: pages.iter().cloned().collect(),
+                })
+            }
+        })
+        .buffered(concurrency.max(1))
+        .collect()
+        .await;
+    progress_bar.finish_and_clear();
+    Ok(results.into_iter().flatten().collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record_external_links() {
+        let mut links = ExternalLinks::default();
+        links.record("https://example.org/docs#intro", "https://example.com/");
+        links.record("https://example.org/docs", "https://example.com/about/");
+        links.record("https://example.org/docs#usage", "https://example.com/");
+        links.record("mailto:team@example.org", "https://example.com/");
+        links.record("ftp://files.example.org/a.zip", "https://example.com/");
+
+        assert_eq!(links.len(), 1);
+        let pages: Vec<&str> = links.links["https://example.org/docs"].iter().map(String::as_str).collect();
+        assert_eq!(pages, vec!["https://example.com/", "https://example.com/about/"]);
+    }
+
+    #[test]
+    fn test_link_status() {
+        assert!(head_unsupported(StatusCode::METHOD_NOT_ALLOWED));
+        assert!(!head_unsupported(StatusCode::NOT_FOUND));
+        assert!(is_dead(StatusCode::NOT_FOUND));
+        assert!(is_dead(StatusCode::BAD_GATEWAY));
+        assert!(!is_dead(StatusCode::OK));
+        assert!(!is_dead(StatusCode::MOVED_PERMANENTLY));
+    }
+}
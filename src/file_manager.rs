@@ -2,11 +2,29 @@ use anyhow::{Result, Context};
 use std::path::{Path, PathBuf};
 use std::fs;
 use mime_guess::MimeGuess;
+use sha2::{Digest, Sha256};
 use std::io::Write;
+use std::sync::Arc;
+
+use crate::io_throttle::{IoThrottle, ThrottledFile};
+
+/// Device names Windows reserves in every directory, with or without an extension
+const WINDOWS_RESERVED_NAMES: &[&str] = &[
+    "CON", "PRN", "AUX", "NUL",
+    "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8", "COM9",
+    "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+/// Longest file or directory name most filesystems accept, in bytes
+pub const MAX_COMPONENT_BYTES: usize = 255;
+
+/// Longest extension kept when an overlong name is shortened
+const MAX_EXTENSION_BYTES: usize = 16;
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct FileManager {
     base_dir: PathBuf,
+    throttle: Option<Arc<IoThrottle>>,
 }
 
 impl FileManager {
@@ -15,7 +33,79 @@ impl FileManager {
         fs::create_dir_all(&base_dir)
             .with_context(|| format!("Failed to create base directory: {:?}", base_dir))?;
         
-        Ok(Self { base_dir })
+        Ok(Self { base_dir, throttle: None })
+    }
+    
+    /// Route every write through `throttle` (`--io-throttle`)
+    pub fn with_throttle(mut self, throttle: Arc<IoThrottle>) -> Self {
+        self.throttle = Some(throttle);
+        self
+    }
+    
+    pub fn throttle(&self) -> Option<&IoThrottle> {
+        self.throttle.as_deref()
+    }
+    
+    /// Short, filesystem-safe hash identifying a string
+    pub fn short_hash(value: &str) -> String {
+        let digest = Sha256::digest(value.as_bytes());
+        format!("{:x}", digest)[..8].to_string()
+    }
+    
+    /// Make every component of a relative path valid on Windows, macOS and Linux:
+    /// reserved device names (`CON`, `nul.html`) get a `_` suffix, trailing dots and
+    /// spaces become `_`, and names over 255 bytes are shortened with a hash suffix
+    pub fn safe_path(path: &str) -> String {
+        path.split('/').map(Self::safe_component).collect::<Vec<_>>().join("/")
+    }
+    
+    fn safe_component(component: &str) -> String {
+        let trimmed = component.trim_end_matches(['.', ' ']);
+        let mut name = format!("{}{}", trimmed, "_".repeat(component.len() - trimmed.len()));
+        
+        let stem_len = name.find('.').unwrap_or(name.len());
+        if WINDOWS_RESERVED_NAMES.iter().any(|reserved| reserved.eq_ignore_ascii_case(&name[..stem_len])) {
+            name.insert(stem_len, '_');
+        }
+        
+        if name.len() > MAX_COMPONENT_BYTES {
+            name = Self::shorten_component(&name);
+        }
+        name
+    }
+    
+    /// `<first ~240 bytes>.<hash>.<ext>`, keeping the extension so the file type survives
+    fn shorten_component(name: &str) -> String {
+        let hash = Self::short_hash(name);
+        let extension = match name.rfind('.') {
+            Some(dot) if dot > 0 && name.len() - dot <= MAX_EXTENSION_BYTES => &name[dot..],
+            _ => "",
+        };
+        let mut end = MAX_COMPONENT_BYTES - extension.len() - hash.len() - 1;
+        while !name.is_char_boundary(end) {
+            end -= 1;
+        }
+        format!("{}.{}{}", &name[..end], hash, extension)
+    }
+    
+    /// Keep names that differ only in case apart on case-insensitive filesystems
+    /// (Windows, macOS): components with uppercase letters get a hash of their
+    /// spelling before the extension (`About` -> `About.<hash>`)
+    pub fn case_insensitive_path(path: &str) -> String {
+        path.split('/')
+            .map(|component| {
+                if !component.bytes().any(|b| b.is_ascii_uppercase()) {
+                    return component.to_string();
+                }
+                let hash = Self::short_hash(component);
+                let suffixed = match component.rfind('.') {
+                    Some(dot) if dot > 0 => format!("{}.{}{}", &component[..dot], hash, &component[dot..]),
+                    _ => format!("{}.{}", component, hash),
+                };
+                Self::safe_component(&suffixed)
+            })
+            .collect::<Vec<_>>()
+            .join("/")
     }
     
     pub fn create_directories_for_url(&self, url_path: &str) -> Result<PathBuf> {
@@ -35,14 +125,29 @@ impl FileManager {
         Ok(path)
     }
     
-    pub fn save_file(&self, url_path: &str, content: &[u8], mime_type: Option<&str>) -> Result<PathBuf> {
-        let mut file_path = self.create_directories_for_url(url_path)?;
+    /// Where `save_file` would write this content, without creating anything
+    pub fn file_path(&self, url_path: &str, content: &[u8], mime_type: Option<&str>) -> PathBuf {
+        let mut file_path = self.base_dir.clone();
+        for segment in url_path.split('/').filter(|s| !s.is_empty()) {
+            file_path.push(segment);
+        }
         
         // Determine file extension based on MIME type or content
         let extension = self.get_file_extension(url_path, mime_type, content);
         if !extension.is_empty() {
             file_path.set_extension(extension);
         }
+        file_path
+    }
+    
+    pub fn save_file(&self, url_path: &str, content: &[u8], mime_type: Option<&str>) -> Result<PathBuf> {
+        self.create_directories_for_url(url_path)?;
+        let file_path = self.file_path(url_path, content, mime_type);
+        
+        if let Some(throttle) = &self.throttle {
+            throttle.write_file(&file_path, content)?;
+            return Ok(file_path);
+        }
         
         // Write the file
         let mut file = fs::File::create(&file_path)
@@ -54,6 +159,23 @@ impl FileManager {
         Ok(file_path)
     }
     
+    /// Open the file `save_file` would write for content arriving in pieces
+    /// (a streamed download), starting with `head`
+    pub fn create_file(&self, url_path: &str, head: &[u8], mime_type: Option<&str>) -> Result<OutputFile<'_>> {
+        self.create_directories_for_url(url_path)?;
+        let path = self.file_path(url_path, head, mime_type);
+        let mut file = match &self.throttle {
+            Some(throttle) => OutputFile::Throttled(throttle.create(&path)?, path),
+            None => {
+                let file = fs::File::create(&path)
+                    .with_context(|| format!("Failed to create file: {:?}", path))?;
+                OutputFile::Direct(file, path)
+            }
+        };
+        file.write_all(head)?;
+        Ok(file)
+    }
+    
     fn get_file_extension(&self, url_path: &str, mime_type: Option<&str>, content: &[u8]) -> String {
         // First try to get extension from MIME type
         if let Some(mime) = mime_type {
@@ -91,6 +213,46 @@ impl FileManager {
     }
 } 
 
+/// A file `FileManager::create_file` opened, written directly or through the
+/// `--io-throttle` limiter
+pub enum OutputFile<'a> {
+    Direct(fs::File, PathBuf),
+    Throttled(ThrottledFile<'a>, PathBuf),
+}
+
+impl OutputFile<'_> {
+    pub fn write_all(&mut self, content: &[u8]) -> Result<()> {
+        match self {
+            OutputFile::Direct(file, path) => file.write_all(content)
+                .with_context(|| format!("Failed to write to file: {:?}", path)),
+            OutputFile::Throttled(file, _) => file.write_all(content),
+        }
+    }
+    
+    /// Keep the file, returning where it was saved
+    pub fn finish(self) -> Result<PathBuf> {
+        match self {
+            OutputFile::Direct(_, path) => Ok(path),
+            OutputFile::Throttled(file, path) => {
+                file.finish()?;
+                Ok(path)
+            }
+        }
+    }
+    
+    /// Drop a partly written file
+    pub fn discard(self) -> Result<()> {
+        match self {
+            OutputFile::Direct(file, path) => {
+                drop(file);
+                fs::remove_file(&path).with_context(|| format!("Failed to remove {:?}", path))
+            }
+            // The `.part` file is removed when it's dropped unfinished
+            OutputFile::Throttled(..) => Ok(()),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -121,6 +283,7 @@ mod tests {
         
         let saved_path = result.unwrap();
         assert!(saved_path.exists());
+        assert_eq!(file_manager.file_path("test.txt", content, Some("text/plain")), saved_path);
         
         let read_content = fs::read(&saved_path).unwrap();
         assert_eq!(read_content, content);
@@ -170,6 +333,40 @@ mod tests {
         assert!(saved_path.exists());
     }
 
+    #[test]
+    fn test_safe_path_reserved_names_and_trailing_dots() {
+        assert_eq!(FileManager::safe_path("con/index.html"), "con_/index.html");
+        assert_eq!(FileManager::safe_path("docs/NUL.html"), "docs/NUL_.html");
+        assert_eq!(FileManager::safe_path("lpt1.tar.gz"), "lpt1_.tar.gz");
+        assert_eq!(FileManager::safe_path("console/index.html"), "console/index.html");
+        assert_eq!(FileManager::safe_path("v1./index.html"), "v1_/index.html");
+        assert_eq!(FileManager::safe_path("..."), "___");
+    }
+
+    #[test]
+    fn test_safe_path_shortens_long_components() {
+        let long_name = format!("{}.html", "a".repeat(300));
+        let safe = FileManager::safe_path(&format!("posts/{}", long_name));
+        let file_name = safe.strip_prefix("posts/").unwrap();
+        
+        assert_eq!(file_name.len(), MAX_COMPONENT_BYTES);
+        assert!(file_name.ends_with(&format!(".{}.html", FileManager::short_hash(&long_name))));
+        assert_ne!(safe, FileManager::safe_path(&format!("posts/{}b.html", "a".repeat(300))));
+    }
+
+    #[test]
+    fn test_case_insensitive_path() {
+        let upper = FileManager::case_insensitive_path("About/Logo.PNG");
+        let lower = FileManager::case_insensitive_path("about/logo.png");
+        
+        assert_eq!(lower, "about/logo.png");
+        assert_eq!(
+            upper,
+            format!("About.{}/Logo.{}.PNG", FileManager::short_hash("About"), FileManager::short_hash("Logo.PNG"))
+        );
+        assert_ne!(upper.to_lowercase(), lower);
+    }
+
     #[test]
     fn test_save_file_with_query_parameters() {
         let temp_dir = tempdir().unwrap();
@@ -450,6 +647,8 @@ mod tests {
     }
 
     #[test]
+    // `IoThrottle` hashes only its settings, not the state behind its locks
+    #[allow(clippy::mutable_key_type)]
     fn test_file_manager_hash() {
         use std::collections::HashMap;
         
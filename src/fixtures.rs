@@ -0,0 +1,340 @@
+use anyhow::{anyhow, Result, Context};
+use base64::Engine;
+use reqwest::header::{HeaderName, HeaderValue};
+use reqwest::{Client, Response, ResponseBuilderExt};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::net::SocketAddr;
+use std::path::Path;
+use std::sync::Arc;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::task::JoinHandle;
+use tracing::warn;
+use url::Url;
+
+/// Recorded responses in a fixtures directory
+pub const FIXTURES_FILE_NAME: &str = "fixtures.json";
+
+/// Request header naming the recorded URL the stub server should answer with
+const URL_HEADER: &str = "x-fixture-url";
+/// Response header carrying the URL the recorded response was finally served from
+const FINAL_URL_HEADER: &str = "x-fixture-final-url";
+/// Response header carrying the redirect hops, space-separated
+const REDIRECTS_HEADER: &str = "x-fixture-redirects";
+
+/// Headers describing the stub server's connection rather than the recorded response
+const CONNECTION_HEADERS: &[&str] = &["content-length", "transfer-encoding", "connection", "keep-alive"];
+
+/// One HTTP interaction of a recorded crawl
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RecordedResponse {
+    /// URL as requested by the crawler
+    pub url: String,
+    /// Redirect hops the request went through, ending at the URL that answered
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub redirects: Vec<String>,
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    /// Body, base64-encoded
+    pub body: String,
+    /// Set when the request failed without a response (DNS, TLS, timeout, ...)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl RecordedResponse {
+    pub fn new(url: &str, status: u16, headers: &[(&str, &str)], body: &[u8]) -> Self {
+        Self {
+            url: url.to_string(),
+            redirects: Vec::new(),
+            status,
+            headers: headers.iter().map(|(name, value)| (name.to_string(), value.to_string())).collect(),
+            body: base64::engine::general_purpose::STANDARD.encode(body),
+            error: None,
+        }
+    }
+
+    /// A request that failed before any response arrived
+    pub fn failed(url: &str, error: &str) -> Self {
+        Self {
+            error: Some(error.to_string()),
+            ..Self::new(url, 0, &[], b"")
+        }
+    }
+
+    pub fn body_bytes(&self) -> Result<Vec<u8>> {
+        base64::engine::general_purpose::STANDARD
+            .decode(&self.body)
+            .with_context(|| format!("Invalid recorded body for {}", self.url))
+    }
+
+    /// URL the response was served from, after redirects
+    pub fn final_url(&self) -> &str {
+        self.redirects.last().unwrap_or(&self.url)
+    }
+}
+
+/// The HTTP interactions of a crawl, saved as `fixtures.json` by `--record-fixtures`
+/// and served back by `--replay-fixtures`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FixtureSet {
+    pub start_url: String,
+    pub responses: BTreeMap<String, RecordedResponse>,
+}
+
+impl FixtureSet {
+    pub fn new(start_url: &str) -> Self {
+        Self {
+            start_url: start_url.to_string(),
+            responses: BTreeMap::new(),
+        }
+    }
+
+    /// Add a response, replacing any earlier one for its URL
+    pub fn insert(&mut self, response: RecordedResponse) {
+        self.responses.insert(response.url.clone(), response);
+    }
+
+    pub fn get(&self, url: &str) -> Option<&RecordedResponse> {
+        self.responses.get(url)
+    }
+
+    pub fn len(&self) -> usize {
+        self.responses.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.responses.is_empty()
+    }
+
+    pub fn load(dir: &Path) -> Result<Self> {
+        let path = dir.join(FIXTURES_FILE_NAME);
+        let data = fs::read_to_string(&path).with_context(|| format!("Failed to read fixtures: {:?}", path))?;
+        serde_json::from_str(&data).with_context(|| format!("Failed to parse fixtures: {:?}", path))
+    }
+
+    pub fn save(&self, dir: &Path) -> Result<()> {
+        fs::create_dir_all(dir).with_context(|| format!("Failed to create fixtures directory: {:?}", dir))?;
+        let path = dir.join(FIXTURES_FILE_NAME);
+        fs::write(&path, serde_json::to_string_pretty(self)?).with_context(|| format!("Failed to write fixtures: {:?}", path))
+    }
+}
+
+/// Buffer a live response so it can be both recorded and handed on to the crawler
+pub async fn capture(url: &str, redirects: Vec<String>, response: Response) -> Result<(Response, RecordedResponse)> {
+    let status = response.status();
+    let final_url = response.url().clone();
+    let headers: Vec<(String, String)> = response
+        .headers()
+        .iter()
+        .filter_map(|(name, value)| Some((name.as_str().to_string(), value.to_str().ok()?.to_string())))
+        .collect();
+    let body = response.bytes().await?.to_vec();
+
+    let mut builder = http::Response::builder().status(status).url(final_url);
+    if let Some(builder_headers) = builder.headers_mut() {
+        append_headers(builder_headers, &headers);
+    }
+    let recorded = RecordedResponse {
+        url: url.to_string(),
+        redirects,
+        status: status.as_u16(),
+        headers,
+        body: base64::engine::general_purpose::STANDARD.encode(&body),
+        error: None,
+    };
+    Ok((Response::from(builder.body(body)?), recorded))
+}
+
+fn append_headers(headers: &mut http::HeaderMap, recorded: &[(String, String)]) {
+    for (name, value) in recorded {
+        if CONNECTION_HEADERS.contains(&name.to_ascii_lowercase().as_str()) {
+            continue;
+        }
+        if let (Ok(name), Ok(value)) = (HeaderName::from_bytes(name.as_bytes()), HeaderValue::from_str(value)) {
+            headers.append(name, value);
+        }
+    }
+}
+
+/// A stub HTTP server on localhost answering the crawler's requests from a
+/// `FixtureSet`. Requests name the recorded URL in a header, so pages of any
+/// scheme and host are served from the one port; unrecorded URLs get a 404 and
+/// recorded failures a dropped connection.
+#[derive(Debug)]
+pub struct FixtureServer {
+    addr: SocketAddr,
+    task: JoinHandle<()>,
+}
+
+impl FixtureServer {
+    pub async fn start(fixtures: FixtureSet) -> Result<Self> {
+        let listener = TcpListener::bind("127.0.0.1:0").await.context("Failed to start fixture server")?;
+        let addr = listener.local_addr()?;
+        let fixtures = Arc::new(fixtures);
+        let task = tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let fixtures = fixtures.clone();
+                tokio::spawn(async move {
+                    if let Err(e) = serve(stream, &fixtures).await {
+                        warn!("⚠️  Fixture server: {}", e);
+                    }
+                });
+            }
+        });
+        Ok(Self { addr, task })
+    }
+
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// GET the recorded response for `url` through the stub server, returning it
+    /// as if it came from `url`, with the redirect hops it went through
+    pub async fn fetch(&self, client: &Client, url: &str) -> Result<(Response, Vec<String>)> {
+        let response = client
+            .get(format!("http://{}/", self.addr))
+            .header(URL_HEADER, url)
+            .send()
+            .await
+            .map_err(|e| anyhow!("Replayed request for {} failed: {}", url, e))?;
+        let header = |name: &str| response.headers().get(name).and_then(|value| value.to_str().ok()).map(str::to_string);
+        let final_url = header(FINAL_URL_HEADER).unwrap_or_else(|| url.to_string());
+        let redirects: Vec<String> = header(REDIRECTS_HEADER)
+            .map(|hops| hops.split(' ').map(str::to_string).collect())
+            .unwrap_or_default();
+
+        let mut builder = http::Response::builder().status(response.status()).url(Url::parse(&final_url)?);
+        if let Some(headers) = builder.headers_mut() {
+            for (name, value) in response.headers() {
+                if !name.as_str().starts_with("x-fixture-") && !CONNECTION_HEADERS.contains(&name.as_str()) {
+                    headers.append(name.clone(), value.clone());
+                }
+            }
+        }
+        let body = response.bytes().await?.to_vec();
+        Ok((Response::from(builder.body(body)?), redirects))
+    }
+}
+
+impl Drop for FixtureServer {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+/// Answer one request with its recorded response
+async fn serve(stream: TcpStream, fixtures: &FixtureSet) -> Result<()> {
+    let mut reader = BufReader::new(stream);
+    let mut url = None;
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line).await? == 0 {
+            break;
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            if name.trim().eq_ignore_ascii_case(URL_HEADER) {
+                url = Some(value.trim().to_string());
+            }
+        }
+    }
+    let mut stream = reader.into_inner();
+
+    let Some(recorded) = url.as_deref().and_then(|url| fixtures.get(url)) else {
+        let body = format!("Not recorded: {}", url.as_deref().unwrap_or_default());
+        let head = format!(
+            "HTTP/1.1 404 Not Found\r\ncontent-type: text/plain\r\ncontent-length: {}\r\nconnection: close\r\n\r\n",
+            body.len()
+        );
+        stream.write_all(head.as_bytes()).await?;
+        stream.write_all(body.as_bytes()).await?;
+        return Ok(stream.shutdown().await?);
+    };
+    // Dropping the connection fails the request like the recorded error did
+    if recorded.error.is_some() {
+        return Ok(());
+    }
+
+    let body = recorded.body_bytes()?;
+    let reason = http::StatusCode::from_u16(recorded.status)
+        .ok()
+        .and_then(|status| status.canonical_reason())
+        .unwrap_or("");
+    let mut head = format!("HTTP/1.1 {} {}\r\n", recorded.status, reason);
+    for (name, value) in &recorded.headers {
+        if !CONNECTION_HEADERS.contains(&name.to_ascii_lowercase().as_str()) {
+            head.push_str(&format!("{}: {}\r\n", name, value));
+        }
+    }
+    head.push_str(&format!("{}: {}\r\n", FINAL_URL_HEADER, recorded.final_url()));
+    if !recorded.redirects.is_empty() {
+        head.push_str(&format!("{}: {}\r\n", REDIRECTS_HEADER, recorded.redirects.join(" ")));
+    }
+    head.push_str(&format!("content-length: {}\r\nconnection: close\r\n\r\n", body.len()));
+    stream.write_all(head.as_bytes()).await?;
+    stream.write_all(&body).await?;
+    Ok(stream.shutdown().await?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn fixtures() -> FixtureSet {
+        let mut fixtures = FixtureSet::new("https://example.com/");
+        fixtures.insert(RecordedResponse::new(
+            "https://example.com/",
+            200,
+            &[("Content-Type", "text/html"), ("ETag", "\"v1\"")],
+            b"<p>Home</p>",
+        ));
+        let mut moved = RecordedResponse::new("https://example.com/docs", 200, &[("Content-Type", "text/html")], b"<p>Docs</p>");
+        moved.redirects = vec!["https://example.com/docs/".to_string()];
+        fixtures.insert(moved);
+        fixtures.insert(RecordedResponse::new("https://example.com/gone", 410, &[], b""));
+        fixtures.insert(RecordedResponse::failed("https://cdn.example.org/app.js", "dns error"));
+        fixtures
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let temp_dir = tempdir().unwrap();
+        let fixtures = fixtures();
+        fixtures.save(&temp_dir.path().join("recorded")).unwrap();
+        let loaded = FixtureSet::load(&temp_dir.path().join("recorded")).unwrap();
+        assert_eq!(loaded, fixtures);
+        assert_eq!(loaded.get("https://example.com/").unwrap().body_bytes().unwrap(), b"<p>Home</p>");
+        assert!(FixtureSet::load(temp_dir.path()).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_server_replays_responses() {
+        let server = FixtureServer::start(fixtures()).await.unwrap();
+        let client = Client::new();
+
+        let (response, redirects) = server.fetch(&client, "https://example.com/").await.unwrap();
+        assert_eq!(response.status(), 200);
+        assert_eq!(response.url().as_str(), "https://example.com/");
+        assert_eq!(response.headers().get("etag").unwrap(), "\"v1\"");
+        assert!(response.headers().get(FINAL_URL_HEADER).is_none());
+        assert!(redirects.is_empty());
+        assert_eq!(response.text().await.unwrap(), "<p>Home</p>");
+
+        let (response, redirects) = server.fetch(&client, "https://example.com/docs").await.unwrap();
+        assert_eq!(response.url().as_str(), "https://example.com/docs/");
+        assert_eq!(redirects, vec!["https://example.com/docs/"]);
+
+        let (response, _) = server.fetch(&client, "https://example.com/gone").await.unwrap();
+        assert_eq!(response.status(), 410);
+        let (response, _) = server.fetch(&client, "https://example.com/unrecorded").await.unwrap();
+        assert_eq!(response.status(), 404);
+        assert!(server.fetch(&client, "https://cdn.example.org/app.js").await.is_err());
+    }
+}
@@ -0,0 +1,178 @@
+use anyhow::{Result, Context};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::css_parser::classify_css_url;
+use crate::html_parser::ResourceType;
+use crate::store::DownloadStore;
+
+/// Directory (inside the output directory) holding generated thumbnails
+pub const THUMBNAIL_DIR: &str = "_thumbnails";
+/// Gallery page written at the root of the output directory
+pub const GALLERY_FILE_NAME: &str = "index.html";
+/// Longest edge of a generated thumbnail, in pixels
+pub const THUMBNAIL_SIZE: u32 = 240;
+
+/// A downloaded image and the thumbnail shown for it in the gallery
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GalleryItem {
+    pub url: String,
+    /// Path of the full image, relative to the output directory
+    pub image_path: String,
+    /// Path of the thumbnail, relative to the output directory
+    pub thumbnail_path: String,
+}
+
+fn is_image_entry(url: &str, local_path: &str, content_type: Option<&str>) -> bool {
+    match content_type {
+        Some(content_type) if content_type.starts_with("image/") => true,
+        _ => classify_css_url(local_path) == ResourceType::Image || classify_css_url(url) == ResourceType::Image,
+    }
+}
+
+/// Write a thumbnail for `image_path`, returning its path relative to the
+/// output directory. Images that can't be decoded (e.g. SVG) are shown at
+/// their original path instead.
+fn create_thumbnail(output_dir: &Path, image_path: &str) -> String {
+    let source = output_dir.join(image_path);
+    let thumbnail_path = format!("{}/{}.png", THUMBNAIL_DIR, image_path);
+
+    let result = image::open(&source).and_then(|img| {
+        let target = output_dir.join(&thumbnail_path);
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        img.thumbnail(THUMBNAIL_SIZE, THUMBNAIL_SIZE).save_with_format(&target, image::ImageFormat::Png)
+    });
+
+    match result {
+        Ok(()) => thumbnail_path,
+        Err(_) => image_path.to_string(),
+    }
+}
+
+pub(crate) fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Collect the images recorded in the store that exist on disk, creating a
+/// thumbnail for each
+pub fn collect_gallery_items(output_dir: &Path, store: &DownloadStore) -> Vec<GalleryItem> {
+    store
+        .entries
+        .iter()
+        .filter(|(url, entry)| {
+            !entry.local_path.is_empty()
+                && is_image_entry(url, &entry.local_path, entry.content_type.as_deref())
+        })
+        .filter_map(|(url, entry)| {
+            let image_path = entry.local_path.trim_start_matches('/').to_string();
+            if !output_dir.join(&image_path).is_file() {
+                return None;
+            }
+            let thumbnail_path = create_thumbnail(output_dir, &image_path);
+            Some(GalleryItem {
+                url: url.clone(),
+                image_path,
+                thumbnail_path,
+            })
+        })
+        .collect()
+}
+
+/// Render the gallery page for a set of images
+pub fn render_gallery(items: &[GalleryItem]) -> String {
+    let mut html = String::new();
+    html.push_str("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n");
+    html.push_str("<title>Image Gallery</title>\n<style>\n");
+    html.push_str("body { font-family: sans-serif; margin: 1rem; }\n");
+    html.push_str(".gallery { display: grid; grid-template-columns: repeat(auto-fill, minmax(240px, 1fr)); gap: 1rem; }\n");
+    html.push_str(".gallery a { display: block; text-align: center; word-break: break-all; font-size: 0.8rem; color: #333; }\n");
+    html.push_str(".gallery img { max-width: 240px; max-height: 240px; }\n");
+    html.push_str("</style>\n</head>\n<body>\n");
+    html.push_str(&format!("<h1>Image Gallery ({} images)</h1>\n<div class=\"gallery\">\n", items.len()));
+
+    for item in items {
+        let image_path = escape_html(&item.image_path);
+        html.push_str(&format!(
+            "<a href=\"{}\" title=\"{}\"><img src=\"{}\" alt=\"{}\" loading=\"lazy\"><br>{}</a>\n",
+            image_path,
+            escape_html(&item.url),
+            escape_html(&item.thumbnail_path),
+            image_path,
+            image_path,
+        ));
+    }
+
+    html.push_str("</div>\n</body>\n</html>\n");
+    html
+}
+
+/// Generate `index.html` with thumbnails linking to every downloaded image
+pub fn generate_gallery(output_dir: &Path, store: &DownloadStore) -> Result<PathBuf> {
+    let items = collect_gallery_items(output_dir, store);
+    let gallery_path = output_dir.join(GALLERY_FILE_NAME);
+    fs::write(&gallery_path, render_gallery(&items))
+        .with_context(|| format!("Failed to write gallery: {:?}", gallery_path))?;
+    Ok(gallery_path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_generate_gallery_with_thumbnails() {
+        let temp_dir = tempdir().unwrap();
+        let images_dir = temp_dir.path().join("images");
+        fs::create_dir_all(&images_dir).unwrap();
+        image::RgbImage::from_pixel(600, 300, image::Rgb([10, 20, 30]))
+            .save(images_dir.join("photo.png"))
+            .unwrap();
+        fs::write(images_dir.join("logo.svg"), "<svg xmlns=\"http://www.w3.org/2000/svg\"/>").unwrap();
+
+        let mut store = DownloadStore::default();
+        store.record_download("https://example.com/images/photo.png", "images/photo.png", Some("image/png"), 100);
+        store.record_download("https://example.com/images/logo.svg", "images/logo.svg", Some("image/svg+xml"), 40);
+        store.record_download("https://example.com/app.js", "app.js", Some("application/javascript"), 10);
+
+        let gallery_path = generate_gallery(temp_dir.path(), &store).unwrap();
+        let html = fs::read_to_string(gallery_path).unwrap();
+
+        assert!(html.contains("Image Gallery (2 images)"));
+        assert!(html.contains("href=\"images/photo.png\""));
+        assert!(html.contains("src=\"_thumbnails/images/photo.png.png\""));
+        // Undecodable images fall back to the original file
+        assert!(html.contains("src=\"images/logo.svg\""));
+        assert!(!html.contains("app.js"));
+
+        let thumbnail = image::open(temp_dir.path().join("_thumbnails/images/photo.png.png")).unwrap();
+        assert_eq!((thumbnail.width(), thumbnail.height()), (240, 120));
+    }
+
+    #[test]
+    fn test_gallery_skips_missing_files() {
+        let temp_dir = tempdir().unwrap();
+        let mut store = DownloadStore::default();
+        store.record_download("https://example.com/missing.jpg", "missing.jpg", Some("image/jpeg"), 10);
+
+        assert!(collect_gallery_items(temp_dir.path(), &store).is_empty());
+    }
+
+    #[test]
+    fn test_render_gallery_escapes_paths() {
+        let items = vec![GalleryItem {
+            url: "https://example.com/a&b.png".to_string(),
+            image_path: "a&b.png".to_string(),
+            thumbnail_path: "_thumbnails/a&b.png.png".to_string(),
+        }];
+
+        let html = render_gallery(&items);
+        assert!(html.contains("href=\"a&amp;b.png\""));
+        assert!(!html.contains("a&b.png"));
+    }
+}
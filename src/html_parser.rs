@@ -1,13 +1,37 @@
-use anyhow::{Result, Context};
+use anyhow::{anyhow, Result, Context};
+use html5ever::tendril::StrTendril;
+use html5ever::tokenizer::states::RawKind;
+use html5ever::tokenizer::{BufferQueue, Tag, TagKind, Token, TokenSink, TokenSinkResult, Tokenizer, TokenizerOpts};
 use select::document::Document;
 use select::predicate::{Name, Attr};
+use regex::Regex;
+use std::collections::HashMap;
+use std::str::FromStr;
 use url::Url;
 
+use crate::css_parser::{self, CssReference};
+use crate::file_manager::FileManager;
+
 #[derive(Debug, Clone)]
 pub struct ResourceLink {
     pub original_url: String,
     pub local_path: String,
     pub resource_type: ResourceType,
+    /// Where the resource is referenced; only filled by `HtmlParser::extract_all`
+    pub locations: Vec<SourceLocation>,
+}
+
+/// A place a resource is referenced from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SourceLocation {
+    /// URL of the page or stylesheet containing the reference
+    pub document: String,
+    /// Element holding the reference (`img`, `style`), or `@import`/`url` in stylesheets
+    pub element: String,
+    /// Attribute holding the reference (`src`, `srcset`, `style`), `None` for CSS text
+    pub attribute: Option<String>,
+    /// Byte offset of the reference in the document
+    pub offset: usize,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -15,25 +39,360 @@ pub enum ResourceType {
     CSS,
     JavaScript,
     Image,
+    Video,
+    Audio,
+    Font,
     Link,
     Other,
 }
 
+impl ResourceType {
+    /// Name used for this resource type by the `--only-resources` filter
+    pub fn filter_name(&self) -> &'static str {
+        match self {
+            ResourceType::Image => "images",
+            ResourceType::CSS => "css",
+            ResourceType::JavaScript => "js",
+            ResourceType::Video => "video",
+            ResourceType::Audio => "audio",
+            ResourceType::Font => "fonts",
+            ResourceType::Link => "html",
+            ResourceType::Other => "other",
+        }
+    }
+    
+    /// Guess the resource type of a URL from its file extension
+    pub fn from_extension(url: &str) -> ResourceType {
+        let path = url.split(['?', '#']).next().unwrap_or(url).to_lowercase();
+        let file_name = path.rsplit('/').next().unwrap_or(&path);
+        let extension = file_name.rsplit_once('.').map(|(_, ext)| ext).unwrap_or("");
+        match extension {
+            "css" => ResourceType::CSS,
+            "js" | "mjs" => ResourceType::JavaScript,
+            "png" | "jpg" | "jpeg" | "gif" | "webp" | "svg" | "avif" | "ico" | "bmp" => ResourceType::Image,
+            "mp4" | "webm" | "ogv" | "mov" => ResourceType::Video,
+            "mp3" | "ogg" | "oga" | "opus" | "wav" | "m4a" | "aac" | "flac" => ResourceType::Audio,
+            "woff" | "woff2" | "ttf" | "otf" | "eot" => ResourceType::Font,
+            _ => ResourceType::Other,
+        }
+    }
+    
+    /// Resource type implied by a `Content-Type` header, if it is recognized
+    pub fn from_content_type(content_type: &str) -> Option<ResourceType> {
+        let mime = content_type.split(';').next().unwrap_or("").trim().to_lowercase();
+        let (top_level, subtype) = mime.split_once('/')?;
+        match (top_level, subtype) {
+            ("text", "html") | ("application", "xhtml+xml") => Some(ResourceType::Link),
+            ("text", "css") => Some(ResourceType::CSS),
+            (_, "javascript") | (_, "ecmascript") => Some(ResourceType::JavaScript),
+            ("image", _) => Some(ResourceType::Image),
+            ("video", _) => Some(ResourceType::Video),
+            ("audio", _) | ("application", "ogg") => Some(ResourceType::Audio),
+            ("font", _) | ("application", "font-woff") | ("application", "vnd.ms-fontobject") => Some(ResourceType::Font),
+            _ => None,
+        }
+    }
+}
+
 #[derive(Clone)]
 #[derive(Debug)]
 pub struct HtmlParser {
     base_url: Url,
+    /// Attributes holding JS lazy-loaded image sources (e.g. `data-src`)
+    lazy_attrs: Vec<String>,
+    /// Which HTML comments are searched for references
+    comment_mode: CommentMode,
+}
+
+/// Which HTML comments are searched for references (`--comment-resources`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CommentMode {
+    /// Comments are not searched
+    Ignore,
+    /// IE conditional comments (`<!--[if lt IE 9]>...<![endif]-->`)
+    Conditional,
+    /// Conditional comments and every other comment, e.g. commented-out assets
+    All,
+}
+
+impl FromStr for CommentMode {
+    type Err = anyhow::Error;
+
+    fn from_str(mode: &str) -> Result<Self> {
+        match mode.to_ascii_lowercase().as_str() {
+            "ignore" => Ok(CommentMode::Ignore),
+            "conditional" => Ok(CommentMode::Conditional),
+            "all" => Ok(CommentMode::All),
+            _ => Err(anyhow!("Unknown comment mode: {} (expected ignore, conditional or all)", mode)),
+        }
+    }
+}
+
+/// Markup inside a comment body `mode` searches: the guarded markup of a
+/// conditional comment, or the whole body of others under `CommentMode::All`
+fn comment_markup(body: &str, mode: CommentMode) -> Option<&str> {
+    if let Some(condition) = body.trim_start().strip_prefix("[if") {
+        let (_, markup) = condition.split_once("]>")?;
+        return Some(markup.split("<![endif]").next().unwrap_or(markup));
+    }
+    (mode == CommentMode::All).then_some(body)
+}
+
+/// Split a `srcset` value into its candidate URLs
+fn srcset_urls(srcset: &str) -> Vec<&str> {
+    srcset
+        .split(',')
+        .filter_map(|candidate| candidate.split_whitespace().next())
+        .collect()
+}
+
+/// Candidate URLs of a `srcset` value with their byte offsets in it
+fn srcset_url_offsets(srcset: &str) -> Vec<(usize, &str)> {
+    let mut offsets = Vec::new();
+    let mut start = 0;
+    for candidate in srcset.split(',') {
+        let trimmed = candidate.trim_start();
+        if let Some(candidate_url) = trimmed.split_whitespace().next() {
+            offsets.push((start + candidate.len() - trimmed.len(), candidate_url));
+        }
+        start += candidate.len() + 1;
+    }
+    offsets
+}
+
+/// References in a piece of CSS with the byte offset of every occurrence
+fn css_reference_offsets(css_content: &str) -> Vec<(usize, CssReference)> {
+    let mut seen = std::collections::HashSet::new();
+    let mut offsets = Vec::new();
+    for reference in css_parser::extract_css_references(css_content) {
+        if !seen.insert(reference.url.clone()) {
+            continue;
+        }
+        for (offset, _) in css_content.match_indices(reference.url.as_str()) {
+            offsets.push((offset, reference.clone()));
+        }
+    }
+    offsets.sort_by_key(|(offset, _)| *offset);
+    offsets
+}
+
+/// Collects the references `extract_resources` looks for from a token stream,
+/// without building a DOM, for pages too large to parse comfortably
+struct ResourceSink {
+    lazy_attrs: Vec<String>,
+    /// References in document order
+    references: Vec<(String, ResourceType)>,
+    /// Values of inline `style` attributes
+    style_attributes: Vec<String>,
+    /// Text of `<style>` blocks
+    style_blocks: Vec<String>,
+    in_style: bool,
+    /// Types of the open `<video>`/`<audio>` elements, for their `<source>` children
+    media_stack: Vec<ResourceType>,
+}
+
+impl ResourceSink {
+    fn new(lazy_attrs: Vec<String>) -> Self {
+        Self {
+            lazy_attrs,
+            references: Vec::new(),
+            style_attributes: Vec::new(),
+            style_blocks: Vec::new(),
+            in_style: false,
+            media_stack: Vec::new(),
+        }
+    }
+    
+    fn process_tag(&mut self, tag: Tag) -> TokenSinkResult<()> {
+        let name: &str = &tag.name;
+        if tag.kind == TagKind::EndTag {
+            match name {
+                "style" => self.in_style = false,
+                "video" | "audio" => {
+                    self.media_stack.pop();
+                }
+                _ => {}
+            }
+            return TokenSinkResult::Continue;
+        }
+        
+        let attr = |attr_name: &str| tag.attrs.iter().find(|a| &*a.name.local == attr_name).map(|a| a.value.to_string());
+        match name {
+            "link" => {
+                if let (Some(href), Some(rel)) = (attr("href"), attr("rel")) {
+                    if rel.contains("stylesheet") {
+                        self.references.push((href.clone(), ResourceType::CSS));
+                    }
+                    if let Some(resource_type) = HtmlParser::preload_resource_type(&rel, attr("as").as_deref()) {
+                        self.references.push((href.clone(), resource_type));
+                    }
+                    if rel.split_whitespace().any(|r| r.eq_ignore_ascii_case("manifest")) {
+                        self.references.push((href, ResourceType::Other));
+                    }
+                }
+            }
+            "script" => {
+                if let Some(src) = attr("src") {
+                    self.references.push((src, ResourceType::JavaScript));
+                }
+            }
+            "img" => {
+                if let Some(src) = attr("src") {
+                    self.references.push((src, ResourceType::Image));
+                }
+            }
+            "video" | "audio" => {
+                let media_type = if name == "video" { ResourceType::Video } else { ResourceType::Audio };
+                if let Some(src) = attr("src") {
+                    self.references.push((src, media_type.clone()));
+                }
+                if let Some(poster) = attr("poster").filter(|_| name == "video") {
+                    self.references.push((poster, ResourceType::Image));
+                }
+                if !tag.self_closing {
+                    self.media_stack.push(media_type);
+                }
+            }
+            "source" => {
+                if let (Some(media_type), Some(src)) = (self.media_stack.last(), attr("src")) {
+                    self.references.push((src, media_type.clone()));
+                }
+            }
+            "a" => {
+                if let Some(href) = attr("href") {
+                    let resource_type = match ResourceType::from_extension(&href) {
+                        ResourceType::Audio => ResourceType::Audio,
+                        _ => ResourceType::Link,
+                    };
+                    self.references.push((href, resource_type));
+                }
+            }
+            "style" => {
+                self.in_style = true;
+                self.style_blocks.push(String::new());
+            }
+            _ => {}
+        }
+        
+        for lazy_attr in &self.lazy_attrs {
+            let Some(value) = attr(lazy_attr) else { continue };
+            if value.trim_start().starts_with("data:") {
+                continue;
+            }
+            let urls = if lazy_attr.ends_with("srcset") { srcset_urls(&value) } else { vec![value.trim()] };
+            self.references.extend(urls.into_iter().map(|lazy_url| (lazy_url.to_string(), ResourceType::Image)));
+        }
+        if let Some(style) = attr("style") {
+            self.style_attributes.push(style);
+        }
+        
+        // Without a tree builder the sink switches the tokenizer into the text
+        // states the HTML parser would, so markup inside scripts isn't scanned
+        match name {
+            "style" | "xmp" | "iframe" | "noembed" | "noframes" | "noscript" => TokenSinkResult::RawData(RawKind::Rawtext),
+            "script" => TokenSinkResult::RawData(RawKind::ScriptData),
+            "title" | "textarea" => TokenSinkResult::RawData(RawKind::Rcdata),
+            _ => TokenSinkResult::Continue,
+        }
+    }
+}
+
+impl TokenSink for ResourceSink {
+    type Handle = ();
+    
+    fn process_token(&mut self, token: Token, _line_number: u64) -> TokenSinkResult<()> {
+        match token {
+            Token::TagToken(tag) => return self.process_tag(tag),
+            Token::CharacterTokens(text) if self.in_style => {
+                if let Some(block) = self.style_blocks.last_mut() {
+                    block.push_str(&text);
+                }
+            }
+            _ => {}
+        }
+        TokenSinkResult::Continue
+    }
+}
+
+/// Rewrite each candidate URL of a `srcset` value, keeping its descriptor
+fn rewrite_srcset<F>(srcset: &str, rewrite: &mut F) -> String
+where
+    F: FnMut(&str) -> Option<String>,
+{
+    srcset
+        .split(',')
+        .filter(|candidate| !candidate.trim().is_empty())
+        .map(|candidate| {
+            let candidate = candidate.trim();
+            let (candidate_url, descriptor) = candidate.split_once(char::is_whitespace).unwrap_or((candidate, ""));
+            let new_url = rewrite(candidate_url).unwrap_or_else(|| candidate_url.to_string());
+            if descriptor.is_empty() { new_url } else { format!("{} {}", new_url, descriptor.trim()) }
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Whether a URL is resolved against the document base (not absolute,
+/// protocol-relative, a fragment or a `data:`/`mailto:`-style URL)
+fn is_base_relative(url: &str) -> bool {
+    let url = url.trim();
+    let has_scheme = url
+        .split_once(':')
+        .is_some_and(|(scheme, _)| {
+            !scheme.is_empty()
+                && scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+                && scheme.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '.' | '-'))
+        });
+    !(url.is_empty() || url.starts_with('#') || url.starts_with("//") || has_scheme)
 }
 
 impl HtmlParser {
+    /// Lazy-loading attributes treated as image sources when none are configured
+    pub const DEFAULT_LAZY_ATTRS: &'static [&'static str] = &[
+        "data-src",
+        "data-srcset",
+        "data-original",
+        "data-lazy",
+        "data-lazy-src",
+        "data-lazy-srcset",
+    ];
+    
+    /// Pages larger than this (bytes) are scanned with the streaming tokenizer
+    /// instead of being parsed into a DOM, which takes several times their size
+    pub const STREAMING_THRESHOLD: usize = 8 * 1024 * 1024;
+    
+    /// Bytes of a large page handed to the tokenizer at a time
+    const STREAMING_CHUNK_SIZE: usize = 64 * 1024;
+    
     pub fn new(base_url: &str) -> Result<Self> {
         let base_url = Url::parse(base_url)
             .with_context(|| format!("Failed to parse base URL: {}", base_url))?;
         
-        Ok(Self { base_url })
+        Ok(Self {
+            base_url,
+            lazy_attrs: Vec::new(),
+            comment_mode: CommentMode::Ignore,
+        })
+    }
+    
+    /// Treat these attributes as image sources; names ending in `srcset`
+    /// are parsed as srcset candidate lists
+    pub fn with_lazy_attrs(mut self, lazy_attrs: &[String]) -> Self {
+        self.lazy_attrs = lazy_attrs.iter().map(|attr| attr.trim().to_ascii_lowercase()).filter(|attr| !attr.is_empty()).collect();
+        self
+    }
+    
+    /// Search these HTML comments for references (see `extract_comment_resources`)
+    pub fn with_comment_mode(mut self, comment_mode: CommentMode) -> Self {
+        self.comment_mode = comment_mode;
+        self
     }
     
     pub fn extract_resources(&self, html_content: &str) -> Result<Vec<ResourceLink>> {
+        if html_content.len() > Self::STREAMING_THRESHOLD {
+            return Ok(self.extract_resources_streaming(html_content));
+        }
+        
         let document = Document::from(html_content);
         let mut resources = Vec::new();
         
@@ -50,6 +409,28 @@ impl HtmlParser {
             }
         }
         
+        // Extract preload, prefetch and modulepreload hints, classified by their `as` attribute
+        for link in document.find(Name("link")) {
+            if let (Some(href), Some(rel)) = (link.attr("href"), link.attr("rel")) {
+                if let Some(resource_type) = Self::preload_resource_type(rel, link.attr("as")) {
+                    if let Ok(resource) = self.create_resource_link(href, resource_type) {
+                        resources.push(resource);
+                    }
+                }
+            }
+        }
+        
+        // Extract web app manifests (their icons are fetched when the manifest is processed)
+        for link in document.find(Name("link")) {
+            if let (Some(href), Some(rel)) = (link.attr("href"), link.attr("rel")) {
+                if rel.split_whitespace().any(|r| r.eq_ignore_ascii_case("manifest")) {
+                    if let Ok(resource) = self.create_resource_link(href, ResourceType::Other) {
+                        resources.push(resource);
+                    }
+                }
+            }
+        }
+        
         // Extract JavaScript files
         for script in document.find(Name("script")) {
             if let Some(src) = script.attr("src") {
@@ -68,6 +449,59 @@ impl HtmlParser {
             }
         }
         
+        // Extract JS lazy-loaded images (data-src, data-srcset, ...)
+        for attr in &self.lazy_attrs {
+            for element in document.find(Attr(attr.as_str(), ())) {
+                let Some(value) = element.attr(attr) else { continue };
+                if value.trim_start().starts_with("data:") {
+                    continue;
+                }
+                let urls = if attr.ends_with("srcset") { srcset_urls(value) } else { vec![value.trim()] };
+                for lazy_url in urls {
+                    if let Ok(resource) = self.create_resource_link(lazy_url, ResourceType::Image) {
+                        resources.push(resource);
+                    }
+                }
+            }
+        }
+        
+        // Extract video sources, posters and nested <source> elements
+        for video in document.find(Name("video")) {
+            if let Some(src) = video.attr("src") {
+                if let Ok(resource) = self.create_resource_link(src, ResourceType::Video) {
+                    resources.push(resource);
+                }
+            }
+            if let Some(poster) = video.attr("poster") {
+                if let Ok(resource) = self.create_resource_link(poster, ResourceType::Image) {
+                    resources.push(resource);
+                }
+            }
+            for source in video.find(Name("source")) {
+                if let Some(src) = source.attr("src") {
+                    if let Ok(resource) = self.create_resource_link(src, ResourceType::Video) {
+                        resources.push(resource);
+                    }
+                }
+            }
+        }
+        
+        // Extract audio sources and nested <source> elements
+        for audio in document.find(Name("audio")) {
+            if let Some(src) = audio.attr("src") {
+                if let Ok(resource) = self.create_resource_link(src, ResourceType::Audio) {
+                    resources.push(resource);
+                }
+            }
+            for source in audio.find(Name("source")) {
+                if let Some(src) = source.attr("src") {
+                    if let Ok(resource) = self.create_resource_link(src, ResourceType::Audio) {
+                        resources.push(resource);
+                    }
+                }
+            }
+        }
+        
         // Extract background images from inline styles
         for element in document.find(Attr("style", ())) {
             if let Some(style) = element.attr("style") {
@@ -75,6 +509,15 @@ impl HtmlParser {
             }
         }
         
+        // Extract url() and @import references from <style> blocks
+        for style in document.find(Name("style")) {
+            for reference in css_parser::extract_css_references(&style.text()) {
+                if let Ok(resource) = self.create_resource_link(&reference.url, reference.resource_type()) {
+                    resources.push(resource);
+                }
+            }
+        }
+        
         // Extract background images from CSS files
         for link in document.find(Name("link")) {
             if let Some(href) = link.attr("href") {
@@ -89,10 +532,14 @@ impl HtmlParser {
             }
         }
         
-        // Extract links
+        // Extract links (direct links to audio files, e.g. podcast episodes, are audio resources)
         for link in document.find(Name("a")) {
             if let Some(href) = link.attr("href") {
-                if let Ok(resource) = self.create_resource_link(href, ResourceType::Link) {
+                let resource_type = match ResourceType::from_extension(href) {
+                    ResourceType::Audio => ResourceType::Audio,
+                    _ => ResourceType::Link,
+                };
+                if let Ok(resource) = self.create_resource_link(href, resource_type) {
                     resources.push(resource);
                 }
             }
@@ -100,6 +547,396 @@ impl HtmlParser {
         Ok(resources)
     }
     
+    /// References inside the HTML comments selected by the comment mode, which
+    /// `extract_resources` doesn't see: legacy IE stylesheets and scripts in
+    /// conditional comments and, under `CommentMode::All`, commented-out assets
+    pub fn extract_comment_resources(&self, html_content: &str) -> Vec<ResourceLink> {
+        if self.comment_mode == CommentMode::Ignore {
+            return Vec::new();
+        }
+        let comment_regex = Regex::new(r"(?s)<!--(.*?)-->").unwrap();
+        let mut seen = std::collections::HashSet::new();
+        let mut resources = Vec::new();
+        for comment in comment_regex.captures_iter(html_content) {
+            let Some(markup) = comment_markup(&comment[1], self.comment_mode) else { continue };
+            if !markup.contains('<') {
+                continue;
+            }
+            if let Ok(found) = self.extract_resources(markup) {
+                // `extract_resources` reports each stylesheet `<link>` twice
+                resources.extend(found.into_iter().filter(|resource| seen.insert(resource.original_url.clone())));
+            }
+        }
+        resources
+    }
+    
+    /// `extract_resources` for very large pages: the page is fed to the html5ever
+    /// tokenizer in slices and references are collected from the tag tokens, so
+    /// memory stays flat however big the page is
+    pub fn extract_resources_streaming(&self, html_content: &str) -> Vec<ResourceLink> {
+        let mut tokenizer = Tokenizer::new(ResourceSink::new(self.lazy_attrs.clone()), TokenizerOpts::default());
+        let mut input = BufferQueue::new();
+        let mut rest = html_content;
+        while !rest.is_empty() {
+            let mut end = rest.len().min(Self::STREAMING_CHUNK_SIZE);
+            while !rest.is_char_boundary(end) {
+                end += 1;
+            }
+            let (chunk, remaining) = rest.split_at(end);
+            input.push_back(StrTendril::from_slice(chunk));
+            let _ = tokenizer.feed(&mut input);
+            rest = remaining;
+        }
+        tokenizer.end();
+        let sink = tokenizer.sink;
+        
+        let mut resources: Vec<ResourceLink> = sink
+            .references
+            .iter()
+            .filter_map(|(url, resource_type)| self.create_resource_link(url, resource_type.clone()).ok())
+            .collect();
+        for style in &sink.style_attributes {
+            self.extract_background_images_from_css(style, &mut resources);
+        }
+        for block in &sink.style_blocks {
+            for reference in css_parser::extract_css_references(block) {
+                if let Ok(resource) = self.create_resource_link(&reference.url, reference.resource_type()) {
+                    resources.push(resource);
+                }
+            }
+        }
+        resources
+    }
+    
+    /// Every resource a page and the stylesheets in `css_map` (stylesheet URL ->
+    /// CSS text) reference, deduplicated by URL in order of first appearance and
+    /// with the location of each reference: a standalone entry point for
+    /// analyzers that don't need the downloader
+    pub fn extract_all(&self, html_content: &str, css_map: &HashMap<String, String>) -> Result<Vec<ResourceLink>> {
+        let mut resources: Vec<ResourceLink> = Vec::new();
+        let mut index: HashMap<String, usize> = HashMap::new();
+        for resource in self.extract_resources(html_content)? {
+            if !index.contains_key(&resource.original_url) {
+                index.insert(resource.original_url.clone(), resources.len());
+                resources.push(resource);
+            }
+        }
+        let page_url = self.base_url.to_string();
+        let locate = |resources: &mut Vec<ResourceLink>, absolute_url: &str, location: SourceLocation| {
+            if let Some(&i) = index.get(absolute_url) {
+                resources[i].locations.push(location);
+            }
+        };
+        
+        // References in attributes of start tags
+        let tag_regex = Regex::new(r"(?i)<([a-z][a-z0-9-]*)\b[^>]*>").unwrap();
+        let attribute_regex = Regex::new(r#"(?i)\s([a-z][a-z0-9:_-]*)\s*=\s*(?:"([^"]*)"|'([^']*)'|([^\s"'>]+))"#).unwrap();
+        for tag in tag_regex.captures_iter(html_content) {
+            let tag_match = tag.get(0).unwrap();
+            let element = tag[1].to_ascii_lowercase();
+            for attribute in attribute_regex.captures_iter(tag_match.as_str()) {
+                let name = attribute[1].to_ascii_lowercase();
+                let Some(value) = attribute.get(2).or_else(|| attribute.get(3)).or_else(|| attribute.get(4)) else { continue };
+                let is_lazy = self.lazy_attrs.contains(&name);
+                let references: Vec<(usize, String)> = if name == "style" {
+                    css_reference_offsets(value.as_str()).into_iter().map(|(offset, reference)| (offset, reference.url)).collect()
+                } else if name == "srcset" || (is_lazy && name.ends_with("srcset")) {
+                    srcset_url_offsets(value.as_str()).into_iter().map(|(offset, url)| (offset, url.to_string())).collect()
+                } else if matches!(name.as_str(), "src" | "href" | "poster") || is_lazy {
+                    let trimmed = value.as_str().trim_start();
+                    vec![(value.as_str().len() - trimmed.len(), trimmed.trim_end().to_string())]
+                } else {
+                    continue;
+                };
+                for (offset, reference) in references {
+                    let Ok(absolute_url) = self.resolve_url(&reference.replace("&amp;", "&")) else { continue };
+                    locate(&mut resources, absolute_url.as_str(), SourceLocation {
+                        document: page_url.clone(),
+                        element: element.clone(),
+                        attribute: Some(name.clone()),
+                        offset: tag_match.start() + value.start() + offset,
+                    });
+                }
+            }
+        }
+        
+        // References in <style> blocks
+        let style_regex = Regex::new(r"(?is)<style\b[^>]*>(.*?)</style\s*>").unwrap();
+        for style in style_regex.captures_iter(html_content) {
+            let block = style.get(1).unwrap();
+            for (offset, reference) in css_reference_offsets(block.as_str()) {
+                let Ok(absolute_url) = self.resolve_url(&reference.url) else { continue };
+                locate(&mut resources, absolute_url.as_str(), SourceLocation {
+                    document: page_url.clone(),
+                    element: "style".to_string(),
+                    attribute: None,
+                    offset: block.start() + offset,
+                });
+            }
+        }
+        
+        // References in the linked stylesheets resolve against the stylesheet URL
+        let mut stylesheet_urls: Vec<&String> = css_map.keys().collect();
+        stylesheet_urls.sort();
+        for stylesheet_url in stylesheet_urls {
+            let Ok(stylesheet_parser) = HtmlParser::new(stylesheet_url) else { continue };
+            for (offset, reference) in css_reference_offsets(&css_map[stylesheet_url]) {
+                let Ok(resource) = stylesheet_parser.create_resource_link(&reference.url, reference.resource_type()) else { continue };
+                let i = match index.get(&resource.original_url) {
+                    Some(&i) => i,
+                    None => {
+                        index.insert(resource.original_url.clone(), resources.len());
+                        resources.push(resource);
+                        resources.len() - 1
+                    }
+                };
+                resources[i].locations.push(SourceLocation {
+                    document: stylesheet_url.clone(),
+                    element: if reference.is_import { "@import" } else { "url" }.to_string(),
+                    attribute: None,
+                    offset,
+                });
+            }
+        }
+        Ok(resources)
+    }
+    
+    /// Rewrite the configured lazy-loading attributes to the URLs returned by
+    /// `rewrite` (`None` leaves a URL untouched). With `copy_to_src`, the
+    /// rewritten value is also copied into `src` (or `srcset` for srcset-style
+    /// attributes) so the image shows without the site's JavaScript.
+    pub fn rewrite_lazy_attributes<F>(&self, html_content: &str, copy_to_src: bool, mut rewrite: F) -> String
+    where
+        F: FnMut(&str) -> Option<String>,
+    {
+        if self.lazy_attrs.is_empty() {
+            return html_content.to_string();
+        }
+        
+        let tag_regex = Regex::new(r"<[a-zA-Z][^>]*>").unwrap();
+        let attr_regexes: Vec<(&String, Regex)> = self
+            .lazy_attrs
+            .iter()
+            .map(|attr| {
+                let pattern = format!(r#"(?i)(\s{})\s*=\s*(?:"([^"]*)"|'([^']*)')"#, regex::escape(attr));
+                (attr, Regex::new(&pattern).unwrap())
+            })
+            .collect();
+        
+        tag_regex
+            .replace_all(html_content, |caps: &regex::Captures| {
+                let mut tag = caps[0].to_string();
+                for (attr, attr_regex) in &attr_regexes {
+                    let Some(attr_caps) = attr_regex.captures(&tag) else { continue };
+                    let value = attr_caps.get(2).or_else(|| attr_caps.get(3)).map(|m| m.as_str()).unwrap_or("");
+                    let is_srcset = attr.ends_with("srcset");
+                    let new_value = if is_srcset {
+                        rewrite_srcset(value, &mut rewrite)
+                    } else {
+                        rewrite(value.trim()).unwrap_or_else(|| value.to_string())
+                    };
+                    
+                    let range = attr_caps.get(0).unwrap().range();
+                    let replacement = format!("{}=\"{}\"", &attr_caps[1], new_value);
+                    tag.replace_range(range, &replacement);
+                    
+                    if copy_to_src {
+                        tag = Self::set_attribute(&tag, if is_srcset { "srcset" } else { "src" }, &new_value);
+                    }
+                }
+                tag
+            })
+            .into_owned()
+    }
+    
+    /// The URL of the document's `<base href>`, resolved against the page URL
+    pub fn document_base_url(&self, html_content: &str) -> Option<Url> {
+        let document = Document::from(html_content);
+        let href = document.find(Name("base")).find_map(|base| base.attr("href"))?;
+        self.resolve_url(href.trim()).ok()
+    }
+    
+    /// Origins (`https://fonts.gstatic.com`) the page announces with
+    /// `<link rel="preconnect">` or `<link rel="dns-prefetch">`, in page order
+    pub fn connection_hints(&self, html_content: &str) -> Vec<String> {
+        let document = Document::from(html_content);
+        let mut origins: Vec<String> = Vec::new();
+        for link in document.find(Name("link")) {
+            let (Some(href), Some(rel)) = (link.attr("href"), link.attr("rel")) else { continue };
+            if !rel.split_whitespace().any(|r| r.eq_ignore_ascii_case("preconnect") || r.eq_ignore_ascii_case("dns-prefetch")) {
+                continue;
+            }
+            let Ok(url) = self.resolve_url(href.trim()) else { continue };
+            if !matches!(url.scheme(), "http" | "https") {
+                continue;
+            }
+            let origin = url.origin().ascii_serialization();
+            if !origins.contains(&origin) {
+                origins.push(origin);
+            }
+        }
+        origins
+    }
+    
+    /// Resolve relative URLs against `base_url` instead of the page URL
+    pub fn with_base_url(mut self, base_url: Url) -> Self {
+        self.base_url = base_url;
+        self
+    }
+    
+    /// Remove `<base href>` tags so the saved page's relative paths resolve locally
+    pub fn strip_base_tags(html_content: &str) -> String {
+        let base_regex = Regex::new(r#"(?is)<base\b[^>]*\bhref\s*=[^>]*>"#).unwrap();
+        base_regex.replace_all(html_content, "").into_owned()
+    }
+    
+    /// Rewrite every base-relative `src`, `href`, `poster` and `srcset` URL
+    /// with `rewrite` (`None` leaves a URL untouched)
+    pub fn rewrite_relative_urls<F>(html_content: &str, mut rewrite: F) -> String
+    where
+        F: FnMut(&str) -> Option<String>,
+    {
+        let attr_regex = Regex::new(r#"(?i)(\s(src|href|poster|srcset)\s*=\s*)(?:"([^"]*)"|'([^']*)')"#).unwrap();
+        let mut rewrite_relative = |url: &str| if is_base_relative(url) { rewrite(url.trim()) } else { None };
+        attr_regex
+            .replace_all(html_content, |caps: &regex::Captures| {
+                let value = caps.get(3).or_else(|| caps.get(4)).map(|m| m.as_str()).unwrap_or("");
+                let new_value = if caps[2].eq_ignore_ascii_case("srcset") {
+                    rewrite_srcset(value, &mut rewrite_relative)
+                } else {
+                    rewrite_relative(value).unwrap_or_else(|| value.to_string())
+                };
+                format!("{}\"{}\"", &caps[1], new_value)
+            })
+            .into_owned()
+    }
+    
+    /// Rewrite the `href` of `<link rel="canonical">` and `<link rel="alternate" hreflang>`
+    /// tags with `rewrite` (`None` leaves a link untouched)
+    pub fn rewrite_canonical_links<F>(html_content: &str, mut rewrite: F) -> String
+    where
+        F: FnMut(&str) -> Option<String>,
+    {
+        let link_regex = Regex::new(r"(?i)<link\b[^>]*>").unwrap();
+        link_regex
+            .replace_all(html_content, |caps: &regex::Captures| {
+                let tag = &caps[0];
+                if !Self::is_canonical_link(tag) {
+                    return tag.to_string();
+                }
+                match Self::tag_attribute(tag, "href").and_then(|href| rewrite(href.trim())) {
+                    Some(new_href) => Self::set_attribute(tag, "href", &new_href),
+                    None => tag.to_string(),
+                }
+            })
+            .into_owned()
+    }
+    
+    /// Rewrite the `href` of `<a>` and `<area>` tags with `rewrite` (`None`
+    /// leaves a link untouched)
+    pub fn rewrite_anchor_links<F>(html_content: &str, mut rewrite: F) -> String
+    where
+        F: FnMut(&str) -> Option<String>,
+    {
+        let anchor_regex = Regex::new(r"(?i)<(?:a|area)\b[^>]*>").unwrap();
+        anchor_regex
+            .replace_all(html_content, |caps: &regex::Captures| {
+                let tag = &caps[0];
+                match Self::tag_attribute(tag, "href").and_then(|href| rewrite(href.trim())) {
+                    Some(new_href) => Self::set_attribute(tag, "href", &new_href),
+                    None => tag.to_string(),
+                }
+            })
+            .into_owned()
+    }
+    
+    /// Remove `<link rel="canonical">` and `<link rel="alternate" hreflang>` tags
+    pub fn strip_canonical_links(html_content: &str) -> String {
+        let link_regex = Regex::new(r"(?i)<link\b[^>]*>\s*").unwrap();
+        link_regex
+            .replace_all(html_content, |caps: &regex::Captures| {
+                if Self::is_canonical_link(&caps[0]) { String::new() } else { caps[0].to_string() }
+            })
+            .into_owned()
+    }
+    
+    /// Whether a `<link>` tag is a canonical link or a hreflang alternate
+    /// (other alternates, such as RSS feeds, are left alone)
+    fn is_canonical_link(tag: &str) -> bool {
+        let Some(rel) = Self::tag_attribute(tag, "rel") else { return false };
+        rel.split_whitespace().any(|r| {
+            r.eq_ignore_ascii_case("canonical")
+                || (r.eq_ignore_ascii_case("alternate") && Self::tag_attribute(tag, "hreflang").is_some())
+        })
+    }
+    
+    /// Value of an attribute on a single start tag
+    pub(crate) fn tag_attribute<'a>(tag: &'a str, name: &str) -> Option<&'a str> {
+        let pattern = format!(r#"(?i)\s{}\s*=\s*(?:"([^"]*)"|'([^']*)'|([^\s>]+))"#, regex::escape(name));
+        let attr_regex = Regex::new(&pattern).unwrap();
+        let caps = attr_regex.captures(tag)?;
+        caps.get(1).or_else(|| caps.get(2)).or_else(|| caps.get(3)).map(|m| m.as_str())
+    }
+    
+    /// Set (or add) an attribute on a single start tag
+    pub(crate) fn set_attribute(tag: &str, name: &str, value: &str) -> String {
+        let pattern = format!(r#"(?i)(\s{})\s*=\s*(?:"[^"]*"|'[^']*')"#, regex::escape(name));
+        let attr_regex = Regex::new(&pattern).unwrap();
+        if let Some(existing) = attr_regex.captures(tag) {
+            let mut updated = tag.to_string();
+            updated.replace_range(existing.get(0).unwrap().range(), &format!("{}=\"{}\"", &existing[1], value));
+            return updated;
+        }
+        
+        let insert_at = if tag.ends_with("/>") { tag.len() - 2 } else { tag.len() - 1 };
+        format!("{} {}=\"{}\"{}", tag[..insert_at].trim_end(), name, value, &tag[insert_at..])
+    }
+    
+    /// Remove an attribute (quoted, unquoted or valueless) from a single start tag
+    pub(crate) fn remove_attribute(tag: &str, name: &str) -> String {
+        let pattern = format!(r#"(?i)\s{}(?:\s*=\s*(?:"[^"]*"|'[^']*'|[^\s>]+))?([\s/>])"#, regex::escape(name));
+        let attr_regex = Regex::new(&pattern).unwrap();
+        attr_regex.replace_all(tag, "${1}").into_owned()
+    }
+    
+    /// Lowercased directives from every `<meta name="robots">` tag
+    /// (e.g. `noarchive`, `nosnippet`)
+    pub fn meta_robots_directives(html_content: &str) -> Vec<String> {
+        let document = Document::from(html_content);
+        document
+            .find(Name("meta"))
+            .filter(|meta| meta.attr("name").is_some_and(|name| name.trim().eq_ignore_ascii_case("robots")))
+            .filter_map(|meta| meta.attr("content"))
+            .flat_map(|content| content.split(','))
+            .map(|directive| directive.trim().to_ascii_lowercase())
+            .filter(|directive| !directive.is_empty())
+            .collect()
+    }
+    
+    /// Resource type for a `<link>` resource hint, or `None` if `rel` is not a
+    /// preload/prefetch/modulepreload hint
+    pub fn preload_resource_type(rel: &str, as_attr: Option<&str>) -> Option<ResourceType> {
+        let rels: Vec<String> = rel.split_whitespace().map(|r| r.to_ascii_lowercase()).collect();
+        if rels.iter().any(|r| r == "modulepreload") {
+            return Some(ResourceType::JavaScript);
+        }
+        if !rels.iter().any(|r| r == "preload" || r == "prefetch") {
+            return None;
+        }
+        
+        let as_attr = as_attr.map(|a| a.trim().to_ascii_lowercase());
+        Some(match as_attr.as_deref() {
+            Some("style") => ResourceType::CSS,
+            Some("script") | Some("worker") => ResourceType::JavaScript,
+            Some("image") => ResourceType::Image,
+            Some("video") => ResourceType::Video,
+            Some("audio") => ResourceType::Audio,
+            Some("font") => ResourceType::Font,
+            Some("document") => ResourceType::Link,
+            _ => ResourceType::Other,
+        })
+    }
+    
     fn create_resource_link(&self, url: &str, resource_type: ResourceType) -> Result<ResourceLink> {
         let absolute_url = self.resolve_url(url)?;
         let local_path = self.url_to_local_path(&absolute_url)?;
@@ -108,6 +945,7 @@ impl HtmlParser {
             original_url: absolute_url.to_string(),
             local_path,
             resource_type,
+            locations: Vec::new(),
         })
     }
     
@@ -143,19 +981,39 @@ impl HtmlParser {
             path.push_str("/index.html");
         }
         
-        // Add query parameters if they exist
+        // Each query string gets its own file: `style.css?v=2` -> `style.<hash>.css`
         if let Some(query) = url.query() {
             if !query.is_empty() {
-                path = format!("{}?{}", path, query);
+                path = Self::with_query_hash(&path, query);
             }
         }
         
-        // Sanitize the path for filesystem
-        path = self.sanitize_path(&path);
+        // Sanitize the path for filesystem (including Windows reserved names and
+        // overlong components)
+        path = FileManager::safe_path(&self.sanitize_path(&path));
         
         Ok(path)
     }
     
+    /// Short, filesystem-safe hash identifying a query string
+    pub fn query_hash(query: &str) -> String {
+        FileManager::short_hash(query)
+    }
+    
+    /// Insert the query hash before the file extension (`index.html` ->
+    /// `index.<hash>.html`) so the saved file keeps its type
+    fn with_query_hash(path: &str, query: &str) -> String {
+        let hash = Self::query_hash(query);
+        let file_start = path.rfind('/').map_or(0, |slash| slash + 1);
+        match path[file_start..].rfind('.') {
+            Some(dot) if dot > 0 => {
+                let dot = file_start + dot;
+                format!("{}.{}{}", &path[..dot], hash, &path[dot..])
+            }
+            _ => format!("{}.{}", path, hash),
+        }
+    }
+    
     pub fn sanitize_path(&self, path: &str) -> String {
         path.chars()
             .map(|c| match c {
@@ -187,6 +1045,20 @@ impl HtmlParser {
             }
         }
         
+        // Convert preload, prefetch and modulepreload links
+        for link in document.find(Name("link")) {
+            if let (Some(href), Some(rel)) = (link.attr("href"), link.attr("rel")) {
+                if Self::preload_resource_type(rel, link.attr("as")).is_some() {
+                    if let Ok(local_path) = self.convert_url_to_local(href) {
+                        modified_html = modified_html.replace(
+                            &format!("href=\"{}\"", href),
+                            &format!("href=\"{}\"", local_path)
+                        );
+                    }
+                }
+            }
+        }
+        
         // Convert JavaScript links
         for script in document.find(Name("script")) {
             if let Some(src) = script.attr("src") {
@@ -211,6 +1083,20 @@ impl HtmlParser {
             }
         }
         
+        // Convert video, audio and <source> links, plus video posters
+        for media in document.find(Name("video")).chain(document.find(Name("audio"))).chain(document.find(Name("source"))) {
+            for attr in ["src", "poster"] {
+                if let Some(value) = media.attr(attr) {
+                    if let Ok(local_path) = self.convert_url_to_local(value) {
+                        modified_html = modified_html.replace(
+                            &format!("{}=\"{}\"", attr, value),
+                            &format!("{}=\"{}\"", attr, local_path)
+                        );
+                    }
+                }
+            }
+        }
+        
         // Convert anchor links
         for link in document.find(Name("a")) {
             if let Some(href) = link.attr("href") {
@@ -402,6 +1288,275 @@ mod tests {
         assert!(resources.len() > 0);
     }
 
+    #[test]
+    fn test_extract_video_and_audio_resources() {
+        let html_content = r#"
+            <html>
+                <body>
+                    <video src="/media/intro" poster="/media/intro-poster.jpg"></video>
+                    <video controls>
+                        <source src="/media/clip.webm" type="video/webm">
+                        <source src="/media/clip.mp4" type="video/mp4">
+                    </video>
+                    <audio src="/media/theme"></audio>
+                    <audio controls>
+                        <source src="/media/episode.ogg" type="audio/ogg">
+                    </audio>
+                </body>
+            </html>
+        "#;
+        
+        let parser = HtmlParser::new("https://example.com").unwrap();
+        let resources = parser.extract_resources(html_content).unwrap();
+        
+        let video_count = resources.iter().filter(|r| r.resource_type == ResourceType::Video).count();
+        let audio_count = resources.iter().filter(|r| r.resource_type == ResourceType::Audio).count();
+        let image_count = resources.iter().filter(|r| r.resource_type == ResourceType::Image).count();
+        
+        assert_eq!(video_count, 3);
+        assert_eq!(audio_count, 2);
+        assert_eq!(image_count, 1);
+        assert!(resources.iter().any(|r| r.original_url == "https://example.com/media/intro"));
+        assert!(resources.iter().any(|r| r.original_url == "https://example.com/media/intro-poster.jpg"));
+    }
+
+    #[test]
+    fn test_extract_style_block_resources() {
+        let parser = HtmlParser::new("https://example.com/blog/").unwrap();
+        let html = r#"
+            <html><head><style>
+                @import "theme.css";
+                @font-face { font-family: X; src: url('/fonts/x.woff2'); }
+                .hero { background-image: url(../img/hero.jpg); }
+            </style></head></html>
+        "#;
+
+        let resources = parser.extract_resources(html).unwrap();
+        let find = |url: &str| resources.iter().find(|r| r.original_url == url).map(|r| r.resource_type.clone());
+
+        assert_eq!(find("https://example.com/blog/theme.css"), Some(ResourceType::CSS));
+        assert_eq!(find("https://example.com/fonts/x.woff2"), Some(ResourceType::Font));
+        assert_eq!(find("https://example.com/img/hero.jpg"), Some(ResourceType::Image));
+    }
+
+    #[test]
+    fn test_meta_robots_directives() {
+        let html = r#"<html><head>
+            <meta name="ROBOTS" content="NoArchive, nosnippet">
+            <meta name="description" content="noarchive">
+        </head></html>"#;
+
+        assert_eq!(HtmlParser::meta_robots_directives(html), vec!["noarchive", "nosnippet"]);
+        assert!(HtmlParser::meta_robots_directives("<html></html>").is_empty());
+    }
+
+    #[test]
+    fn test_extract_lazy_loaded_images() {
+        let lazy_attrs = vec!["data-src".to_string(), "data-lazy-srcset".to_string()];
+        let parser = HtmlParser::new("https://example.com/").unwrap().with_lazy_attrs(&lazy_attrs);
+        let html = r#"
+            <img src="placeholder.gif" data-src="/img/photo.jpg">
+            <img data-lazy-srcset="/img/a.jpg 1x, /img/b.jpg 2x">
+            <img data-original="/img/ignored.jpg">
+        "#;
+
+        let resources = parser.extract_resources(html).unwrap();
+        let urls: Vec<&str> = resources.iter().filter(|r| r.resource_type == ResourceType::Image).map(|r| r.original_url.as_str()).collect();
+
+        assert!(urls.contains(&"https://example.com/img/photo.jpg"));
+        assert!(urls.contains(&"https://example.com/img/a.jpg"));
+        assert!(urls.contains(&"https://example.com/img/b.jpg"));
+        assert!(!urls.contains(&"https://example.com/img/ignored.jpg"));
+    }
+
+    #[test]
+    fn test_connection_hints() {
+        let parser = HtmlParser::new("https://example.com/").unwrap();
+        let html = r#"
+            <link rel="preconnect" href="https://fonts.gstatic.com" crossorigin>
+            <link rel="dns-prefetch" href="//cdn.example.net">
+            <link rel="preconnect dns-prefetch" href="https://fonts.gstatic.com/">
+            <link rel="stylesheet" href="https://static.example.org/site.css">
+        "#;
+        assert_eq!(parser.connection_hints(html), vec!["https://fonts.gstatic.com", "https://cdn.example.net"]);
+    }
+
+    #[test]
+    fn test_extract_comment_resources() {
+        let html = r#"
+            <!--[if lt IE 9]><link rel="stylesheet" href="/css/ie8.css"><script src="/js/html5shiv.js"></script><![endif]-->
+            <!--[if !IE]><!--><link rel="stylesheet" href="/css/modern.css"><!--<![endif]-->
+            <!-- <img src="/img/old-banner.gif"> -->
+            <!-- navigation -->
+        "#;
+        let urls = |mode: CommentMode| -> Vec<String> {
+            let parser = HtmlParser::new("https://example.com/").unwrap().with_comment_mode(mode);
+            parser.extract_comment_resources(html).into_iter().map(|r| r.original_url).collect()
+        };
+
+        assert!(urls(CommentMode::Ignore).is_empty());
+        assert_eq!(urls(CommentMode::Conditional), vec!["https://example.com/css/ie8.css", "https://example.com/js/html5shiv.js"]);
+        assert_eq!(
+            urls(CommentMode::All),
+            vec!["https://example.com/css/ie8.css", "https://example.com/js/html5shiv.js", "https://example.com/img/old-banner.gif"]
+        );
+        assert_eq!("conditional".parse::<CommentMode>().unwrap(), CommentMode::Conditional);
+        assert!("some".parse::<CommentMode>().is_err());
+    }
+
+    #[test]
+    fn test_rewrite_lazy_attributes() {
+        let lazy_attrs = vec!["data-src".to_string(), "data-srcset".to_string()];
+        let parser = HtmlParser::new("https://example.com/").unwrap().with_lazy_attrs(&lazy_attrs);
+        let html = r#"<img src="placeholder.gif" data-src="/img/photo.jpg"><img data-srcset="/img/a.jpg 1x, /img/b.jpg 2x" alt="x"/>"#;
+        let rewrite = |url: &str| Some(format!("local{}", url));
+
+        let rewritten = parser.rewrite_lazy_attributes(html, false, rewrite);
+        assert!(rewritten.contains(r#"src="placeholder.gif" data-src="local/img/photo.jpg""#));
+        assert!(rewritten.contains(r#"data-srcset="local/img/a.jpg 1x, local/img/b.jpg 2x""#));
+
+        let copied = parser.rewrite_lazy_attributes(html, true, rewrite);
+        assert!(copied.contains(r#"<img src="local/img/photo.jpg" data-src="local/img/photo.jpg">"#));
+        assert!(copied.contains(r#"alt="x" srcset="local/img/a.jpg 1x, local/img/b.jpg 2x"/>"#));
+    }
+
+    #[test]
+    fn test_extract_web_manifest_link() {
+        let parser = HtmlParser::new("https://example.com/app/").unwrap();
+        let html = r#"<html><head><link rel="manifest" href="site.webmanifest"></head></html>"#;
+
+        let resources = parser.extract_resources(html).unwrap();
+        assert_eq!(resources.len(), 1);
+        assert_eq!(resources[0].original_url, "https://example.com/app/site.webmanifest");
+        assert_eq!(resources[0].resource_type, ResourceType::Other);
+    }
+
+    #[test]
+    fn test_base_href_resolution() {
+        let html = r#"<html><head><base href="https://cdn.example.com/assets/"></head>
+            <body><img src="img/logo.png"></body></html>"#;
+        let parser = HtmlParser::new("https://example.com/blog/post").unwrap();
+        let base_url = parser.document_base_url(html).unwrap();
+        assert_eq!(base_url.as_str(), "https://cdn.example.com/assets/");
+
+        let parser = parser.with_base_url(base_url);
+        let resources = parser.extract_resources(html).unwrap();
+        assert_eq!(resources[0].original_url, "https://cdn.example.com/assets/img/logo.png");
+
+        let stripped = HtmlParser::strip_base_tags(html);
+        assert!(!stripped.contains("<base"));
+        assert!(stripped.contains(r#"<img src="img/logo.png">"#));
+    }
+
+    #[test]
+    fn test_rewrite_relative_urls() {
+        let html = r##"<a href="docs/">Docs</a><a href="#top">Top</a><a href="mailto:a@b.c">Mail</a>
+            <img src="https://example.com/abs.png" srcset="a.png 1x, b.png 2x">"##;
+
+        let rewritten = HtmlParser::rewrite_relative_urls(html, |url| Some(format!("../{}", url)));
+        assert!(rewritten.contains(r#"href="../docs/""#));
+        assert!(rewritten.contains(r##"href="#top""##));
+        assert!(rewritten.contains(r#"href="mailto:a@b.c""#));
+        assert!(rewritten.contains(r#"src="https://example.com/abs.png""#));
+        assert!(rewritten.contains(r#"srcset="../a.png 1x, ../b.png 2x""#));
+    }
+
+    #[test]
+    fn test_rewrite_anchor_links() {
+        let html = r#"<a class="nav" href="/archive/2019/">2019</a><area shape="rect" href='/map/'><link href="/archive/2019/feed.xml"><a name="top">"#;
+        let rewritten = HtmlParser::rewrite_anchor_links(html, |href| href.starts_with("/archive/").then(|| format!("https://example.com{}", href)));
+        assert_eq!(
+            rewritten,
+            r#"<a class="nav" href="https://example.com/archive/2019/">2019</a><area shape="rect" href='/map/'><link href="/archive/2019/feed.xml"><a name="top">"#
+        );
+    }
+
+    #[test]
+    fn test_rewrite_and_strip_canonical_links() {
+        let html = r#"<head>
+<link rel="canonical" href="https://example.com/about/">
+<link rel="alternate" hreflang="de" href="https://example.com/de/about/">
+<link rel="alternate" type="application/rss+xml" href="https://example.com/feed.xml">
+<link rel="stylesheet" href="/style.css">
+</head>"#;
+        
+        let rewritten = HtmlParser::rewrite_canonical_links(html, |href| Some(href.replace("https://example.com/", "local/")));
+        assert!(rewritten.contains(r#"<link rel="canonical" href="local/about/">"#));
+        assert!(rewritten.contains(r#"hreflang="de" href="local/de/about/""#));
+        assert!(rewritten.contains(r#"href="https://example.com/feed.xml""#));
+        assert!(rewritten.contains(r#"href="/style.css""#));
+        
+        let stripped = HtmlParser::strip_canonical_links(html);
+        assert!(!stripped.contains("canonical"));
+        assert!(!stripped.contains("hreflang"));
+        assert!(stripped.contains("feed.xml"));
+        assert!(stripped.contains("style.css"));
+    }
+    
+    #[test]
+    fn test_extract_preload_links() {
+        let html_content = r#"
+            <html>
+                <head>
+                    <link rel="preload" href="/fonts/inter.woff2" as="font" crossorigin>
+                    <link rel="preload" href="/css/critical.css" as="style">
+                    <link rel="prefetch" href="/img/hero.jpg" as="image">
+                    <link rel="modulepreload" href="/js/app.mjs">
+                    <link rel="icon" href="/favicon.ico">
+                </head>
+            </html>
+        "#;
+        
+        let parser = HtmlParser::new("https://example.com").unwrap();
+        let resources = parser.extract_resources(html_content).unwrap();
+        
+        assert_eq!(resources.len(), 4);
+        let find = |url: &str| resources.iter().find(|r| r.original_url == url).unwrap().resource_type.clone();
+        assert_eq!(find("https://example.com/fonts/inter.woff2"), ResourceType::Font);
+        assert_eq!(find("https://example.com/css/critical.css"), ResourceType::CSS);
+        assert_eq!(find("https://example.com/img/hero.jpg"), ResourceType::Image);
+        assert_eq!(find("https://example.com/js/app.mjs"), ResourceType::JavaScript);
+    }
+
+    #[test]
+    fn test_preload_resource_type() {
+        assert_eq!(HtmlParser::preload_resource_type("preload", Some("script")), Some(ResourceType::JavaScript));
+        assert_eq!(HtmlParser::preload_resource_type("Prefetch", Some("document")), Some(ResourceType::Link));
+        assert_eq!(HtmlParser::preload_resource_type("modulepreload", None), Some(ResourceType::JavaScript));
+        assert_eq!(HtmlParser::preload_resource_type("stylesheet", None), None);
+    }
+
+    #[test]
+    fn test_resource_type_from_extension_and_content_type() {
+        assert_eq!(ResourceType::from_extension("/podcast/ep1.MP3?dl=1"), ResourceType::Audio);
+        assert_eq!(ResourceType::from_extension("/media/theme.m4a"), ResourceType::Audio);
+        assert_eq!(ResourceType::from_extension("/v1.2/page"), ResourceType::Other);
+        assert_eq!(ResourceType::from_content_type("audio/mpeg"), Some(ResourceType::Audio));
+        assert_eq!(ResourceType::from_content_type("application/ogg"), Some(ResourceType::Audio));
+        assert_eq!(ResourceType::from_content_type("text/html; charset=utf-8"), Some(ResourceType::Link));
+        assert_eq!(ResourceType::from_content_type("application/octet-stream"), None);
+    }
+
+    #[test]
+    fn test_extract_audio_links() {
+        let parser = HtmlParser::new("https://example.com/podcast/").unwrap();
+        let html = r#"<a href="ep1.mp3">Episode 1</a><a href="ep2/">Episode 2 notes</a>"#;
+
+        let resources = parser.extract_resources(html).unwrap();
+        assert_eq!(resources.len(), 2);
+        assert_eq!(resources[0].resource_type, ResourceType::Audio);
+        assert_eq!(resources[1].resource_type, ResourceType::Link);
+    }
+
+    #[test]
+    fn test_resource_type_filter_name() {
+        assert_eq!(ResourceType::Image.filter_name(), "images");
+        assert_eq!(ResourceType::Video.filter_name(), "video");
+        assert_eq!(ResourceType::Audio.filter_name(), "audio");
+        assert_eq!(ResourceType::Font.filter_name(), "fonts");
+        assert_eq!(ResourceType::Link.filter_name(), "html");
+    }
+
     #[test]
     fn test_url_to_local_path_string_absolute() {
         let parser = HtmlParser::new("https://example.com").unwrap();
@@ -441,7 +1596,30 @@ mod tests {
     fn test_url_to_local_path_string_with_query() {
         let parser = HtmlParser::new("https://example.com").unwrap();
         let result = parser.url_to_local_path_string("https://example.com/page?param=value").unwrap();
-        assert_eq!(result, "page/index.html?param=value");
+        assert_eq!(result, format!("page/index.{}.html", HtmlParser::query_hash("param=value")));
+    }
+
+    #[test]
+    fn test_query_strings_get_distinct_file_names() {
+        let parser = HtmlParser::new("https://example.com").unwrap();
+        let v1 = parser.url_to_local_path_string("https://example.com/css/style.css?v=1").unwrap();
+        let v2 = parser.url_to_local_path_string("https://example.com/css/style.css?v=2").unwrap();
+        
+        assert_eq!(v1, format!("css/style.{}.css", HtmlParser::query_hash("v=1")));
+        assert_ne!(v1, v2);
+        assert_eq!(v1, parser.url_to_local_path_string("https://example.com/css/style.css?v=1#top").unwrap());
+        assert_eq!(HtmlParser::query_hash("v=1").len(), 8);
+        
+        let long_query = format!("q={}", "x".repeat(500));
+        let long = parser.url_to_local_path_string(&format!("https://example.com/search/results.html?{}", long_query)).unwrap();
+        assert_eq!(long, format!("search/results.{}.html", HtmlParser::query_hash(&long_query)));
+    }
+
+    #[test]
+    fn test_url_to_local_path_string_windows_reserved_name() {
+        let parser = HtmlParser::new("https://example.com").unwrap();
+        let result = parser.url_to_local_path_string("https://example.com/aux/con.css").unwrap();
+        assert_eq!(result, "aux_/con_.css");
     }
 
     #[test]
@@ -587,12 +1765,71 @@ mod tests {
         assert!(result.is_err());
     }
 
+    #[test]
+    fn test_extract_resources_streaming_matches_dom() {
+        let parser = HtmlParser::new("https://example.com/").unwrap().with_lazy_attrs(&["data-src".to_string()]);
+        let html = r#"<html><head><link rel="stylesheet" href="/a.css"><link rel="preload" as="font" href="/f.woff2">
+<style>body { background: url(/bg.png) }</style><script>var s = '<img src="/fake.png">';</script><script src="/app.js"></script></head>
+<body style="background-image: url(/inline.png)"><img src="/i.png" data-src="/lazy.png"><video poster="/p.jpg"><source src="/v.mp4"></video>
+<audio><source src="/s.mp3"></audio><a href="/page?a=1&amp;b=2">Page</a><a href="/ep.mp3">Episode</a></body></html>"#;
+        let summarize = |resources: Vec<ResourceLink>| {
+            let mut summary: Vec<(String, ResourceType)> = resources.into_iter().map(|resource| (resource.original_url, resource.resource_type)).collect();
+            summary.sort_by(|a, b| a.0.cmp(&b.0));
+            summary.dedup();
+            summary
+        };
+        
+        let streamed = summarize(parser.extract_resources_streaming(html));
+        assert_eq!(streamed, summarize(parser.extract_resources(html).unwrap()));
+        assert!(streamed.contains(&("https://example.com/page?a=1&b=2".to_string(), ResourceType::Link)));
+        assert!(streamed.contains(&("https://example.com/s.mp3".to_string(), ResourceType::Audio)));
+        assert!(!streamed.iter().any(|(url, _)| url.ends_with("/fake.png")));
+    }
+
+    #[test]
+    fn test_extract_all() {
+        let parser = HtmlParser::new("https://example.com/").unwrap();
+        let html = r#"<html><head><link rel="stylesheet" href="/css/site.css"><style>body { background: url('/img/bg.png'); }</style></head>
+<body><img src="/img/logo.png" alt=""><a href="/about/">About</a> <a href='/about/'>About us</a></body></html>"#;
+        let mut css_map = HashMap::new();
+        css_map.insert("https://example.com/css/site.css".to_string(), "@import url(\"print.css\");\n.hero { background: url(../img/hero.jpg); }".to_string());
+        
+        let resources = parser.extract_all(html, &css_map).unwrap();
+        let find = |url: &str| resources.iter().find(|resource| resource.original_url == url).unwrap();
+        assert_eq!(resources.iter().filter(|resource| resource.original_url == "https://example.com/about/").count(), 1);
+        
+        let about = find("https://example.com/about/");
+        assert_eq!(about.locations.len(), 2);
+        assert_eq!(about.locations[0].element, "a");
+        assert_eq!(about.locations[0].attribute.as_deref(), Some("href"));
+        assert_eq!(about.locations[0].offset, html.find("/about/").unwrap());
+        
+        let logo = find("https://example.com/img/logo.png");
+        assert_eq!(logo.locations, vec![SourceLocation {
+            document: "https://example.com/".to_string(),
+            element: "img".to_string(),
+            attribute: Some("src".to_string()),
+            offset: html.find("/img/logo.png").unwrap(),
+        }]);
+        
+        let background = find("https://example.com/img/bg.png");
+        assert_eq!(background.locations[0].element, "style");
+        assert_eq!(background.locations[0].offset, html.find("/img/bg.png").unwrap());
+        
+        let hero = find("https://example.com/img/hero.jpg");
+        assert_eq!(hero.resource_type, ResourceType::Image);
+        assert_eq!(hero.locations[0].document, "https://example.com/css/site.css");
+        assert_eq!(hero.locations[0].element, "url");
+        assert_eq!(find("https://example.com/css/print.css").locations[0].element, "@import");
+    }
+
     #[test]
     fn test_resource_link_clone() {
         let resource = ResourceLink {
             original_url: "/test.css".to_string(),
             local_path: "/local/test.css".to_string(),
             resource_type: ResourceType::CSS,
+            locations: Vec::new(),
         };
         
         let cloned = resource.clone();
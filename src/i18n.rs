@@ -0,0 +1,249 @@
+use anyhow::{anyhow, Result};
+use std::fmt;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU8, Ordering};
+
+/// Languages user-facing output is available in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Lang {
+    #[default]
+    En,
+    Es,
+    De,
+}
+
+impl FromStr for Lang {
+    type Err = anyhow::Error;
+
+    /// `de`, or a locale such as `de_DE.UTF-8` or `es-MX`
+    fn from_str(lang: &str) -> Result<Self> {
+        let code = lang.split(['_', '-', '.']).next().unwrap_or_default().to_ascii_lowercase();
+        match code.as_str() {
+            "en" | "c" | "posix" => Ok(Lang::En),
+            "es" => Ok(Lang::Es),
+            "de" => Ok(Lang::De),
+            _ => Err(anyhow!("Unsupported language: {} (expected en, es or de)", lang)),
+        }
+    }
+}
+
+impl Lang {
+    /// Language of the environment's locale (`LC_ALL`, `LC_MESSAGES`, `LANG`),
+    /// when it is one output is available in
+    pub fn from_env() -> Option<Lang> {
+        ["LC_ALL", "LC_MESSAGES", "LANG"]
+            .iter()
+            .filter_map(|name| std::env::var(name).ok())
+            .find(|value| !value.is_empty())
+            .and_then(|value| value.parse().ok())
+    }
+}
+
+static LANGUAGE: AtomicU8 = AtomicU8::new(Lang::En as u8);
+
+/// Language of all output for the rest of the process
+pub fn set_language(lang: Lang) {
+    LANGUAGE.store(lang as u8, Ordering::Relaxed);
+}
+
+pub fn language() -> Lang {
+    match LANGUAGE.load(Ordering::Relaxed) {
+        1 => Lang::Es,
+        2 => Lang::De,
+        _ => Lang::En,
+    }
+}
+
+/// User-facing messages with a translation in every `Lang`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Message {
+    Starting,
+    OutputDirectory,
+    MaxDepth,
+    MaxConcurrent,
+    AllDownloadsCompleted,
+    TotalPagesDownloaded,
+    SkippedUrls,
+    CrawlReportWritten,
+    HintSummary,
+    MirrorCompleted,
+    VerifyChecked,
+    MissingFile,
+    DanglingLink,
+    ZeroByteFile,
+    MirrorComplete,
+    NoOrphans,
+    FoundOrphans,
+    DeletedOrphans,
+    RunWithDelete,
+}
+
+impl Message {
+    pub const ALL: [Message; 19] = [
+        Message::Starting,
+        Message::OutputDirectory,
+        Message::MaxDepth,
+        Message::MaxConcurrent,
+        Message::AllDownloadsCompleted,
+        Message::TotalPagesDownloaded,
+        Message::SkippedUrls,
+        Message::CrawlReportWritten,
+        Message::HintSummary,
+        Message::MirrorCompleted,
+        Message::VerifyChecked,
+        Message::MissingFile,
+        Message::DanglingLink,
+        Message::ZeroByteFile,
+        Message::MirrorComplete,
+        Message::NoOrphans,
+        Message::FoundOrphans,
+        Message::DeletedOrphans,
+        Message::RunWithDelete,
+    ];
+
+    /// English, Spanish and German templates; `{0}`, `{1}`, ... are the arguments
+    fn templates(self) -> [&'static str; 3] {
+        match self {
+            Message::Starting => [
+                "🚀 Starting website mirroring for: {0}",
+                "🚀 Iniciando la réplica del sitio web: {0}",
+                "🚀 Spiegelung der Website wird gestartet: {0}",
+            ],
+            Message::OutputDirectory => [
+                "📁 Output directory: {0}",
+                "📁 Directorio de salida: {0}",
+                "📁 Ausgabeverzeichnis: {0}",
+            ],
+            Message::MaxDepth => [
+                "🔗 Max depth: {0}",
+                "🔗 Profundidad máxima: {0}",
+                "🔗 Maximale Tiefe: {0}",
+            ],
+            Message::MaxConcurrent => [
+                "⚡ Max concurrent downloads: {0}",
+                "⚡ Descargas simultáneas máximas: {0}",
+                "⚡ Maximale gleichzeitige Downloads: {0}",
+            ],
+            Message::AllDownloadsCompleted => [
+                "✅ All downloads completed!",
+                "✅ ¡Todas las descargas completadas!",
+                "✅ Alle Downloads abgeschlossen!",
+            ],
+            Message::TotalPagesDownloaded => [
+                "📊 Total pages downloaded: {0}",
+                "📊 Total de páginas descargadas: {0}",
+                "📊 Heruntergeladene Seiten insgesamt: {0}",
+            ],
+            Message::SkippedUrls => [
+                "🚫 Skipped {0} URLs:",
+                "🚫 Se omitieron {0} URL:",
+                "🚫 {0} URLs übersprungen:",
+            ],
+            Message::CrawlReportWritten => [
+                "📊 Crawl report written to {0}",
+                "📊 Informe del rastreo guardado en {0}",
+                "📊 Crawl-Bericht gespeichert in {0}",
+            ],
+            Message::HintSummary => [
+                "💡 {0} URLs: {1}",
+                "💡 {0} URL: {1}",
+                "💡 {0} URLs: {1}",
+            ],
+            Message::MirrorCompleted => [
+                "✅ Website mirroring completed successfully!",
+                "✅ ¡Réplica del sitio web completada con éxito!",
+                "✅ Spiegelung der Website erfolgreich abgeschlossen!",
+            ],
+            Message::VerifyChecked => [
+                "🔍 Checked {0} references in {1} pages and stylesheets",
+                "🔍 Se comprobaron {0} referencias en {1} páginas y hojas de estilo",
+                "🔍 {0} Verweise in {1} Seiten und Stylesheets geprüft",
+            ],
+            Message::MissingFile => [
+                "❌ Missing file: {0} (referenced as {1} in {2})",
+                "❌ Archivo faltante: {0} (referenciado como {1} en {2})",
+                "❌ Fehlende Datei: {0} (als {1} referenziert in {2})",
+            ],
+            Message::DanglingLink => [
+                "❌ Dangling link: {0} (linked as {1} from {2})",
+                "❌ Enlace roto: {0} (enlazado como {1} desde {2})",
+                "❌ Toter Link: {0} (als {1} verlinkt von {2})",
+            ],
+            Message::ZeroByteFile => [
+                "❌ Zero-byte file: {0}",
+                "❌ Archivo vacío: {0}",
+                "❌ Leere Datei: {0}",
+            ],
+            Message::MirrorComplete => [
+                "✅ {0} is complete",
+                "✅ {0} está completo",
+                "✅ {0} ist vollständig",
+            ],
+            Message::NoOrphans => [
+                "🧹 No orphaned files in {0}",
+                "🧹 No hay archivos huérfanos en {0}",
+                "🧹 Keine verwaisten Dateien in {0}",
+            ],
+            Message::FoundOrphans => [
+                "🧹 Found {0} orphaned files ({1} bytes) no mirrored page leads to:",
+                "🧹 Se encontraron {0} archivos huérfanos ({1} bytes) a los que no lleva ninguna página replicada:",
+                "🧹 {0} verwaiste Dateien ({1} Bytes) gefunden, auf die keine gespiegelte Seite verweist:",
+            ],
+            Message::DeletedOrphans => [
+                "🗑️  Deleted {0} orphaned files",
+                "🗑️  Se eliminaron {0} archivos huérfanos",
+                "🗑️  {0} verwaiste Dateien gelöscht",
+            ],
+            Message::RunWithDelete => [
+                "💡 Run again with --delete to remove them",
+                "💡 Vuelva a ejecutar con --delete para eliminarlos",
+                "💡 Erneut mit --delete ausführen, um sie zu löschen",
+            ],
+        }
+    }
+
+    pub fn template(self, lang: Lang) -> &'static str {
+        self.templates()[lang as usize]
+    }
+}
+
+/// A message in the current language with its `{n}` placeholders filled in
+pub fn tr(message: Message, args: &[&dyn fmt::Display]) -> String {
+    let mut text = message.template(language()).to_string();
+    for (index, arg) in args.iter().enumerate() {
+        text = text.replace(&format!("{{{}}}", index), &arg.to_string());
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn placeholders(template: &str) -> Vec<usize> {
+        let mut found: Vec<usize> = (0..10).filter(|index| template.contains(&format!("{{{}}}", index))).collect();
+        found.sort();
+        found
+    }
+
+    #[test]
+    fn test_translations_keep_placeholders() {
+        for message in Message::ALL {
+            let english = placeholders(message.template(Lang::En));
+            for lang in [Lang::Es, Lang::De] {
+                assert_eq!(placeholders(message.template(lang)), english, "{:?} in {:?}", message, lang);
+            }
+        }
+    }
+
+    #[test]
+    fn test_lang_and_tr() {
+        assert_eq!("de_DE.UTF-8".parse::<Lang>().unwrap(), Lang::De);
+        assert_eq!("es-MX".parse::<Lang>().unwrap(), Lang::Es);
+        assert_eq!("C".parse::<Lang>().unwrap(), Lang::En);
+        assert!("fr".parse::<Lang>().is_err());
+
+        assert_eq!(tr(Message::MissingFile, &[&"img/a.png", &"a.png", &"index.html"]), "❌ Missing file: img/a.png (referenced as a.png in index.html)");
+        assert_eq!(Message::MaxDepth.template(Lang::De).replace("{0}", "3"), "🔗 Maximale Tiefe: 3");
+    }
+}
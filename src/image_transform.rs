@@ -0,0 +1,247 @@
+use anyhow::{Result, Context};
+use image::codecs::jpeg::JpegDecoder;
+use image::codecs::png::PngDecoder;
+use image::{ImageDecoder, ImageFormat};
+use std::io::Cursor;
+use tracing::{info, warn};
+
+/// VP8X feature flag signalling that an ICCP chunk is present
+const VP8X_ICC_FLAG: u8 = 0x20;
+
+/// Format-preserving optimizations applied to downloaded images before they
+/// are saved. WebP conversion changes the file's format and path, so it runs
+/// instead of this pipeline rather than as part of it.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ImagePipeline {
+    /// Re-encode JPEGs as progressive at this quality (1-100)
+    pub jpeg_quality: Option<u8>,
+    /// Reduce PNGs to an indexed palette with at most this many colors (2-256)
+    pub png_colors: Option<u16>,
+}
+
+impl ImagePipeline {
+    pub fn is_empty(&self) -> bool {
+        self.jpeg_quality.is_none() && self.png_colors.is_none()
+    }
+
+    /// Run the configured optimizations, returning the new bytes only when
+    /// they are smaller than the original
+    pub fn optimize(&self, image_data: &[u8], original_url: &str, preserve_icc: bool) -> Option<Vec<u8>> {
+        if self.is_empty() {
+            return None;
+        }
+
+        let icc_profile = if preserve_icc { extract_icc_profile(image_data) } else { None };
+        let result = match image::guess_format(image_data).ok()? {
+            ImageFormat::Jpeg => optimize_jpeg(image_data, self.jpeg_quality?, icc_profile.as_deref()),
+            ImageFormat::Png => quantize_png(image_data, self.png_colors?),
+            _ => return None,
+        };
+
+        match result {
+            Ok(optimized) if optimized.len() < image_data.len() => {
+                info!("🗜️  Optimized {}: {} -> {} bytes", original_url, image_data.len(), optimized.len());
+                Some(optimized)
+            }
+            Ok(_) => None,
+            Err(e) => {
+                warn!("⚠️  Failed to optimize image {}: {}", original_url, e);
+                None
+            }
+        }
+    }
+}
+
+/// Re-encode a JPEG as a progressive JPEG with optimized Huffman tables
+pub fn optimize_jpeg(image_data: &[u8], quality: u8, icc_profile: Option<&[u8]>) -> Result<Vec<u8>> {
+    let img = image::load_from_memory_with_format(image_data, ImageFormat::Jpeg)?;
+    let rgb_img = img.to_rgb8();
+    let width = u16::try_from(rgb_img.width()).context("JPEG width exceeds 65535 pixels")?;
+    let height = u16::try_from(rgb_img.height()).context("JPEG height exceeds 65535 pixels")?;
+
+    let mut output = Vec::with_capacity(image_data.len());
+    let mut encoder = jpeg_encoder::Encoder::new(&mut output, quality.clamp(1, 100));
+    encoder.set_progressive(true);
+    encoder.set_optimized_huffman_tables(true);
+    if let Some(icc_profile) = icc_profile {
+        encoder.add_icc_profile(icc_profile)?;
+    }
+    encoder.encode(rgb_img.as_raw(), width, height, jpeg_encoder::ColorType::Rgb)?;
+
+    Ok(output)
+}
+
+/// Reduce a PNG to an indexed palette (pngquant-style) using NeuQuant,
+/// keeping per-entry alpha via a tRNS chunk
+pub fn quantize_png(image_data: &[u8], colors: u16) -> Result<Vec<u8>> {
+    let img = image::load_from_memory_with_format(image_data, ImageFormat::Png)?;
+    let rgba_img = img.to_rgba8();
+    let (width, height) = rgba_img.dimensions();
+    let colors = usize::from(colors.clamp(2, 256));
+
+    let quantizer = color_quant::NeuQuant::new(10, colors, rgba_img.as_raw());
+    let indices: Vec<u8> = rgba_img
+        .as_raw()
+        .chunks_exact(4)
+        .map(|pixel| quantizer.index_of(pixel) as u8)
+        .collect();
+
+    let color_map = quantizer.color_map_rgba();
+    let mut palette = Vec::with_capacity(colors * 3);
+    let mut alpha = Vec::with_capacity(colors);
+    for entry in color_map.chunks_exact(4) {
+        palette.extend_from_slice(&entry[..3]);
+        alpha.push(entry[3]);
+    }
+
+    let mut output = Vec::new();
+    {
+        let mut encoder = png::Encoder::new(&mut output, width, height);
+        encoder.set_color(png::ColorType::Indexed);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder.set_compression(png::Compression::Best);
+        encoder.set_palette(palette);
+        if alpha.iter().any(|&a| a != u8::MAX) {
+            encoder.set_trns(alpha);
+        }
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(&indices)?;
+        writer.finish()?;
+    }
+
+    Ok(output)
+}
+
+/// Read the embedded ICC color profile from a JPEG or PNG image, if any
+pub fn extract_icc_profile(image_data: &[u8]) -> Option<Vec<u8>> {
+    match image::guess_format(image_data).ok()? {
+        ImageFormat::Jpeg => JpegDecoder::new(Cursor::new(image_data)).ok()?.icc_profile(),
+        ImageFormat::Png => PngDecoder::new(Cursor::new(image_data)).ok()?.icc_profile(),
+        _ => None,
+    }
+}
+
+/// Rewrite a simple (single VP8/VP8L chunk) WebP file into the extended
+/// format with an ICCP chunk, so wide-gamut images keep their color space.
+/// Data that is not a simple WebP file is returned unchanged.
+pub fn embed_icc_profile(webp_data: &[u8], icc_profile: &[u8], width: u32, height: u32) -> Vec<u8> {
+    if icc_profile.is_empty()
+        || webp_data.len() < 20
+        || &webp_data[0..4] != b"RIFF"
+        || &webp_data[8..12] != b"WEBP"
+    {
+        return webp_data.to_vec();
+    }
+
+    let image_chunk = &webp_data[12..];
+    let fourcc = &image_chunk[0..4];
+    if fourcc != b"VP8 " && fourcc != b"VP8L" {
+        return webp_data.to_vec();
+    }
+
+    let mut vp8x = Vec::with_capacity(10);
+    vp8x.push(VP8X_ICC_FLAG);
+    vp8x.extend_from_slice(&[0, 0, 0]);
+    vp8x.extend_from_slice(&width.saturating_sub(1).to_le_bytes()[..3]);
+    vp8x.extend_from_slice(&height.saturating_sub(1).to_le_bytes()[..3]);
+
+    let mut body = Vec::with_capacity(webp_data.len() + icc_profile.len() + 32);
+    body.extend_from_slice(b"WEBP");
+    push_chunk(&mut body, b"VP8X", &vp8x);
+    push_chunk(&mut body, b"ICCP", icc_profile);
+    body.extend_from_slice(image_chunk);
+
+    let mut output = Vec::with_capacity(body.len() + 8);
+    output.extend_from_slice(b"RIFF");
+    output.extend_from_slice(&(body.len() as u32).to_le_bytes());
+    output.extend_from_slice(&body);
+    output
+}
+
+fn push_chunk(buffer: &mut Vec<u8>, fourcc: &[u8; 4], payload: &[u8]) {
+    buffer.extend_from_slice(fourcc);
+    buffer.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+    buffer.extend_from_slice(payload);
+    // RIFF chunks are padded to an even size
+    if payload.len() % 2 == 1 {
+        buffer.push(0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn simple_webp() -> Vec<u8> {
+        let payload = [1u8, 2, 3, 4, 5, 6];
+        let mut body = b"WEBP".to_vec();
+        push_chunk(&mut body, b"VP8L", &payload);
+        let mut data = b"RIFF".to_vec();
+        data.extend_from_slice(&(body.len() as u32).to_le_bytes());
+        data.extend_from_slice(&body);
+        data
+    }
+
+    #[test]
+    fn test_embed_icc_profile_creates_extended_webp() {
+        let icc = vec![9u8; 7];
+        let output = embed_icc_profile(&simple_webp(), &icc, 640, 480);
+
+        assert_eq!(&output[0..4], b"RIFF");
+        assert_eq!(&output[8..12], b"WEBP");
+        assert_eq!(&output[12..16], b"VP8X");
+        assert_eq!(output[20] & VP8X_ICC_FLAG, VP8X_ICC_FLAG);
+        assert_eq!(&output[30..34], b"ICCP");
+
+        let riff_size = u32::from_le_bytes([output[4], output[5], output[6], output[7]]) as usize;
+        assert_eq!(riff_size, output.len() - 8);
+        assert!(output.windows(4).any(|w| w == b"VP8L"));
+    }
+
+    #[test]
+    fn test_embed_icc_profile_ignores_non_webp() {
+        let data = b"not a webp file at all".to_vec();
+        assert_eq!(embed_icc_profile(&data, &[1, 2, 3], 1, 1), data);
+    }
+
+    #[test]
+    fn test_image_pipeline_empty_is_noop() {
+        let pipeline = ImagePipeline::default();
+        assert!(pipeline.is_empty());
+        assert_eq!(pipeline.optimize(b"anything", "test.jpg", false), None);
+    }
+
+    #[test]
+    fn test_optimize_jpeg_produces_progressive_jpeg() {
+        let img = image::RgbImage::from_fn(32, 32, |x, y| image::Rgb([(x * 8) as u8, (y * 8) as u8, 128]));
+        let mut source = Vec::new();
+        image::DynamicImage::ImageRgb8(img)
+            .write_to(&mut Cursor::new(&mut source), ImageFormat::Jpeg)
+            .unwrap();
+
+        let optimized = optimize_jpeg(&source, 75, None).unwrap();
+        assert_eq!(&optimized[0..2], &[0xFF, 0xD8]);
+        // SOF2 marks a progressive DCT frame
+        assert!(optimized.windows(2).any(|w| w == [0xFF, 0xC2]));
+    }
+
+    #[test]
+    fn test_quantize_png_produces_indexed_png() {
+        let img = image::RgbaImage::from_fn(64, 64, |x, y| image::Rgba([(x * 4) as u8, (y * 4) as u8, 200, 255]));
+        let mut source = Vec::new();
+        image::DynamicImage::ImageRgba8(img)
+            .write_to(&mut Cursor::new(&mut source), ImageFormat::Png)
+            .unwrap();
+
+        let quantized = quantize_png(&source, 16).unwrap();
+        let decoder = png::Decoder::new(Cursor::new(&quantized));
+        let reader = decoder.read_info().unwrap();
+        assert_eq!(reader.info().color_type, png::ColorType::Indexed);
+        assert!(reader.info().palette.as_ref().unwrap().len() <= 16 * 3);
+    }
+
+    #[test]
+    fn test_extract_icc_profile_without_profile() {
+        assert_eq!(extract_icc_profile(b"not an image"), None);
+    }
+}
@@ -0,0 +1,139 @@
+use regex::{Captures, Regex};
+
+/// Widths kept by `--preset shopify` when `--image-variants` isn't given
+pub const DEFAULT_VARIANT_WIDTHS: &[u32] = &[480, 1024];
+
+/// Shopify size suffix before the extension: `_1024x1024`, `_300x`, `_x300`,
+/// the lazysizes `_{width}x` template, optionally with `_crop_center` and `@2x`
+const SIZE_SUFFIX_PATTERN: &str = r"(?i)_(?:(\d+|\{width\})x(\d*)|x(\d+))(?:_crop_[a-z]+)?(?:@[23]x)?(\.(?:jpe?g|png|gif|webp|avif))";
+/// Newer `/cdn/shop/...?width=533` image URLs
+const WIDTH_PARAM_PATTERN: &str = r"([?&]width=)(\d+)";
+/// Absolute and protocol-relative URLs in markup
+const URL_PATTERN: &str = r#"(?:https?:)?//[^\s"'<>(),]+"#;
+const SRCSET_ATTR_PATTERN: &str = r#"(?i)(\s(?:data-)?srcset\s*=\s*)(?:"([^"]*)"|'([^']*)')"#;
+
+/// Images served by Shopify's CDN, whose size is chosen by the URL
+pub fn is_shopify_image(url: &str) -> bool {
+    url.contains("cdn.shopify.com/") || url.contains("/cdn/shop/")
+}
+
+/// Smallest kept width that is at least `requested`, or the largest kept width
+fn target_width(requested: Option<u32>, widths: &[u32]) -> Option<u32> {
+    let largest = widths.iter().copied().max()?;
+    Some(match requested {
+        Some(requested) => widths.iter().copied().filter(|&width| width >= requested).min().unwrap_or(largest),
+        None => largest,
+    })
+}
+
+/// URL of the kept size variant for a Shopify image URL, or `None` if the
+/// URL doesn't select a size
+pub fn select_variant(url: &str, widths: &[u32]) -> Option<String> {
+    if !is_shopify_image(url) {
+        return None;
+    }
+
+    let suffix_regex = Regex::new(SIZE_SUFFIX_PATTERN).unwrap();
+    if let Some(caps) = suffix_regex.captures(url) {
+        // Height-only (`_x300`) and `{width}` template URLs get the largest kept width
+        let requested = caps.get(1).and_then(|width| width.as_str().parse().ok());
+        let target = target_width(requested, widths)?;
+        let range = caps.get(0)?.range();
+        let mut selected = url.to_string();
+        selected.replace_range(range, &format!("_{}x{}", target, &caps[4]));
+        return Some(selected);
+    }
+
+    let width_regex = Regex::new(WIDTH_PARAM_PATTERN).unwrap();
+    let caps = width_regex.captures(url)?;
+    let target = target_width(caps[2].parse().ok(), widths)?;
+    let range = caps.get(0)?.range();
+    let mut selected = url.to_string();
+    selected.replace_range(range, &format!("{}{}", &caps[1], target));
+    Some(selected)
+}
+
+/// Width a variant URL selects, if any
+fn variant_width(url: &str) -> Option<u32> {
+    let suffix_regex = Regex::new(SIZE_SUFFIX_PATTERN).unwrap();
+    if let Some(caps) = suffix_regex.captures(url) {
+        return caps.get(1)?.as_str().parse().ok();
+    }
+    let width_regex = Regex::new(WIDTH_PARAM_PATTERN).unwrap();
+    width_regex.captures(url)?[2].parse().ok()
+}
+
+/// Point every Shopify image URL in a page at one of the kept size variants,
+/// collapsing `srcset` candidates that end up on the same variant
+pub fn select_image_variants(html_content: &str, widths: &[u32]) -> String {
+    if widths.is_empty() {
+        return html_content.to_string();
+    }
+
+    let url_regex = Regex::new(URL_PATTERN).unwrap();
+    let selected = url_regex.replace_all(html_content, |caps: &Captures| {
+        select_variant(&caps[0], widths).unwrap_or_else(|| caps[0].to_string())
+    });
+
+    let srcset_regex = Regex::new(SRCSET_ATTR_PATTERN).unwrap();
+    srcset_regex
+        .replace_all(&selected, |caps: &Captures| {
+            let srcset = caps.get(2).or_else(|| caps.get(3)).map(|m| m.as_str()).unwrap_or("");
+            format!("{}\"{}\"", &caps[1], dedupe_srcset(srcset))
+        })
+        .into_owned()
+}
+
+/// Keep one candidate per URL; width descriptors of Shopify variants are set
+/// to the width actually served
+fn dedupe_srcset(srcset: &str) -> String {
+    let mut seen: Vec<&str> = Vec::new();
+    let mut candidates = Vec::new();
+    for candidate in srcset.split(',').map(str::trim).filter(|candidate| !candidate.is_empty()) {
+        let (candidate_url, descriptor) = candidate.split_once(char::is_whitespace).unwrap_or((candidate, ""));
+        if seen.contains(&candidate_url) {
+            continue;
+        }
+        seen.push(candidate_url);
+
+        let descriptor = descriptor.trim();
+        let descriptor = match variant_width(candidate_url) {
+            Some(width) if is_shopify_image(candidate_url) && descriptor.ends_with('w') => format!("{}w", width),
+            _ => descriptor.to_string(),
+        };
+        candidates.push(if descriptor.is_empty() { candidate_url.to_string() } else { format!("{} {}", candidate_url, descriptor) });
+    }
+    candidates.join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WIDTHS: &[u32] = &[480, 1024];
+
+    #[test]
+    fn test_select_variant() {
+        let cdn = "//cdn.shopify.com/s/files/1/0001/products/shirt";
+        assert_eq!(select_variant(&format!("{}_300x300_crop_center.jpg?v=1", cdn), WIDTHS), Some(format!("{}_480x.jpg?v=1", cdn)));
+        assert_eq!(select_variant(&format!("{}_600x.png", cdn), WIDTHS), Some(format!("{}_1024x.png", cdn)));
+        assert_eq!(select_variant(&format!("{}_2048x2048@2x.jpg", cdn), WIDTHS), Some(format!("{}_1024x.jpg", cdn)));
+        assert_eq!(select_variant(&format!("{}_{{width}}x.jpg", cdn), WIDTHS), Some(format!("{}_1024x.jpg", cdn)));
+        assert_eq!(select_variant(&format!("{}_x120.jpg", cdn), WIDTHS), Some(format!("{}_1024x.jpg", cdn)));
+        assert_eq!(
+            select_variant("https://shop.example.com/cdn/shop/products/shirt.jpg?v=1&width=533", WIDTHS).as_deref(),
+            Some("https://shop.example.com/cdn/shop/products/shirt.jpg?v=1&width=1024")
+        );
+        assert_eq!(select_variant(&format!("{}.jpg", cdn), WIDTHS), None);
+        assert_eq!(select_variant("https://example.com/banner_800x600.jpg", WIDTHS), None);
+    }
+
+    #[test]
+    fn test_select_image_variants_dedupes_srcset() {
+        let html = r#"<img src="//cdn.shopify.com/s/files/1/products/a_720x.jpg" srcset="//cdn.shopify.com/s/files/1/products/a_360x.jpg 360w, //cdn.shopify.com/s/files/1/products/a_720x.jpg 720w, //cdn.shopify.com/s/files/1/products/a_900x.jpg 900w, //cdn.shopify.com/s/files/1/products/a_1500x.jpg 1500w">"#;
+        let selected = select_image_variants(html, WIDTHS);
+
+        assert!(selected.contains(r#"src="//cdn.shopify.com/s/files/1/products/a_1024x.jpg""#));
+        assert!(selected.contains(r#"srcset="//cdn.shopify.com/s/files/1/products/a_480x.jpg 480w, //cdn.shopify.com/s/files/1/products/a_1024x.jpg 1024w""#));
+    }
+}
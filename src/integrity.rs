@@ -0,0 +1,118 @@
+use anyhow::{anyhow, Result};
+use base64::Engine;
+use regex::{Captures, Regex};
+use sha2::{Digest, Sha384};
+use std::str::FromStr;
+
+use crate::html_parser::HtmlParser;
+
+/// What to do with subresource integrity (`integrity="sha384-..."`) attributes
+/// on stylesheets and scripts whose local copies may differ from the originals
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IntegrityMode {
+    /// Leave the attributes untouched
+    Keep,
+    /// Remove `integrity` and `crossorigin`
+    Strip,
+    /// Replace the hash with one computed over the saved local file
+    Recompute,
+}
+
+impl FromStr for IntegrityMode {
+    type Err = anyhow::Error;
+
+    fn from_str(mode: &str) -> Result<Self> {
+        match mode.to_ascii_lowercase().as_str() {
+            "keep" => Ok(IntegrityMode::Keep),
+            "strip" => Ok(IntegrityMode::Strip),
+            "recompute" => Ok(IntegrityMode::Recompute),
+            _ => Err(anyhow!("Unknown integrity mode: {} (expected keep, strip or recompute)", mode)),
+        }
+    }
+}
+
+/// `<link>` and `<script>` start tags carrying an `integrity` attribute
+const INTEGRITY_TAG_PATTERN: &str = r#"(?is)<(?:link|script)\b[^>]*\sintegrity\s*=[^>]*>"#;
+
+/// Subresource integrity value (`sha384-<base64>`) for a file's contents
+pub fn sri_hash(content: &[u8]) -> String {
+    let digest = Sha384::digest(content);
+    format!("sha384-{}", base64::engine::general_purpose::STANDARD.encode(digest))
+}
+
+/// Apply `mode` to every stylesheet and script with an `integrity` attribute.
+/// For `Recompute`, `read_local` returns the saved file behind an `href`/`src`,
+/// or `None` for references that still point at the origin (left untouched).
+pub fn rewrite_integrity<F>(html_content: &str, mode: IntegrityMode, mut read_local: F) -> String
+where
+    F: FnMut(&str) -> Option<Vec<u8>>,
+{
+    if mode == IntegrityMode::Keep {
+        return html_content.to_string();
+    }
+
+    let tag_regex = Regex::new(INTEGRITY_TAG_PATTERN).unwrap();
+    tag_regex
+        .replace_all(html_content, |caps: &Captures| {
+            let tag = &caps[0];
+            match mode {
+                IntegrityMode::Keep => tag.to_string(),
+                IntegrityMode::Strip => {
+                    let tag = HtmlParser::remove_attribute(tag, "integrity");
+                    HtmlParser::remove_attribute(&tag, "crossorigin")
+                }
+                IntegrityMode::Recompute => {
+                    let reference = HtmlParser::tag_attribute(tag, "href").or_else(|| HtmlParser::tag_attribute(tag, "src"));
+                    match reference.and_then(|reference| read_local(reference.trim())) {
+                        Some(content) => HtmlParser::set_attribute(tag, "integrity", &sri_hash(&content)),
+                        None => tag.to_string(),
+                    }
+                }
+            }
+        })
+        .into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HTML: &str = r#"<head>
+<link rel="stylesheet" href="css/site.css" integrity="sha384-oldhash" crossorigin="anonymous">
+<script src="https://cdn.example.com/lib.js" integrity="sha384-remote" crossorigin="anonymous"></script>
+<script src="js/app.js"></script>
+</head>"#;
+
+    #[test]
+    fn test_integrity_mode_from_str() {
+        assert_eq!("strip".parse::<IntegrityMode>().unwrap(), IntegrityMode::Strip);
+        assert_eq!("Recompute".parse::<IntegrityMode>().unwrap(), IntegrityMode::Recompute);
+        assert!("remove".parse::<IntegrityMode>().is_err());
+    }
+
+    #[test]
+    fn test_sri_hash() {
+        // echo -n "" | openssl dgst -sha384 -binary | base64
+        assert_eq!(sri_hash(b""), "sha384-OLBgp1GsljhM2TJ+sbHjaiH9txEUvgdDTAzHv2P24donTt6/529l+9Ua0vFImLlb");
+    }
+
+    #[test]
+    fn test_strip_integrity() {
+        let stripped = rewrite_integrity(HTML, IntegrityMode::Strip, |_| None);
+        assert!(!stripped.contains("integrity"));
+        assert!(!stripped.contains("crossorigin"));
+        assert!(stripped.contains(r#"<link rel="stylesheet" href="css/site.css">"#));
+        assert!(stripped.contains(r#"<script src="https://cdn.example.com/lib.js"></script>"#));
+    }
+
+    #[test]
+    fn test_recompute_integrity() {
+        let recomputed = rewrite_integrity(HTML, IntegrityMode::Recompute, |reference| {
+            (reference == "css/site.css").then(|| b"body {}".to_vec())
+        });
+        let expected = format!(r#"integrity="{}" crossorigin="anonymous""#, sri_hash(b"body {}"));
+        assert!(recomputed.contains(&expected));
+        assert!(recomputed.contains(r#"integrity="sha384-remote""#));
+        assert!(recomputed.contains(r#"<script src="js/app.js"></script>"#));
+    }
+}
@@ -0,0 +1,219 @@
+use anyhow::{Context, Result};
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Condvar, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Bytes written between pauses when throughput is limited
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// Files written at once by default under `--io-throttle`
+pub const DEFAULT_MAX_OPEN_FILES: usize = 4;
+
+/// Paced, bounded writes for output directories on network shares (SMB/NFS),
+/// which drop or truncate writes when flooded (`--io-throttle`)
+///
+/// Files are written in chunks no faster than `bytes_per_sec`, at most
+/// `max_open_files` at a time, to a `.part` file that is flushed to the share
+/// and renamed into place, so an interrupted write never leaves a truncated file
+#[derive(Debug)]
+pub struct IoThrottle {
+    /// Write throughput limit, `None` for unlimited
+    pub bytes_per_sec: Option<u64>,
+    pub max_open_files: usize,
+    open_files: Mutex<usize>,
+    file_closed: Condvar,
+    /// When the bytes written so far have been paid for at `bytes_per_sec`
+    next_write: Mutex<Instant>,
+}
+
+impl IoThrottle {
+    pub fn new(bytes_per_sec: Option<u64>, max_open_files: usize) -> Self {
+        Self {
+            bytes_per_sec: bytes_per_sec.filter(|rate| *rate > 0),
+            max_open_files: max_open_files.max(1),
+            open_files: Mutex::new(0),
+            file_closed: Condvar::new(),
+            next_write: Mutex::new(Instant::now()),
+        }
+    }
+
+    /// Write `content` to `path`, blocking while too many files are open or the
+    /// throughput budget is spent
+    pub fn write_file(&self, path: &Path, content: &[u8]) -> Result<()> {
+        let mut file = self.create(path)?;
+        file.write_all(content)?;
+        file.finish()
+    }
+
+    /// Open `path` for content written in pieces (a streamed download), paced
+    /// and bounded like `write_file`; nothing appears at `path` until `finish`
+    pub fn create(&self, path: &Path) -> Result<ThrottledFile<'_>> {
+        let slot = self.open_slot();
+        let mut part_name = path.file_name().unwrap_or_default().to_os_string();
+        part_name.push(".part");
+        let part_path = path.with_file_name(part_name);
+        let file = fs::File::create(&part_path)
+            .with_context(|| format!("Failed to create file: {:?}", part_path))?;
+        Ok(ThrottledFile { throttle: self, _slot: slot, file, part_path, path: path.to_path_buf(), finished: false })
+    }
+
+    /// Wait for a free open-file slot, held until the returned guard is dropped
+    fn open_slot(&self) -> OpenFileSlot<'_> {
+        let mut open_files = self.open_files.lock().unwrap();
+        while *open_files >= self.max_open_files {
+            open_files = self.file_closed.wait(open_files).unwrap();
+        }
+        *open_files += 1;
+        OpenFileSlot { throttle: self }
+    }
+
+    /// Sleep until writing `bytes` more stays within `bytes_per_sec`
+    fn pace(&self, bytes: usize) {
+        let Some(rate) = self.bytes_per_sec else {
+            return;
+        };
+        let cost = Duration::from_secs_f64(bytes as f64 / rate as f64);
+        let wait = {
+            let mut next_write = self.next_write.lock().unwrap();
+            let now = Instant::now();
+            let start = (*next_write).max(now);
+            *next_write = start + cost;
+            start - now
+        };
+        if !wait.is_zero() {
+            thread::sleep(wait);
+        }
+    }
+}
+
+// Throttles are compared by their settings, like the rest of `FileManager`
+impl PartialEq for IoThrottle {
+    fn eq(&self, other: &Self) -> bool {
+        self.bytes_per_sec == other.bytes_per_sec && self.max_open_files == other.max_open_files
+    }
+}
+
+impl Eq for IoThrottle {}
+
+impl Hash for IoThrottle {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.bytes_per_sec.hash(state);
+        self.max_open_files.hash(state);
+    }
+}
+
+/// A `.part` file being written through the throttle, removed unless `finish`ed
+pub struct ThrottledFile<'a> {
+    throttle: &'a IoThrottle,
+    _slot: OpenFileSlot<'a>,
+    file: fs::File,
+    part_path: PathBuf,
+    path: PathBuf,
+    finished: bool,
+}
+
+impl ThrottledFile<'_> {
+    pub fn write_all(&mut self, content: &[u8]) -> Result<()> {
+        for chunk in content.chunks(CHUNK_SIZE) {
+            self.throttle.pace(chunk.len());
+            self.file.write_all(chunk)
+                .with_context(|| format!("Failed to write to file: {:?}", self.part_path))?;
+        }
+        Ok(())
+    }
+
+    /// Flush the file to the share and move it into place
+    pub fn finish(mut self) -> Result<()> {
+        self.file.sync_all()
+            .with_context(|| format!("Failed to flush file: {:?}", self.part_path))?;
+        fs::rename(&self.part_path, &self.path)
+            .with_context(|| format!("Failed to move {:?} into place", self.part_path))?;
+        self.finished = true;
+        Ok(())
+    }
+}
+
+impl Drop for ThrottledFile<'_> {
+    fn drop(&mut self) {
+        if !self.finished {
+            let _ = fs::remove_file(&self.part_path);
+        }
+    }
+}
+
+struct OpenFileSlot<'a> {
+    throttle: &'a IoThrottle,
+}
+
+impl Drop for OpenFileSlot<'_> {
+    fn drop(&mut self) {
+        *self.throttle.open_files.lock().unwrap() -= 1;
+        self.throttle.file_closed.notify_one();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_throttled_write() {
+        let temp_dir = tempdir().unwrap();
+        let throttle = IoThrottle::new(Some(1024 * 1024), 2);
+        let path = temp_dir.path().join("video.mp4");
+        let content = vec![7u8; 256 * 1024];
+
+        let started = Instant::now();
+        throttle.write_file(&path, &content).unwrap();
+        // The first 64 KiB chunk is written right away, the other three wait their turn
+        assert!(started.elapsed() >= Duration::from_millis(150));
+        assert_eq!(fs::read(&path).unwrap(), content);
+        assert!(!temp_dir.path().join("video.mp4.part").exists());
+    }
+
+    #[test]
+    fn test_throttled_file_kept_only_when_finished() {
+        let temp_dir = tempdir().unwrap();
+        let throttle = IoThrottle::new(None, 1);
+        let path = temp_dir.path().join("page.html");
+
+        let mut file = throttle.create(&path).unwrap();
+        file.write_all(b"<html>").unwrap();
+        assert!(temp_dir.path().join("page.html.part").exists());
+        drop(file);
+        assert!(!temp_dir.path().join("page.html.part").exists());
+        assert!(!path.exists());
+
+        let mut file = throttle.create(&path).unwrap();
+        file.write_all(b"<html>").unwrap();
+        file.write_all(b"</html>").unwrap();
+        file.finish().unwrap();
+        assert_eq!(fs::read(&path).unwrap(), b"<html></html>");
+        // The open-file slot is released with the file
+        assert_eq!(*throttle.open_files.lock().unwrap(), 0);
+    }
+
+    #[test]
+    fn test_open_file_limit() {
+        let throttle = Arc::new(IoThrottle::new(None, 1));
+        let slot = throttle.open_slot();
+        let waiting = {
+            let throttle = throttle.clone();
+            thread::spawn(move || {
+                let _slot = throttle.open_slot();
+                let open_files = *throttle.open_files.lock().unwrap();
+                open_files
+            })
+        };
+        thread::sleep(Duration::from_millis(50));
+        assert!(!waiting.is_finished());
+        drop(slot);
+        assert_eq!(waiting.join().unwrap(), 1);
+    }
+}
@@ -1,10 +1,75 @@
+pub mod a11y;
+pub mod alerts;
+pub mod archive;
+pub mod assertions;
+pub mod auth;
+pub mod autoindex;
+pub mod bench;
+pub mod broken_links;
+pub mod cdx;
+pub mod circuit_breaker;
 pub mod cli;
+pub mod config;
+pub mod crawl_report;
+pub mod css_parser;
+pub mod dead_assets;
+pub mod doc_generators;
 pub mod downloader;
+pub mod email;
+pub mod embeds;
+pub mod encoding;
+#[cfg(feature = "encryption")]
+pub mod encryption;
+pub mod epub;
+pub mod event_log;
+pub mod explain;
+pub mod external_links;
 pub mod file_manager;
+pub mod fixtures;
+pub mod gallery;
 pub mod html_parser;
+pub mod i18n;
+pub mod image_transform;
+pub mod image_variants;
+pub mod integrity;
+pub mod io_throttle;
+pub mod link_graph;
+pub mod logging;
+pub mod manifest;
+pub mod mhtml;
+pub mod pack;
+pub mod parallel;
+pub mod pii;
+pub mod preset;
+pub mod progress;
+pub mod prune;
+pub mod rate_report;
+pub mod redirects;
+pub mod remediation;
+pub mod review;
+pub mod rewrite_diff;
+pub mod robots;
+pub mod search;
+pub mod sections;
+pub mod single_file;
+pub mod snapshot_diff;
+pub mod ssg;
+pub mod storage;
+pub mod store;
+pub mod svg_parser;
+#[cfg(test)]
+mod test_util;
+pub mod text_diff;
+pub mod url_normalizer;
+pub mod validation;
+pub mod verify;
+pub mod visual_diff;
+pub mod warc;
 
 // Re-export main types for convenience
 pub use cli::MirrorCommand;
 pub use downloader::{WebsiteMirror, DownloadTask, DownloadPriority};
 pub use file_manager::FileManager;
-pub use html_parser::{HtmlParser, ResourceType, ResourceLink}; 
\ No newline at end of file
+pub use html_parser::{HtmlParser, ResourceType, ResourceLink};
+pub use image_transform::ImagePipeline;
+pub use store::{DownloadStore, StoreEntry}; 
\ No newline at end of file
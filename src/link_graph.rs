@@ -0,0 +1,396 @@
+use anyhow::{Result, Context};
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::Path;
+
+use crate::gallery::escape_html;
+use crate::snapshot_diff;
+use crate::store::DownloadStore;
+
+/// Whether a reference leads to a page (`<a href>`) or to an asset of the page
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EdgeKind {
+    Page,
+    Asset,
+}
+
+impl EdgeKind {
+    fn as_str(self) -> &'static str {
+        match self {
+            EdgeKind::Page => "page",
+            EdgeKind::Asset => "asset",
+        }
+    }
+}
+
+/// One reference found while crawling
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Edge {
+    pub from: String,
+    pub to: String,
+    pub kind: EdgeKind,
+    /// Why the crawler didn't follow the reference (`external`, `excluded domain`, ...)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub not_followed: Option<String>,
+}
+
+/// Every page -> page and page -> asset reference seen during a crawl
+#[derive(Debug, Clone, Default)]
+pub struct LinkGraph {
+    edges: BTreeMap<(String, String), (EdgeKind, Option<String>)>,
+}
+
+impl LinkGraph {
+    /// Remember a reference from `from` to `to`, with the reason it isn't
+    /// followed; a reference followed from anywhere counts as followed
+    pub fn record(&mut self, from: &str, to: &str, kind: EdgeKind, not_followed: Option<&str>) {
+        if from == to {
+            return;
+        }
+        let edge = self
+            .edges
+            .entry((from.to_string(), to.to_string()))
+            .or_insert_with(|| (kind, not_followed.map(String::from)));
+        edge.0 = edge.0.min(kind);
+        if not_followed.is_none() {
+            edge.1 = None;
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.edges.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.edges.is_empty()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NodeStatus {
+    Saved,
+    /// Fetched but deliberately not archived (robots noarchive, too large, ...)
+    Skipped,
+    /// Only referenced from links the crawler doesn't follow
+    NotFollowed,
+    /// Queued but never saved: beyond --depth or --max-pages, failed or filtered out
+    NotSaved,
+}
+
+impl NodeStatus {
+    fn as_str(self) -> &'static str {
+        match self {
+            NodeStatus::Saved => "saved",
+            NodeStatus::Skipped => "skipped",
+            NodeStatus::NotFollowed => "not_followed",
+            NodeStatus::NotSaved => "not_saved",
+        }
+    }
+}
+
+/// A URL of the graph and what became of it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Node {
+    pub url: String,
+    pub kind: EdgeKind,
+    pub status: NodeStatus,
+    /// Saved file, relative to the output directory
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub local_path: Option<String>,
+    /// Why it wasn't saved, when known
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+    /// Pages (or stylesheets) referencing it
+    pub inbound: usize,
+    /// References it makes
+    pub outbound: usize,
+    /// A saved page, other than the start page, that no other page links to
+    pub orphan: bool,
+}
+
+/// The link graph of a crawl, as written by `--link-graph`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct GraphReport {
+    pub start: String,
+    pub nodes: Vec<Node>,
+    pub edges: Vec<Edge>,
+    /// URLs of the orphan pages
+    pub orphans: Vec<String>,
+}
+
+impl GraphReport {
+    /// Combine the references recorded during the crawl with what the store knows
+    /// about each URL. Pages saved by earlier runs are included, so pages no
+    /// longer linked from anywhere show up as orphans.
+    pub fn build(graph: &LinkGraph, store: &DownloadStore, start: &str) -> Self {
+        let edges: Vec<Edge> = graph
+            .edges
+            .iter()
+            .map(|((from, to), (kind, not_followed))| Edge {
+                from: from.clone(),
+                to: to.clone(),
+                kind: *kind,
+                not_followed: not_followed.clone(),
+            })
+            .collect();
+
+        let saved = |url: &str| store.entries.get(url).map(|entry| entry.local_path.as_str()).filter(|path| !path.is_empty());
+        let mut urls: BTreeSet<&str> = store.entries.keys().map(String::as_str).filter(|url| saved(url).is_some()).collect();
+        urls.insert(start);
+        for edge in &edges {
+            urls.insert(&edge.from);
+            urls.insert(&edge.to);
+        }
+
+        let mut nodes = Vec::new();
+        for url in urls {
+            let inbound: Vec<&Edge> = edges.iter().filter(|edge| edge.to == url).collect();
+            let outbound = edges.iter().filter(|edge| edge.from == url).count();
+            let local_path = saved(url).map(String::from);
+            let is_page = url == start
+                || inbound.iter().any(|edge| edge.kind == EdgeKind::Page)
+                || local_path.as_deref().is_some_and(snapshot_diff::is_page);
+            let (status, reason) = if local_path.is_some() {
+                (NodeStatus::Saved, None)
+            } else if let Some(reason) = store.skipped.get(url) {
+                (NodeStatus::Skipped, Some(reason.clone()))
+            } else if !inbound.is_empty() && inbound.iter().all(|edge| edge.not_followed.is_some()) {
+                (NodeStatus::NotFollowed, inbound[0].not_followed.clone())
+            } else {
+                (NodeStatus::NotSaved, None)
+            };
+            let orphan = is_page
+                && status == NodeStatus::Saved
+                && url != start
+                && !inbound.iter().any(|edge| edge.kind == EdgeKind::Page);
+            nodes.push(Node {
+                url: url.to_string(),
+                kind: if is_page { EdgeKind::Page } else { EdgeKind::Asset },
+                status,
+                local_path,
+                reason,
+                inbound: inbound.len(),
+                outbound,
+                orphan,
+            });
+        }
+        let orphans = nodes.iter().filter(|node| node.orphan).map(|node| node.url.clone()).collect();
+        Self { start: start.to_string(), nodes, edges, orphans }
+    }
+
+    pub fn pages(&self) -> usize {
+        self.nodes.iter().filter(|node| node.kind == EdgeKind::Page).count()
+    }
+
+    /// Pages that are linked to but weren't saved
+    pub fn unreached_pages(&self) -> usize {
+        self.nodes
+            .iter()
+            .filter(|node| node.kind == EdgeKind::Page && node.status != NodeStatus::Saved)
+            .count()
+    }
+
+    /// Graphviz DOT: pages as boxes and assets as ellipses, the start page
+    /// double-bordered, orphans filled, unsaved URLs grey, and references that
+    /// weren't followed dashed
+    pub fn to_dot(&self) -> String {
+        let quote = |text: &str| format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""));
+        let mut dot = String::from("digraph mirror {\n  rankdir=LR;\n  node [fontsize=10];\n");
+        for node in &self.nodes {
+            let mut attributes = vec![format!("shape={}", if node.kind == EdgeKind::Page { "box" } else { "ellipse" })];
+            if node.url == self.start {
+                attributes.push("peripheries=2".to_string());
+            }
+            if node.orphan {
+                attributes.push("style=filled".to_string());
+                attributes.push("fillcolor=orange".to_string());
+            }
+            if node.status != NodeStatus::Saved {
+                attributes.push("color=gray".to_string());
+                attributes.push("fontcolor=gray".to_string());
+                attributes.push(format!("tooltip={}", quote(node.reason.as_deref().unwrap_or(node.status.as_str()))));
+            }
+            dot.push_str(&format!("  {} [{}];\n", quote(&node.url), attributes.join(", ")));
+        }
+        for edge in &self.edges {
+            let mut attributes = Vec::new();
+            if edge.kind == EdgeKind::Asset {
+                attributes.push("color=gray".to_string());
+            }
+            if let Some(reason) = &edge.not_followed {
+                attributes.push("style=dashed".to_string());
+                attributes.push(format!("label={}", quote(reason)));
+            }
+            let attributes = if attributes.is_empty() { String::new() } else { format!(" [{}]", attributes.join(", ")) };
+            dot.push_str(&format!("  {} -> {}{};\n", quote(&edge.from), quote(&edge.to), attributes));
+        }
+        dot.push_str("}\n");
+        dot
+    }
+
+    /// GraphML (yEd, Gephi, NetworkX) with the node and edge fields as data keys
+    pub fn to_graphml(&self) -> String {
+        let mut xml = String::from(concat!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n",
+            "<graphml xmlns=\"http://graphml.graphdrawing.org/xmlns\">\n",
+            "  <key id=\"url\" for=\"node\" attr.name=\"url\" attr.type=\"string\"/>\n",
+            "  <key id=\"kind\" for=\"all\" attr.name=\"kind\" attr.type=\"string\"/>\n",
+            "  <key id=\"status\" for=\"node\" attr.name=\"status\" attr.type=\"string\"/>\n",
+            "  <key id=\"local_path\" for=\"node\" attr.name=\"local_path\" attr.type=\"string\"/>\n",
+            "  <key id=\"reason\" for=\"all\" attr.name=\"reason\" attr.type=\"string\"/>\n",
+            "  <key id=\"orphan\" for=\"node\" attr.name=\"orphan\" attr.type=\"boolean\"/>\n",
+            "  <key id=\"followed\" for=\"edge\" attr.name=\"followed\" attr.type=\"boolean\"/>\n",
+            "  <graph id=\"mirror\" edgedefault=\"directed\">\n",
+        ));
+        let data = |key: &str, value: &str| format!("<data key=\"{}\">{}</data>", key, escape_html(value));
+        let mut ids = BTreeMap::new();
+        for (index, node) in self.nodes.iter().enumerate() {
+            ids.insert(node.url.as_str(), index);
+            let mut fields = vec![
+                data("url", &node.url),
+                data("kind", node.kind.as_str()),
+                data("status", node.status.as_str()),
+            ];
+            if let Some(local_path) = &node.local_path {
+                fields.push(data("local_path", local_path));
+            }
+            if let Some(reason) = &node.reason {
+                fields.push(data("reason", reason));
+            }
+            fields.push(data("orphan", &node.orphan.to_string()));
+            xml.push_str(&format!("    <node id=\"n{}\">{}</node>\n", index, fields.concat()));
+        }
+        for edge in &self.edges {
+            let mut fields = vec![data("kind", edge.kind.as_str()), data("followed", &edge.not_followed.is_none().to_string())];
+            if let Some(reason) = &edge.not_followed {
+                fields.push(data("reason", reason));
+            }
+            xml.push_str(&format!(
+                "    <edge source=\"n{}\" target=\"n{}\">{}</edge>\n",
+                ids[edge.from.as_str()],
+                ids[edge.to.as_str()],
+                fields.concat()
+            ));
+        }
+        xml.push_str("  </graph>\n</graphml>\n");
+        xml
+    }
+}
+
+/// Output formats of the link graph, chosen by the file's extension
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GraphFormat {
+    Dot,
+    GraphMl,
+    Json,
+}
+
+impl GraphFormat {
+    /// Format implied by a file name: `links.dot` (or `.gv`), `links.graphml`, `links.json`
+    pub fn from_path(path: &Path) -> Option<Self> {
+        match path.extension()?.to_string_lossy().to_ascii_lowercase().as_str() {
+            "dot" | "gv" => Some(GraphFormat::Dot),
+            "graphml" => Some(GraphFormat::GraphMl),
+            "json" => Some(GraphFormat::Json),
+            _ => None,
+        }
+    }
+}
+
+/// Write the graph to `path` in `format`
+pub fn write_graph(report: &GraphReport, path: &Path, format: GraphFormat) -> Result<()> {
+    let data = match format {
+        GraphFormat::Dot => report.to_dot(),
+        GraphFormat::GraphMl => report.to_graphml(),
+        GraphFormat::Json => serde_json::to_string_pretty(report)?,
+    };
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(path, data).with_context(|| format!("Failed to write link graph: {:?}", path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> GraphReport {
+        let mut graph = LinkGraph::default();
+        graph.record("https://example.com/", "https://example.com/about/", EdgeKind::Page, None);
+        graph.record("https://example.com/", "https://example.com/css/site.css", EdgeKind::Asset, None);
+        graph.record("https://example.com/", "https://example.com/private/", EdgeKind::Page, Some("outside include paths"));
+        graph.record("https://example.com/", "https://other.org/", EdgeKind::Page, Some("external"));
+        graph.record("https://example.com/about/", "https://example.com/", EdgeKind::Page, None);
+        graph.record("https://example.com/about/", "https://example.com/deep/", EdgeKind::Page, None);
+        graph.record("https://example.com/about/", "https://example.com/about/", EdgeKind::Page, None);
+        let mut store = DownloadStore::default();
+        store.record_download("https://example.com/", "index.html", Some("text/html"), 10);
+        store.record_download("https://example.com/about/", "about/index.html", Some("text/html"), 10);
+        store.record_download("https://example.com/css/site.css", "css/site.css", Some("text/css"), 10);
+        store.record_download("https://example.com/old/", "old/index.html", Some("text/html"), 10);
+        GraphReport::build(&graph, &store, "https://example.com/")
+    }
+
+    #[test]
+    fn test_record_merges_edges() {
+        let mut graph = LinkGraph::default();
+        graph.record("https://example.com/", "https://example.com/a", EdgeKind::Asset, Some("resource filter"));
+        graph.record("https://example.com/", "https://example.com/a", EdgeKind::Page, None);
+        graph.record("https://example.com/", "https://example.com/", EdgeKind::Page, None);
+        assert_eq!(graph.len(), 1);
+        let report = GraphReport::build(&graph, &DownloadStore::default(), "https://example.com/");
+        assert_eq!(report.edges[0].kind, EdgeKind::Page);
+        assert_eq!(report.edges[0].not_followed, None);
+    }
+
+    #[test]
+    fn test_build_report() {
+        let report = sample();
+        let node = |url: &str| report.nodes.iter().find(|node| node.url == url).unwrap();
+        assert_eq!(report.edges.len(), 6);
+        assert_eq!(report.orphans, vec!["https://example.com/old/".to_string()]);
+        assert_eq!(node("https://example.com/").inbound, 1);
+        assert_eq!(node("https://example.com/").outbound, 4);
+        assert!(!node("https://example.com/").orphan);
+        assert_eq!(node("https://example.com/css/site.css").kind, EdgeKind::Asset);
+        assert_eq!(node("https://example.com/private/").status, NodeStatus::NotFollowed);
+        assert_eq!(node("https://example.com/private/").reason.as_deref(), Some("outside include paths"));
+        assert_eq!(node("https://example.com/deep/").status, NodeStatus::NotSaved);
+        assert_eq!(report.pages(), 6);
+        assert_eq!(report.unreached_pages(), 3);
+    }
+
+    #[test]
+    fn test_to_dot() {
+        let dot = sample().to_dot();
+        assert!(dot.starts_with("digraph mirror {\n"));
+        assert!(dot.contains("  \"https://example.com/\" [shape=box, peripheries=2];\n"));
+        assert!(dot.contains("  \"https://example.com/old/\" [shape=box, style=filled, fillcolor=orange];\n"));
+        assert!(dot.contains("  \"https://example.com/\" -> \"https://example.com/css/site.css\" [color=gray];\n"));
+        assert!(dot.contains("  \"https://example.com/\" -> \"https://other.org/\" [style=dashed, label=\"external\"];\n"));
+        assert!(dot.ends_with("}\n"));
+    }
+
+    #[test]
+    fn test_to_graphml() {
+        let report = sample();
+        let xml = report.to_graphml();
+        assert!(xml.contains("<graph id=\"mirror\" edgedefault=\"directed\">"));
+        assert_eq!(xml.matches("<node ").count(), report.nodes.len());
+        assert_eq!(xml.matches("<edge ").count(), 6);
+        assert!(xml.contains("<data key=\"url\">https://example.com/old/</data><data key=\"kind\">page</data><data key=\"status\">saved</data><data key=\"local_path\">old/index.html</data><data key=\"orphan\">true</data>"));
+        assert!(xml.contains("<data key=\"followed\">false</data><data key=\"reason\">external</data>"));
+    }
+
+    #[test]
+    fn test_graph_format_from_path() {
+        assert_eq!(GraphFormat::from_path(Path::new("links.dot")), Some(GraphFormat::Dot));
+        assert_eq!(GraphFormat::from_path(Path::new("links.GV")), Some(GraphFormat::Dot));
+        assert_eq!(GraphFormat::from_path(Path::new("out/links.graphml")), Some(GraphFormat::GraphMl));
+        assert_eq!(GraphFormat::from_path(Path::new("links.json")), Some(GraphFormat::Json));
+        assert_eq!(GraphFormat::from_path(Path::new("links.txt")), None);
+    }
+}
@@ -0,0 +1,124 @@
+use std::ffi::OsString;
+use std::io::{self, IsTerminal, Write};
+use tracing::{Level, Subscriber};
+use tracing_subscriber::filter::Targets;
+use tracing_subscriber::fmt::writer::{BoxMakeWriter, MakeWriterExt};
+use tracing_subscriber::layer::SubscriberExt;
+
+use crate::progress;
+
+/// Log level of the CLI for `-q`, the default, `-v` and `-vv`
+pub fn level_for(verbose: u8, quiet: bool) -> Level {
+    match (quiet, verbose) {
+        (true, _) => Level::WARN,
+        (false, 0) => Level::INFO,
+        (false, 1) => Level::DEBUG,
+        (false, _) => Level::TRACE,
+    }
+}
+
+/// Install the console subscriber as the process-wide subscriber. Library
+/// users that never install one get no output at all.
+pub fn init(level: Level) {
+    let _ = tracing::subscriber::set_global_default(subscriber(level, false));
+}
+
+/// Like `init`, with every event written to stderr so stdout carries only the
+/// command's result (`--output-format json`)
+pub fn init_stderr(level: Level) {
+    let _ = tracing::subscriber::set_global_default(subscriber(level, true));
+}
+
+/// Events as the crawler always printed them: info and below to stdout,
+/// warnings and errors to stderr, clearing the progress bars around each line.
+/// At debug and trace levels each line is prefixed with the level and the spans
+/// it happened in (`page{url=...}`).
+fn subscriber(max_level: Level, stderr_only: bool) -> impl Subscriber + Send + Sync {
+    let writer = if stderr_only {
+        BoxMakeWriter::new(|| Console::Stderr)
+    } else {
+        BoxMakeWriter::new((|| Console::Stderr).with_max_level(Level::WARN).or_else(|| Console::Stdout))
+    };
+    tracing_subscriber::fmt()
+        .with_max_level(max_level)
+        .with_writer(writer)
+        .without_time()
+        .with_target(false)
+        .with_level(max_level >= Level::DEBUG)
+        .finish()
+        // Only the crate's own events; dependencies (hyper, reqwest) log through tracing too
+        .with(Targets::new().with_target("website_mirror", max_level))
+}
+
+/// A log line's stream, written with the progress bars suspended
+enum Console {
+    Stdout,
+    Stderr,
+}
+
+impl Write for Console {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        progress::suspend(|| match self {
+            Console::Stdout => io::stdout().write(buf),
+            Console::Stderr => io::stderr().write(buf),
+        })
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Console::Stdout => io::stdout().flush(),
+            Console::Stderr => io::stderr().flush(),
+        }
+    }
+}
+
+/// Whether output is colored: not with `--no-color`, when `NO_COLOR` is set to
+/// anything but the empty string (https://no-color.org), or when stdout isn't a
+/// terminal, so cron mails and CI logs get no escape codes
+pub fn color_enabled(no_color: bool) -> bool {
+    wants_color(no_color, std::env::var_os("NO_COLOR"), std::io::stdout().is_terminal())
+}
+
+fn wants_color(no_color: bool, no_color_env: Option<OsString>, terminal: bool) -> bool {
+    let no_color_set = no_color_env.is_some_and(|value| !value.is_empty());
+    !no_color && !no_color_set && terminal
+}
+
+/// Turn colors on or off for everything printed: log lines, subcommand output
+/// and progress bars
+pub fn set_color(enabled: bool) {
+    colored::control::set_override(enabled);
+    console::set_colors_enabled(enabled);
+    console::set_colors_enabled_stderr(enabled);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_level_for() {
+        assert_eq!(level_for(0, false), Level::INFO);
+        assert_eq!(level_for(1, false), Level::DEBUG);
+        assert_eq!(level_for(2, false), Level::TRACE);
+        assert_eq!(level_for(2, true), Level::WARN);
+    }
+
+    #[test]
+    fn test_wants_color() {
+        assert!(wants_color(false, None, true));
+        assert!(wants_color(false, Some(OsString::new()), true));
+        assert!(!wants_color(true, None, true));
+        assert!(!wants_color(false, Some(OsString::from("1")), true));
+        assert!(!wants_color(false, None, false));
+    }
+
+    #[test]
+    fn test_enabled_only_for_crate_events() {
+        tracing::subscriber::with_default(subscriber(Level::INFO, false), || {
+            assert!(tracing::enabled!(Level::INFO));
+            assert!(!tracing::enabled!(Level::DEBUG));
+            assert!(!tracing::enabled!(target: "hyper::proto", Level::INFO));
+        });
+    }
+}
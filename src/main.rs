@@ -1,12 +1,388 @@
 use clap::Parser;
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 
-use website_mirror::{cli::MirrorCommand, downloader::WebsiteMirror};
+use colored::*;
+use std::sync::Arc;
+#[cfg(feature = "encryption")]
+use website_mirror::encryption::{self, Encryption};
+use website_mirror::{alerts::AlertRule, archive::{self, ArchiveFormat}, assertions::CrawlAssertion, bench, cdx, cli::{BenchCommand, CleanCommand, ConfigAction, ConfigCommand, DiffCommand, ExportCommand, ExportFormat, ExtractCommand, ImportWarcCommand, MirrorCommand, SearchCommand, VerifyCommand}, config::{self, MergedArgs, Severity}, downloader::WebsiteMirror, email, embeds, epub, explain, fixtures::{FixtureServer, FixtureSet}, i18n::{self, tr, Lang, Message}, io_throttle::{self, IoThrottle}, logging, link_graph::GraphFormat, mhtml, pack, pii::PiiScrubber, preset::Preset, progress, prune, review::Reviewer, search, sections::OutputSection, single_file, snapshot_diff, ssg, storage::{S3Location, S3Storage}, store::DownloadStore, text_diff::TextChange, verify, warc::WarcArchive};
 
-#[tokio::main]
-async fn main() -> Result<()> {
-    let args = MirrorCommand::parse();
+/// `website-mirror search <dir> <query>`
+fn run_search(args: SearchCommand) -> Result<()> {
+    logging::set_color(logging::color_enabled(args.no_color));
+    let hits = search::search(&args.dir, &args.query, args.limit)?;
+    if hits.is_empty() {
+        println!("No pages match \"{}\"", args.query);
+        return Ok(());
+    }
+    for hit in &hits {
+        let title = if hit.title.is_empty() { hit.url.as_str() } else { hit.title.as_str() };
+        println!("{} {}", title.bold(), format!("({:.2})", hit.score).dimmed());
+        println!("   {} -> {}", hit.url.cyan(), args.dir.join(&hit.local_path).display());
+        if !hit.snippet.is_empty() {
+            println!("   {}", hit.snippet);
+        }
+    }
+    Ok(())
+}
+
+/// `website-mirror export compact|archive|mhtml|single-file|epub|cdx <dir> <output>`
+/// `website-mirror export ssg <dir> <out-dir> --format hugo|jekyll`
+/// `website-mirror export email <dir> <url> <output>`
+fn run_export(args: ExportCommand) -> Result<()> {
+    #[cfg(feature = "encryption")]
+    let encryption = args.encrypt.as_deref().map(str::parse::<Encryption>).transpose()?;
+    #[cfg(feature = "encryption")]
+    if encryption.is_some() && !matches!(args.format, ExportFormat::Compact { .. } | ExportFormat::Archive { .. }) {
+        return Err(anyhow!("--encrypt is only supported by export compact and export archive"));
+    }
+    match args.format {
+        ExportFormat::Compact { dir, pack } => {
+            #[cfg(feature = "encryption")]
+            let (stats, pack_path, index_path) = match &encryption {
+                Some(encryption) => {
+                    let stats = pack::compact_encrypted(&dir, &pack, encryption)?;
+                    (stats, encryption::encrypted_path(&pack), encryption::encrypted_path(&pack::index_path(&pack)))
+                }
+                None => (pack::compact(&dir, &pack)?, pack.clone(), pack::index_path(&pack)),
+            };
+            #[cfg(not(feature = "encryption"))]
+            let (stats, pack_path, index_path) = (pack::compact(&dir, &pack)?, pack.clone(), pack::index_path(&pack));
+            println!(
+                "📦 Packed {} files ({} distinct, {:.1} MB -> {:.1} MB) into {} and {}",
+                stats.files,
+                stats.objects,
+                stats.total_bytes as f64 / 1_048_576.0,
+                stats.packed_bytes as f64 / 1_048_576.0,
+                pack_path.display(),
+                index_path.display()
+            );
+        }
+        ExportFormat::Archive { dir, archive, format } => {
+            let format = match format {
+                Some(format) => format.parse()?,
+                None => ArchiveFormat::from_path(&archive)
+                    .ok_or_else(|| anyhow!("Can't tell the format of {} from its extension; pass --format zip or --format tar.gz", archive.display()))?,
+            };
+            #[cfg(feature = "encryption")]
+            let (index, archive) = match &encryption {
+                Some(encryption) => (archive::export_archive_encrypted(&dir, &archive, format, encryption)?, encryption::encrypted_path(&archive)),
+                None => (archive::export_archive(&dir, &archive, format)?, archive),
+            };
+            #[cfg(not(feature = "encryption"))]
+            let index = archive::export_archive(&dir, &archive, format)?;
+            println!(
+                "📦 Archived {} files ({:.1} MB) into {}",
+                index.files.len(),
+                index.total_bytes as f64 / 1_048_576.0,
+                archive.display()
+            );
+        }
+        ExportFormat::Mhtml { dir, out_dir } => {
+            let stats = mhtml::export_mhtml(&dir, &out_dir)?;
+            println!(
+                "🗂️  Wrote {} pages with {} embedded resources ({:.1} MB) as MHTML into {}",
+                stats.pages,
+                stats.resources,
+                stats.total_bytes as f64 / 1_048_576.0,
+                out_dir.display()
+            );
+        }
+        ExportFormat::SingleFile { dir, out_dir } => {
+            let stats = single_file::export_single_files(&dir, &out_dir)?;
+            println!(
+                "🗂️  Wrote {} pages ({:.1} MB) as single-file HTML into {}",
+                stats.pages,
+                stats.total_bytes as f64 / 1_048_576.0,
+                out_dir.display()
+            );
+        }
+        ExportFormat::Epub { dir, epub, title } => {
+            let stats = epub::export_epub(&dir, &epub, title.as_deref())?;
+            println!(
+                "📚 Wrote {} chapters with {} embedded resources ({:.1} MB) into {}",
+                stats.chapters,
+                stats.resources,
+                stats.total_bytes as f64 / 1_048_576.0,
+                epub.display()
+            );
+        }
+        ExportFormat::Cdx { dir, index } => {
+            let records = cdx::write_index(&dir, &index)?;
+            println!("🗃️  Indexed {} captures into {}", records, index.display());
+        }
+        ExportFormat::Ssg { dir, out_dir, format } => {
+            let stats = ssg::export_ssg(&dir, &out_dir, format.parse()?)?;
+            println!(
+                "🏗️  Wrote {} pages and {} assets ({:.1} MB) as a {} site into {}",
+                stats.pages,
+                stats.assets,
+                stats.total_bytes as f64 / 1_048_576.0,
+                format,
+                out_dir.display()
+            );
+        }
+        ExportFormat::Email { dir, url, output, remote_images } => {
+            let stats = email::export_email(&dir, &url, &output, remote_images)?;
+            println!(
+                "📧 Wrote {} ({} styled elements, {} images, {:.1} KB) to {}",
+                url,
+                stats.styled_elements,
+                stats.images,
+                stats.total_bytes as f64 / 1024.0,
+                output.display()
+            );
+        }
+    }
+    Ok(())
+}
+
+/// `website-mirror extract <pack> <dir>`
+fn run_extract(args: ExtractCommand) -> Result<()> {
+    #[cfg(feature = "encryption")]
+    let files = match &args.identity {
+        Some(identity) => pack::extract_encrypted(&args.pack, identity, &args.dir)?,
+        None if encryption::decrypted_path(&args.pack).is_some() => {
+            return Err(anyhow!("{} is encrypted; pass --identity <FILE>", args.pack.display()));
+        }
+        None => pack::extract(&args.pack, &args.dir)?,
+    };
+    #[cfg(not(feature = "encryption"))]
+    let files = pack::extract(&args.pack, &args.dir)?;
+    println!("📂 Extracted {} files into {}", files, args.dir.display());
+    Ok(())
+}
+
+/// Parse a command line merged with the config file and environment
+/// (`config::merge_args`), pointing parse errors at the file or variable
+/// that set the offending option
+fn parse_merged<C: Parser>(args: Vec<String>, leading: usize) -> Result<(C, MergedArgs)> {
+    let leading = leading.min(args.len());
+    let merged = config::merge_args(&args[..leading], &args[leading..], std::env::vars())?;
+    match C::try_parse_from(&merged.args) {
+        Ok(command) => Ok((command, merged)),
+        Err(e) => match merged.explain_error(&e) {
+            Some(explained) => {
+                eprintln!("{}", explained);
+                std::process::exit(e.exit_code());
+            }
+            None => e.exit(),
+        },
+    }
+}
+
+/// `website-mirror config check <url> [options]`
+fn run_config(args: ConfigCommand, merged: &MergedArgs) -> Result<()> {
+    match args.action {
+        ConfigAction::Check(mirror_args) => {
+            let mirror = build_mirror(&mirror_args)?;
+            let issues = config::check_config(&mirror);
+            print!("{}", config::format_config(&mirror, merged));
+            println!();
+            if issues.is_empty() {
+                println!("✅ No conflicting or invalid options");
+            }
+            for issue in &issues {
+                println!("{}", config::format_issue(issue, merged));
+            }
+            let errors = issues.iter().filter(|issue| issue.severity == Severity::Error).count();
+            if errors > 0 {
+                return Err(anyhow!("{} invalid option(s)", errors));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// `+1.5 KB`, `-200 B`
+fn format_delta(bytes: i64) -> String {
+    let sign = if bytes < 0 { "-" } else { "+" };
+    let magnitude = bytes.unsigned_abs();
+    if magnitude < 1024 {
+        format!("{}{} B", sign, magnitude)
+    } else if magnitude < 1_048_576 {
+        format!("{}{:.1} KB", sign, magnitude as f64 / 1024.0)
+    } else {
+        format!("{}{:.1} MB", sign, magnitude as f64 / 1_048_576.0)
+    }
+}
+
+/// `website-mirror diff <old> <new>`
+fn run_diff(args: DiffCommand) -> Result<()> {
+    logging::set_color(logging::color_enabled(args.no_color));
+    let diff = snapshot_diff::diff_snapshots(&args.old_dir, &args.new_dir)?;
+    println!(
+        "📊 {} -> {}: {} added, {} removed, {} changed, {} unchanged ({})",
+        args.old_dir.display(),
+        args.new_dir.display(),
+        diff.added.len(),
+        diff.removed.len(),
+        diff.changed.len(),
+        diff.unchanged,
+        format_delta(diff.byte_delta())
+    );
+
+    for (heading, pages) in [("Pages", true), ("Assets", false)] {
+        let added: Vec<_> = diff.added.iter().filter(|file| snapshot_diff::is_page(&file.path) == pages).collect();
+        let removed: Vec<_> = diff.removed.iter().filter(|file| snapshot_diff::is_page(&file.path) == pages).collect();
+        let changed: Vec<_> = diff.changed.iter().filter(|file| snapshot_diff::is_page(&file.path) == pages).collect();
+        if added.is_empty() && removed.is_empty() && changed.is_empty() {
+            continue;
+        }
+        println!();
+        println!("{}", heading.bold());
+        for file in added {
+            println!("   {} {} ({})", "+".green(), file.path, format_delta(file.size as i64));
+        }
+        for file in removed {
+            println!("   {} {} ({})", "-".red(), file.path, format_delta(-(file.size as i64)));
+        }
+        for file in changed {
+            println!("   {} {} ({})", "~".yellow(), file.path, format_delta(file.byte_delta()));
+            for change in file.text_changes.iter().take(args.text_blocks) {
+                match change {
+                    TextChange::Added(text) => println!("      {} {}", "+".green(), text),
+                    TextChange::Removed(text) => println!("      {} {}", "-".red(), text),
+                }
+            }
+            if args.text_blocks > 0 && file.text_changes.len() > args.text_blocks {
+                println!("      ... {} more text changes", file.text_changes.len() - args.text_blocks);
+            }
+        }
+    }
+    if diff.is_empty() {
+        println!("✅ No differences");
+    }
+    Ok(())
+}
+
+/// Output language: `--lang`, else the environment's locale, else English
+fn select_language(lang: Option<&str>) -> Result<()> {
+    let lang = match lang {
+        Some(lang) => lang.parse()?,
+        None => Lang::from_env().unwrap_or_default(),
+    };
+    i18n::set_language(lang);
+    Ok(())
+}
+
+/// `website-mirror bench <dir>`
+fn run_bench(args: BenchCommand) -> Result<()> {
+    let report = bench::run_bench(&args.dir, &args.base_url, args.iterations as usize)?;
+    print!("{}", bench::format_report(&report));
+    if let Some(output) = &args.output {
+        bench::save_report(&report, output)?;
+        println!("💾 Timings written to {}", output.display());
+    }
+    if let Some(baseline) = &args.baseline {
+        let changes = bench::compare(&report, &bench::load_report(baseline)?);
+        println!("\nAgainst {}:", baseline.display());
+        let mut regressions = Vec::new();
+        for change in &changes {
+            let regressed = change.change_percent > args.max_regression;
+            println!(
+                "{} {:<10} {:>10.1} µs -> {:>10.1} µs ({:+.1}%)",
+                if regressed { "❌" } else { "✅" },
+                change.stage,
+                change.baseline_us,
+                change.current_us,
+                change.change_percent
+            );
+            if regressed {
+                regressions.push(change.stage.as_str());
+            }
+        }
+        if !regressions.is_empty() {
+            return Err(anyhow!("{} slower than the baseline by more than {}%", regressions.join(", "), args.max_regression));
+        }
+    }
+    Ok(())
+}
+
+/// `website-mirror verify <dir>`
+fn run_verify(args: VerifyCommand) -> Result<()> {
+    select_language(args.lang.as_deref())?;
+    let report = verify::verify_mirror(&args.dir, args.link_root.as_deref())?;
+    println!("{}", tr(Message::VerifyChecked, &[&report.references_checked, &report.documents_checked]));
+    for missing in &report.missing_files {
+        println!("{}", tr(Message::MissingFile, &[&missing.target, &missing.reference, &missing.document]));
+    }
+    for missing in &report.dangling_links {
+        println!("{}", tr(Message::DanglingLink, &[&missing.target, &missing.reference, &missing.document]));
+    }
+    for file in &report.zero_byte_files {
+        println!("{}", tr(Message::ZeroByteFile, &[file]));
+    }
+    if report.pages_with_parse_errors > 0 {
+        println!("⚠️  {} pages have HTML parse errors (run with --validate for details)", report.pages_with_parse_errors);
+    }
+    if !report.is_ok() {
+        return Err(anyhow!(
+            "{} missing files, {} dangling links, {} zero-byte files",
+            report.missing_files.len(),
+            report.dangling_links.len(),
+            report.zero_byte_files.len()
+        ));
+    }
+    println!("{}", tr(Message::MirrorComplete, &[&args.dir.display()]));
+    Ok(())
+}
+
+fn run_clean(args: CleanCommand) -> Result<()> {
+    logging::set_color(logging::color_enabled(args.no_color));
+    select_language(args.lang.as_deref())?;
+    let mut store = DownloadStore::load(&args.dir)?;
+    let orphans = prune::find_orphaned_files(&args.dir, &store, args.link_root.as_deref())?;
+    if orphans.is_empty() {
+        println!("{}", tr(Message::NoOrphans, &[&args.dir.display()]));
+        return Ok(());
+    }
+    let total_size: u64 = orphans.iter().map(|orphan| orphan.size).sum();
+    println!("{}", tr(Message::FoundOrphans, &[&orphans.len(), &total_size]));
+    for orphan in &orphans {
+        match &orphan.url {
+            Some(url) => println!("   {} ({})", orphan.local_path.yellow(), url),
+            None => println!("   {}", orphan.local_path.yellow()),
+        }
+    }
+    if args.delete {
+        prune::delete_orphaned_files(&args.dir, &mut store, &orphans)?;
+        store.save(&args.dir)?;
+        println!("{}", tr(Message::DeletedOrphans, &[&orphans.len()]));
+    } else {
+        println!("{}", tr(Message::RunWithDelete, &[]));
+    }
+    Ok(())
+}
+
+/// `website-mirror import-warc <file> -o <dir>`
+async fn run_import_warc(args: ImportWarcCommand) -> Result<()> {
+    logging::init(tracing::Level::INFO);
+    let archive = WarcArchive::load(&args.warc)?;
+    println!("📼 Read {} captured responses from {}", archive.len(), args.warc.display());
+    for skipped in &archive.skipped {
+        eprintln!("⚠️  Not importing {}", skipped);
+    }
+    let start_url = match args.url {
+        Some(url) => url,
+        None => archive
+            .pages()
+            .next()
+            .map(|page| page.to_string())
+            .ok_or_else(|| anyhow!("{} contains no HTML pages to start from", args.warc.display()))?,
+    };
+    
+    // robots.txt was the capturing crawler's concern; everything captured is imported
+    let mut mirror = WebsiteMirror::new(&start_url, &args.output_dir, 0, 10, true, true, None, false)?;
+    mirror.replay = Some(Arc::new(archive));
+    mirror.mirror_website().await?;
     
+    println!("✅ Imported {} into {}", args.warc.display(), args.output_dir.display());
+    Ok(())
+}
+
+/// The mirror a run with these options would use, with presets and `--full-mirror` applied
+fn build_mirror(args: &MirrorCommand) -> Result<WebsiteMirror> {
     // Handle full mirror option
     let (max_depth, max_concurrent, ignore_robots, download_external) = if args.full_mirror {
         // Full mirror: unlimited depth crawling of target site + all media files from any site
@@ -16,20 +392,186 @@ async fn main() -> Result<()> {
         (args.max_depth, args.max_concurrent, args.ignore_robots, true)
     };
     
+    // An s3:// output is mirrored into a local staging directory, then published
+    let (output_dir, publish_target) = match S3Location::parse(&args.output_dir.to_string_lossy()) {
+        Some(location) => {
+            let location = location?;
+            let staging_dir = args.s3_staging_dir.clone().unwrap_or_else(|| location.default_staging_dir());
+            (staging_dir, Some(S3Storage::from_env(location, args.s3_endpoint.clone())?))
+        }
+        None => (args.output_dir.clone(), None),
+    };
+    
                 let mut mirror = WebsiteMirror::new(
                 &args.url,
-                &args.output_dir,
+                &output_dir,
                 max_depth,
                 max_concurrent,
                 ignore_robots,
                 download_external,
                 args.only_resources.clone(),
-                args.convert_to_webp,
+                args.convert_to_webp || args.convert_formats.is_some(),
             )?;
     
+    mirror.preserve_icc = args.preserve_icc;
+    mirror.image_pipeline.jpeg_quality = args.optimize_jpeg;
+    mirror.image_pipeline.png_colors = args.quantize_png;
+    mirror.generate_gallery = args.gallery;
+    mirror.report_dead_assets = args.report_dead_assets;
+    mirror.delete_dead_assets = args.delete_dead_assets;
+    mirror.prune = args.prune;
+    mirror.respect_noarchive = args.respect_noarchive;
+    mirror.token_command = args.token_command.clone();
+    mirror.lazy_copy_src = args.lazy_copy_src;
+    mirror.keep_alive_url = args.keep_alive_url.clone();
+    mirror.keep_alive_interval = args.keep_alive_interval;
+    mirror.circuit_breaker_threshold = args.circuit_breaker_threshold;
+    mirror.differential_min_size = args.differential_min_mb.map(|mb| mb * 1024 * 1024);
+    mirror.rewrite_canonical = args.rewrite_canonical;
+    mirror.strip_canonical = args.strip_canonical;
+    mirror.integrity_mode = args.integrity.parse()?;
+    mirror.comment_mode = args.comment_resources.parse()?;
+    mirror.preserve_encoding = args.preserve_encoding;
+    mirror.fix_charset = args.fix_charset;
+    
+    if let Some(lazy_attrs) = &args.lazy_attrs {
+        mirror.lazy_attrs = lazy_attrs.clone();
+    }
+    mirror.rewrite_host = args.rewrite_host.clone();
+    mirror.link_base = args.link_base.clone();
+    mirror.strip_tracking_params = args.strip_tracking_params;
+    mirror.redirect_stubs = args.redirect_stubs;
+    mirror.external_host_dirs = args.external_host_dirs;
+    mirror.case_insensitive_names = args.case_insensitive_names;
+    mirror.validate = args.validate;
+    mirror.validate_strict = args.validate_strict;
+    mirror.check_external_links = args.check_external_links;
+    mirror.screenshot_command = args.screenshot_command.clone();
+    mirror.visual_diff_threshold = args.visual_diff_threshold;
+    mirror.text_diff = args.text_diff;
+    mirror.alert_rules = args.alert_on.iter().map(|pattern| pattern.parse::<AlertRule>()).collect::<Result<_>>()?;
+    mirror.alert_webhook = args.alert_webhook.clone();
+    mirror.search_index = args.search_index;
+    mirror.max_total_size = args.max_total_size;
+    mirror.max_file_size = args.max_file_size;
+    mirror.max_html_size = args.max_html_size;
+    if args.io_throttle.is_some() || args.io_max_open_files.is_some() {
+        let max_open_files = args.io_max_open_files.unwrap_or(io_throttle::DEFAULT_MAX_OPEN_FILES);
+        mirror = mirror.with_io_throttle(IoThrottle::new(args.io_throttle, max_open_files));
+    }
+    mirror.max_pages = args.max_pages;
+    if args.scrub_pii || !args.pii_pattern.is_empty() {
+        mirror.pii_scrubber = Some(PiiScrubber::new(&args.pii_pattern)?);
+    }
+    mirror.rate_report = args.rate_report;
+    mirror.html_report = args.html_report;
+    mirror.a11y_audit = args.a11y_audit;
+    mirror.cdx_index = args.cdx;
+    if let Some(link_graph) = &args.link_graph {
+        if GraphFormat::from_path(link_graph).is_none() {
+            return Err(anyhow!("--link-graph {} must end in .dot, .gv, .graphml or .json", link_graph.display()));
+        }
+        mirror.link_graph = Some(link_graph.clone());
+    }
+    mirror.retry_failed = !args.no_retry_failed;
+    mirror.record_fixtures = args.record_fixtures.clone();
+    match (args.log_format.as_str(), &args.log_file) {
+        ("jsonl", Some(log_file)) => mirror.log_file = Some(log_file.clone()),
+        ("jsonl", None) => return Err(anyhow!("--log-format jsonl needs --log-file <FILE> to write events to")),
+        (_, Some(_)) => return Err(anyhow!("--log-file is written with --log-format jsonl")),
+        _ => {}
+    }
+    if let Some(archive_output) = &args.archive_output {
+        if ArchiveFormat::from_path(archive_output).is_none() {
+            return Err(anyhow!("--archive-output {} must end in .zip, .tar.gz or .tgz", archive_output.display()));
+        }
+        mirror.archive_output = Some(archive_output.clone());
+    }
+    mirror.single_file_output = args.single_file.clone();
+    mirror.publish_target = publish_target;
+    mirror.rewrite_diff = args.rewrite_diff;
+    mirror.include_paths = args.include_path.clone();
+    mirror.crawl_domains = args.crawl_domains.clone();
+    mirror.span_subdomains = args.span_subdomains && !args.no_subdomains;
+    mirror.alias_hosts = args.alias_host.clone();
+    mirror.exclude_domains = args.exclude_domains.clone();
+    mirror.preconnect = args.preconnect;
+    mirror.trust_hinted_hosts = args.trust_hinted_hosts;
+    mirror.assertions = args.assertions.iter().map(|condition| condition.parse::<CrawlAssertion>()).collect::<Result<_>>()?;
+    mirror.output_sections = args.split.iter().map(|rule| rule.parse::<OutputSection>()).collect::<Result<_>>()?;
+    if let Some(file) = &args.review_file {
+        mirror.reviewer = Some(Reviewer::File(file.clone()));
+    } else if args.review {
+        mirror.reviewer = Some(Reviewer::Interactive);
+    }
+    if let Some(widths) = &args.image_variants {
+        mirror.image_variant_widths = widths.clone();
+    }
+    mirror.embed_placeholders = embeds::parse_providers(&args.embed_placeholder)?;
+    mirror.resolve_oembed = args.oembed;
+    if let Some(preset) = &args.preset {
+        mirror.apply_preset(preset.parse::<Preset>()?);
+    }
+    if let Some(formats) = &args.convert_formats {
+        mirror.convert_formats = formats.iter().map(|f| f.trim_start_matches('.').to_lowercase()).collect();
+    }
+    Ok(mirror)
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    match std::env::args().nth(1).as_deref() {
+        Some("search") => return run_search(SearchCommand::parse_from(std::env::args().skip(1))),
+        Some("export") => return run_export(ExportCommand::parse_from(std::env::args().skip(1))),
+        Some("extract") => return run_extract(ExtractCommand::parse_from(std::env::args().skip(1))),
+        Some("config") => {
+            let (args, merged) = parse_merged::<ConfigCommand>(std::env::args().skip(1).collect(), 2)?;
+            return run_config(args, &merged);
+        }
+        Some("clean") => return run_clean(CleanCommand::parse_from(std::env::args().skip(1))),
+        Some("diff") => return run_diff(DiffCommand::parse_from(std::env::args().skip(1))),
+        Some("verify") => return run_verify(VerifyCommand::parse_from(std::env::args().skip(1))),
+        Some("bench") => return run_bench(BenchCommand::parse_from(std::env::args().skip(1))),
+        Some("import-warc") => return run_import_warc(ImportWarcCommand::parse_from(std::env::args().skip(1))).await,
+        _ => {}
+    }
+    let (args, merged) = parse_merged::<MirrorCommand>(std::env::args().collect(), 1)?;
+    let level = logging::level_for(args.verbose, args.quiet);
+    if args.output_format == "json" {
+        logging::init_stderr(level);
+    } else {
+        logging::init(level);
+    }
+    logging::set_color(logging::color_enabled(args.no_color));
+    if args.quiet {
+        progress::hide();
+    }
+    select_language(args.lang.as_deref())?;
+    let mut mirror = build_mirror(&args)?;
+    
+    if args.print_config {
+        print!("{}", config::format_config(&mirror, &merged));
+        return Ok(());
+    }
+    if let Some(explain_url) = &args.explain {
+        let decisions = explain::explain_url(&mirror, explain_url);
+        print!("{}", explain::format_explanation(explain_url, &decisions));
+        return Ok(());
+    }
+    
+    if let Some(fixtures_dir) = &args.replay_fixtures {
+        let fixtures = FixtureSet::load(fixtures_dir)?;
+        tracing::info!("📼 Replaying {} recorded responses from {}", fixtures.len(), fixtures_dir.display());
+        mirror.fixture_server = Some(Arc::new(FixtureServer::start(fixtures).await?));
+    }
+    
     mirror.mirror_website().await?;
     
-    println!("✅ Website mirroring completed successfully!");
+    if args.output_format == "json" {
+        println!("{}", serde_json::to_string(&mirror.summary())?);
+    } else if !args.quiet {
+        println!("{}", tr(Message::MirrorCompleted, &[]));
+    }
     Ok(())
 } 
 
@@ -0,0 +1,88 @@
+use anyhow::{Result, Context};
+use serde_json::Value;
+
+/// Manifest members whose entries carry an image `src`
+const IMAGE_LIST_MEMBERS: &[&str] = &["icons", "screenshots"];
+
+/// Whether a downloaded file is a web app manifest (`<link rel="manifest">`)
+pub fn is_web_manifest(url: &str, content_type: &str) -> bool {
+    let path = url.split(['?', '#']).next().unwrap_or(url).to_lowercase();
+    content_type.contains("manifest+json") || path.ends_with(".webmanifest") || path.ends_with("manifest.json")
+}
+
+/// Every `icons[].src` and `screenshots[].src` entry, as written
+pub fn extract_manifest_images(manifest_content: &str) -> Result<Vec<String>> {
+    let manifest: Value = serde_json::from_str(manifest_content).context("Failed to parse web manifest")?;
+    Ok(IMAGE_LIST_MEMBERS
+        .iter()
+        .filter_map(|member| manifest.get(*member).and_then(Value::as_array))
+        .flatten()
+        .filter_map(|entry| entry.get("src").and_then(Value::as_str))
+        .map(|src| src.to_string())
+        .collect())
+}
+
+/// Rewrite `icons[].src` and `screenshots[].src`. The callback returns the
+/// replacement, or `None` to leave an entry untouched.
+pub fn rewrite_manifest_images<F>(manifest_content: &str, mut rewrite: F) -> Result<String>
+where
+    F: FnMut(&str) -> Option<String>,
+{
+    let mut manifest: Value = serde_json::from_str(manifest_content).context("Failed to parse web manifest")?;
+    for member in IMAGE_LIST_MEMBERS {
+        let Some(entries) = manifest.get_mut(*member).and_then(Value::as_array_mut) else {
+            continue;
+        };
+        for entry in entries {
+            let Some(src) = entry.get("src").and_then(Value::as_str) else {
+                continue;
+            };
+            if let Some(new_src) = rewrite(src) {
+                entry["src"] = Value::String(new_src);
+            }
+        }
+    }
+    Ok(serde_json::to_string_pretty(&manifest)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MANIFEST: &str = r#"{
+        "name": "Example",
+        "start_url": "/",
+        "icons": [
+            { "src": "/icons/192.png", "sizes": "192x192", "type": "image/png" },
+            { "src": "icons/512.png", "sizes": "512x512" }
+        ],
+        "screenshots": [{ "src": "https://cdn.example.com/shot.jpg" }]
+    }"#;
+
+    #[test]
+    fn test_is_web_manifest() {
+        assert!(is_web_manifest("https://example.com/site.webmanifest", "text/plain"));
+        assert!(is_web_manifest("https://example.com/manifest.json?v=3", "application/json"));
+        assert!(is_web_manifest("https://example.com/app", "application/manifest+json"));
+        assert!(!is_web_manifest("https://example.com/data.json", "application/json"));
+    }
+
+    #[test]
+    fn test_extract_manifest_images() {
+        let images = extract_manifest_images(MANIFEST).unwrap();
+        assert_eq!(images, vec!["/icons/192.png", "icons/512.png", "https://cdn.example.com/shot.jpg"]);
+    }
+
+    #[test]
+    fn test_rewrite_manifest_images() {
+        let rewritten = rewrite_manifest_images(MANIFEST, |src| {
+            (!src.starts_with("https://")).then(|| format!("local/{}", src.trim_start_matches('/')))
+        }).unwrap();
+
+        let manifest: Value = serde_json::from_str(&rewritten).unwrap();
+        assert_eq!(manifest["icons"][0]["src"], "local/icons/192.png");
+        assert_eq!(manifest["icons"][1]["sizes"], "512x512");
+        assert_eq!(manifest["screenshots"][0]["src"], "https://cdn.example.com/shot.jpg");
+        assert_eq!(manifest["start_url"], "/");
+    }
+}
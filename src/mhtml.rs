@@ -0,0 +1,197 @@
+use anyhow::{Result, Context};
+use base64::Engine;
+use mime_guess::MimeGuess;
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::fs;
+use std::path::{Path, PathBuf};
+use url::Url;
+
+use crate::dead_assets::{self, DocumentKind};
+use crate::search;
+use crate::snapshot_diff;
+use crate::store::{self, DownloadStore};
+
+/// Base64 line length of MIME bodies
+const LINE_LENGTH: usize = 76;
+
+/// Outcome of exporting a mirror's pages as MHTML files
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MhtmlStats {
+    pub pages: usize,
+    /// Stylesheets, scripts, images and fonts embedded, counted once per page
+    pub resources: usize,
+    pub total_bytes: u64,
+}
+
+/// `blog/index.html` -> `blog/index.mhtml`
+pub fn mhtml_path(page: &str) -> PathBuf {
+    Path::new(page).with_extension("mhtml")
+}
+
+/// Where a part is found inside the archive; relative references in the saved
+/// page resolve against the page's location to the locations of its resources
+fn part_location(local_path: &str) -> String {
+    Url::parse("file:///")
+        .and_then(|root| root.join(local_path))
+        .map(|url| url.to_string())
+        .unwrap_or_else(|_| format!("file:///{}", local_path))
+}
+
+/// `Subject` header value, as an RFC 2047 encoded word when it isn't plain ASCII
+fn encode_header(value: &str) -> String {
+    if value.chars().all(|c| c.is_ascii() && !c.is_ascii_control()) {
+        value.to_string()
+    } else {
+        format!("=?utf-8?B?{}?=", base64::engine::general_purpose::STANDARD.encode(value))
+    }
+}
+
+/// The stylesheets, scripts, images and other assets a page needs, following
+/// stylesheet imports and `url()`s; links to other pages are left out
+fn page_resources(output_dir: &Path, page: &str, files: &BTreeMap<String, u64>) -> Vec<String> {
+    let mut resources = Vec::new();
+    let mut seen: HashSet<String> = HashSet::from([page.to_string()]);
+    let mut documents = VecDeque::from([page.to_string()]);
+    while let Some(document) = documents.pop_front() {
+        let Ok(content) = fs::read(output_dir.join(&document)) else { continue };
+        let kind = dead_assets::document_kind(&document, None);
+        for reference in dead_assets::extract_references(&String::from_utf8_lossy(&content), kind) {
+            let Some(target) = dead_assets::resolve_reference(&document, &reference) else { continue };
+            if !files.contains_key(&target) || !seen.insert(target.clone()) {
+                continue;
+            }
+            match dead_assets::document_kind(&target, None) {
+                DocumentKind::Html => {}
+                DocumentKind::Css => {
+                    resources.push(target.clone());
+                    documents.push_back(target);
+                }
+                DocumentKind::Asset => resources.push(target),
+            }
+        }
+    }
+    resources
+}
+
+/// Append one MIME part with a base64 body
+fn write_part(mhtml: &mut String, boundary: &str, content_type: &str, local_path: &str, content: &[u8]) {
+    mhtml.push_str(&format!("--{}\r\n", boundary));
+    mhtml.push_str(&format!("Content-Type: {}\r\n", content_type));
+    mhtml.push_str("Content-Transfer-Encoding: base64\r\n");
+    mhtml.push_str(&format!("Content-Location: {}\r\n\r\n", part_location(local_path)));
+    let encoded = base64::engine::general_purpose::STANDARD.encode(content);
+    for line in encoded.as_bytes().chunks(LINE_LENGTH) {
+        mhtml.push_str(&String::from_utf8_lossy(line));
+        mhtml.push_str("\r\n");
+    }
+}
+
+/// A saved page and every resource it uses as a single `multipart/related`
+/// MHTML document. `original_url` is recorded as the snapshot's location.
+/// Returns the document and the number of resources embedded.
+pub fn page_mhtml(
+    output_dir: &Path,
+    page: &str,
+    files: &BTreeMap<String, u64>,
+    content_types: &HashMap<String, String>,
+    original_url: Option<&str>,
+) -> Result<(String, usize)> {
+    let html = fs::read(output_dir.join(page)).with_context(|| format!("Failed to read page: {:?}", page))?;
+    let boundary = format!("----MultipartBoundary--{}----", store::content_hash(&html).get(..32).unwrap_or_default());
+    let content_type = |path: &str| {
+        content_types
+            .get(path)
+            .cloned()
+            .unwrap_or_else(|| MimeGuess::from_path(path).first_or_octet_stream().to_string())
+    };
+
+    let mut mhtml = String::from("From: <Saved by website-mirror>\r\n");
+    if let Some(original_url) = original_url {
+        mhtml.push_str(&format!("Snapshot-Content-Location: {}\r\n", original_url));
+    }
+    let title = search::page_title(&String::from_utf8_lossy(&html));
+    if !title.is_empty() {
+        mhtml.push_str(&format!("Subject: {}\r\n", encode_header(&title)));
+    }
+    mhtml.push_str("MIME-Version: 1.0\r\n");
+    mhtml.push_str(&format!("Content-Type: multipart/related;\r\n\ttype=\"text/html\";\r\n\tboundary=\"{}\"\r\n\r\n", boundary));
+
+    write_part(&mut mhtml, &boundary, "text/html", page, &html);
+    let resources = page_resources(output_dir, page, files);
+    for resource in &resources {
+        let content = fs::read(output_dir.join(resource)).with_context(|| format!("Failed to read resource: {:?}", resource))?;
+        write_part(&mut mhtml, &boundary, &content_type(resource), resource, &content);
+    }
+    mhtml.push_str(&format!("--{}--\r\n", boundary));
+    Ok((mhtml, resources.len()))
+}
+
+/// Write an `.mhtml` file for every saved page of a mirror into `out_dir`,
+/// at the page's path (`blog/index.html` -> `blog/index.mhtml`)
+pub fn export_mhtml(output_dir: &Path, out_dir: &Path) -> Result<MhtmlStats> {
+    let mut files = BTreeMap::new();
+    snapshot_diff::collect_files(output_dir, output_dir, &mut files)?;
+    let store = DownloadStore::load(output_dir)?;
+    let mut urls: HashMap<String, &str> = HashMap::new();
+    let mut content_types = HashMap::new();
+    for (url, entry) in &store.entries {
+        let local_path = dead_assets::normalize_path(Path::new(&entry.local_path));
+        if let Some(content_type) = &entry.content_type {
+            content_types.insert(local_path.clone(), content_type.clone());
+        }
+        urls.insert(local_path, url.as_str());
+    }
+
+    let mut stats = MhtmlStats::default();
+    for page in files.keys().filter(|path| snapshot_diff::is_page(path)) {
+        let (mhtml, resources) = page_mhtml(output_dir, page, &files, &content_types, urls.get(page).copied())?;
+        let path = out_dir.join(mhtml_path(page));
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).with_context(|| format!("Failed to create directory: {:?}", parent))?;
+        }
+        fs::write(&path, &mhtml).with_context(|| format!("Failed to write MHTML file: {:?}", path))?;
+        stats.pages += 1;
+        stats.resources += resources;
+        stats.total_bytes += mhtml.len() as u64;
+    }
+    Ok(stats)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::write;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_export_mhtml() {
+        let temp_dir = tempdir().unwrap();
+        let dir = temp_dir.path().join("site");
+        write(&dir, "index.html", r#"<title>Café</title><link href="css/site.css" rel="stylesheet"><img src="img/logo.png"><a href="blog/">Blog</a>"#);
+        write(&dir, "blog/index.html", r#"<title>Blog</title><img src="../img/logo.png">"#);
+        write(&dir, "css/site.css", "@font-face { src: url(../fonts/a.woff2); }");
+        write(&dir, "fonts/a.woff2", "font");
+        write(&dir, "img/logo.png", "png");
+        let mut store = DownloadStore::default();
+        store.record_download("https://example.com/", "index.html", Some("text/html"), 10);
+        store.save(&dir).unwrap();
+
+        let out_dir = temp_dir.path().join("mhtml");
+        let stats = export_mhtml(&dir, &out_dir).unwrap();
+        assert_eq!(stats, MhtmlStats { pages: 2, resources: 4, total_bytes: stats.total_bytes });
+
+        let index = fs::read_to_string(out_dir.join("index.mhtml")).unwrap();
+        assert!(index.starts_with("From: <Saved by website-mirror>\r\nSnapshot-Content-Location: https://example.com/\r\n"));
+        assert!(index.contains("Subject: =?utf-8?B?Q2Fmw6k=?=\r\n"));
+        assert!(index.contains("Content-Location: file:///css/site.css\r\n"));
+        assert!(index.contains("Content-Location: file:///fonts/a.woff2\r\n"));
+        assert!(index.contains("Content-Type: image/png\r\nContent-Transfer-Encoding: base64\r\nContent-Location: file:///img/logo.png\r\n\r\ncG5n\r\n"));
+        assert!(!index.contains("file:///blog/index.html"));
+        assert!(index.trim_end().ends_with("----"));
+
+        let blog = fs::read_to_string(out_dir.join("blog/index.mhtml")).unwrap();
+        assert!(blog.contains("Subject: Blog\r\n"));
+        assert!(!blog.contains("Snapshot-Content-Location"));
+        assert_eq!(blog.matches("Content-Location: file:///").count(), 2);
+    }
+}
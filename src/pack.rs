@@ -0,0 +1,296 @@
+use anyhow::{anyhow, Result, Context};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Component, Path, PathBuf};
+
+#[cfg(feature = "encryption")]
+use crate::encryption::{self, Encryption};
+use crate::parallel;
+
+/// First bytes of every pack file
+pub const PACK_MAGIC: &[u8] = b"WMPACK1\n";
+
+/// Extension of the index written next to a pack file
+pub const INDEX_EXTENSION: &str = "idx";
+
+/// Temporary plaintext copy of an encrypted pack while it is being extracted
+#[cfg(feature = "encryption")]
+const DECRYPTED_PACK_NAME: &str = ".decrypted.pack";
+
+/// Where an object's bytes live in the pack file
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PackObject {
+    pub offset: u64,
+    pub size: u64,
+}
+
+/// Index of a pack: every file of the snapshot mapped to the SHA-256 of its
+/// content, and every distinct content stored once in the pack
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PackIndex {
+    pub files: BTreeMap<String, String>,
+    pub objects: BTreeMap<String, PackObject>,
+}
+
+impl PackIndex {
+    pub fn load(index_path: &Path) -> Result<Self> {
+        let data = fs::read_to_string(index_path)
+            .with_context(|| format!("Failed to read pack index: {:?}", index_path))?;
+        serde_json::from_str(&data)
+            .with_context(|| format!("Failed to parse pack index: {:?}", index_path))
+    }
+
+    pub fn save(&self, index_path: &Path) -> Result<()> {
+        let data = serde_json::to_string_pretty(self)?;
+        fs::write(index_path, data)
+            .with_context(|| format!("Failed to write pack index: {:?}", index_path))
+    }
+}
+
+/// Outcome of packing a snapshot
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PackStats {
+    pub files: usize,
+    pub objects: usize,
+    pub total_bytes: u64,
+    /// Bytes written to the pack after deduplicating identical files
+    pub packed_bytes: u64,
+}
+
+/// `archive.pack` -> `archive.idx`
+pub fn index_path(pack_path: &Path) -> PathBuf {
+    pack_path.with_extension(INDEX_EXTENSION)
+}
+
+fn collect_files(root: &Path, dir: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
+    let entries = fs::read_dir(dir).with_context(|| format!("Failed to read directory: {:?}", dir))?;
+    for entry in entries {
+        let path = entry?.path();
+        if path.is_dir() {
+            collect_files(root, &path, files)?;
+        } else if path.is_file() {
+            files.push(path.strip_prefix(root)?.to_path_buf());
+        }
+    }
+    Ok(())
+}
+
+/// Write every file under `snapshot_dir` to `writer` in pack format, storing
+/// identical contents once; `excluded` files (the pack being written) are left out
+fn write_pack<W: Write>(snapshot_dir: &Path, mut writer: W, excluded: &[PathBuf]) -> Result<(PackIndex, PackStats)> {
+    let mut files = Vec::new();
+    collect_files(snapshot_dir, snapshot_dir, &mut files)?;
+    files.sort();
+    let excluded: Vec<_> = excluded.iter().filter_map(|path| fs::canonicalize(path).ok()).collect();
+    files.retain(|relative| !fs::canonicalize(snapshot_dir.join(relative)).is_ok_and(|path| excluded.contains(&path)));
+
+    // Identical files are found by hashing the whole snapshot in parallel up front;
+    // only the first file with each hash is read again and written
+    let hashes = parallel::hash_files(snapshot_dir, &files, "Hashing")?;
+
+    writer.write_all(PACK_MAGIC)?;
+    let mut offset = PACK_MAGIC.len() as u64;
+    let mut index = PackIndex::default();
+    let mut stats = PackStats::default();
+    for (relative, file_hash) in files.iter().zip(hashes) {
+        if !index.objects.contains_key(&file_hash.hash) {
+            let path = snapshot_dir.join(relative);
+            let content = fs::read(&path).with_context(|| format!("Failed to read {:?}", path))?;
+            writer.write_all(&content)?;
+            index.objects.insert(file_hash.hash.clone(), PackObject { offset, size: content.len() as u64 });
+            offset += content.len() as u64;
+            stats.packed_bytes += content.len() as u64;
+        }
+        stats.total_bytes += file_hash.size;
+        index.files.insert(relative.to_string_lossy().replace('\\', "/"), file_hash.hash);
+    }
+    writer.flush()?;
+
+    stats.files = index.files.len();
+    stats.objects = index.objects.len();
+    Ok((index, stats))
+}
+
+/// Repack every file under `snapshot_dir` (including the crawl manifest) into
+/// `pack_path`, storing identical contents once, and write the index next to it
+pub fn compact(snapshot_dir: &Path, pack_path: &Path) -> Result<PackStats> {
+    let excluded = [pack_path.to_path_buf(), index_path(pack_path)];
+    let pack_file = File::create(pack_path).with_context(|| format!("Failed to create pack: {:?}", pack_path))?;
+    let (index, stats) = write_pack(snapshot_dir, BufWriter::new(pack_file), &excluded)?;
+    index.save(&index_path(pack_path))?;
+    Ok(stats)
+}
+
+/// Like `compact`, but the pack and its index are encrypted as they are
+/// written (`site.pack.age`, `site.idx.age`) so no plaintext copy touches the disk
+#[cfg(feature = "encryption")]
+pub fn compact_encrypted(snapshot_dir: &Path, pack_path: &Path, encryption: &Encryption) -> Result<PackStats> {
+    let encrypted_pack = encryption::encrypted_path(pack_path);
+    let encrypted_index = encryption::encrypted_path(&index_path(pack_path));
+    let excluded = [encrypted_pack.clone(), encrypted_index.clone()];
+
+    let pack_file = File::create(&encrypted_pack).with_context(|| format!("Failed to create pack: {:?}", encrypted_pack))?;
+    let mut writer = encryption.wrap_output(BufWriter::new(pack_file))?;
+    let (index, stats) = write_pack(snapshot_dir, &mut writer, &excluded)?;
+    writer.finish()?.flush()?;
+
+    let index_file = File::create(&encrypted_index).with_context(|| format!("Failed to create pack index: {:?}", encrypted_index))?;
+    let mut writer = encryption.wrap_output(BufWriter::new(index_file))?;
+    serde_json::to_writer_pretty(&mut writer, &index)?;
+    writer.finish()?.flush()?;
+    Ok(stats)
+}
+
+/// Reject index paths that would write outside the extraction directory
+fn safe_relative_path(path: &str) -> Result<PathBuf> {
+    let relative = PathBuf::from(path);
+    if relative.components().all(|component| matches!(component, Component::Normal(_))) {
+        Ok(relative)
+    } else {
+        Err(anyhow!("Refusing to extract unsafe path from pack: {}", path))
+    }
+}
+
+/// Write every file of `index` from `pack` into `output_dir`, checking each
+/// against its hash
+fn extract_files<R: Read + Seek>(mut pack: R, index: &PackIndex, output_dir: &Path) -> Result<usize> {
+    let mut magic = vec![0; PACK_MAGIC.len()];
+    pack.read_exact(&mut magic)?;
+    if magic != PACK_MAGIC {
+        return Err(anyhow!("Not a website-mirror pack"));
+    }
+
+    for (path, hash) in &index.files {
+        let object = index.objects.get(hash)
+            .ok_or_else(|| anyhow!("Pack index has no object {} for {}", hash, path))?;
+        pack.seek(SeekFrom::Start(object.offset))?;
+        let mut content = vec![0; object.size as usize];
+        pack.read_exact(&mut content)
+            .with_context(|| format!("Pack is truncated at {}", path))?;
+        if format!("{:x}", Sha256::digest(&content)) != *hash {
+            return Err(anyhow!("Corrupt pack: content of {} doesn't match its hash", path));
+        }
+
+        let target = output_dir.join(safe_relative_path(path)?);
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&target, content).with_context(|| format!("Failed to write {:?}", target))?;
+    }
+    Ok(index.files.len())
+}
+
+/// Restore the snapshot tree of `pack_path` into `output_dir`, checking every
+/// file against its hash. Returns the number of files written.
+pub fn extract(pack_path: &Path, output_dir: &Path) -> Result<usize> {
+    let index = PackIndex::load(&index_path(pack_path))?;
+    let pack = File::open(pack_path).with_context(|| format!("Failed to open pack: {:?}", pack_path))?;
+    extract_files(pack, &index, output_dir)
+        .with_context(|| format!("Failed to extract {:?}", pack_path))
+}
+
+/// Restore an encrypted pack (`site.pack.age` with `site.idx.age`) using the
+/// age identities in `identity_file`. The pack is decrypted to a temporary file
+/// inside `output_dir`, which is removed afterwards.
+#[cfg(feature = "encryption")]
+pub fn extract_encrypted(encrypted_pack: &Path, identity_file: &Path, output_dir: &Path) -> Result<usize> {
+    let pack_path = encryption::decrypted_path(encrypted_pack)
+        .ok_or_else(|| anyhow!("Encrypted packs end in .{}: {:?}", encryption::AGE_EXTENSION, encrypted_pack))?;
+    let encrypted_index = encryption::encrypted_path(&index_path(&pack_path));
+    let identities = encryption::load_identities(identity_file)?;
+
+    let index_file = File::open(&encrypted_index).with_context(|| format!("Failed to open pack index: {:?}", encrypted_index))?;
+    let index: PackIndex = serde_json::from_reader(encryption::decrypt_reader(index_file, &identities)?)
+        .with_context(|| format!("Failed to parse pack index: {:?}", encrypted_index))?;
+
+    fs::create_dir_all(output_dir)?;
+    let decrypted = output_dir.join(DECRYPTED_PACK_NAME);
+    let result = (|| {
+        let pack_file = File::open(encrypted_pack).with_context(|| format!("Failed to open pack: {:?}", encrypted_pack))?;
+        let mut reader = encryption::decrypt_reader(pack_file, &identities)?;
+        std::io::copy(&mut reader, &mut File::create(&decrypted)?)?;
+        extract_files(File::open(&decrypted)?, &index, output_dir)
+    })();
+    let _ = fs::remove_file(&decrypted);
+    result.with_context(|| format!("Failed to extract {:?}", encrypted_pack))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::write;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_compact_and_extract() {
+        let temp_dir = tempdir().unwrap();
+        let snapshot = temp_dir.path().join("site");
+        write(&snapshot, "index.html", "<p>home</p>");
+        write(&snapshot, "blog/index.html", "<p>blog</p>");
+        write(&snapshot, "img/logo.png", "logo");
+        write(&snapshot, "_external/cdn.example.com/logo.png", "logo");
+        write(&snapshot, ".download_store.json", "{}");
+
+        let pack_path = temp_dir.path().join("site.pack");
+        let stats = compact(&snapshot, &pack_path).unwrap();
+        assert_eq!(stats.files, 5);
+        assert_eq!(stats.objects, 4);
+        assert_eq!(stats.total_bytes - stats.packed_bytes, 4);
+        assert!(temp_dir.path().join("site.idx").is_file());
+
+        let restored = temp_dir.path().join("restored");
+        assert_eq!(extract(&pack_path, &restored).unwrap(), 5);
+        assert_eq!(fs::read_to_string(restored.join("blog/index.html")).unwrap(), "<p>blog</p>");
+        assert_eq!(fs::read_to_string(restored.join("_external/cdn.example.com/logo.png")).unwrap(), "logo");
+        assert_eq!(fs::read_to_string(restored.join(".download_store.json")).unwrap(), "{}");
+    }
+
+    #[test]
+    fn test_extract_rejects_corruption_and_unsafe_paths() {
+        let temp_dir = tempdir().unwrap();
+        let snapshot = temp_dir.path().join("site");
+        write(&snapshot, "index.html", "<p>home</p>");
+        let pack_path = temp_dir.path().join("site.pack");
+        compact(&snapshot, &pack_path).unwrap();
+
+        let mut index = PackIndex::load(&index_path(&pack_path)).unwrap();
+        let hash = index.files["index.html"].clone();
+        index.files.insert("../escape.html".to_string(), hash);
+        index.save(&index_path(&pack_path)).unwrap();
+        assert!(extract(&pack_path, &temp_dir.path().join("out")).is_err());
+        assert!(!temp_dir.path().join("escape.html").exists());
+
+        compact(&snapshot, &pack_path).unwrap();
+        let mut data = fs::read(&pack_path).unwrap();
+        *data.last_mut().unwrap() = b'!';
+        fs::write(&pack_path, data).unwrap();
+        assert!(extract(&pack_path, &temp_dir.path().join("out2")).is_err());
+    }
+
+    #[cfg(feature = "encryption")]
+    #[test]
+    fn test_compact_and_extract_encrypted() {
+        let temp_dir = tempdir().unwrap();
+        let snapshot = temp_dir.path().join("site");
+        write(&snapshot, "index.html", "<p>pre-release pricing</p>");
+        write(&snapshot, "img/logo.png", "logo");
+        let identity = age::x25519::Identity::generate();
+        let identity_file = temp_dir.path().join("key.txt");
+        fs::write(&identity_file, age::secrecy::ExposeSecret::expose_secret(&identity.to_string())).unwrap();
+
+        let pack_path = temp_dir.path().join("site.pack");
+        let stats = compact_encrypted(&snapshot, &pack_path, &Encryption::Age(identity.to_public())).unwrap();
+        assert_eq!(stats.files, 2);
+        assert!(!pack_path.exists() && !temp_dir.path().join("site.idx").exists());
+        let encrypted = fs::read(temp_dir.path().join("site.pack.age")).unwrap();
+        assert!(!encrypted.windows(7).any(|window| window == b"pricing"));
+
+        let restored = temp_dir.path().join("restored");
+        assert_eq!(extract_encrypted(&temp_dir.path().join("site.pack.age"), &identity_file, &restored).unwrap(), 2);
+        assert_eq!(fs::read_to_string(restored.join("index.html")).unwrap(), "<p>pre-release pricing</p>");
+        assert!(!restored.join(DECRYPTED_PACK_NAME).exists());
+    }
+}
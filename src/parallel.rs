@@ -0,0 +1,88 @@
+use anyhow::{Result, Context};
+use indicatif::{ProgressBar, ProgressDrawTarget, ProgressStyle};
+use rayon::prelude::*;
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::progress;
+
+/// SHA-256 and size of one file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileHash {
+    pub hash: String,
+    pub size: u64,
+}
+
+/// Progress bar for a post-processing pass over `len` files, drawn on stderr
+/// (and hidden when stderr isn't a terminal or with `--quiet`)
+pub fn progress_bar(len: usize, message: &'static str) -> ProgressBar {
+    let target = if progress::enabled() { ProgressDrawTarget::stderr() } else { ProgressDrawTarget::hidden() };
+    let progress_bar = ProgressBar::with_draw_target(Some(len as u64), target).with_message(message);
+    progress_bar.set_style(
+        ProgressStyle::default_bar()
+            .template("{msg} [{wide_bar}] {pos}/{len} ({eta})")
+            .unwrap()
+            .progress_chars("=> ")
+    );
+    progress_bar
+}
+
+/// Run `check` over `items` on the work-stealing thread pool, showing progress,
+/// and return the results in the order of `items`
+pub fn map_with_progress<T, R, F>(items: &[T], message: &'static str, check: F) -> Vec<R>
+where
+    T: Sync,
+    R: Send,
+    F: Fn(&T) -> R + Sync,
+{
+    let progress_bar = progress_bar(items.len(), message);
+    let results = items
+        .par_iter()
+        .map(|item| {
+            let result = check(item);
+            progress_bar.inc(1);
+            result
+        })
+        .collect();
+    progress_bar.finish_and_clear();
+    results
+}
+
+/// Hash every file (relative to `root`) in parallel; results are in the order
+/// of `files`
+pub fn hash_files(root: &Path, files: &[PathBuf], message: &'static str) -> Result<Vec<FileHash>> {
+    map_with_progress(files, message, |relative| {
+        let path = root.join(relative);
+        let content = fs::read(&path).with_context(|| format!("Failed to read {:?}", path))?;
+        Ok(FileHash {
+            hash: format!("{:x}", Sha256::digest(&content)),
+            size: content.len() as u64,
+        })
+    })
+    .into_iter()
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_hash_files_keeps_order() {
+        let temp_dir = tempdir().unwrap();
+        let files: Vec<PathBuf> = (0..200).map(|i| PathBuf::from(format!("file-{}.txt", i))).collect();
+        for (i, file) in files.iter().enumerate() {
+            fs::write(temp_dir.path().join(file), "x".repeat(i)).unwrap();
+        }
+
+        let hashes = hash_files(temp_dir.path(), &files, "Hashing").unwrap();
+        assert_eq!(hashes.len(), 200);
+        assert!(hashes.iter().enumerate().all(|(i, file_hash)| file_hash.size == i as u64));
+        assert_eq!(hashes[0].hash, "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855");
+
+        let missing = [PathBuf::from("missing.txt")];
+        assert!(hash_files(temp_dir.path(), &missing, "Hashing").is_err());
+    }
+}
@@ -0,0 +1,128 @@
+use anyhow::{Result, Context};
+use regex::{Captures, Regex};
+
+/// Email addresses, including ones in `mailto:` links
+const EMAIL_PATTERN: &str = r"[A-Za-z0-9._%+-]+@[A-Za-z0-9-]+(?:\.[A-Za-z0-9-]+)*\.[A-Za-z]{2,}";
+
+/// Phone numbers written with separators: `+1 (555) 123-4567`, `020 7946 0958`,
+/// `+49 30 1234 5678`. Bare digit runs are left alone so prices, IDs and dates survive.
+const PHONE_PATTERN: &str = r"(?:\+\d{1,3}[\s.-]?)?(?:\(\d{2,4}\)[\s.-]?|\d{2,4}[\s.-])\d{3,4}[\s.-]\d{3,5}\b";
+
+/// Attributes whose values are shown to readers or hold contact details;
+/// other attributes (`src`, `class`, ...) are never rewritten
+const TEXT_ATTRIBUTES: &[&str] = &["title", "alt", "content", "value", "placeholder", "aria-label", "label"];
+
+/// A pattern of personal data and the text it is replaced with
+#[derive(Debug, Clone)]
+pub struct PiiPattern {
+    pub regex: Regex,
+    pub replacement: String,
+}
+
+/// Redacts email addresses, phone numbers and user-supplied patterns from the
+/// text and human-readable attributes of saved pages, leaving markup intact
+#[derive(Debug, Clone)]
+pub struct PiiScrubber {
+    pub patterns: Vec<PiiPattern>,
+}
+
+impl PiiScrubber {
+    /// The built-in email and phone patterns plus `extra_patterns` (regexes
+    /// replaced with `[redacted]`)
+    pub fn new(extra_patterns: &[String]) -> Result<Self> {
+        let mut patterns = vec![
+            PiiPattern {
+                regex: Regex::new(EMAIL_PATTERN).unwrap(),
+                replacement: "[redacted email]".to_string(),
+            },
+            PiiPattern {
+                regex: Regex::new(PHONE_PATTERN).unwrap(),
+                replacement: "[redacted phone]".to_string(),
+            },
+        ];
+        for pattern in extra_patterns {
+            patterns.push(PiiPattern {
+                regex: Regex::new(pattern).with_context(|| format!("Invalid PII pattern: {}", pattern))?,
+                replacement: "[redacted]".to_string(),
+            });
+        }
+        Ok(Self { patterns })
+    }
+
+    /// Apply every pattern to a piece of text
+    pub fn scrub_text(&self, text: &str) -> String {
+        let mut text = text.to_string();
+        for pattern in &self.patterns {
+            text = pattern.regex.replace_all(&text, pattern.replacement.as_str()).into_owned();
+        }
+        text
+    }
+
+    fn scrub_tag(&self, tag: &str) -> String {
+        let attribute_regex = Regex::new(r#"(?i)(\s)([a-z-]+)(\s*=\s*)("[^"]*"|'[^']*')"#).unwrap();
+        attribute_regex
+            .replace_all(tag, |caps: &Captures| {
+                let name = caps[2].to_ascii_lowercase();
+                let quoted = &caps[4];
+                let value = &quoted[1..quoted.len() - 1];
+                let is_contact_link = name == "href" && {
+                    let value = value.trim_start().to_ascii_lowercase();
+                    value.starts_with("mailto:") || value.starts_with("tel:")
+                };
+                if !is_contact_link && !TEXT_ATTRIBUTES.contains(&name.as_str()) {
+                    return caps[0].to_string();
+                }
+                let quote = &quoted[..1];
+                format!("{}{}{}{}{}{}", &caps[1], &caps[2], &caps[3], quote, self.scrub_text(value), quote)
+            })
+            .into_owned()
+    }
+
+    /// Redact personal data in a page: text between tags (including inline
+    /// scripts such as JSON-LD) and the values of readable attributes and
+    /// `mailto:`/`tel:` links
+    pub fn scrub_html(&self, html_content: &str) -> String {
+        let tag_regex = Regex::new(r"<[^>]*>").unwrap();
+        let mut scrubbed = String::with_capacity(html_content.len());
+        let mut last_end = 0;
+        for tag in tag_regex.find_iter(html_content) {
+            scrubbed.push_str(&self.scrub_text(&html_content[last_end..tag.start()]));
+            scrubbed.push_str(&self.scrub_tag(tag.as_str()));
+            last_end = tag.end();
+        }
+        scrubbed.push_str(&self.scrub_text(&html_content[last_end..]));
+        scrubbed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scrub_text() {
+        let scrubber = PiiScrubber::new(&[]).unwrap();
+        assert_eq!(
+            scrubber.scrub_text("Write to jane.doe+news@example.co.uk or call +1 (555) 123-4567 / 020 7946 0958."),
+            "Write to [redacted email] or call [redacted phone] / [redacted phone]."
+        );
+        assert_eq!(scrubber.scrub_text("Order 12345678 shipped 2024-10-16 for $1,299.00"), "Order 12345678 shipped 2024-10-16 for $1,299.00");
+    }
+
+    #[test]
+    fn test_scrub_html_keeps_markup() {
+        let scrubber = PiiScrubber::new(&[]).unwrap();
+        let html = r#"<a href="mailto:jane@example.com" class="contact" title="Email jane@example.com">jane@example.com</a><img src="/team/jane@2x.png" alt="Call 555-123-4567">"#;
+        assert_eq!(
+            scrubber.scrub_html(html),
+            r#"<a href="mailto:[redacted email]" class="contact" title="Email [redacted email]">[redacted email]</a><img src="/team/jane@2x.png" alt="Call [redacted phone]">"#
+        );
+    }
+
+    #[test]
+    fn test_custom_patterns() {
+        let scrubber = PiiScrubber::new(&[r"EMP-\d{6}".to_string()]).unwrap();
+        assert_eq!(scrubber.scrub_html("<td>EMP-004211</td>"), "<td>[redacted]</td>");
+        assert!(PiiScrubber::new(&["(".to_string()]).is_err());
+    }
+}
@@ -0,0 +1,213 @@
+use anyhow::{anyhow, Result};
+use regex::{Captures, Regex};
+use std::str::FromStr;
+
+use crate::html_parser::HtmlParser;
+use crate::image_variants;
+
+/// Crawl settings tuned for a particular CMS or shop platform (`--preset`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Preset {
+    WordPress,
+    Shopify,
+}
+
+impl FromStr for Preset {
+    type Err = anyhow::Error;
+
+    fn from_str(name: &str) -> Result<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "wordpress" | "wp" => Ok(Preset::WordPress),
+            "shopify" => Ok(Preset::Shopify),
+            _ => Err(anyhow!("Unknown preset: {} (expected wordpress or shopify)", name)),
+        }
+    }
+}
+
+/// Source attributes of common WordPress lazy-load plugins (WP Rocket, a3 Lazy Load,
+/// Smush/lazysizes, Jetpack) and Jetpack's gallery image sizes
+const WORDPRESS_LAZY_ATTRS: &[&str] = &[
+    "data-lazy-src",
+    "data-lazy-srcset",
+    "data-src",
+    "data-srcset",
+    "data-lazy-original",
+    "data-orig-file",
+    "data-medium-file",
+    "data-large-file",
+];
+
+/// REST API, oEmbed, feed, admin and comment-reply URLs: dynamic endpoints
+/// that either fail offline or duplicate every post
+const WORDPRESS_EXCLUDED_URL_PARTS: &[&str] = &[
+    "/wp-json/",
+    "?rest_route=",
+    "/oembed/",
+    "/wp-admin/",
+    "/wp-login.php",
+    "/xmlrpc.php",
+    "/feed/",
+    "replytocom=",
+    "/wp-includes/wlwmanifest.xml",
+];
+
+/// Shopify themes lazy-load with lazysizes; `srcset` is included so product
+/// image variants are downloaded and rewritten too
+const SHOPIFY_LAZY_ATTRS: &[&str] = &["data-src", "data-srcset", "srcset"];
+
+/// Cart, checkout and account pages, sorted/filtered collection duplicates,
+/// variant selections and the oEmbed endpoint
+const SHOPIFY_EXCLUDED_URL_PARTS: &[&str] = &[
+    "/cart",
+    "/checkout",
+    "/account",
+    "/search?",
+    "sort_by=",
+    "filter.",
+    "?variant=",
+    "&variant=",
+    ".oembed",
+];
+
+/// Storefront JSON endpoints (`/products/shirt.js`, `/collections/all/products.json`)
+fn is_shopify_json_endpoint(url: &str) -> bool {
+    let path = url.split(['?', '#']).next().unwrap_or(url);
+    (path.contains("/products/") && (path.ends_with(".js") || path.ends_with(".json")))
+        || path.ends_with("/products.json")
+}
+
+/// `<link rel>` values pointing at the REST API, RSD, Live Writer manifest and shortlinks
+const WORDPRESS_API_LINK_RELS: &[&str] = &["https://api.w.org/", "edituri", "wlwmanifest", "shortlink"];
+
+impl Preset {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Preset::WordPress => "wordpress",
+            Preset::Shopify => "shopify",
+        }
+    }
+
+    /// Extra attributes holding lazy-loaded image sources
+    pub fn lazy_attrs(&self) -> &'static [&'static str] {
+        match self {
+            Preset::WordPress => WORDPRESS_LAZY_ATTRS,
+            Preset::Shopify => SHOPIFY_LAZY_ATTRS,
+        }
+    }
+
+    /// Whether a URL is a dynamic endpoint this preset doesn't crawl
+    pub fn excludes_url(&self, url: &str) -> bool {
+        match self {
+            Preset::WordPress => WORDPRESS_EXCLUDED_URL_PARTS.iter().any(|part| url.contains(part)),
+            Preset::Shopify => SHOPIFY_EXCLUDED_URL_PARTS.iter().any(|part| url.contains(part)) || is_shopify_json_endpoint(url),
+        }
+    }
+
+    /// Remove markup that only works against the live site
+    pub fn clean_html(&self, html_content: &str) -> String {
+        match self {
+            Preset::WordPress => strip_wordpress_dynamic_markup(html_content),
+            Preset::Shopify => html_content.to_string(),
+        }
+    }
+    
+    /// Product image widths kept when `--image-variants` isn't given
+    pub fn image_variant_widths(&self) -> &'static [u32] {
+        match self {
+            Preset::WordPress => &[],
+            Preset::Shopify => image_variants::DEFAULT_VARIANT_WIDTHS,
+        }
+    }
+}
+
+/// Drop the emoji detection script and styles (they load twemoji from s.w.org),
+/// and the oEmbed discovery and REST API/RSD `<link>` tags
+fn strip_wordpress_dynamic_markup(html_content: &str) -> String {
+    let script_regex = Regex::new(r"(?is)<script\b[^>]*>.*?</script>\s*").unwrap();
+    let without_emoji_script = script_regex.replace_all(html_content, |caps: &Captures| {
+        let script = &caps[0];
+        if script.contains("_wpemojiSettings") || script.contains("wp-emoji-release") {
+            String::new()
+        } else {
+            script.to_string()
+        }
+    });
+
+    let style_regex = Regex::new(r"(?is)<style\b[^>]*>.*?</style>\s*").unwrap();
+    let without_emoji_style = style_regex.replace_all(&without_emoji_script, |caps: &Captures| {
+        let style = &caps[0];
+        if style.contains("img.wp-smiley") || style.contains("img.emoji") {
+            String::new()
+        } else {
+            style.to_string()
+        }
+    });
+
+    let link_regex = Regex::new(r"(?i)<link\b[^>]*>\s*").unwrap();
+    link_regex
+        .replace_all(&without_emoji_style, |caps: &Captures| {
+            let link = &caps[0];
+            let is_oembed = HtmlParser::tag_attribute(link, "type").is_some_and(|link_type| link_type.contains("oembed"));
+            let is_api = HtmlParser::tag_attribute(link, "rel")
+                .is_some_and(|rel| rel.split_whitespace().any(|r| WORDPRESS_API_LINK_RELS.contains(&r.to_ascii_lowercase().as_str())));
+            if is_oembed || is_api { String::new() } else { link.to_string() }
+        })
+        .into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_preset_from_str() {
+        assert_eq!("wordpress".parse::<Preset>().unwrap(), Preset::WordPress);
+        assert_eq!("WP".parse::<Preset>().unwrap(), Preset::WordPress);
+        assert_eq!("shopify".parse::<Preset>().unwrap(), Preset::Shopify);
+        assert!("drupal".parse::<Preset>().is_err());
+    }
+
+    #[test]
+    fn test_wordpress_excluded_urls() {
+        let preset = Preset::WordPress;
+        assert!(preset.excludes_url("https://blog.example.com/wp-json/wp/v2/posts"));
+        assert!(preset.excludes_url("https://blog.example.com/wp-json/oembed/1.0/embed?url=x"));
+        assert!(preset.excludes_url("https://blog.example.com/hello-world/?replytocom=12"));
+        assert!(preset.excludes_url("https://blog.example.com/feed/"));
+        assert!(!preset.excludes_url("https://blog.example.com/wp-content/uploads/2024/01/photo.jpg"));
+        assert!(!preset.excludes_url("https://blog.example.com/hello-world/"));
+    }
+
+    #[test]
+    fn test_shopify_excluded_urls() {
+        let preset = Preset::Shopify;
+        assert!(preset.excludes_url("https://shop.example.com/cart"));
+        assert!(preset.excludes_url("https://shop.example.com/collections/all?sort_by=price-ascending"));
+        assert!(preset.excludes_url("https://shop.example.com/products/shirt?variant=4211"));
+        assert!(preset.excludes_url("https://shop.example.com/products/shirt.json"));
+        assert!(!preset.excludes_url("https://shop.example.com/products/shirt"));
+        assert!(!preset.excludes_url("https://shop.example.com/collections/shirts"));
+        assert!(!preset.excludes_url("https://shop.example.com/cdn/shop/t/3/assets/theme.js?v=12"));
+    }
+
+    #[test]
+    fn test_wordpress_clean_html() {
+        let html = r#"<head>
+<script type="text/javascript">window._wpemojiSettings = {"source":{"concatemoji":"https:\/\/blog.example.com\/wp-includes\/js\/wp-emoji-release.min.js"}};</script>
+<style type="text/css">img.wp-smiley, img.emoji { display: inline !important; }</style>
+<link rel="stylesheet" href="https://blog.example.com/wp-content/themes/theme/style.css">
+<link rel="https://api.w.org/" href="https://blog.example.com/wp-json/">
+<link rel="EditURI" type="application/rsd+xml" href="https://blog.example.com/xmlrpc.php?rsd">
+<link rel="alternate" type="application/json+oembed" href="https://blog.example.com/wp-json/oembed/1.0/embed?url=x">
+<script src="https://blog.example.com/wp-includes/js/jquery/jquery.min.js"></script>
+</head>"#;
+        let cleaned = Preset::WordPress.clean_html(html);
+
+        assert!(!cleaned.contains("_wpemojiSettings"));
+        assert!(!cleaned.contains("wp-smiley"));
+        assert!(!cleaned.contains("wp-json"));
+        assert!(!cleaned.contains("xmlrpc.php"));
+        assert!(cleaned.contains("style.css"));
+        assert!(cleaned.contains("jquery.min.js"));
+    }
+}
@@ -0,0 +1,197 @@
+use indicatif::{MultiProgress, ProgressBar, ProgressDrawTarget, ProgressStyle};
+use std::collections::BTreeMap;
+use std::io::IsTerminal;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Mutex;
+
+/// Downloads announcing at least this many bytes get their own byte progress bar
+pub const LARGE_FILE_BYTES: u64 = 1024 * 1024;
+
+/// The display currently drawing on the terminal, if any, by id; log lines
+/// are written through it so they don't tear the bars
+static ACTIVE: Mutex<Option<(u64, MultiProgress)>> = Mutex::new(None);
+static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+static HIDDEN: AtomicBool = AtomicBool::new(false);
+
+/// Never draw progress bars in this process (`--quiet`)
+pub fn hide() {
+    HIDDEN.store(true, Ordering::Relaxed);
+}
+
+/// Whether progress bars can be drawn: they go to stderr, so only when it's a
+/// terminal, and not after `hide`
+pub fn enabled() -> bool {
+    !HIDDEN.load(Ordering::Relaxed) && std::io::stderr().is_terminal()
+}
+
+/// Run `write` (printing a log line) with the progress bars cleared, redrawing them after
+pub fn suspend<R>(write: impl FnOnce() -> R) -> R {
+    let active = ACTIVE.lock().unwrap().as_ref().map(|(_, multi)| multi.clone());
+    match active {
+        Some(multi) => multi.suspend(write),
+        None => write(),
+    }
+}
+
+/// `html 12 · css 4 · images 40` for the files saved per type
+fn format_counts(counts: &BTreeMap<&'static str, u64>) -> String {
+    counts
+        .iter()
+        .map(|(file_type, count)| format!("{} {}", file_type, count))
+        .collect::<Vec<_>>()
+        .join(" · ")
+}
+
+/// Progress of a crawl on one display: the URLs done out of those queued so
+/// far, files saved per type, and a byte bar for each large download in flight.
+/// Hidden when stderr isn't a terminal.
+#[derive(Debug)]
+pub struct CrawlProgress {
+    id: u64,
+    multi: MultiProgress,
+    overall: ProgressBar,
+    types: ProgressBar,
+    counts: Mutex<BTreeMap<&'static str, u64>>,
+    dequeued: AtomicU64,
+}
+
+impl CrawlProgress {
+    pub fn new(enabled: bool) -> Self {
+        let target = if enabled { ProgressDrawTarget::stderr() } else { ProgressDrawTarget::hidden() };
+        let multi = MultiProgress::with_draw_target(target);
+        let overall = multi.add(ProgressBar::new(0));
+        overall.set_style(
+            ProgressStyle::default_bar()
+                .template("{spinner} [{elapsed_precise}] [{bar:30}] {pos}/{len} done {wide_msg}")
+                .unwrap()
+                .progress_chars("=> ")
+        );
+        let types = multi.add(ProgressBar::new(0));
+        types.set_style(ProgressStyle::default_bar().template("  {wide_msg}").unwrap());
+        let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+        if enabled {
+            *ACTIVE.lock().unwrap() = Some((id, multi.clone()));
+        }
+        Self {
+            id,
+            multi,
+            overall,
+            types,
+            counts: Mutex::new(BTreeMap::new()),
+            dequeued: AtomicU64::new(0),
+        }
+    }
+
+    /// A display that draws nothing, for mirrors not crawling yet
+    pub fn hidden() -> Self {
+        Self::new(false)
+    }
+
+    /// `url` was taken from the queue, with `queued` URLs still waiting behind it
+    pub fn start(&self, url: &str, queued: usize) {
+        let dequeued = self.dequeued.fetch_add(1, Ordering::Relaxed) + 1;
+        self.overall.set_length(dequeued + queued as u64);
+        self.overall.set_message(url.to_string());
+    }
+
+    /// A URL taken from the queue was saved, failed or skipped
+    pub fn finish_one(&self) {
+        self.overall.inc(1);
+    }
+
+    /// A file of `file_type` (`html`, `css`, `images`, ...) was saved
+    pub fn count_file(&self, file_type: &'static str) {
+        let mut counts = self.counts.lock().unwrap();
+        *counts.entry(file_type).or_default() += 1;
+        self.types.set_message(format_counts(&counts));
+    }
+
+    /// A byte bar for downloading `url`, when it announced at least `LARGE_FILE_BYTES`;
+    /// it is removed from the display when dropped
+    pub fn file_bar(&self, url: &str, content_length: Option<u64>) -> Option<FileProgress> {
+        let length = content_length.filter(|length| *length >= LARGE_FILE_BYTES)?;
+        let bar = self.multi.add(ProgressBar::new(length).with_message(url.to_string()));
+        bar.set_style(
+            ProgressStyle::default_bar()
+                .template("  [{bar:30}] {bytes}/{total_bytes} ({bytes_per_sec}) {wide_msg}")
+                .unwrap()
+                .progress_chars("=> ")
+        );
+        Some(FileProgress { multi: self.multi.clone(), bar })
+    }
+
+    /// Leave the overall bar on screen with `message` and clear the rest
+    pub fn finish(&self, message: String) {
+        self.types.finish_and_clear();
+        self.overall.finish_with_message(message);
+    }
+}
+
+impl Drop for CrawlProgress {
+    fn drop(&mut self) {
+        let mut active = ACTIVE.lock().unwrap();
+        if active.as_ref().is_some_and(|(id, _)| *id == self.id) {
+            *active = None;
+        }
+    }
+}
+
+/// Byte progress of one large download
+#[derive(Debug)]
+pub struct FileProgress {
+    multi: MultiProgress,
+    bar: ProgressBar,
+}
+
+impl FileProgress {
+    pub fn inc(&self, bytes: usize) {
+        self.bar.inc(bytes as u64);
+    }
+}
+
+impl Drop for FileProgress {
+    fn drop(&mut self) {
+        self.bar.finish_and_clear();
+        self.multi.remove(&self.bar);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_counts() {
+        let mut counts = BTreeMap::new();
+        assert_eq!(format_counts(&counts), "");
+        counts.insert("images", 40);
+        counts.insert("css", 4);
+        counts.insert("html", 12);
+        assert_eq!(format_counts(&counts), "css 4 · html 12 · images 40");
+    }
+
+    #[test]
+    fn test_hidden_progress() {
+        let progress = CrawlProgress::hidden();
+        progress.start("https://example.com/", 3);
+        progress.start("https://example.com/about/", 2);
+        progress.finish_one();
+        assert_eq!(progress.overall.length(), Some(4));
+        assert_eq!(progress.overall.position(), 1);
+
+        progress.count_file("html");
+        progress.count_file("images");
+        progress.count_file("images");
+        assert_eq!(progress.types.message(), "html 1 · images 2");
+    }
+
+    #[test]
+    fn test_file_bar_only_for_large_files() {
+        let progress = CrawlProgress::hidden();
+        assert!(progress.file_bar("https://example.com/small.png", Some(10_000)).is_none());
+        assert!(progress.file_bar("https://example.com/unknown.bin", None).is_none());
+        let bar = progress.file_bar("https://example.com/video.mp4", Some(50 * LARGE_FILE_BYTES)).unwrap();
+        bar.inc(4096);
+        assert_eq!(bar.bar.position(), 4096);
+    }
+}
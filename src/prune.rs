@@ -0,0 +1,172 @@
+use anyhow::{anyhow, Result, Context};
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::fs;
+use std::path::Path;
+
+use crate::dead_assets::{self, DocumentKind};
+use crate::snapshot_diff;
+use crate::store::DownloadStore;
+
+/// A file in the output directory that nothing in the current mirror leads to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OrphanedFile {
+    /// Path relative to the output directory
+    pub local_path: String,
+    pub size: u64,
+    /// URL the file was downloaded from, when the store still records it
+    pub url: Option<String>,
+}
+
+/// The saved file a resolved reference points at: the file itself, or the
+/// `index.html` of a directory
+fn saved_target(files: &BTreeMap<String, u64>, target: &str) -> Option<String> {
+    if files.contains_key(target) {
+        return Some(target.to_string());
+    }
+    let index = if target.is_empty() { "index.html".to_string() } else { format!("{}/index.html", target) };
+    files.contains_key(&index).then_some(index)
+}
+
+/// Mark a saved file live, queueing pages and stylesheets to follow their references
+fn visit(path: String, live: &mut HashSet<String>, documents: &mut VecDeque<String>) {
+    if live.insert(path.clone()) && dead_assets::document_kind(&path, None) != DocumentKind::Asset {
+        documents.push_back(path);
+    }
+}
+
+/// Files in a mirror that are neither reachable from its start pages nor
+/// referenced, according to the store, by a page that is
+///
+/// The start pages (the root `index.html` and pages the store records no
+/// referrer for) are followed through every saved page and stylesheet that
+/// links on, so pages that disappeared upstream, and the assets only they
+/// used, are left out. `link_root` is the prefix links to the output root
+/// were written with (`--rewrite-host`/`--link-base`).
+pub fn find_orphaned_files(output_dir: &Path, store: &DownloadStore, link_root: Option<&str>) -> Result<Vec<OrphanedFile>> {
+    if store.entries.is_empty() {
+        return Err(anyhow!("{} has no download store to compare against", output_dir.display()));
+    }
+    let mut files = BTreeMap::new();
+    snapshot_diff::collect_files(output_dir, output_dir, &mut files)?;
+
+    let mut urls_by_path: HashMap<String, &str> = HashMap::new();
+    for (url, entry) in &store.entries {
+        if !entry.local_path.is_empty() {
+            urls_by_path.insert(dead_assets::normalize_path(Path::new(&entry.local_path)), url.as_str());
+        }
+    }
+
+    let mut live: HashSet<String> = HashSet::new();
+    let mut documents = VecDeque::new();
+
+    if files.contains_key("index.html") {
+        visit("index.html".to_string(), &mut live, &mut documents);
+    }
+    for entry in store.entries.values() {
+        let path = dead_assets::normalize_path(Path::new(&entry.local_path));
+        let is_page = dead_assets::document_kind(&path, entry.content_type.as_deref()) == DocumentKind::Html;
+        if is_page && entry.referrers.is_empty() && files.contains_key(&path) {
+            visit(path, &mut live, &mut documents);
+        }
+    }
+
+    while let Some(document) = documents.pop_front() {
+        if let Some(url) = urls_by_path.get(&document) {
+            // Files the crawl recorded for this page, including ones only its scripts load
+            for resource in store.referenced_by(url) {
+                let path = dead_assets::normalize_path(Path::new(&store.entries[resource].local_path));
+                if files.contains_key(&path) {
+                    visit(path, &mut live, &mut documents);
+                }
+            }
+        }
+        let Ok(content) = fs::read(output_dir.join(&document)) else { continue };
+        let kind = dead_assets::document_kind(&document, None);
+        for reference in dead_assets::extract_references(&String::from_utf8_lossy(&content), kind) {
+            let root_path = link_root
+                .filter(|_| !reference.trim().starts_with("//"))
+                .and_then(|root| reference.trim().strip_prefix(root));
+            let target = match root_path {
+                Some(root_path) => dead_assets::resolve_reference(&document, &format!("/{}", root_path)),
+                None => dead_assets::resolve_reference(&document, &reference),
+            };
+            if let Some(target) = target.and_then(|target| saved_target(&files, &target)) {
+                visit(target, &mut live, &mut documents);
+            }
+        }
+    }
+
+    Ok(files
+        .into_iter()
+        .filter(|(path, _)| !live.contains(path))
+        .map(|(local_path, size)| OrphanedFile {
+            url: urls_by_path.get(&local_path).map(|url| url.to_string()),
+            local_path,
+            size,
+        })
+        .collect())
+}
+
+/// Delete orphaned files and the directories they leave empty, and drop them
+/// (and the references the deleted pages made) from the store
+pub fn delete_orphaned_files(output_dir: &Path, store: &mut DownloadStore, orphans: &[OrphanedFile]) -> Result<()> {
+    for orphan in orphans {
+        let path = output_dir.join(&orphan.local_path);
+        fs::remove_file(&path)
+            .with_context(|| format!("Failed to delete orphaned file: {:?}", path))?;
+        for dir in path.ancestors().skip(1).take_while(|dir| *dir != output_dir) {
+            if fs::remove_dir(dir).is_err() {
+                break;
+            }
+        }
+        if let Some(url) = &orphan.url {
+            store.remove_referrer(url);
+            store.entries.remove(url);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::write;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_prune_orphaned_files() {
+        let temp_dir = tempdir().unwrap();
+        let dir = temp_dir.path();
+        write(dir, "index.html", r#"<link href="css/site.css" rel="stylesheet"><a href="blog/">Blog</a><script src="js/app.js"></script>"#);
+        write(dir, "blog/index.html", r#"<img src="../img/post.png">"#);
+        write(dir, "css/site.css", ".hero { background: url(../img/hero.jpg); }");
+        write(dir, "js/app.js", "fetch('data/menu.json')");
+        write(dir, "data/menu.json", "{}");
+        write(dir, "img/post.png", "x");
+        write(dir, "img/hero.jpg", "x");
+        write(dir, "old/page.html", r#"<img src="../img/old.png">"#);
+        write(dir, "img/old.png", "xx");
+        write(dir, "report.json", "{}");
+
+        let mut store = DownloadStore::default();
+        store.record_download("https://example.com/", "index.html", Some("text/html"), 10);
+        store.record_download("https://example.com/data/menu.json", "data/menu.json", Some("application/json"), 2);
+        store.add_referrer("https://example.com/data/menu.json", "https://example.com/");
+        store.record_download("https://example.com/old/page.html", "old/page.html", Some("text/html"), 10);
+        store.add_referrer("https://example.com/old/page.html", "https://example.com/gone.html");
+        store.record_download("https://example.com/img/old.png", "img/old.png", Some("image/png"), 2);
+        store.add_referrer("https://example.com/img/old.png", "https://example.com/old/page.html");
+
+        let orphans = find_orphaned_files(dir, &store, None).unwrap();
+        let paths: Vec<&str> = orphans.iter().map(|orphan| orphan.local_path.as_str()).collect();
+        assert_eq!(paths, vec!["img/old.png", "old/page.html"]);
+        assert_eq!(orphans[0].url.as_deref(), Some("https://example.com/img/old.png"));
+
+        delete_orphaned_files(dir, &mut store, &orphans).unwrap();
+        assert!(!dir.join("old").exists());
+        assert!(dir.join("img/hero.jpg").exists());
+        assert!(!store.entries.contains_key("https://example.com/old/page.html"));
+        assert!(find_orphaned_files(dir, &store, None).unwrap().is_empty());
+        assert!(find_orphaned_files(dir, &DownloadStore::default(), None).is_err());
+    }
+}
@@ -0,0 +1,155 @@
+use anyhow::{Result, Context};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use std::time::Instant;
+
+/// File name of the request rate report, stored at the root of the output directory
+pub const REPORT_FILE_NAME: &str = "rate-report.json";
+
+/// Width of the buckets in a host's request timeline
+const TIMELINE_BUCKET_SECONDS: f64 = 60.0;
+
+/// When each request of the crawl was sent, per origin (`https://example.com`)
+#[derive(Debug, Clone)]
+pub struct RequestLog {
+    started: Instant,
+    /// Origin -> seconds since the log was created, in send order
+    requests: BTreeMap<String, Vec<f64>>,
+}
+
+impl Default for RequestLog {
+    fn default() -> Self {
+        Self {
+            started: Instant::now(),
+            requests: BTreeMap::new(),
+        }
+    }
+}
+
+impl RequestLog {
+    pub fn record(&mut self, origin: &str) {
+        let elapsed = self.started.elapsed().as_secs_f64();
+        self.requests.entry(origin.to_string()).or_default().push(elapsed);
+    }
+
+    pub fn origins(&self) -> impl Iterator<Item = &str> {
+        self.requests.keys().map(String::as_str)
+    }
+
+    /// Requests sent per origin
+    pub fn request_counts(&self) -> impl Iterator<Item = (&str, usize)> {
+        self.requests.iter().map(|(origin, times)| (origin.as_str(), times.len()))
+    }
+
+    /// Rates observed for every origin, checked against its robots.txt
+    /// `Crawl-delay` (origin -> seconds) where one was found
+    pub fn report(&self, crawl_delays: &BTreeMap<String, f64>) -> RateReport {
+        let hosts = self
+            .requests
+            .iter()
+            .map(|(host, times)| HostRate::from_times(host, times, crawl_delays.get(host).copied()))
+            .collect();
+        RateReport { hosts }
+    }
+}
+
+/// Request rate to one host over the crawl
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct HostRate {
+    /// Scheme, host and port the requests went to
+    pub host: String,
+    pub requests: usize,
+    /// Seconds between the first and the last request
+    pub duration_seconds: f64,
+    /// Most requests sent within any one-second window
+    pub max_requests_per_second: usize,
+    pub average_requests_per_second: f64,
+    /// Shortest gap between two consecutive requests
+    pub min_interval_seconds: Option<f64>,
+    /// Requests per minute of the crawl, starting with the first request to the host
+    pub requests_per_minute: Vec<usize>,
+    /// `Crawl-delay` from the host's robots.txt
+    pub crawl_delay_seconds: Option<f64>,
+    /// Whether consecutive requests were always at least `crawl_delay_seconds` apart
+    pub crawl_delay_honored: Option<bool>,
+}
+
+impl HostRate {
+    fn from_times(host: &str, times: &[f64], crawl_delay: Option<f64>) -> Self {
+        let mut times = times.to_vec();
+        times.sort_by(|a, b| a.total_cmp(b));
+        let first = times.first().copied().unwrap_or_default();
+        let duration = times.last().copied().unwrap_or_default() - first;
+
+        let mut max_requests_per_second = 0;
+        let mut window_start = 0;
+        for (end, time) in times.iter().enumerate() {
+            while time - times[window_start] >= 1.0 {
+                window_start += 1;
+            }
+            max_requests_per_second = max_requests_per_second.max(end + 1 - window_start);
+        }
+
+        let min_interval = times.windows(2).map(|pair| pair[1] - pair[0]).min_by(|a, b| a.total_cmp(b));
+
+        let mut requests_per_minute = vec![0; (duration / TIMELINE_BUCKET_SECONDS) as usize + 1];
+        for time in &times {
+            requests_per_minute[((time - first) / TIMELINE_BUCKET_SECONDS) as usize] += 1;
+        }
+
+        Self {
+            host: host.to_string(),
+            requests: times.len(),
+            duration_seconds: duration,
+            max_requests_per_second,
+            average_requests_per_second: if duration > 0.0 { times.len() as f64 / duration } else { times.len() as f64 },
+            min_interval_seconds: min_interval,
+            requests_per_minute,
+            crawl_delay_seconds: crawl_delay,
+            crawl_delay_honored: crawl_delay.map(|delay| min_interval.is_none_or(|interval| interval >= delay)),
+        }
+    }
+}
+
+/// Per-host request rates of a crawl, written to `rate-report.json` so
+/// archivists can show the crawl was polite
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct RateReport {
+    pub hosts: Vec<HostRate>,
+}
+
+impl RateReport {
+    /// Hosts whose robots.txt `Crawl-delay` was not honored
+    pub fn violations(&self) -> Vec<&HostRate> {
+        self.hosts.iter().filter(|host| host.crawl_delay_honored == Some(false)).collect()
+    }
+
+    pub fn save(&self, output_dir: &Path) -> Result<()> {
+        let path = output_dir.join(REPORT_FILE_NAME);
+        let data = serde_json::to_string_pretty(self)?;
+        fs::write(&path, data)
+            .with_context(|| format!("Failed to write rate report: {:?}", path))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_host_rate() {
+        let rate = HostRate::from_times("example.com", &[0.0, 0.2, 0.5, 0.9, 3.0, 65.0], Some(1.0));
+        assert_eq!(rate.requests, 6);
+        assert_eq!(rate.max_requests_per_second, 4);
+        assert_eq!(rate.requests_per_minute, vec![5, 1]);
+        assert_eq!(rate.min_interval_seconds.map(|interval| (interval * 10.0).round() / 10.0), Some(0.2));
+        assert_eq!(rate.crawl_delay_honored, Some(false));
+
+        let polite = HostRate::from_times("example.com", &[0.0, 5.0, 10.0], Some(5.0));
+        assert_eq!(polite.max_requests_per_second, 1);
+        assert_eq!(polite.crawl_delay_honored, Some(true));
+        assert_eq!(HostRate::from_times("example.com", &[0.0], None).crawl_delay_honored, None);
+    }
+}
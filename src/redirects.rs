@@ -0,0 +1,114 @@
+use anyhow::{Result, Context};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use crate::gallery::escape_html;
+
+/// File name of the redirect mapping, stored at the root of the output directory
+pub const REDIRECTS_FILE_NAME: &str = "redirects.json";
+
+/// Redirects followed for a single request before giving up (reqwest's default)
+pub const MAX_REDIRECTS: usize = 10;
+
+/// Where a redirected URL ended up
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Redirect {
+    /// Final URL, under which the content was saved
+    pub target: String,
+    /// Every URL the request was redirected through, ending with `target`
+    pub chain: Vec<String>,
+}
+
+/// Requested URL -> redirect, saved as `redirects.json`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct RedirectMap {
+    pub redirects: BTreeMap<String, Redirect>,
+}
+
+impl RedirectMap {
+    /// Record that `from` was redirected through `chain` (ending at the final URL)
+    pub fn record(&mut self, from: &str, chain: Vec<String>) {
+        let Some(target) = chain.last().cloned() else {
+            return;
+        };
+        self.redirects.insert(from.to_string(), Redirect { target, chain });
+    }
+
+    /// Final URL `url` redirects to, if it was redirected
+    pub fn resolve(&self, url: &str) -> Option<&str> {
+        self.redirects.get(url).map(|redirect| redirect.target.as_str())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.redirects.is_empty()
+    }
+
+    pub fn save(&self, output_dir: &Path) -> Result<()> {
+        let path = output_dir.join(REDIRECTS_FILE_NAME);
+        let data = serde_json::to_string_pretty(self)?;
+        fs::write(&path, data)
+            .with_context(|| format!("Failed to write redirect map: {:?}", path))
+    }
+}
+
+/// Page left at a redirected URL's old path that forwards to its new location
+pub fn stub_page(target: &str) -> String {
+    let target = escape_html(target);
+    format!(
+        r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<meta http-equiv="refresh" content="0; url={0}">
+<link rel="canonical" href="{0}">
+<title>Redirecting</title>
+</head>
+<body>
+<p>This page has moved to <a href="{0}">{0}</a>.</p>
+</body>
+</html>
+"#,
+        target
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_record_and_resolve() {
+        let mut redirects = RedirectMap::default();
+        redirects.record(
+            "http://example.com/old",
+            vec!["https://example.com/old".to_string(), "https://example.com/new/".to_string()],
+        );
+        redirects.record("https://example.com/empty", Vec::new());
+
+        assert_eq!(redirects.resolve("http://example.com/old"), Some("https://example.com/new/"));
+        assert_eq!(redirects.resolve("https://example.com/new/"), None);
+        assert_eq!(redirects.resolve("https://example.com/empty"), None);
+    }
+
+    #[test]
+    fn test_save_redirect_map() {
+        let temp_dir = tempdir().unwrap();
+        let mut redirects = RedirectMap::default();
+        redirects.record("https://example.com/old", vec!["https://example.com/new".to_string()]);
+        redirects.save(temp_dir.path()).unwrap();
+
+        let saved: serde_json::Value = serde_json::from_str(&fs::read_to_string(temp_dir.path().join(REDIRECTS_FILE_NAME)).unwrap()).unwrap();
+        assert_eq!(saved["https://example.com/old"]["target"], "https://example.com/new");
+    }
+
+    #[test]
+    fn test_stub_page() {
+        let stub = stub_page("../new/index.html?a=1&b=2");
+        assert!(stub.contains(r#"<meta http-equiv="refresh" content="0; url=../new/index.html?a=1&amp;b=2">"#));
+        assert!(stub.contains(r#"<a href="../new/index.html?a=1&amp;b=2">"#));
+    }
+}
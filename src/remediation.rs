@@ -0,0 +1,90 @@
+use crate::circuit_breaker::FailureKind;
+use crate::robots;
+
+const UNAUTHORIZED: &str = "The server wants credentials: pass --token-command to send (and refresh) a bearer token";
+const FORBIDDEN: &str = "Forbidden, often a WAF or bot filter rejecting the crawler: lower --max-concurrent (e.g. -c 2) so the crawl looks less like an attack, or ask the site owner to allowlist it";
+const NOT_FOUND: &str = "The link is broken on the live site too; nothing to fix in the mirror";
+const RATE_LIMITED: &str = "Rate limited: lower --max-concurrent (e.g. -c 2) and re-run; --rate-report shows how hard each host was hit";
+const UNAVAILABLE: &str = "The server is overloaded or down for maintenance: re-run later, with a lower --max-concurrent if it keeps happening";
+const SERVER_ERROR: &str = "Server error, usually transient: re-run later, with a lower --max-concurrent if it keeps happening";
+const TLS: &str = "TLS handshake or certificate check failed: check the site's certificate chain (e.g. a missing intermediate certificate), or mirror the http:// URL if the site serves one";
+const DNS: &str = "The host name doesn't resolve: check the URL for typos, or --exclude-domains the host if it is gone for good";
+const TIMEOUT: &str = "The server is slow to answer: lower --max-concurrent; hosts that keep timing out are skipped after --circuit-breaker-threshold failures";
+const CONNECTION: &str = "The server refused or dropped the connection, often because too many were open at once: lower --max-concurrent";
+const CIRCUIT_OPEN: &str = "The host was considered down after repeated connection failures: if it was a brief outage, re-run or raise --circuit-breaker-threshold (0 disables it)";
+const ROBOTS_TXT: &str = "Disallowed by robots.txt: with the site owner's permission, --ignore-robots archives it anyway";
+const META_ROBOTS: &str = "The page asks not to be archived; drop --respect-noarchive to archive it anyway";
+const PAGE_BUDGET: &str = "Over the page budget: raise --max-pages to mirror more of the site";
+const SIZE_BUDGET: &str = "Over the output size budget: raise --max-total-size to mirror more of the site";
+const FILE_TOO_LARGE: &str = "Larger than --max-file-size: raise it to keep files this big";
+
+/// An actionable suggestion for a URL that couldn't be fetched, from its
+/// HTTP status or, when the server never answered, its error message
+pub fn failure_hint(status: Option<u16>, error: &str) -> Option<&'static str> {
+    match status {
+        Some(401) => return Some(UNAUTHORIZED),
+        Some(403) => return Some(FORBIDDEN),
+        Some(404) | Some(410) => return Some(NOT_FOUND),
+        Some(429) => return Some(RATE_LIMITED),
+        Some(503) => return Some(UNAVAILABLE),
+        Some(500..=599) => return Some(SERVER_ERROR),
+        Some(_) => return None,
+        None => {}
+    }
+    if error.contains("circuit breaker open") {
+        return Some(CIRCUIT_OPEN);
+    }
+    match FailureKind::classify_message(error) {
+        FailureKind::Tls => Some(TLS),
+        FailureKind::Dns => Some(DNS),
+        FailureKind::Timeout => Some(TIMEOUT),
+        FailureKind::ConnectionReset | FailureKind::ConnectionRefused => Some(CONNECTION),
+        FailureKind::Other => None,
+    }
+}
+
+/// An actionable suggestion for a URL deliberately not archived, from the
+/// skip reason recorded in the crawl manifest
+pub fn skip_hint(reason: &str) -> Option<&'static str> {
+    if reason.starts_with(robots::ROBOTS_TXT_REASON) {
+        Some(ROBOTS_TXT)
+    } else if reason.starts_with(robots::META_ROBOTS_REASON) {
+        Some(META_ROBOTS)
+    } else if reason.contains("--max-pages") {
+        Some(PAGE_BUDGET)
+    } else if reason.contains("--max-total-size") {
+        Some(SIZE_BUDGET)
+    } else if reason.contains("--max-file-size") {
+        Some(FILE_TOO_LARGE)
+    } else if reason.contains("circuit breaker open") {
+        Some(CIRCUIT_OPEN)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_failure_hint() {
+        assert_eq!(failure_hint(Some(429), "HTTP 429 Too Many Requests"), Some(RATE_LIMITED));
+        assert_eq!(failure_hint(Some(403), "HTTP 403 Forbidden"), Some(FORBIDDEN));
+        assert_eq!(failure_hint(Some(502), "HTTP 502 Bad Gateway"), Some(SERVER_ERROR));
+        assert_eq!(failure_hint(Some(418), "HTTP 418"), None);
+        assert_eq!(failure_hint(None, "TLS error: invalid peer certificate: UnknownIssuer"), Some(TLS));
+        assert_eq!(failure_hint(None, "timeout error: operation timed out"), Some(TIMEOUT));
+        assert_eq!(failure_hint(None, "Skipped: circuit breaker open for example.com after 5 consecutive timeout failures"), Some(CIRCUIT_OPEN));
+        assert_eq!(failure_hint(None, "Failed to save: disk full"), None);
+    }
+
+    #[test]
+    fn test_skip_hint() {
+        assert_eq!(skip_hint("robots.txt Disallow: /admin/"), Some(ROBOTS_TXT));
+        assert_eq!(skip_hint("meta robots noarchive"), Some(META_ROBOTS));
+        assert_eq!(skip_hint("over --max-pages budget"), Some(PAGE_BUDGET));
+        assert_eq!(skip_hint("larger than --max-file-size 50.0 MB"), Some(FILE_TOO_LARGE));
+        assert_eq!(skip_hint("denied in review"), None);
+    }
+}
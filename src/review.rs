@@ -0,0 +1,187 @@
+use anyhow::{anyhow, Result, Context};
+use std::fs;
+use std::io::{BufRead, Write};
+use std::path::{Path, PathBuf};
+
+/// Seconds between re-reads of the review file while URLs wait for a decision
+pub const REVIEW_POLL_SECONDS: u64 = 2;
+
+/// Whether the curator lets a page into the mirror
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReviewDecision {
+    Allow,
+    Deny,
+}
+
+/// Allow/deny rules from a review file: `+ <url prefix>` allows, `- <url prefix>`
+/// denies, a bare URL prefix allows and `#` starts a comment. The longest
+/// matching prefix wins.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ReviewRules {
+    pub rules: Vec<(String, ReviewDecision)>,
+}
+
+impl ReviewRules {
+    pub fn parse(text: &str) -> Self {
+        let rules = text
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(|line| {
+                if let Some(prefix) = line.strip_prefix('+') {
+                    (prefix.trim().to_string(), ReviewDecision::Allow)
+                } else if let Some(prefix) = line.strip_prefix('-') {
+                    (prefix.trim().to_string(), ReviewDecision::Deny)
+                } else {
+                    (line.to_string(), ReviewDecision::Allow)
+                }
+            })
+            .filter(|(prefix, _)| !prefix.is_empty())
+            .collect();
+        Self { rules }
+    }
+
+    /// Load the rules, treating a file that doesn't exist yet as empty
+    pub fn load(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let text = fs::read_to_string(path).with_context(|| format!("Failed to read review file: {:?}", path))?;
+        Ok(Self::parse(&text))
+    }
+
+    pub fn decide(&self, url: &str) -> Option<ReviewDecision> {
+        self.rules
+            .iter()
+            .filter(|(prefix, _)| url.starts_with(prefix.as_str()))
+            .max_by_key(|(prefix, _)| prefix.len())
+            .map(|(_, decision)| *decision)
+    }
+}
+
+/// How newly discovered pages are put in front of the curator (`--review`)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Reviewer {
+    /// Prompt on the terminal for each batch
+    Interactive,
+    /// Wait until an allowlist file, edited while the crawl runs, decides every page
+    File(PathBuf),
+}
+
+impl Reviewer {
+    /// `<review file>.pending`, listing the URLs still waiting for a decision
+    pub fn pending_path(file: &Path) -> PathBuf {
+        let mut name = file.as_os_str().to_owned();
+        name.push(".pending");
+        PathBuf::from(name)
+    }
+}
+
+/// Parse page numbers like `1,3-5 7` (1-based) within a batch of `batch_len`
+fn parse_numbers(text: &str, batch_len: usize) -> Result<Vec<usize>> {
+    let mut numbers = Vec::new();
+    for part in text.split(|c: char| c == ',' || c.is_whitespace()).filter(|part| !part.is_empty()) {
+        let (start, end) = match part.split_once('-') {
+            Some((start, end)) => (start.trim().parse::<usize>()?, end.trim().parse::<usize>()?),
+            None => {
+                let number = part.parse::<usize>()?;
+                (number, number)
+            }
+        };
+        if start == 0 || end > batch_len || start > end {
+            return Err(anyhow!("{} is not within 1-{}", part, batch_len));
+        }
+        numbers.extend(start..=end);
+    }
+    Ok(numbers)
+}
+
+/// Decisions for a batch from a prompt answer: empty/`y` allows every page,
+/// `n` denies every page, `d 2,4-6` denies the listed pages and `k 1 3` keeps
+/// only the listed ones
+pub fn parse_answer(answer: &str, batch_len: usize) -> Result<Vec<ReviewDecision>> {
+    let answer = answer.trim();
+    let (command, numbers) = answer.split_at(answer.find(char::is_whitespace).unwrap_or(answer.len()));
+    let (listed, others) = match command.to_ascii_lowercase().as_str() {
+        "" | "y" | "yes" => return Ok(vec![ReviewDecision::Allow; batch_len]),
+        "n" | "no" => return Ok(vec![ReviewDecision::Deny; batch_len]),
+        "d" | "deny" => (ReviewDecision::Deny, ReviewDecision::Allow),
+        "k" | "keep" => (ReviewDecision::Allow, ReviewDecision::Deny),
+        _ => return Err(anyhow!("Unknown answer: {}", answer)),
+    };
+    let numbers = parse_numbers(numbers, batch_len)?;
+    Ok((1..=batch_len).map(|number| if numbers.contains(&number) { listed } else { others }).collect())
+}
+
+/// Ask the curator about a batch of page URLs on `output`, reading answers
+/// from `input` until one parses; when input runs out the batch is denied
+pub fn prompt(urls: &[String], input: &mut impl BufRead, output: &mut impl Write) -> Result<Vec<ReviewDecision>> {
+    loop {
+        writeln!(output, "📝 {} newly discovered pages to review:", urls.len())?;
+        for (number, url) in urls.iter().enumerate() {
+            writeln!(output, "   {:>3}. {}", number + 1, url)?;
+        }
+        write!(output, "Crawl them? [Enter/y = all, n = none, d 2,4-6 = deny listed, k 1 3 = keep only listed] ")?;
+        output.flush()?;
+        let mut answer = String::new();
+        if input.read_line(&mut answer)? == 0 {
+            writeln!(output, "⚠️  No input available, denying the batch")?;
+            return Ok(vec![ReviewDecision::Deny; urls.len()]);
+        }
+        match parse_answer(&answer, urls.len()) {
+            Ok(decisions) => return Ok(decisions),
+            Err(e) => writeln!(output, "⚠️  {}", e)?,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ReviewDecision::{Allow, Deny};
+
+    #[test]
+    fn test_review_rules() {
+        let rules = ReviewRules::parse("# curated\nhttps://example.com/blog/\n- https://example.com/blog/drafts/\n+ https://example.com/blog/drafts/published-\n\n");
+        assert_eq!(rules.decide("https://example.com/blog/2024/post/"), Some(Allow));
+        assert_eq!(rules.decide("https://example.com/blog/drafts/secret/"), Some(Deny));
+        assert_eq!(rules.decide("https://example.com/blog/drafts/published-1/"), Some(Allow));
+        assert_eq!(rules.decide("https://example.com/hr/"), None);
+    }
+
+    #[test]
+    fn test_parse_answer() {
+        assert_eq!(parse_answer("", 2).unwrap(), vec![Allow, Allow]);
+        assert_eq!(parse_answer("n", 2).unwrap(), vec![Deny, Deny]);
+        assert_eq!(parse_answer("d 2,4-5", 5).unwrap(), vec![Allow, Deny, Allow, Deny, Deny]);
+        assert_eq!(parse_answer("k 1 3", 3).unwrap(), vec![Allow, Deny, Allow]);
+        assert!(parse_answer("d 6", 5).is_err());
+        assert!(parse_answer("maybe", 5).is_err());
+    }
+
+    #[test]
+    fn test_prompt_asks_again_until_answer_parses() {
+        let urls = vec!["https://example.com/a/".to_string(), "https://example.com/b/".to_string()];
+        let mut output = Vec::new();
+        let decisions = prompt(&urls, &mut "maybe\nd 3\nd 2\n".as_bytes(), &mut output).unwrap();
+        assert_eq!(decisions, vec![Allow, Deny]);
+        let output = String::from_utf8(output).unwrap();
+        assert_eq!(output.matches("2 newly discovered pages to review").count(), 3);
+        assert!(output.contains("     2. https://example.com/b/"));
+        assert!(output.contains("Unknown answer: maybe"));
+        assert!(output.contains("3 is not within 1-2"));
+    }
+
+    #[test]
+    fn test_prompt_denies_batch_without_input() {
+        let urls = vec!["https://example.com/a/".to_string()];
+        assert_eq!(prompt(&urls, &mut "".as_bytes(), &mut Vec::new()).unwrap(), vec![Deny]);
+        // An answer cut off by the end of input still counts
+        assert_eq!(prompt(&urls, &mut "y".as_bytes(), &mut Vec::new()).unwrap(), vec![Allow]);
+    }
+
+    #[test]
+    fn test_pending_path() {
+        assert_eq!(Reviewer::pending_path(Path::new("review.txt")), PathBuf::from("review.txt.pending"));
+    }
+}
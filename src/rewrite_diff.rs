@@ -0,0 +1,171 @@
+use anyhow::{Result, Context};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Directory (inside the output directory) holding the rewrite diffs of saved pages
+pub const DIFF_DIR: &str = "_diffs";
+
+/// Unchanged lines shown around each change
+const CONTEXT_LINES: usize = 3;
+
+/// Largest table the line matching may use; bigger differences are shown as
+/// the whole changed region removed and re-added
+const MAX_LCS_CELLS: usize = 4_000_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Line<'a> {
+    Same(&'a str),
+    Removed(&'a str),
+    Added(&'a str),
+}
+
+/// Lines removed and added by a rewrite
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DiffStats {
+    pub removed: usize,
+    pub added: usize,
+}
+
+/// Line-by-line edit script from `old` to `new`
+fn diff_lines<'a>(old: &[&'a str], new: &[&'a str]) -> Vec<Line<'a>> {
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..].iter().rev().zip(new[prefix..].iter().rev()).take_while(|(a, b)| a == b).count();
+    let (old_middle, new_middle) = (&old[prefix..old.len() - suffix], &new[prefix..new.len() - suffix]);
+
+    let mut lines: Vec<Line> = old[..prefix].iter().map(|line| Line::Same(line)).collect();
+    let (n, m) = (old_middle.len(), new_middle.len());
+    if (n + 1) * (m + 1) > MAX_LCS_CELLS {
+        lines.extend(old_middle.iter().map(|line| Line::Removed(line)));
+        lines.extend(new_middle.iter().map(|line| Line::Added(line)));
+    } else {
+        let mut common = vec![vec![0usize; m + 1]; n + 1];
+        for i in (0..n).rev() {
+            for j in (0..m).rev() {
+                common[i][j] = if old_middle[i] == new_middle[j] {
+                    common[i + 1][j + 1] + 1
+                } else {
+                    common[i + 1][j].max(common[i][j + 1])
+                };
+            }
+        }
+        let (mut i, mut j) = (0, 0);
+        while i < n || j < m {
+            if i < n && j < m && old_middle[i] == new_middle[j] {
+                lines.push(Line::Same(old_middle[i]));
+                i += 1;
+                j += 1;
+            } else if i < n && (j == m || common[i + 1][j] >= common[i][j + 1]) {
+                lines.push(Line::Removed(old_middle[i]));
+                i += 1;
+            } else {
+                lines.push(Line::Added(new_middle[j]));
+                j += 1;
+            }
+        }
+    }
+    lines.extend(old[old.len() - suffix..].iter().map(|line| Line::Same(line)));
+    lines
+}
+
+/// `start,count` of a hunk side; empty sides start at the line before the hunk
+fn hunk_range(lines_before: usize, count: usize) -> String {
+    let start = if count == 0 { lines_before } else { lines_before + 1 };
+    format!("{},{}", start, count)
+}
+
+/// Unified diff (`diff -u`) of a page before and after rewriting, empty if
+/// nothing changed
+pub fn unified_diff(before: &str, after: &str, path: &str) -> (String, DiffStats) {
+    let old: Vec<&str> = before.lines().collect();
+    let new: Vec<&str> = after.lines().collect();
+    let lines = diff_lines(&old, &new);
+
+    let changes: Vec<usize> = (0..lines.len()).filter(|&index| !matches!(lines[index], Line::Same(_))).collect();
+    let mut stats = DiffStats::default();
+    if changes.is_empty() {
+        return (String::new(), stats);
+    }
+
+    // Changes closer together than twice the context share a hunk
+    let mut hunks: Vec<(usize, usize)> = Vec::new();
+    for &index in &changes {
+        let start = index.saturating_sub(CONTEXT_LINES);
+        let end = (index + CONTEXT_LINES + 1).min(lines.len());
+        match hunks.last_mut() {
+            Some(hunk) if start <= hunk.1 => hunk.1 = end,
+            _ => hunks.push((start, end)),
+        }
+    }
+
+    let mut diff = format!("--- a/{}\n+++ b/{}\n", path, path);
+    let (mut old_line, mut new_line, mut position) = (0, 0, 0);
+    for (start, end) in hunks {
+        for line in &lines[position..start] {
+            if let Line::Same(_) = line {
+                old_line += 1;
+                new_line += 1;
+            }
+        }
+        let old_count = lines[start..end].iter().filter(|line| !matches!(line, Line::Added(_))).count();
+        let new_count = lines[start..end].iter().filter(|line| !matches!(line, Line::Removed(_))).count();
+        diff.push_str(&format!("@@ -{} +{} @@\n", hunk_range(old_line, old_count), hunk_range(new_line, new_count)));
+        for line in &lines[start..end] {
+            match line {
+                Line::Same(text) => diff.push_str(&format!(" {}\n", text)),
+                Line::Removed(text) => {
+                    diff.push_str(&format!("-{}\n", text));
+                    stats.removed += 1;
+                }
+                Line::Added(text) => {
+                    diff.push_str(&format!("+{}\n", text));
+                    stats.added += 1;
+                }
+            }
+        }
+        old_line += old_count;
+        new_line += new_count;
+        position = end;
+    }
+    (diff, stats)
+}
+
+/// Write the diff of the page saved at `local_path` to `_diffs/<local_path>.diff`
+pub fn save_diff(output_dir: &Path, local_path: &str, diff: &str) -> Result<PathBuf> {
+    let path = output_dir.join(DIFF_DIR).join(format!("{}.diff", local_path.trim_start_matches('/')));
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).with_context(|| format!("Failed to create directory: {:?}", parent))?;
+    }
+    fs::write(&path, diff).with_context(|| format!("Failed to write rewrite diff: {:?}", path))?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unified_diff() {
+        let before = "<html>\n<head>\n<link href=\"https://example.com/css/site.css\">\n</head>\n<body>\n<p>one</p>\n<p>two</p>\n<p>three</p>\n<p>four</p>\n<p>five</p>\n<p>six</p>\n<p>seven</p>\n<a href=\"https://example.com/about/\">About</a>\n</body>\n</html>\n";
+        let after = before
+            .replace("https://example.com/css/site.css", "css/site.css")
+            .replace("https://example.com/about/", "about/index.html");
+        let (diff, stats) = unified_diff(before, &after, "index.html");
+        assert_eq!(stats, DiffStats { removed: 2, added: 2 });
+        assert_eq!(
+            diff,
+            "--- a/index.html\n+++ b/index.html\n\
+             @@ -1,6 +1,6 @@\n <html>\n <head>\n-<link href=\"https://example.com/css/site.css\">\n+<link href=\"css/site.css\">\n </head>\n <body>\n <p>one</p>\n\
+             @@ -10,6 +10,6 @@\n <p>five</p>\n <p>six</p>\n <p>seven</p>\n-<a href=\"https://example.com/about/\">About</a>\n+<a href=\"about/index.html\">About</a>\n </body>\n </html>\n"
+        );
+        assert_eq!(unified_diff(before, before, "index.html").0, "");
+    }
+
+    #[test]
+    fn test_diff_added_lines() {
+        let (diff, stats) = unified_diff("<head>\n</head>\n", "<head>\n<meta charset=\"utf-8\">\n</head>\n", "a.html");
+        assert_eq!(stats, DiffStats { removed: 0, added: 1 });
+        assert!(diff.ends_with("@@ -1,2 +1,3 @@\n <head>\n+<meta charset=\"utf-8\">\n </head>\n"));
+        let (diff, _) = unified_diff("", "<p>new</p>", "b.html");
+        assert!(diff.contains("@@ -0,0 +1,1 @@\n+<p>new</p>\n"));
+    }
+}
@@ -0,0 +1,188 @@
+use regex::Regex;
+use std::collections::BTreeMap;
+
+/// Product token matched against `User-agent` lines in robots.txt
+pub const ROBOTS_USER_AGENT: &str = "WebsiteMirror";
+
+/// Prefix of the skip reasons recorded for pages robots.txt disallows
+pub const ROBOTS_TXT_REASON: &str = "robots.txt";
+
+/// Prefix of the skip reasons recorded for pages excluded by `<meta name="robots">`
+pub const META_ROBOTS_REASON: &str = "meta robots";
+
+/// An `Allow` or `Disallow` line of robots.txt
+#[derive(Debug, Clone)]
+pub struct RobotsRule {
+    pub allow: bool,
+    /// Path pattern as written (`/private/`, `/*.pdf$`)
+    pub pattern: String,
+    regex: Regex,
+}
+
+impl RobotsRule {
+    fn new(allow: bool, pattern: &str) -> Self {
+        let anchored = pattern.ends_with('$');
+        let body = pattern.trim_end_matches('$');
+        let mut regex = String::from("^");
+        regex.push_str(&body.split('*').map(regex::escape).collect::<Vec<_>>().join(".*"));
+        if anchored {
+            regex.push('$');
+        }
+        Self {
+            allow,
+            pattern: pattern.to_string(),
+            regex: Regex::new(&regex).unwrap(),
+        }
+    }
+
+    /// The rule as it appears in robots.txt, e.g. `Disallow: /private/`
+    pub fn describe(&self) -> String {
+        format!("{}: {}", if self.allow { "Allow" } else { "Disallow" }, self.pattern)
+    }
+}
+
+/// The robots.txt group that applies to one user agent: the groups naming the
+/// agent, else the `*` groups
+#[derive(Debug, Clone, Default)]
+pub struct RobotsRules {
+    pub rules: Vec<RobotsRule>,
+    pub crawl_delay: Option<f64>,
+}
+
+#[derive(Default)]
+struct Group {
+    agents: Vec<String>,
+    rules: Vec<RobotsRule>,
+    crawl_delay: Option<f64>,
+}
+
+impl RobotsRules {
+    pub fn parse(robots_txt: &str, user_agent: &str) -> Self {
+        let mut groups: Vec<Group> = Vec::new();
+        let mut in_agent_lines = false;
+        for line in robots_txt.lines() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            let Some((field, value)) = line.split_once(':') else { continue };
+            let value = value.trim();
+            let field = field.trim().to_ascii_lowercase();
+            if field == "user-agent" {
+                if !in_agent_lines {
+                    groups.push(Group::default());
+                    in_agent_lines = true;
+                }
+                if let Some(group) = groups.last_mut() {
+                    group.agents.push(value.to_ascii_lowercase());
+                }
+                continue;
+            }
+            in_agent_lines = false;
+            let Some(group) = groups.last_mut() else { continue };
+            match field.as_str() {
+                "allow" | "disallow" if !value.is_empty() => group.rules.push(RobotsRule::new(field == "allow", value)),
+                "crawl-delay" => {
+                    if let Ok(delay) = value.parse::<f64>() {
+                        group.crawl_delay.get_or_insert(delay);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        let user_agent = user_agent.to_ascii_lowercase();
+        let names_agent = |group: &Group| group.agents.iter().any(|agent| !agent.is_empty() && agent != "*" && user_agent.contains(agent.as_str()));
+        let selected: Vec<Group> = if groups.iter().any(names_agent) {
+            groups.into_iter().filter(names_agent).collect()
+        } else {
+            groups.into_iter().filter(|group| group.agents.iter().any(|agent| agent == "*")).collect()
+        };
+        Self {
+            crawl_delay: selected.iter().find_map(|group| group.crawl_delay),
+            rules: selected.into_iter().flat_map(|group| group.rules).collect(),
+        }
+    }
+
+    /// The `Disallow` rule keeping `path` (path and query of a URL) out of the
+    /// crawl; the longest matching pattern wins and `Allow` wins ties
+    pub fn blocking_rule(&self, path: &str) -> Option<&RobotsRule> {
+        self.rules
+            .iter()
+            .filter(|rule| rule.regex.is_match(path))
+            .max_by_key(|rule| (rule.pattern.len(), rule.allow))
+            .filter(|rule| !rule.allow)
+    }
+}
+
+/// Pages kept out of the mirror by one robots rule
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RobotsExclusion {
+    /// The recorded skip reason, e.g. `robots.txt Disallow: /private/` or `meta robots noarchive`
+    pub rule: String,
+    pub count: usize,
+    /// One of the excluded URLs
+    pub example: String,
+}
+
+/// Group the skipped URLs excluded by robots.txt or meta robots by rule, most
+/// excluded pages first
+pub fn summarize_exclusions(skipped: &BTreeMap<String, String>) -> Vec<RobotsExclusion> {
+    let mut by_rule: BTreeMap<&str, RobotsExclusion> = BTreeMap::new();
+    for (url, reason) in skipped {
+        if !reason.starts_with(ROBOTS_TXT_REASON) && !reason.starts_with(META_ROBOTS_REASON) {
+            continue;
+        }
+        by_rule
+            .entry(reason)
+            .or_insert_with(|| RobotsExclusion {
+                rule: reason.clone(),
+                count: 0,
+                example: url.clone(),
+            })
+            .count += 1;
+    }
+    let mut exclusions: Vec<RobotsExclusion> = by_rule.into_values().collect();
+    exclusions.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.rule.cmp(&b.rule)));
+    exclusions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ROBOTS_TXT: &str = "User-agent: *\nDisallow: /private/\nCrawl-delay: 10\n\nUser-agent: Googlebot\nUser-agent: WebsiteMirror\nDisallow: /drafts/\nAllow: /drafts/published\nDisallow: /*.pdf$\nCrawl-delay: 2.5 # be nice\n";
+
+    #[test]
+    fn test_parse_groups() {
+        let rules = RobotsRules::parse(ROBOTS_TXT, ROBOTS_USER_AGENT);
+        assert_eq!(rules.crawl_delay, Some(2.5));
+        assert_eq!(rules.rules.len(), 3);
+        let other = RobotsRules::parse(ROBOTS_TXT, "OtherBot");
+        assert_eq!(other.crawl_delay, Some(10.0));
+        assert_eq!(other.blocking_rule("/private/notes").map(RobotsRule::describe), Some("Disallow: /private/".to_string()));
+        assert!(RobotsRules::parse("User-agent: *\nDisallow:\n", ROBOTS_USER_AGENT).rules.is_empty());
+    }
+
+    #[test]
+    fn test_blocking_rule() {
+        let rules = RobotsRules::parse(ROBOTS_TXT, ROBOTS_USER_AGENT);
+        assert_eq!(rules.blocking_rule("/drafts/secret").map(|rule| rule.pattern.as_str()), Some("/drafts/"));
+        assert!(rules.blocking_rule("/drafts/published-1").is_none());
+        assert_eq!(rules.blocking_rule("/files/report.pdf").map(|rule| rule.pattern.as_str()), Some("/*.pdf$"));
+        assert!(rules.blocking_rule("/files/report.pdf?download=1").is_none());
+        assert!(rules.blocking_rule("/private/").is_none());
+    }
+
+    #[test]
+    fn test_summarize_exclusions() {
+        let mut skipped = BTreeMap::new();
+        skipped.insert("https://example.com/a".to_string(), "meta robots noarchive".to_string());
+        skipped.insert("https://example.com/private/1".to_string(), "robots.txt Disallow: /private/".to_string());
+        skipped.insert("https://example.com/private/2".to_string(), "robots.txt Disallow: /private/".to_string());
+        skipped.insert("https://example.com/big.iso".to_string(), "larger than --max-file-size 1.0 MB".to_string());
+        let exclusions = summarize_exclusions(&skipped);
+        assert_eq!(exclusions.len(), 2);
+        assert_eq!(exclusions[0].rule, "robots.txt Disallow: /private/");
+        assert_eq!(exclusions[0].count, 2);
+        assert_eq!(exclusions[0].example, "https://example.com/private/1");
+        assert_eq!(exclusions[1].count, 1);
+    }
+}
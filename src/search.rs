@@ -0,0 +1,185 @@
+use anyhow::Result;
+use regex::Regex;
+use std::path::Path;
+
+use crate::store::DownloadStore;
+
+/// Directory inside the output holding the full-text index of saved pages
+pub const INDEX_DIR: &str = ".search_index";
+
+/// Results shown by `website-mirror search` unless `--limit` says otherwise
+pub const DEFAULT_LIMIT: usize = 20;
+
+/// A saved page matching a search query
+#[derive(Debug, Clone, PartialEq)]
+pub struct SearchHit {
+    pub url: String,
+    pub local_path: String,
+    pub title: String,
+    /// Passage of the page around the matched terms
+    pub snippet: String,
+    pub score: f32,
+}
+
+/// Text of a page's `<title>`, whitespace collapsed
+pub fn page_title(html_content: &str) -> String {
+    let title_regex = Regex::new(r"(?is)<title\b[^>]*>(.*?)</title\s*>").unwrap();
+    title_regex
+        .captures(html_content)
+        .map(|caps| caps[1].split_whitespace().collect::<Vec<_>>().join(" "))
+        .unwrap_or_default()
+}
+
+#[cfg(feature = "search")]
+mod index {
+    use anyhow::{anyhow, Context, Result};
+    use std::fs;
+    use std::path::Path;
+    use tantivy::collector::TopDocs;
+    use tantivy::query::QueryParser;
+    use tantivy::schema::{Schema, STORED, STRING, TEXT};
+    use tantivy::{doc, Document, Index, SnippetGenerator};
+
+    use super::{page_title, SearchHit, INDEX_DIR};
+    use crate::store::DownloadStore;
+    use crate::text_diff::extract_text_blocks;
+
+    /// Memory the index writer may buffer before flushing a segment
+    const WRITER_HEAP_BYTES: usize = 50_000_000;
+
+    fn text_field(document: &Document, schema: &Schema, name: &str) -> String {
+        schema
+            .get_field(name)
+            .ok()
+            .and_then(|field| document.get_first(field))
+            .and_then(|value| value.as_text())
+            .unwrap_or_default()
+            .to_string()
+    }
+
+    pub fn build_index(output_dir: &Path, store: &DownloadStore) -> Result<usize> {
+        let mut schema_builder = Schema::builder();
+        let url = schema_builder.add_text_field("url", STRING | STORED);
+        let local_path = schema_builder.add_text_field("local_path", STRING | STORED);
+        let title = schema_builder.add_text_field("title", TEXT | STORED);
+        let body = schema_builder.add_text_field("body", TEXT | STORED);
+        let schema = schema_builder.build();
+
+        let index_dir = output_dir.join(INDEX_DIR);
+        if index_dir.exists() {
+            fs::remove_dir_all(&index_dir).with_context(|| format!("Failed to clear {:?}", index_dir))?;
+        }
+        fs::create_dir_all(&index_dir)?;
+        let index = Index::create_in_dir(&index_dir, schema)?;
+        let mut writer = index.writer(WRITER_HEAP_BYTES)?;
+
+        let mut indexed = 0;
+        for (page_url, entry) in &store.entries {
+            let is_page = entry.local_path.ends_with(".html") || entry.content_type.as_deref().map_or(false, |c| c.starts_with("text/html"));
+            if entry.local_path.is_empty() || !is_page {
+                continue;
+            }
+            let Ok(content) = fs::read(output_dir.join(&entry.local_path)) else { continue };
+            let content = String::from_utf8_lossy(&content);
+            writer.add_document(doc!(
+                url => page_url.as_str(),
+                local_path => entry.local_path.as_str(),
+                title => page_title(&content),
+                body => extract_text_blocks(&content).join("\n"),
+            ))?;
+            indexed += 1;
+        }
+        writer.commit()?;
+        Ok(indexed)
+    }
+
+    pub fn search(output_dir: &Path, query: &str, limit: usize) -> Result<Vec<SearchHit>> {
+        let index_dir = output_dir.join(INDEX_DIR);
+        if !index_dir.is_dir() {
+            return Err(anyhow!("No search index in {:?}; mirror with --search-index first", output_dir));
+        }
+        let index = Index::open_in_dir(&index_dir)
+            .with_context(|| format!("Failed to open search index: {:?}", index_dir))?;
+        let schema = index.schema();
+        let title = schema.get_field("title")?;
+        let body = schema.get_field("body")?;
+
+        let searcher = index.reader()?.searcher();
+        let query = QueryParser::for_index(&index, vec![title, body]).parse_query(query)?;
+        let snippets = SnippetGenerator::create(&searcher, &*query, body)?;
+
+        let mut hits = Vec::new();
+        for (score, address) in searcher.search(&query, &TopDocs::with_limit(limit))? {
+            let document: Document = searcher.doc(address)?;
+            let snippet = snippets.snippet_from_doc(&document);
+            hits.push(SearchHit {
+                url: text_field(&document, &schema, "url"),
+                local_path: text_field(&document, &schema, "local_path"),
+                title: text_field(&document, &schema, "title"),
+                snippet: snippet.fragment().split_whitespace().collect::<Vec<_>>().join(" "),
+                score,
+            });
+        }
+        Ok(hits)
+    }
+}
+
+/// Index the title and visible text of every saved page into `.search_index/`,
+/// replacing the previous run's index. Returns the number of pages indexed.
+#[cfg(feature = "search")]
+pub fn build_index(output_dir: &Path, store: &DownloadStore) -> Result<usize> {
+    index::build_index(output_dir, store)
+}
+
+#[cfg(not(feature = "search"))]
+pub fn build_index(_output_dir: &Path, _store: &DownloadStore) -> Result<usize> {
+    Err(anyhow::anyhow!("Search indexing requires building with `--features search`"))
+}
+
+/// Best-matching saved pages for `query` (tantivy query syntax: `privacy AND policy`,
+/// `"exact phrase"`, `title:pricing`)
+#[cfg(feature = "search")]
+pub fn search(output_dir: &Path, query: &str, limit: usize) -> Result<Vec<SearchHit>> {
+    index::search(output_dir, query, limit)
+}
+
+#[cfg(not(feature = "search"))]
+pub fn search(_output_dir: &Path, _query: &str, _limit: usize) -> Result<Vec<SearchHit>> {
+    Err(anyhow::anyhow!("Search requires building with `--features search`"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_page_title() {
+        assert_eq!(page_title("<html><head><TITLE lang=\"en\">\n  Privacy\n  Policy </TITLE></head></html>"), "Privacy Policy");
+        assert_eq!(page_title("<p>no title</p>"), "");
+    }
+
+    #[cfg(feature = "search")]
+    #[test]
+    fn test_build_index_and_search() {
+        use std::fs;
+        use tempfile::tempdir;
+
+        let temp_dir = tempdir().unwrap();
+        let dir = temp_dir.path();
+        fs::create_dir_all(dir.join("legal")).unwrap();
+        fs::write(dir.join("index.html"), "<title>Home</title><p>Welcome to our shop</p>").unwrap();
+        fs::write(dir.join("legal/index.html"), "<title>Privacy Policy</title><p>We never sell your personal data.</p>").unwrap();
+        let mut store = DownloadStore::default();
+        store.record_download("https://example.com/", "index.html", Some("text/html"), 1);
+        store.record_download("https://example.com/legal/", "legal/index.html", Some("text/html"), 1);
+        store.record_download("https://example.com/logo.png", "logo.png", Some("image/png"), 1);
+
+        assert_eq!(build_index(dir, &store).unwrap(), 2);
+        let hits = search(dir, "personal data", DEFAULT_LIMIT).unwrap();
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].url, "https://example.com/legal/");
+        assert_eq!(hits[0].title, "Privacy Policy");
+        assert!(hits[0].snippet.contains("personal"));
+        assert!(search(dir, "refund", DEFAULT_LIMIT).unwrap().is_empty());
+    }
+}
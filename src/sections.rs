@@ -0,0 +1,106 @@
+use anyhow::{anyhow, Result};
+use std::path::{Component, Path, PathBuf};
+use std::str::FromStr;
+
+/// A subtree of the site saved to its own directory (`--split "/docs/**=docs-mirror"`)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OutputSection {
+    /// URL path prefix, matched by whole segments (`/docs`)
+    pub prefix: String,
+    /// Directory the subtree is saved to, relative to the working directory like `--output-dir`
+    pub dir: PathBuf,
+}
+
+impl FromStr for OutputSection {
+    type Err = anyhow::Error;
+
+    fn from_str(rule: &str) -> Result<Self> {
+        let (pattern, dir) = rule
+            .split_once('=')
+            .ok_or_else(|| anyhow!("Invalid output section: {} (expected <path>=<dir>, e.g. /docs/**=docs-mirror)", rule))?;
+        let prefix = pattern.trim().trim_end_matches("**").trim_matches('/');
+        let dir = dir.trim();
+        if prefix.is_empty() || prefix.contains('*') || dir.is_empty() {
+            return Err(anyhow!("Invalid output section: {} (expected <path>=<dir>, e.g. /docs/**=docs-mirror)", rule));
+        }
+        Ok(Self {
+            prefix: format!("/{}", prefix),
+            dir: PathBuf::from(dir),
+        })
+    }
+}
+
+impl OutputSection {
+    /// The part of `path` below the section prefix (`/docs/guide/` -> `/guide/`),
+    /// if `path` is in the section
+    pub fn path_within<'a>(&self, path: &'a str) -> Option<&'a str> {
+        let rest = path.strip_prefix(&self.prefix)?;
+        (rest.is_empty() || rest.starts_with('/')).then_some(rest)
+    }
+
+    /// The section's directory as a path relative to `output_dir`, e.g.
+    /// `../docs-mirror` for sibling directories
+    pub fn relative_dir(&self, output_dir: &Path) -> String {
+        let dir = normalize_path(&std::path::absolute(&self.dir).unwrap_or_else(|_| self.dir.clone()));
+        let output_dir = normalize_path(&std::path::absolute(output_dir).unwrap_or_else(|_| output_dir.to_path_buf()));
+        pathdiff::diff_paths(&dir, &output_dir)
+            .map(|path| path.to_string_lossy().replace('\\', "/"))
+            .unwrap_or_else(|| dir.to_string_lossy().to_string())
+    }
+}
+
+/// The section `path` is routed to; the longest matching prefix wins
+pub fn find_section<'a>(sections: &'a [OutputSection], path: &str) -> Option<&'a OutputSection> {
+    sections
+        .iter()
+        .filter(|section| section.path_within(path).is_some())
+        .max_by_key(|section| section.prefix.len())
+}
+
+/// `path` with `.` and `..` components resolved lexically
+pub fn normalize_path(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                if !normalized.pop() {
+                    normalized.push("..");
+                }
+            }
+            component => normalized.push(component),
+        }
+    }
+    normalized
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_section() {
+        let section: OutputSection = "/docs/**=docs-mirror".parse().unwrap();
+        assert_eq!(section.prefix, "/docs");
+        assert_eq!(section.dir, PathBuf::from("docs-mirror"));
+        assert_eq!("blog/=out/blog".parse::<OutputSection>().unwrap().prefix, "/blog");
+        assert!("/docs/**".parse::<OutputSection>().is_err());
+        assert!("/**=everything".parse::<OutputSection>().is_err());
+    }
+
+    #[test]
+    fn test_find_section() {
+        let sections: Vec<OutputSection> = vec!["/docs=docs-mirror".parse().unwrap(), "/docs/api=api-mirror".parse().unwrap()];
+        assert_eq!(find_section(&sections, "/docs/guide/").map(|section| section.prefix.as_str()), Some("/docs"));
+        assert_eq!(find_section(&sections, "/docs/api/v1").map(|section| section.prefix.as_str()), Some("/docs/api"));
+        assert_eq!(find_section(&sections, "/docs").unwrap().path_within("/docs"), Some(""));
+        assert!(find_section(&sections, "/docsearch").is_none());
+    }
+
+    #[test]
+    fn test_relative_dir() {
+        let section: OutputSection = "/docs=sites/docs-mirror".parse().unwrap();
+        assert_eq!(section.relative_dir(Path::new("./sites/main")), "../docs-mirror");
+        assert_eq!(normalize_path(Path::new("a/./b/../../../c")), PathBuf::from("../c"));
+    }
+}
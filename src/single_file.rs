@@ -0,0 +1,222 @@
+use anyhow::{Result, Context};
+use base64::Engine;
+use mime_guess::MimeGuess;
+use regex::{Captures, Regex};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::Path;
+
+use crate::css_parser;
+use crate::dead_assets::{self, DocumentKind};
+use crate::html_parser::HtmlParser;
+use crate::snapshot_diff;
+use crate::store::DownloadStore;
+
+/// How deep `@import`ed stylesheets are followed
+const MAX_IMPORT_DEPTH: usize = 8;
+
+/// Outcome of writing a mirror's pages as single-file HTML
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SingleFileStats {
+    pub pages: usize,
+    pub total_bytes: u64,
+}
+
+/// Inlines the saved files a page uses; links to other pages are left as they are
+pub(crate) struct Inliner<'a> {
+    pub(crate) output_dir: &'a Path,
+    /// Normalized local path -> Content-Type it was served with
+    pub(crate) content_types: &'a HashMap<String, String>,
+}
+
+impl Inliner<'_> {
+    /// Path and content of the saved file a reference in `document` points at,
+    /// unless it is another page
+    pub(crate) fn read(&self, document: &str, reference: &str) -> Option<(String, Vec<u8>)> {
+        let target = dead_assets::resolve_reference(document, reference)?;
+        if dead_assets::document_kind(&target, None) == DocumentKind::Html {
+            return None;
+        }
+        let content = fs::read(self.output_dir.join(&target)).ok()?;
+        Some((target, content))
+    }
+
+    pub(crate) fn data_uri(&self, path: &str, content: &[u8]) -> String {
+        let content_type = self
+            .content_types
+            .get(path)
+            .cloned()
+            .unwrap_or_else(|| MimeGuess::from_path(path).first_or_octet_stream().to_string());
+        let content_type = content_type.split(';').next().unwrap_or_default().trim();
+        format!("data:{};base64,{}", content_type, base64::engine::general_purpose::STANDARD.encode(content))
+    }
+
+    /// A stylesheet with its `url()`s as data URIs and its imports inlined
+    fn inline_css(&self, css_path: &str, css: &str, depth: usize) -> String {
+        css_parser::rewrite_css_references(css, |reference| {
+            let (path, content) = self.read(css_path, &reference.url)?;
+            if reference.is_import && depth < MAX_IMPORT_DEPTH {
+                let imported = self.inline_css(&path, &String::from_utf8_lossy(&content), depth + 1);
+                return Some(format!("data:text/css;base64,{}", base64::engine::general_purpose::STANDARD.encode(imported)));
+            }
+            Some(self.data_uri(&path, &content))
+        })
+    }
+
+    /// `<link rel="stylesheet">` -> `<style>` with the stylesheet inlined
+    fn inline_stylesheet_link(&self, page: &str, tag: &str) -> Option<String> {
+        let rel = HtmlParser::tag_attribute(tag, "rel")?;
+        if !rel.split_whitespace().any(|rel| rel.eq_ignore_ascii_case("stylesheet")) {
+            return None;
+        }
+        let (path, content) = self.read(page, HtmlParser::tag_attribute(tag, "href")?)?;
+        let css = self.inline_css(&path, &String::from_utf8_lossy(&content), 0).replace("</style", "<\\/style");
+        let media = HtmlParser::tag_attribute(tag, "media")
+            .map(|media| format!(" media=\"{}\"", media))
+            .unwrap_or_default();
+        Some(format!("<style{}>\n{}\n</style>", media, css))
+    }
+
+    /// `<script src>` -> `<script>` with the script inlined
+    fn inline_script(&self, page: &str, start_tag: &str) -> Option<String> {
+        let (_, content) = self.read(page, HtmlParser::tag_attribute(start_tag, "src")?)?;
+        let mut tag = start_tag.to_string();
+        for attribute in ["src", "integrity", "crossorigin"] {
+            tag = HtmlParser::remove_attribute(&tag, attribute);
+        }
+        let script = String::from_utf8_lossy(&content).replace("</script", "<\\/script");
+        Some(format!("{}{}</script>", tag, script))
+    }
+
+    /// A page with its stylesheets, scripts, images, media, fonts and icons
+    /// inlined, so it renders from the one file
+    fn inline_page(&self, page: &str, html: &str) -> String {
+        let html = css_parser::rewrite_style_blocks(html, |reference| {
+            let (path, content) = self.read(page, &reference.url)?;
+            Some(self.data_uri(&path, &content))
+        });
+
+        let tag_regex = Regex::new(r"(?is)<([a-z][a-z0-9-]*)\b[^>]*>").unwrap();
+        let style_attr_regex = Regex::new(r#"(?i)(\sstyle\s*=\s*)(?:"([^"]*)"|'([^']*)')"#).unwrap();
+        let html = tag_regex
+            .replace_all(&html, |caps: &Captures| {
+                let tag = &caps[0];
+                let name = caps[1].to_ascii_lowercase();
+                if name == "link" {
+                    if let Some(style) = self.inline_stylesheet_link(page, tag) {
+                        return style;
+                    }
+                    let is_icon = HtmlParser::tag_attribute(tag, "rel").is_some_and(|rel| rel.to_ascii_lowercase().contains("icon"));
+                    if !is_icon {
+                        return tag.to_string();
+                    }
+                } else if name == "a" || name == "area" || name == "base" || name == "script" {
+                    return tag.to_string();
+                }
+
+                let tag = HtmlParser::rewrite_relative_urls(tag, |url| {
+                    let (path, content) = self.read(page, url)?;
+                    Some(self.data_uri(&path, &content))
+                });
+                style_attr_regex
+                    .replace_all(&tag, |caps: &Captures| {
+                        let style = caps.get(2).or_else(|| caps.get(3)).map_or("", |m| m.as_str());
+                        let style = css_parser::rewrite_css_references(style, |reference| {
+                            let (path, content) = self.read(page, &reference.url)?;
+                            Some(self.data_uri(&path, &content))
+                        });
+                        format!("{}\"{}\"", &caps[1], style.replace('"', "&quot;"))
+                    })
+                    .into_owned()
+            })
+            .into_owned();
+
+        // Scripts last, so markup inside the inlined code is left as written
+        let script_regex = Regex::new(r"(?is)(<script\b[^>]*>)\s*</script\s*>").unwrap();
+        script_regex
+            .replace_all(&html, |caps: &Captures| {
+                self.inline_script(page, &caps[1]).unwrap_or_else(|| caps[0].to_string())
+            })
+            .into_owned()
+    }
+}
+
+/// Content-Type each saved file was served with, by normalized local path
+pub(crate) fn served_content_types(store: &DownloadStore) -> HashMap<String, String> {
+    store
+        .entries
+        .values()
+        .filter_map(|entry| {
+            let content_type = entry.content_type.clone()?;
+            Some((dead_assets::normalize_path(Path::new(&entry.local_path)), content_type))
+        })
+        .collect()
+}
+
+/// Write every saved page of a mirror to `out_dir` as one self-contained HTML
+/// file, at the same path, so links between the pages keep working
+pub fn export_single_files(output_dir: &Path, out_dir: &Path) -> Result<SingleFileStats> {
+    let mut files = BTreeMap::new();
+    snapshot_diff::collect_files(output_dir, output_dir, &mut files)?;
+    let content_types = served_content_types(&DownloadStore::load(output_dir)?);
+    let inliner = Inliner { output_dir, content_types: &content_types };
+
+    let mut stats = SingleFileStats::default();
+    for page in files.keys().filter(|path| snapshot_diff::is_page(path)) {
+        let html = fs::read(output_dir.join(page)).with_context(|| format!("Failed to read page: {:?}", page))?;
+        let single_file = inliner.inline_page(page, &String::from_utf8_lossy(&html));
+        let path = out_dir.join(page);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).with_context(|| format!("Failed to create directory: {:?}", parent))?;
+        }
+        fs::write(&path, &single_file).with_context(|| format!("Failed to write single-file page: {:?}", path))?;
+        stats.pages += 1;
+        stats.total_bytes += single_file.len() as u64;
+    }
+    Ok(stats)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::write;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_export_single_files() {
+        let temp_dir = tempdir().unwrap();
+        let dir = temp_dir.path().join("site");
+        write(&dir, "blog/index.html", concat!(
+            r#"<link rel="stylesheet" href="../css/site.css" media="screen" integrity="sha384-x">"#,
+            r#"<link rel="icon" href="../favicon.ico"><link rel="preload" href="../fonts/a.woff2" as="font">"#,
+            r#"<script src="../js/app.js" integrity="sha384-y" defer></script>"#,
+            r#"<img src="../img/a.png" srcset="../img/a.png 1x, ../img/missing.png 2x" style="background: url('../img/a.png')">"#,
+            r#"<a href="../index.html">Home</a><a href="../files/report.pdf">Report</a>"#,
+        ));
+        write(&dir, "index.html", "<p>Home</p>");
+        write(&dir, "css/site.css", r#"@import "base.css"; .hero { background: url(../img/a.png); }"#);
+        write(&dir, "css/base.css", "body { margin: 0 }");
+        write(&dir, "js/app.js", "document.write('</script>')");
+        write(&dir, "img/a.png", "png");
+        write(&dir, "favicon.ico", "ico");
+        write(&dir, "files/report.pdf", "pdf");
+        let mut store = DownloadStore::default();
+        store.record_download("https://example.com/favicon.ico", "favicon.ico", Some("image/x-icon; charset=binary"), 3);
+        store.save(&dir).unwrap();
+
+        let out_dir = temp_dir.path().join("single");
+        let stats = export_single_files(&dir, &out_dir).unwrap();
+        assert_eq!(stats.pages, 2);
+
+        let page = fs::read_to_string(out_dir.join("blog/index.html")).unwrap();
+        let png = "data:image/png;base64,cG5n";
+        let base = format!("data:text/css;base64,{}", base64::engine::general_purpose::STANDARD.encode("body { margin: 0 }"));
+        assert!(page.contains(&format!("<style media=\"screen\">\n@import \"{}\"; .hero {{ background: url(\"{}\"); }}\n</style>", base, png)));
+        assert!(page.contains(r#"<link rel="icon" href="data:image/x-icon;base64,aWNv">"#));
+        assert!(page.contains(r#"<link rel="preload" href="../fonts/a.woff2" as="font">"#));
+        assert!(page.contains(r#"<script defer>document.write('<\/script>')</script>"#));
+        assert!(page.contains(&format!(r#"<img src="{0}" srcset="{0} 1x, ../img/missing.png 2x" style="background: url(&quot;{0}&quot;)">"#, png)));
+        assert!(page.contains(r#"<a href="../index.html">Home</a><a href="../files/report.pdf">Report</a>"#));
+        assert_eq!(fs::read_to_string(out_dir.join("index.html")).unwrap(), "<p>Home</p>");
+    }
+}
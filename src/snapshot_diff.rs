@@ -0,0 +1,178 @@
+use anyhow::{anyhow, Result, Context};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use crate::broken_links;
+use crate::crawl_report;
+use crate::dead_assets::{self, DocumentKind};
+use crate::rate_report;
+use crate::rewrite_diff;
+use crate::text_diff::{self, TextChange};
+use crate::validation;
+
+/// Reports a run writes next to the mirrored files, which differ on every run
+const GENERATED_FILES: &[&str] = &[
+    broken_links::JSON_FILE_NAME,
+    broken_links::CSV_FILE_NAME,
+    crawl_report::REPORT_FILE_NAME,
+    crawl_report::HTML_REPORT_FILE_NAME,
+    rate_report::REPORT_FILE_NAME,
+    text_diff::REPORT_FILE_NAME,
+    validation::REPORT_FILE_NAME,
+];
+
+/// A file present in only one of the snapshots
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SnapshotFile {
+    /// Path relative to the snapshot directory
+    pub path: String,
+    pub size: u64,
+}
+
+/// A file whose content differs between the snapshots
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChangedFile {
+    pub path: String,
+    pub old_size: u64,
+    pub new_size: u64,
+    /// Paragraphs of visible text removed and added, for HTML pages
+    pub text_changes: Vec<TextChange>,
+}
+
+impl ChangedFile {
+    pub fn byte_delta(&self) -> i64 {
+        self.new_size as i64 - self.old_size as i64
+    }
+}
+
+/// Differences between two mirror snapshots (`website-mirror diff <old> <new>`)
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SnapshotDiff {
+    pub added: Vec<SnapshotFile>,
+    pub removed: Vec<SnapshotFile>,
+    pub changed: Vec<ChangedFile>,
+    pub unchanged: usize,
+    pub old_bytes: u64,
+    pub new_bytes: u64,
+}
+
+impl SnapshotDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+
+    pub fn byte_delta(&self) -> i64 {
+        self.new_bytes as i64 - self.old_bytes as i64
+    }
+}
+
+/// Whether a snapshot path is a page rather than an asset
+pub fn is_page(path: &str) -> bool {
+    dead_assets::document_kind(path, None) == DocumentKind::Html
+}
+
+/// Files under `dir` (path relative to `root` -> size), skipping hidden files
+/// such as the crawl manifest and text snapshot, and generated reports
+pub(crate) fn collect_files(root: &Path, dir: &Path, files: &mut BTreeMap<String, u64>) -> Result<()> {
+    let entries = fs::read_dir(dir).with_context(|| format!("Failed to read directory: {:?}", dir))?;
+    for entry in entries {
+        let entry = entry?;
+        let path = entry.path();
+        if entry.file_name().to_string_lossy().starts_with('.') {
+            continue;
+        }
+        let Ok(relative) = path.strip_prefix(root) else { continue };
+        let relative = relative.to_string_lossy().replace('\\', "/");
+        if path.is_dir() {
+            if relative != rewrite_diff::DIFF_DIR {
+                collect_files(root, &path, files)?;
+            }
+        } else if !GENERATED_FILES.contains(&relative.as_str()) {
+            files.insert(relative, entry.metadata()?.len());
+        }
+    }
+    Ok(())
+}
+
+/// Compare two mirror output directories: files added, removed and changed,
+/// with the visible text changes of changed pages
+pub fn diff_snapshots(old_dir: &Path, new_dir: &Path) -> Result<SnapshotDiff> {
+    for dir in [old_dir, new_dir] {
+        if !dir.is_dir() {
+            return Err(anyhow!("{} is not a directory", dir.display()));
+        }
+    }
+    let mut old_files = BTreeMap::new();
+    collect_files(old_dir, old_dir, &mut old_files)?;
+    let mut new_files = BTreeMap::new();
+    collect_files(new_dir, new_dir, &mut new_files)?;
+
+    let mut diff = SnapshotDiff {
+        old_bytes: old_files.values().sum(),
+        new_bytes: new_files.values().sum(),
+        ..Default::default()
+    };
+    for (path, &size) in &old_files {
+        if !new_files.contains_key(path) {
+            diff.removed.push(SnapshotFile { path: path.clone(), size });
+        }
+    }
+    for (path, &new_size) in &new_files {
+        let Some(&old_size) = old_files.get(path) else {
+            diff.added.push(SnapshotFile { path: path.clone(), size: new_size });
+            continue;
+        };
+        let old_content = fs::read(old_dir.join(path)).with_context(|| format!("Failed to read {:?}", old_dir.join(path)))?;
+        let new_content = fs::read(new_dir.join(path)).with_context(|| format!("Failed to read {:?}", new_dir.join(path)))?;
+        if old_content == new_content {
+            diff.unchanged += 1;
+            continue;
+        }
+        let text_changes = if is_page(path) {
+            text_diff::diff_blocks(
+                &text_diff::extract_text_blocks(&String::from_utf8_lossy(&old_content)),
+                &text_diff::extract_text_blocks(&String::from_utf8_lossy(&new_content)),
+            )
+        } else {
+            Vec::new()
+        };
+        diff.changed.push(ChangedFile { path: path.clone(), old_size, new_size, text_changes });
+    }
+    Ok(diff)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::write;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_diff_snapshots() {
+        let temp_dir = tempdir().unwrap();
+        let (old_dir, new_dir) = (temp_dir.path().join("old"), temp_dir.path().join("new"));
+        write(&old_dir, "index.html", "<h1>Shop</h1><p>Prices from $10</p><script>var t = 1;</script>");
+        write(&new_dir, "index.html", "<h1>Shop</h1><p>Prices from $12</p><script>var t = 2;</script>");
+        write(&old_dir, "css/site.css", "body {}");
+        write(&new_dir, "css/site.css", "body {}");
+        write(&old_dir, "img/old.png", "12345");
+        write(&new_dir, "blog/index.html", "<p>New post</p>");
+        write(&old_dir, ".download_store.json", "{}");
+        write(&new_dir, "report.json", "{}");
+        write(&new_dir, "_diffs/index.html.diff", "--- a/index.html");
+
+        let diff = diff_snapshots(&old_dir, &new_dir).unwrap();
+        assert_eq!(diff.unchanged, 1);
+        assert_eq!(diff.added, vec![SnapshotFile { path: "blog/index.html".to_string(), size: 15 }]);
+        assert_eq!(diff.removed, vec![SnapshotFile { path: "img/old.png".to_string(), size: 5 }]);
+        assert_eq!(diff.changed.len(), 1);
+        assert_eq!(diff.changed[0].byte_delta(), 0);
+        assert_eq!(
+            diff.changed[0].text_changes,
+            vec![TextChange::Added("Prices from $12".to_string()), TextChange::Removed("Prices from $10".to_string())]
+        );
+        assert_eq!(diff.byte_delta(), 10);
+        assert!(diff_snapshots(&old_dir, &temp_dir.path().join("missing")).is_err());
+    }
+}
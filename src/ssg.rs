@@ -0,0 +1,279 @@
+use anyhow::{anyhow, bail, Result, Context};
+use regex::Regex;
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+use std::time::UNIX_EPOCH;
+
+use crate::archive;
+use crate::css_parser;
+use crate::dead_assets;
+use crate::html_parser::HtmlParser;
+use crate::search;
+use crate::snapshot_diff;
+use crate::store::DownloadStore;
+
+/// Publication date of a page, from its metadata
+const DATE_PATTERNS: &[&str] = &[
+    r#"(?is)<meta\b[^>]*\b(?:property|name|itemprop)\s*=\s*["'](?:article:published_time|datePublished|date|dc\.date)["'][^>]*\bcontent\s*=\s*["']([^"']+)["']"#,
+    r#"(?is)<meta\b[^>]*\bcontent\s*=\s*["']([^"']+)["'][^>]*\b(?:property|name|itemprop)\s*=\s*["'](?:article:published_time|datePublished|date|dc\.date)["']"#,
+    r#"(?is)<time\b[^>]*\bdatetime\s*=\s*["']([^"']+)["']"#,
+];
+
+/// Static site generators a mirror can be handed off to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SsgFormat {
+    Hugo,
+    Jekyll,
+}
+
+impl FromStr for SsgFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(format: &str) -> Result<Self> {
+        match format.to_ascii_lowercase().as_str() {
+            "hugo" => Ok(SsgFormat::Hugo),
+            "jekyll" => Ok(SsgFormat::Jekyll),
+            _ => Err(anyhow!("Unknown static site generator: {} (expected hugo or jekyll)", format)),
+        }
+    }
+}
+
+impl SsgFormat {
+    /// Directory assets are copied to, served from the site root (Hugo) or
+    /// from `/assets/` (Jekyll)
+    fn assets_dir(self) -> &'static str {
+        match self {
+            SsgFormat::Hugo => "static",
+            SsgFormat::Jekyll => "assets",
+        }
+    }
+
+    fn asset_url(self, path: &str) -> String {
+        match self {
+            SsgFormat::Hugo => format!("/{}", path),
+            SsgFormat::Jekyll => format!("/assets/{}", path),
+        }
+    }
+
+    /// Front matter key fixing the URL a page is published at
+    fn url_key(self) -> &'static str {
+        match self {
+            SsgFormat::Hugo => "url",
+            SsgFormat::Jekyll => "permalink",
+        }
+    }
+}
+
+/// Outcome of an SSG export
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SsgStats {
+    pub pages: usize,
+    pub assets: usize,
+    pub total_bytes: u64,
+}
+
+/// URL a saved page is published at: `blog/index.html` -> `/blog/`,
+/// `about.html` -> `/about.html`
+fn permalink(page: &str) -> String {
+    match page.strip_suffix("index.html") {
+        Some(dir) => format!("/{}", dir),
+        None => format!("/{}", page),
+    }
+}
+
+/// Content file of a page, relative to the export directory. Jekyll keeps the
+/// mirror's paths; Hugo files go under `content/`, with index pages of
+/// directories holding other pages as section `_index.html` files
+fn content_path(page: &str, format: SsgFormat, pages: &[String]) -> String {
+    if format == SsgFormat::Jekyll {
+        return page.to_string();
+    }
+    let Some(dir) = page.strip_suffix("index.html") else {
+        return format!("content/{}", page);
+    };
+    if dir.is_empty() || pages.iter().any(|other| other != page && other.starts_with(dir)) {
+        format!("content/{}_index.html", dir)
+    } else {
+        format!("content/{}.html", dir.trim_end_matches('/'))
+    }
+}
+
+/// `YYYY-MM-DDThh:mm:ssZ` of a Unix time
+fn iso_date(seconds: u64) -> String {
+    let (year, month, day) = archive::civil_date(seconds);
+    let time = seconds % 86_400;
+    format!("{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z", year, month, day, time / 3_600, (time % 3_600) / 60, time % 60)
+}
+
+/// Publication date declared by the page, if it looks like an ISO 8601 date
+fn page_date(html: &str) -> Option<String> {
+    let iso_regex = Regex::new(r"^\d{4}-\d{2}-\d{2}([T ]\d{2}:\d{2}(:\d{2}(\.\d+)?)?(Z|[+-]\d{2}:?\d{2})?)?$").unwrap();
+    DATE_PATTERNS
+        .iter()
+        .filter_map(|pattern| Regex::new(pattern).unwrap().captures(html).map(|caps| caps[1].trim().to_string()))
+        .find(|date| iso_regex.is_match(date))
+}
+
+/// The page's main content: the inside of `<main>`, else of `<body>`, without scripts
+fn page_content(html: &str) -> String {
+    let main_regex = Regex::new(r"(?is)<main\b[^>]*>(.*)</main\s*>").unwrap();
+    let body_regex = Regex::new(r"(?is)<body\b[^>]*>(.*?)(?:</body\s*>|$)").unwrap();
+    let script_regex = Regex::new(r"(?is)<script\b.*?</script\s*>").unwrap();
+    let content = main_regex
+        .captures(html)
+        .or_else(|| body_regex.captures(html))
+        .map(|caps| caps[1].to_string())
+        .unwrap_or_else(|| html.to_string());
+    script_regex.replace_all(&content, "").trim().to_string()
+}
+
+/// Convert a mirror into the content and assets of a Hugo or Jekyll site in
+/// `out_dir`: every page becomes a content file with front matter (title,
+/// date, the URL it's published at and the original URL) holding its main
+/// content, and every other file goes to the assets directory. Links between
+/// pages and to assets are rewritten to the site's URLs.
+pub fn export_ssg(output_dir: &Path, out_dir: &Path, format: SsgFormat) -> Result<SsgStats> {
+    let mut files = BTreeMap::new();
+    snapshot_diff::collect_files(output_dir, output_dir, &mut files)?;
+    let pages: Vec<String> = files.keys().filter(|path| snapshot_diff::is_page(path)).cloned().collect();
+    if pages.is_empty() {
+        bail!("No saved pages in {}", output_dir.display());
+    }
+    let store = DownloadStore::load(output_dir)?;
+    let urls: HashMap<String, String> = store
+        .entries
+        .iter()
+        .map(|(url, entry)| (dead_assets::normalize_path(Path::new(&entry.local_path)), url.clone()))
+        .collect();
+
+    let mut stats = SsgStats::default();
+    for (path, size) in &files {
+        if snapshot_diff::is_page(path) {
+            continue;
+        }
+        let target = out_dir.join(format.assets_dir()).join(path);
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent).with_context(|| format!("Failed to create directory: {:?}", parent))?;
+        }
+        fs::copy(output_dir.join(path), &target).with_context(|| format!("Failed to copy asset: {:?}", path))?;
+        stats.assets += 1;
+        stats.total_bytes += size;
+    }
+
+    for page in &pages {
+        let source = output_dir.join(page);
+        let html = fs::read(&source).with_context(|| format!("Failed to read page: {:?}", page))?;
+        let html = String::from_utf8_lossy(&html);
+        let rewrite = |reference: &str| {
+            let fragment = reference.find('#').map_or("", |index| &reference[index..]);
+            let target = dead_assets::resolve_reference(page, reference)?;
+            if snapshot_diff::is_page(&target) && files.contains_key(&target) {
+                Some(format!("{}{}", permalink(&target), fragment))
+            } else if files.contains_key(&target) {
+                Some(format.asset_url(&target))
+            } else {
+                None
+            }
+        };
+        let content = HtmlParser::rewrite_relative_urls(&page_content(&html), rewrite);
+        let content = css_parser::rewrite_style_blocks(&content, |reference| rewrite(&reference.url));
+
+        let title = search::page_title(&html);
+        let date = page_date(&html).unwrap_or_else(|| {
+            let modified = fs::metadata(&source)
+                .and_then(|metadata| metadata.modified())
+                .ok()
+                .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+                .map_or(0, |elapsed| elapsed.as_secs());
+            iso_date(modified)
+        });
+        let mut front_matter = format!(
+            "---\ntitle: {}\ndate: {}\n{}: {}\n",
+            serde_json::to_string(if title.is_empty() { page } else { &title })?,
+            date,
+            format.url_key(),
+            serde_json::to_string(&permalink(page))?
+        );
+        if let Some(url) = urls.get(page) {
+            front_matter.push_str(&format!("original_url: {}\n", serde_json::to_string(url)?));
+        }
+        front_matter.push_str("---\n");
+
+        let target = out_dir.join(content_path(page, format, &pages));
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent).with_context(|| format!("Failed to create directory: {:?}", parent))?;
+        }
+        let data = format!("{}{}\n", front_matter, content);
+        fs::write(&target, &data).with_context(|| format!("Failed to write content file: {:?}", target))?;
+        stats.pages += 1;
+        stats.total_bytes += data.len() as u64;
+    }
+    Ok(stats)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::write;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_permalink_and_content_path() {
+        let pages = vec!["index.html".to_string(), "blog/index.html".to_string(), "blog/launch/index.html".to_string(), "about.html".to_string()];
+        assert_eq!(permalink("index.html"), "/");
+        assert_eq!(permalink("blog/launch/index.html"), "/blog/launch/");
+        assert_eq!(permalink("about.html"), "/about.html");
+        assert_eq!(content_path("index.html", SsgFormat::Hugo, &pages), "content/_index.html");
+        assert_eq!(content_path("blog/index.html", SsgFormat::Hugo, &pages), "content/blog/_index.html");
+        assert_eq!(content_path("blog/launch/index.html", SsgFormat::Hugo, &pages), "content/blog/launch.html");
+        assert_eq!(content_path("about.html", SsgFormat::Hugo, &pages), "content/about.html");
+        assert_eq!(content_path("blog/launch/index.html", SsgFormat::Jekyll, &pages), "blog/launch/index.html");
+    }
+
+    #[test]
+    fn test_page_date() {
+        assert_eq!(page_date(r#"<meta property="article:published_time" content="2024-03-05T09:30:00+00:00">"#).as_deref(), Some("2024-03-05T09:30:00+00:00"));
+        assert_eq!(page_date(r#"<meta content="2023-11-02" name="date">"#).as_deref(), Some("2023-11-02"));
+        assert_eq!(page_date(r#"<p>Posted <time datetime="2022-01-09">Jan 9</time></p>"#).as_deref(), Some("2022-01-09"));
+        assert_eq!(page_date(r#"<time datetime="yesterday">"#), None);
+        assert_eq!(iso_date(1_700_000_000), "2023-11-14T22:13:20Z");
+    }
+
+    #[test]
+    fn test_export_ssg() {
+        let temp_dir = tempdir().unwrap();
+        let dir = temp_dir.path().join("site");
+        write(&dir, "index.html", r#"<html><head><title>Home</title></head><body><nav><a href="blog/launch/index.html">Launch</a></nav><main><h1>Welcome</h1><img src="img/logo.png"><a href="blog/launch/index.html#more">Read</a></main><script>track()</script></body></html>"#);
+        write(&dir, "blog/launch/index.html", r#"<html><head><title>We "launched"</title><meta property="article:published_time" content="2024-03-05"></head><body><p><a href="../../index.html">Home</a> <img src="../../img/logo.png"><script src="../../js/app.js"></script></p></body></html>"#);
+        write(&dir, "img/logo.png", "png");
+        write(&dir, "js/app.js", "track()");
+        let mut store = DownloadStore::default();
+        store.record_download("https://example.com/", "index.html", Some("text/html"), 0);
+        store.record_download("https://example.com/blog/launch/", "blog/launch/index.html", Some("text/html"), 0);
+        store.save(&dir).unwrap();
+
+        let hugo = temp_dir.path().join("hugo");
+        let stats = export_ssg(&dir, &hugo, SsgFormat::Hugo).unwrap();
+        assert_eq!((stats.pages, stats.assets), (2, 2));
+        assert_eq!(fs::read_to_string(hugo.join("static/img/logo.png")).unwrap(), "png");
+        let home = fs::read_to_string(hugo.join("content/_index.html")).unwrap();
+        assert!(home.starts_with("---\ntitle: \"Home\"\ndate: "));
+        assert!(home.contains("\nurl: \"/\"\noriginal_url: \"https://example.com/\"\n---\n"));
+        assert!(home.ends_with("---\n<h1>Welcome</h1><img src=\"/img/logo.png\"><a href=\"/blog/launch/#more\">Read</a>\n"));
+        let launch = fs::read_to_string(hugo.join("content/blog/launch.html")).unwrap();
+        assert_eq!(launch, concat!(
+            "---\ntitle: \"We \\\"launched\\\"\"\ndate: 2024-03-05\nurl: \"/blog/launch/\"\n",
+            "original_url: \"https://example.com/blog/launch/\"\n---\n",
+            "<p><a href=\"/\">Home</a> <img src=\"/img/logo.png\"></p>\n",
+        ));
+
+        let jekyll = temp_dir.path().join("jekyll");
+        export_ssg(&dir, &jekyll, SsgFormat::Jekyll).unwrap();
+        assert!(jekyll.join("assets/js/app.js").exists());
+        let launch = fs::read_to_string(jekyll.join("blog/launch/index.html")).unwrap();
+        assert!(launch.contains("\npermalink: \"/blog/launch/\"\n"));
+        assert!(launch.contains("<img src=\"/assets/img/logo.png\">"));
+    }
+}
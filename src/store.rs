@@ -0,0 +1,369 @@
+use anyhow::{bail, Result, Context};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::btree_map::Entry;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::Path;
+use tracing::info;
+
+use crate::url_normalizer;
+
+/// File name of the crawl manifest, stored at the root of the output directory
+pub const STORE_FILE_NAME: &str = ".download_store.json";
+
+/// Format version of the manifests this build writes. Manifests from before
+/// versioning are version 1; older ones are migrated when loaded:
+/// - 2: URLs are stored normalized (no fragment, default port or dot-segments)
+pub const STORE_VERSION: u32 = 2;
+
+fn unversioned() -> u32 {
+    1
+}
+
+/// Hex SHA-256 of a saved file, as recorded in `StoreEntry::content_hash`
+pub fn content_hash(content: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(content))
+}
+
+/// A single downloaded URL and the pages that referenced it
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StoreEntry {
+    pub local_path: String,
+    pub content_type: Option<String>,
+    pub size: u64,
+    /// URLs of the pages (or stylesheets) that referenced this resource
+    #[serde(default)]
+    pub referrers: BTreeSet<String>,
+    /// `ETag` response header, used to revalidate the file on later runs
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub etag: Option<String>,
+    /// `Last-Modified` response header, used when no `ETag` was sent
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_modified: Option<String>,
+    /// SHA-256 of the page as last saved, after rewriting; an update run that
+    /// produces the same page leaves the file (and its mtime) alone
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub content_hash: Option<String>,
+    /// The last download attempt failed: the URL is referenced but, unless an
+    /// earlier run saved it, has no file
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub failed: bool,
+}
+
+impl StoreEntry {
+    /// Validator for conditional requests (`If-Range`), preferring a strong `ETag`
+    pub fn validator(&self) -> Option<&str> {
+        match self.etag.as_deref() {
+            Some(etag) if !etag.starts_with("W/") => Some(etag),
+            _ => self.last_modified.as_deref(),
+        }
+    }
+}
+
+/// Persistent crawl manifest mapping every downloaded URL to its local file
+/// and the many-to-many referrer relationships between pages and assets
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DownloadStore {
+    /// Format version the manifest was written with, see `STORE_VERSION`
+    #[serde(default = "unversioned")]
+    pub version: u32,
+    pub entries: BTreeMap<String, StoreEntry>,
+    /// URLs deliberately not archived, with the reason they were skipped
+    #[serde(default)]
+    pub skipped: BTreeMap<String, String>,
+}
+
+impl Default for DownloadStore {
+    fn default() -> Self {
+        Self {
+            version: STORE_VERSION,
+            entries: BTreeMap::new(),
+            skipped: BTreeMap::new(),
+        }
+    }
+}
+
+impl DownloadStore {
+    /// Load the manifest from an output directory, starting empty if none exists.
+    /// Manifests written by older versions are migrated to `STORE_VERSION`.
+    pub fn load(output_dir: &Path) -> Result<Self> {
+        let path = output_dir.join(STORE_FILE_NAME);
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+
+        let data = fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read download store: {:?}", path))?;
+        let value: serde_json::Value = serde_json::from_str(&data)
+            .with_context(|| format!("Failed to parse download store: {:?}", path))?;
+        let version = value.get("version").and_then(serde_json::Value::as_u64).unwrap_or(1);
+        if version > STORE_VERSION as u64 {
+            bail!("Download store {:?} has format version {}, newer than the supported {}; upgrade website-mirror or use another output directory", path, version, STORE_VERSION);
+        }
+        let mut store: Self = serde_json::from_value(value)
+            .with_context(|| format!("Failed to parse download store: {:?}", path))?;
+        if store.version < STORE_VERSION {
+            info!("🔄 Migrating download store from format version {} to {}", store.version, STORE_VERSION);
+            store.migrate();
+        }
+        Ok(store)
+    }
+
+    /// Bring a manifest written by an older version up to `STORE_VERSION`, one
+    /// version at a time
+    fn migrate(&mut self) {
+        while self.version < STORE_VERSION {
+            if self.version == 1 {
+                self.normalize_urls();
+            }
+            self.version += 1;
+        }
+    }
+
+    /// Version 1 -> 2: re-key entries, referrers and skips by normalized URL,
+    /// merging entries recorded under different spellings of the same URL
+    fn normalize_urls(&mut self) {
+        let normalize = |url: &str| url_normalizer::normalize_url(url, false);
+        for (url, mut entry) in std::mem::take(&mut self.entries) {
+            entry.referrers = entry.referrers.iter().map(|referrer| normalize(referrer)).collect();
+            match self.entries.entry(normalize(&url)) {
+                Entry::Vacant(slot) => {
+                    slot.insert(entry);
+                }
+                Entry::Occupied(mut slot) => {
+                    let existing = slot.get_mut();
+                    existing.referrers.append(&mut entry.referrers);
+                    if existing.local_path.is_empty() {
+                        entry.referrers = std::mem::take(&mut existing.referrers);
+                        *existing = entry;
+                    }
+                }
+            }
+        }
+        self.skipped = std::mem::take(&mut self.skipped)
+            .into_iter()
+            .map(|(url, reason)| (normalize(&url), reason))
+            .collect();
+    }
+
+    pub fn save(&self, output_dir: &Path) -> Result<()> {
+        let path = output_dir.join(STORE_FILE_NAME);
+        let data = serde_json::to_string_pretty(self)?;
+        fs::write(&path, data)
+            .with_context(|| format!("Failed to write download store: {:?}", path))
+    }
+
+    /// Record that `url` was saved to `local_path`, keeping any known referrers
+    pub fn record_download(&mut self, url: &str, local_path: &str, content_type: Option<&str>, size: u64) {
+        let entry = self.entries.entry(url.to_string()).or_default();
+        entry.local_path = local_path.to_string();
+        entry.content_type = content_type.map(|c| c.to_string());
+        entry.size = size;
+        entry.failed = false;
+    }
+
+    /// Record that downloading `url` failed
+    pub fn record_failure(&mut self, url: &str) {
+        self.entries.entry(url.to_string()).or_default().failed = true;
+    }
+
+    /// Record the HTTP validators a URL was served with
+    pub fn record_validators(&mut self, url: &str, etag: Option<&str>, last_modified: Option<&str>) {
+        let entry = self.entries.entry(url.to_string()).or_default();
+        entry.etag = etag.map(|e| e.to_string());
+        entry.last_modified = last_modified.map(|l| l.to_string());
+    }
+
+    /// Record the hash (`content_hash`) of the page saved for `url`
+    pub fn record_content_hash(&mut self, url: &str, content: &[u8]) {
+        self.entries.entry(url.to_string()).or_default().content_hash = Some(content_hash(content));
+    }
+
+    /// Whether `content` is what was last saved for `url` at `local_path`
+    pub fn is_unchanged(&self, url: &str, local_path: &str, content: &[u8]) -> bool {
+        self.entries.get(url).is_some_and(|entry| {
+            entry.local_path == local_path && entry.content_hash.as_deref() == Some(content_hash(content).as_str())
+        })
+    }
+
+    /// Record that `url` was deliberately not archived
+    pub fn record_skip(&mut self, url: &str, reason: &str) {
+        self.skipped.insert(url.to_string(), reason.to_string());
+    }
+
+    /// Record that `referrer` references `url`
+    pub fn add_referrer(&mut self, url: &str, referrer: &str) {
+        if url == referrer {
+            return;
+        }
+        self.entries
+            .entry(url.to_string())
+            .or_default()
+            .referrers
+            .insert(referrer.to_string());
+    }
+
+    /// Pages that referenced `url`
+    pub fn referrers(&self, url: &str) -> Vec<&str> {
+        self.entries
+            .get(url)
+            .map(|entry| entry.referrers.iter().map(|r| r.as_str()).collect())
+            .unwrap_or_default()
+    }
+
+    /// Resources referenced by the page at `referrer`
+    pub fn referenced_by(&self, referrer: &str) -> Vec<&str> {
+        self.entries
+            .iter()
+            .filter(|(_, entry)| entry.referrers.contains(referrer))
+            .map(|(url, _)| url.as_str())
+            .collect()
+    }
+
+    /// Remove a page from every referrer set, returning the resources
+    /// that are no longer referenced by anything and can be pruned safely
+    pub fn remove_referrer(&mut self, referrer: &str) -> Vec<String> {
+        let mut orphaned = Vec::new();
+        for (url, entry) in self.entries.iter_mut() {
+            if entry.referrers.remove(referrer) && entry.referrers.is_empty() {
+                orphaned.push(url.clone());
+            }
+        }
+        orphaned
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_add_referrer_many_to_many() {
+        let mut store = DownloadStore::default();
+        store.add_referrer("https://example.com/video.mp4", "https://example.com/a");
+        store.add_referrer("https://example.com/video.mp4", "https://example.com/b");
+        store.add_referrer("https://example.com/logo.png", "https://example.com/a");
+
+        assert_eq!(store.referrers("https://example.com/video.mp4"), vec!["https://example.com/a", "https://example.com/b"]);
+        assert_eq!(store.referenced_by("https://example.com/a"), vec!["https://example.com/logo.png", "https://example.com/video.mp4"]);
+    }
+
+    #[test]
+    fn test_record_download_keeps_referrers() {
+        let mut store = DownloadStore::default();
+        store.add_referrer("https://example.com/style.css", "https://example.com/");
+        store.record_download("https://example.com/style.css", "style.css", Some("text/css"), 42);
+
+        let entry = store.entries.get("https://example.com/style.css").unwrap();
+        assert_eq!(entry.local_path, "style.css");
+        assert_eq!(entry.size, 42);
+        assert_eq!(entry.referrers.len(), 1);
+    }
+
+    #[test]
+    fn test_record_failure_until_downloaded() {
+        let mut store = DownloadStore::default();
+        store.add_referrer("https://example.com/missing.png", "https://example.com/");
+        store.record_failure("https://example.com/missing.png");
+        assert!(store.entries["https://example.com/missing.png"].failed);
+        assert_eq!(store.referrers("https://example.com/missing.png"), vec!["https://example.com/"]);
+
+        store.record_download("https://example.com/missing.png", "missing.png", Some("image/png"), 7);
+        assert!(!store.entries["https://example.com/missing.png"].failed);
+    }
+
+    #[test]
+    fn test_remove_referrer_reports_orphans() {
+        let mut store = DownloadStore::default();
+        store.add_referrer("https://example.com/shared.css", "https://example.com/a");
+        store.add_referrer("https://example.com/shared.css", "https://example.com/b");
+        store.add_referrer("https://example.com/only-a.jpg", "https://example.com/a");
+
+        let orphaned = store.remove_referrer("https://example.com/a");
+        assert_eq!(orphaned, vec!["https://example.com/only-a.jpg".to_string()]);
+    }
+
+    #[test]
+    fn test_save_and_load_roundtrip() {
+        let temp_dir = tempdir().unwrap();
+        let mut store = DownloadStore::default();
+        store.record_download("https://example.com/", "index.html", Some("text/html"), 10);
+        store.add_referrer("https://example.com/a.js", "https://example.com/");
+        store.save(temp_dir.path()).unwrap();
+
+        let loaded = DownloadStore::load(temp_dir.path()).unwrap();
+        assert_eq!(loaded, store);
+    }
+
+    #[test]
+    fn test_content_hash_detects_unchanged_pages() {
+        let temp_dir = tempdir().unwrap();
+        let mut store = DownloadStore::default();
+        store.record_download("https://example.com/", "index.html", Some("text/html"), 10);
+        store.record_content_hash("https://example.com/", b"<p>Hello</p>");
+        store.save(temp_dir.path()).unwrap();
+
+        let loaded = DownloadStore::load(temp_dir.path()).unwrap();
+        assert!(loaded.is_unchanged("https://example.com/", "index.html", b"<p>Hello</p>"));
+        assert!(!loaded.is_unchanged("https://example.com/", "index.html", b"<p>Hello!</p>"));
+        assert!(!loaded.is_unchanged("https://example.com/", "home.html", b"<p>Hello</p>"));
+        assert!(!loaded.is_unchanged("https://example.com/about/", "about/index.html", b"<p>Hello</p>"));
+    }
+
+    #[test]
+    fn test_record_skip_roundtrip() {
+        let temp_dir = tempdir().unwrap();
+        let mut store = DownloadStore::default();
+        store.record_skip("https://example.com/private", "meta robots noarchive");
+        store.save(temp_dir.path()).unwrap();
+
+        let loaded = DownloadStore::load(temp_dir.path()).unwrap();
+        assert_eq!(loaded.skipped.get("https://example.com/private").map(String::as_str), Some("meta robots noarchive"));
+    }
+
+    #[test]
+    fn test_validator_prefers_strong_etag() {
+        let mut store = DownloadStore::default();
+        store.record_validators("https://example.com/a.mp4", Some("\"abc\""), Some("Wed, 21 Oct 2015 07:28:00 GMT"));
+        store.record_validators("https://example.com/b.mp4", Some("W/\"weak\""), Some("Wed, 21 Oct 2015 07:28:00 GMT"));
+        store.record_validators("https://example.com/c.mp4", None, None);
+
+        assert_eq!(store.entries["https://example.com/a.mp4"].validator(), Some("\"abc\""));
+        // Weak ETags can't be used with If-Range
+        assert_eq!(store.entries["https://example.com/b.mp4"].validator(), Some("Wed, 21 Oct 2015 07:28:00 GMT"));
+        assert_eq!(store.entries["https://example.com/c.mp4"].validator(), None);
+    }
+
+    #[test]
+    fn test_migrate_unversioned_store() {
+        let temp_dir = tempdir().unwrap();
+        let legacy = r#"{"entries": {
+            "https://Example.com:443/style.css#x": {"local_path": "", "content_type": null, "size": 0, "referrers": ["https://example.com/a#top"]},
+            "https://example.com/style.css": {"local_path": "style.css", "content_type": "text/css", "size": 42, "referrers": ["https://example.com/b"]}
+        }}"#;
+        fs::write(temp_dir.path().join(STORE_FILE_NAME), legacy).unwrap();
+
+        let store = DownloadStore::load(temp_dir.path()).unwrap();
+        assert_eq!(store.version, STORE_VERSION);
+        assert_eq!(store.entries.len(), 1);
+        let entry = &store.entries["https://example.com/style.css"];
+        assert_eq!(entry.local_path, "style.css");
+        assert_eq!(store.referrers("https://example.com/style.css"), vec!["https://example.com/a", "https://example.com/b"]);
+    }
+
+    #[test]
+    fn test_load_newer_store_fails() {
+        let temp_dir = tempdir().unwrap();
+        fs::write(temp_dir.path().join(STORE_FILE_NAME), format!(r#"{{"version": {}, "entries": {{}}}}"#, STORE_VERSION + 1)).unwrap();
+        assert!(DownloadStore::load(temp_dir.path()).is_err());
+    }
+
+    #[test]
+    fn test_load_missing_store() {
+        let temp_dir = tempdir().unwrap();
+        let store = DownloadStore::load(temp_dir.path()).unwrap();
+        assert!(store.entries.is_empty());
+    }
+}
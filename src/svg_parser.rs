@@ -0,0 +1,174 @@
+use regex::{Captures, Regex};
+
+use crate::css_parser;
+use crate::html_parser::ResourceType;
+
+/// An external resource referenced from an SVG document
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SvgReference {
+    /// The reference as written, without any `#fragment`
+    pub url: String,
+    pub resource_type: ResourceType,
+}
+
+/// `href`/`xlink:href` on elements that load external files
+const HREF_PATTERN: &str = r#"(?is)(<(image|use|feImage|script)\b[^>]*?\s(?:xlink:)?href\s*=\s*)(?:"([^"]*)"|'([^']*)')"#;
+const XML_STYLESHEET_PATTERN: &str = r#"(?is)(<\?xml-stylesheet\b[^>]*?\shref\s*=\s*)(?:"([^"]*)"|'([^']*)')"#;
+
+/// Split `sprite.svg#icon` into the fetchable part and the fragment (with `#`)
+fn split_fragment(url: &str) -> (&str, &str) {
+    match url.find('#') {
+        Some(index) => (&url[..index], &url[index..]),
+        None => (url, ""),
+    }
+}
+
+fn is_external(url: &str) -> bool {
+    let url = url.trim();
+    !(url.is_empty() || url.starts_with("data:") || url.starts_with("javascript:"))
+}
+
+fn element_resource_type(element: &str, url: &str) -> ResourceType {
+    if element.eq_ignore_ascii_case("script") {
+        return ResourceType::JavaScript;
+    }
+    match ResourceType::from_extension(url) {
+        ResourceType::Other => ResourceType::Image,
+        resource_type => resource_type,
+    }
+}
+
+fn captured<'t>(caps: &Captures<'t>, groups: &[usize]) -> Option<&'t str> {
+    groups.iter().find_map(|&i| caps.get(i)).map(|m| m.as_str())
+}
+
+/// Extract external `<image>`/`<use>`/`<feImage>`/`<script>` hrefs,
+/// `<?xml-stylesheet?>` stylesheets and CSS `url()`/`@import` references
+pub fn extract_svg_references(svg_content: &str) -> Vec<SvgReference> {
+    let mut references = Vec::new();
+
+    let href_regex = Regex::new(HREF_PATTERN).unwrap();
+    for caps in href_regex.captures_iter(svg_content) {
+        let Some(value) = captured(&caps, &[3, 4]) else { continue };
+        let (url, _) = split_fragment(value.trim());
+        if is_external(url) {
+            references.push(SvgReference {
+                url: url.to_string(),
+                resource_type: element_resource_type(&caps[2], url),
+            });
+        }
+    }
+
+    let stylesheet_regex = Regex::new(XML_STYLESHEET_PATTERN).unwrap();
+    for caps in stylesheet_regex.captures_iter(svg_content) {
+        let Some(value) = captured(&caps, &[2, 3]) else { continue };
+        if is_external(value) {
+            references.push(SvgReference {
+                url: value.trim().to_string(),
+                resource_type: ResourceType::CSS,
+            });
+        }
+    }
+
+    for reference in css_parser::extract_css_references(svg_content) {
+        references.push(SvgReference {
+            resource_type: reference.resource_type(),
+            url: reference.url,
+        });
+    }
+
+    references
+}
+
+/// Rewrite the references found by [`extract_svg_references`]. The callback
+/// returns the replacement URL (fragments are re-appended), or `None` to
+/// leave the reference untouched.
+pub fn rewrite_svg_references<F>(svg_content: &str, mut rewrite: F) -> String
+where
+    F: FnMut(&SvgReference) -> Option<String>,
+{
+    let href_regex = Regex::new(HREF_PATTERN).unwrap();
+    let rewritten = href_regex.replace_all(svg_content, |caps: &Captures| {
+        let original = caps[0].to_string();
+        let Some(value) = captured(caps, &[3, 4]) else { return original };
+        let (url, fragment) = split_fragment(value.trim());
+        if !is_external(url) {
+            return original;
+        }
+
+        let reference = SvgReference {
+            url: url.to_string(),
+            resource_type: element_resource_type(&caps[2], url),
+        };
+        match rewrite(&reference) {
+            Some(new_url) => format!("{}\"{}{}\"", &caps[1], new_url, fragment),
+            None => original,
+        }
+    });
+
+    let stylesheet_regex = Regex::new(XML_STYLESHEET_PATTERN).unwrap();
+    let rewritten = stylesheet_regex.replace_all(&rewritten, |caps: &Captures| {
+        let original = caps[0].to_string();
+        let Some(value) = captured(caps, &[2, 3]) else { return original };
+        if !is_external(value) {
+            return original;
+        }
+
+        let reference = SvgReference {
+            url: value.trim().to_string(),
+            resource_type: ResourceType::CSS,
+        };
+        match rewrite(&reference) {
+            Some(new_url) => format!("{}\"{}\"", &caps[1], new_url),
+            None => original,
+        }
+    });
+
+    css_parser::rewrite_css_references(&rewritten, |reference| {
+        rewrite(&SvgReference {
+            url: reference.url.clone(),
+            resource_type: reference.resource_type(),
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SVG: &str = r##"<?xml version="1.0"?>
+<?xml-stylesheet type="text/css" href="theme.css"?>
+<svg xmlns="http://www.w3.org/2000/svg" xmlns:xlink="http://www.w3.org/1999/xlink">
+  <style>@import url(/css/icons.css); .bg { fill: url(#gradient); }</style>
+  <image x="0" y="0" xlink:href="photo.jpg"/>
+  <image href='data:image/png;base64,AAAA'/>
+  <use href="sprite.svg#icon-home"/>
+  <use href="#local-symbol"/>
+</svg>"##;
+
+    #[test]
+    fn test_extract_svg_references() {
+        let references = extract_svg_references(SVG);
+        let find = |url: &str| references.iter().find(|r| r.url == url).map(|r| r.resource_type.clone());
+
+        assert_eq!(references.len(), 4);
+        assert_eq!(find("photo.jpg"), Some(ResourceType::Image));
+        assert_eq!(find("sprite.svg"), Some(ResourceType::Image));
+        assert_eq!(find("theme.css"), Some(ResourceType::CSS));
+        assert_eq!(find("/css/icons.css"), Some(ResourceType::CSS));
+    }
+
+    #[test]
+    fn test_rewrite_svg_references() {
+        let rewritten = rewrite_svg_references(SVG, |reference| {
+            Some(format!("local/{}", reference.url.trim_start_matches('/')))
+        });
+
+        assert!(rewritten.contains(r#"href="local/theme.css""#));
+        assert!(rewritten.contains(r#"xlink:href="local/photo.jpg""#));
+        assert!(rewritten.contains(r#"href="local/sprite.svg#icon-home""#));
+        assert!(rewritten.contains(r#"@import url("local/css/icons.css")"#));
+        assert!(rewritten.contains(r##"href="#local-symbol""##));
+        assert!(rewritten.contains("url(#gradient)"));
+    }
+}
@@ -0,0 +1,11 @@
+//! Fixtures shared by the unit tests of several modules
+
+use std::fs;
+use std::path::Path;
+
+/// Write `content` to `path` under `dir`, creating parent directories
+pub fn write(dir: &Path, path: &str, content: &str) {
+    let path = dir.join(path);
+    fs::create_dir_all(path.parent().unwrap()).unwrap();
+    fs::write(path, content).unwrap();
+}
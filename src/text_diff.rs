@@ -0,0 +1,274 @@
+use anyhow::{Result, Context};
+use regex::{Captures, Regex};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::gallery::escape_html;
+use crate::store::DownloadStore;
+
+/// File name of the visible-text snapshot the next run diffs against
+pub const SNAPSHOT_FILE_NAME: &str = ".text_snapshot.json";
+
+/// File name of the HTML report of textual changes
+pub const REPORT_FILE_NAME: &str = "text-diff.html";
+
+/// Elements whose content never renders as text
+const HIDDEN_ELEMENTS: &[&str] = &["head", "script", "style", "noscript", "template", "svg"];
+
+/// Tags that start a new block of text
+const BLOCK_TAG_PATTERN: &str = r"(?i)</?(?:p|div|h[1-6]|li|ul|ol|dl|dt|dd|tr|td|th|table|caption|section|article|header|footer|nav|aside|main|blockquote|pre|figure|figcaption|form|fieldset|legend|details|summary|br|hr)\b[^>]*>";
+
+/// A paragraph added to or removed from a page since the previous run
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub enum TextChange {
+    Added(String),
+    Removed(String),
+}
+
+/// Textual changes to one page that exists in both runs
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PageTextDiff {
+    pub url: String,
+    pub local_path: String,
+    pub changes: Vec<TextChange>,
+}
+
+/// Visible text blocks of every saved page, keyed by URL
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct TextSnapshot {
+    pub pages: BTreeMap<String, Vec<String>>,
+}
+
+impl TextSnapshot {
+    /// Load the previous run's snapshot, starting empty if there is none
+    pub fn load(output_dir: &Path) -> Result<Self> {
+        let path = output_dir.join(SNAPSHOT_FILE_NAME);
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let data = fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read text snapshot: {:?}", path))?;
+        serde_json::from_str(&data)
+            .with_context(|| format!("Failed to parse text snapshot: {:?}", path))
+    }
+
+    pub fn save(&self, output_dir: &Path) -> Result<()> {
+        let path = output_dir.join(SNAPSHOT_FILE_NAME);
+        let data = serde_json::to_string_pretty(self)?;
+        fs::write(&path, data)
+            .with_context(|| format!("Failed to write text snapshot: {:?}", path))
+    }
+}
+
+fn decode_entities(text: &str) -> String {
+    let entity_regex = Regex::new(r"&(?:#(\d+)|#[xX]([0-9a-fA-F]+)|([a-zA-Z]+));").unwrap();
+    entity_regex
+        .replace_all(text, |caps: &Captures| {
+            let decoded = if let Some(decimal) = caps.get(1) {
+                decimal.as_str().parse().ok().and_then(char::from_u32)
+            } else if let Some(hex) = caps.get(2) {
+                u32::from_str_radix(hex.as_str(), 16).ok().and_then(char::from_u32)
+            } else {
+                match &caps[3] {
+                    "amp" => Some('&'),
+                    "lt" => Some('<'),
+                    "gt" => Some('>'),
+                    "quot" => Some('"'),
+                    "apos" => Some('\''),
+                    "nbsp" => Some(' '),
+                    _ => None,
+                }
+            };
+            decoded.map_or_else(|| caps[0].to_string(), String::from)
+        })
+        .into_owned()
+}
+
+/// Visible text of a page as paragraphs: one per block element, whitespace collapsed
+pub fn extract_text_blocks(html_content: &str) -> Vec<String> {
+    let mut text = Regex::new(r"(?s)<!--.*?-->").unwrap().replace_all(html_content, "").into_owned();
+    for element in HIDDEN_ELEMENTS {
+        let element_regex = Regex::new(&format!(r"(?is)<{0}\b.*?</{0}\s*>", element)).unwrap();
+        text = element_regex.replace_all(&text, "").into_owned();
+    }
+    text = Regex::new(BLOCK_TAG_PATTERN).unwrap().replace_all(&text, "\n").into_owned();
+    text = Regex::new(r"<[^>]*>").unwrap().replace_all(&text, "").into_owned();
+
+    decode_entities(&text)
+        .lines()
+        .map(|line| line.split_whitespace().collect::<Vec<_>>().join(" "))
+        .filter(|line| !line.is_empty())
+        .collect()
+}
+
+/// Paragraphs removed from `previous` and added in `current`, in page order
+/// (longest common subsequence; unchanged paragraphs are omitted)
+pub fn diff_blocks(previous: &[String], current: &[String]) -> Vec<TextChange> {
+    let (n, m) = (previous.len(), current.len());
+    let mut common = vec![vec![0usize; m + 1]; n + 1];
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            common[i][j] = if previous[i] == current[j] {
+                common[i + 1][j + 1] + 1
+            } else {
+                common[i + 1][j].max(common[i][j + 1])
+            };
+        }
+    }
+
+    let mut changes = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < n || j < m {
+        if i < n && j < m && previous[i] == current[j] {
+            i += 1;
+            j += 1;
+        } else if j < m && (i == n || common[i][j + 1] >= common[i + 1][j]) {
+            changes.push(TextChange::Added(current[j].clone()));
+            j += 1;
+        } else {
+            changes.push(TextChange::Removed(previous[i].clone()));
+            i += 1;
+        }
+    }
+    changes
+}
+
+/// Extract the text of every saved page, diff it against the previous run's
+/// snapshot and save this run's snapshot for the next one. Pages that are new
+/// in this run aren't reported.
+pub fn compare_runs(output_dir: &Path, store: &DownloadStore) -> Result<Vec<PageTextDiff>> {
+    let previous = TextSnapshot::load(output_dir)?;
+    let mut current = TextSnapshot::default();
+    let mut diffs = Vec::new();
+
+    for (url, entry) in &store.entries {
+        let is_page = entry.local_path.ends_with(".html") || entry.content_type.as_deref().is_some_and(|c| c.starts_with("text/html"));
+        if entry.local_path.is_empty() || !is_page {
+            continue;
+        }
+        let Ok(content) = fs::read(output_dir.join(&entry.local_path)) else { continue };
+        let blocks = extract_text_blocks(&String::from_utf8_lossy(&content));
+
+        if let Some(previous_blocks) = previous.pages.get(url) {
+            let changes = diff_blocks(previous_blocks, &blocks);
+            if !changes.is_empty() {
+                diffs.push(PageTextDiff {
+                    url: url.clone(),
+                    local_path: entry.local_path.clone(),
+                    changes,
+                });
+            }
+        }
+        current.pages.insert(url.clone(), blocks);
+    }
+
+    current.save(output_dir)?;
+    Ok(diffs)
+}
+
+/// Render the report of textual changes, one section per changed page
+pub fn render_report(diffs: &[PageTextDiff]) -> String {
+    let mut html = String::new();
+    html.push_str("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n");
+    html.push_str("<title>Text Changes</title>\n<style>\n");
+    html.push_str("body { font-family: sans-serif; margin: 1rem; }\n");
+    html.push_str("section { margin-bottom: 2rem; }\n");
+    html.push_str("p { margin: 0.25rem 0; padding: 0.25rem 0.5rem; white-space: pre-wrap; }\n");
+    html.push_str(".added { background: #e6ffec; border-left: 4px solid #2da44e; }\n");
+    html.push_str(".removed { background: #ffebe9; border-left: 4px solid #cf222e; text-decoration: line-through; }\n");
+    html.push_str("</style>\n</head>\n<body>\n");
+    html.push_str(&format!("<h1>Text Changes ({} pages)</h1>\n", diffs.len()));
+
+    for diff in diffs {
+        html.push_str(&format!(
+            "<section>\n<h2><a href=\"{}\">{}</a></h2>\n",
+            escape_html(&diff.local_path),
+            escape_html(&diff.url)
+        ));
+        for change in &diff.changes {
+            let (class, text) = match change {
+                TextChange::Added(text) => ("added", text),
+                TextChange::Removed(text) => ("removed", text),
+            };
+            html.push_str(&format!("<p class=\"{}\">{}</p>\n", class, escape_html(text)));
+        }
+        html.push_str("</section>\n");
+    }
+
+    html.push_str("</body>\n</html>\n");
+    html
+}
+
+/// Write `text-diff.html` at the root of the output directory
+pub fn save_report(output_dir: &Path, diffs: &[PageTextDiff]) -> Result<PathBuf> {
+    let report_path = output_dir.join(REPORT_FILE_NAME);
+    fs::write(&report_path, render_report(diffs))
+        .with_context(|| format!("Failed to write text diff report: {:?}", report_path))?;
+    Ok(report_path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn blocks(texts: &[&str]) -> Vec<String> {
+        texts.iter().map(|text| text.to_string()).collect()
+    }
+
+    #[test]
+    fn test_extract_text_blocks() {
+        let html = r#"<html><head><title>Home</title><style>p { color: red; }</style></head>
+<body><nav><a href="/">Home</a> | <a href="/about/">About &amp; contact</a></nav>
+<script>var hidden = "<p>not text</p>";</script><!-- comment -->
+<h1>Welcome</h1><p>First   paragraph<br>second line &#8212; &quot;quoted&quot;</p>
+<ul><li>One</li><li>Two</li></ul></body></html>"#;
+
+        assert_eq!(
+            extract_text_blocks(html),
+            blocks(&["Home | About & contact", "Welcome", "First paragraph", "second line \u{2014} \"quoted\"", "One", "Two"])
+        );
+    }
+
+    #[test]
+    fn test_diff_blocks() {
+        let previous = blocks(&["Title", "Old intro", "Body", "Footer"]);
+        let current = blocks(&["Title", "New intro", "Body", "Extra", "Footer"]);
+
+        assert_eq!(
+            diff_blocks(&previous, &current),
+            vec![
+                TextChange::Added("New intro".to_string()),
+                TextChange::Removed("Old intro".to_string()),
+                TextChange::Added("Extra".to_string()),
+            ]
+        );
+        assert!(diff_blocks(&previous, &previous).is_empty());
+    }
+
+    #[test]
+    fn test_compare_runs() {
+        let temp_dir = tempdir().unwrap();
+        let dir = temp_dir.path();
+        let mut store = DownloadStore::default();
+        store.record_download("https://example.com/", "index.html", Some("text/html"), 1);
+
+        fs::write(dir.join("index.html"), "<p>Hello</p><p>Prices from $10</p>").unwrap();
+        assert!(compare_runs(dir, &store).unwrap().is_empty());
+
+        fs::write(dir.join("index.html"), "<p>Hello</p><p>Prices from $12</p>").unwrap();
+        let diffs = compare_runs(dir, &store).unwrap();
+        assert_eq!(diffs.len(), 1);
+        assert_eq!(
+            diffs[0].changes,
+            vec![TextChange::Added("Prices from $12".to_string()), TextChange::Removed("Prices from $10".to_string())]
+        );
+
+        let report = render_report(&diffs);
+        assert!(report.contains(r#"<a href="index.html">https://example.com/</a>"#));
+        assert!(report.contains(r#"<p class="removed">Prices from $10</p>"#));
+    }
+}
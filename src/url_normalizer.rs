@@ -0,0 +1,145 @@
+use url::Url;
+
+/// Query parameters that only carry analytics/campaign data
+pub const TRACKING_PARAMS: &[&str] = &[
+    "fbclid",
+    "gclid",
+    "dclid",
+    "msclkid",
+    "yclid",
+    "igshid",
+    "mc_cid",
+    "mc_eid",
+    "_ga",
+    "_gl",
+];
+
+fn is_tracking_param(name: &str) -> bool {
+    let name = name.to_ascii_lowercase();
+    name.starts_with("utm_") || TRACKING_PARAMS.contains(&name.as_str())
+}
+
+/// Canonical form of a URL: fragment removed, dot-segments resolved, scheme and
+/// host lowercased, default port dropped and, with `strip_tracking`, `utm_*`
+/// and click-id parameters removed. Unparseable URLs are returned unchanged.
+pub fn normalize_url(url: &str, strip_tracking: bool) -> String {
+    let Ok(mut parsed) = Url::parse(url.trim()) else {
+        return url.to_string();
+    };
+    parsed.set_fragment(None);
+
+    if strip_tracking && parsed.query().is_some() {
+        let kept: Vec<(String, String)> = parsed
+            .query_pairs()
+            .filter(|(name, _)| !is_tracking_param(name))
+            .map(|(name, value)| (name.into_owned(), value.into_owned()))
+            .collect();
+        if kept.is_empty() {
+            parsed.set_query(None);
+        } else {
+            parsed.query_pairs_mut().clear().extend_pairs(kept);
+        }
+    }
+    if parsed.query() == Some("") {
+        parsed.set_query(None);
+    }
+    parsed.to_string()
+}
+
+/// Key under which a URL is deduplicated: its normalized form, treating
+/// `/page` and `/page/` (both saved as `page/index.html`) as the same page
+pub fn dedup_key(url: &str, strip_tracking: bool) -> String {
+    let normalized = normalize_url(url, strip_tracking);
+    let Ok(mut parsed) = Url::parse(&normalized) else {
+        return normalized;
+    };
+    let path = parsed.path().to_string();
+    if path.len() > 1 && path.ends_with('/') {
+        parsed.set_path(path.trim_end_matches('/'));
+    }
+    parsed.to_string()
+}
+
+/// Registrable domain of a host per the Public Suffix List (`shop.example.co.uk`
+/// -> `example.co.uk`); `None` for IP addresses and bare public suffixes
+pub fn registrable_domain(host: &str) -> Option<&str> {
+    if host.parse::<std::net::IpAddr>().is_ok() || host.starts_with('[') {
+        return None;
+    }
+    psl::domain_str(host)
+}
+
+/// The other usual name of a site's host: `www.example.com` for `example.com`
+/// and the reverse
+pub fn www_alias(host: &str) -> String {
+    match host.strip_prefix("www.") {
+        Some(apex) => apex.to_string(),
+        None => format!("www.{}", host),
+    }
+}
+
+/// Whether `host` is another name of `site_host`: its www/apex counterpart or
+/// one of `aliases`
+pub fn is_host_alias(host: &str, site_host: &str, aliases: &[String]) -> bool {
+    !host.eq_ignore_ascii_case(site_host)
+        && (host.eq_ignore_ascii_case(&www_alias(site_host)) || aliases.iter().any(|alias| alias.eq_ignore_ascii_case(host)))
+}
+
+/// `url` with an alias host of `site_host` replaced by `site_host`, so the
+/// site's pages are fetched and saved once however their host is spelled
+pub fn unalias_host(url: &str, site_host: &str, aliases: &[String]) -> String {
+    let Ok(mut parsed) = Url::parse(url) else {
+        return url.to_string();
+    };
+    if !parsed.host_str().is_some_and(|host| is_host_alias(host, site_host, aliases)) || parsed.set_host(Some(site_host)).is_err() {
+        return url.to_string();
+    }
+    parsed.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_url() {
+        assert_eq!(normalize_url("https://Example.com:443/a/./b/../page#section", false), "https://example.com/a/page");
+        assert_eq!(normalize_url("http://example.com:80/", false), "http://example.com/");
+        assert_eq!(normalize_url("https://example.com/page?", false), "https://example.com/page");
+        assert_eq!(normalize_url("not a url", false), "not a url");
+    }
+
+    #[test]
+    fn test_normalize_url_strips_tracking_params() {
+        assert_eq!(normalize_url("https://example.com/page?utm_source=x&utm_medium=email", true), "https://example.com/page");
+        assert_eq!(normalize_url("https://example.com/page?id=3&fbclid=abc", true), "https://example.com/page?id=3");
+        assert_eq!(normalize_url("https://example.com/page?utm_source=x", false), "https://example.com/page?utm_source=x");
+    }
+
+    #[test]
+    fn test_dedup_key() {
+        let key = dedup_key("https://example.com/page", true);
+        assert_eq!(dedup_key("https://example.com/page/", true), key);
+        assert_eq!(dedup_key("https://example.com/page#section", true), key);
+        assert_eq!(dedup_key("https://example.com/page?utm_source=x", true), key);
+        assert_eq!(dedup_key("https://example.com/", true), "https://example.com/");
+    }
+
+    #[test]
+    fn test_registrable_domain() {
+        assert_eq!(registrable_domain("shop.example.co.uk"), Some("example.co.uk"));
+        assert_eq!(registrable_domain("www.example.com"), Some("example.com"));
+        assert_eq!(registrable_domain("co.uk"), None);
+        assert_eq!(registrable_domain("192.168.0.1"), None);
+    }
+
+    #[test]
+    fn test_unalias_host() {
+        let aliases = vec!["old.example.org".to_string()];
+        assert_eq!(unalias_host("https://www.example.com/a?b=1", "example.com", &aliases), "https://example.com/a?b=1");
+        assert_eq!(unalias_host("https://OLD.example.org/a", "example.com", &aliases), "https://example.com/a");
+        assert_eq!(unalias_host("https://example.com/a", "www.example.com", &[]), "https://www.example.com/a");
+        assert_eq!(unalias_host("https://cdn.example.com/a", "example.com", &aliases), "https://cdn.example.com/a");
+        assert_eq!(unalias_host("page.html", "example.com", &aliases), "page.html");
+    }
+}
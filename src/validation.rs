@@ -0,0 +1,262 @@
+use anyhow::{Result, Context};
+use html5ever::tendril::TendrilSink;
+use html5ever::tree_builder::TreeBuilderOpts;
+use html5ever::{parse_document, ParseOpts};
+use markup5ever_rcdom::RcDom;
+use regex::Regex;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use crate::dead_assets::{self, DocumentKind};
+use crate::external_links::DeadExternalLink;
+use crate::parallel;
+
+/// File name of the validation report, stored at the root of the output directory
+pub const REPORT_FILE_NAME: &str = "validation-report.json";
+
+/// A local reference in a saved page or stylesheet whose target wasn't saved
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct MissingReference {
+    /// Page or stylesheet containing the reference, relative to the output directory
+    pub document: String,
+    /// The reference as written
+    pub reference: String,
+    /// Path the reference resolves to, relative to the output directory
+    pub target: String,
+}
+
+/// HTML parse errors (unclosed tags, misnesting, stray end tags) in one saved page,
+/// i.e. places where browsers have to repair the markup
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PageParseErrors {
+    pub document: String,
+    pub error_count: usize,
+    /// Error description -> occurrences
+    pub errors: BTreeMap<String, usize>,
+}
+
+/// Outcome of checking every saved page and stylesheet against the output tree
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct ValidationReport {
+    pub documents_checked: usize,
+    pub references_checked: usize,
+    pub missing: Vec<MissingReference>,
+    /// Pages with HTML parse errors, in document order
+    pub parse_errors: Vec<PageParseErrors>,
+    /// Links to pages that aren't mirrored and no longer answer (`--check-external-links`)
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub dead_external_links: Vec<DeadExternalLink>,
+}
+
+impl ValidationReport {
+    pub fn is_valid(&self) -> bool {
+        self.missing.is_empty()
+    }
+
+    pub fn save(&self, output_dir: &Path) -> Result<()> {
+        let path = output_dir.join(REPORT_FILE_NAME);
+        let data = serde_json::to_string_pretty(self)?;
+        fs::write(&path, data)
+            .with_context(|| format!("Failed to write validation report: {:?}", path))
+    }
+}
+
+/// Saved pages and stylesheets under `dir`, relative to `output_dir`, skipping
+/// hidden files such as the crawl manifest
+fn collect_documents(output_dir: &Path, dir: &Path, documents: &mut Vec<(String, DocumentKind)>) -> Result<()> {
+    let entries = fs::read_dir(dir).with_context(|| format!("Failed to read directory: {:?}", dir))?;
+    for entry in entries {
+        let path = entry?.path();
+        if path.file_name().is_some_and(|name| name.to_string_lossy().starts_with('.')) {
+            continue;
+        }
+        if path.is_dir() {
+            collect_documents(output_dir, &path, documents)?;
+            continue;
+        }
+        let Ok(relative) = path.strip_prefix(output_dir) else { continue };
+        let relative = relative.to_string_lossy().replace('\\', "/");
+        match dead_assets::document_kind(&relative, None) {
+            DocumentKind::Asset => {}
+            kind => documents.push((relative, kind)),
+        }
+    }
+    Ok(())
+}
+
+/// `<td>`/`</div>` for tag tokens in html5ever's exact error messages,
+/// otherwise the kind of token
+fn describe_token(token: &str) -> String {
+    let tag_regex = Regex::new(r"kind: (StartTag|EndTag), name: Atom\(\\?'([^'\\]+)").unwrap();
+    if let Some(caps) = tag_regex.captures(token) {
+        let slash = if &caps[1] == "EndTag" { "/" } else { "" };
+        return format!("<{}{}>", slash, &caps[2]);
+    }
+    let kind = if token.starts_with("CharacterTokens") {
+        "text"
+    } else if token.starts_with("CommentToken") {
+        "comment"
+    } else if token.starts_with("DoctypeToken") {
+        "DOCTYPE"
+    } else if token.starts_with("NullCharacterToken") {
+        "NUL character"
+    } else if token.starts_with("EOFToken") {
+        "end of file"
+    } else {
+        "token"
+    };
+    kind.to_string()
+}
+
+/// Readable form of an html5ever parse error: `Unexpected </div> in InCell`,
+/// `Unexpected open tag <div> at end of body`
+fn describe_parse_error(message: &str) -> String {
+    if let Some(token) = message.strip_prefix("Unexpected token ") {
+        return match token.rsplit_once(" in insertion mode ") {
+            Some((token, mode)) => format!("Unexpected {} in {}", describe_token(token), mode),
+            None => format!("Unexpected {}", describe_token(token)),
+        };
+    }
+    let element_regex = Regex::new(r"\{[^}]*\}:([A-Za-z0-9-]+)").unwrap();
+    element_regex.replace_all(message, "<$1>").into_owned()
+}
+
+/// Parse errors a browser's HTML parser would hit in a page
+pub fn html_parse_errors(html_content: &str) -> Vec<String> {
+    let opts = ParseOpts {
+        tree_builder: TreeBuilderOpts {
+            exact_errors: true,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let dom = parse_document(RcDom::default(), opts).one(html_content);
+    dom.errors.iter().map(|error| describe_parse_error(error)).collect()
+}
+
+/// Whether a resolved reference points at a saved file (directories need an `index.html`)
+fn target_exists(output_dir: &Path, target: &str) -> bool {
+    let path = output_dir.join(target);
+    path.is_file() || path.join("index.html").is_file()
+}
+
+/// Load every saved HTML page and stylesheet and resolve each local reference
+/// against the output tree. `link_root` is the prefix links to the output root
+/// were written with (`--rewrite-host`/`--link-base`); links to the live web are
+/// not checked.
+pub fn validate_output(output_dir: &Path, link_root: Option<&str>) -> Result<ValidationReport> {
+    let mut documents = Vec::new();
+    collect_documents(output_dir, output_dir, &mut documents)?;
+    documents.sort_by(|a, b| a.0.cmp(&b.0));
+
+    // Documents are parsed in parallel and their findings merged in document order
+    let checked = parallel::map_with_progress(&documents, "Validating", |(document, kind)| {
+        check_document(output_dir, document, *kind, link_root)
+    });
+    let mut report = ValidationReport::default();
+    for document_report in checked.into_iter().flatten() {
+        report.documents_checked += 1;
+        report.references_checked += document_report.references_checked;
+        report.missing.extend(document_report.missing);
+        report.parse_errors.extend(document_report.parse_errors);
+    }
+    Ok(report)
+}
+
+/// Findings for one page or stylesheet, `None` if it can't be read
+fn check_document(output_dir: &Path, document: &str, kind: DocumentKind, link_root: Option<&str>) -> Option<ValidationReport> {
+    let content = fs::read(output_dir.join(document)).ok()?;
+    let content = String::from_utf8_lossy(&content);
+    let mut report = ValidationReport::default();
+    
+    if kind == DocumentKind::Html {
+        let errors = html_parse_errors(&content);
+        if !errors.is_empty() {
+            let mut counts = BTreeMap::new();
+            for error in &errors {
+                *counts.entry(error.clone()).or_insert(0) += 1;
+            }
+            report.parse_errors.push(PageParseErrors {
+                document: document.to_string(),
+                error_count: errors.len(),
+                errors: counts,
+            });
+        }
+    }
+
+    for reference in dead_assets::extract_references(&content, kind) {
+        let root_path = link_root
+            .filter(|_| !reference.trim().starts_with("//"))
+            .and_then(|root| reference.trim().strip_prefix(root));
+        let target = match root_path {
+            Some(root_path) => dead_assets::resolve_reference(document, &format!("/{}", root_path)),
+            None => dead_assets::resolve_reference(document, &reference),
+        };
+        let Some(target) = target else { continue };
+        report.references_checked += 1;
+        if !target_exists(output_dir, &target) {
+            report.missing.push(MissingReference {
+                document: document.to_string(),
+                reference,
+                target,
+            });
+        }
+    }
+    Some(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::write;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_validate_output() {
+        let temp_dir = tempdir().unwrap();
+        let dir = temp_dir.path();
+        write(dir, "index.html", r#"<link href="css/site.css" rel="stylesheet"><a href="blog/">Blog</a><a href="https://example.com/about/">About</a><img src="img/missing.png">"#);
+        write(dir, "blog/index.html", r##"<img srcset="../img/a.png 1x, ../img/b.png 2x"><a href="#top">Top</a>"##);
+        write(dir, "css/site.css", ".hero { background: url('../img/a.png'); } .old { background: url(../img/old.jpg); }");
+        write(dir, "img/a.png", "x");
+        write(dir, ".download_store.json", "{}");
+
+        let report = validate_output(dir, None).unwrap();
+        assert_eq!(report.documents_checked, 3);
+        assert!(!report.is_valid());
+        let missing: Vec<(&str, &str)> = report.missing.iter().map(|m| (m.document.as_str(), m.target.as_str())).collect();
+        assert_eq!(missing, vec![("blog/index.html", "img/b.png"), ("css/site.css", "img/old.jpg"), ("index.html", "img/missing.png")]);
+        // Fragments without a DOCTYPE put browsers in quirks mode
+        assert_eq!(report.parse_errors.len(), 2);
+        assert_eq!(report.parse_errors[0].document, "blog/index.html");
+    }
+
+    #[test]
+    fn test_html_parse_errors() {
+        let errors = html_parse_errors("<!DOCTYPE html><html><head><title>t</title></head><body><b><i>x</b></i><table><td>a</td></table><div>open</body></html>");
+        assert_eq!(
+            errors,
+            vec![
+                "Formatting element not current node",
+                "Formatting element not open",
+                "Unexpected <td> in InTableBody",
+                "Unexpected open tag <div> at end of body",
+            ]
+        );
+        assert!(html_parse_errors("<!DOCTYPE html><html><head><title>t</title></head><body><p>fine</p></body></html>").is_empty());
+    }
+
+    #[test]
+    fn test_validate_output_with_link_root() {
+        let temp_dir = tempdir().unwrap();
+        let dir = temp_dir.path();
+        write(dir, "blog/index.html", r#"<link href="/archive/site1/css/site.css" rel="stylesheet"><script src="/archive/site1/js/app.js"></script>"#);
+        write(dir, "css/site.css", "body {}");
+
+        let report = validate_output(dir, Some("/archive/site1/")).unwrap();
+        assert_eq!(report.missing.len(), 1);
+        assert_eq!(report.missing[0].target, "js/app.js");
+    }
+}
@@ -0,0 +1,117 @@
+use anyhow::{anyhow, Result, Context};
+use std::fs;
+use std::path::Path;
+
+use crate::dead_assets::{self, DocumentKind};
+use crate::validation::{self, MissingReference};
+
+/// Outcome of `website-mirror verify <dir>`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct VerifyReport {
+    /// Saved pages and stylesheets parsed
+    pub documents_checked: usize,
+    pub references_checked: usize,
+    /// Images, stylesheets, scripts and other files referenced but not saved
+    pub missing_files: Vec<MissingReference>,
+    /// Links to pages that weren't saved
+    pub dangling_links: Vec<MissingReference>,
+    /// Saved files with no content, relative to the mirror directory
+    pub zero_byte_files: Vec<String>,
+    /// Pages browsers have to repair the markup of (informational)
+    pub pages_with_parse_errors: usize,
+}
+
+impl VerifyReport {
+    pub fn is_ok(&self) -> bool {
+        self.missing_files.is_empty() && self.dangling_links.is_empty() && self.zero_byte_files.is_empty()
+    }
+}
+
+/// Whether a missing target is a page rather than a file: `about/`, `about.html`
+fn is_page_target(target: &str) -> bool {
+    Path::new(target).extension().is_none() || dead_assets::document_kind(target, None) == DocumentKind::Html
+}
+
+/// Empty files under `dir`, relative to `root`, skipping hidden files such as the crawl manifest
+fn collect_zero_byte_files(root: &Path, dir: &Path, files: &mut Vec<String>) -> Result<()> {
+    let entries = fs::read_dir(dir).with_context(|| format!("Failed to read directory: {:?}", dir))?;
+    for entry in entries {
+        let entry = entry?;
+        let path = entry.path();
+        if entry.file_name().to_string_lossy().starts_with('.') {
+            continue;
+        }
+        if path.is_dir() {
+            collect_zero_byte_files(root, &path, files)?;
+        } else if entry.metadata()?.len() == 0 {
+            if let Ok(relative) = path.strip_prefix(root) {
+                files.push(relative.to_string_lossy().replace('\\', "/"));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Re-parse every saved page and stylesheet in a finished mirror, resolve each
+/// local reference and look for empty files. `link_root` is the prefix links to
+/// the mirror root were written with (`--rewrite-host`/`--link-base`).
+pub fn verify_mirror(dir: &Path, link_root: Option<&str>) -> Result<VerifyReport> {
+    if !dir.is_dir() {
+        return Err(anyhow!("{} is not a directory", dir.display()));
+    }
+    let validation = validation::validate_output(dir, link_root)?;
+    let (dangling_links, missing_files): (Vec<_>, Vec<_>) = validation.missing.into_iter().partition(|missing| is_page_target(&missing.target));
+
+    let mut zero_byte_files = Vec::new();
+    collect_zero_byte_files(dir, dir, &mut zero_byte_files)?;
+    zero_byte_files.sort();
+
+    Ok(VerifyReport {
+        documents_checked: validation.documents_checked,
+        references_checked: validation.references_checked,
+        missing_files,
+        dangling_links,
+        zero_byte_files,
+        pages_with_parse_errors: validation.parse_errors.len(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::write;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_verify_mirror() {
+        let temp_dir = tempdir().unwrap();
+        let dir = temp_dir.path();
+        write(dir, "index.html", r#"<link href="css/site.css" rel="stylesheet"><a href="blog/">Blog</a><a href="about/">About</a><img src="img/logo.png"><script src="js/app.js"></script>"#);
+        write(dir, "blog/index.html", r#"<a href="../index.html">Home</a><a href="../old.html">Old</a>"#);
+        write(dir, "css/site.css", "");
+        write(dir, "img/logo.png", "x");
+        write(dir, ".download_store.json", "");
+
+        let report = verify_mirror(dir, None).unwrap();
+        assert!(!report.is_ok());
+        assert_eq!(report.documents_checked, 3);
+        let targets = |references: &[MissingReference]| references.iter().map(|missing| missing.target.clone()).collect::<Vec<_>>();
+        assert_eq!(targets(&report.missing_files), vec!["js/app.js"]);
+        assert_eq!(targets(&report.dangling_links), vec!["old.html", "about"]);
+        assert_eq!(report.zero_byte_files, vec!["css/site.css"]);
+    }
+
+    #[test]
+    fn test_verify_complete_mirror() {
+        let temp_dir = tempdir().unwrap();
+        let dir = temp_dir.path();
+        write(dir, "index.html", r#"<!DOCTYPE html><html><head><title>t</title></head><body><a href="blog/">Blog</a></body></html>"#);
+        write(dir, "blog/index.html", r#"<!DOCTYPE html><html><head><title>b</title></head><body><a href="../index.html">Home</a></body></html>"#);
+
+        let report = verify_mirror(dir, None).unwrap();
+        assert!(report.is_ok());
+        assert_eq!(report.references_checked, 2);
+        assert_eq!(report.pages_with_parse_errors, 0);
+        assert!(verify_mirror(&dir.join("missing"), None).is_err());
+    }
+}
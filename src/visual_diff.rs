@@ -0,0 +1,248 @@
+use anyhow::{anyhow, Result, Context};
+use image::{Rgba, RgbaImage};
+use serde::Serialize;
+use std::fs;
+use std::path::Path;
+use tokio::process::Command;
+use tracing::warn;
+
+use crate::store::DownloadStore;
+
+/// Directory inside the output holding page screenshots (`previous/` from the
+/// last run, `diff/` with the highlighted differences) and `report.json`
+pub const VISUAL_DIR: &str = ".visual";
+
+/// Percentage of changed pixels above which a page is flagged
+pub const DEFAULT_THRESHOLD: f64 = 1.0;
+
+/// Per-channel difference treated as noise (anti-aliasing, font hinting)
+const CHANNEL_TOLERANCE: u8 = 16;
+
+/// Pixels that differ between two screenshots
+pub struct PixelDiff {
+    pub changed_pixels: u64,
+    pub total_pixels: u64,
+    /// The new screenshot faded out, with changed pixels in red
+    pub image: RgbaImage,
+}
+
+impl PixelDiff {
+    pub fn changed_percent(&self) -> f64 {
+        if self.total_pixels == 0 {
+            return 0.0;
+        }
+        self.changed_pixels as f64 * 100.0 / self.total_pixels as f64
+    }
+}
+
+/// A page whose screenshot was compared with the previous run's
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PageVisualChange {
+    pub url: String,
+    pub local_path: String,
+    pub changed_percent: f64,
+    /// Diff image relative to the output directory, written when anything changed
+    pub diff_image: Option<String>,
+}
+
+/// Compare two screenshots pixel by pixel; when the page grew or shrank, the
+/// area only one of them covers counts as changed
+pub fn pixel_diff(previous: &RgbaImage, current: &RgbaImage) -> PixelDiff {
+    let width = previous.width().max(current.width());
+    let height = previous.height().max(current.height());
+    let mut image = RgbaImage::new(width, height);
+    let mut changed_pixels = 0;
+
+    for y in 0..height {
+        for x in 0..width {
+            let before = previous.get_pixel_checked(x, y);
+            let after = current.get_pixel_checked(x, y);
+            let changed = match (before, after) {
+                (Some(before), Some(after)) => before.0.iter().zip(after.0.iter()).any(|(a, b)| a.abs_diff(*b) > CHANNEL_TOLERANCE),
+                _ => true,
+            };
+            let pixel = if changed {
+                changed_pixels += 1;
+                Rgba([255, 0, 0, 255])
+            } else {
+                let [r, g, b, _] = after.map_or([255; 4], |pixel| pixel.0);
+                let luma = (r as u32 * 299 + g as u32 * 587 + b as u32 * 114) / 1000;
+                let faded = (191 + luma / 4) as u8;
+                Rgba([faded, faded, faded, 255])
+            };
+            image.put_pixel(x, y, pixel);
+        }
+    }
+
+    PixelDiff {
+        changed_pixels,
+        total_pixels: width as u64 * height as u64,
+        image,
+    }
+}
+
+fn shell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', r"'\''"))
+}
+
+/// Render a saved page to a PNG with the user's screenshot command, e.g.
+/// `chromium --headless --screenshot={output} {url}`. `{url}` is the page's
+/// `file://` URL and `{output}` the PNG to write.
+pub async fn take_screenshot(command_template: &str, page_file: &Path, output: &Path) -> Result<()> {
+    let page_url = url::Url::from_file_path(page_file)
+        .map_err(|_| anyhow!("Can't build a file:// URL for {:?}", page_file))?;
+    let command = command_template
+        .replace("{url}", &shell_quote(page_url.as_str()))
+        .replace("{output}", &shell_quote(&output.to_string_lossy()));
+
+    let result = Command::new("sh")
+        .arg("-c")
+        .arg(&command)
+        .output()
+        .await
+        .with_context(|| format!("Failed to run screenshot command: {}", command))?;
+    if !result.status.success() {
+        return Err(anyhow!(
+            "Screenshot command exited with {}: {}",
+            result.status,
+            String::from_utf8_lossy(&result.stderr).trim()
+        ));
+    }
+    if !output.is_file() {
+        return Err(anyhow!("Screenshot command didn't write {:?}", output));
+    }
+    Ok(())
+}
+
+/// Screenshot every saved page, compare each with the previous run's screenshot
+/// and write diff images for the ones that changed. This run's screenshots
+/// become the baseline for the next run. Pages without a previous screenshot
+/// (new pages, first run) aren't reported.
+pub async fn compare_runs(output_dir: &Path, store: &DownloadStore, command_template: &str) -> Result<Vec<PageVisualChange>> {
+    let output_dir = fs::canonicalize(output_dir)
+        .with_context(|| format!("Failed to resolve output directory: {:?}", output_dir))?;
+    let visual_dir = output_dir.join(VISUAL_DIR);
+    let current_dir = visual_dir.join("current");
+    let previous_dir = visual_dir.join("previous");
+    let diff_dir = visual_dir.join("diff");
+    for dir in [&current_dir, &diff_dir] {
+        if dir.exists() {
+            fs::remove_dir_all(dir).with_context(|| format!("Failed to clear {:?}", dir))?;
+        }
+    }
+
+    let mut changes = Vec::new();
+    for (url, entry) in &store.entries {
+        let local_path = entry.local_path.as_str();
+        let is_page = local_path.ends_with(".html") || entry.content_type.as_deref().is_some_and(|c| c.starts_with("text/html"));
+        let page_file = output_dir.join(local_path);
+        if local_path.is_empty() || !is_page || !page_file.is_file() {
+            continue;
+        }
+
+        let screenshot_name = format!("{}.png", local_path);
+        let screenshot = current_dir.join(&screenshot_name);
+        fs::create_dir_all(screenshot.parent().unwrap_or(&current_dir))?;
+        if let Err(e) = take_screenshot(command_template, &page_file, &screenshot).await {
+            warn!("⚠️  Screenshot failed for {}: {}", local_path, e);
+            continue;
+        }
+
+        let previous = previous_dir.join(&screenshot_name);
+        if !previous.is_file() {
+            continue;
+        }
+        let (Ok(before), Ok(after)) = (image::open(&previous), image::open(&screenshot)) else {
+            warn!("⚠️  Can't read screenshots of {} to compare", local_path);
+            continue;
+        };
+        let diff = pixel_diff(&before.to_rgba8(), &after.to_rgba8());
+        let diff_image = if diff.changed_pixels > 0 {
+            let diff_path = diff_dir.join(&screenshot_name);
+            fs::create_dir_all(diff_path.parent().unwrap_or(&diff_dir))?;
+            diff.image.save(&diff_path).with_context(|| format!("Failed to write diff image: {:?}", diff_path))?;
+            Some(format!("{}/diff/{}", VISUAL_DIR, screenshot_name))
+        } else {
+            None
+        };
+        changes.push(PageVisualChange {
+            url: url.clone(),
+            local_path: local_path.to_string(),
+            changed_percent: diff.changed_percent(),
+            diff_image,
+        });
+    }
+
+    let report_path = visual_dir.join("report.json");
+    fs::create_dir_all(&visual_dir)?;
+    fs::write(&report_path, serde_json::to_string_pretty(&changes)?)
+        .with_context(|| format!("Failed to write visual diff report: {:?}", report_path))?;
+
+    if current_dir.exists() {
+        if previous_dir.exists() {
+            fs::remove_dir_all(&previous_dir).with_context(|| format!("Failed to clear {:?}", previous_dir))?;
+        }
+        fs::rename(&current_dir, &previous_dir).with_context(|| format!("Failed to keep screenshots in {:?}", previous_dir))?;
+    }
+    Ok(changes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn solid(width: u32, height: u32, color: [u8; 4]) -> RgbaImage {
+        RgbaImage::from_pixel(width, height, Rgba(color))
+    }
+
+    #[test]
+    fn test_pixel_diff() {
+        let white = solid(10, 10, [255, 255, 255, 255]);
+        assert_eq!(pixel_diff(&white, &white).changed_pixels, 0);
+
+        let mut changed = white.clone();
+        for x in 0..10 {
+            changed.put_pixel(x, 0, Rgba([0, 0, 0, 255]));
+        }
+        // Below the noise tolerance
+        changed.put_pixel(0, 5, Rgba([250, 250, 250, 255]));
+        let diff = pixel_diff(&white, &changed);
+        assert_eq!(diff.changed_pixels, 10);
+        assert_eq!(diff.changed_percent(), 10.0);
+        assert_eq!(diff.image.get_pixel(3, 0), &Rgba([255, 0, 0, 255]));
+
+        let taller = solid(10, 20, [255, 255, 255, 255]);
+        assert_eq!(pixel_diff(&white, &taller).changed_percent(), 50.0);
+    }
+
+    #[test]
+    fn test_shell_quote() {
+        assert_eq!(shell_quote("/tmp/my site/index.html"), "'/tmp/my site/index.html'");
+        assert_eq!(shell_quote("it's"), r"'it'\''s'");
+    }
+
+    #[tokio::test]
+    async fn test_compare_runs() {
+        let temp_dir = tempdir().unwrap();
+        let dir = temp_dir.path();
+        fs::write(dir.join("index.html"), "<p>home</p>").unwrap();
+        let mut store = DownloadStore::default();
+        store.record_download("https://example.com/", "index.html", Some("text/html"), 11);
+        store.record_download("https://example.com/logo.png", "logo.png", Some("image/png"), 1);
+
+        let render = dir.join("render.png");
+        let command = format!("cp {} {{output}}", shell_quote(&render.to_string_lossy()));
+
+        solid(4, 4, [255, 255, 255, 255]).save(&render).unwrap();
+        assert!(compare_runs(dir, &store, &command).await.unwrap().is_empty());
+        assert!(dir.join(VISUAL_DIR).join("previous/index.html.png").is_file());
+
+        solid(4, 4, [0, 0, 0, 255]).save(&render).unwrap();
+        let changes = compare_runs(dir, &store, &command).await.unwrap();
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].local_path, "index.html");
+        assert_eq!(changes[0].changed_percent, 100.0);
+        assert!(dir.join(VISUAL_DIR).join("diff/index.html.png").is_file());
+    }
+}
@@ -0,0 +1,324 @@
+use anyhow::{anyhow, bail, Result, Context};
+use flate2::read::{DeflateDecoder, MultiGzDecoder};
+use reqwest::header::{HeaderName, HeaderValue};
+use reqwest::{Response, ResponseBuilderExt};
+use std::collections::HashMap;
+use std::fs;
+use std::io::Read;
+use std::path::Path;
+use url::Url;
+
+use crate::redirects;
+use crate::url_normalizer;
+
+/// Headers that describe the captured bytes on the wire rather than the
+/// decoded body that is replayed
+const TRANSFER_HEADERS: &[&str] = &["content-encoding", "content-length", "transfer-encoding"];
+
+/// An HTTP response as captured in a WARC, with the body already de-chunked
+/// and decompressed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CapturedResponse {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl CapturedResponse {
+    /// First value of a header, matched case-insensitively
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(header, _)| header.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    pub fn is_html(&self) -> bool {
+        self.header("content-type").is_some_and(|content_type| content_type.contains("text/html"))
+    }
+
+    fn is_redirect(&self) -> bool {
+        (300..400).contains(&self.status) && self.header("location").is_some()
+    }
+}
+
+/// The `response` (and `revisit`) records of a WARC file, keyed by normalized
+/// target URL, for replaying a capture through the crawler instead of the network
+#[derive(Debug, Clone, Default)]
+pub struct WarcArchive {
+    responses: HashMap<String, CapturedResponse>,
+    /// Target URLs in capture order
+    order: Vec<String>,
+    /// Records that couldn't be replayed, with the reason
+    pub skipped: Vec<String>,
+}
+
+impl WarcArchive {
+    /// Read a `.warc` or `.warc.gz` file (a gzip member per record, or the
+    /// whole file compressed)
+    pub fn load(path: &Path) -> Result<Self> {
+        let data = fs::read(path).with_context(|| format!("Failed to read WARC file: {:?}", path))?;
+        let data = if data.starts_with(&[0x1f, 0x8b]) {
+            let mut decompressed = Vec::new();
+            MultiGzDecoder::new(data.as_slice())
+                .read_to_end(&mut decompressed)
+                .with_context(|| format!("Failed to decompress WARC file: {:?}", path))?;
+            decompressed
+        } else {
+            data
+        };
+        Self::parse(&data)
+    }
+
+    /// Parse uncompressed WARC records
+    pub fn parse(data: &[u8]) -> Result<Self> {
+        let mut archive = WarcArchive::default();
+        // Bodies of earlier responses by payload digest, for identical-payload revisits
+        let mut payloads: HashMap<String, Vec<u8>> = HashMap::new();
+        let mut rest = data;
+        loop {
+            while rest.first().is_some_and(|byte| byte.is_ascii_whitespace()) {
+                rest = &rest[1..];
+            }
+            if rest.is_empty() {
+                break;
+            }
+            if !rest.starts_with(b"WARC/") {
+                bail!("Malformed WARC record at byte {}", data.len() - rest.len());
+            }
+            let header_end = find(rest, b"\r\n\r\n").ok_or_else(|| anyhow!("Truncated WARC record header"))?;
+            let warc_headers = parse_headers(&String::from_utf8_lossy(&rest[..header_end]));
+            let warc_header = |name: &str| {
+                warc_headers.iter().find(|(header, _)| header.eq_ignore_ascii_case(name)).map(|(_, value)| value.as_str())
+            };
+            let length: usize = warc_header("content-length")
+                .and_then(|length| length.trim().parse().ok())
+                .ok_or_else(|| anyhow!("WARC record without a Content-Length"))?;
+            let block_start = header_end + 4;
+            let block = rest
+                .get(block_start..block_start + length)
+                .ok_or_else(|| anyhow!("Truncated WARC record block"))?;
+            rest = &rest[block_start + length..];
+
+            let record_type = warc_header("warc-type").unwrap_or_default();
+            if record_type != "response" && record_type != "revisit" {
+                continue;
+            }
+            let Some(target) = warc_header("warc-target-uri") else { continue };
+            let url = url_normalizer::normalize_url(target.trim_start_matches('<').trim_end_matches('>'), false);
+            let Some(mut response) = parse_http_response(block) else {
+                archive.skipped.push(format!("{} (not an HTTP response)", url));
+                continue;
+            };
+            let digest = warc_header("warc-payload-digest").map(|digest| digest.to_string());
+            if record_type == "revisit" {
+                let Some(body) = digest.as_ref().and_then(|digest| payloads.get(digest)) else {
+                    archive.skipped.push(format!("{} (revisit of a payload not in this file)", url));
+                    continue;
+                };
+                response.body = body.clone();
+            } else if let Some(digest) = digest {
+                payloads.entry(digest).or_insert_with(|| response.body.clone());
+            }
+            if let Err(e) = decode_body(&mut response) {
+                archive.skipped.push(format!("{} ({})", url, e));
+                continue;
+            }
+            // The first capture of a URL wins, like the first visit of a crawl
+            if !archive.responses.contains_key(&url) {
+                archive.order.push(url.clone());
+                archive.responses.insert(url, response);
+            }
+        }
+        Ok(archive)
+    }
+
+    pub fn len(&self) -> usize {
+        self.responses.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.responses.is_empty()
+    }
+
+    /// Captured response for a URL; `/docs` and `/docs/` match each other
+    pub fn get(&self, url: &str) -> Option<&CapturedResponse> {
+        let url = url_normalizer::normalize_url(url, false);
+        self.responses.get(&url).or_else(|| {
+            let mut parsed = Url::parse(&url).ok()?;
+            let path = parsed.path().to_string();
+            match path.strip_suffix('/') {
+                Some(trimmed) if !trimmed.is_empty() => parsed.set_path(trimmed),
+                Some(_) => return None,
+                None => parsed.set_path(&format!("{}/", path)),
+            }
+            self.responses.get(parsed.as_str())
+        })
+    }
+
+    /// URLs of the captured HTML pages that loaded successfully, in capture order
+    pub fn pages(&self) -> impl Iterator<Item = &str> {
+        self.order
+            .iter()
+            .filter(|url| self.responses[*url].status == 200 && self.responses[*url].is_html())
+            .map(String::as_str)
+    }
+
+    /// Replay a GET of `url` as a `reqwest` response, following captured
+    /// redirects. Also returns the redirect hops after `url`, like the live
+    /// client's redirect policy records them.
+    pub fn replay(&self, url: &str) -> Result<(Response, Vec<String>)> {
+        let mut current = url.to_string();
+        let mut hops = Vec::new();
+        let mut captured = self.get(&current).ok_or_else(|| anyhow!("{} was not captured in the WARC", url))?;
+        while captured.is_redirect() && hops.len() < redirects::MAX_REDIRECTS {
+            let location = captured.header("location").unwrap_or_default();
+            let Ok(target) = Url::parse(&current).and_then(|base| base.join(location)) else { break };
+            let Some(target_response) = self.get(target.as_str()) else { break };
+            current = target.to_string();
+            hops.push(current.clone());
+            captured = target_response;
+        }
+
+        let mut builder = http::Response::builder()
+            .status(captured.status)
+            .url(Url::parse(&current)?);
+        if let Some(headers) = builder.headers_mut() {
+            for (name, value) in &captured.headers {
+                if TRANSFER_HEADERS.contains(&name.to_ascii_lowercase().as_str()) {
+                    continue;
+                }
+                if let (Ok(name), Ok(value)) = (HeaderName::from_bytes(name.as_bytes()), HeaderValue::from_str(value)) {
+                    headers.append(name, value);
+                }
+            }
+        }
+        Ok((Response::from(builder.body(captured.body.clone())?), hops))
+    }
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|window| window == needle)
+}
+
+/// `Name: value` lines after the first (version or status) line
+fn parse_headers(head: &str) -> Vec<(String, String)> {
+    head.lines()
+        .skip(1)
+        .filter_map(|line| line.split_once(':'))
+        .map(|(name, value)| (name.trim().to_string(), value.trim().to_string()))
+        .collect()
+}
+
+/// Status, headers and raw body of an HTTP response block
+fn parse_http_response(block: &[u8]) -> Option<CapturedResponse> {
+    let head_end = find(block, b"\r\n\r\n")?;
+    let head = String::from_utf8_lossy(&block[..head_end]);
+    let status_line = head.lines().next()?;
+    if !status_line.starts_with("HTTP/") {
+        return None;
+    }
+    let status = status_line.split_whitespace().nth(1)?.parse().ok()?;
+    Some(CapturedResponse {
+        status,
+        headers: parse_headers(&head),
+        body: block[head_end + 4..].to_vec(),
+    })
+}
+
+/// Undo chunked transfer encoding and gzip/deflate content encoding
+fn decode_body(response: &mut CapturedResponse) -> Result<()> {
+    if response.header("transfer-encoding").is_some_and(|encoding| encoding.eq_ignore_ascii_case("chunked")) {
+        response.body = dechunk(&response.body).ok_or_else(|| anyhow!("malformed chunked body"))?;
+    }
+    let encoding = response.header("content-encoding").unwrap_or("identity").to_ascii_lowercase();
+    let mut decoded = Vec::new();
+    match encoding.as_str() {
+        "identity" | "" => return Ok(()),
+        "gzip" | "x-gzip" => MultiGzDecoder::new(response.body.as_slice()).read_to_end(&mut decoded)?,
+        "deflate" => DeflateDecoder::new(response.body.as_slice()).read_to_end(&mut decoded)?,
+        other => bail!("unsupported Content-Encoding: {}", other),
+    };
+    response.body = decoded;
+    Ok(())
+}
+
+fn dechunk(mut data: &[u8]) -> Option<Vec<u8>> {
+    let mut body = Vec::new();
+    loop {
+        let line_end = find(data, b"\r\n")?;
+        let size_text = String::from_utf8_lossy(&data[..line_end]);
+        let size = usize::from_str_radix(size_text.split(';').next()?.trim(), 16).ok()?;
+        data = &data[line_end + 2..];
+        if size == 0 {
+            return Some(body);
+        }
+        body.extend_from_slice(data.get(..size)?);
+        data = data.get(size + 2..)?;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+    use std::io::Write;
+    use tempfile::tempdir;
+
+    fn record(record_type: &str, url: &str, digest: &str, block: &[u8]) -> Vec<u8> {
+        let mut record = format!(
+            "WARC/1.0\r\nWARC-Type: {}\r\nWARC-Target-URI: {}\r\nWARC-Payload-Digest: {}\r\nContent-Length: {}\r\n\r\n",
+            record_type, url, digest, block.len()
+        )
+        .into_bytes();
+        record.extend_from_slice(block);
+        record.extend_from_slice(b"\r\n\r\n");
+        record
+    }
+
+    #[test]
+    fn test_parse_warc() {
+        let mut gzipped = GzEncoder::new(Vec::new(), Compression::default());
+        gzipped.write_all(b"body { color: red }").unwrap();
+        let mut css = b"HTTP/1.1 200 OK\r\nContent-Type: text/css\r\nContent-Encoding: gzip\r\n\r\n".to_vec();
+        css.extend_from_slice(&gzipped.finish().unwrap());
+
+        let mut warc = b"WARC/1.0\r\nWARC-Type: warcinfo\r\nContent-Length: 4\r\n\r\ninfo\r\n\r\n".to_vec();
+        warc.extend(record("response", "https://example.com/", "sha1:A",
+            b"HTTP/1.1 200 OK\r\nContent-Type: text/html\r\nTransfer-Encoding: chunked\r\n\r\n5\r\n<html\r\n1\r\n>\r\n0\r\n\r\n"));
+        warc.extend(record("response", "<https://example.com/old>", "sha1:B",
+            b"HTTP/1.1 301 Moved\r\nLocation: /docs/\r\n\r\n"));
+        warc.extend(record("response", "https://example.com/docs/", "sha1:C",
+            b"HTTP/1.1 200 OK\r\nContent-Type: text/html\r\n\r\n<p>Docs</p>"));
+        warc.extend(record("response", "https://example.com/site.css", "sha1:D", &css));
+        warc.extend(record("revisit", "https://example.com/copy.css", "sha1:D",
+            b"HTTP/1.1 200 OK\r\nContent-Type: text/css\r\nContent-Encoding: gzip\r\n\r\n"));
+        warc.extend(record("response", "https://example.com/font.woff2", "sha1:E",
+            b"HTTP/1.1 200 OK\r\nContent-Encoding: br\r\n\r\nxx"));
+
+        let temp_dir = tempdir().unwrap();
+        let path = temp_dir.path().join("capture.warc.gz");
+        let mut file = GzEncoder::new(Vec::new(), Compression::default());
+        file.write_all(&warc).unwrap();
+        fs::write(&path, file.finish().unwrap()).unwrap();
+
+        let archive = WarcArchive::load(&path).unwrap();
+        assert_eq!(archive.len(), 5);
+        assert_eq!(archive.get("https://example.com/").unwrap().body, b"<html>");
+        assert_eq!(archive.get("https://example.com/site.css").unwrap().body, b"body { color: red }");
+        assert_eq!(archive.get("https://example.com/copy.css").unwrap().body, b"body { color: red }");
+        assert_eq!(archive.get("https://example.com/docs").unwrap().body, b"<p>Docs</p>");
+        assert_eq!(archive.skipped.len(), 1);
+        assert!(archive.skipped[0].contains("unsupported Content-Encoding: br"));
+        assert_eq!(archive.pages().collect::<Vec<_>>(), vec!["https://example.com/", "https://example.com/docs/"]);
+
+        let (response, hops) = archive.replay("https://example.com/old").unwrap();
+        assert_eq!(response.status(), 200);
+        assert_eq!(response.url().as_str(), "https://example.com/docs/");
+        assert_eq!(response.headers().get("content-type").unwrap(), "text/html");
+        assert_eq!(hops, vec!["https://example.com/docs/"]);
+        assert!(archive.replay("https://example.com/missing").is_err());
+        assert!(WarcArchive::parse(b"not a warc").is_err());
+    }
+}
@@ -0,0 +1,183 @@
+use std::collections::HashMap;
+use std::fs;
+use std::time::Duration;
+use tempfile::tempdir;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+use website_mirror::io_throttle::IoThrottle;
+use website_mirror::robots::ROBOTS_TXT_REASON;
+use website_mirror::{DownloadStore, WebsiteMirror};
+
+type Site = HashMap<String, (&'static str, String)>;
+
+const HTML: &str = "text/html; charset=utf-8";
+
+/// Serve the site `build` makes for the server's base URL (path -> content
+/// type and body) over plain HTTP on a local port, 404 for everything else
+async fn serve(build: impl FnOnce(&str) -> Site) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let base_url = format!("http://{}", listener.local_addr().unwrap());
+    let site = build(&base_url);
+    tokio::spawn(async move {
+        loop {
+            let Ok((mut stream, _)) = listener.accept().await else { break };
+            let site = site.clone();
+            tokio::spawn(async move {
+                let mut request = Vec::new();
+                let mut buf = [0u8; 1024];
+                while !request.windows(4).any(|window| window == b"\r\n\r\n") {
+                    match stream.read(&mut buf).await {
+                        Ok(0) | Err(_) => return,
+                        Ok(n) => request.extend_from_slice(&buf[..n]),
+                    }
+                }
+                let request = String::from_utf8_lossy(&request);
+                let path = request.split_whitespace().nth(1).unwrap_or("/").to_string();
+                let (status, content_type, body) = match site.get(&path) {
+                    Some((content_type, body)) => ("200 OK", *content_type, body.clone()),
+                    None => ("404 Not Found", "text/plain", "not found".to_string()),
+                };
+                let response = format!(
+                    "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    status, content_type, body.len(), body
+                );
+                let _ = stream.write_all(response.as_bytes()).await;
+            });
+        }
+    });
+    format!("{}/", base_url)
+}
+
+/// A home page linking to `pages` pages
+fn linked_pages(pages: usize) -> Site {
+    let mut site = HashMap::new();
+    let links: String = (0..pages).map(|page| format!(r#"<a href="/page{}/">Page {}</a>"#, page, page)).collect();
+    site.insert("/".to_string(), (HTML, format!("<html><body>{}</body></html>", links)));
+    for page in 0..pages {
+        site.insert(format!("/page{}/", page), (HTML, format!("<html><body><p>Page {}</p></body></html>", page)));
+    }
+    site
+}
+
+#[tokio::test]
+async fn test_max_pages_links_remaining_pages_to_live_site() {
+    let base_url = serve(|_| linked_pages(5)).await;
+    let temp_dir = tempdir().unwrap();
+    let output_dir = temp_dir.path().join("site");
+
+    let mut mirror = WebsiteMirror::new(&base_url, &output_dir, 2, 4, true, false, None, false).unwrap();
+    mirror.max_pages = Some(3);
+    mirror.mirror_website().await.unwrap();
+
+    // The home page and two of the pages it links to
+    let saved: Vec<usize> = (0..5).filter(|page| output_dir.join(format!("page{}/index.html", page)).exists()).collect();
+    assert_eq!(saved.len(), 2);
+    let store = DownloadStore::load(&output_dir).unwrap();
+    let home = fs::read_to_string(output_dir.join("index.html")).unwrap();
+    for page in (0..5).filter(|page| !saved.contains(page)) {
+        let url = format!("{}page{}/", base_url, page);
+        assert_eq!(store.skipped.get(&url).map(String::as_str), Some("over --max-pages budget"));
+        assert!(home.contains(&format!(r#"href="{}""#, url)), "link to skipped page{} doesn't point at the live site: {}", page, home);
+    }
+}
+
+/// A home page linking to a public and a private page, with a robots.txt
+/// disallowing the private one
+fn robots_site(_base_url: &str) -> Site {
+    HashMap::from([
+        ("/robots.txt".to_string(), ("text/plain", "User-agent: *\nDisallow: /private/\n".to_string())),
+        ("/".to_string(), (HTML, r#"<html><body><a href="/public/">Public</a><a href="/private/">Private</a></body></html>"#.to_string())),
+        ("/public/".to_string(), (HTML, "<html><body><p>Public</p></body></html>".to_string())),
+        ("/private/".to_string(), (HTML, "<html><body><p>Private</p></body></html>".to_string())),
+    ])
+}
+
+#[tokio::test]
+async fn test_robots_disallowed_page_skipped_with_reason() {
+    let base_url = serve(robots_site).await;
+    let temp_dir = tempdir().unwrap();
+    let output_dir = temp_dir.path().join("site");
+
+    let mut mirror = WebsiteMirror::new(&base_url, &output_dir, 2, 4, false, false, None, false).unwrap();
+    mirror.mirror_website().await.unwrap();
+
+    assert!(output_dir.join("public/index.html").exists());
+    assert!(!output_dir.join("private/index.html").exists());
+    let store = DownloadStore::load(&output_dir).unwrap();
+    let reason = store.skipped.get(&format!("{}private/", base_url)).expect("the disallowed page is recorded as skipped");
+    assert!(reason.starts_with(ROBOTS_TXT_REASON) && reason.contains("/private/"), "unexpected skip reason: {}", reason);
+}
+
+#[tokio::test]
+async fn test_ignore_robots_crawls_disallowed_page() {
+    let base_url = serve(robots_site).await;
+    let temp_dir = tempdir().unwrap();
+    let output_dir = temp_dir.path().join("site");
+
+    let mut mirror = WebsiteMirror::new(&base_url, &output_dir, 2, 4, true, false, None, false).unwrap();
+    mirror.mirror_website().await.unwrap();
+
+    assert!(output_dir.join("private/index.html").exists());
+    let store = DownloadStore::load(&output_dir).unwrap();
+    assert!(store.skipped.is_empty(), "nothing should be skipped: {:?}", store.skipped);
+}
+
+/// More pages than fit in the channel between the crawl and asset stages, each
+/// with its own stylesheet and image linked by absolute URL
+fn pages_with_assets(base_url: &str, pages: usize) -> Site {
+    let mut site = HashMap::new();
+    let links: String = (0..pages).map(|page| format!(r#"<a href="/page{}/">Page {}</a>"#, page, page)).collect();
+    site.insert("/".to_string(), (HTML, format!("<html><body>{}</body></html>", links)));
+    for page in 0..pages {
+        site.insert(
+            format!("/page{}/", page),
+            (
+                HTML,
+                format!(r#"<html><head><link rel="stylesheet" href="{0}/css/page{1}.css"></head><body><img src="{0}/img/page{1}.png" alt="Page {1}"></body></html>"#, base_url, page),
+            ),
+        );
+        site.insert(format!("/css/page{}.css", page), ("text/css", format!("body {{ order: {} }}", page)));
+        site.insert(format!("/img/page{}.png", page), ("image/png", format!("PNG {}", page)));
+    }
+    site
+}
+
+#[tokio::test]
+async fn test_pipeline_saves_every_page_with_its_assets() {
+    const PAGES: usize = 12;
+    let base_url = serve(|base_url| pages_with_assets(base_url, PAGES)).await;
+    let temp_dir = tempdir().unwrap();
+    let output_dir = temp_dir.path().join("site");
+
+    let mut mirror = WebsiteMirror::new(&base_url, &output_dir, 2, 3, true, false, None, false).unwrap();
+    // The run ends once the crawl stage runs out of pages and the asset stage drains the channel
+    tokio::time::timeout(Duration::from_secs(60), mirror.mirror_website()).await.expect("pipeline did not shut down").unwrap();
+
+    assert!(output_dir.join("index.html").exists());
+    for page in 0..PAGES {
+        // Pages are only saved by the asset stage, after their resources
+        let html = fs::read_to_string(output_dir.join(format!("page{}/index.html", page))).unwrap();
+        assert!(html.contains(&format!("\"../css/page{}.css\"", page)), "stylesheet of page{} was not rewritten: {}", page, html);
+        assert!(html.contains(&format!("\"../img/page{}.png\"", page)), "image of page{} was not rewritten: {}", page, html);
+        assert_eq!(fs::read_to_string(output_dir.join(format!("css/page{}.css", page))).unwrap(), format!("body {{ order: {} }}", page));
+        assert_eq!(fs::read_to_string(output_dir.join(format!("img/page{}.png", page))).unwrap(), format!("PNG {}", page));
+    }
+}
+
+#[tokio::test]
+async fn test_oversized_html_saved_through_io_throttle() {
+    let big_page = format!("<html><body>{}</body></html>", "<p>Lorem ipsum dolor sit amet</p>".repeat(4096));
+    let expected = big_page.clone();
+    let base_url = serve(move |_| HashMap::from([("/".to_string(), (HTML, big_page))])).await;
+    let temp_dir = tempdir().unwrap();
+    let output_dir = temp_dir.path().join("site");
+
+    let mut mirror = WebsiteMirror::new(&base_url, &output_dir, 2, 4, true, false, None, false)
+        .unwrap()
+        .with_io_throttle(IoThrottle::new(None, 1));
+    mirror.max_html_size = Some(16 * 1024);
+    mirror.mirror_website().await.unwrap();
+
+    assert_eq!(fs::read_to_string(output_dir.join("index.html")).unwrap(), expected);
+    assert!(!output_dir.join("index.html.part").exists());
+}
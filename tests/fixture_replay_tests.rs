@@ -0,0 +1,75 @@
+use std::fs;
+use std::sync::Arc;
+use tempfile::tempdir;
+use website_mirror::fixtures::{FixtureServer, FixtureSet, RecordedResponse};
+use website_mirror::WebsiteMirror;
+
+const HTML: &[(&str, &str)] = &[("Content-Type", "text/html; charset=utf-8")];
+
+fn blog_fixtures() -> FixtureSet {
+    let mut fixtures = FixtureSet::new("https://example.com/");
+    fixtures.insert(RecordedResponse::new(
+        "https://example.com/",
+        200,
+        HTML,
+        br#"<html><head><link rel="stylesheet" href="/css/site.css"></head>
+<body><img src="/img/logo.png" alt="Logo"><a href="/about/">About</a><a href="/missing/">Missing</a></body></html>"#,
+    ));
+    fixtures.insert(RecordedResponse::new(
+        "https://example.com/about/",
+        200,
+        HTML,
+        br#"<html><body><a href="/">Home</a><img src="/img/logo.png" alt="Logo"></body></html>"#,
+    ));
+    fixtures.insert(RecordedResponse::new(
+        "https://example.com/css/site.css",
+        200,
+        &[("Content-Type", "text/css")],
+        b"body { background: url(../img/bg.png) }",
+    ));
+    fixtures.insert(RecordedResponse::new("https://example.com/img/logo.png", 200, &[("Content-Type", "image/png")], b"\x89PNG logo"));
+    fixtures.insert(RecordedResponse::new("https://example.com/img/bg.png", 200, &[("Content-Type", "image/png")], b"\x89PNG bg"));
+    fixtures
+}
+
+#[tokio::test]
+async fn test_mirror_replayed_fixtures() {
+    let temp_dir = tempdir().unwrap();
+    let output_dir = temp_dir.path().join("site");
+    let fixtures = blog_fixtures();
+    fixtures.save(&temp_dir.path().join("fixtures")).unwrap();
+
+    let mut mirror = WebsiteMirror::new(&fixtures.start_url, &output_dir, 2, 4, true, false, None, false).unwrap();
+    let replayed = FixtureSet::load(&temp_dir.path().join("fixtures")).unwrap();
+    mirror.fixture_server = Some(Arc::new(FixtureServer::start(replayed).await.unwrap()));
+    mirror.mirror_website().await.unwrap();
+
+    for path in ["index.html", "about/index.html", "css/site.css", "img/logo.png", "img/bg.png"] {
+        assert!(output_dir.join(path).exists(), "{} was not mirrored", path);
+    }
+    assert_eq!(fs::read(output_dir.join("img/logo.png")).unwrap(), b"\x89PNG logo");
+
+    // Unrecorded URLs are served as 404s and reported with the page linking to them
+    let broken_links: serde_json::Value = serde_json::from_str(&fs::read_to_string(output_dir.join("broken-links.json")).unwrap()).unwrap();
+    let missing = &broken_links.as_array().unwrap()[0];
+    assert_eq!(missing["url"], "https://example.com/missing/");
+    assert_eq!(missing["status"], 404);
+    assert_eq!(missing["retry"], "not_retried");
+}
+
+#[tokio::test]
+async fn test_replay_is_deterministic() {
+    let temp_dir = tempdir().unwrap();
+    let mut runs = Vec::new();
+    for run in ["first", "second"] {
+        let output_dir = temp_dir.path().join(run);
+        let mut mirror = WebsiteMirror::new("https://example.com/", &output_dir, 2, 4, true, false, None, false).unwrap();
+        mirror.fixture_server = Some(Arc::new(FixtureServer::start(blog_fixtures()).await.unwrap()));
+        mirror.mirror_website().await.unwrap();
+        runs.push((
+            fs::read_to_string(output_dir.join("index.html")).unwrap(),
+            fs::read_to_string(output_dir.join("about/index.html")).unwrap(),
+        ));
+    }
+    assert_eq!(runs[0], runs[1]);
+}